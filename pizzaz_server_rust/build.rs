@@ -0,0 +1,66 @@
+//! Captures build provenance for `GET /internal/version` and the `initialize` response.
+//!
+//! `PIZZAZ_GIT_SHA` and `SOURCE_DATE_EPOCH` override the detected commit and build time, for
+//! image builds that run without a `.git` directory or need reproducible output.
+
+use std::{
+    env,
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=PIZZAZ_GIT_SHA");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=Cargo.toml");
+
+    let git_sha = env::var("PIZZAZ_GIT_SHA")
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .or_else(|| {
+            if let Some(git_dir) = command_output("git", &["rev-parse", "--absolute-git-dir"]) {
+                println!("cargo:rerun-if-changed={git_dir}/HEAD");
+                println!("cargo:rerun-if-changed={git_dir}/refs");
+            }
+            command_output("git", &["rev-parse", "HEAD"])
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let build_epoch = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|value| value.trim().parse::<i64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs() as i64)
+                .unwrap_or_default()
+        });
+
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc_version =
+        command_output(&rustc, &["--version"]).unwrap_or_else(|| "unknown".to_string());
+
+    let mut features: Vec<String> = env::vars()
+        .filter_map(|(key, _)| {
+            key.strip_prefix("CARGO_FEATURE_")
+                .map(|feature| feature.to_ascii_lowercase().replace('_', "-"))
+        })
+        .collect();
+    features.sort();
+
+    println!("cargo:rustc-env=PIZZAZ_GIT_SHA={git_sha}");
+    println!("cargo:rustc-env=PIZZAZ_BUILD_EPOCH={build_epoch}");
+    println!("cargo:rustc-env=PIZZAZ_RUSTC_VERSION={rustc_version}");
+    println!("cargo:rustc-env=PIZZAZ_FEATURES={}", features.join(","));
+}
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8(output.stdout).ok()?;
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
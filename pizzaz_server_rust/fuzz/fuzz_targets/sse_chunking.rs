@@ -0,0 +1,24 @@
+//! Feeds arbitrary bytes to the SSE rewriter in two different chunkings and checks that the
+//! output does not depend on where the chunks were cut.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pizzaz_server_rust::sse_rewrite::rewrite_chunks;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&seed, body)) = data.split_first() else {
+        return;
+    };
+    let whole = rewrite_chunks([body]);
+
+    // Cut every `seed`-th byte (at least every byte), which splits CRLF pairs and multi-byte
+    // characters wherever they fall.
+    let step = usize::from(seed).max(1);
+    let split = rewrite_chunks(body.chunks(step));
+
+    if std::str::from_utf8(body).is_ok() {
+        assert_eq!(whole, split);
+        let output = String::from_utf8(whole).expect("UTF-8 in, UTF-8 out");
+        assert!(output.is_empty() || output.ends_with("\n\n"));
+    }
+});
@@ -0,0 +1,10 @@
+//! Rewrites a single arbitrary SSE event and checks that a second pass changes nothing.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pizzaz_server_rust::sse_rewrite::augment_sse_event;
+
+fuzz_target!(|event: &str| {
+    let (rewritten, _) = augment_sse_event(event);
+    assert_eq!(augment_sse_event(&rewritten).0, rewritten);
+});
@@ -0,0 +1,318 @@
+//! Aggregate counts of the argument values widgets are called with.
+//!
+//! Every tool call that reaches an executor is counted per widget, along with the values of the
+//! arguments declared in the widget's input schema: the toppings people ask `pizza-map` for, the
+//! locations they search. Strings are trimmed, lowercased and cut to 64 characters so spelling
+//! variants group together, and each element of an array argument counts on its own. Objects and
+//! `null` are not counted. Validate-only and simulated calls are left out.
+//!
+//! Each argument tracks at most `WIDGETS_ANALYTICS_MAX_VALUES` distinct values (50 by default);
+//! later values are folded into an `other` count, so free-text arguments cannot grow the table
+//! without bound. Counts are held in memory and start over when the server restarts. Operators
+//! read them from `GET /internal/analytics`.
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    extract::{ConnectInfo, Query},
+    http::HeaderMap,
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{error::ApiError, events::Event, AppState};
+
+pub const DEFAULT_MAX_VALUES: usize = 50;
+const DEFAULT_TOP: usize = 10;
+const MAX_VALUE_CHARS: usize = 64;
+
+#[derive(Debug, Default)]
+struct WidgetUsage {
+    calls: u64,
+    arguments: HashMap<String, ArgumentUsage>,
+}
+
+#[derive(Debug, Default)]
+struct ArgumentUsage {
+    values: HashMap<String, u64>,
+    other: u64,
+}
+
+impl ArgumentUsage {
+    fn count(&mut self, value: String, max_values: usize) {
+        if let Some(count) = self.values.get_mut(&value) {
+            *count += 1;
+        } else if self.values.len() < max_values {
+            self.values.insert(value, 1);
+        } else {
+            self.other += 1;
+        }
+    }
+}
+
+/// Argument value counts for every widget. Clones share them.
+#[derive(Debug, Clone)]
+pub struct Usage {
+    max_values: usize,
+    since: OffsetDateTime,
+    widgets: Arc<Mutex<HashMap<String, WidgetUsage>>>,
+}
+
+impl Default for Usage {
+    fn default() -> Self {
+        Self::with_max_values(DEFAULT_MAX_VALUES)
+    }
+}
+
+impl Usage {
+    pub fn with_max_values(max_values: usize) -> Self {
+        Self {
+            max_values,
+            since: OffsetDateTime::now_utc(),
+            widgets: Arc::default(),
+        }
+    }
+
+    /// Reads the distinct value cap from `WIDGETS_ANALYTICS_MAX_VALUES`.
+    pub(crate) fn from_env() -> Self {
+        let max_values = match std::env::var("WIDGETS_ANALYTICS_MAX_VALUES") {
+            Ok(raw) => raw.trim().parse().unwrap_or_else(|_| {
+                tracing::warn!(value = %raw, "Ignoring invalid WIDGETS_ANALYTICS_MAX_VALUES");
+                DEFAULT_MAX_VALUES
+            }),
+            Err(_) => DEFAULT_MAX_VALUES,
+        };
+        Self::with_max_values(max_values)
+    }
+
+    /// Counts one call to `widget_id`, with the `arguments` declared by its input `schema`.
+    pub fn record(&self, widget_id: &str, schema: &JsonValue, arguments: &JsonValue) {
+        let mut widgets = self.widgets.lock().expect("usage lock poisoned");
+        let usage = widgets.entry(widget_id.to_string()).or_default();
+        usage.calls += 1;
+
+        let Some(properties) = schema["properties"].as_object() else {
+            return;
+        };
+        for (name, value) in arguments.as_object().into_iter().flatten() {
+            if !properties.contains_key(name) {
+                continue;
+            }
+            let values = match value {
+                JsonValue::Array(items) => items.iter().filter_map(value_key).collect(),
+                value => value_key(value).into_iter().collect::<Vec<_>>(),
+            };
+            if values.is_empty() {
+                continue;
+            }
+            let argument = usage.arguments.entry(name.clone()).or_default();
+            for value in values {
+                argument.count(value, self.max_values);
+            }
+        }
+    }
+
+    /// Counts each [`Event::WidgetCalled`] that did not opt out of telemetry.
+    pub fn observe(&self, event: &Event) {
+        if let Event::WidgetCalled {
+            widget,
+            arguments: Some(arguments),
+        } = event
+        {
+            self.record(&widget.id, &widget.input_schema, arguments);
+        }
+    }
+
+    /// The `top` most frequent values of each argument, for every widget or only `widget_id`.
+    pub fn report(&self, widget_id: Option<&str>, top: usize) -> UsageReport {
+        let widgets = self.widgets.lock().expect("usage lock poisoned");
+        let mut report: Vec<WidgetReport> = widgets
+            .iter()
+            .filter(|(id, _)| widget_id.is_none_or(|wanted| wanted == id.as_str()))
+            .map(|(id, usage)| {
+                let mut arguments: Vec<ArgumentReport> = usage
+                    .arguments
+                    .iter()
+                    .map(|(name, argument)| {
+                        let mut values: Vec<ValueCount> = argument
+                            .values
+                            .iter()
+                            .map(|(value, count)| ValueCount {
+                                value: value.clone(),
+                                count: *count,
+                            })
+                            .collect();
+                        values.sort_by(|a, b| b.count.cmp(&a.count).then(a.value.cmp(&b.value)));
+                        let distinct = values.len();
+                        values.truncate(top);
+                        ArgumentReport {
+                            name: name.clone(),
+                            distinct,
+                            other: argument.other,
+                            values,
+                        }
+                    })
+                    .collect();
+                arguments.sort_by(|a, b| a.name.cmp(&b.name));
+                WidgetReport {
+                    widget_id: id.clone(),
+                    calls: usage.calls,
+                    arguments,
+                }
+            })
+            .collect();
+        report.sort_by(|a, b| {
+            b.calls
+                .cmp(&a.calls)
+                .then_with(|| a.widget_id.cmp(&b.widget_id))
+        });
+
+        UsageReport {
+            since: self.since.format(&Rfc3339).unwrap_or_default(),
+            max_values: self.max_values,
+            widgets: report,
+        }
+    }
+}
+
+/// The key a scalar argument value is counted under.
+fn value_key(value: &JsonValue) -> Option<String> {
+    match value {
+        JsonValue::String(text) => Some(
+            text.trim()
+                .to_lowercase()
+                .chars()
+                .take(MAX_VALUE_CHARS)
+                .collect(),
+        ),
+        JsonValue::Number(number) => Some(number.to_string()),
+        JsonValue::Bool(flag) => Some(flag.to_string()),
+        JsonValue::Null | JsonValue::Array(_) | JsonValue::Object(_) => None,
+    }
+}
+
+/// Body of `GET /internal/analytics`.
+#[derive(Debug, Serialize)]
+pub struct UsageReport {
+    /// RFC 3339 time counting started.
+    pub since: String,
+    pub max_values: usize,
+    /// Most called first.
+    pub widgets: Vec<WidgetReport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WidgetReport {
+    pub widget_id: String,
+    pub calls: u64,
+    pub arguments: Vec<ArgumentReport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ArgumentReport {
+    pub name: String,
+    /// Distinct values tracked, including those cut from `values`.
+    pub distinct: usize,
+    /// Values seen after the distinct value cap was reached.
+    pub other: u64,
+    /// Most frequent first.
+    pub values: Vec<ValueCount>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct ValueCount {
+    pub value: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct AnalyticsQuery {
+    widget: Option<String>,
+    top: Option<usize>,
+}
+
+/// `GET /internal/analytics` reports argument value counts; `?widget=` limits it to one widget
+/// and `?top=` sets how many values to list per argument (10 by default). It needs the operator
+/// token, since the values are what users typed.
+pub(crate) async fn analytics_handler(
+    Extension(state): Extension<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Query(query): Query<AnalyticsQuery>,
+) -> Result<Json<UsageReport>, ApiError> {
+    crate::require_operator(&state, addr, &headers)?;
+    Ok(Json(state.usage.report(
+        query.widget.as_deref(),
+        query.top.unwrap_or(DEFAULT_TOP),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn counts_declared_arguments_and_caps_distinct_values() {
+        let usage = Usage::with_max_values(2);
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "pizzaTopping": { "type": "string" },
+                "extras": { "type": "array" },
+                "slices": { "type": "integer" }
+            }
+        });
+        for arguments in [
+            json!({ "pizzaTopping": "Pepperoni ", "slices": 8, "note": "undeclared" }),
+            json!({ "pizzaTopping": "pepperoni", "extras": ["basil", "olives", null] }),
+            json!({ "pizzaTopping": "mushroom", "slices": 8 }),
+            json!({ "pizzaTopping": "anchovy", "extras": [] }),
+        ] {
+            usage.record("pizza-map", &schema, &arguments);
+        }
+        usage.record("pizza-list", &schema, &json!({}));
+
+        let report = usage.report(None, 10);
+        assert_eq!(
+            report
+                .widgets
+                .iter()
+                .map(|widget| (widget.widget_id.as_str(), widget.calls))
+                .collect::<Vec<_>>(),
+            vec![("pizza-map", 4), ("pizza-list", 1)]
+        );
+
+        let arguments = &report.widgets[0].arguments;
+        let names: Vec<_> = arguments.iter().map(|argument| &argument.name).collect();
+        assert_eq!(names, vec!["extras", "pizzaTopping", "slices"]);
+        let topping = &arguments[1];
+        assert_eq!(
+            topping.values,
+            vec![
+                ValueCount {
+                    value: "pepperoni".to_string(),
+                    count: 2
+                },
+                ValueCount {
+                    value: "mushroom".to_string(),
+                    count: 1
+                },
+            ]
+        );
+        assert_eq!((topping.distinct, topping.other), (2, 1));
+        assert_eq!((arguments[0].distinct, arguments[0].other), (2, 0));
+        assert_eq!(arguments[2].values[0].value, "8");
+
+        let report = usage.report(Some("pizza-map"), 1);
+        assert_eq!(report.widgets.len(), 1);
+        assert_eq!(report.widgets[0].arguments[1].values.len(), 1);
+        assert_eq!(report.widgets[0].arguments[1].distinct, 2);
+    }
+}
@@ -0,0 +1,230 @@
+//! Preparation of tool arguments against a tool's input schema.
+//!
+//! Omitted properties take the `default` their schema declares. Models also regularly send `"2"`
+//! where the schema asks for a number, or a bare value where it asks for an array. With
+//! `WIDGETS_LENIENT_ARGUMENTS=1` those arguments are repaired before they are deserialized instead
+//! of failing the whole call. Only unambiguous conversions are made; anything else is left as is,
+//! so strict validation still reports it.
+
+use serde_json::{Map as JsonMap, Number, Value as JsonValue};
+
+/// Fills in top-level properties that `value` omits and `schema` gives a `default`.
+///
+/// Returns the defaults that were applied, keyed by property name. Explicit `null`s are kept.
+pub fn apply_defaults(schema: &JsonValue, value: &mut JsonValue) -> JsonMap<String, JsonValue> {
+    let mut applied = JsonMap::new();
+    let (Some(properties), JsonValue::Object(map)) = (
+        schema.get("properties").and_then(JsonValue::as_object),
+        value,
+    ) else {
+        return applied;
+    };
+    for (name, property_schema) in properties {
+        if let Some(default) = property_schema.get("default") {
+            if !map.contains_key(name) {
+                map.insert(name.clone(), default.clone());
+                applied.insert(name.clone(), default.clone());
+            }
+        }
+    }
+    applied
+}
+
+/// Rewrites `value` in place to match `schema` where a lossless conversion exists.
+///
+/// Returns the JSON pointer of every value that was changed, for logging.
+pub fn coerce_to_schema(schema: &JsonValue, value: &mut JsonValue) -> Vec<String> {
+    let mut coerced = Vec::new();
+    coerce_at(schema, value, String::new(), &mut coerced);
+    coerced
+}
+
+fn coerce_at(schema: &JsonValue, value: &mut JsonValue, path: String, coerced: &mut Vec<String>) {
+    let Some(expected) = schema.get("type").and_then(JsonValue::as_str) else {
+        return;
+    };
+
+    if !matches_type(expected, value) {
+        match coerced_value(expected, value) {
+            Some(replacement) => {
+                *value = replacement;
+                coerced.push(pointer_or_root(&path));
+            }
+            None => return,
+        }
+    }
+
+    match value {
+        JsonValue::Object(map) => coerce_properties(schema, map, &path, coerced),
+        JsonValue::Array(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for (index, item) in items.iter_mut().enumerate() {
+                    coerce_at(item_schema, item, format!("{path}/{index}"), coerced);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn coerce_properties(
+    schema: &JsonValue,
+    map: &mut JsonMap<String, JsonValue>,
+    path: &str,
+    coerced: &mut Vec<String>,
+) {
+    let Some(properties) = schema.get("properties").and_then(JsonValue::as_object) else {
+        return;
+    };
+    for (name, property_schema) in properties {
+        if let Some(property) = map.get_mut(name) {
+            let escaped = name.replace('~', "~0").replace('/', "~1");
+            coerce_at(
+                property_schema,
+                property,
+                format!("{path}/{escaped}"),
+                coerced,
+            );
+        }
+    }
+}
+
+fn matches_type(expected: &str, value: &JsonValue) -> bool {
+    match expected {
+        "string" => value.is_string(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        "null" => value.is_null(),
+        // Unknown types are not ours to judge.
+        _ => true,
+    }
+}
+
+fn coerced_value(expected: &str, value: &JsonValue) -> Option<JsonValue> {
+    match (expected, value) {
+        // A missing value stays missing; defaults and `required` decide what happens to it.
+        (_, JsonValue::Null) => None,
+        ("string", JsonValue::Number(number)) => Some(JsonValue::String(number.to_string())),
+        ("string", JsonValue::Bool(flag)) => Some(JsonValue::String(flag.to_string())),
+        ("integer", JsonValue::String(text)) => {
+            text.trim().parse::<i64>().ok().map(JsonValue::from)
+        }
+        ("number", JsonValue::String(text)) => {
+            let text = text.trim();
+            text.parse::<i64>().map(JsonValue::from).ok().or_else(|| {
+                text.parse::<f64>()
+                    .ok()
+                    .and_then(Number::from_f64)
+                    .map(JsonValue::Number)
+            })
+        }
+        ("boolean", JsonValue::String(text)) => match text.trim().to_ascii_lowercase().as_str() {
+            "true" => Some(JsonValue::Bool(true)),
+            "false" => Some(JsonValue::Bool(false)),
+            _ => None,
+        },
+        ("array", single) => Some(JsonValue::Array(vec![single.clone()])),
+        _ => None,
+    }
+}
+
+fn pointer_or_root(path: &str) -> String {
+    if path.is_empty() {
+        "/".to_string()
+    } else {
+        path.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema() -> JsonValue {
+        json!({
+            "type": "object",
+            "properties": {
+                "topping": { "type": "string" },
+                "slices": { "type": "integer" },
+                "price": { "type": "number" },
+                "extraCheese": { "type": "boolean" },
+                "sides": { "type": "array", "items": { "type": "string" } }
+            }
+        })
+    }
+
+    #[test]
+    fn coerces_mistyped_scalars_and_wraps_single_values() {
+        let mut args = json!({
+            "topping": 42,
+            "slices": " 2 ",
+            "price": "9.5",
+            "extraCheese": "TRUE",
+            "sides": 7
+        });
+        let mut coerced = coerce_to_schema(&schema(), &mut args);
+        coerced.sort();
+
+        assert_eq!(
+            args,
+            json!({
+                "topping": "42",
+                "slices": 2,
+                "price": 9.5,
+                "extraCheese": true,
+                "sides": ["7"]
+            })
+        );
+        assert_eq!(
+            coerced,
+            vec![
+                "/extraCheese",
+                "/price",
+                "/sides",
+                "/sides/0",
+                "/slices",
+                "/topping"
+            ]
+        );
+    }
+
+    #[test]
+    fn defaults_fill_only_omitted_properties() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "topping": { "type": "string", "default": "cheese" },
+                "slices": { "type": "integer", "default": 8 },
+                "size": { "type": "string", "default": "large" },
+                "crust": { "type": "string" }
+            }
+        });
+        let mut args = json!({ "slices": 4, "size": null });
+
+        let applied = apply_defaults(&schema, &mut args);
+
+        assert_eq!(
+            args,
+            json!({ "topping": "cheese", "slices": 4, "size": null })
+        );
+        assert_eq!(JsonValue::Object(applied), json!({ "topping": "cheese" }));
+    }
+
+    #[test]
+    fn leaves_ambiguous_values_for_validation_to_reject() {
+        let mut args = json!({
+            "slices": "two",
+            "extraCheese": "maybe",
+            "topping": null,
+            "unknown": "2"
+        });
+        let original = args.clone();
+
+        assert!(coerce_to_schema(&schema(), &mut args).is_empty());
+        assert_eq!(args, original);
+    }
+}
@@ -0,0 +1,237 @@
+//! Accounting for the bytes the metadata augmenter holds in memory.
+//!
+//! JSON responses are buffered whole so widget metadata can be added, and SSE responses hold each
+//! event until its blank line arrives. Every in-flight response reserves what it buffers against
+//! one process-wide budget, `MCP_AUGMENT_MAX_BUFFERED_BYTES` (64 MiB by default, `0` for no cap).
+//! A response that would take the total over the budget switches to passthrough: what it has
+//! buffered so far and everything after is forwarded unmodified, without widget metadata. A JSON
+//! response that announces a `Content-Length` over the remaining budget is never buffered at all.
+//!
+//! `GET /internal/widgets/status` reports the gauge under `augment_memory`.
+
+use std::{
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+use http_body::{Body, Frame, SizeHint};
+use serde::Serialize;
+
+const DEFAULT_MAX_BUFFERED_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Budget read from `MCP_AUGMENT_MAX_BUFFERED_BYTES`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AugmentMemoryConfig {
+    /// `None` leaves buffering uncapped.
+    pub max_buffered_bytes: Option<u64>,
+}
+
+impl Default for AugmentMemoryConfig {
+    fn default() -> Self {
+        Self {
+            max_buffered_bytes: Some(DEFAULT_MAX_BUFFERED_BYTES),
+        }
+    }
+}
+
+impl AugmentMemoryConfig {
+    pub(crate) fn from_env() -> Self {
+        let Ok(raw) = std::env::var("MCP_AUGMENT_MAX_BUFFERED_BYTES") else {
+            return Self::default();
+        };
+        match raw.trim().parse::<u64>() {
+            Ok(0) => Self {
+                max_buffered_bytes: None,
+            },
+            Ok(bytes) => Self {
+                max_buffered_bytes: Some(bytes),
+            },
+            Err(_) => {
+                tracing::warn!(value = %raw, "Ignoring invalid MCP_AUGMENT_MAX_BUFFERED_BYTES");
+                Self::default()
+            }
+        }
+    }
+}
+
+/// Bytes buffered by in-flight augmented responses, across all connections.
+#[derive(Debug, Default)]
+pub struct AugmentMemory {
+    config: AugmentMemoryConfig,
+    buffered: AtomicU64,
+    peak: AtomicU64,
+    passthrough_responses: AtomicU64,
+}
+
+/// Point-in-time copy of [`AugmentMemory`].
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub struct AugmentMemorySnapshot {
+    pub buffered_bytes: u64,
+    pub peak_buffered_bytes: u64,
+    pub max_buffered_bytes: Option<u64>,
+    /// Responses forwarded without metadata because the budget was used up.
+    pub passthrough_responses: u64,
+}
+
+impl AugmentMemory {
+    pub fn new(config: AugmentMemoryConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// An empty reservation for one response, released when it is dropped.
+    pub fn reserve(self: &Arc<Self>) -> Reservation {
+        Reservation {
+            memory: Arc::clone(self),
+            bytes: 0,
+        }
+    }
+
+    pub(crate) fn record_passthrough(&self) {
+        self.passthrough_responses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> AugmentMemorySnapshot {
+        AugmentMemorySnapshot {
+            buffered_bytes: self.buffered.load(Ordering::Relaxed),
+            peak_buffered_bytes: self.peak.load(Ordering::Relaxed),
+            max_buffered_bytes: self.config.max_buffered_bytes,
+            passthrough_responses: self.passthrough_responses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Adds `delta` to the gauge, refusing when `checked` and the budget would be exceeded.
+    fn grow(&self, delta: u64, checked: bool) -> bool {
+        let limit = self
+            .config
+            .max_buffered_bytes
+            .filter(|_| checked)
+            .unwrap_or(u64::MAX);
+        let grown = self
+            .buffered
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |buffered| {
+                buffered.checked_add(delta).filter(|total| *total <= limit)
+            });
+        match grown {
+            Ok(previous) => {
+                self.peak.fetch_max(previous + delta, Ordering::Relaxed);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+/// Bytes one response holds against the budget.
+#[derive(Debug)]
+pub struct Reservation {
+    memory: Arc<AugmentMemory>,
+    bytes: u64,
+}
+
+impl Reservation {
+    /// Resizes the reservation to `bytes`. Growing past the budget is refused, leaving the
+    /// reservation as it was.
+    pub fn try_resize(&mut self, bytes: u64) -> bool {
+        self.resize_to(bytes, true)
+    }
+
+    /// Resizes the reservation to `bytes` even past the budget, for memory already allocated.
+    pub fn resize(&mut self, bytes: u64) {
+        self.resize_to(bytes, false);
+    }
+
+    fn resize_to(&mut self, bytes: u64, checked: bool) -> bool {
+        if bytes > self.bytes {
+            if !self.memory.grow(bytes - self.bytes, checked) {
+                return false;
+            }
+        } else {
+            self.memory
+                .buffered
+                .fetch_sub(self.bytes - bytes, Ordering::AcqRel);
+        }
+        self.bytes = bytes;
+        true
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.resize(0);
+    }
+}
+
+/// A response body that keeps its [`Reservation`] until the body is dropped.
+pub struct AccountedBody<B> {
+    inner: B,
+    _reservation: Reservation,
+}
+
+impl<B> AccountedBody<B> {
+    pub fn new(inner: B, reservation: Reservation) -> Self {
+        Self {
+            inner,
+            _reservation: reservation,
+        }
+    }
+}
+
+impl<B> Body for AccountedBody<B>
+where
+    B: Body + Unpin,
+{
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        Pin::new(&mut self.inner).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reservations_share_one_budget_and_release_on_drop() {
+        let memory = Arc::new(AugmentMemory::new(AugmentMemoryConfig {
+            max_buffered_bytes: Some(100),
+        }));
+        let mut first = memory.reserve();
+        let mut second = memory.reserve();
+        assert!(first.try_resize(60));
+        assert!(!second.try_resize(50));
+        assert!(second.try_resize(40));
+        assert_eq!(memory.snapshot().buffered_bytes, 100);
+
+        // Shrinking frees budget for others; forced growth is counted but may overshoot.
+        assert!(first.try_resize(10));
+        assert!(second.try_resize(90));
+        first.resize(30);
+        assert_eq!(memory.snapshot().buffered_bytes, 120);
+
+        drop(first);
+        drop(second);
+        let snapshot = memory.snapshot();
+        assert_eq!(snapshot.buffered_bytes, 0);
+        assert_eq!(snapshot.peak_buffered_bytes, 120);
+    }
+}
@@ -0,0 +1,225 @@
+//! Caller identity resolution for the MCP endpoint.
+
+use std::collections::HashMap;
+
+use axum::{
+    extract::Request,
+    http::{header, HeaderMap},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use subtle::ConstantTimeEq;
+
+use crate::{
+    error::{ApiError, BearerError},
+    AppState,
+};
+
+/// Authenticated caller attached to every `/mcp` request.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Identity {
+    pub subject: Option<String>,
+    pub roles: Vec<String>,
+}
+
+impl Identity {
+    /// Identity used when MCP authentication is disabled.
+    pub fn anonymous() -> Self {
+        Self::default()
+    }
+
+    /// Returns true when the identity holds at least one of the given roles.
+    pub fn has_any_role(&self, roles: &[String]) -> bool {
+        roles.iter().any(|role| self.roles.contains(role))
+    }
+}
+
+/// Static bearer tokens mapped to identities, loaded from `MCP_AUTH_TOKENS`.
+///
+/// The variable holds comma-separated `token=subject[:role|role...]` entries, e.g.
+/// `s3cret=alice:admin|staff,other=bob`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct AuthConfig {
+    tokens: HashMap<String, Identity>,
+}
+
+impl AuthConfig {
+    pub(crate) fn from_env() -> Self {
+        Self::from_env_var("MCP_AUTH_TOKENS")
+    }
+
+    /// Reads tokens in the `MCP_AUTH_TOKENS` format from another variable.
+    pub(crate) fn from_env_var(variable: &str) -> Self {
+        std::env::var(variable)
+            .ok()
+            .map(|raw| parse_auth_tokens(&raw))
+            .unwrap_or_default()
+    }
+
+    /// Configured identities ordered by subject; the tokens themselves are not exposed.
+    pub(crate) fn identities(&self) -> impl Iterator<Item = &Identity> {
+        let mut identities: Vec<_> = self.tokens.values().collect();
+        identities.sort_by(|a, b| a.subject.cmp(&b.subject));
+        identities.into_iter()
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        !self.tokens.is_empty()
+    }
+
+    /// Resolves a bearer token to its identity using constant-time comparisons.
+    pub(crate) fn identify(&self, provided: &str) -> Option<Identity> {
+        let mut matched = None;
+        for (token, identity) in &self.tokens {
+            let equal = token.len() == provided.len()
+                && token.as_bytes().ct_eq(provided.as_bytes()).unwrap_u8() == 1;
+            if equal {
+                matched = Some(identity.clone());
+            }
+        }
+        matched
+    }
+}
+
+pub(crate) fn parse_auth_tokens(raw: &str) -> AuthConfig {
+    let mut tokens = HashMap::new();
+    for entry in raw.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let Some((token, rest)) = entry.split_once('=') else {
+            tracing::warn!("Ignoring auth token entry without '=' separator");
+            continue;
+        };
+        let token = token.trim();
+        let (subject, roles) = match rest.split_once(':') {
+            Some((subject, roles)) => (subject.trim(), roles),
+            None => (rest.trim(), ""),
+        };
+        if token.is_empty() || subject.is_empty() {
+            tracing::warn!("Ignoring auth token entry with empty token or subject");
+            continue;
+        }
+        let roles = roles
+            .split('|')
+            .map(str::trim)
+            .filter(|role| !role.is_empty())
+            .map(str::to_string)
+            .collect();
+        tokens.insert(
+            token.to_string(),
+            Identity {
+                subject: Some(subject.to_string()),
+                roles,
+            },
+        );
+    }
+    AuthConfig { tokens }
+}
+
+/// Extracts the token from an `Authorization: Bearer <token>` header.
+pub(crate) fn extract_bearer_token(headers: &HeaderMap) -> Option<&str> {
+    let value = headers.get(header::AUTHORIZATION)?;
+    let value = value.to_str().ok()?.trim();
+    let mut parts = value.splitn(2, ' ');
+    let scheme = parts.next()?.to_ascii_lowercase();
+    if scheme != "bearer" {
+        return None;
+    }
+    let token = parts.next()?.trim();
+    if token.is_empty() {
+        return None;
+    }
+    Some(token)
+}
+
+/// Classifies credentials that failed to authenticate, for the RFC 6750 challenge.
+pub(crate) fn bearer_error(headers: &HeaderMap) -> BearerError {
+    let Some(value) = headers.get(header::AUTHORIZATION) else {
+        return BearerError::Missing;
+    };
+    let Ok(value) = value.to_str() else {
+        return BearerError::InvalidRequest;
+    };
+    let (scheme, token) = value.trim().split_once(' ').unwrap_or((value.trim(), ""));
+    if !scheme.eq_ignore_ascii_case("bearer") {
+        BearerError::Missing
+    } else if token.trim().is_empty() {
+        BearerError::InvalidRequest
+    } else {
+        BearerError::InvalidToken
+    }
+}
+
+/// Middleware that resolves the caller identity and stores it in request extensions.
+///
+/// When no tokens are configured every request proceeds as [`Identity::anonymous`].
+pub(crate) async fn authenticate_mcp(
+    Extension(state): Extension<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let config = &state.auth;
+    let identity = if config.is_enabled() {
+        match extract_bearer_token(request.headers()).and_then(|token| config.identify(token)) {
+            Some(identity) => identity,
+            None => {
+                return ApiError::unauthorized("mcp", bearer_error(request.headers()))
+                    .into_response()
+            }
+        }
+    } else {
+        Identity::anonymous()
+    };
+
+    request.extensions_mut().insert(identity);
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_auth_tokens_reads_subject_and_roles() {
+        let config = parse_auth_tokens("abc=alice:admin|staff, def=bob");
+        let alice = config.identify("abc").expect("alice token");
+        assert_eq!(alice.subject.as_deref(), Some("alice"));
+        assert_eq!(alice.roles, vec!["admin".to_string(), "staff".to_string()]);
+
+        let bob = config.identify("def").expect("bob token");
+        assert_eq!(bob.subject.as_deref(), Some("bob"));
+        assert!(bob.roles.is_empty());
+
+        assert!(config.identify("nope").is_none());
+    }
+
+    #[test]
+    fn parse_auth_tokens_skips_malformed_entries() {
+        let config = parse_auth_tokens("missing-separator,=nobody,tok=");
+        assert!(!config.is_enabled());
+    }
+
+    #[test]
+    fn bearer_error_tells_missing_from_malformed_and_rejected_tokens() {
+        let with = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::AUTHORIZATION, value.parse().unwrap());
+            bearer_error(&headers)
+        };
+        assert_eq!(bearer_error(&HeaderMap::new()), BearerError::Missing);
+        assert_eq!(with("Basic dXNlcjpwYXNz"), BearerError::Missing);
+        assert_eq!(with("Bearer"), BearerError::InvalidRequest);
+        assert_eq!(with("bearer   "), BearerError::InvalidRequest);
+        assert_eq!(with("Bearer wrong"), BearerError::InvalidToken);
+    }
+
+    #[test]
+    fn identity_role_matching() {
+        let identity = Identity {
+            subject: Some("alice".into()),
+            roles: vec!["staff".into()],
+        };
+        assert!(identity.has_any_role(&["admin".into(), "staff".into()]));
+        assert!(!identity.has_any_role(&["admin".into()]));
+        assert!(!Identity::anonymous().has_any_role(&["staff".into()]));
+    }
+}
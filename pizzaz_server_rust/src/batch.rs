@@ -0,0 +1,156 @@
+//! The `pizza-batch` tool: several widget tool calls in one round trip.
+//!
+//! Enabled with `WIDGETS_BATCH_TOOL=1`. Sub-calls run concurrently, at most
+//! `WIDGETS_BATCH_CONCURRENCY` at a time, and go through the same access checks, rate limits and
+//! executors as individual calls. Results come back in request order; one failing sub-call never
+//! fails the batch.
+
+use futures::{stream, StreamExt};
+use rmcp::model::{Content, Meta};
+use serde::Deserialize;
+use serde_json::{Map as JsonMap, Value as JsonValue};
+
+use crate::handler::{CallContext, PizzazServerHandler, ToolCallError, WidgetCallResult};
+
+/// Name under which the batch tool is listed.
+pub const BATCH_TOOL_NAME: &str = "pizza-batch";
+
+/// Upper bound on sub-calls per batch, so one request cannot queue unbounded work.
+pub const MAX_BATCH_CALLS: usize = 16;
+
+const DEFAULT_CONCURRENCY: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchConfig {
+    /// Sub-calls allowed to run at the same time.
+    pub concurrency: usize,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: DEFAULT_CONCURRENCY,
+        }
+    }
+}
+
+impl BatchConfig {
+    /// Returns `None` unless `WIDGETS_BATCH_TOOL` is enabled.
+    pub(crate) fn from_env() -> Option<Self> {
+        if !crate::env_flag("WIDGETS_BATCH_TOOL") {
+            return None;
+        }
+        let concurrency = std::env::var("WIDGETS_BATCH_CONCURRENCY")
+            .ok()
+            .and_then(|value| value.trim().parse::<usize>().ok())
+            .filter(|value| *value > 0)
+            .unwrap_or(DEFAULT_CONCURRENCY);
+        Some(Self { concurrency })
+    }
+}
+
+#[derive(Deserialize)]
+struct BatchInput {
+    calls: Vec<SubCall>,
+}
+
+#[derive(Deserialize)]
+struct SubCall {
+    name: String,
+    #[serde(default = "empty_arguments")]
+    arguments: JsonValue,
+}
+
+fn empty_arguments() -> JsonValue {
+    JsonValue::Object(JsonMap::new())
+}
+
+pub(crate) fn input_schema() -> JsonValue {
+    serde_json::json!({
+        "type": "object",
+        "properties": {
+            "calls": {
+                "type": "array",
+                "description": "Widget tool calls to run together; results keep this order.",
+                "minItems": 1,
+                "maxItems": MAX_BATCH_CALLS,
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "name": { "type": "string", "description": "Widget tool to call." },
+                        "arguments": { "type": "object", "description": "Arguments for that tool." }
+                    },
+                    "required": ["name"],
+                    "additionalProperties": false
+                }
+            }
+        },
+        "required": ["calls"],
+        "additionalProperties": false
+    })
+}
+
+/// Runs every sub-call and collects the outcomes under `structuredContent.results`.
+pub(crate) async fn run(
+    handler: &PizzazServerHandler,
+    config: BatchConfig,
+    ctx: &CallContext,
+    arguments: JsonValue,
+) -> Result<WidgetCallResult, ToolCallError> {
+    let input: BatchInput =
+        serde_json::from_value(arguments).map_err(ToolCallError::InvalidArguments)?;
+    if input.calls.is_empty() || input.calls.len() > MAX_BATCH_CALLS {
+        return Err(ToolCallError::InvalidArguments(serde::de::Error::custom(
+            format!("calls must hold between 1 and {MAX_BATCH_CALLS} entries"),
+        )));
+    }
+
+    let results: Vec<JsonValue> = stream::iter(input.calls)
+        .map(|call| async move {
+            let outcome = handler.call_widget(ctx, &call.name, call.arguments).await;
+            sub_call_entry(call.name, outcome)
+        })
+        .buffered(config.concurrency)
+        .collect()
+        .await;
+
+    let total = results.len();
+    let failed = results
+        .iter()
+        .filter(|entry| entry["ok"] != JsonValue::Bool(true))
+        .count();
+    let mut structured = JsonMap::new();
+    structured.insert("results".to_string(), JsonValue::Array(results));
+    Ok(WidgetCallResult {
+        content: vec![Content::text(format!(
+            "Ran {total} calls: {} succeeded, {failed} failed.",
+            total - failed
+        ))],
+        structured_content: JsonValue::Object(structured),
+        meta: Meta::default(),
+        is_error: false,
+    })
+}
+
+/// One entry of the results array. Executor failures (`isError` results) count as failed.
+fn sub_call_entry(name: String, outcome: Result<WidgetCallResult, ToolCallError>) -> JsonValue {
+    match outcome {
+        Ok(result) => serde_json::json!({
+            "name": name,
+            "ok": !result.is_error,
+            "structuredContent": result.structured_content,
+        }),
+        Err(error) => {
+            let error = error.into_error_data();
+            serde_json::json!({
+                "name": name,
+                "ok": false,
+                "error": {
+                    "code": error.code.0,
+                    "message": error.message,
+                    "data": error.data,
+                },
+            })
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! Pokes a running server over MCP without the MCP Inspector.
+//!
+//! Usage: `pizzaz-client [--url <mcp url>] [--token <bearer>] [--json] <command>`
+//!
+//! Commands:
+//! - `info`: the `initialize` result.
+//! - `tools`: tools with their `_meta`.
+//! - `resources`: resources and resource templates with their `_meta`.
+//! - `read <uri>`: a resource's contents.
+//! - `call <tool> [<json object> | key=value...]`: calls a tool and prints its content,
+//!   `structuredContent` and `_meta`. `key=value` values are parsed as JSON when they can be, so
+//!   `count=3` sends a number and `topping=pepperoni` a string.
+//!
+//! `--url` defaults to `PIZZAZ_MCP_URL`, then `http://127.0.0.1:$PORT/mcp`; `--token` to
+//! `PIZZAZ_MCP_TOKEN`. `--json` prints raw results instead.
+
+use anyhow::{anyhow, bail, Context};
+use pizzaz_server_rust::mcp_client::{parse_cli_arguments, McpClient};
+use serde_json::Value;
+
+const USAGE: &str = "usage: pizzaz-client [--url <mcp url>] [--token <bearer>] [--json] \
+                     <info | tools | resources | read <uri> | call <tool> [args...]>";
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let _ = dotenvy::dotenv();
+
+    let mut args = std::env::args().skip(1);
+    let mut url = None;
+    let mut token = std::env::var("PIZZAZ_MCP_TOKEN").ok();
+    let mut raw = false;
+    let mut command = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--url" if command.is_empty() => {
+                url = Some(args.next().context("--url requires a URL")?)
+            }
+            "--token" if command.is_empty() => {
+                token = Some(args.next().context("--token requires a value")?)
+            }
+            "--json" if command.is_empty() => raw = true,
+            _ => command.push(arg),
+        }
+    }
+    let url = url
+        .or_else(|| std::env::var("PIZZAZ_MCP_URL").ok())
+        .unwrap_or_else(|| {
+            let port = std::env::var("PORT").unwrap_or_else(|_| "8000".to_string());
+            format!("http://127.0.0.1:{port}/mcp")
+        });
+
+    let mut client = McpClient::new(&url);
+    if let Some(token) = token {
+        client = client.with_token(token);
+    }
+    let mut client = client
+        .connect()
+        .await
+        .with_context(|| format!("failed to connect to {url}"))?;
+
+    let outcome = run(&mut client, &command, raw).await;
+    client.close().await;
+    outcome
+}
+
+async fn run(client: &mut McpClient, command: &[String], raw: bool) -> anyhow::Result<()> {
+    match command {
+        [name] if name == "info" => print_json(client.server()),
+        [name] if name == "tools" => {
+            let result = client.list_tools().await?;
+            if raw {
+                return print_json(&result);
+            }
+            for tool in result["tools"].as_array().into_iter().flatten() {
+                println!("{}", tool["name"].as_str().unwrap_or_default());
+                print_field("title", &tool["title"]);
+                print_field("description", &tool["description"]);
+                print_field("_meta", &tool["_meta"]);
+            }
+            Ok(())
+        }
+        [name] if name == "resources" => {
+            let resources = client.list_resources().await?;
+            let templates = client.list_resource_templates().await?;
+            if raw {
+                return print_json(&serde_json::json!({
+                    "resources": resources["resources"],
+                    "resourceTemplates": templates["resourceTemplates"],
+                }));
+            }
+            for resource in resources["resources"].as_array().into_iter().flatten() {
+                println!("{}", resource["uri"].as_str().unwrap_or_default());
+                print_field("name", &resource["name"]);
+                print_field("mimeType", &resource["mimeType"]);
+                print_field("_meta", &resource["_meta"]);
+            }
+            for template in templates["resourceTemplates"]
+                .as_array()
+                .into_iter()
+                .flatten()
+            {
+                println!(
+                    "{} (template)",
+                    template["uriTemplate"].as_str().unwrap_or_default()
+                );
+                print_field("name", &template["name"]);
+                print_field("_meta", &template["_meta"]);
+            }
+            Ok(())
+        }
+        [name, uri] if name == "read" => print_json(&client.read_resource(uri).await?),
+        [name, tool, args @ ..] if name == "call" => {
+            let arguments = parse_cli_arguments(args).map_err(|error| anyhow!(error))?;
+            let result = client.call_tool(tool, arguments).await?;
+            if raw {
+                return print_json(&result);
+            }
+            if result["isError"] == Value::Bool(true) {
+                println!("isError: true");
+            }
+            for content in result["content"].as_array().into_iter().flatten() {
+                match content["text"].as_str() {
+                    Some(text) => println!("content: {text}"),
+                    None => print_field("content", content),
+                }
+            }
+            print_field("structuredContent", &result["structuredContent"]);
+            print_field("_meta", &result["_meta"]);
+            Ok(())
+        }
+        _ => bail!(USAGE),
+    }
+}
+
+fn print_field(label: &str, value: &Value) {
+    match value {
+        Value::Null => {}
+        Value::String(text) => println!("  {label}: {text}"),
+        other => {
+            let pretty = serde_json::to_string_pretty(other).expect("JSON value serializes");
+            println!("  {label}: {}", pretty.replace('\n', "\n  "));
+        }
+    }
+}
+
+fn print_json(value: &Value) -> anyhow::Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
@@ -0,0 +1,46 @@
+//! Writes JSON-RPC fixtures for every widget in a manifest.
+//!
+//! Usage: `pizzaz_fixtures [<widgets.json>] [--out <dir>]`
+//!
+//! The manifest defaults to the one the server would load and `--out` to
+//! `tests/fixtures/generated`. Existing `*.json` files in the output directory are replaced.
+
+use std::path::PathBuf;
+
+use anyhow::{bail, Context};
+use pizzaz_server_rust::{
+    fixtures::{generate_fixtures, write_fixtures, DEFAULT_FIXTURES_DIR},
+    handler::PizzazServerHandler,
+    widgets,
+};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let _ = dotenvy::dotenv();
+
+    let mut args = std::env::args().skip(1);
+    let mut manifest = None;
+    let mut out = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" => out = Some(PathBuf::from(args.next().context("--out requires a path")?)),
+            _ if manifest.is_none() => manifest = Some(PathBuf::from(arg)),
+            other => bail!("unexpected argument: {other}"),
+        }
+    }
+    let manifest = manifest.unwrap_or_else(widgets::resolve_manifest_path);
+    let out = out.unwrap_or_else(|| PathBuf::from(DEFAULT_FIXTURES_DIR));
+
+    widgets::switch_manifest_path(manifest.clone())
+        .await
+        .with_context(|| format!("failed to load {}", manifest.display()))?;
+    // A plain handler, so fixtures do not depend on flags or experiments set in the environment.
+    let fixtures = generate_fixtures(&PizzazServerHandler::new()).await;
+    let written = write_fixtures(&out, &fixtures)
+        .with_context(|| format!("failed to write fixtures to {}", out.display()))?;
+    for path in &written {
+        println!("{}", path.display());
+    }
+    println!("Wrote {} fixtures to {}", written.len(), out.display());
+    Ok(())
+}
@@ -0,0 +1,63 @@
+//! Lints the HTML of every widget in a manifest against the Apps SDK rules in `lint`.
+//!
+//! Usage: `pizzaz_lint [<widgets.json>] [--strict]`
+//!
+//! The manifest defaults to the one the server would load. Findings are printed per widget with
+//! the action `WIDGETS_LINT_POLICY` gives them. The exit status is non-zero when a finding would
+//! block the widget at load time, or on any finding with `--strict`.
+
+use std::{path::PathBuf, process::ExitCode};
+
+use anyhow::{bail, Context};
+use pizzaz_server_rust::{
+    lint::{self, lint_manifest, TemplateLinter},
+    scan::ScanAction,
+    widgets,
+};
+
+fn main() -> anyhow::Result<ExitCode> {
+    let _ = dotenvy::dotenv();
+
+    let mut manifest = None;
+    let mut strict = false;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--strict" => strict = true,
+            _ if manifest.is_none() => manifest = Some(PathBuf::from(arg)),
+            other => bail!("unexpected argument: {other}"),
+        }
+    }
+    let manifest = manifest.unwrap_or_else(widgets::resolve_manifest_path);
+    let policy = lint::settings().policy;
+
+    let results = lint_manifest(&manifest, &TemplateLinter::from_env())
+        .with_context(|| format!("failed to lint {}", manifest.display()))?;
+    let mut failed = false;
+    for result in &results {
+        let Some(html_path) = &result.html_path else {
+            println!("{:<28} skipped: HTML is remote", result.widget_id);
+            continue;
+        };
+        if result.findings.is_empty() {
+            println!("{:<28} ok ({})", result.widget_id, html_path.display());
+            continue;
+        }
+        println!("{:<28} {}", result.widget_id, html_path.display());
+        for finding in &result.findings {
+            let action = policy.action(&finding.rule);
+            let label = match action {
+                ScanAction::Off => "off",
+                ScanAction::Warn => "warn",
+                ScanAction::Block => "block",
+            };
+            failed |= action == ScanAction::Block || (strict && action != ScanAction::Off);
+            println!("  {label:<5} {}: {}", finding.rule, finding.message);
+        }
+    }
+
+    Ok(if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
@@ -0,0 +1,74 @@
+//! Generates `widgets.json` from a directory of built widget files.
+//!
+//! Usage: `pizzaz_manifest <build-dir> [--out <widgets.json>] [--merge <widgets.json>]
+//! [--base-url <url>]`
+//!
+//! `--out` defaults to `<build-dir>/widgets.json`. Hand-written fields are kept from `--merge`, or
+//! from the existing output file when `--merge` is not given.
+
+use std::path::PathBuf;
+
+use anyhow::{bail, Context};
+use pizzaz_server_rust::{
+    manifest_generator::{write_manifest, ManifestGenerator, DEFAULT_ASSET_BASE_URL},
+    widgets_manifest::read_manifest_verbatim,
+};
+
+fn main() -> anyhow::Result<()> {
+    let _ = dotenvy::dotenv();
+
+    let mut args = std::env::args().skip(1);
+    let mut dir = None;
+    let mut out = None;
+    let mut merge = None;
+    let mut base_url = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" => out = Some(PathBuf::from(args.next().context("--out requires a path")?)),
+            "--merge" => {
+                merge = Some(PathBuf::from(
+                    args.next().context("--merge requires a path")?,
+                ))
+            }
+            "--base-url" => base_url = Some(args.next().context("--base-url requires a URL")?),
+            _ if dir.is_none() => dir = Some(PathBuf::from(arg)),
+            other => bail!("unexpected argument: {other}"),
+        }
+    }
+    let Some(dir) = dir else {
+        bail!("usage: pizzaz_manifest <build-dir> [--out <widgets.json>] [--merge <widgets.json>] [--base-url <url>]");
+    };
+    let out = out.unwrap_or_else(|| dir.join("widgets.json"));
+    let base_url = base_url
+        .or_else(|| std::env::var("WIDGETS_ASSET_BASE_URL").ok())
+        .unwrap_or_else(|| DEFAULT_ASSET_BASE_URL.to_string());
+
+    let mut generator = ManifestGenerator::new(&dir).with_base_url(base_url);
+    let merge = merge.or_else(|| out.exists().then(|| out.clone()));
+    if let Some(merge) = &merge {
+        generator = generator.with_existing(read_manifest_verbatim(merge)?);
+    }
+    let out_dir = match out.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let generated = generator.generate(&out_dir)?;
+    for warning in &generated.warnings {
+        eprintln!("warning: {warning}");
+    }
+
+    let registry = write_manifest(&generated.manifest, &out)?;
+    // The same content hashes the server reports under `content_hashes` once it loads this file.
+    for widget in registry.widgets() {
+        println!(
+            "{:<28} {:<40} {}",
+            widget.id, widget.template_uri, widget.content_hash
+        );
+    }
+    println!(
+        "Wrote {} widgets to {}",
+        registry.widgets().len(),
+        out.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,68 @@
+//! Replays traffic recorded with `MCP_CAPTURE_DIR` through a fresh in-process server.
+//!
+//! Usage: `pizzaz_replay <capture-dir> [--token <bearer>]`
+
+use std::path::PathBuf;
+
+use anyhow::{bail, Context};
+use pizzaz_server_rust::capture;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let _ = dotenvy::dotenv();
+
+    let mut args = std::env::args().skip(1);
+    let mut dir = None;
+    let mut token = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--token" => token = Some(args.next().context("--token requires a value")?),
+            _ if dir.is_none() => dir = Some(PathBuf::from(arg)),
+            other => bail!("unexpected argument: {other}"),
+        }
+    }
+    let Some(dir) = dir else {
+        bail!("usage: pizzaz_replay <capture-dir> [--token <bearer>]");
+    };
+
+    // Never record the replay itself into the directory being replayed.
+    std::env::remove_var("MCP_CAPTURE_DIR");
+
+    let exchanges = capture::load_captures(&dir)
+        .with_context(|| format!("failed to read captures from {}", dir.display()))?;
+    let app = pizzaz_server_rust::create_app();
+    let outcomes = capture::replay(app, &exchanges, token.as_deref()).await;
+
+    let mut mismatches = 0;
+    for outcome in &outcomes {
+        let marker = if outcome.status_matches() {
+            "ok  "
+        } else {
+            mismatches += 1;
+            "DIFF"
+        };
+        println!(
+            "{marker} #{:<6} {:<28} recorded={} replayed={}",
+            outcome.sequence,
+            outcome.rpc_method.as_deref().unwrap_or("-"),
+            outcome
+                .recorded_status
+                .map(|status| status.to_string())
+                .unwrap_or_else(|| "-".into()),
+            outcome.replayed_status,
+        );
+        if !outcome.status_matches() {
+            println!("       {}", outcome.body);
+        }
+    }
+
+    println!(
+        "{} exchanges replayed, {} status mismatches",
+        outcomes.len(),
+        mismatches
+    );
+    if mismatches > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,66 @@
+//! Build provenance captured by `build.rs`.
+
+use std::sync::LazyLock;
+
+use axum::Json;
+use serde::Serialize;
+use serde_json::Value;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+/// `_meta` key under which `initialize` responses carry [`BuildInfo`] in `serverInfo`.
+pub const BUILD_META_KEY: &str = "pizzaz/build";
+
+/// Identifies exactly which build a process is running.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct BuildInfo {
+    pub crate_version: &'static str,
+    pub git_sha: &'static str,
+    /// RFC 3339 timestamp of the build, or `SOURCE_DATE_EPOCH` when that was set.
+    pub build_timestamp: String,
+    pub rustc_version: &'static str,
+    pub features: Vec<&'static str>,
+}
+
+static BUILD_INFO: LazyLock<BuildInfo> = LazyLock::new(|| BuildInfo {
+    crate_version: env!("CARGO_PKG_VERSION"),
+    git_sha: env!("PIZZAZ_GIT_SHA"),
+    build_timestamp: env!("PIZZAZ_BUILD_EPOCH")
+        .parse::<i64>()
+        .ok()
+        .and_then(|epoch| OffsetDateTime::from_unix_timestamp(epoch).ok())
+        .and_then(|timestamp| timestamp.format(&Rfc3339).ok())
+        .unwrap_or_else(|| "unknown".to_string()),
+    rustc_version: env!("PIZZAZ_RUSTC_VERSION"),
+    features: env!("PIZZAZ_FEATURES")
+        .split(',')
+        .filter(|feature| !feature.is_empty())
+        .collect(),
+});
+
+pub fn build_info() -> &'static BuildInfo {
+    &BUILD_INFO
+}
+
+/// The build info as a JSON value, ready to embed in `_meta`.
+pub(crate) fn build_meta() -> Value {
+    serde_json::to_value(build_info()).unwrap_or_default()
+}
+
+/// `GET /internal/version`.
+pub(crate) async fn version_handler() -> Json<&'static BuildInfo> {
+    Json(build_info())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_info_is_populated() {
+        let info = build_info();
+        assert_eq!(info.crate_version, env!("CARGO_PKG_VERSION"));
+        assert!(!info.git_sha.is_empty());
+        assert!(info.rustc_version.starts_with("rustc") || info.rustc_version == "unknown");
+        assert_ne!(info.build_timestamp, "unknown");
+    }
+}
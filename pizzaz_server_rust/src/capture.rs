@@ -0,0 +1,648 @@
+//! Development capture of raw `/mcp` traffic and replay through [`crate::create_app`].
+//!
+//! Set `MCP_CAPTURE_DIR` to record every JSON-RPC POST and its response into a ring buffer of
+//! files (`MCP_CAPTURE_LIMIT`, default 200). Bodies larger than `MCP_CAPTURE_MAX_BODY_BYTES`
+//! (default 1 MiB) are passed through but recorded as truncated, without their content.
+//! Credentials in headers and bodies are redacted before anything touches disk. Sessions and calls that opted out of telemetry (see
+//! [`crate::consent`]) also have their tool arguments and structured results redacted.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use async_stream::stream;
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Request},
+    http::{HeaderMap, HeaderName, HeaderValue, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension, Router,
+};
+use bytes::{Bytes, BytesMut};
+use futures::StreamExt;
+use http_body_util::BodyExt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use time::{format_description::well_known::Iso8601, OffsetDateTime};
+use tower::ServiceExt;
+
+use crate::{consent, error::ApiError, AppState};
+
+const DEFAULT_CAPTURE_LIMIT: u64 = 200;
+const DEFAULT_MAX_BODY_BYTES: usize = 1024 * 1024;
+pub(crate) const REDACTED: &str = "[redacted]";
+const SESSION_ID_HEADER: &str = "mcp-session-id";
+const SENSITIVE_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+    "x-api-key",
+];
+/// JSON field names whose values are redacted, lowercased with `_` and `-` removed. Matched
+/// exactly, so protocol fields such as `progressToken` are kept.
+const SENSITIVE_KEYS: &[&str] = &[
+    "accesstoken",
+    "apikey",
+    "apitoken",
+    "authorization",
+    "authtoken",
+    "bearertoken",
+    "clientsecret",
+    "idtoken",
+    "passphrase",
+    "password",
+    "privatekey",
+    "refreshtoken",
+    "secret",
+    "sessiontoken",
+    "token",
+];
+
+/// One captured request/response pair as stored on disk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CapturedExchange {
+    pub sequence: u64,
+    pub recorded_at: Option<String>,
+    pub request: CapturedMessage,
+    pub response: CapturedMessage,
+}
+
+/// Headers and body of one side of an exchange; `status` is only set on responses.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CapturedMessage {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    pub headers: BTreeMap<String, String>,
+    pub body: Value,
+    /// Set when the body exceeded the recorder's cap; `body` is then `null`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+}
+
+/// Writes captured exchanges into a fixed number of slot files, overwriting the oldest.
+#[derive(Debug)]
+pub struct CaptureRecorder {
+    dir: PathBuf,
+    capacity: u64,
+    max_body_bytes: usize,
+    next_sequence: AtomicU64,
+}
+
+impl CaptureRecorder {
+    /// Creates the capture directory and continues numbering after any existing captures.
+    pub fn new(dir: impl Into<PathBuf>, capacity: u64) -> io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        let next_sequence = load_captures(&dir)?
+            .last()
+            .map(|exchange| exchange.sequence + 1)
+            .unwrap_or(0);
+
+        Ok(Self {
+            dir,
+            capacity: capacity.max(1),
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+            next_sequence: AtomicU64::new(next_sequence),
+        })
+    }
+
+    /// Largest request or response body recorded; larger ones are stored as truncated.
+    pub fn with_max_body_bytes(mut self, max_body_bytes: usize) -> Self {
+        self.max_body_bytes = max_body_bytes;
+        self
+    }
+
+    pub(crate) fn from_env() -> Option<Arc<Self>> {
+        let dir = std::env::var("MCP_CAPTURE_DIR")
+            .ok()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())?;
+        let capacity = std::env::var("MCP_CAPTURE_LIMIT")
+            .ok()
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .filter(|value| *value > 0)
+            .unwrap_or(DEFAULT_CAPTURE_LIMIT);
+        let max_body_bytes = match std::env::var("MCP_CAPTURE_MAX_BODY_BYTES") {
+            Ok(raw) => raw.trim().parse::<usize>().unwrap_or_else(|_| {
+                tracing::warn!(
+                    value = %raw,
+                    "Ignoring invalid MCP_CAPTURE_MAX_BODY_BYTES; using {DEFAULT_MAX_BODY_BYTES}"
+                );
+                DEFAULT_MAX_BODY_BYTES
+            }),
+            Err(_) => DEFAULT_MAX_BODY_BYTES,
+        };
+
+        match Self::new(&dir, capacity) {
+            Ok(recorder) => Some(Arc::new(recorder.with_max_body_bytes(max_body_bytes))),
+            Err(error) => {
+                tracing::error!(dir = %dir, error = %error, "Unable to enable MCP capture");
+                None
+            }
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Number of slot files kept before the oldest is overwritten.
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    pub fn max_body_bytes(&self) -> usize {
+        self.max_body_bytes
+    }
+
+    fn slot_path(&self, sequence: u64) -> PathBuf {
+        self.dir
+            .join(format!("capture-{:04}.json", sequence % self.capacity))
+    }
+
+    async fn write(&self, exchange: &CapturedExchange) {
+        let path = self.slot_path(exchange.sequence);
+        let result = match serde_json::to_vec_pretty(exchange) {
+            Ok(bytes) => tokio::fs::write(&path, bytes).await,
+            Err(error) => Err(io::Error::other(error)),
+        };
+        if let Err(error) = result {
+            tracing::warn!(path = %path.display(), error = %error, "Failed to write MCP capture");
+        }
+    }
+}
+
+/// Reads every capture in `dir`, ordered by sequence number.
+pub fn load_captures(dir: &Path) -> io::Result<Vec<CapturedExchange>> {
+    let mut exchanges = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+        match serde_json::from_slice::<CapturedExchange>(&std::fs::read(&path)?) {
+            Ok(exchange) => exchanges.push(exchange),
+            Err(error) => {
+                tracing::warn!(path = %path.display(), error = %error, "Skipping unreadable capture")
+            }
+        }
+    }
+    exchanges.sort_by_key(|exchange| exchange.sequence);
+    Ok(exchanges)
+}
+
+/// Middleware that records `/mcp` POSTs once their response body has been fully sent.
+pub(crate) async fn capture_mcp(
+    Extension(state): Extension<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(recorder) = state.capture.clone() else {
+        return next.run(request).await;
+    };
+    if request.method() != Method::POST {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let max_body_bytes = recorder.max_body_bytes;
+    let (read, rest) = match read_up_to(body, max_body_bytes).await {
+        Ok(read) => read,
+        Err(error) => {
+            return ApiError::bad_request(
+                "invalid_body",
+                format!("Failed to read request body: {error}"),
+            )
+            .into_response()
+        }
+    };
+    let sequence = recorder.next_sequence.fetch_add(1, Ordering::Relaxed);
+    let request_method = parts.method.to_string();
+    let request_uri = parts.uri.to_string();
+    let request_headers = redact_headers(&parts.headers);
+    let session_id = parts
+        .headers
+        .get(SESSION_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let request_truncated = rest.is_some();
+    let request_body = if request_truncated {
+        Bytes::new()
+    } else {
+        read.clone()
+    };
+    let body = match rest {
+        None => Body::from(read),
+        // Hand the handler the whole body: what was read, then the unread rest.
+        Some(rest) => Body::from_stream(
+            futures::stream::once(async move { Ok(read) }).chain(rest.into_data_stream()),
+        ),
+    };
+
+    let response = next.run(Request::from_parts(parts, body)).await;
+    let (parts, body) = response.into_parts();
+    let status = parts.status.as_u16();
+    let headers = redact_headers(&parts.headers);
+
+    // Tee the body so streamed SSE responses reach the client unchanged while being recorded.
+    let mut data = body.into_data_stream();
+    let teed = stream! {
+        let mut captured = Vec::new();
+        let mut response_truncated = false;
+        while let Some(chunk) = data.next().await {
+            if let Ok(bytes) = &chunk {
+                if captured.len() + bytes.len() > max_body_bytes {
+                    response_truncated = true;
+                    captured = Vec::new();
+                } else if !response_truncated {
+                    captured.extend_from_slice(bytes);
+                }
+            }
+            yield chunk;
+        }
+
+        // Checked once the handler has run, so a session it just saw opting out is known. A
+        // truncated request cannot be checked, so its tool data is treated as opted out.
+        let tool_data = request_truncated
+            || opts_out(&request_body)
+            || session_id
+                .as_deref()
+                .is_some_and(|session_id| state.consent.is_opted_out(session_id));
+        let exchange = CapturedExchange {
+            sequence,
+            recorded_at: OffsetDateTime::now_utc().format(&Iso8601::DEFAULT).ok(),
+            request: CapturedMessage {
+                method: Some(request_method),
+                uri: Some(request_uri),
+                status: None,
+                headers: request_headers,
+                body: redact_body(&request_body, tool_data),
+                truncated: request_truncated,
+            },
+            response: CapturedMessage {
+                method: None,
+                uri: None,
+                status: Some(status),
+                headers,
+                body: redact_body(&captured, tool_data),
+                truncated: response_truncated,
+            },
+        };
+        recorder.write(&exchange).await;
+    };
+
+    Response::from_parts(parts, Body::from_stream(teed))
+}
+
+/// Reads `body` until it ends or has yielded more than `limit` bytes. Returns what was read and,
+/// when the limit was passed, the unread rest.
+async fn read_up_to(mut body: Body, limit: usize) -> Result<(Bytes, Option<Body>), axum::Error> {
+    let mut read = BytesMut::new();
+    while let Some(frame) = body.frame().await {
+        if let Ok(data) = frame?.into_data() {
+            read.extend_from_slice(&data);
+            if read.len() > limit {
+                return Ok((read.freeze(), Some(body)));
+            }
+        }
+    }
+    Ok((read.freeze(), None))
+}
+
+fn redact_headers(headers: &HeaderMap) -> BTreeMap<String, String> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if SENSITIVE_HEADERS.contains(&name.as_str()) {
+                REDACTED.to_string()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).into_owned()
+            };
+            (name.as_str().to_string(), value)
+        })
+        .collect()
+}
+
+/// Whether a JSON-RPC request body (or any message of a batch) opts out of telemetry.
+fn opts_out(body: &[u8]) -> bool {
+    let Ok(json) = serde_json::from_slice::<Value>(body) else {
+        return false;
+    };
+    let messages = match &json {
+        Value::Array(messages) => messages.as_slice(),
+        message => std::slice::from_ref(message),
+    };
+    messages.iter().any(|message| {
+        message["params"]["_meta"]
+            .as_object()
+            .is_some_and(consent::opts_out)
+    })
+}
+
+/// Stores JSON bodies as JSON and anything else (such as SSE) as text, redacting credentials and,
+/// with `tool_data`, tool arguments and structured results.
+fn redact_body(bytes: &[u8], tool_data: bool) -> Value {
+    if bytes.is_empty() {
+        return Value::Null;
+    }
+    let redact = |json: &mut Value| {
+        redact_json(json);
+        if tool_data {
+            redact_tool_data(json);
+        }
+    };
+    if let Ok(mut json) = serde_json::from_slice::<Value>(bytes) {
+        redact(&mut json);
+        return json;
+    }
+
+    let text = String::from_utf8_lossy(bytes);
+    let lines: Vec<String> = text
+        .split('\n')
+        .map(|line| {
+            let Some(data) = line.strip_prefix("data:") else {
+                return line.to_string();
+            };
+            match serde_json::from_str::<Value>(data.trim()) {
+                Ok(mut json) => {
+                    redact(&mut json);
+                    format!("data: {json}")
+                }
+                Err(_) => line.to_string(),
+            }
+        })
+        .collect();
+    Value::String(lines.join("\n"))
+}
+
+fn redact_json(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, entry) in map.iter_mut() {
+                let key = key.to_ascii_lowercase().replace(['_', '-'], "");
+                if SENSITIVE_KEYS.contains(&key.as_str()) {
+                    *entry = Value::String(REDACTED.to_string());
+                } else {
+                    redact_json(entry);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_json),
+        _ => {}
+    }
+}
+
+/// Redacts `params.arguments` and `result.structuredContent` of each JSON-RPC message.
+fn redact_tool_data(value: &mut Value) {
+    if let Value::Array(messages) = value {
+        messages.iter_mut().for_each(redact_tool_data);
+        return;
+    }
+    for (outer, inner) in [("params", "arguments"), ("result", "structuredContent")] {
+        if let Some(entry) = value.get_mut(outer).and_then(|outer| outer.get_mut(inner)) {
+            *entry = Value::String(REDACTED.to_string());
+        }
+    }
+}
+
+/// Outcome of replaying one captured exchange.
+#[derive(Debug, Clone)]
+pub struct ReplayOutcome {
+    pub sequence: u64,
+    pub rpc_method: Option<String>,
+    pub recorded_status: Option<u16>,
+    pub replayed_status: u16,
+    pub body: String,
+}
+
+impl ReplayOutcome {
+    /// True when the replay produced the same HTTP status as the original exchange.
+    pub fn status_matches(&self) -> bool {
+        self.recorded_status == Some(self.replayed_status)
+    }
+}
+
+/// Feeds captured requests back through `app` in order.
+///
+/// Session ids handed out during the capture are mapped onto the ones the replayed server
+/// issues, and redacted `Authorization` headers are replaced with `bearer_token` when given.
+/// Requests recorded as truncated are skipped, since their bodies were not kept.
+pub async fn replay(
+    app: Router,
+    exchanges: &[CapturedExchange],
+    bearer_token: Option<&str>,
+) -> Vec<ReplayOutcome> {
+    let mut sessions: HashMap<String, String> = HashMap::new();
+    let mut outcomes = Vec::with_capacity(exchanges.len());
+
+    for exchange in exchanges {
+        if exchange.request.truncated {
+            tracing::warn!(
+                sequence = exchange.sequence,
+                "Skipping capture whose request body was truncated"
+            );
+            continue;
+        }
+        let request = build_replay_request(&exchange.request, &sessions, bearer_token);
+        let response = match app.clone().oneshot(request).await {
+            Ok(response) => response,
+            Err(never) => match never {},
+        };
+
+        let replayed_session = response
+            .headers()
+            .get(SESSION_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        if let (Some(recorded), Some(replayed)) = (
+            exchange.response.headers.get(SESSION_ID_HEADER),
+            replayed_session,
+        ) {
+            sessions.insert(recorded.clone(), replayed);
+        }
+
+        let replayed_status = response.status().as_u16();
+        let body = match response.into_body().collect().await {
+            Ok(collected) => String::from_utf8_lossy(&collected.to_bytes()).into_owned(),
+            Err(error) => format!("<failed to read body: {error}>"),
+        };
+
+        outcomes.push(ReplayOutcome {
+            sequence: exchange.sequence,
+            rpc_method: exchange
+                .request
+                .body
+                .get("method")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            recorded_status: exchange.response.status,
+            replayed_status,
+            body,
+        });
+    }
+
+    outcomes
+}
+
+fn build_replay_request(
+    captured: &CapturedMessage,
+    sessions: &HashMap<String, String>,
+    bearer_token: Option<&str>,
+) -> Request {
+    let method = captured
+        .method
+        .as_deref()
+        .and_then(|method| Method::from_bytes(method.as_bytes()).ok())
+        .unwrap_or(Method::POST);
+    let uri = captured.uri.as_deref().unwrap_or("/mcp");
+    let body = match &captured.body {
+        Value::Null => Body::empty(),
+        Value::String(text) => Body::from(text.clone()),
+        json => Body::from(json.to_string()),
+    };
+
+    let mut request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .body(body)
+        .expect("captured method and uri are valid");
+
+    let headers = request.headers_mut();
+    for (name, value) in &captured.headers {
+        if value == REDACTED || name == "content-length" {
+            continue;
+        }
+        let value = match name.as_str() {
+            SESSION_ID_HEADER => sessions.get(value).unwrap_or(value),
+            _ => value,
+        };
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            headers.insert(name, value);
+        }
+    }
+    if let Some(token) = bearer_token {
+        if let Ok(value) = HeaderValue::from_str(&format!("Bearer {token}")) {
+            headers.insert(axum::http::header::AUTHORIZATION, value);
+        }
+    }
+
+    request
+        .extensions_mut()
+        .insert(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 0))));
+    request
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redaction_covers_headers_and_nested_json() {
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", HeaderValue::from_static("Bearer abc"));
+        headers.insert("accept", HeaderValue::from_static("application/json"));
+        let redacted = redact_headers(&headers);
+        assert_eq!(redacted["authorization"], REDACTED);
+        assert_eq!(redacted["accept"], "application/json");
+
+        let body =
+            br#"{"params":{"arguments":{"apiKey":"k","refresh_token":"t","topping":"basil"},"_meta":{"progressToken":7}}}"#;
+        let value = redact_body(body, false);
+        let arguments = &value["params"]["arguments"];
+        assert_eq!(arguments["apiKey"], REDACTED);
+        assert_eq!(arguments["refresh_token"], REDACTED);
+        assert_eq!(arguments["topping"], "basil");
+        assert_eq!(value["params"]["_meta"]["progressToken"], 7);
+
+        let sse = redact_body(b"event: message\ndata: {\"password\":\"p\"}\n\n", false);
+        assert!(!sse.as_str().unwrap().contains("\"p\""));
+    }
+
+    #[test]
+    fn telemetry_opt_out_redacts_tool_data() {
+        let request = br#"{"method":"tools/call","params":{"name":"pizza-map","arguments":{"pizzaTopping":"basil"},"_meta":{"pizzaz/telemetry":false}}}"#;
+        assert!(opts_out(request));
+        assert!(!opts_out(
+            br#"{"method":"tools/call","params":{"_meta":{}}}"#
+        ));
+        let value = redact_body(request, true);
+        assert_eq!(value["params"]["arguments"], REDACTED);
+        assert_eq!(value["params"]["name"], "pizza-map");
+
+        let sse = redact_body(
+            b"data: {\"result\":{\"structuredContent\":{\"pizzaTopping\":\"basil\"}}}\n\n",
+            true,
+        );
+        assert!(!sse.as_str().unwrap().contains("basil"));
+    }
+
+    #[tokio::test]
+    async fn oversized_bodies_are_read_only_up_to_the_limit() {
+        let chunks = ["{\"a\":", "1,\"b\":", "2}"].map(Ok::<_, io::Error>);
+        let (read, rest) = read_up_to(Body::from_stream(futures::stream::iter(chunks)), 8)
+            .await
+            .unwrap();
+        assert_eq!(&read[..], b"{\"a\":1,\"b\":");
+        let rest = rest.expect("the limit was passed").collect().await.unwrap();
+        assert_eq!(&rest.to_bytes()[..], b"2}");
+
+        let (read, rest) = read_up_to(Body::from("{}"), 8).await.unwrap();
+        assert_eq!(&read[..], b"{}");
+        assert!(rest.is_none());
+    }
+
+    #[tokio::test]
+    async fn recorder_overwrites_oldest_slot() {
+        let dir = tempfile::tempdir().unwrap();
+        let recorder = CaptureRecorder::new(dir.path(), 2).unwrap();
+        for sequence in 0..3 {
+            let message = CapturedMessage {
+                method: None,
+                uri: None,
+                status: Some(200),
+                headers: BTreeMap::new(),
+                body: Value::Null,
+                truncated: false,
+            };
+            let exchange = CapturedExchange {
+                sequence,
+                recorded_at: None,
+                request: message.clone(),
+                response: message,
+            };
+            recorder.write(&exchange).await;
+        }
+
+        let sequences: Vec<u64> = load_captures(dir.path())
+            .unwrap()
+            .iter()
+            .map(|exchange| exchange.sequence)
+            .collect();
+        assert_eq!(sequences, vec![1, 2]);
+        assert_eq!(
+            CaptureRecorder::new(dir.path(), 2)
+                .unwrap()
+                .next_sequence
+                .load(Ordering::Relaxed),
+            3
+        );
+    }
+}
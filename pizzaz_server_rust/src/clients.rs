@@ -0,0 +1,273 @@
+//! Client detection from `initialize`, and the adaptations made for older clients.
+//!
+//! rmcp keeps each session's `initialize` parameters on its peer. From them the handler derives a
+//! [`ClientProfile`] saying which parts of the protocol the client understands, based on the
+//! protocol version it asked for:
+//!
+//! - `structuredContent` (2025-06-18): older clients also get it as a JSON text block.
+//! - `resource_link` content (2025-06-18) and `audio` content (2025-03-26): downgraded to text.
+//!
+//! Clients whose behaviour does not match their protocol version can be corrected by name with
+//! `WIDGETS_CLIENT_PROFILES`, e.g. `{"legacy-desktop": {"resourceTemplates": false}}`. The same
+//! overrides can opt a client out of telemetry with `"telemetry": false` (see [`crate::consent`]).
+
+use std::{collections::HashMap, sync::Arc};
+
+use rmcp::{
+    model::{CallToolResult, ClientInfo, Content, RawContent},
+    service::{Peer, RoleServer},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::consent;
+
+const STRUCTURED_CONTENT_SINCE: &str = "2025-06-18";
+const RESOURCE_LINKS_SINCE: &str = "2025-06-18";
+const AUDIO_SINCE: &str = "2025-03-26";
+
+/// What a connected client is and which protocol features it handles.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientProfile {
+    pub name: String,
+    pub version: String,
+    pub protocol_version: String,
+    pub resource_templates: bool,
+    pub structured_content: bool,
+    pub resource_links: bool,
+    pub audio: bool,
+    /// False when the client opted out of telemetry.
+    pub telemetry: bool,
+}
+
+/// Per-client corrections to the features detected from the protocol version.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ClientOverride {
+    pub resource_templates: Option<bool>,
+    pub structured_content: Option<bool>,
+    pub resource_links: Option<bool>,
+    pub audio: Option<bool>,
+    pub telemetry: Option<bool>,
+}
+
+impl ClientProfile {
+    /// Features implied by the protocol version `info` asked for, before overrides.
+    pub fn detect(info: &ClientInfo) -> Self {
+        // Protocol versions are ISO dates, so they order as strings.
+        let protocol_version = info.protocol_version.to_string();
+        let since = |version: &str| protocol_version.as_str() >= version;
+        Self {
+            name: info.client_info.name.clone(),
+            version: info.client_info.version.clone(),
+            resource_templates: true,
+            structured_content: since(STRUCTURED_CONTENT_SINCE),
+            resource_links: since(RESOURCE_LINKS_SINCE),
+            audio: since(AUDIO_SINCE),
+            telemetry: !consent::declared_opt_out(info),
+            protocol_version,
+        }
+    }
+
+    fn apply_override(mut self, corrections: &ClientOverride) -> Self {
+        let ClientOverride {
+            resource_templates,
+            structured_content,
+            resource_links,
+            audio,
+            telemetry,
+        } = corrections;
+        self.resource_templates = resource_templates.unwrap_or(self.resource_templates);
+        self.structured_content = structured_content.unwrap_or(self.structured_content);
+        self.resource_links = resource_links.unwrap_or(self.resource_links);
+        self.audio = audio.unwrap_or(self.audio);
+        // Configuration can opt a client out, but not back in over its own signal.
+        self.telemetry &= telemetry.unwrap_or(true);
+        self
+    }
+
+    /// `name/version`, for logs and spans.
+    pub fn label(&self) -> String {
+        format!("{}/{}", self.name, self.version)
+    }
+
+    /// Rewrites `result` into a form this client can display.
+    pub fn adapt(&self, result: &mut CallToolResult) {
+        for content in &mut result.content {
+            if let Some(text) = self.downgrade(&content.raw) {
+                *content = Content::text(text);
+            }
+        }
+        if !self.structured_content {
+            if let Some(structured) = &result.structured_content {
+                result.content.push(Content::text(structured.to_string()));
+            }
+        }
+    }
+
+    /// A text stand-in for content the client cannot render.
+    fn downgrade(&self, content: &RawContent) -> Option<String> {
+        match content {
+            RawContent::ResourceLink(link) if !self.resource_links => {
+                Some(format!("{}: {}", link.name, link.uri))
+            }
+            RawContent::Audio(audio) if !self.audio => {
+                Some(format!("[{} audio omitted]", audio.mime_type))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Detects client profiles, applying configured per-client overrides. Clones share them.
+#[derive(Debug, Clone, Default)]
+pub struct ClientProfiles {
+    overrides: Arc<HashMap<String, ClientOverride>>,
+}
+
+impl ClientProfiles {
+    pub fn new(overrides: HashMap<String, ClientOverride>) -> Self {
+        Self {
+            overrides: Arc::new(overrides),
+        }
+    }
+
+    /// Overrides from `WIDGETS_CLIENT_PROFILES`; none when unset or invalid.
+    pub(crate) fn from_env() -> Self {
+        let Ok(json) = std::env::var("WIDGETS_CLIENT_PROFILES") else {
+            return Self::default();
+        };
+        match serde_json::from_str(&json) {
+            Ok(overrides) => Self::new(overrides),
+            Err(error) => {
+                tracing::warn!(error = %error, "Ignoring invalid WIDGETS_CLIENT_PROFILES");
+                Self::default()
+            }
+        }
+    }
+
+    /// Client names with overrides, sorted.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self.overrides.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    pub fn profile(&self, info: &ClientInfo) -> ClientProfile {
+        let profile = ClientProfile::detect(info);
+        match self.overrides.get(&profile.name) {
+            Some(corrections) => profile.apply_override(corrections),
+            None => profile,
+        }
+    }
+
+    /// The profile of the client behind `peer`, once it has initialized.
+    pub fn for_peer(&self, peer: Option<&Peer<RoleServer>>) -> Option<ClientProfile> {
+        peer.and_then(Peer::peer_info)
+            .map(|info| self.profile(info))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rmcp::model::{
+        AnnotateAble, ClientCapabilities, Implementation, ProtocolVersion, RawResource,
+    };
+    use serde_json::json;
+
+    fn client_info(name: &str, protocol_version: ProtocolVersion) -> ClientInfo {
+        ClientInfo {
+            protocol_version,
+            capabilities: ClientCapabilities::default(),
+            client_info: Implementation {
+                name: name.to_string(),
+                title: None,
+                version: "1.2.0".to_string(),
+                icons: None,
+                website_url: None,
+            },
+        }
+    }
+
+    #[test]
+    fn features_follow_protocol_version_and_overrides() {
+        let profiles = ClientProfiles::new(HashMap::from([(
+            "legacy-desktop".to_string(),
+            ClientOverride {
+                resource_templates: Some(false),
+                ..ClientOverride::default()
+            },
+        )]));
+
+        let current = profiles.profile(&client_info("chat", ProtocolVersion::V_2025_06_18));
+        assert!(current.structured_content && current.resource_links && current.audio);
+        assert!(current.resource_templates);
+        assert_eq!(current.label(), "chat/1.2.0");
+
+        let middle = profiles.profile(&client_info("chat", ProtocolVersion::V_2025_03_26));
+        assert!(middle.audio && !middle.structured_content && !middle.resource_links);
+
+        let legacy = profiles.profile(&client_info(
+            "legacy-desktop",
+            ProtocolVersion::V_2024_11_05,
+        ));
+        assert!(!legacy.resource_templates && !legacy.audio);
+    }
+
+    #[test]
+    fn telemetry_opt_out_comes_from_the_client_or_configuration() {
+        let profiles = ClientProfiles::new(HashMap::from([
+            (
+                "private-desktop".to_string(),
+                ClientOverride {
+                    telemetry: Some(false),
+                    ..ClientOverride::default()
+                },
+            ),
+            (
+                "chat".to_string(),
+                ClientOverride {
+                    telemetry: Some(true),
+                    ..ClientOverride::default()
+                },
+            ),
+        ]));
+        let info = client_info("chat", ProtocolVersion::V_2025_06_18);
+        assert!(profiles.profile(&info).telemetry);
+
+        let mut opted_out = info.clone();
+        consent::declare_opt_out(&mut opted_out);
+        assert!(!profiles.profile(&opted_out).telemetry);
+
+        let configured = client_info("private-desktop", ProtocolVersion::V_2025_06_18);
+        assert!(!profiles.profile(&configured).telemetry);
+    }
+
+    #[test]
+    fn older_clients_get_text_stand_ins() {
+        let profile = ClientProfile::detect(&client_info("chat", ProtocolVersion::V_2024_11_05));
+        let mut result = CallToolResult::structured(json!({ "pizzaTopping": "basil" }));
+        result.content = vec![
+            Content::text("Rendered a pizza map!"),
+            RawContent::ResourceLink(RawResource::new("ui://widget/pizza-map.html", "Pizza map"))
+                .no_annotation(),
+        ];
+
+        profile.adapt(&mut result);
+
+        let texts: Vec<_> = result
+            .content
+            .iter()
+            .map(|content| content.as_text().unwrap().text.as_str())
+            .collect();
+        assert_eq!(
+            texts,
+            vec![
+                "Rendered a pizza map!",
+                "Pizza map: ui://widget/pizza-map.html",
+                r#"{"pizzaTopping":"basil"}"#,
+            ]
+        );
+    }
+}
@@ -0,0 +1,408 @@
+//! Effective runtime configuration, as reported by `GET /internal/config`.
+//!
+//! Settings come from the environment (optionally seeded from `.env`) and are parsed by several
+//! modules, each with its own defaults and fallbacks for invalid values. This collects what the
+//! process actually ended up with; secrets are never included, only whether they are set.
+
+use std::{net::SocketAddr, time::Duration};
+
+use axum::{extract::ConnectInfo, http::HeaderMap, Extension, Json};
+use serde::Serialize;
+
+use crate::{
+    augment_memory::AugmentMemoryConfig,
+    auth::AuthConfig,
+    batch::{BatchConfig, MAX_BATCH_CALLS},
+    capture::{CaptureRecorder, REDACTED},
+    connections::ConnectionTuning,
+    cors::CorsConfig,
+    egress::EgressPolicy,
+    env_compat::{self, EnvMigration},
+    error::ApiError,
+    lint::LintSettings,
+    listeners::ListenerConfig,
+    load_shed::LoadShedConfig,
+    rate_limit::RateLimitConfig,
+    refresh_scope::RefreshScopes,
+    scan::ScanPolicy,
+    secrets::SecretsSettings,
+    signing::PublicKey,
+    sse_backpressure::{BackpressureConfig, BackpressurePolicy},
+    webhooks::WebhookConfig,
+    widgets, AppState,
+};
+
+/// Settings fixed when the application was created.
+#[derive(Debug, Clone, Serialize)]
+pub struct EffectiveConfig {
+    pub refresh: RefreshSettings,
+    pub rate_limit_gc_interval_secs: u64,
+    pub auth: AuthSettings,
+    pub cors: CorsSettings,
+    pub load_shed: LoadShedSettings,
+    pub sse_backpressure: BackpressureSettings,
+    /// Bytes all augmented responses may buffer at once; `None` is uncapped.
+    pub augment_max_buffered_bytes: Option<u64>,
+    pub capture: Option<CaptureSettings>,
+    pub preload: Option<PreloadSettings>,
+    /// Replaced registries kept for rollback.
+    pub registry_history_depth: usize,
+    /// Caps on widgets and HTML bytes per manifest load.
+    pub registry_limits: widgets::RegistryLimits,
+    /// How loads treat widgets that reuse an id or template URI.
+    pub duplicate_policy: widgets::DuplicatePolicy,
+    /// Replaces `ui://widget/` in the template URIs clients see.
+    pub template_uri_prefix: Option<&'static str>,
+    pub manifest_watch: Option<ManifestWatchSettings>,
+    pub scheduled_refresh: Option<ScheduledRefreshSettings>,
+    pub dev_endpoints: bool,
+    pub strict_conformance: bool,
+    pub prefetch_hints: bool,
+    pub lenient_arguments: bool,
+    pub trace_meta: bool,
+    pub feature_flags: bool,
+    pub experiments: Vec<String>,
+    /// Client names with feature overrides.
+    pub client_profiles: Vec<String>,
+    pub batch: Option<BatchSettings>,
+    pub defer_after_ms: Option<u128>,
+    pub webhooks: Option<WebhookSettings>,
+    /// Subjects allowed to submit and approve manifest publishes.
+    pub publishers: Vec<String>,
+    pub content_scan: ScanPolicy,
+    pub template_lint: LintSettings,
+    /// Public half of the key widget HTML is signed with; the private key is never included.
+    pub signing_key: Option<PublicKey>,
+    /// Hosts, timeout and response cap for executor HTTP calls.
+    pub egress: EgressPolicy,
+    /// Where executors read integration secrets from; values are never included.
+    pub secrets: SecretsSettings,
+    /// Deprecated variable names found at startup.
+    pub legacy_env: Vec<EnvMigration>,
+    /// Addresses served, when `PORT`, `MCP_LISTEN` and `MCP_ADMIN_LISTEN` are valid.
+    pub listeners: Option<ListenerConfig>,
+    /// HTTP/2 limits and the per-connection request cap, when their variables are valid.
+    pub connections: Option<ConnectionTuning>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookSettings {
+    /// Endpoint count only; webhook URLs often embed credentials.
+    pub endpoints: usize,
+    pub secret: Option<&'static str>,
+    pub max_attempts: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RefreshSettings {
+    pub token: Option<&'static str>,
+    /// Scoped refresh tokens, with the tokens left out.
+    pub scopes: Vec<RefreshScopeSettings>,
+    pub rate_limit: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RefreshScopeSettings {
+    pub name: String,
+    pub manifests: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthSettings {
+    pub enabled: bool,
+    /// One entry per configured token, with the token itself left out.
+    pub principals: Vec<Principal>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Principal {
+    pub subject: Option<String>,
+    pub roles: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CorsSettings {
+    pub allowed_origins: Vec<String>,
+    pub origin_patterns: Vec<String>,
+    pub custom_validator: bool,
+    pub allow_credentials: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LoadShedSettings {
+    pub max_in_flight: usize,
+    pub max_loop_lag_ms: u128,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BackpressureSettings {
+    pub queue_limit: usize,
+    pub policy: &'static str,
+    /// `None` when stalled clients are never cancelled.
+    pub stall_timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptureSettings {
+    pub dir: String,
+    pub limit: u64,
+    pub max_body_bytes: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchSettings {
+    pub concurrency: usize,
+    pub max_calls: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PreloadSettings {
+    pub timeout_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestWatchSettings {
+    pub poll_interval_ms: u128,
+    pub debounce_ms: u128,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScheduledRefreshSettings {
+    pub interval_secs: u64,
+    pub jitter_secs: u64,
+}
+
+/// Borrowed view of every parsed configuration, used to build an [`EffectiveConfig`].
+pub(crate) struct ConfigSources<'a> {
+    pub refresh_token_set: bool,
+    pub refresh_scopes: &'a RefreshScopes,
+    pub refresh_rate_limit: RateLimitConfig,
+    pub rate_limit_gc_interval: Duration,
+    pub auth: &'a AuthConfig,
+    pub cors: &'a CorsConfig,
+    pub load_shed: LoadShedConfig,
+    pub backpressure: BackpressureConfig,
+    pub augment_memory: AugmentMemoryConfig,
+    pub capture: Option<&'a CaptureRecorder>,
+    pub manifest_watch: Option<widgets::ManifestWatchConfig>,
+    pub scheduled_refresh: Option<widgets::ScheduledRefreshConfig>,
+    pub dev_endpoints: bool,
+    pub strict_conformance: bool,
+    pub prefetch_hints: bool,
+    pub lenient_arguments: bool,
+    pub trace_meta: bool,
+    pub feature_flags: bool,
+    pub experiments: Vec<String>,
+    pub client_profiles: Vec<String>,
+    pub batch: Option<BatchConfig>,
+    pub defer_after_ms: Option<u128>,
+    pub webhooks: Option<&'a WebhookConfig>,
+    pub publishers: Vec<String>,
+    pub content_scan: ScanPolicy,
+    pub template_lint: LintSettings,
+    pub signing_key: Option<PublicKey>,
+    pub egress: EgressPolicy,
+    pub secrets: SecretsSettings,
+}
+
+impl EffectiveConfig {
+    pub(crate) fn collect(sources: ConfigSources<'_>) -> Self {
+        let rate_limit = sources.refresh_rate_limit;
+        Self {
+            refresh: RefreshSettings {
+                token: sources.refresh_token_set.then_some(REDACTED),
+                scopes: sources
+                    .refresh_scopes
+                    .scopes()
+                    .iter()
+                    .map(|scope| RefreshScopeSettings {
+                        name: scope.name.clone(),
+                        manifests: scope.manifests.clone(),
+                    })
+                    .collect(),
+                rate_limit: format!(
+                    "{}/{}s",
+                    rate_limit.max_requests,
+                    rate_limit.window.as_secs()
+                ),
+            },
+            rate_limit_gc_interval_secs: sources.rate_limit_gc_interval.as_secs(),
+            auth: AuthSettings {
+                enabled: sources.auth.is_enabled(),
+                principals: sources
+                    .auth
+                    .identities()
+                    .map(|identity| Principal {
+                        subject: identity.subject.clone(),
+                        roles: identity.roles.clone(),
+                    })
+                    .collect(),
+            },
+            cors: CorsSettings {
+                allowed_origins: sources.cors.allowed_origins.clone(),
+                origin_patterns: sources
+                    .cors
+                    .origin_patterns
+                    .iter()
+                    .map(|regex| regex.as_str().to_string())
+                    .collect(),
+                custom_validator: sources.cors.validator.is_some(),
+                allow_credentials: sources.cors.allow_credentials,
+            },
+            load_shed: LoadShedSettings {
+                max_in_flight: sources.load_shed.max_in_flight,
+                max_loop_lag_ms: sources.load_shed.max_loop_lag.as_millis(),
+            },
+            sse_backpressure: BackpressureSettings {
+                queue_limit: sources.backpressure.queue_limit,
+                policy: match sources.backpressure.policy {
+                    BackpressurePolicy::DropOldest => "drop-oldest",
+                    BackpressurePolicy::Close => "close",
+                },
+                stall_timeout_secs: sources
+                    .backpressure
+                    .stall_timeout
+                    .map(|timeout| timeout.as_secs()),
+            },
+            augment_max_buffered_bytes: sources.augment_memory.max_buffered_bytes,
+            capture: sources.capture.map(|recorder| CaptureSettings {
+                dir: recorder.dir().display().to_string(),
+                limit: recorder.capacity(),
+                max_body_bytes: recorder.max_body_bytes(),
+            }),
+            preload: widgets::PreloadConfig::from_env().map(|preload| PreloadSettings {
+                timeout_secs: preload.timeout.as_secs(),
+            }),
+            registry_history_depth: widgets::registry_history_depth(),
+            registry_limits: widgets::RegistryLimits::from_env(),
+            duplicate_policy: widgets::DuplicatePolicy::from_env(),
+            template_uri_prefix: widgets::template_uri_prefix(),
+            manifest_watch: sources.manifest_watch.map(|watch| ManifestWatchSettings {
+                poll_interval_ms: watch.poll_interval.as_millis(),
+                debounce_ms: watch.debounce.as_millis(),
+            }),
+            scheduled_refresh: sources
+                .scheduled_refresh
+                .map(|schedule| ScheduledRefreshSettings {
+                    interval_secs: schedule.interval.as_secs(),
+                    jitter_secs: schedule.jitter.as_secs(),
+                }),
+            dev_endpoints: sources.dev_endpoints,
+            strict_conformance: sources.strict_conformance,
+            prefetch_hints: sources.prefetch_hints,
+            lenient_arguments: sources.lenient_arguments,
+            trace_meta: sources.trace_meta,
+            feature_flags: sources.feature_flags,
+            experiments: sources.experiments,
+            client_profiles: sources.client_profiles,
+            batch: sources.batch.map(|batch| BatchSettings {
+                concurrency: batch.concurrency,
+                max_calls: MAX_BATCH_CALLS,
+            }),
+            defer_after_ms: sources.defer_after_ms,
+            webhooks: sources.webhooks.map(|webhooks| WebhookSettings {
+                endpoints: webhooks.urls.len(),
+                secret: webhooks.secret.as_ref().map(|_| REDACTED),
+                max_attempts: webhooks.max_attempts,
+            }),
+            publishers: sources.publishers,
+            content_scan: sources.content_scan,
+            template_lint: sources.template_lint,
+            signing_key: sources.signing_key,
+            egress: sources.egress,
+            secrets: sources.secrets,
+            legacy_env: env_compat::applied().to_vec(),
+            listeners: ListenerConfig::from_env().ok(),
+            connections: ConnectionTuning::from_env().ok(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct ConfigResponse {
+    /// The manifest path can be switched at runtime, so it is read when the request arrives.
+    manifest_path: String,
+    /// Also read on every load, from `WIDGETS_MANIFEST_OVERLAY_PATH`.
+    manifest_overlay_path: Option<String>,
+    /// Also read on every load, from `WIDGETS_MANIFEST_CACHE_PATH`.
+    manifest_cache_path: Option<String>,
+    /// Also read on every load, from `WIDGETS_REGISTRY_SNAPSHOT_PATH`.
+    registry_snapshot_path: Option<String>,
+    #[serde(flatten)]
+    config: EffectiveConfig,
+}
+
+/// `GET /internal/config`: the effective configuration, guarded by the operator token.
+pub(crate) async fn config_handler(
+    Extension(state): Extension<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Json<ConfigResponse>, ApiError> {
+    crate::require_operator(&state, addr, &headers)?;
+    Ok(Json(ConfigResponse {
+        manifest_path: widgets::manifest_path().display().to_string(),
+        manifest_overlay_path: widgets::manifest_overlay_path()
+            .map(|path| path.display().to_string()),
+        manifest_cache_path: widgets::manifest_cache_path().map(|path| path.display().to_string()),
+        registry_snapshot_path: crate::snapshot::snapshot_path()
+            .map(|path| path.display().to_string()),
+        config: state.config.as_ref().clone(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collected_config_never_contains_secrets() {
+        let auth = crate::auth::parse_auth_tokens("s3cret=alice:admin");
+        let config = EffectiveConfig::collect(ConfigSources {
+            refresh_token_set: true,
+            refresh_scopes: &RefreshScopes::default(),
+            refresh_rate_limit: RateLimitConfig {
+                max_requests: 10,
+                window: Duration::from_secs(60),
+            },
+            rate_limit_gc_interval: Duration::from_secs(60),
+            auth: &auth,
+            cors: &CorsConfig::default(),
+            load_shed: LoadShedConfig::default(),
+            backpressure: BackpressureConfig::default(),
+            augment_memory: AugmentMemoryConfig::default(),
+            capture: None,
+            manifest_watch: None,
+            scheduled_refresh: None,
+            dev_endpoints: false,
+            strict_conformance: false,
+            prefetch_hints: false,
+            lenient_arguments: false,
+            trace_meta: false,
+            feature_flags: false,
+            experiments: Vec::new(),
+            client_profiles: Vec::new(),
+            batch: None,
+            defer_after_ms: None,
+            webhooks: None,
+            publishers: Vec::new(),
+            content_scan: ScanPolicy::default(),
+            template_lint: LintSettings {
+                policy: ScanPolicy::default(),
+                max_html_bytes: crate::lint::DEFAULT_MAX_HTML_BYTES,
+            },
+            signing_key: None,
+            egress: EgressPolicy::default(),
+            secrets: SecretsSettings {
+                provider: "env",
+                ttl_secs: 300,
+            },
+        });
+
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(!json.contains("s3cret"));
+        assert_eq!(config.refresh.token, Some(REDACTED));
+        assert_eq!(config.refresh.rate_limit, "10/60s");
+        assert_eq!(config.auth.principals[0].subject.as_deref(), Some("alice"));
+        assert_eq!(config.sse_backpressure.policy, "drop-oldest");
+    }
+}
@@ -0,0 +1,451 @@
+//! Strict MCP conformance checks on outgoing messages.
+//!
+//! With `WIDGETS_STRICT_CONFORMANCE=1`, every JSON-RPC message leaving `/mcp` is checked against
+//! the parts of the MCP schema clients depend on, after widget metadata has been added. Violations
+//! are logged under the `pizzaz::conformance` target; the message is still sent unchanged. This is
+//! meant for development and CI, where it catches drift between rmcp and the spec (or mistakes in
+//! our own response rewriting) before a client does.
+//!
+//! Results do not say which request they answer, so result checks are keyed on the fields that
+//! identify each result type (`tools`, `content`, `contents`, ...).
+
+use std::fmt;
+
+use serde_json::{Map as JsonMap, Value as JsonValue};
+
+/// A schema violation at a JSON path within a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// Location such as `$.result.tools[2].inputSchema`.
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Logs every violation in an outgoing JSON-RPC `message`.
+pub(crate) fn report(message: &JsonValue) {
+    let violations = validate(message);
+    if violations.is_empty() {
+        return;
+    }
+    let id = message.get("id").map(JsonValue::to_string);
+    for violation in &violations {
+        tracing::warn!(
+            target: "pizzaz::conformance",
+            id = id.as_deref(),
+            path = %violation.path,
+            "Outgoing MCP message violates the schema: {}",
+            violation.message
+        );
+    }
+}
+
+/// Checks each JSON `data:` line of an SSE event, as [`report`] does.
+pub(crate) fn report_sse_event(event: &str) {
+    for line in event.split('\n') {
+        if let Some(data) = line.strip_prefix("data:").map(str::trim_start) {
+            if let Ok(message) = serde_json::from_str::<JsonValue>(data) {
+                report(&message);
+            }
+        }
+    }
+}
+
+/// Every violation in a JSON-RPC message; empty when it conforms.
+pub fn validate(message: &JsonValue) -> Vec<Violation> {
+    let mut checker = Checker::default();
+    checker.message(message);
+    checker.violations
+}
+
+#[derive(Default)]
+struct Checker {
+    violations: Vec<Violation>,
+}
+
+impl Checker {
+    fn violation(&mut self, path: &str, message: impl Into<String>) {
+        self.violations.push(Violation {
+            path: path.to_string(),
+            message: message.into(),
+        });
+    }
+
+    fn object<'a>(
+        &mut self,
+        value: &'a JsonValue,
+        path: &str,
+    ) -> Option<&'a JsonMap<String, JsonValue>> {
+        let object = value.as_object();
+        if object.is_none() {
+            self.violation(path, "expected an object");
+        }
+        object
+    }
+
+    fn required_string(&mut self, object: &JsonMap<String, JsonValue>, key: &str, path: &str) {
+        match object.get(key) {
+            Some(JsonValue::String(_)) => {}
+            Some(_) => self.violation(&format!("{path}.{key}"), "expected a string"),
+            None => self.violation(path, format!("missing required field `{key}`")),
+        }
+    }
+
+    fn optional(
+        &mut self,
+        object: &JsonMap<String, JsonValue>,
+        key: &str,
+        path: &str,
+        expected: &str,
+        matches: fn(&JsonValue) -> bool,
+    ) {
+        if let Some(value) = object.get(key) {
+            if !matches(value) {
+                self.violation(&format!("{path}.{key}"), format!("expected {expected}"));
+            }
+        }
+    }
+
+    /// `_meta`, where present, must be an object.
+    fn meta(&mut self, object: &JsonMap<String, JsonValue>, path: &str) {
+        self.optional(object, "_meta", path, "an object", JsonValue::is_object);
+    }
+
+    fn array<'a>(
+        &mut self,
+        object: &'a JsonMap<String, JsonValue>,
+        key: &str,
+        path: &str,
+    ) -> &'a [JsonValue] {
+        match object.get(key) {
+            Some(JsonValue::Array(items)) => items,
+            Some(_) => {
+                self.violation(&format!("{path}.{key}"), "expected an array");
+                &[]
+            }
+            None => &[],
+        }
+    }
+
+    fn message(&mut self, message: &JsonValue) {
+        let Some(envelope) = self.object(message, "$") else {
+            return;
+        };
+        if envelope.get("jsonrpc").and_then(JsonValue::as_str) != Some("2.0") {
+            self.violation("$.jsonrpc", "must be \"2.0\"");
+        }
+        let has_id = match envelope.get("id") {
+            Some(JsonValue::String(_)) => true,
+            Some(JsonValue::Number(id)) if id.is_i64() || id.is_u64() => true,
+            // Only error responses to unparseable requests may carry a null id.
+            Some(JsonValue::Null) if envelope.contains_key("error") => true,
+            Some(_) => {
+                self.violation("$.id", "must be a string or an integer");
+                true
+            }
+            None => false,
+        };
+
+        match (
+            envelope.get("result"),
+            envelope.get("error"),
+            envelope.get("method"),
+        ) {
+            (Some(_), Some(_), _) => self.violation("$", "has both `result` and `error`"),
+            (Some(result), None, _) => {
+                if !has_id {
+                    self.violation("$", "response is missing `id`");
+                }
+                self.result(result);
+            }
+            (None, Some(error), _) => {
+                if !has_id {
+                    self.violation("$", "response is missing `id`");
+                }
+                self.error(error);
+            }
+            (None, None, Some(JsonValue::String(_))) => {
+                self.optional(envelope, "params", "$", "an object", JsonValue::is_object);
+            }
+            (None, None, Some(_)) => self.violation("$.method", "expected a string"),
+            (None, None, None) => {
+                self.violation("$", "is neither a request, a notification nor a response")
+            }
+        }
+    }
+
+    fn error(&mut self, error: &JsonValue) {
+        let Some(error) = self.object(error, "$.error") else {
+            return;
+        };
+        if !error.get("code").is_some_and(|code| code.is_i64()) {
+            self.violation("$.error.code", "expected an integer");
+        }
+        self.required_string(error, "message", "$.error");
+    }
+
+    fn result(&mut self, result: &JsonValue) {
+        let path = "$.result";
+        let Some(result) = self.object(result, path) else {
+            return;
+        };
+        self.meta(result, path);
+        self.optional(result, "nextCursor", path, "a string", JsonValue::is_string);
+
+        if result.contains_key("serverInfo") {
+            self.initialize_result(result);
+        }
+        if result.contains_key("tools") {
+            for (index, tool) in self.array(result, "tools", path).iter().enumerate() {
+                self.tool(tool, &format!("{path}.tools[{index}]"));
+            }
+        }
+        if result.contains_key("content") {
+            self.call_tool_result(result);
+        }
+        if result.contains_key("resources") {
+            for (index, resource) in self.array(result, "resources", path).iter().enumerate() {
+                let path = format!("{path}.resources[{index}]");
+                if let Some(resource) = self.object(resource, &path) {
+                    self.resource(resource, &path);
+                }
+            }
+        }
+        if result.contains_key("resourceTemplates") {
+            let templates = self.array(result, "resourceTemplates", path);
+            for (index, template) in templates.iter().enumerate() {
+                let path = format!("{path}.resourceTemplates[{index}]");
+                if let Some(template) = self.object(template, &path) {
+                    self.required_string(template, "uriTemplate", &path);
+                    self.required_string(template, "name", &path);
+                    self.meta(template, &path);
+                }
+            }
+        }
+        if result.contains_key("contents") {
+            for (index, contents) in self.array(result, "contents", path).iter().enumerate() {
+                self.resource_contents(contents, &format!("{path}.contents[{index}]"));
+            }
+        }
+        if result.contains_key("prompts") {
+            for (index, prompt) in self.array(result, "prompts", path).iter().enumerate() {
+                let path = format!("{path}.prompts[{index}]");
+                if let Some(prompt) = self.object(prompt, &path) {
+                    self.required_string(prompt, "name", &path);
+                }
+            }
+        }
+        if result.contains_key("messages") {
+            for (index, message) in self.array(result, "messages", path).iter().enumerate() {
+                let path = format!("{path}.messages[{index}]");
+                if let Some(message) = self.object(message, &path) {
+                    match message.get("role").and_then(JsonValue::as_str) {
+                        Some("user" | "assistant") => {}
+                        _ => {
+                            self.violation(&format!("{path}.role"), "must be `user` or `assistant`")
+                        }
+                    }
+                    match message.get("content") {
+                        Some(content) => self.content_block(content, &format!("{path}.content")),
+                        None => self.violation(&path, "missing required field `content`"),
+                    }
+                }
+            }
+        }
+        if let Some(completion) = result.get("completion") {
+            self.completion(completion);
+        }
+    }
+
+    fn initialize_result(&mut self, result: &JsonMap<String, JsonValue>) {
+        let path = "$.result";
+        self.required_string(result, "protocolVersion", path);
+        match result.get("capabilities") {
+            Some(capabilities) => {
+                self.object(capabilities, "$.result.capabilities");
+            }
+            None => self.violation(path, "missing required field `capabilities`"),
+        }
+        if let Some(server_info) = self.object(&result["serverInfo"], "$.result.serverInfo") {
+            self.required_string(server_info, "name", "$.result.serverInfo");
+            self.required_string(server_info, "version", "$.result.serverInfo");
+        }
+    }
+
+    fn tool(&mut self, tool: &JsonValue, path: &str) {
+        let Some(tool) = self.object(tool, path) else {
+            return;
+        };
+        self.required_string(tool, "name", path);
+        self.meta(tool, path);
+        self.optional(tool, "annotations", path, "an object", JsonValue::is_object);
+        for key in ["inputSchema", "outputSchema"] {
+            let Some(schema) = tool.get(key) else {
+                if key == "inputSchema" {
+                    self.violation(path, "missing required field `inputSchema`");
+                }
+                continue;
+            };
+            let schema_path = format!("{path}.{key}");
+            if let Some(schema) = self.object(schema, &schema_path) {
+                if schema.get("type").and_then(JsonValue::as_str) != Some("object") {
+                    self.violation(&format!("{schema_path}.type"), "must be \"object\"");
+                }
+            }
+        }
+    }
+
+    fn call_tool_result(&mut self, result: &JsonMap<String, JsonValue>) {
+        let path = "$.result";
+        for (index, block) in self.array(result, "content", path).iter().enumerate() {
+            self.content_block(block, &format!("{path}.content[{index}]"));
+        }
+        self.optional(
+            result,
+            "structuredContent",
+            path,
+            "an object",
+            JsonValue::is_object,
+        );
+        self.optional(result, "isError", path, "a boolean", JsonValue::is_boolean);
+    }
+
+    fn content_block(&mut self, block: &JsonValue, path: &str) {
+        let Some(block) = self.object(block, path) else {
+            return;
+        };
+        self.meta(block, path);
+        match block.get("type").and_then(JsonValue::as_str) {
+            Some("text") => self.required_string(block, "text", path),
+            Some("image" | "audio") => {
+                self.required_string(block, "data", path);
+                self.required_string(block, "mimeType", path);
+            }
+            Some("resource") => match block.get("resource") {
+                Some(resource) => self.resource_contents(resource, &format!("{path}.resource")),
+                None => self.violation(path, "missing required field `resource`"),
+            },
+            Some("resource_link") => self.resource(block, path),
+            Some(other) => self.violation(
+                &format!("{path}.type"),
+                format!("unknown content type `{other}`"),
+            ),
+            None => self.violation(path, "missing required field `type`"),
+        }
+    }
+
+    fn resource(&mut self, resource: &JsonMap<String, JsonValue>, path: &str) {
+        self.required_string(resource, "uri", path);
+        self.required_string(resource, "name", path);
+        self.optional(resource, "mimeType", path, "a string", JsonValue::is_string);
+        self.meta(resource, path);
+    }
+
+    fn resource_contents(&mut self, contents: &JsonValue, path: &str) {
+        let Some(contents) = self.object(contents, path) else {
+            return;
+        };
+        self.required_string(contents, "uri", path);
+        self.meta(contents, path);
+        match (contents.get("text"), contents.get("blob")) {
+            (Some(JsonValue::String(_)), None) | (None, Some(JsonValue::String(_))) => {}
+            (Some(_), Some(_)) => self.violation(path, "has both `text` and `blob`"),
+            (None, None) => self.violation(path, "needs a `text` or `blob` string"),
+            _ => self.violation(path, "`text` and `blob` must be strings"),
+        }
+    }
+
+    fn completion(&mut self, completion: &JsonValue) {
+        let path = "$.result.completion";
+        let Some(completion) = self.object(completion, path) else {
+            return;
+        };
+        match completion.get("values").and_then(JsonValue::as_array) {
+            Some(values) if values.len() > 100 => {
+                self.violation(&format!("{path}.values"), "must not exceed 100 items")
+            }
+            Some(values) if !values.iter().all(JsonValue::is_string) => {
+                self.violation(&format!("{path}.values"), "must contain only strings")
+            }
+            Some(_) => {}
+            None => self.violation(path, "missing required array `values`"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn paths(message: JsonValue) -> Vec<String> {
+        validate(&message).into_iter().map(|v| v.path).collect()
+    }
+
+    #[test]
+    fn well_formed_messages_pass() {
+        assert!(validate(&json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": {
+                "tools": [{
+                    "name": "pizza-map",
+                    "inputSchema": { "type": "object" },
+                    "_meta": { "openai/outputTemplate": "ui://widget/pizza-map.html" }
+                }]
+            }
+        }))
+        .is_empty());
+        assert!(validate(&json!({
+            "jsonrpc": "2.0",
+            "id": "a",
+            "result": {
+                "content": [{ "type": "text", "text": "Rendered a pizza map!" }],
+                "structuredContent": { "pizzaTopping": "basil" },
+                "isError": false
+            }
+        }))
+        .is_empty());
+        assert!(validate(&json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/message",
+            "params": { "level": "info", "data": {} }
+        }))
+        .is_empty());
+    }
+
+    #[test]
+    fn violations_name_the_offending_field() {
+        assert_eq!(
+            paths(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": {
+                    "content": [{ "type": "text" }, { "type": "video", "url": "x" }],
+                    "structuredContent": ["not", "an", "object"]
+                }
+            })),
+            vec![
+                "$.result.content[0]",
+                "$.result.content[1].type",
+                "$.result.structuredContent",
+            ]
+        );
+        assert_eq!(
+            paths(json!({
+                "jsonrpc": "1.0",
+                "result": { "tools": [{ "name": "x", "inputSchema": { "type": "string" } }] }
+            })),
+            vec!["$.jsonrpc", "$", "$.result.tools[0].inputSchema.type"]
+        );
+        assert_eq!(
+            paths(json!({ "jsonrpc": "2.0", "id": 3, "error": { "code": "oops" } })),
+            vec!["$.error.code", "$.error"]
+        );
+    }
+}
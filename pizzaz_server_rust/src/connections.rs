@@ -0,0 +1,236 @@
+//! Connection handling for the listeners.
+//!
+//! Each accepted connection speaks HTTP/1.1 or HTTP/2; HTTP/2 is detected from the client's
+//! connection preface, so gateways can use cleartext HTTP/2 with prior knowledge and need no
+//! upgrade or TLS. Gateways that multiplex many MCP sessions over a few connections can raise the
+//! HTTP/2 limits below:
+//!
+//! - `MCP_HTTP2_MAX_CONCURRENT_STREAMS`: streams a client may have open on one connection (default
+//!   200). Every open SSE stream holds one.
+//! - `MCP_HTTP2_INITIAL_STREAM_WINDOW_BYTES` and `MCP_HTTP2_INITIAL_CONNECTION_WINDOW_BYTES`: flow
+//!   control windows (default 1 MiB each, at most 2^31 - 1).
+//! - `MCP_MAX_REQUESTS_PER_CONNECTION`: once a connection has carried this many requests it is
+//!   closed gracefully, so long-lived gateway connections get rebalanced across replicas. Requests
+//!   already in flight finish. Unset means no cap.
+
+use std::{
+    io,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use axum::{extract::ConnectInfo, Router};
+use hyper::{body::Incoming, service::service_fn, Request};
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::conn::auto::Builder,
+};
+use serde::Serialize;
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::{watch, Notify},
+    task::JoinSet,
+};
+use tower::Service;
+use tracing::{debug, warn};
+
+/// The largest flow control window HTTP/2 allows.
+pub const MAX_WINDOW_BYTES: u32 = (1 << 31) - 1;
+
+/// Per-connection protocol limits; `None` keeps the HTTP library's default.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ConnectionTuning {
+    pub http2_max_concurrent_streams: Option<u32>,
+    pub http2_initial_stream_window_bytes: Option<u32>,
+    pub http2_initial_connection_window_bytes: Option<u32>,
+    pub max_requests_per_connection: Option<u64>,
+}
+
+impl ConnectionTuning {
+    /// Reads the `MCP_HTTP2_*` and `MCP_MAX_REQUESTS_PER_CONNECTION` variables. An invalid value
+    /// is an error rather than a silent default, since these are only set to fix throughput.
+    pub fn from_env() -> Result<Self> {
+        Self::from_lookup(|name| std::env::var(name).ok())
+    }
+
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let read = |variable: &str| -> Result<Option<u64>> {
+            let Some(raw) = lookup(variable).filter(|value| !value.trim().is_empty()) else {
+                return Ok(None);
+            };
+            match raw.trim().parse::<u64>() {
+                Ok(value) if value > 0 => Ok(Some(value)),
+                _ => bail!("Invalid {variable}: {raw:?} is not a positive integer"),
+            }
+        };
+        let window = |variable: &str| -> Result<Option<u32>> {
+            read(variable)?
+                .map(|bytes| {
+                    u32::try_from(bytes)
+                        .ok()
+                        .filter(|bytes| *bytes <= MAX_WINDOW_BYTES)
+                        .with_context(|| {
+                            format!("Invalid {variable}: {bytes} exceeds {MAX_WINDOW_BYTES}")
+                        })
+                })
+                .transpose()
+        };
+
+        let http2_max_concurrent_streams = read("MCP_HTTP2_MAX_CONCURRENT_STREAMS")?
+            .map(|streams| {
+                u32::try_from(streams).context("Invalid MCP_HTTP2_MAX_CONCURRENT_STREAMS")
+            })
+            .transpose()?;
+        Ok(Self {
+            http2_max_concurrent_streams,
+            http2_initial_stream_window_bytes: window("MCP_HTTP2_INITIAL_STREAM_WINDOW_BYTES")?,
+            http2_initial_connection_window_bytes: window(
+                "MCP_HTTP2_INITIAL_CONNECTION_WINDOW_BYTES",
+            )?,
+            max_requests_per_connection: read("MCP_MAX_REQUESTS_PER_CONNECTION")?,
+        })
+    }
+
+    fn builder(&self) -> Builder<TokioExecutor> {
+        let mut builder = Builder::new(TokioExecutor::new());
+        builder
+            .http2()
+            .max_concurrent_streams(self.http2_max_concurrent_streams)
+            .initial_stream_window_size(self.http2_initial_stream_window_bytes)
+            .initial_connection_window_size(self.http2_initial_connection_window_bytes);
+        builder
+    }
+}
+
+/// Serves `app` on `listener` until `shutdown` turns true, then waits for open connections to
+/// finish. Requests carry the peer address as [`ConnectInfo`], like
+/// `into_make_service_with_connect_info::<SocketAddr>()`.
+pub async fn serve(
+    listener: TcpListener,
+    app: Router,
+    tuning: ConnectionTuning,
+    mut shutdown: watch::Receiver<bool>,
+) -> io::Result<()> {
+    let builder = Arc::new(tuning.builder());
+    let mut connections = JoinSet::new();
+    loop {
+        let (stream, remote) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(error) => {
+                    // Usually out of file descriptors; back off instead of spinning.
+                    warn!(%error, "Failed to accept connection");
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            },
+            _ = stopped(&mut shutdown) => break,
+        };
+        connections.spawn(serve_connection(
+            Arc::clone(&builder),
+            stream,
+            remote,
+            app.clone(),
+            tuning.max_requests_per_connection,
+            shutdown.clone(),
+        ));
+        while connections.try_join_next().is_some() {}
+    }
+    while connections.join_next().await.is_some() {}
+    Ok(())
+}
+
+async fn serve_connection(
+    builder: Arc<Builder<TokioExecutor>>,
+    stream: TcpStream,
+    remote: SocketAddr,
+    app: Router,
+    max_requests: Option<u64>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let served = AtomicU64::new(0);
+    let cap_reached = Arc::new(Notify::new());
+    let service = {
+        let cap_reached = Arc::clone(&cap_reached);
+        service_fn(move |mut request: Request<Incoming>| {
+            request.extensions_mut().insert(ConnectInfo(remote));
+            let count = served.fetch_add(1, Ordering::Relaxed) + 1;
+            if max_requests.is_some_and(|max| count >= max) {
+                cap_reached.notify_one();
+            }
+            app.clone().call(request)
+        })
+    };
+
+    let connection = builder.serve_connection_with_upgrades(TokioIo::new(stream), service);
+    tokio::pin!(connection);
+    let mut closing = false;
+    loop {
+        tokio::select! {
+            result = connection.as_mut() => {
+                if let Err(error) = result {
+                    debug!(%remote, %error, "Connection ended with an error");
+                }
+                return;
+            }
+            _ = cap_reached.notified(), if !closing => {
+                debug!(%remote, "Closing connection after its request cap");
+                connection.as_mut().graceful_shutdown();
+                closing = true;
+            }
+            _ = stopped(&mut shutdown), if !closing => {
+                connection.as_mut().graceful_shutdown();
+                closing = true;
+            }
+        }
+    }
+}
+
+/// Resolves once shutdown is requested, or its sender is gone.
+async fn stopped(shutdown: &mut watch::Receiver<bool>) {
+    let _ = shutdown.wait_for(|stopped| *stopped).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tuning_reads_positive_limits_and_rejects_oversized_windows() {
+        let tuning = |env: &[(&str, &str)]| {
+            let env = env.to_vec();
+            ConnectionTuning::from_lookup(move |name| {
+                env.iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| value.to_string())
+            })
+        };
+
+        assert_eq!(tuning(&[]).unwrap(), ConnectionTuning::default());
+        assert_eq!(
+            tuning(&[
+                ("MCP_HTTP2_MAX_CONCURRENT_STREAMS", "1000"),
+                ("MCP_HTTP2_INITIAL_STREAM_WINDOW_BYTES", "4194304"),
+                ("MCP_HTTP2_INITIAL_CONNECTION_WINDOW_BYTES", "2147483647"),
+                ("MCP_MAX_REQUESTS_PER_CONNECTION", " 10000 "),
+            ])
+            .unwrap(),
+            ConnectionTuning {
+                http2_max_concurrent_streams: Some(1000),
+                http2_initial_stream_window_bytes: Some(4 << 20),
+                http2_initial_connection_window_bytes: Some(MAX_WINDOW_BYTES),
+                max_requests_per_connection: Some(10_000),
+            }
+        );
+
+        let error = tuning(&[("MCP_HTTP2_INITIAL_CONNECTION_WINDOW_BYTES", "2147483648")]);
+        assert!(format!("{:#}", error.unwrap_err()).contains("exceeds 2147483647"));
+        assert!(tuning(&[("MCP_MAX_REQUESTS_PER_CONNECTION", "0")]).is_err());
+        assert!(tuning(&[("MCP_HTTP2_MAX_CONCURRENT_STREAMS", "many")]).is_err());
+    }
+}
@@ -0,0 +1,133 @@
+//! Telemetry opt-out signalled by clients or configured per client.
+//!
+//! A session opts out of telemetry when any of these hold:
+//!
+//! - its `initialize` request carries `_meta["pizzaz/telemetry"]: false`;
+//! - the client declares `capabilities.experimental["pizzaz/telemetry"] = {"enabled": false}`;
+//! - `WIDGETS_CLIENT_PROFILES` sets `"telemetry": false` for the client's name.
+//!
+//! A single `tools/call` can also opt out with `_meta["pizzaz/telemetry"]: false`. The handler
+//! resolves the opt-out once per call, before any executor runs, and an opted-out call is left out
+//! of argument analytics and experiment exposure logs. Sessions seen opting out are remembered here
+//! so the capture recorder can redact their tool arguments and results.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, Weak},
+    time::{Duration, Instant},
+};
+
+use rmcp::model::{ClientInfo, JsonObject};
+use serde_json::Value as JsonValue;
+
+use crate::rate_limit::PurgeExpired;
+
+/// `_meta` key and experimental capability name carrying the telemetry choice.
+pub const TELEMETRY_META_KEY: &str = "pizzaz/telemetry";
+
+/// How long an opted-out session is remembered after its last tool call.
+pub const SESSION_RETENTION: Duration = Duration::from_secs(3600);
+
+/// Whether `meta` carries `"pizzaz/telemetry": false`.
+pub fn opts_out(meta: &JsonObject) -> bool {
+    meta.get(TELEMETRY_META_KEY) == Some(&JsonValue::Bool(false))
+}
+
+/// Whether the client declared the `pizzaz/telemetry` capability with `enabled: false`.
+pub fn declared_opt_out(info: &ClientInfo) -> bool {
+    info.capabilities
+        .experimental
+        .as_ref()
+        .and_then(|experimental| experimental.get(TELEMETRY_META_KEY))
+        .and_then(|capability| capability.get("enabled"))
+        == Some(&JsonValue::Bool(false))
+}
+
+/// Records an opt-out made in `initialize`'s `_meta` as the equivalent capability, so it stays
+/// with the session's client info.
+pub(crate) fn declare_opt_out(info: &mut ClientInfo) {
+    let mut capability = JsonObject::new();
+    capability.insert("enabled".to_string(), JsonValue::Bool(false));
+    info.capabilities
+        .experimental
+        .get_or_insert_with(Default::default)
+        .insert(TELEMETRY_META_KEY.to_string(), capability);
+}
+
+/// Sessions that opted out of telemetry. Clones share them.
+#[derive(Debug, Clone, Default)]
+pub struct Consent {
+    sessions: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+impl Consent {
+    /// Remembers that `session_id` opted out.
+    pub fn opt_out(&self, session_id: &str) {
+        self.sessions
+            .lock()
+            .expect("consent lock poisoned")
+            .insert(session_id.to_string(), Instant::now());
+    }
+
+    pub fn is_opted_out(&self, session_id: &str) -> bool {
+        self.sessions
+            .lock()
+            .expect("consent lock poisoned")
+            .contains_key(session_id)
+    }
+
+    /// Opted-out sessions shared by every clone, for the background sweep.
+    pub(crate) fn gc_state(&self) -> Weak<dyn PurgeExpired> {
+        let state: Arc<dyn PurgeExpired> = self.sessions.clone();
+        Arc::downgrade(&state)
+    }
+}
+
+impl PurgeExpired for Mutex<HashMap<String, Instant>> {
+    fn purge_expired(&self, now: Instant) -> usize {
+        let Ok(mut sessions) = self.try_lock() else {
+            return 0;
+        };
+        let before = sessions.len();
+        sessions.retain(|_, seen| now.duration_since(*seen) < SESSION_RETENTION);
+        before - sessions.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rmcp::model::{ClientCapabilities, Implementation, ProtocolVersion};
+    use serde_json::json;
+
+    #[test]
+    fn opt_out_comes_from_meta_or_capabilities() {
+        let meta = |value: JsonValue| value.as_object().cloned().unwrap();
+        assert!(opts_out(&meta(json!({ "pizzaz/telemetry": false }))));
+        assert!(!opts_out(&meta(json!({ "pizzaz/telemetry": "false" }))));
+        assert!(!opts_out(&meta(json!({}))));
+
+        let mut info = ClientInfo {
+            protocol_version: ProtocolVersion::V_2025_06_18,
+            capabilities: ClientCapabilities::default(),
+            client_info: Implementation::from_build_env(),
+        };
+        assert!(!declared_opt_out(&info));
+        declare_opt_out(&mut info);
+        assert!(declared_opt_out(&info));
+    }
+
+    #[test]
+    fn opted_out_sessions_expire() {
+        let consent = Consent::default();
+        consent.opt_out("session-1");
+        assert!(consent.is_opted_out("session-1"));
+        assert!(!consent.is_opted_out("session-2"));
+
+        let state = consent.gc_state().upgrade().unwrap();
+        assert_eq!(state.purge_expired(Instant::now()), 0);
+        let later = Instant::now() + SESSION_RETENTION;
+        assert_eq!(state.purge_expired(later), 1);
+        assert!(!consent.is_opted_out("session-1"));
+    }
+}
@@ -0,0 +1,141 @@
+//! The server's public MCP contract as one canonical JSON document.
+//!
+//! [`ContractSnapshot`] renders `tools/list`, `resources/list` and `resources/templates/list` for
+//! the current registry exactly as a client receives them, widget `_meta` included. Entries are
+//! sorted by name or URI and object keys are sorted, so two renders of the same contract are
+//! byte-identical and a release can be diffed against a checked-in golden file:
+//!
+//! ```no_run
+//! # async fn check() {
+//! use pizzaz_server_rust::{contract::ContractSnapshot, handler::PizzazServerHandler};
+//!
+//! let rendered = ContractSnapshot::new()
+//!     .render_string(&PizzazServerHandler::new())
+//!     .await;
+//! assert_eq!(rendered, std::fs::read_to_string("contract.json").unwrap());
+//! # }
+//! ```
+//!
+//! Content hashes and signatures change whenever widget HTML changes, so they are left out unless
+//! requested with [`ContractSnapshot::with_content_hashes`].
+
+use serde_json::{json, Value as JsonValue};
+
+use crate::{
+    auth::Identity, handler::PizzazServerHandler, signing::SIGNATURE_META_KEY,
+    widgets::CONTENT_HASH_META_KEY, wire_result,
+};
+
+/// Bumped when the layout of the snapshot document itself changes.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Options for rendering a contract snapshot.
+#[derive(Debug, Clone, Default)]
+pub struct ContractSnapshot {
+    identity: Identity,
+    content_hashes: bool,
+}
+
+impl ContractSnapshot {
+    /// The contract an anonymous client sees, without content hashes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Renders the tools visible to `identity` instead of an anonymous client.
+    pub fn for_identity(mut self, identity: Identity) -> Self {
+        self.identity = identity;
+        self
+    }
+
+    /// Keeps `_meta["pizzaz/contentHash"]` and `_meta["pizzaz/signature"]` on resources and
+    /// templates.
+    pub fn with_content_hashes(mut self, enabled: bool) -> Self {
+        self.content_hashes = enabled;
+        self
+    }
+
+    /// The snapshot as a JSON document.
+    pub async fn render(&self, handler: &PizzazServerHandler) -> JsonValue {
+        let tools = wire_result(&handler.list_tools_result(&self.identity).await);
+        let resources = wire_result(&handler.list_resources_result().await);
+        let templates = wire_result(&handler.list_resource_templates_result().await);
+
+        let mut snapshot = json!({
+            "snapshotVersion": SNAPSHOT_VERSION,
+            "tools": sorted_by(tools, "tools", "name"),
+            "resources": sorted_by(resources, "resources", "uri"),
+            "resourceTemplates": sorted_by(templates, "resourceTemplates", "uriTemplate"),
+        });
+        if !self.content_hashes {
+            for key in ["resources", "resourceTemplates"] {
+                for entry in snapshot[key].as_array_mut().into_iter().flatten() {
+                    if let Some(meta) = entry.get_mut("_meta").and_then(JsonValue::as_object_mut) {
+                        meta.remove(CONTENT_HASH_META_KEY);
+                        meta.remove(SIGNATURE_META_KEY);
+                    }
+                }
+            }
+        }
+        snapshot
+    }
+
+    /// The snapshot as pretty-printed JSON with a trailing newline, ready to write to a file.
+    pub async fn render_string(&self, handler: &PizzazServerHandler) -> String {
+        let mut rendered = serde_json::to_string_pretty(&self.render(handler).await)
+            .expect("contract snapshot serializes");
+        rendered.push('\n');
+        rendered
+    }
+}
+
+fn sorted_by(mut result: JsonValue, list: &str, key: &str) -> JsonValue {
+    let mut entries = match result[list].take() {
+        JsonValue::Array(entries) => entries,
+        _ => Vec::new(),
+    };
+    entries.sort_by(|a, b| a[key].as_str().cmp(&b[key].as_str()));
+    JsonValue::Array(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::initialize_widgets_for_tests;
+
+    #[tokio::test]
+    async fn snapshot_is_sorted_and_stable() {
+        initialize_widgets_for_tests();
+        let handler = PizzazServerHandler::new();
+
+        let rendered = ContractSnapshot::new().render_string(&handler).await;
+        assert_eq!(
+            rendered,
+            ContractSnapshot::new().render_string(&handler).await
+        );
+        assert!(rendered.ends_with("}\n"));
+
+        let snapshot: JsonValue = serde_json::from_str(&rendered).unwrap();
+        let names: Vec<_> = snapshot["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|tool| tool["name"].as_str().unwrap())
+            .collect();
+        let mut sorted = names.clone();
+        sorted.sort();
+        assert_eq!(names, sorted);
+        assert!(names.contains(&"pizza-map"));
+        assert!(snapshot["tools"][0]["_meta"]["openai/outputTemplate"].is_string());
+
+        let resource = &snapshot["resources"][0];
+        assert!(resource["_meta"].is_object());
+        assert!(resource["_meta"].get(CONTENT_HASH_META_KEY).is_none());
+
+        let hashed = ContractSnapshot::new()
+            .with_content_hashes(true)
+            .render(&handler)
+            .await;
+        assert!(hashed["resources"][0]["_meta"][CONTENT_HASH_META_KEY].is_string());
+    }
+}
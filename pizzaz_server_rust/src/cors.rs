@@ -0,0 +1,205 @@
+//! Cross-origin policy for browser-hosted MCP clients and widget embeds.
+//!
+//! Without configuration every origin is allowed, as before. Operators can restrict origins to
+//! an exact list, anchored regular expressions, or a custom validator, and opt in to
+//! credentialed requests for embeds that send cookies or HTTP auth.
+
+use std::sync::Arc;
+
+use axum::http::{header, HeaderName, HeaderValue};
+use regex::Regex;
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, Any, CorsLayer, ExposeHeaders};
+
+use crate::env_flag;
+
+/// Custom origin check; receives the `Origin` header value.
+pub type OriginValidator = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// Headers browsers may read from credentialed responses, where `*` is not allowed.
+const EXPOSED_HEADERS: [HeaderName; 4] = [
+    HeaderName::from_static("mcp-session-id"),
+    header::RETRY_AFTER,
+    header::WWW_AUTHENTICATE,
+    header::CONTENT_TYPE,
+];
+
+/// Which origins may call the server, and whether they may send credentials.
+#[derive(Clone, Default)]
+pub struct CorsConfig {
+    /// Exact origins such as `https://chatgpt.com`; `*` allows any origin.
+    pub allowed_origins: Vec<String>,
+    /// Patterns matched against the whole origin.
+    pub origin_patterns: Vec<Regex>,
+    pub validator: Option<OriginValidator>,
+    pub allow_credentials: bool,
+}
+
+impl std::fmt::Debug for CorsConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CorsConfig")
+            .field("allowed_origins", &self.allowed_origins)
+            .field("origin_patterns", &self.origin_patterns)
+            .field("validator", &self.validator.is_some())
+            .field("allow_credentials", &self.allow_credentials)
+            .finish()
+    }
+}
+
+impl CorsConfig {
+    /// Reads `MCP_CORS_ALLOWED_ORIGINS`, `MCP_CORS_ORIGIN_PATTERNS` and
+    /// `MCP_CORS_ALLOW_CREDENTIALS`. Invalid patterns are skipped with a warning.
+    pub(crate) fn from_env() -> Self {
+        let list = |variable: &str| -> Vec<String> {
+            std::env::var(variable)
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|entry| !entry.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+
+        let origin_patterns = list("MCP_CORS_ORIGIN_PATTERNS")
+            .into_iter()
+            .filter_map(|pattern| match Regex::new(&format!("^(?:{pattern})$")) {
+                Ok(regex) => Some(regex),
+                Err(err) => {
+                    tracing::warn!(
+                        "Ignoring invalid MCP_CORS_ORIGIN_PATTERNS entry '{pattern}': {err}"
+                    );
+                    None
+                }
+            })
+            .collect();
+
+        Self {
+            allowed_origins: list("MCP_CORS_ALLOWED_ORIGINS"),
+            origin_patterns,
+            validator: None,
+            allow_credentials: env_flag("MCP_CORS_ALLOW_CREDENTIALS"),
+        }
+    }
+
+    /// Adds a custom check; an origin is allowed if it passes this or any configured rule.
+    pub fn with_validator<F>(mut self, validator: F) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.validator = Some(Arc::new(validator));
+        self
+    }
+
+    fn allows_any(&self) -> bool {
+        let restricted = !self.allowed_origins.is_empty()
+            || !self.origin_patterns.is_empty()
+            || self.validator.is_some();
+        !restricted || self.allowed_origins.iter().any(|origin| origin == "*")
+    }
+
+    /// Whether `origin` passes the configured rules.
+    pub fn allows(&self, origin: &str) -> bool {
+        self.allows_any()
+            || self.allowed_origins.iter().any(|allowed| allowed == origin)
+            || self
+                .origin_patterns
+                .iter()
+                .any(|regex| regex.is_match(origin))
+            || self.validator.as_ref().is_some_and(|check| check(origin))
+    }
+
+    /// Builds the CORS layer for the whole router.
+    pub fn layer(&self) -> CorsLayer {
+        if self.allows_any() {
+            if self.allow_credentials {
+                tracing::warn!(
+                    "MCP_CORS_ALLOW_CREDENTIALS ignored: credentials need an explicit origin allowlist"
+                );
+            }
+            return CorsLayer::permissive();
+        }
+
+        let config = self.clone();
+        let allow_origin = AllowOrigin::predicate(move |origin: &HeaderValue, _| {
+            let origin = origin.to_str().unwrap_or_default();
+            let allowed = config.allows(origin);
+            tracing::debug!(origin, allowed, "CORS origin decision");
+            allowed
+        });
+        let layer = CorsLayer::new().allow_origin(allow_origin);
+
+        if self.allow_credentials {
+            // Wildcards are invalid alongside credentials, so echo what the browser asks for.
+            layer
+                .allow_credentials(true)
+                .allow_methods(AllowMethods::mirror_request())
+                .allow_headers(AllowHeaders::mirror_request())
+                .expose_headers(ExposeHeaders::list(EXPOSED_HEADERS))
+        } else {
+            layer
+                .allow_methods(Any)
+                .allow_headers(Any)
+                .expose_headers(Any)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request, routing::get, Router};
+    use tower::ServiceExt;
+
+    fn restricted() -> CorsConfig {
+        CorsConfig {
+            allowed_origins: vec!["https://chatgpt.com".into()],
+            origin_patterns: vec![
+                Regex::new(r"^(?:https://[a-z0-9-]+\.oaiusercontent\.com)$").unwrap()
+            ],
+            validator: None,
+            allow_credentials: true,
+        }
+    }
+
+    #[test]
+    fn allows_matches_list_patterns_and_validator() {
+        let config = restricted().with_validator(|origin| origin == "http://localhost:4444");
+        assert!(config.allows("https://chatgpt.com"));
+        assert!(config.allows("https://widgets-1.oaiusercontent.com"));
+        assert!(config.allows("http://localhost:4444"));
+        assert!(!config.allows("https://evil.example"));
+        assert!(!config.allows("https://x.oaiusercontent.com.evil.example"));
+        assert!(CorsConfig::default().allows("https://anything.example"));
+    }
+
+    #[tokio::test]
+    async fn credentialed_layer_echoes_only_allowed_origins() {
+        let app = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .layer(restricted().layer());
+
+        let request = |origin: &str| {
+            Request::builder()
+                .uri("/")
+                .header(header::ORIGIN, origin)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let allowed = app
+            .clone()
+            .oneshot(request("https://chatgpt.com"))
+            .await
+            .unwrap();
+        let headers = allowed.headers();
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://chatgpt.com"
+        );
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+
+        let denied = app.oneshot(request("https://evil.example")).await.unwrap();
+        assert!(!denied
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+    }
+}
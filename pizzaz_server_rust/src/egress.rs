@@ -0,0 +1,499 @@
+//! Outbound HTTP for tool executors, limited to an allowlist of hosts.
+//!
+//! Executors that call third-party APIs go through [`EgressClient`] (see
+//! [`Invocation::http`](crate::executor::Invocation::http)) instead of opening their own
+//! connections, so one policy bounds what any executor can reach:
+//!
+//! - `WIDGETS_EGRESS_ALLOWLIST`: comma-separated hosts, e.g. `api.example.com,*.cdn.example`. A
+//!   `*.` entry covers subdomains but not the domain itself. Empty or unset denies every request.
+//! - `WIDGETS_EGRESS_TIMEOUT_MS`: time allowed for the whole exchange, 5000 by default.
+//! - `WIDGETS_EGRESS_MAX_BYTES`: largest response body accepted, 1 MiB by default.
+//!
+//! Denied requests and responses over the cap are logged under the `pizzaz::egress` target and
+//! returned as [`EgressError`]s, which convert into [`ToolFailure`]s. Requests go out over HTTP or
+//! HTTPS and redirects are not followed, so a response can never come from a host outside the
+//! allowlist.
+
+use std::{
+    sync::{Arc, LazyLock, RwLock},
+    time::Duration,
+};
+
+use futures::future::BoxFuture;
+use serde::Serialize;
+
+use crate::{
+    executor::ToolFailure,
+    http::{http_client, transport_error},
+};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_MAX_RESPONSE_BYTES: u64 = 1024 * 1024;
+
+/// Which hosts executors may call and how much they may read back.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EgressPolicy {
+    /// Lowercase hosts; `*.example.com` covers subdomains of `example.com`.
+    pub allowed_hosts: Vec<String>,
+    #[serde(rename = "timeout_ms", serialize_with = "serialize_millis")]
+    pub timeout: Duration,
+    pub max_response_bytes: u64,
+}
+
+fn serialize_millis<S: serde::Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u128(duration.as_millis())
+}
+
+impl Default for EgressPolicy {
+    fn default() -> Self {
+        Self {
+            allowed_hosts: Vec::new(),
+            timeout: DEFAULT_TIMEOUT,
+            max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
+        }
+    }
+}
+
+impl EgressPolicy {
+    pub fn new(allowed_hosts: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            allowed_hosts: allowed_hosts
+                .into_iter()
+                .map(|host| host.into().trim().to_ascii_lowercase())
+                .filter(|host| !host.is_empty())
+                .collect(),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_max_response_bytes(mut self, max_response_bytes: u64) -> Self {
+        self.max_response_bytes = max_response_bytes;
+        self
+    }
+
+    /// Policy from the `WIDGETS_EGRESS_*` variables; invalid limits fall back to the defaults.
+    pub(crate) fn from_env() -> Self {
+        let allowed = std::env::var("WIDGETS_EGRESS_ALLOWLIST").unwrap_or_default();
+        let mut policy = Self::new(allowed.split(','));
+        if let Some(millis) = env_u64("WIDGETS_EGRESS_TIMEOUT_MS") {
+            policy.timeout = Duration::from_millis(millis);
+        }
+        if let Some(bytes) = env_u64("WIDGETS_EGRESS_MAX_BYTES") {
+            policy.max_response_bytes = bytes;
+        }
+        policy
+    }
+
+    pub fn allows(&self, host: &str) -> bool {
+        let host = host.to_ascii_lowercase();
+        self.allowed_hosts
+            .iter()
+            .any(|allowed| match allowed.strip_prefix("*.") {
+                Some(domain) => host
+                    .strip_suffix(domain)
+                    .is_some_and(|prefix| prefix.len() > 1 && prefix.ends_with('.')),
+                None => host == *allowed,
+            })
+    }
+}
+
+fn env_u64(name: &str) -> Option<u64> {
+    let raw = std::env::var(name).ok()?;
+    match raw.trim().parse::<u64>() {
+        Ok(value) if value > 0 => Some(value),
+        _ => {
+            tracing::warn!(value = %raw, "Ignoring invalid {name}");
+            None
+        }
+    }
+}
+
+/// An outbound request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EgressRequest {
+    pub method: &'static str,
+    pub url: String,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Vec<u8>,
+}
+
+impl EgressRequest {
+    pub fn get(url: impl Into<String>) -> Self {
+        Self {
+            method: "GET",
+            url: url.into(),
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn post_json(url: impl Into<String>, body: &serde_json::Value) -> Self {
+        Self {
+            method: "POST",
+            url: url.into(),
+            headers: vec![("Content-Type", "application/json".to_string())],
+            body: body.to_string().into_bytes(),
+        }
+    }
+
+    pub fn header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+}
+
+/// Status and body of a response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EgressResponse {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+impl EgressResponse {
+    pub fn json(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::from_slice(&self.body)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum EgressError {
+    #[error("{0} is not an absolute http:// or https:// URL")]
+    InvalidUrl(String),
+    #[error("Outbound requests to {host} are not allowed")]
+    Denied { host: String },
+    #[error("{host} did not respond within {} ms", .timeout.as_millis())]
+    TimedOut { host: String, timeout: Duration },
+    #[error("Response from {host} exceeded {limit} bytes")]
+    TooLarge { host: String, limit: u64 },
+    #[error("Request to {host} failed: {source}")]
+    Io {
+        host: String,
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+impl From<EgressError> for ToolFailure {
+    fn from(error: EgressError) -> Self {
+        let detail = error.to_string();
+        match error {
+            EgressError::InvalidUrl(_) | EgressError::Denied { .. } => {
+                ToolFailure::new("egress_denied", "Request not allowed", detail)
+            }
+            EgressError::TimedOut { .. } | EgressError::Io { .. } => {
+                ToolFailure::new("upstream_unavailable", "Service unavailable", detail).retryable()
+            }
+            EgressError::TooLarge { .. } => {
+                ToolFailure::new("upstream_response_too_large", "Response too large", detail)
+            }
+        }
+    }
+}
+
+/// Sends one request, reading little more than `max_body_bytes` of the response body.
+///
+/// Implementations may stop reading early; [`EgressClient`] treats a body longer than
+/// `max_body_bytes` as too large either way.
+pub trait EgressTransport: Send + Sync {
+    fn send<'a>(
+        &'a self,
+        request: &'a EgressRequest,
+        max_body_bytes: u64,
+    ) -> BoxFuture<'a, std::io::Result<EgressResponse>>;
+}
+
+static TRANSPORT: LazyLock<RwLock<Arc<dyn EgressTransport>>> =
+    LazyLock::new(|| RwLock::new(Arc::new(HttpClient::default())));
+
+static CLIENT: LazyLock<EgressClient> = LazyLock::new(|| EgressClient {
+    policy: Arc::new(EgressPolicy::from_env()),
+    transport: None,
+    caller: None,
+});
+
+/// Replaces the transport used for executor requests, e.g. with one that records them in tests.
+pub fn set_transport(transport: Arc<dyn EgressTransport>) {
+    *TRANSPORT.write().expect("egress transport lock poisoned") = transport;
+}
+
+/// The policy in effect, for the config endpoint.
+pub fn policy() -> EgressPolicy {
+    CLIENT.policy.as_ref().clone()
+}
+
+/// The client configured from the environment, labelled with `caller` in logs.
+pub fn client(caller: &str) -> EgressClient {
+    CLIENT.clone().for_caller(caller)
+}
+
+/// Sends with a shared [`reqwest::Client`], over TLS for `https://` URLs.
+struct HttpClient(reqwest::Client);
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        Self(http_client("pizzaz-egress"))
+    }
+}
+
+impl EgressTransport for HttpClient {
+    fn send<'a>(
+        &'a self,
+        request: &'a EgressRequest,
+        max_body_bytes: u64,
+    ) -> BoxFuture<'a, std::io::Result<EgressResponse>> {
+        Box::pin(async move {
+            let method = reqwest::Method::from_bytes(request.method.as_bytes()).map_err(|_| {
+                std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid HTTP method")
+            })?;
+            let mut builder = self
+                .0
+                .request(method, &request.url)
+                .body(request.body.clone());
+            for (name, value) in &request.headers {
+                builder = builder.header(*name, value);
+            }
+            let mut response = builder.send().await.map_err(transport_error)?;
+            let status = response.status().as_u16();
+
+            // One byte past the cap is enough for the client to reject the body.
+            let mut body = Vec::new();
+            while (body.len() as u64) <= max_body_bytes {
+                match response.chunk().await.map_err(transport_error)? {
+                    Some(chunk) => body.extend_from_slice(&chunk),
+                    None => break,
+                }
+            }
+            Ok(EgressResponse { status, body })
+        })
+    }
+}
+
+/// HTTP client that enforces an [`EgressPolicy`]. Clones share the policy.
+#[derive(Clone)]
+pub struct EgressClient {
+    policy: Arc<EgressPolicy>,
+    /// `None` uses the transport installed with [`set_transport`].
+    transport: Option<Arc<dyn EgressTransport>>,
+    caller: Option<String>,
+}
+
+impl std::fmt::Debug for EgressClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EgressClient")
+            .field("policy", &self.policy)
+            .field("caller", &self.caller)
+            .finish_non_exhaustive()
+    }
+}
+
+impl EgressClient {
+    /// Sends through the transport installed with [`set_transport`].
+    pub fn new(policy: EgressPolicy) -> Self {
+        Self {
+            policy: Arc::new(policy),
+            transport: None,
+            caller: None,
+        }
+    }
+
+    pub fn with_transport(policy: EgressPolicy, transport: Arc<dyn EgressTransport>) -> Self {
+        Self {
+            transport: Some(transport),
+            ..Self::new(policy)
+        }
+    }
+
+    /// Labels log lines with `caller`, usually the widget id.
+    pub fn for_caller(mut self, caller: impl Into<String>) -> Self {
+        self.caller = Some(caller.into());
+        self
+    }
+
+    pub fn policy(&self) -> &EgressPolicy {
+        &self.policy
+    }
+
+    pub async fn get(&self, url: impl Into<String>) -> Result<EgressResponse, EgressError> {
+        self.send(EgressRequest::get(url)).await
+    }
+
+    /// Sends `request` if its host is allowed, within the policy's timeout and size cap.
+    pub async fn send(&self, request: EgressRequest) -> Result<EgressResponse, EgressError> {
+        let caller = self.caller.as_deref().unwrap_or("-");
+        let host =
+            host_of(&request.url).ok_or_else(|| EgressError::InvalidUrl(request.url.clone()))?;
+        if !self.policy.allows(&host) {
+            tracing::warn!(
+                target: "pizzaz::egress",
+                caller,
+                host = %host,
+                method = request.method,
+                "Egress denied: host not in allowlist"
+            );
+            return Err(EgressError::Denied { host });
+        }
+
+        let transport = match &self.transport {
+            Some(transport) => transport.clone(),
+            None => TRANSPORT
+                .read()
+                .expect("egress transport lock poisoned")
+                .clone(),
+        };
+        let limit = self.policy.max_response_bytes;
+        let response = tokio::time::timeout(self.policy.timeout, transport.send(&request, limit))
+            .await
+            .map_err(|_| EgressError::TimedOut {
+                host: host.clone(),
+                timeout: self.policy.timeout,
+            })?
+            .map_err(|source| EgressError::Io {
+                host: host.clone(),
+                source,
+            })?;
+        if response.body.len() as u64 > limit {
+            tracing::warn!(
+                target: "pizzaz::egress",
+                caller,
+                host = %host,
+                limit,
+                "Egress denied: response exceeded the size cap"
+            );
+            return Err(EgressError::TooLarge { host, limit });
+        }
+        tracing::debug!(
+            target: "pizzaz::egress",
+            caller,
+            host = %host,
+            method = request.method,
+            status = response.status,
+            "Egress request completed"
+        );
+        Ok(response)
+    }
+}
+
+/// Lowercase host of an absolute `http://` or `https://` URL, without port or brackets.
+fn host_of(url: &str) -> Option<String> {
+    let url = reqwest::Url::parse(url).ok()?;
+    let credentials = !url.username().is_empty() || url.password().is_some();
+    if !matches!(url.scheme(), "http" | "https") || credentials {
+        return None;
+    }
+    let host = url.host_str()?;
+    let host = host
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host);
+    (!host.is_empty()).then(|| host.to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    struct Canned {
+        body: Vec<u8>,
+        requests: Mutex<Vec<String>>,
+    }
+
+    impl EgressTransport for Canned {
+        fn send<'a>(
+            &'a self,
+            request: &'a EgressRequest,
+            _max_body_bytes: u64,
+        ) -> BoxFuture<'a, std::io::Result<EgressResponse>> {
+            self.requests.lock().unwrap().push(request.url.clone());
+            Box::pin(async move {
+                Ok(EgressResponse {
+                    status: 200,
+                    body: self.body.clone(),
+                })
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_are_limited_to_allowed_hosts_and_sizes() {
+        let transport = Arc::new(Canned {
+            body: b"{\"ok\":true}".to_vec(),
+            requests: Mutex::new(Vec::new()),
+        });
+        let client = EgressClient::with_transport(
+            EgressPolicy::new(["api.example.com", "*.cdn.example"]),
+            transport.clone(),
+        )
+        .for_caller("pizza-map");
+
+        let response = client.get("https://API.example.com/v1/menu").await.unwrap();
+        assert_eq!(response.json().unwrap()["ok"], true);
+        assert!(client.get("http://eu.cdn.example:8080/x").await.is_ok());
+
+        for url in [
+            "https://cdn.example/x",
+            "https://api.example.com.evil.example/",
+            "https://api.example.com@evil.example/",
+        ] {
+            assert!(
+                matches!(
+                    client.get(url).await,
+                    Err(EgressError::Denied { .. } | EgressError::InvalidUrl(_))
+                ),
+                "{url}"
+            );
+        }
+        assert_eq!(transport.requests.lock().unwrap().len(), 2);
+
+        let capped = EgressClient::with_transport(
+            EgressPolicy::new(["api.example.com"]).with_max_response_bytes(4),
+            transport,
+        );
+        let error = capped
+            .get("https://api.example.com/v1/menu")
+            .await
+            .unwrap_err();
+        assert!(matches!(error, EgressError::TooLarge { limit: 4, .. }));
+        assert_eq!(ToolFailure::from(error).code, "upstream_response_too_large");
+    }
+
+    #[tokio::test]
+    async fn http_transport_reads_status_and_body() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buffer = [0u8; 1024];
+            let _ = socket.read(&mut buffer).await.unwrap();
+            socket
+                .write_all(b"HTTP/1.0 201 Created\r\nContent-Length: 5\r\n\r\nhello")
+                .await
+                .unwrap();
+        });
+
+        let client = EgressClient::with_transport(
+            EgressPolicy::new(["127.0.0.1"]).with_timeout(Duration::from_secs(2)),
+            Arc::new(HttpClient::default()),
+        );
+        let response = client
+            .send(EgressRequest::post_json(
+                format!("http://127.0.0.1:{port}/orders"),
+                &serde_json::json!({"topping": "basil"}),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status, 201);
+        assert_eq!(response.body, b"hello");
+    }
+}
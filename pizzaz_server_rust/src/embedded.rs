@@ -0,0 +1,53 @@
+//! A default manifest compiled into the binary with the `embedded-manifest` feature.
+//!
+//! The manifest and widget HTML under `embedded/` are included at build time. When no manifest
+//! exists at `WIDGETS_MANIFEST_PATH` and no registry snapshot stands in for it,
+//! `bootstrap_registry()` serves the embedded widgets instead of an empty registry, and
+//! `GET /internal/widgets/status` says so under `degraded`. A manifest that exists but fails to
+//! load is still reported as an error; the embedded one only covers a missing file.
+
+use std::{collections::HashMap, path::Path};
+
+use anyhow::Context;
+
+use crate::widgets_manifest::{parse_manifest, WidgetManifest};
+
+const MANIFEST: &[u8] = include_bytes!("../embedded/widgets.json");
+
+/// The HTML assets `MANIFEST` references, keyed by `htmlPath`.
+const HTML: &[(&str, &[u8])] = &[
+    (
+        "pizzaz-map.html",
+        include_bytes!("../embedded/pizzaz-map.html"),
+    ),
+    (
+        "pizzaz-carousel.html",
+        include_bytes!("../embedded/pizzaz-carousel.html"),
+    ),
+    (
+        "pizzaz-albums.html",
+        include_bytes!("../embedded/pizzaz-albums.html"),
+    ),
+    (
+        "pizzaz-list.html",
+        include_bytes!("../embedded/pizzaz-list.html"),
+    ),
+    (
+        "pizzaz-video.html",
+        include_bytes!("../embedded/pizzaz-video.html"),
+    ),
+];
+
+/// The embedded manifest and the contents of the assets it references.
+pub(crate) fn manifest() -> anyhow::Result<(WidgetManifest, HashMap<String, String>)> {
+    let manifest = parse_manifest(MANIFEST, Path::new("embedded/widgets.json"))?;
+    let assets = HTML
+        .iter()
+        .map(|(reference, contents)| {
+            let html = String::from_utf8(contents.to_vec())
+                .with_context(|| format!("Embedded {reference} is not UTF-8"))?;
+            Ok((reference.to_string(), html))
+        })
+        .collect::<anyhow::Result<_>>()?;
+    Ok((manifest, assets))
+}
@@ -0,0 +1,124 @@
+//! Older environment variable names, mapped onto the ones this server reads.
+//!
+//! Deployments written for the Python and Node examples, or for earlier versions of this server,
+//! use names such as `MCP_PORT` and `WIDGET_MANIFEST`. At startup each legacy name that is set is
+//! copied to its current name, with a deprecation warning, so those deployments keep working while
+//! they migrate. A current name that is already set always wins; the legacy value is then ignored
+//! with a warning. `GET /internal/config` lists the names that were migrated under `legacy_env`.
+
+use std::sync::OnceLock;
+
+use serde::Serialize;
+use tracing::warn;
+
+/// Legacy names and the current names they map to.
+pub const LEGACY_ENV_NAMES: &[(&str, &str)] = &[
+    ("MCP_PORT", "PORT"),
+    ("WIDGET_MANIFEST", "WIDGETS_MANIFEST_PATH"),
+    ("WIDGETS_MANIFEST", "WIDGETS_MANIFEST_PATH"),
+    ("WIDGET_MANIFEST_PATH", "WIDGETS_MANIFEST_PATH"),
+    ("WIDGET_MANIFEST_OVERLAY", "WIDGETS_MANIFEST_OVERLAY_PATH"),
+    ("REFRESH_TOKEN", "WIDGETS_REFRESH_TOKEN"),
+    ("CORS_ALLOWED_ORIGINS", "MCP_CORS_ALLOWED_ORIGINS"),
+];
+
+/// One legacy variable found in the environment.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EnvMigration {
+    pub legacy: &'static str,
+    pub current: &'static str,
+    /// False when the current name, or an earlier legacy name for it, was already set.
+    pub applied: bool,
+}
+
+static MIGRATIONS: OnceLock<Vec<EnvMigration>> = OnceLock::new();
+
+/// Copies set legacy variables to their current names and logs a deprecation warning for each.
+///
+/// Call it once at startup, before anything reads the environment. Later calls do nothing.
+pub fn apply() -> &'static [EnvMigration] {
+    MIGRATIONS.get_or_init(|| {
+        let migrations = plan(|name| std::env::var(name).ok());
+        for migration in &migrations {
+            if migration.applied {
+                let value = std::env::var(migration.legacy).unwrap_or_default();
+                std::env::set_var(migration.current, value);
+                warn!(
+                    legacy = migration.legacy,
+                    current = migration.current,
+                    "Deprecated environment variable; rename it"
+                );
+            } else {
+                warn!(
+                    legacy = migration.legacy,
+                    current = migration.current,
+                    "Ignoring deprecated environment variable because its replacement is set"
+                );
+            }
+        }
+        migrations
+    })
+}
+
+/// The migrations applied at startup; empty before [`apply`] runs.
+pub fn applied() -> &'static [EnvMigration] {
+    MIGRATIONS.get().map(Vec::as_slice).unwrap_or_default()
+}
+
+/// Which legacy variables `lookup` finds set, and whether each would be copied.
+fn plan(lookup: impl Fn(&str) -> Option<String>) -> Vec<EnvMigration> {
+    let is_set = |name: &str| lookup(name).is_some_and(|value| !value.trim().is_empty());
+    let mut filled: Vec<&str> = Vec::new();
+    LEGACY_ENV_NAMES
+        .iter()
+        .filter(|(legacy, _)| is_set(legacy))
+        .map(|&(legacy, current)| {
+            let applied = !is_set(current) && !filled.contains(&current);
+            if applied {
+                filled.push(current);
+            }
+            EnvMigration {
+                legacy,
+                current,
+                applied,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_names_fill_unset_current_names_only() {
+        let env = [
+            ("MCP_PORT", "9000"),
+            ("WIDGET_MANIFEST", "/srv/widgets.json"),
+            ("WIDGETS_MANIFEST", "/srv/other.json"),
+            ("REFRESH_TOKEN", "legacy"),
+            ("WIDGETS_REFRESH_TOKEN", "current"),
+            ("CORS_ALLOWED_ORIGINS", " "),
+        ];
+        let lookup = |name: &str| {
+            env.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        };
+
+        let migration = |legacy, current, applied| EnvMigration {
+            legacy,
+            current,
+            applied,
+        };
+        assert_eq!(
+            plan(lookup),
+            vec![
+                migration("MCP_PORT", "PORT", true),
+                migration("WIDGET_MANIFEST", "WIDGETS_MANIFEST_PATH", true),
+                migration("WIDGETS_MANIFEST", "WIDGETS_MANIFEST_PATH", false),
+                migration("REFRESH_TOKEN", "WIDGETS_REFRESH_TOKEN", false),
+            ]
+        );
+    }
+}
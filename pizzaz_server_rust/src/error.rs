@@ -0,0 +1,327 @@
+//! Shared JSON error envelope for the HTTP endpoints outside the MCP protocol.
+//!
+//! Every failure is rendered as
+//! `{"error": {"code", "message", "request_id", "docs", "details"}}`, where `code` is a stable
+//! machine-readable string and `request_id` matches the `X-Request-Id` response header.
+//!
+//! Bearer token failures also carry an RFC 6750 `WWW-Authenticate` challenge. Its `error` and
+//! `error_description` parameters are repeated in `details`, so OAuth clients can tell a missing
+//! token from a rejected one.
+
+use axum::{
+    extract::{
+        rejection::{BytesRejection, JsonRejection},
+        Request,
+    },
+    http::{header, HeaderName, HeaderValue, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use serde_json::Value;
+
+/// Header carrying the request id in both directions.
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+const MAX_REQUEST_ID_LEN: usize = 128;
+const DOCS_PATH: &str = "docs/widgets-manifest.md";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// The id of the request currently being handled, if it passed through [`assign_request_id`].
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Middleware that reuses a caller-supplied `X-Request-Id` or generates one, and echoes it back.
+pub(crate) async fn assign_request_id(request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| {
+            !value.is_empty()
+                && value.len() <= MAX_REQUEST_ID_LEN
+                && value.bytes().all(|byte| byte.is_ascii_graphic())
+        })
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    let header_value = HeaderValue::from_str(&request_id).ok();
+    let mut response = REQUEST_ID.scope(request_id, next.run(request)).await;
+    if let Some(value) = header_value {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// Why bearer credentials were refused, with the RFC 6750 error code each one maps to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BearerError {
+    /// No credentials, or another scheme; the challenge carries no error code (`401`).
+    Missing,
+    /// An `Authorization: Bearer` header without a usable token (`400`).
+    InvalidRequest,
+    /// A token that is not recognized (`401`).
+    InvalidToken,
+    /// A recognized token that does not cover the request (`403`).
+    InsufficientScope,
+}
+
+impl BearerError {
+    /// The `error` parameter of the challenge.
+    pub fn code(self) -> Option<&'static str> {
+        match self {
+            BearerError::Missing => None,
+            BearerError::InvalidRequest => Some("invalid_request"),
+            BearerError::InvalidToken => Some("invalid_token"),
+            BearerError::InsufficientScope => Some("insufficient_scope"),
+        }
+    }
+
+    // Quoted in the header, so these must not contain `"` or `\`.
+    fn description(self) -> &'static str {
+        match self {
+            BearerError::Missing => "Missing bearer token",
+            BearerError::InvalidRequest => {
+                "Malformed Authorization header; expected Bearer <token>"
+            }
+            BearerError::InvalidToken => "The bearer token is not recognized",
+            BearerError::InsufficientScope => "The bearer token does not cover this request",
+        }
+    }
+}
+
+/// An HTTP error rendered with the shared envelope.
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+    docs: Option<&'static str>,
+    details: Option<Value>,
+    headers: Vec<(HeaderName, HeaderValue)>,
+}
+
+#[derive(Serialize)]
+struct ErrorEnvelope<'a> {
+    error: ErrorBody<'a>,
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    code: &'a str,
+    message: &'a str,
+    request_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    docs: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<&'a Value>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+            docs: None,
+            details: None,
+            headers: Vec::new(),
+        }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, "not_found", message)
+    }
+
+    pub fn bad_request(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, code, message)
+    }
+
+    /// A missing or wrong bearer token; `realm` names the protected area.
+    pub fn unauthorized(realm: &'static str, error: BearerError) -> Self {
+        let (status, code) = match error {
+            BearerError::Missing | BearerError::InvalidToken => {
+                (StatusCode::UNAUTHORIZED, "unauthorized")
+            }
+            BearerError::InvalidRequest => (StatusCode::BAD_REQUEST, "invalid_authorization"),
+            BearerError::InsufficientScope => (StatusCode::FORBIDDEN, "forbidden"),
+        };
+        Self::new(status, code, error.description()).with_bearer_challenge(realm, error)
+    }
+
+    pub fn rate_limited(retry_after_seconds: u64) -> Self {
+        Self::new(
+            StatusCode::TOO_MANY_REQUESTS,
+            "rate_limited",
+            format!("Rate limit exceeded. Retry after {retry_after_seconds} seconds."),
+        )
+        .with_header(header::RETRY_AFTER, HeaderValue::from(retry_after_seconds))
+    }
+
+    /// Points at a section of the widgets manifest guide, e.g. `mcp-server-refresh-workflow`.
+    pub fn with_docs(mut self, anchor: &'static str) -> Self {
+        self.docs = Some(anchor);
+        self
+    }
+
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    /// Adds the RFC 6750 challenge for `error`, merging its parameters into `details`.
+    pub fn with_bearer_challenge(mut self, realm: &'static str, error: BearerError) -> Self {
+        let mut challenge = format!("Bearer realm=\"{realm}\"");
+        if let Some(code) = error.code() {
+            let description = error.description();
+            challenge.push_str(&format!(
+                ", error=\"{code}\", error_description=\"{description}\""
+            ));
+            let details = self
+                .details
+                .get_or_insert_with(|| Value::Object(Default::default()));
+            if let Some(details) = details.as_object_mut() {
+                details.insert("error".to_string(), code.into());
+                details.insert("error_description".to_string(), description.into());
+            }
+        }
+        self.with_header(
+            header::WWW_AUTHENTICATE,
+            HeaderValue::from_str(&challenge).expect("challenge is a valid header value"),
+        )
+    }
+
+    pub fn with_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.push((name, value));
+        self
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+
+    pub fn details_mut(&mut self) -> Option<&mut Value> {
+        self.details.as_mut()
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let envelope = ErrorEnvelope {
+            error: ErrorBody {
+                code: self.code,
+                message: &self.message,
+                request_id: current_request_id(),
+                docs: self.docs.map(|anchor| format!("{DOCS_PATH}#{anchor}")),
+                details: self.details.as_ref(),
+            },
+        };
+        let mut response = (self.status, Json(envelope)).into_response();
+        for (name, value) in self.headers {
+            response.headers_mut().insert(name, value);
+        }
+        response
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        Self::from_body_rejection(rejection.status(), rejection.body_text())
+    }
+}
+
+impl From<BytesRejection> for ApiError {
+    fn from(rejection: BytesRejection) -> Self {
+        Self::from_body_rejection(rejection.status(), rejection.body_text())
+    }
+}
+
+impl ApiError {
+    fn from_body_rejection(status: StatusCode, message: String) -> Self {
+        let code = match status {
+            StatusCode::PAYLOAD_TOO_LARGE => "body_too_large",
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => "unsupported_media_type",
+            _ => "invalid_body",
+        };
+        Self::new(status, code, message)
+    }
+}
+
+/// Router fallback so unknown paths get the envelope instead of an empty 404.
+pub(crate) async fn route_not_found(uri: Uri) -> ApiError {
+    ApiError::not_found(format!("No route for {}", uri.path()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::BodyExt;
+
+    #[tokio::test]
+    async fn envelope_carries_code_request_id_and_docs() {
+        let response = REQUEST_ID
+            .scope("req-1".to_string(), async {
+                ApiError::rate_limited(7)
+                    .with_docs("mcp-server-refresh-workflow")
+                    .into_response()
+            })
+            .await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "7");
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let json: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"]["code"], "rate_limited");
+        assert_eq!(json["error"]["request_id"], "req-1");
+        assert_eq!(
+            json["error"]["docs"],
+            "docs/widgets-manifest.md#mcp-server-refresh-workflow"
+        );
+        assert!(json["error"].get("details").is_none());
+    }
+
+    #[tokio::test]
+    async fn bearer_challenges_follow_rfc_6750() {
+        let missing = ApiError::unauthorized("mcp", BearerError::Missing).into_response();
+        assert_eq!(missing.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            missing.headers()[header::WWW_AUTHENTICATE],
+            "Bearer realm=\"mcp\""
+        );
+
+        let invalid = ApiError::unauthorized("mcp", BearerError::InvalidToken).into_response();
+        assert_eq!(invalid.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            invalid.headers()[header::WWW_AUTHENTICATE],
+            "Bearer realm=\"mcp\", error=\"invalid_token\", \
+             error_description=\"The bearer token is not recognized\""
+        );
+        let body = invalid.into_body().collect().await.unwrap().to_bytes();
+        let json: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"]["code"], "unauthorized");
+        assert_eq!(json["error"]["details"]["error"], "invalid_token");
+
+        // Existing details are kept alongside the OAuth parameters.
+        let refused = ApiError::new(StatusCode::FORBIDDEN, "out_of_scope", "nope")
+            .with_details(serde_json::json!({"scope": "team-a"}))
+            .with_bearer_challenge("widgets-refresh", BearerError::InsufficientScope)
+            .into_response();
+        let challenge = refused.headers()[header::WWW_AUTHENTICATE]
+            .to_str()
+            .unwrap();
+        assert!(challenge.contains("error=\"insufficient_scope\""));
+        let body = refused.into_body().collect().await.unwrap().to_bytes();
+        let json: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"]["details"]["scope"], "team-a");
+        assert_eq!(json["error"]["details"]["error"], "insufficient_scope");
+    }
+}
@@ -0,0 +1,272 @@
+//! In-process bus for events that several features react to.
+//!
+//! Request handlers publish what happened (a reload finished, a widget was called, a session
+//! started, a caller was rate limited) and move on. Argument analytics, webhook deliveries, the
+//! audit log and per-session list change notifications each subscribe to the bus and pick out the
+//! events they need, so adding one of them does not touch the handlers.
+//!
+//! Delivery is best effort: a subscriber that falls more than 1024 events behind skips the ones it
+//! missed and logs a warning. Subscribers started by [`EventBus::spawn_subscriber`] are supervised
+//! and appear under `background_tasks` in `GET /internal/widgets/status`.
+
+use std::{
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use serde_json::Value as JsonValue;
+use tokio::sync::broadcast;
+
+use crate::{
+    supervisor::Supervisor,
+    widgets::{self, LoadError, RegistryChange, RegistryReloadOutcome, Widget},
+};
+
+/// Subscribers that fall further behind than this miss events.
+const EVENT_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// A full reload or manifest path switch finished. Deduplicated reloads are not published.
+    RegistryReloaded {
+        /// Operator action that started it, e.g. `refresh`, `watch` or `rollback`.
+        trigger: &'static str,
+        result: Arc<Result<RegistryReloadOutcome, LoadError>>,
+    },
+    /// One widget was refreshed in place.
+    WidgetRefreshed(Arc<Widget>),
+    /// A new registry was installed, by any means; see [`widgets::subscribe_registry_changes`].
+    RegistryChanged(RegistryChange),
+    /// A tool call passed validation and is about to run.
+    WidgetCalled {
+        widget: Arc<Widget>,
+        /// `None` when the call opted out of telemetry.
+        arguments: Option<JsonValue>,
+    },
+    /// An MCP client sent `initialize`.
+    SessionStarted {
+        client: String,
+        protocol_version: String,
+    },
+    /// A request was turned away by a rate limit.
+    RateLimited {
+        limit: RateLimit,
+        retry_after: Duration,
+    },
+}
+
+/// The rate limit a request ran into.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RateLimit {
+    /// `POST /internal/widgets/refresh`, per client address.
+    Refresh { ip: IpAddr },
+    /// A widget's `rateLimit`, per caller.
+    Tool { tool: String, caller: String },
+}
+
+impl Event {
+    pub fn registry_reloaded(
+        trigger: &'static str,
+        result: &Result<RegistryReloadOutcome, LoadError>,
+    ) -> Self {
+        Event::RegistryReloaded {
+            trigger,
+            result: Arc::new(widgets::duplicate_result(result)),
+        }
+    }
+}
+
+/// Publishes events to every subscriber. Clones share subscribers.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<Event>,
+    forwarding_registry_changes: Arc<AtomicBool>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(EVENT_CAPACITY).0,
+            forwarding_registry_changes: Arc::default(),
+        }
+    }
+}
+
+impl EventBus {
+    /// Sends `event` to current subscribers; with none, it is dropped.
+    pub fn publish(&self, event: Event) {
+        let _ = self.sender.send(event);
+    }
+
+    /// Receives events published after the call.
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+
+    /// Calls `observe` with every event, from a task named `name`, until the bus is dropped.
+    ///
+    /// Events published after this call are observed even if the task has not started yet. A
+    /// restart after a panic subscribes again and skips what was published in between.
+    pub fn spawn_subscriber<F>(&self, supervisor: &Supervisor, name: &'static str, observe: F)
+    where
+        F: Fn(&Event) + Send + Sync + 'static,
+    {
+        let observe = Arc::new(observe);
+        let sender = self.sender.downgrade();
+        let mut first = Some(self.sender.subscribe());
+        supervisor.spawn(name, move || {
+            let receiver = first
+                .take()
+                .or_else(|| sender.upgrade().map(|sender| sender.subscribe()));
+            let observe = Arc::clone(&observe);
+            async move {
+                let Some(mut receiver) = receiver else {
+                    return;
+                };
+                loop {
+                    match receiver.recv().await {
+                        Ok(event) => observe(&event),
+                        Err(broadcast::error::RecvError::Lagged(missed)) => {
+                            tracing::warn!(
+                                subscriber = name,
+                                missed,
+                                "Event subscriber fell behind"
+                            );
+                        }
+                        Err(broadcast::error::RecvError::Closed) => return,
+                    }
+                }
+            }
+        });
+    }
+
+    /// Republishes every registry swap as [`Event::RegistryChanged`], until the bus is dropped.
+    /// Only the first call on a bus and its clones starts forwarding.
+    pub fn forward_registry_changes(&self, supervisor: &Supervisor) {
+        if self
+            .forwarding_registry_changes
+            .swap(true, Ordering::SeqCst)
+        {
+            return;
+        }
+        let sender = self.sender.downgrade();
+        let mut first = Some(widgets::subscribe_registry_changes());
+        supervisor.spawn("registry_change_events", move || {
+            let mut changes = first
+                .take()
+                .unwrap_or_else(widgets::subscribe_registry_changes);
+            let sender = sender.clone();
+            async move {
+                loop {
+                    let event = match changes.recv().await {
+                        Ok(change) => Event::RegistryChanged(change),
+                        Err(broadcast::error::RecvError::Lagged(missed)) => {
+                            tracing::warn!(missed, "Registry change forwarding fell behind");
+                            continue;
+                        }
+                        Err(broadcast::error::RecvError::Closed) => return,
+                    };
+                    let Some(sender) = sender.upgrade() else {
+                        return;
+                    };
+                    let _ = sender.send(event);
+                }
+            }
+        });
+    }
+}
+
+/// Writes reloads, session starts and rate limit rejections to the `pizzaz::audit` log target.
+pub fn audit(event: &Event) {
+    match event {
+        Event::RegistryReloaded { trigger, result } => match result.as_ref() {
+            Ok(outcome) if outcome.not_modified => {}
+            Ok(outcome) => tracing::info!(
+                target: "pizzaz::audit",
+                trigger,
+                widgets = outcome.widget_count,
+                "Registry reloaded"
+            ),
+            Err(error) => tracing::info!(
+                target: "pizzaz::audit",
+                trigger,
+                error = %error,
+                "Registry reload failed"
+            ),
+        },
+        Event::SessionStarted {
+            client,
+            protocol_version,
+        } => tracing::info!(
+            target: "pizzaz::audit",
+            client = %client,
+            protocol_version = %protocol_version,
+            "MCP session started"
+        ),
+        Event::RateLimited {
+            limit: RateLimit::Refresh { ip },
+            retry_after,
+        } => tracing::info!(
+            target: "pizzaz::audit",
+            ip = %ip,
+            retry_after_ms = retry_after.as_millis() as u64,
+            "Refresh rejected by rate limit"
+        ),
+        Event::RateLimited {
+            limit: RateLimit::Tool { tool, caller },
+            retry_after,
+        } => tracing::info!(
+            target: "pizzaz::audit",
+            tool = %tool,
+            caller = %caller,
+            retry_after_ms = retry_after.as_millis() as u64,
+            "Tool call rejected by rate limit"
+        ),
+        Event::WidgetRefreshed(_) | Event::RegistryChanged(_) | Event::WidgetCalled { .. } => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn subscribers_see_events_published_before_their_task_starts() {
+        let bus = EventBus::default();
+        let supervisor = Supervisor::default();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&seen);
+        bus.spawn_subscriber(&supervisor, "recorder", move |event| {
+            if let Event::SessionStarted { client, .. } = event {
+                recorded.lock().unwrap().push(client.clone());
+            }
+        });
+
+        bus.publish(Event::SessionStarted {
+            client: "inspector".to_string(),
+            protocol_version: "2025-06-18".to_string(),
+        });
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while seen.lock().unwrap().is_empty() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("event observed");
+        assert_eq!(*seen.lock().unwrap(), ["inspector"]);
+
+        drop(bus);
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while supervisor.status()["recorder"].state != crate::supervisor::TaskState::Finished {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("subscriber stops with the bus");
+    }
+}
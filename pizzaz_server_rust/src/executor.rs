@@ -0,0 +1,477 @@
+//! Tool executors and their runtime bindings.
+//!
+//! Every widget tool call is carried out by a named [`ToolExecutor`]. Widgets use
+//! [`DEFAULT_EXECUTOR`] unless an operator binds them to another one with
+//! `PUT /internal/widgets/{id}/executor`, e.g. to take a misbehaving integration offline during an
+//! incident without a redeploy. Bindings live in memory and reset on restart.
+//!
+//! Each call gets `WIDGETS_EXECUTOR_TIMEOUT_MS` (30 seconds by default) of wall-clock time. One
+//! that runs longer is dropped and fails with a retryable `tool_timeout`, counted in the status
+//! endpoint. This is a per-call timeout only: executors run in-process, so there is no CPU or
+//! memory budget, and dropping a call stops it only at its next `.await`. Work an executor moved
+//! to another thread, or CPU-bound code that never yields, keeps running after the timeout.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
+
+use axum::{
+    body::Bytes,
+    extract::{rejection::BytesRejection, ConnectInfo, Path},
+    http::HeaderMap,
+    Extension, Json,
+};
+use futures::future::BoxFuture;
+use rmcp::model::Content;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map as JsonMap, Value as JsonValue};
+
+use crate::{
+    auth::Identity, egress, error::ApiError, handler::ToolCallError, secrets, types::ToolInput,
+    widgets, widgets::Widget, AppState,
+};
+
+/// Executor used by widgets without an explicit binding; serves the manifest's canned response.
+pub const DEFAULT_EXECUTOR: &str = "mock";
+
+/// Built-in executor that refuses every call, for taking a tool offline.
+pub const DISABLED_EXECUTOR: &str = "disabled";
+
+const EXECUTOR_DOCS: &str = "executor-bindings";
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A validated tool call handed to an executor.
+pub struct Invocation<'a> {
+    pub widget: &'a Widget,
+    pub input: &'a ToolInput,
+    pub identity: &'a Identity,
+    /// Feature flags evaluated for this widget when the call started.
+    pub flags: &'a JsonMap<String, JsonValue>,
+}
+
+impl Invocation<'_> {
+    /// HTTP client for calls to third-party services, bound by the egress allowlist.
+    pub fn http(&self) -> egress::EgressClient {
+        egress::client(&self.widget.id)
+    }
+
+    /// An integration credential, from the configured secrets provider.
+    pub async fn secret(&self, name: &str) -> Result<secrets::Secret, secrets::SecretsError> {
+        secrets::get(name).await
+    }
+}
+
+/// What an executor produced; the handler adds `_meta` and applied defaults.
+pub struct ExecutorOutput {
+    pub content: Vec<Content>,
+    pub structured_content: JsonMap<String, JsonValue>,
+}
+
+/// A failure reported as tool output (`isError: true`) rather than as a JSON-RPC error.
+///
+/// The widget still renders and finds the failure under `structuredContent.error`, so it can show
+/// a friendly error card instead of the conversation surfacing a raw protocol error.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, thiserror::Error)]
+#[error("{title}: {detail}")]
+pub struct ToolFailure {
+    /// Stable machine-readable code, e.g. `tool_disabled`.
+    pub code: String,
+    /// Short heading suitable for an error card.
+    pub title: String,
+    /// One or two sentences explaining what happened.
+    pub detail: String,
+    /// True when calling again later may succeed.
+    pub retryable: bool,
+}
+
+impl ToolFailure {
+    pub fn new(
+        code: impl Into<String>,
+        title: impl Into<String>,
+        detail: impl Into<String>,
+    ) -> Self {
+        Self {
+            code: code.into(),
+            title: title.into(),
+            detail: detail.into(),
+            retryable: false,
+        }
+    }
+
+    pub fn retryable(mut self) -> Self {
+        self.retryable = true;
+        self
+    }
+
+    /// `structuredContent` for the failed result: `{"error": {code, title, detail, retryable}}`.
+    pub fn structured_content(&self) -> JsonValue {
+        serde_json::json!({ "error": self })
+    }
+}
+
+/// Carries out widget tool calls.
+///
+/// Failures the widget should render are returned as [`ToolCallError::Failed`].
+pub trait ToolExecutor: Send + Sync {
+    fn execute<'a>(
+        &'a self,
+        invocation: Invocation<'a>,
+    ) -> BoxFuture<'a, Result<ExecutorOutput, ToolCallError>>;
+}
+
+/// Echoes the requested topping alongside the widget's manifest response text.
+struct MockExecutor;
+
+impl ToolExecutor for MockExecutor {
+    fn execute<'a>(
+        &'a self,
+        invocation: Invocation<'a>,
+    ) -> BoxFuture<'a, Result<ExecutorOutput, ToolCallError>> {
+        Box::pin(async move {
+            let mut structured = JsonMap::new();
+            structured.insert(
+                "pizzaTopping".to_string(),
+                JsonValue::String(invocation.input.pizza_topping.clone()),
+            );
+            Ok(ExecutorOutput {
+                content: vec![Content::text(invocation.widget.response_text())],
+                structured_content: structured,
+            })
+        })
+    }
+}
+
+struct DisabledExecutor;
+
+impl ToolExecutor for DisabledExecutor {
+    fn execute<'a>(
+        &'a self,
+        invocation: Invocation<'a>,
+    ) -> BoxFuture<'a, Result<ExecutorOutput, ToolCallError>> {
+        Box::pin(async move {
+            Err(ToolCallError::Failed(
+                ToolFailure::new(
+                    "tool_disabled",
+                    "Temporarily unavailable",
+                    format!(
+                        "{} has been switched off by an operator. Try again later.",
+                        invocation.widget.title
+                    ),
+                )
+                .retryable(),
+            ))
+        })
+    }
+}
+
+/// Named executors plus the widget bindings that select between them.
+///
+/// Clones share state, so the MCP handler, the simulator and the admin API all see one table.
+#[derive(Clone)]
+pub struct Executors {
+    inner: Arc<RwLock<ExecutorTable>>,
+    timeout: Duration,
+    timed_out: Arc<AtomicU64>,
+}
+
+struct ExecutorTable {
+    available: BTreeMap<String, Arc<dyn ToolExecutor>>,
+    bindings: BTreeMap<String, String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown executor: {0}")]
+pub struct UnknownExecutor(pub String);
+
+impl Default for Executors {
+    fn default() -> Self {
+        let mut available: BTreeMap<String, Arc<dyn ToolExecutor>> = BTreeMap::new();
+        available.insert(DEFAULT_EXECUTOR.to_string(), Arc::new(MockExecutor));
+        available.insert(DISABLED_EXECUTOR.to_string(), Arc::new(DisabledExecutor));
+        Self {
+            inner: Arc::new(RwLock::new(ExecutorTable {
+                available,
+                bindings: BTreeMap::new(),
+            })),
+            timeout: DEFAULT_TIMEOUT,
+            timed_out: Arc::default(),
+        }
+    }
+}
+
+impl fmt::Debug for Executors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Executors")
+            .field("available", &self.names())
+            .field("bindings", &self.bindings())
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+impl Executors {
+    /// The built-in executors, with the call timeout from `WIDGETS_EXECUTOR_TIMEOUT_MS`.
+    pub(crate) fn from_env() -> Self {
+        let timeout = std::env::var("WIDGETS_EXECUTOR_TIMEOUT_MS")
+            .ok()
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .filter(|millis| *millis > 0)
+            .map_or(DEFAULT_TIMEOUT, Duration::from_millis);
+        Self::default().with_timeout(timeout)
+    }
+
+    /// Fails calls that run longer than `timeout` of wall-clock time with a retryable
+    /// `tool_timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Calls that failed because they ran past the timeout, across every clone.
+    pub fn timeouts(&self) -> u64 {
+        self.timed_out.load(Ordering::Relaxed)
+    }
+
+    /// Runs `invocation` on `executor`, dropping it once the timeout passes.
+    pub(crate) async fn execute(
+        &self,
+        executor: &dyn ToolExecutor,
+        invocation: Invocation<'_>,
+    ) -> Result<ExecutorOutput, ToolCallError> {
+        let widget = invocation.widget;
+        match tokio::time::timeout(self.timeout, executor.execute(invocation)).await {
+            Ok(outcome) => outcome,
+            Err(_) => {
+                self.timed_out.fetch_add(1, Ordering::Relaxed);
+                let timeout_ms = self.timeout.as_millis();
+                tracing::warn!(tool = %widget.id, timeout_ms, "Tool executor timed out");
+                Err(ToolCallError::Failed(
+                    ToolFailure::new(
+                        "tool_timeout",
+                        "Took too long",
+                        format!("{} did not finish within {timeout_ms} ms.", widget.title),
+                    )
+                    .retryable(),
+                ))
+            }
+        }
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, ExecutorTable> {
+        self.inner.read().expect("executor table lock poisoned")
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, ExecutorTable> {
+        self.inner.write().expect("executor table lock poisoned")
+    }
+
+    /// Makes an executor available for binding, replacing any executor with the same name.
+    pub fn register(&self, name: impl Into<String>, executor: Arc<dyn ToolExecutor>) {
+        self.write().available.insert(name.into(), executor);
+    }
+
+    /// Names of every registered executor, sorted.
+    pub fn names(&self) -> Vec<String> {
+        self.read().available.keys().cloned().collect()
+    }
+
+    /// Explicit bindings by widget id; unbound widgets use [`DEFAULT_EXECUTOR`].
+    pub fn bindings(&self) -> BTreeMap<String, String> {
+        self.read().bindings.clone()
+    }
+
+    /// Name of the executor serving `widget_id`.
+    pub fn binding(&self, widget_id: &str) -> String {
+        self.read()
+            .bindings
+            .get(widget_id)
+            .cloned()
+            .unwrap_or_else(|| DEFAULT_EXECUTOR.to_string())
+    }
+
+    /// Routes `widget_id` to `executor`, returning the executor it was using before.
+    pub fn bind(&self, widget_id: &str, executor: &str) -> Result<String, UnknownExecutor> {
+        let mut table = self.write();
+        if !table.available.contains_key(executor) {
+            return Err(UnknownExecutor(executor.to_string()));
+        }
+        let previous = table
+            .bindings
+            .insert(widget_id.to_string(), executor.to_string());
+        Ok(previous.unwrap_or_else(|| DEFAULT_EXECUTOR.to_string()))
+    }
+
+    /// Returns `widget_id` to the default executor, returning the executor it was using before.
+    pub fn unbind(&self, widget_id: &str) -> String {
+        self.write()
+            .bindings
+            .remove(widget_id)
+            .unwrap_or_else(|| DEFAULT_EXECUTOR.to_string())
+    }
+
+    /// The executor for `widget_id`. A binding whose executor has gone away falls back to the default.
+    pub(crate) fn resolve(&self, widget_id: &str) -> Arc<dyn ToolExecutor> {
+        let table = self.read();
+        table
+            .bindings
+            .get(widget_id)
+            .and_then(|name| table.available.get(name))
+            .or_else(|| table.available.get(DEFAULT_EXECUTOR))
+            .cloned()
+            .unwrap_or_else(|| Arc::new(MockExecutor))
+    }
+
+    pub(crate) fn status(&self) -> ExecutorStatus {
+        ExecutorStatus {
+            timeout_ms: self.timeout.as_millis(),
+            timeouts: self.timeouts(),
+            available: self.names(),
+            bindings: widgets::get_all_widgets()
+                .iter()
+                .map(|widget| (widget.id.clone(), self.binding(&widget.id)))
+                .collect(),
+        }
+    }
+}
+
+/// Executor names and bindings as shown in the status endpoint.
+#[derive(Debug, Serialize)]
+pub struct ExecutorStatus {
+    pub available: Vec<String>,
+    /// The executor serving each loaded widget, including defaults.
+    pub bindings: BTreeMap<String, String>,
+    /// Wall-clock time each executor call may take.
+    pub timeout_ms: u128,
+    /// Calls failed with `tool_timeout` since startup.
+    pub timeouts: u64,
+}
+
+#[derive(Deserialize)]
+struct BindRequest {
+    executor: String,
+}
+
+#[derive(Serialize)]
+pub(crate) struct BindingResponse {
+    widget_id: String,
+    executor: String,
+    previous: String,
+}
+
+fn ensure_widget(id: &str) -> Result<(), ApiError> {
+    if widgets::get_widget_by_id(id).is_none() {
+        return Err(ApiError::not_found(format!("Unknown widget: {id}"))
+            .with_docs(EXECUTOR_DOCS)
+            .with_details(serde_json::json!({ "widget_id": id })));
+    }
+    Ok(())
+}
+
+/// `PUT /internal/widgets/{id}/executor`: routes a widget's calls to another executor.
+pub(crate) async fn bind_executor_handler(
+    Extension(state): Extension<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    body: Result<Bytes, BytesRejection>,
+) -> Result<Json<BindingResponse>, ApiError> {
+    crate::require_operator(&state, addr, &headers)?;
+    let request: BindRequest = serde_json::from_slice(&body?).map_err(|error| {
+        ApiError::bad_request("invalid_body", format!("Invalid request body: {error}"))
+            .with_docs(EXECUTOR_DOCS)
+    })?;
+    ensure_widget(&id)?;
+
+    let previous = state
+        .executors
+        .bind(&id, &request.executor)
+        .map_err(|error| {
+            ApiError::bad_request("unknown_executor", error.to_string())
+                .with_docs(EXECUTOR_DOCS)
+                .with_details(serde_json::json!({ "available": state.executors.names() }))
+        })?;
+    tracing::info!(
+        ip = %addr.ip(),
+        widget_id = %id,
+        executor = %request.executor,
+        previous = %previous,
+        "Executor binding changed"
+    );
+    Ok(Json(BindingResponse {
+        widget_id: id,
+        executor: request.executor,
+        previous,
+    }))
+}
+
+/// `DELETE /internal/widgets/{id}/executor`: returns a widget to the default executor.
+pub(crate) async fn unbind_executor_handler(
+    Extension(state): Extension<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<BindingResponse>, ApiError> {
+    crate::require_operator(&state, addr, &headers)?;
+    ensure_widget(&id)?;
+
+    let previous = state.executors.unbind(&id);
+    tracing::info!(
+        ip = %addr.ip(),
+        widget_id = %id,
+        previous = %previous,
+        "Executor binding reset to default"
+    );
+    Ok(Json(BindingResponse {
+        widget_id: id,
+        executor: DEFAULT_EXECUTOR.to_string(),
+        previous,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failure_serializes_under_error_key() {
+        let failure = ToolFailure::new("oven_cold", "Oven not ready", "Preheating.").retryable();
+        assert_eq!(
+            failure.structured_content(),
+            serde_json::json!({
+                "error": {
+                    "code": "oven_cold",
+                    "title": "Oven not ready",
+                    "detail": "Preheating.",
+                    "retryable": true,
+                }
+            })
+        );
+        assert_eq!(failure.to_string(), "Oven not ready: Preheating.");
+    }
+
+    #[test]
+    fn bind_and_unbind_report_previous_executor() {
+        let executors = Executors::default();
+        assert_eq!(executors.binding("pizza-map"), DEFAULT_EXECUTOR);
+
+        assert_eq!(
+            executors.bind("pizza-map", DISABLED_EXECUTOR).unwrap(),
+            DEFAULT_EXECUTOR
+        );
+        assert_eq!(executors.binding("pizza-map"), DISABLED_EXECUTOR);
+        assert!(executors.bind("pizza-map", "live").is_err());
+        assert_eq!(executors.binding("pizza-map"), DISABLED_EXECUTOR);
+
+        assert_eq!(executors.unbind("pizza-map"), DISABLED_EXECUTOR);
+        assert_eq!(executors.binding("pizza-map"), DEFAULT_EXECUTOR);
+        assert!(executors.bindings().is_empty());
+    }
+}
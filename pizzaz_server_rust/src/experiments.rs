@@ -0,0 +1,184 @@
+//! A/B experiment assignment for widget tool calls.
+//!
+//! Experiments are configured with `WIDGETS_EXPERIMENTS`, a JSON array:
+//!
+//! ```json
+//! [{ "name": "map-layout", "widgets": ["pizza-map"],
+//!    "variants": [{ "name": "control" }, { "name": "grid", "weight": 2 }] }]
+//! ```
+//!
+//! A caller is bucketed by hashing its MCP session id with the experiment name, so it sees the
+//! same variant for the whole session and different experiments bucket independently. Callers
+//! without a session fall back to their rate limit key. Each assignment a result carries is logged
+//! as an exposure under the `pizzaz::exposure` target.
+
+use std::sync::Arc;
+
+use serde::Deserialize;
+use serde_json::{Map as JsonMap, Value as JsonValue};
+use sha2::{Digest, Sha256};
+
+/// `_meta` key carrying the caller's variant per experiment.
+pub const EXPERIMENTS_META_KEY: &str = "pizzaz/experiments";
+
+/// `structuredContent` key carrying the caller's variant per experiment.
+pub const EXPERIMENTS_KEY: &str = "experiments";
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Experiment {
+    pub name: String,
+    pub variants: Vec<Variant>,
+    /// Widgets the experiment applies to; empty means every widget.
+    #[serde(default)]
+    pub widgets: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Variant {
+    pub name: String,
+    /// Relative share of callers; `0` takes the variant out of rotation.
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+impl Experiment {
+    fn applies_to(&self, widget_id: &str) -> bool {
+        self.widgets.is_empty() || self.widgets.iter().any(|id| id == widget_id)
+    }
+
+    /// The variant for `unit`, or `None` when no variant has weight.
+    pub fn assign(&self, unit: &str) -> Option<&Variant> {
+        let total: u64 = self.variants.iter().map(|v| u64::from(v.weight)).sum();
+        if total == 0 {
+            return None;
+        }
+        let digest = Sha256::new()
+            .chain_update(unit.as_bytes())
+            .chain_update(b"\0")
+            .chain_update(self.name.as_bytes())
+            .finalize();
+        let mut bucket =
+            u64::from_be_bytes(digest[..8].try_into().expect("digest is 32 bytes")) % total;
+        self.variants.iter().find(|variant| {
+            let weight = u64::from(variant.weight);
+            if bucket < weight {
+                true
+            } else {
+                bucket -= weight;
+                false
+            }
+        })
+    }
+}
+
+/// The configured experiments. Clones share them.
+#[derive(Debug, Clone, Default)]
+pub struct Experiments {
+    experiments: Arc<Vec<Experiment>>,
+}
+
+impl Experiments {
+    pub fn new(experiments: Vec<Experiment>) -> Self {
+        Self {
+            experiments: Arc::new(experiments),
+        }
+    }
+
+    /// Experiments from `WIDGETS_EXPERIMENTS`; none when unset or invalid.
+    pub(crate) fn from_env() -> Self {
+        let Ok(json) = std::env::var("WIDGETS_EXPERIMENTS") else {
+            return Self::default();
+        };
+        match serde_json::from_str(&json) {
+            Ok(experiments) => Self::new(experiments),
+            Err(error) => {
+                tracing::warn!(error = %error, "Ignoring invalid WIDGETS_EXPERIMENTS");
+                Self::default()
+            }
+        }
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.experiments.iter().map(|e| e.name.clone()).collect()
+    }
+
+    /// Variant names by experiment for `unit` calling `widget_id`, logging each exposure.
+    pub fn assign(&self, widget_id: &str, unit: &str) -> JsonMap<String, JsonValue> {
+        let assignments = self.variants(widget_id, unit);
+        for (experiment, variant) in &assignments {
+            tracing::info!(
+                target: "pizzaz::exposure",
+                experiment = %experiment,
+                variant = %variant.as_str().unwrap_or_default(),
+                widget = widget_id,
+                unit,
+                "Experiment exposure"
+            );
+        }
+        assignments
+    }
+
+    /// Variant names by experiment for `unit` calling `widget_id`, without logging exposures.
+    pub fn variants(&self, widget_id: &str, unit: &str) -> JsonMap<String, JsonValue> {
+        self.experiments
+            .iter()
+            .filter(|experiment| experiment.applies_to(widget_id))
+            .filter_map(|experiment| {
+                let variant = experiment.assign(unit)?;
+                Some((
+                    experiment.name.clone(),
+                    JsonValue::String(variant.name.clone()),
+                ))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn experiments() -> Experiments {
+        Experiments::new(
+            serde_json::from_value(json!([
+                {
+                    "name": "map-layout",
+                    "widgets": ["pizza-map"],
+                    "variants": [{ "name": "control" }, { "name": "grid", "weight": 3 }]
+                },
+                { "name": "copy", "variants": [{ "name": "short" }, { "name": "long" }] },
+                { "name": "paused", "variants": [{ "name": "only", "weight": 0 }] }
+            ]))
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn assignment_is_stable_and_scoped_to_widgets() {
+        let experiments = experiments();
+        let first = experiments.assign("pizza-map", "session-1");
+        assert_eq!(first, experiments.assign("pizza-map", "session-1"));
+        assert_eq!(first.keys().collect::<Vec<_>>(), vec!["copy", "map-layout"]);
+
+        let other_widget = experiments.assign("pizza-list", "session-1");
+        assert_eq!(other_widget.keys().collect::<Vec<_>>(), vec!["copy"]);
+        assert_eq!(other_widget["copy"], first["copy"]);
+    }
+
+    #[test]
+    fn buckets_follow_variant_weights() {
+        let experiment = &experiments().experiments[0];
+        let grid = (0..2_000)
+            .filter(|n| experiment.assign(&format!("session-{n}")).unwrap().name == "grid")
+            .count();
+        // Three quarters of sessions, give or take sampling noise.
+        assert!((1_350..1_650).contains(&grid), "{grid}");
+    }
+}
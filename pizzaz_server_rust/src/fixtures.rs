@@ -0,0 +1,261 @@
+//! JSON-RPC fixtures generated from the loaded registry.
+//!
+//! [`generate_fixtures`] renders `tools/list`, `resources/list`, `resources/templates/list`, one
+//! `tools/call` per widget and one `resources/read` per widget resource, each paired with the
+//! response a client receives today. `pizzaz_fixtures` writes them to
+//! `tests/fixtures/generated/`, and the integration tests play every file back against a live
+//! server, so a widget added to the manifest is covered as soon as the fixtures are regenerated.
+//!
+//! Calls use example arguments derived from the tool's input schema: a property's `default`, else
+//! its first `enum` value, else a placeholder of its type.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map as JsonMap, Value as JsonValue};
+
+use crate::{
+    auth::Identity,
+    handler::{CallContext, PizzazServerHandler},
+    widgets, wire_result,
+};
+
+/// Where `pizzaz_fixtures` writes by default, relative to the crate root.
+pub const DEFAULT_FIXTURES_DIR: &str = "tests/fixtures/generated";
+
+/// A JSON-RPC request and the response the server gave it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fixture {
+    /// File stem, e.g. `tools-call-pizza-map`.
+    #[serde(skip)]
+    pub name: String,
+    pub request: JsonValue,
+    pub response: JsonValue,
+}
+
+impl Fixture {
+    pub fn method(&self) -> &str {
+        self.request["method"].as_str().unwrap_or_default()
+    }
+
+    pub fn params(&self) -> JsonValue {
+        self.request["params"].clone()
+    }
+}
+
+/// Renders fixtures for every widget in the current registry, in a stable order.
+pub async fn generate_fixtures(handler: &PizzazServerHandler) -> Vec<Fixture> {
+    let anonymous = Identity::anonymous();
+    let mut fixtures = vec![
+        (
+            "tools-list".to_string(),
+            "tools/list",
+            json!({}),
+            Ok(wire_result(&handler.list_tools_result(&anonymous).await)),
+        ),
+        (
+            "resources-list".to_string(),
+            "resources/list",
+            json!({}),
+            Ok(wire_result(&handler.list_resources_result().await)),
+        ),
+        (
+            "resources-templates-list".to_string(),
+            "resources/templates/list",
+            json!({}),
+            Ok(wire_result(&handler.list_resource_templates_result().await)),
+        ),
+    ];
+
+    for widget in widgets::get_all_widgets() {
+        let arguments = example_arguments(&widget.input_schema);
+        let outcome = handler
+            .call_tool_for_client(&CallContext::default(), &widget.id, arguments.clone())
+            .await
+            .map(|result| wire_result(&result))
+            .map_err(|error| json!(error));
+        fixtures.push((
+            format!("tools-call-{}", file_stem(&widget.id)),
+            "tools/call",
+            json!({ "name": widget.id, "arguments": arguments }),
+            outcome,
+        ));
+    }
+    for widget in widgets::get_all_widgets() {
+        let outcome = handler
+            .read_resource_result(&widget.template_uri)
+            .await
+            .map(|result| wire_result(&result))
+            .map_err(|error| json!(error));
+        fixtures.push((
+            format!("resources-read-{}", file_stem(&widget.id)),
+            "resources/read",
+            json!({ "uri": widget.template_uri }),
+            outcome,
+        ));
+    }
+
+    fixtures
+        .into_iter()
+        .zip(1..)
+        .map(|((name, method, params, outcome), id)| {
+            let mut response = json!({ "jsonrpc": "2.0", "id": id });
+            match outcome {
+                Ok(result) => response["result"] = result,
+                Err(error) => response["error"] = error,
+            }
+            Fixture {
+                name,
+                request: json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }),
+                response,
+            }
+        })
+        .collect()
+}
+
+/// Replaces the `*.json` files in `dir` with `fixtures`, one pretty-printed file each, so
+/// fixtures of removed widgets do not linger.
+pub fn write_fixtures(dir: &Path, fixtures: &[Fixture]) -> io::Result<Vec<PathBuf>> {
+    fs::create_dir_all(dir)?;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            fs::remove_file(path)?;
+        }
+    }
+
+    let mut written = Vec::with_capacity(fixtures.len());
+    for fixture in fixtures {
+        let path = dir.join(format!("{}.json", fixture.name));
+        let mut contents = serde_json::to_string_pretty(fixture).map_err(io::Error::other)?;
+        contents.push('\n');
+        fs::write(&path, contents)?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Reads the fixtures in `dir`, ordered by request id.
+pub fn load_fixtures(dir: &Path) -> io::Result<Vec<Fixture>> {
+    let mut fixtures = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|extension| extension != "json") {
+            continue;
+        }
+        let mut fixture: Fixture = serde_json::from_slice(&fs::read(&path)?).map_err(|error| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {error}", path.display()),
+            )
+        })?;
+        fixture.name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        fixtures.push(fixture);
+    }
+    fixtures.sort_by_key(|fixture| fixture.request["id"].as_u64());
+    Ok(fixtures)
+}
+
+/// Arguments that satisfy the top-level properties of `schema`.
+pub fn example_arguments(schema: &JsonValue) -> JsonValue {
+    let mut arguments = JsonMap::new();
+    for (name, property) in schema["properties"].as_object().into_iter().flatten() {
+        arguments.insert(name.clone(), example_value(property));
+    }
+    JsonValue::Object(arguments)
+}
+
+fn example_value(property: &JsonValue) -> JsonValue {
+    if let Some(default) = property.get("default") {
+        return default.clone();
+    }
+    if let Some(first) = property["enum"]
+        .as_array()
+        .and_then(|values| values.first())
+    {
+        return first.clone();
+    }
+    let kind = match &property["type"] {
+        JsonValue::Array(kinds) => kinds
+            .iter()
+            .filter_map(JsonValue::as_str)
+            .find(|kind| *kind != "null")
+            .unwrap_or("string"),
+        kind => kind.as_str().unwrap_or("string"),
+    };
+    match kind {
+        "integer" | "number" => json!(1),
+        "boolean" => json!(true),
+        "array" => json!([]),
+        "object" => example_arguments(property),
+        _ => json!("fixture"),
+    }
+}
+
+fn file_stem(id: &str) -> String {
+    id.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::initialize_widgets_for_tests;
+
+    #[test]
+    fn example_arguments_prefer_defaults_then_enums() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "pizzaTopping": { "type": "string" },
+                "size": { "type": "string", "enum": ["small", "large"] },
+                "slices": { "type": "integer", "default": 8 },
+                "hot": { "type": ["boolean", "null"] },
+                "crust": { "type": "object", "properties": { "thin": { "type": "boolean" } } }
+            }
+        });
+        assert_eq!(
+            example_arguments(&schema),
+            json!({
+                "pizzaTopping": "fixture",
+                "size": "small",
+                "slices": 8,
+                "hot": true,
+                "crust": { "thin": true }
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn fixtures_round_trip_through_a_directory() {
+        initialize_widgets_for_tests();
+        let fixtures = generate_fixtures(&PizzazServerHandler::new()).await;
+        let widgets = widgets::get_all_widgets().len();
+        assert_eq!(fixtures.len(), 3 + 2 * widgets);
+        assert_eq!(fixtures[0].method(), "tools/list");
+        assert!(fixtures
+            .iter()
+            .any(|fixture| fixture.name == "tools-call-pizza-map"));
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("tools-call-removed.json"), "{}").unwrap();
+        write_fixtures(dir.path(), &fixtures).unwrap();
+        assert_eq!(load_fixtures(dir.path()).unwrap(), fixtures);
+    }
+}
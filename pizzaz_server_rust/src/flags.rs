@@ -0,0 +1,243 @@
+//! Feature flags evaluated on every tool call and resource read.
+//!
+//! Flags let widget behaviour be toggled without redeploying the manifest. They come from a JSON
+//! file named by `WIDGETS_FLAGS_FILE`, re-read whenever it changes, overlaid with inline JSON from
+//! `WIDGETS_FLAGS`. Both use the same shape:
+//!
+//! ```json
+//! { "flags": { "newCheckout": true }, "widgets": { "pizza-map": { "showTraffic": false } } }
+//! ```
+//!
+//! A widget sees the global `flags` overridden by its own entry under `widgets`. Executors get the
+//! evaluated flags in their [`Invocation`](crate::executor::Invocation), tool results carry them
+//! under `structuredContent.flags`, and resource reads under `_meta["pizzaz/flags"]`. Other sources,
+//! such as a flag service, plug in through [`FlagProvider`].
+
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use serde::Deserialize;
+use serde_json::{Map as JsonMap, Value as JsonValue};
+
+/// `structuredContent` key carrying the flags a tool call was evaluated with.
+pub const FLAGS_KEY: &str = "flags";
+
+/// Resource `_meta` key carrying the flags a read was evaluated with.
+pub const FLAGS_META_KEY: &str = "pizzaz/flags";
+
+/// Answers which flags apply to a widget right now.
+///
+/// Called on every tool call and resource read, so implementations backed by a remote service
+/// should serve a cached copy and refresh it in the background.
+pub trait FlagProvider: Send + Sync {
+    fn evaluate(&self, widget_id: &str) -> JsonMap<String, JsonValue>;
+}
+
+/// Flags shared by every widget plus per-widget overrides.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FlagSet {
+    #[serde(default)]
+    pub flags: JsonMap<String, JsonValue>,
+    #[serde(default)]
+    pub widgets: HashMap<String, JsonMap<String, JsonValue>>,
+}
+
+impl FlagSet {
+    /// The global flags overridden by `widget_id`'s own.
+    pub fn evaluate(&self, widget_id: &str) -> JsonMap<String, JsonValue> {
+        let mut flags = self.flags.clone();
+        if let Some(overrides) = self.widgets.get(widget_id) {
+            flags.extend(overrides.clone());
+        }
+        flags
+    }
+}
+
+/// Reads flags from a JSON file, reloading it when its modification time changes.
+///
+/// A file that cannot be read or parsed keeps the last flags that loaded, so a bad edit does not
+/// flip every flag off at once.
+pub struct FileFlagProvider {
+    path: Option<PathBuf>,
+    inline: FlagSet,
+    loaded: Mutex<Loaded>,
+}
+
+#[derive(Default)]
+struct Loaded {
+    modified: Option<SystemTime>,
+    flags: Arc<FlagSet>,
+}
+
+impl FileFlagProvider {
+    /// Watches `path`, if any; `inline` flags take precedence over the file's.
+    pub fn new(path: Option<PathBuf>, inline: FlagSet) -> Self {
+        Self {
+            path,
+            inline,
+            loaded: Mutex::new(Loaded::default()),
+        }
+    }
+
+    fn current(&self) -> Arc<FlagSet> {
+        let mut loaded = self.loaded.lock().expect("flag cache lock poisoned");
+        let Some(path) = &self.path else {
+            return loaded.flags.clone();
+        };
+        let modified = std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        if modified.is_some() && modified != loaded.modified {
+            match read_flag_file(path) {
+                Ok(flags) => {
+                    tracing::info!(path = %path.display(), "Loaded feature flags");
+                    loaded.flags = Arc::new(flags);
+                }
+                Err(error) => {
+                    tracing::warn!(path = %path.display(), error = %error, "Keeping previous feature flags");
+                }
+            }
+            loaded.modified = modified;
+        }
+        loaded.flags.clone()
+    }
+}
+
+impl FlagProvider for FileFlagProvider {
+    fn evaluate(&self, widget_id: &str) -> JsonMap<String, JsonValue> {
+        let mut flags = self.current().evaluate(widget_id);
+        flags.extend(self.inline.evaluate(widget_id));
+        flags
+    }
+}
+
+fn read_flag_file(path: &Path) -> anyhow::Result<FlagSet> {
+    let text = std::fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&text)?)
+}
+
+/// The flag provider in use, if any. Clones share it.
+#[derive(Clone, Default)]
+pub struct Flags {
+    provider: Option<Arc<dyn FlagProvider>>,
+}
+
+impl fmt::Debug for Flags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Flags")
+            .field("enabled", &self.provider.is_some())
+            .finish()
+    }
+}
+
+impl Flags {
+    pub fn new(provider: Arc<dyn FlagProvider>) -> Self {
+        Self {
+            provider: Some(provider),
+        }
+    }
+
+    /// Flags from `WIDGETS_FLAGS_FILE` and `WIDGETS_FLAGS`; disabled when neither is set.
+    pub(crate) fn from_env() -> Self {
+        let path = std::env::var_os("WIDGETS_FLAGS_FILE").map(PathBuf::from);
+        let inline = match std::env::var("WIDGETS_FLAGS") {
+            Ok(json) => match serde_json::from_str::<FlagSet>(&json) {
+                Ok(flags) => Some(flags),
+                Err(error) => {
+                    tracing::warn!(error = %error, "Ignoring invalid WIDGETS_FLAGS");
+                    None
+                }
+            },
+            Err(_) => None,
+        };
+        if path.is_none() && inline.is_none() {
+            return Self::default();
+        }
+        Self::new(Arc::new(FileFlagProvider::new(
+            path,
+            inline.unwrap_or_default(),
+        )))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.provider.is_some()
+    }
+
+    /// Flags for `widget_id`; empty when no provider is configured.
+    pub fn evaluate(&self, widget_id: &str) -> JsonMap<String, JsonValue> {
+        self.provider
+            .as_ref()
+            .map(|provider| provider.evaluate(widget_id))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::time::Duration;
+
+    fn flag_set(value: JsonValue) -> FlagSet {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn widget_overrides_win_over_global_flags() {
+        let flags = flag_set(json!({
+            "flags": { "newCheckout": true, "showTraffic": true },
+            "widgets": { "pizza-map": { "showTraffic": false } }
+        }));
+
+        assert_eq!(
+            JsonValue::Object(flags.evaluate("pizza-map")),
+            json!({ "newCheckout": true, "showTraffic": false })
+        );
+        assert_eq!(
+            JsonValue::Object(flags.evaluate("pizza-list")),
+            json!({ "newCheckout": true, "showTraffic": true })
+        );
+    }
+
+    #[test]
+    fn file_changes_apply_to_the_next_evaluation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("flags.json");
+        std::fs::write(
+            &path,
+            r#"{"flags": {"newCheckout": false, "theme": "light"}}"#,
+        )
+        .unwrap();
+        let provider = FileFlagProvider::new(
+            Some(path.clone()),
+            flag_set(json!({ "flags": { "theme": "dark" } })),
+        );
+
+        assert_eq!(
+            JsonValue::Object(provider.evaluate("pizza-map")),
+            json!({ "newCheckout": false, "theme": "dark" })
+        );
+
+        let rewrite = |contents: &str, age: u64| {
+            std::fs::write(&path, contents).unwrap();
+            std::fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(SystemTime::now() + Duration::from_secs(age))
+                .unwrap();
+        };
+        rewrite(r#"{"flags": {"newCheckout": true}}"#, 10);
+        assert_eq!(provider.evaluate("pizza-map")["newCheckout"], true);
+
+        // A broken edit keeps the flags that last loaded.
+        rewrite("{not json", 20);
+        assert_eq!(provider.evaluate("pizza-map")["newCheckout"], true);
+    }
+}
@@ -1,7 +1,25 @@
 //! MCP server handler for Pizzaz widgets
 
-use crate::{types::ToolInput, widgets};
+use crate::{
+    arguments,
+    auth::Identity,
+    batch::{self, BatchConfig},
+    clients::ClientProfiles,
+    consent::{self, Consent},
+    events::{Event, EventBus, RateLimit},
+    executor::{ExecutorOutput, Executors, Invocation, ToolFailure},
+    experiments::{self, Experiments},
+    flags::{self, Flags},
+    jobs::{self, DeferConfig, JobStore},
+    notice::Notices,
+    rate_limit::{CallerKey, PurgeExpired, RateLimitConfig, RateLimiter},
+    supervisor::Supervisor,
+    trace::{TraceContext, TRACEPARENT},
+    types::ToolInput,
+    widgets,
+};
 use anyhow::{Context, Result};
+use axum::{extract::ConnectInfo, http::request::Parts};
 use rmcp::{
     handler::server::ServerHandler,
     model::{
@@ -11,10 +29,112 @@ use rmcp::{
         PaginatedRequestParam, ProtocolVersion, RawResource, RawResourceTemplate, ResourceContents,
         ResourcesCapability, ServerCapabilities, Tool as McpTool, ToolsCapability,
     },
-    service::{NotificationContext, RequestContext, RoleServer},
+    service::{NotificationContext, Peer, RequestContext, RoleServer},
 };
+use serde::Deserialize;
 use serde_json::{Map as JsonMap, Value as JsonValue};
-use std::{future::Future, sync::Arc};
+use std::{
+    collections::HashMap,
+    future::Future,
+    net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, Weak,
+    },
+    time::{Duration, Instant},
+};
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+
+/// JSON-RPC error code returned when a caller exhausts a tool's rate limit budget.
+pub const RATE_LIMITED_ERROR_CODE: i32 = -32029;
+
+/// JSON-RPC error code returned when the widget manifest has never been loaded.
+pub const REGISTRY_NOT_LOADED_ERROR_CODE: i32 = -32033;
+
+/// Machine-readable code carried in results produced while the registry is empty.
+pub const REGISTRY_NOT_LOADED_CODE: &str = "registry_not_loaded";
+
+/// JSON-RPC error code returned when a call outlives the deadline its client asked for.
+pub const DEADLINE_EXCEEDED_ERROR_CODE: i32 = -32032;
+
+/// Request header carrying the MCP session id.
+const SESSION_ID_HEADER: &str = "mcp-session-id";
+
+/// Request header carrying the client's timeout in milliseconds.
+pub const TIMEOUT_HEADER: &str = "x-mcp-timeout-ms";
+
+/// `_meta` key carrying the client's timeout in milliseconds; takes precedence over the header.
+pub const TIMEOUT_META_KEY: &str = "pizzaz/timeoutMs";
+
+/// `_meta` flag asking for argument validation only; the executor is never called.
+pub const VALIDATE_ONLY_META_KEY: &str = "pizzaz/validateOnly";
+
+/// `structuredContent` key listing the schema defaults filled in for omitted arguments.
+pub const APPLIED_DEFAULTS_KEY: &str = "appliedDefaults";
+
+/// Errors produced while invoking a widget tool.
+#[derive(Debug, thiserror::Error)]
+pub enum ToolCallError {
+    #[error("Unknown tool: {0}")]
+    UnknownTool(String),
+    #[error("Invalid tool arguments: {0}")]
+    InvalidArguments(#[source] serde_json::Error),
+    #[error("Rate limit exceeded for {tool}. Retry after {} seconds.", retry_after_seconds(*.retry_after))]
+    RateLimited { tool: String, retry_after: Duration },
+    #[error("Server is too busy to run {tool}; try again shortly")]
+    Overloaded { tool: String, retry_after: Duration },
+    #[error("Widget manifest is not loaded (expected at {}); no tools are available", .manifest_path.display())]
+    RegistryNotLoaded { manifest_path: PathBuf },
+    #[error("Deadline exceeded for {tool}; the client stopped waiting")]
+    DeadlineExceeded { tool: String },
+    #[error("Client went away before {tool} finished")]
+    ClientGone { tool: String },
+    /// Reported to the client as an `isError: true` result, not a JSON-RPC error.
+    #[error(transparent)]
+    Failed(ToolFailure),
+    /// A deferred call's JSON-RPC error, returned again by `get-result`.
+    #[error("{}", .0.message)]
+    DeferredFailure(ErrorData),
+}
+
+impl ToolCallError {
+    pub(crate) fn into_error_data(self) -> ErrorData {
+        match self {
+            ToolCallError::RateLimited { retry_after, .. } => ErrorData::new(
+                model::ErrorCode(RATE_LIMITED_ERROR_CODE),
+                self.to_string(),
+                // Many MCP clients never see HTTP headers, so the wait travels in the error itself.
+                Some(serde_json::json!({
+                    "retryable": true,
+                    "retryAfterMs": retry_after.as_millis() as u64,
+                })),
+            ),
+            ToolCallError::Overloaded { retry_after, .. } => ErrorData::new(
+                model::ErrorCode(crate::load_shed::OVERLOADED_ERROR_CODE),
+                self.to_string(),
+                Some(serde_json::json!({
+                    "retryable": true,
+                    "retryAfterMs": retry_after.as_millis() as u64,
+                })),
+            ),
+            ToolCallError::RegistryNotLoaded { ref manifest_path } => {
+                registry_not_loaded_error(manifest_path)
+            }
+            ToolCallError::DeadlineExceeded { .. } => ErrorData::new(
+                model::ErrorCode(DEADLINE_EXCEEDED_ERROR_CODE),
+                self.to_string(),
+                None,
+            ),
+            ToolCallError::DeferredFailure(error) => error,
+            // Nobody is left to read it, but the request still needs an answer.
+            ToolCallError::ClientGone { .. } => ErrorData::internal_error(self.to_string(), None),
+            other => ErrorData::invalid_params(other.to_string(), None),
+        }
+    }
+}
 
 /// High-level tool information for tests and internal conversion.
 #[derive(Debug, Clone)]
@@ -32,6 +152,8 @@ pub struct WidgetCallResult {
     pub content: Vec<Content>,
     pub structured_content: JsonValue,
     pub meta: Meta,
+    /// Set when an executor reported a [`ToolFailure`]; `structured_content` then holds it.
+    pub is_error: bool,
 }
 
 /// Represents a widget resource entry.
@@ -63,54 +185,642 @@ pub struct WidgetResourceTemplate {
     pub meta: Meta,
 }
 
+/// Request extension cancelled when the HTTP client that sent the request disconnects or stops
+/// reading its response (see [`crate::sse_backpressure`]).
+#[derive(Debug, Clone, Default)]
+pub struct ClientGone(pub CancellationToken);
+
+/// Caller information resolved from the HTTP request that carried an MCP message.
+#[derive(Debug, Clone, Default)]
+pub struct CallContext {
+    pub identity: Identity,
+    pub client_ip: Option<IpAddr>,
+    /// When the client stops waiting for a result, if it said so.
+    pub deadline: Option<tokio::time::Instant>,
+    /// Validate and normalize the arguments without executing the tool.
+    pub validate_only: bool,
+    /// The client's session, for notifications sent after the call has returned.
+    pub peer: Option<Peer<RoleServer>>,
+    /// The caller's trace context, from a `traceparent` in `_meta` or the HTTP headers.
+    pub trace_parent: Option<TraceContext>,
+    /// The MCP session the call arrived on.
+    pub session_id: Option<String>,
+    /// The caller opted out of telemetry, see [`crate::consent`].
+    pub telemetry_opt_out: bool,
+    /// The user's locale from `_meta["openai/locale"]`, which picks widget variants.
+    pub locale: Option<String>,
+    /// Cancelled once the client went away; calls still running are abandoned.
+    pub client_gone: CancellationToken,
+}
+
+impl CallContext {
+    /// Builds a context from the request parts rmcp attaches to each message.
+    fn from_request(context: &RequestContext<RoleServer>) -> Self {
+        let meta_timeout = context
+            .meta
+            .get(TIMEOUT_META_KEY)
+            .and_then(JsonValue::as_u64);
+        let validate_only = context
+            .meta
+            .get(VALIDATE_ONLY_META_KEY)
+            .and_then(JsonValue::as_bool)
+            .unwrap_or(false);
+        let meta_trace_parent = context
+            .meta
+            .get(TRACEPARENT)
+            .and_then(JsonValue::as_str)
+            .and_then(TraceContext::from_traceparent);
+        let telemetry_opt_out = consent::opts_out(&context.meta);
+        let locale = request_locale(&context.meta);
+        let Some(parts) = context.extensions.get::<Parts>() else {
+            return Self {
+                deadline: deadline_from_timeout(meta_timeout),
+                validate_only,
+                telemetry_opt_out,
+                locale,
+                peer: Some(context.peer.clone()),
+                trace_parent: meta_trace_parent,
+                ..Self::default()
+            };
+        };
+        let header_timeout = parts
+            .headers
+            .get(TIMEOUT_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<u64>().ok());
+        let header_trace_parent = parts
+            .headers
+            .get(TRACEPARENT)
+            .and_then(|value| value.to_str().ok())
+            .and_then(TraceContext::from_traceparent);
+        Self {
+            deadline: deadline_from_timeout(meta_timeout.or(header_timeout)),
+            validate_only,
+            telemetry_opt_out,
+            locale,
+            peer: Some(context.peer.clone()),
+            trace_parent: meta_trace_parent.or(header_trace_parent),
+            session_id: parts
+                .headers
+                .get(SESSION_ID_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+            identity: parts
+                .extensions
+                .get::<Identity>()
+                .cloned()
+                .unwrap_or_default(),
+            client_gone: parts
+                .extensions
+                .get::<ClientGone>()
+                .map(|ClientGone(token)| token.clone())
+                .unwrap_or_default(),
+            client_ip: parts
+                .extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip()),
+        }
+    }
+
+    /// Whether the client's deadline has already passed.
+    pub fn deadline_passed(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| deadline <= tokio::time::Instant::now())
+    }
+
+    /// Unit experiments bucket on: the session when known, otherwise the rate limit key.
+    pub fn experiment_unit(&self) -> Option<String> {
+        match (&self.session_id, self.caller_key()) {
+            (Some(session_id), _) => Some(session_id.clone()),
+            (None, CallerKey::Unknown) => None,
+            (None, key) => Some(key.to_string()),
+        }
+    }
+
+    /// Key used to charge rate limit budgets: the subject when authenticated, otherwise the IP.
+    pub fn caller_key(&self) -> CallerKey {
+        match (&self.identity.subject, self.client_ip) {
+            (Some(subject), _) => CallerKey::Subject(subject.clone()),
+            (None, Some(ip)) => CallerKey::Ip(ip),
+            (None, None) => CallerKey::Unknown,
+        }
+    }
+}
+
+/// Per-tool rate limiters built lazily from the manifest's `rateLimit` budgets.
+#[derive(Debug, Default)]
+pub struct ToolRateLimits {
+    limiters: HashMap<String, RateLimiter<CallerKey>>,
+}
+
+impl ToolRateLimits {
+    /// Charges one call against `tool` for `caller`, rebuilding the limiter if the budget changed.
+    pub fn check(
+        &mut self,
+        tool: &str,
+        config: RateLimitConfig,
+        caller: CallerKey,
+        now: Instant,
+    ) -> Result<(), ToolCallError> {
+        let limiter = self
+            .limiters
+            .entry(tool.to_string())
+            .or_insert_with(|| RateLimiter::new(config.max_requests, config.window));
+        if limiter.config() != config {
+            *limiter = RateLimiter::new(config.max_requests, config.window);
+        }
+
+        limiter.check(caller.clone(), now).map_err(|rejection| {
+            tracing::warn!(
+                tool,
+                caller = %caller,
+                retry_after_ms = rejection.retry_after.as_millis() as u64,
+                "Tool rate limit exceeded"
+            );
+            ToolCallError::RateLimited {
+                tool: tool.to_string(),
+                retry_after: rejection.retry_after,
+            }
+        })
+    }
+}
+
+impl PurgeExpired for Mutex<ToolRateLimits> {
+    fn purge_expired(&self, now: Instant) -> usize {
+        self.lock()
+            .expect("tool rate limit lock poisoned")
+            .limiters
+            .values_mut()
+            .map(|limiter| limiter.purge_expired(now))
+            .sum()
+    }
+}
+
 /// MCP server handler for Pizzaz widgets.
+///
+/// Clones share rate limit state, so one handler can be cloned into every session.
 #[derive(Debug, Clone, Default)]
-pub struct PizzazServerHandler;
+pub struct PizzazServerHandler {
+    tool_limits: Arc<Mutex<ToolRateLimits>>,
+    prefetch_hints: bool,
+    lenient_arguments: bool,
+    trace_meta: bool,
+    executors: Executors,
+    flags: Flags,
+    experiments: Experiments,
+    notices: Notices,
+    events: EventBus,
+    consent: Consent,
+    clients: ClientProfiles,
+    batch: Option<BatchConfig>,
+    deferred: Option<DeferConfig>,
+    jobs: JobStore,
+    tool_calls: Arc<AtomicU64>,
+}
 
 impl PizzazServerHandler {
     /// Creates a new handler instance.
     pub fn new() -> Self {
-        Self
+        Self::default()
+    }
+
+    /// Adds `pizzaz/prefetch` hints to tool results (see [`widgets::Widget::prefetch_hints`]).
+    pub fn with_prefetch_hints(mut self, enabled: bool) -> Self {
+        self.prefetch_hints = enabled;
+        self
+    }
+
+    /// Shares an executor table, so bindings changed through the admin API apply to this handler.
+    pub fn with_executors(mut self, executors: Executors) -> Self {
+        self.executors = executors;
+        self
     }
 
-    /// Lists all widget tools for internal use.
+    /// Executors and bindings used by this handler.
+    pub fn executors(&self) -> &Executors {
+        &self.executors
+    }
+
+    /// `tools/call` requests received over MCP by this handler and its clones.
+    pub fn tool_calls(&self) -> u64 {
+        self.tool_calls.load(Ordering::Relaxed)
+    }
+
+    /// Evaluates feature flags from `flags` on every tool call and resource read.
+    pub fn with_flags(mut self, flags: Flags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Assigns callers to `experiments` and reports their variants on tool results.
+    pub fn with_experiments(mut self, experiments: Experiments) -> Self {
+        self.experiments = experiments;
+        self
+    }
+
+    /// Shares the maintenance notice set through the admin API, attaching it to tool results.
+    pub fn with_notices(mut self, notices: Notices) -> Self {
+        self.notices = notices;
+        self
+    }
+
+    /// Publishes session starts, widget calls and rate limit rejections on `events`, and
+    /// announces the registry changes it carries to each session.
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Shares the sessions seen opting out of telemetry with the capture recorder.
+    pub fn with_consent(mut self, consent: Consent) -> Self {
+        self.consent = consent;
+        self
+    }
+
+    /// Adapts results to each session's client, with `clients` correcting detected features.
+    pub fn with_client_profiles(mut self, clients: ClientProfiles) -> Self {
+        self.clients = clients;
+        self
+    }
+
+    /// Coerces mistyped arguments to the tool's input schema before parsing them.
+    pub fn with_lenient_arguments(mut self, enabled: bool) -> Self {
+        self.lenient_arguments = enabled;
+        self
+    }
+
+    /// Runs each call in a traced span and reports its ids in the result's `_meta`.
+    pub fn with_trace_meta(mut self, enabled: bool) -> Self {
+        self.trace_meta = enabled;
+        self
+    }
+
+    /// Lists the `pizza-batch` tool and accepts calls to it; `None` leaves it out.
+    pub fn with_batch_tool(mut self, config: Option<BatchConfig>) -> Self {
+        self.batch = config;
+        self
+    }
+
+    /// Hands calls that run longer than `config.defer_after` a job handle and lists `get-result`.
+    pub fn with_deferred_results(mut self, config: Option<DeferConfig>) -> Self {
+        self.deferred = config;
+        self
+    }
+
+    /// Tool rate limit state shared by every clone, for the background sweep.
+    pub(crate) fn rate_limit_state(&self) -> Weak<dyn PurgeExpired> {
+        let state: Arc<dyn PurgeExpired> = self.tool_limits.clone();
+        Arc::downgrade(&state)
+    }
+
+    /// Deferred calls shared by every clone, so drain and shutdown can wait for or abort them.
+    pub fn jobs(&self) -> &JobStore {
+        &self.jobs
+    }
+
+    /// Finished deferred results shared by every clone, for the background sweep.
+    pub(crate) fn job_state(&self) -> Weak<dyn PurgeExpired> {
+        self.jobs.gc_state()
+    }
+
+    /// Lists widget tools visible to an anonymous caller.
     pub async fn list_widget_tools(&self) -> Vec<WidgetTool> {
-        widgets::get_all_widgets()
+        self.list_widget_tools_for(&Identity::anonymous()).await
+    }
+
+    /// Lists the widget tools the given identity is allowed to see.
+    pub async fn list_widget_tools_for(&self, identity: &Identity) -> Vec<WidgetTool> {
+        self.list_widget_tools_localized(identity, None).await
+    }
+
+    /// Lists the widget tools the given identity is allowed to see, with titles and invocation
+    /// messages in `locale` where the manifest has them.
+    pub async fn list_widget_tools_localized(
+        &self,
+        identity: &Identity,
+        locale: Option<&str>,
+    ) -> Vec<WidgetTool> {
+        let mut tools: Vec<WidgetTool> = widgets::get_all_widgets()
             .into_iter()
+            .filter(|widget| widget.is_accessible_by(identity))
+            .map(|widget| widget.localized(locale))
             .map(|widget| WidgetTool {
                 name: widget.id.clone(),
                 title: widget.title.clone(),
-                description: widget.title.clone(),
-                input_schema: build_tool_input_schema(),
-                meta: widget.meta(),
+                description: widget.tool_description(),
+                input_schema: widget.input_schema.clone(),
+                meta: widget.tool_meta(),
             })
-            .collect()
+            .collect();
+        if self.batch.is_some() && !tools.is_empty() {
+            tools.push(WidgetTool {
+                name: batch::BATCH_TOOL_NAME.to_string(),
+                title: "Run several pizza tools".to_string(),
+                description:
+                    "Calls several widget tools at once and returns every result in order."
+                        .to_string(),
+                input_schema: batch::input_schema(),
+                meta: Meta::default(),
+            });
+        }
+        if self.deferred.is_some() && !tools.is_empty() {
+            tools.push(WidgetTool {
+                name: jobs::GET_RESULT_TOOL_NAME.to_string(),
+                title: "Get a pending result".to_string(),
+                description: "Returns the result of a tool call that answered with a pending \
+                              handle, waiting up to waitMs for it to finish."
+                    .to_string(),
+                input_schema: jobs::input_schema(),
+                meta: builtin_tool_meta(jobs::GET_RESULT_TOOL_NAME).unwrap_or_default(),
+            });
+        }
+        tools
     }
 
-    /// Calls a widget tool with structured arguments.
+    /// Calls a widget tool with structured arguments as an anonymous caller.
     pub async fn call_widget_tool(
         &self,
         name: &str,
         arguments: JsonValue,
-    ) -> Result<WidgetCallResult> {
-        let widget =
-            widgets::get_widget_by_id(name).with_context(|| format!("Unknown tool: {name}"))?;
+    ) -> Result<WidgetCallResult, ToolCallError> {
+        self.call_widget_tool_with(&CallContext::default(), name, arguments)
+            .await
+    }
 
-        let input: ToolInput =
-            serde_json::from_value(arguments).context("Invalid tool arguments")?;
+    /// Calls a widget tool, or the batch tool when enabled, on behalf of the caller in `ctx`.
+    pub async fn call_widget_tool_with(
+        &self,
+        ctx: &CallContext,
+        name: &str,
+        arguments: JsonValue,
+    ) -> Result<WidgetCallResult, ToolCallError> {
+        match self.batch {
+            Some(config) if name == batch::BATCH_TOOL_NAME => {
+                ensure_registry_loaded()?;
+                return batch::run(self, config, ctx, arguments).await;
+            }
+            _ => {}
+        }
+        match self.deferred {
+            Some(_) if name == jobs::GET_RESULT_TOOL_NAME => {
+                match self.jobs.get_result(ctx, arguments).await {
+                    Err(ToolCallError::Failed(failure)) => Ok(failure_result(
+                        jobs::GET_RESULT_TOOL_NAME,
+                        failure,
+                        Meta::default(),
+                    )),
+                    outcome => outcome,
+                }
+            }
+            _ => self.call_widget(ctx, name, arguments).await,
+        }
+    }
 
-        let content = Content::text(widget.response_text.clone());
-        let mut structured = JsonMap::new();
-        structured.insert(
-            "pizzaTopping".to_string(),
-            JsonValue::String(input.pizza_topping),
-        );
+    /// Calls a single widget tool.
+    ///
+    /// Widgets the caller may not access are reported as unknown so their existence is not leaked.
+    pub(crate) async fn call_widget(
+        &self,
+        ctx: &CallContext,
+        name: &str,
+        arguments: JsonValue,
+    ) -> Result<WidgetCallResult, ToolCallError> {
+        ensure_registry_loaded()?;
 
-        Ok(WidgetCallResult {
-            content: vec![content],
-            structured_content: JsonValue::Object(structured),
-            meta: widget.meta(),
-        })
+        let widget = widgets::get_widget_by_id(name)
+            .filter(|widget| widget.is_accessible_by(&ctx.identity))
+            .ok_or_else(|| ToolCallError::UnknownTool(name.to_string()))?
+            .localized(ctx.locale.as_deref());
+
+        // Validation has no side effects, so it is not charged against the tool's budget.
+        if let Some(config) = widget.rate_limit.filter(|_| !ctx.validate_only) {
+            let caller = ctx.caller_key();
+            let checked = self
+                .tool_limits
+                .lock()
+                .expect("tool rate limit lock poisoned")
+                .check(&widget.id, config, caller.clone(), Instant::now());
+            if let Err(ToolCallError::RateLimited { retry_after, .. }) = &checked {
+                self.events.publish(Event::RateLimited {
+                    limit: RateLimit::Tool {
+                        tool: widget.id.clone(),
+                        caller: caller.to_string(),
+                    },
+                    retry_after: *retry_after,
+                });
+            }
+            checked?;
+        }
+
+        let mut arguments = arguments;
+        let applied_defaults = arguments::apply_defaults(&widget.input_schema, &mut arguments);
+        if self.lenient_arguments {
+            let coerced = arguments::coerce_to_schema(&widget.input_schema, &mut arguments);
+            if !coerced.is_empty() {
+                tracing::debug!(tool = %widget.id, ?coerced, "Coerced tool arguments to the input schema");
+            }
+        }
+
+        if ctx.validate_only {
+            return Ok(validation_result(&widget, arguments, applied_defaults));
+        }
+
+        let input = ToolInput::deserialize(&arguments).map_err(ToolCallError::InvalidArguments)?;
+        self.events.publish(Event::WidgetCalled {
+            widget: Arc::clone(&widget),
+            arguments: (!ctx.telemetry_opt_out).then(|| arguments.clone()),
+        });
+        let widget = widget.with_arguments(&arguments);
+
+        let mut meta = widget.meta();
+        if let Some(locale) = &widget.locale {
+            meta.0.insert(
+                widgets::LOCALE_META_KEY.to_string(),
+                JsonValue::from(locale.as_str()),
+            );
+        }
+        if self.prefetch_hints {
+            meta.0.insert(
+                widgets::PREFETCH_META_KEY.to_string(),
+                widget.prefetch_hints(),
+            );
+        }
+
+        // Added to the executor's structured content on success.
+        let mut annotations = JsonMap::new();
+        if !applied_defaults.is_empty() {
+            annotations.insert(
+                APPLIED_DEFAULTS_KEY.to_string(),
+                JsonValue::Object(applied_defaults),
+            );
+        }
+        let flags = self.flags.evaluate(&widget.id);
+        if !flags.is_empty() {
+            annotations.insert(
+                flags::FLAGS_KEY.to_string(),
+                JsonValue::Object(flags.clone()),
+            );
+        }
+        let assignments = ctx
+            .experiment_unit()
+            .map(|unit| {
+                if ctx.telemetry_opt_out {
+                    self.experiments.variants(&widget.id, &unit)
+                } else {
+                    self.experiments.assign(&widget.id, &unit)
+                }
+            })
+            .unwrap_or_default();
+        if !assignments.is_empty() {
+            meta.0.insert(
+                experiments::EXPERIMENTS_META_KEY.to_string(),
+                JsonValue::Object(assignments.clone()),
+            );
+            annotations.insert(
+                experiments::EXPERIMENTS_KEY.to_string(),
+                JsonValue::Object(assignments),
+            );
+        }
+
+        let executor = self.executors.resolve(&widget.id);
+        let work = {
+            let executors = self.executors.clone();
+            let widget = widget.clone();
+            let identity = ctx.identity.clone();
+            let meta = meta.clone();
+            async move {
+                let outcome = executors
+                    .execute(
+                        executor.as_ref(),
+                        Invocation {
+                            widget: &widget,
+                            input: &input,
+                            identity: &identity,
+                            flags: &flags,
+                        },
+                    )
+                    .await;
+                finish_call(&widget.id, outcome, annotations, meta)
+            }
+        };
+        match self.deferred {
+            Some(config) => {
+                self.jobs
+                    .run_or_defer(config, ctx, &widget.id, meta, work)
+                    .await
+            }
+            None => work.await,
+        }
+    }
+
+    /// Calls a widget tool and shapes the outcome exactly as `tools/call` returns it to clients.
+    pub async fn call_tool_for_client(
+        &self,
+        ctx: &CallContext,
+        name: &str,
+        arguments: JsonValue,
+    ) -> Result<McpCallToolResult, ErrorData> {
+        let trace = self
+            .trace_meta
+            .then(|| TraceContext::start(ctx.trace_parent.as_ref()));
+        let client = self.clients.for_peer(ctx.peer.as_ref());
+        // Resolved here, once, so no executor or later stage has to check the client's choice.
+        let opted_out_ctx;
+        let ctx =
+            if !ctx.telemetry_opt_out && client.as_ref().is_some_and(|client| !client.telemetry) {
+                opted_out_ctx = CallContext {
+                    telemetry_opt_out: true,
+                    ..ctx.clone()
+                };
+                &opted_out_ctx
+            } else {
+                ctx
+            };
+        if let Some(session_id) = ctx.session_id.as_deref().filter(|_| ctx.telemetry_opt_out) {
+            self.consent.opt_out(session_id);
+        }
+        let span = match &trace {
+            Some(trace) => tracing::info_span!(
+                "tool_call",
+                tool = name,
+                client = client.as_ref().map(|client| client.label()),
+                trace_id = %trace.trace_id,
+                span_id = %trace.span_id,
+            ),
+            None => tracing::info_span!(
+                "tool_call",
+                tool = name,
+                client = client.as_ref().map(|client| client.label()),
+            ),
+        };
+        let outcome = self
+            .call_tool_before_deadline(ctx, name, arguments)
+            .instrument(span)
+            .await;
+        let mut result = match outcome {
+            Ok(result) => widget_call_result_to_mcp(result),
+            Err(ToolCallError::RegistryNotLoaded { manifest_path }) => {
+                registry_not_loaded_result(&manifest_path)
+            }
+            Err(err) => return Err(err.into_error_data()),
+        };
+        if let Some(trace) = trace {
+            trace.insert_into(result.meta.get_or_insert_with(Meta::default));
+        }
+        if let Some(notice) = self.notices.for_tool(name) {
+            notice.apply(&mut result);
+        }
+        if let Some(client) = client {
+            client.adapt(&mut result);
+        }
+        Ok(result)
+    }
+
+    async fn call_tool_before_deadline(
+        &self,
+        ctx: &CallContext,
+        name: &str,
+        arguments: JsonValue,
+    ) -> Result<WidgetCallResult, ToolCallError> {
+        let work = async {
+            match ctx.deadline {
+                // Work is dropped, and so cancelled, as soon as the client's deadline passes.
+                Some(deadline) if !ctx.deadline_passed() => tokio::time::timeout_at(
+                    deadline,
+                    self.call_widget_tool_with(ctx, name, arguments),
+                )
+                .await
+                .unwrap_or_else(|_| {
+                    Err(ToolCallError::DeadlineExceeded {
+                        tool: name.to_string(),
+                    })
+                }),
+                Some(_) => Err(ToolCallError::DeadlineExceeded {
+                    tool: name.to_string(),
+                }),
+                None => self.call_widget_tool_with(ctx, name, arguments).await,
+            }
+        };
+        // Likewise when the client disconnects or stops reading its response.
+        let outcome = tokio::select! {
+            outcome = work => outcome,
+            () = ctx.client_gone.cancelled() => Err(ToolCallError::ClientGone {
+                tool: name.to_string(),
+            }),
+        };
+        match &outcome {
+            Err(ToolCallError::DeadlineExceeded { .. }) => {
+                tracing::debug!(tool = name, "Abandoning tool call past the client deadline");
+            }
+            Err(ToolCallError::ClientGone { .. }) => {
+                tracing::debug!(
+                    tool = name,
+                    "Abandoning tool call for a client that went away"
+                );
+            }
+            _ => {}
+        }
+        outcome
     }
 
     /// Lists widget resources for internal use.
@@ -122,21 +832,50 @@ impl PizzazServerHandler {
                 name: widget.title.clone(),
                 description: format!("{} widget markup", widget.title),
                 mime_type: HTML_WIDGET_MIME.to_string(),
-                meta: widget.meta(),
+                meta: widget.resource_meta(),
             })
             .collect()
     }
 
     /// Reads the content for a specific widget resource.
     pub async fn read_widget_resource(&self, uri: &str) -> Result<WidgetResourceContent> {
-        let widget =
-            widgets::get_widget_by_uri(uri).with_context(|| format!("Unknown resource: {uri}"))?;
+        self.read_widget_resource_localized(uri, None).await
+    }
+
+    /// Reads a widget resource, serving the HTML of its `locale` variant when there is one.
+    pub async fn read_widget_resource_localized(
+        &self,
+        uri: &str,
+        locale: Option<&str>,
+    ) -> Result<WidgetResourceContent> {
+        let widget = widgets::get_widget_by_uri(uri)
+            .with_context(|| format!("Unknown resource: {uri}"))?
+            .localized(locale);
 
+        let mut meta = widget.resource_meta();
+        if let Some(locale) = &widget.locale {
+            meta.0.insert(
+                widgets::LOCALE_META_KEY.to_string(),
+                JsonValue::from(locale.as_str()),
+            );
+        }
+        let flags = self.flags.evaluate(&widget.id);
+        if !flags.is_empty() {
+            meta.0
+                .insert(flags::FLAGS_META_KEY.to_string(), JsonValue::Object(flags));
+        }
+        if let Some(reason) = widget.degradation() {
+            tracing::warn!(widget_id = %widget.id, reason = %reason, "Serving degraded widget");
+            meta.0.insert(
+                widgets::DEGRADED_META_KEY.to_string(),
+                serde_json::json!({ "reason": reason }),
+            );
+        }
         Ok(WidgetResourceContent {
             uri: widget.template_uri.clone(),
             mime_type: HTML_WIDGET_MIME.to_string(),
             text: widget.html.clone(),
-            meta: widget.meta(),
+            meta,
         })
     }
 
@@ -149,28 +888,301 @@ impl PizzazServerHandler {
                 name: widget.title.clone(),
                 description: format!("{} widget markup", widget.title),
                 mime_type: HTML_WIDGET_MIME.to_string(),
-                meta: widget.meta(),
+                meta: widget.resource_meta(),
             })
             .collect()
     }
 }
 
+impl PizzazServerHandler {
+    /// `tools/list` as answered to `identity`, before the HTTP layer adds widget `_meta`.
+    pub(crate) async fn list_tools_result(&self, identity: &Identity) -> ListToolsResult {
+        self.list_tools_result_localized(identity, None).await
+    }
+
+    pub(crate) async fn list_tools_result_localized(
+        &self,
+        identity: &Identity,
+        locale: Option<&str>,
+    ) -> ListToolsResult {
+        ListToolsResult {
+            tools: self
+                .list_widget_tools_localized(identity, locale)
+                .await
+                .into_iter()
+                .map(widget_tool_to_mcp)
+                .collect(),
+            next_cursor: None,
+        }
+    }
+
+    pub(crate) async fn list_resources_result(&self) -> ListResourcesResult {
+        ListResourcesResult {
+            resources: self
+                .list_widget_resources()
+                .await
+                .into_iter()
+                .map(widget_resource_to_mcp)
+                .collect(),
+            next_cursor: None,
+        }
+    }
+
+    pub(crate) async fn list_resource_templates_result(&self) -> ListResourceTemplatesResult {
+        ListResourceTemplatesResult {
+            resource_templates: self
+                .list_widget_resource_templates()
+                .await
+                .into_iter()
+                .map(widget_template_to_mcp)
+                .collect(),
+            next_cursor: None,
+        }
+    }
+
+    pub(crate) async fn read_resource_result(
+        &self,
+        uri: &str,
+    ) -> Result<model::ReadResourceResult, ErrorData> {
+        self.read_resource_result_localized(uri, None).await
+    }
+
+    pub(crate) async fn read_resource_result_localized(
+        &self,
+        uri: &str,
+        locale: Option<&str>,
+    ) -> Result<model::ReadResourceResult, ErrorData> {
+        let content = self
+            .read_widget_resource_localized(uri, locale)
+            .await
+            .map_err(|err| ErrorData::invalid_params(err.to_string(), None))?;
+
+        Ok(model::ReadResourceResult {
+            contents: vec![widget_resource_content_to_mcp(content)],
+        })
+    }
+}
+
 const HTML_WIDGET_MIME: &str = "text/html+skybridge";
 
-fn build_tool_input_schema() -> JsonValue {
-    serde_json::json!({
-        "type": "object",
-        "properties": {
-            "pizzaTopping": {
-                "type": "string",
-                "description": "Topping to mention when rendering the widget."
+/// Whether sessions are told when a registry swap changes the tool and resource lists; see
+/// [`spawn_list_change_notifier`].
+const LIST_CHANGED_NOTIFICATIONS: bool = true;
+
+/// How often an idle notifier checks whether its session has closed.
+const SESSION_CLOSED_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Sends `notifications/tools/list_changed` and `notifications/resources/list_changed` to one
+/// session whenever a registry swap published on `events` adds, removes or changes widgets, until
+/// the session closes.
+fn spawn_list_change_notifier(peer: Peer<RoleServer>, events: &EventBus) {
+    let mut events = events.subscribe();
+    tokio::spawn(async move {
+        let mut closed_check = tokio::time::interval(SESSION_CLOSED_CHECK_INTERVAL);
+        loop {
+            let notify = tokio::select! {
+                event = events.recv() => match event {
+                    Ok(Event::RegistryChanged(change)) => !change.diff.is_empty(),
+                    Ok(_) => false,
+                    // Missed swaps may have changed the lists; re-listing is cheap.
+                    Err(broadcast::error::RecvError::Lagged(_)) => true,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                _ = closed_check.tick() => {
+                    if peer.is_transport_closed() {
+                        break;
+                    }
+                    false
+                }
+            };
+            if !notify {
+                continue;
+            }
+            let sent = match peer.notify_tool_list_changed().await {
+                Ok(()) => peer.notify_resource_list_changed().await,
+                Err(error) => Err(error),
+            };
+            if let Err(error) = sent {
+                tracing::debug!(%error, "Stopping list change notifications for closed session");
+                break;
             }
-        },
-        "required": ["pizzaTopping"],
-        "additionalProperties": false
+        }
+    });
+}
+
+/// Computes the advertised capabilities from what the current registry can actually serve.
+fn build_server_capabilities() -> ServerCapabilities {
+    let registry = widgets::registry();
+    let has_widgets = !registry.widgets().is_empty();
+
+    ServerCapabilities {
+        tools: Some(ToolsCapability {
+            list_changed: Some(LIST_CHANGED_NOTIFICATIONS),
+        }),
+        resources: has_widgets.then_some(ResourcesCapability {
+            subscribe: Some(false),
+            list_changed: Some(LIST_CHANGED_NOTIFICATIONS),
+        }),
+        ..ServerCapabilities::default()
+    }
+}
+
+/// Whole seconds until `retry_after` has passed, rounded up so clients never retry early.
+pub fn retry_after_seconds(retry_after: Duration) -> u64 {
+    retry_after.as_millis().div_ceil(1000).max(1) as u64
+}
+
+/// The `openai/locale` a request carries, if any.
+fn request_locale(meta: &Meta) -> Option<String> {
+    meta.get(widgets::LOCALE_META_KEY)
+        .and_then(JsonValue::as_str)
+        .map(str::trim)
+        .filter(|locale| !locale.is_empty())
+        .map(str::to_string)
+}
+
+/// Turns a client timeout in milliseconds into a deadline; zero means no timeout.
+fn deadline_from_timeout(timeout_ms: Option<u64>) -> Option<tokio::time::Instant> {
+    timeout_ms
+        .filter(|timeout| *timeout > 0)
+        .map(|timeout| tokio::time::Instant::now() + Duration::from_millis(timeout))
+}
+
+fn ensure_registry_loaded() -> Result<(), ToolCallError> {
+    let metadata = widgets::registry_metadata();
+    if metadata.registry_initialized {
+        return Ok(());
+    }
+    tracing::warn!(
+        manifest = %metadata.manifest_path.display(),
+        "Rejecting MCP request: widget manifest is not loaded"
+    );
+    Err(ToolCallError::RegistryNotLoaded {
+        manifest_path: metadata.manifest_path,
     })
 }
 
+fn registry_not_loaded_error(manifest_path: &Path) -> ErrorData {
+    ErrorData::new(
+        model::ErrorCode(REGISTRY_NOT_LOADED_ERROR_CODE),
+        format!(
+            "Widget manifest is not loaded (expected at {})",
+            manifest_path.display()
+        ),
+        Some(serde_json::json!({
+            "code": REGISTRY_NOT_LOADED_CODE,
+            "manifestPath": manifest_path.display().to_string(),
+        })),
+    )
+}
+
+/// Shapes an executor's outcome into the result returned to the client.
+fn finish_call(
+    tool: &str,
+    outcome: Result<ExecutorOutput, ToolCallError>,
+    annotations: JsonMap<String, JsonValue>,
+    meta: Meta,
+) -> Result<WidgetCallResult, ToolCallError> {
+    match outcome {
+        Ok(output) => {
+            let mut structured = output.structured_content;
+            structured.extend(annotations);
+            Ok(WidgetCallResult {
+                content: output.content,
+                structured_content: JsonValue::Object(structured),
+                meta,
+                is_error: false,
+            })
+        }
+        Err(ToolCallError::Failed(failure)) => Ok(failure_result(tool, failure, meta)),
+        Err(error) => Err(error),
+    }
+}
+
+/// An `isError: true` result carrying `failure` for the widget to render.
+fn failure_result(tool: &str, failure: ToolFailure, meta: Meta) -> WidgetCallResult {
+    tracing::warn!(tool, code = %failure.code, "Tool call failed");
+    WidgetCallResult {
+        content: vec![Content::text(failure.to_string())],
+        structured_content: failure.structured_content(),
+        meta,
+        is_error: true,
+    }
+}
+
+/// `_meta` for tools that are not widgets, which the augmentation layer cannot look up.
+pub(crate) fn builtin_tool_meta(name: &str) -> Option<Meta> {
+    (name == jobs::GET_RESULT_TOOL_NAME).then(|| {
+        let mut meta = JsonMap::new();
+        // Widgets poll for their own pending results.
+        meta.insert("openai/widgetAccessible".to_string(), JsonValue::Bool(true));
+        Meta(meta)
+    })
+}
+
+/// Outcome of a `pizzaz/validateOnly` call: whether the arguments would be accepted, and in what
+/// normalized form. Invalid arguments are reported in the result rather than as a JSON-RPC error,
+/// so widgets can drive form validation with it.
+fn validation_result(
+    widget: &widgets::Widget,
+    arguments: JsonValue,
+    applied_defaults: JsonMap<String, JsonValue>,
+) -> WidgetCallResult {
+    let error = serde_json::from_value::<ToolInput>(arguments.clone())
+        .map_err(ToolCallError::InvalidArguments)
+        .err();
+    let mut structured = JsonMap::new();
+    structured.insert("valid".to_string(), JsonValue::Bool(error.is_none()));
+    structured.insert("arguments".to_string(), arguments);
+    if !applied_defaults.is_empty() {
+        structured.insert(
+            APPLIED_DEFAULTS_KEY.to_string(),
+            JsonValue::Object(applied_defaults),
+        );
+    }
+    let text = match &error {
+        None => format!("Arguments are valid for {}.", widget.id),
+        Some(error) => {
+            let message = error.to_string();
+            structured.insert("error".to_string(), JsonValue::String(message.clone()));
+            message
+        }
+    };
+
+    // No output template: a validation result is not something to render.
+    let mut meta = JsonMap::new();
+    meta.insert(VALIDATE_ONLY_META_KEY.to_string(), JsonValue::Bool(true));
+    WidgetCallResult {
+        content: vec![Content::text(text)],
+        structured_content: JsonValue::Object(structured),
+        meta: Meta(meta),
+        is_error: false,
+    }
+}
+
+/// Tool result returned while the registry is empty, so clients see a warning instead of "Unknown tool".
+fn registry_not_loaded_result(manifest_path: &Path) -> McpCallToolResult {
+    let failure = ToolFailure::new(
+        REGISTRY_NOT_LOADED_CODE,
+        "Widgets unavailable",
+        "No widget tools are available until an operator loads the manifest.",
+    )
+    .retryable();
+    let mut structured = failure.structured_content();
+    structured["error"]["manifestPath"] = JsonValue::String(manifest_path.display().to_string());
+
+    McpCallToolResult {
+        content: vec![Content::text(format!(
+            "Warning: the widget manifest is not loaded on this server (expected at {}). \
+             No widget tools are available until an operator loads it.",
+            manifest_path.display()
+        ))],
+        structured_content: Some(structured),
+        is_error: Some(true),
+        meta: None,
+    }
+}
+
 fn value_to_map(value: &JsonValue) -> JsonMap<String, JsonValue> {
     value.as_object().cloned().unwrap_or_default()
 }
@@ -179,7 +1191,7 @@ fn widget_call_result_to_mcp(result: WidgetCallResult) -> McpCallToolResult {
     McpCallToolResult {
         content: result.content,
         structured_content: Some(result.structured_content),
-        is_error: Some(false),
+        is_error: Some(result.is_error),
         meta: Some(result.meta),
     }
 }
@@ -227,6 +1239,8 @@ fn widget_template_to_mcp(template: WidgetResourceTemplate) -> model::ResourceTe
     .no_annotation()
 }
 
+// The trait spells out `impl Future + Send` signatures; keep them explicit here as well.
+#[allow(clippy::manual_async_fn)]
 impl ServerHandler for PizzazServerHandler {
     fn ping(
         &self,
@@ -237,19 +1251,36 @@ impl ServerHandler for PizzazServerHandler {
 
     fn initialize(
         &self,
-        _request: InitializeRequestParam,
-        _context: RequestContext<RoleServer>,
+        request: InitializeRequestParam,
+        context: RequestContext<RoleServer>,
     ) -> impl Future<Output = Result<InitializeResult, ErrorData>> + Send + '_ {
         async move {
-            let capabilities = ServerCapabilities::builder()
-                .enable_tools_with(ToolsCapability {
-                    list_changed: Some(false),
-                })
-                .enable_resources_with(ResourcesCapability {
-                    subscribe: Some(false),
-                    list_changed: Some(false),
-                })
-                .build();
+            let client = self.clients.profile(&request);
+            tracing::info!(
+                client = %client.label(),
+                protocol_version = %client.protocol_version,
+                structured_content = client.structured_content,
+                resource_templates = client.resource_templates,
+                "MCP client initialized"
+            );
+            self.events.publish(Event::SessionStarted {
+                client: client.label(),
+                protocol_version: client.protocol_version.clone(),
+            });
+            // Kept on the session's peer, where later requests look the client up.
+            if context.peer.peer_info().is_none() {
+                let mut request = request;
+                if consent::opts_out(&context.meta) {
+                    consent::declare_opt_out(&mut request);
+                }
+                context.peer.set_peer_info(request);
+            }
+
+            let mut capabilities = build_server_capabilities();
+            // Finished deferred calls are announced through `notifications/message`.
+            if self.deferred.is_some() {
+                capabilities.logging = Some(JsonMap::new());
+            }
 
             Ok(InitializeResult {
                 protocol_version: ProtocolVersion::V_2024_11_05,
@@ -271,41 +1302,39 @@ impl ServerHandler for PizzazServerHandler {
     fn list_tools(
         &self,
         _request: Option<PaginatedRequestParam>,
-        _context: RequestContext<RoleServer>,
+        context: RequestContext<RoleServer>,
     ) -> impl Future<Output = Result<ListToolsResult, ErrorData>> + Send + '_ {
         async move {
-            let tools = self
-                .list_widget_tools()
-                .await
-                .into_iter()
-                .map(widget_tool_to_mcp)
-                .collect();
+            if let Err(ToolCallError::RegistryNotLoaded { manifest_path }) =
+                ensure_registry_loaded()
+            {
+                return Err(registry_not_loaded_error(&manifest_path));
+            }
 
-            Ok(ListToolsResult {
-                tools,
-                next_cursor: None,
-            })
+            let ctx = CallContext::from_request(&context);
+            Ok(self
+                .list_tools_result_localized(&ctx.identity, ctx.locale.as_deref())
+                .await)
         }
     }
 
     fn call_tool(
         &self,
         request: CallToolRequestParam,
-        _context: RequestContext<RoleServer>,
+        context: RequestContext<RoleServer>,
     ) -> impl Future<Output = Result<McpCallToolResult, ErrorData>> + Send + '_ {
         async move {
-            let result = self
-                .call_widget_tool(
-                    &request.name,
-                    request
-                        .arguments
-                        .map(JsonValue::Object)
-                        .unwrap_or_else(|| JsonValue::Object(JsonMap::new())),
-                )
-                .await
-                .map_err(|err| ErrorData::invalid_params(err.to_string(), None))?;
-
-            Ok(widget_call_result_to_mcp(result))
+            self.tool_calls.fetch_add(1, Ordering::Relaxed);
+            let ctx = CallContext::from_request(&context);
+            self.call_tool_for_client(
+                &ctx,
+                &request.name,
+                request
+                    .arguments
+                    .map(JsonValue::Object)
+                    .unwrap_or_else(|| JsonValue::Object(JsonMap::new())),
+            )
+            .await
         }
     }
 
@@ -314,55 +1343,41 @@ impl ServerHandler for PizzazServerHandler {
         _request: Option<PaginatedRequestParam>,
         _context: RequestContext<RoleServer>,
     ) -> impl Future<Output = Result<ListResourcesResult, ErrorData>> + Send + '_ {
-        async move {
-            let resources = self
-                .list_widget_resources()
-                .await
-                .into_iter()
-                .map(widget_resource_to_mcp)
-                .collect();
-
-            Ok(ListResourcesResult {
-                resources,
-                next_cursor: None,
-            })
-        }
+        async move { Ok(self.list_resources_result().await) }
     }
 
     fn list_resource_templates(
         &self,
         _request: Option<PaginatedRequestParam>,
-        _context: RequestContext<RoleServer>,
+        context: RequestContext<RoleServer>,
     ) -> impl Future<Output = Result<ListResourceTemplatesResult, ErrorData>> + Send + '_ {
         async move {
-            let resource_templates = self
-                .list_widget_resource_templates()
-                .await
-                .into_iter()
-                .map(widget_template_to_mcp)
-                .collect();
-
-            Ok(ListResourceTemplatesResult {
-                resource_templates,
-                next_cursor: None,
-            })
+            let client = self.clients.for_peer(Some(&context.peer));
+            if client.is_some_and(|client| !client.resource_templates) {
+                return Ok(ListResourceTemplatesResult {
+                    resource_templates: Vec::new(),
+                    next_cursor: None,
+                });
+            }
+            Ok(self.list_resource_templates_result().await)
         }
     }
 
     fn read_resource(
         &self,
         request: model::ReadResourceRequestParam,
-        _context: RequestContext<RoleServer>,
+        context: RequestContext<RoleServer>,
     ) -> impl Future<Output = Result<model::ReadResourceResult, ErrorData>> + Send + '_ {
         async move {
-            let content = self
-                .read_widget_resource(&request.uri)
-                .await
-                .map_err(|err| ErrorData::invalid_params(err.to_string(), None))?;
+            if let Err(ToolCallError::RegistryNotLoaded { manifest_path }) =
+                ensure_registry_loaded()
+            {
+                return Err(registry_not_loaded_error(&manifest_path));
+            }
 
-            Ok(model::ReadResourceResult {
-                contents: vec![widget_resource_content_to_mcp(content)],
-            })
+            let locale = request_locale(&context.meta);
+            self.read_resource_result_localized(&request.uri, locale.as_deref())
+                .await
         }
     }
 
@@ -432,9 +1447,13 @@ impl ServerHandler for PizzazServerHandler {
 
     fn on_initialized(
         &self,
-        _context: NotificationContext<RoleServer>,
+        context: NotificationContext<RoleServer>,
     ) -> impl Future<Output = ()> + Send + '_ {
-        async move {}
+        async move {
+            // Handlers built outside `build_app` have no forwarding yet; this starts it once.
+            self.events.forward_registry_changes(&Supervisor::default());
+            spawn_list_change_notifier(context.peer, &self.events)
+        }
     }
 
     fn on_roots_list_changed(
@@ -448,7 +1467,9 @@ impl ServerHandler for PizzazServerHandler {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::executor::ToolExecutor;
     use crate::test_helpers::initialize_widgets_for_tests;
+    use futures::future::BoxFuture;
 
     #[tokio::test]
     async fn test_list_widget_tools_contains_expected_entries() {
@@ -495,6 +1516,173 @@ mod tests {
         assert!(meta["openai/outputTemplate"].is_string());
     }
 
+    #[tokio::test]
+    async fn test_lenient_arguments_coerce_mistyped_values() {
+        initialize_widgets_for_tests();
+        let arguments = serde_json::json!({"pizzaTopping": 42});
+
+        let strict = PizzazServerHandler::new()
+            .call_widget_tool("pizza-map", arguments.clone())
+            .await;
+        assert!(matches!(strict, Err(ToolCallError::InvalidArguments(_))));
+
+        let result = PizzazServerHandler::new()
+            .with_lenient_arguments(true)
+            .call_widget_tool("pizza-map", arguments)
+            .await
+            .expect("coerced call should succeed");
+        assert_eq!(result.structured_content["pizzaTopping"], "42");
+    }
+
+    #[tokio::test]
+    async fn test_schema_defaults_fill_omitted_arguments() {
+        initialize_widgets_for_tests();
+        let handler = PizzazServerHandler::new();
+
+        let result = handler
+            .call_widget_tool("pizza-video", serde_json::json!({}))
+            .await
+            .expect("defaulted call should succeed");
+        assert_eq!(result.structured_content["pizzaTopping"], "margherita");
+        assert_eq!(
+            result.structured_content[APPLIED_DEFAULTS_KEY],
+            serde_json::json!({"pizzaTopping": "margherita"})
+        );
+
+        let result = handler
+            .call_widget_tool("pizza-video", serde_json::json!({"pizzaTopping": "basil"}))
+            .await
+            .expect("explicit call should succeed");
+        assert_eq!(result.structured_content["pizzaTopping"], "basil");
+        assert!(result
+            .structured_content
+            .get(APPLIED_DEFAULTS_KEY)
+            .is_none());
+
+        let tools = handler.list_widget_tools().await;
+        let video = tools
+            .iter()
+            .find(|tool| tool.name == "pizza-video")
+            .unwrap();
+        assert_eq!(
+            video.input_schema["properties"]["pizzaTopping"]["default"],
+            "margherita"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_executors_that_never_return_time_out() {
+        struct Hang;
+        impl ToolExecutor for Hang {
+            fn execute<'a>(
+                &'a self,
+                _invocation: Invocation<'a>,
+            ) -> BoxFuture<'a, Result<ExecutorOutput, ToolCallError>> {
+                Box::pin(futures::future::pending())
+            }
+        }
+
+        initialize_widgets_for_tests();
+        let executors = Executors::default().with_timeout(Duration::from_millis(20));
+        executors.register("hang", Arc::new(Hang));
+        executors.bind("pizza-map", "hang").unwrap();
+        let handler = PizzazServerHandler::new().with_executors(executors.clone());
+
+        let result = handler
+            .call_widget_tool("pizza-map", serde_json::json!({"pizzaTopping": "basil"}))
+            .await
+            .expect("a timeout is reported in the result");
+        assert!(result.is_error);
+        assert_eq!(result.structured_content["error"]["code"], "tool_timeout");
+        assert_eq!(result.structured_content["error"]["retryable"], true);
+        assert_eq!(executors.timeouts(), 1);
+        assert_eq!(executors.status().timeouts, 1);
+    }
+
+    #[tokio::test]
+    async fn test_validate_only_reports_outcome_without_executing() {
+        initialize_widgets_for_tests();
+        let handler = PizzazServerHandler::new();
+        handler.executors().bind("pizza-video", "disabled").unwrap();
+        let ctx = CallContext {
+            validate_only: true,
+            ..CallContext::default()
+        };
+
+        let result = handler
+            .call_widget_tool_with(&ctx, "pizza-video", serde_json::json!({}))
+            .await
+            .expect("validation should succeed");
+        assert!(!result.is_error, "the disabled executor must not run");
+        assert_eq!(result.structured_content["valid"], true);
+        assert_eq!(
+            result.structured_content["arguments"],
+            serde_json::json!({"pizzaTopping": "margherita"})
+        );
+        assert!(result.meta.0.get("openai/outputTemplate").is_none());
+
+        let result = handler
+            .call_widget_tool_with(&ctx, "pizza-map", serde_json::json!({"pizzaTopping": 7}))
+            .await
+            .expect("invalid arguments are reported in the result");
+        assert_eq!(result.structured_content["valid"], false);
+        assert!(result.structured_content["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("Invalid tool arguments"));
+    }
+
+    #[tokio::test]
+    async fn test_batch_tool_returns_results_in_order() {
+        initialize_widgets_for_tests();
+        let handler = PizzazServerHandler::new().with_batch_tool(Some(BatchConfig::default()));
+        let tools = handler.list_widget_tools().await;
+        assert_eq!(tools.last().unwrap().name, batch::BATCH_TOOL_NAME);
+
+        let result = handler
+            .call_widget_tool(
+                batch::BATCH_TOOL_NAME,
+                serde_json::json!({"calls": [
+                    {"name": "pizza-map", "arguments": {"pizzaTopping": "basil"}},
+                    {"name": "pizza-unknown"},
+                    {"name": "pizza-video"},
+                ]}),
+            )
+            .await
+            .expect("batch call should succeed");
+        let results = result.structured_content["results"].as_array().unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0]["ok"], true);
+        assert_eq!(results[0]["structuredContent"]["pizzaTopping"], "basil");
+        assert_eq!(results[1]["ok"], false);
+        assert!(results[1]["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("Unknown tool"));
+        assert_eq!(
+            results[2]["structuredContent"]["pizzaTopping"],
+            "margherita"
+        );
+
+        let oversized = serde_json::json!({
+            "calls": vec![serde_json::json!({"name": "pizza-map"}); batch::MAX_BATCH_CALLS + 1]
+        });
+        assert!(matches!(
+            handler
+                .call_widget_tool(batch::BATCH_TOOL_NAME, oversized)
+                .await,
+            Err(ToolCallError::InvalidArguments(_))
+        ));
+
+        // Without the flag the batch tool does not exist.
+        assert!(matches!(
+            PizzazServerHandler::new()
+                .call_widget_tool(batch::BATCH_TOOL_NAME, serde_json::json!({"calls": []}))
+                .await,
+            Err(ToolCallError::UnknownTool(_))
+        ));
+    }
+
     #[tokio::test]
     async fn test_call_tool_result_serialization_includes_meta() {
         initialize_widgets_for_tests();
@@ -523,6 +1711,362 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_tool_rate_limits_key_on_caller() {
+        let mut limits = ToolRateLimits::default();
+        let config = RateLimitConfig {
+            max_requests: 1,
+            window: Duration::from_secs(60),
+        };
+        let now = Instant::now();
+        let alice = CallContext {
+            identity: Identity {
+                subject: Some("alice".into()),
+                roles: Vec::new(),
+            },
+            client_ip: Some(IpAddr::from([10, 0, 0, 1])),
+            deadline: None,
+            validate_only: false,
+            peer: None,
+            trace_parent: None,
+            session_id: None,
+            telemetry_opt_out: false,
+            locale: None,
+            client_gone: CancellationToken::new(),
+        };
+        let bob = CallContext {
+            identity: Identity {
+                subject: Some("bob".into()),
+                roles: Vec::new(),
+            },
+            client_ip: Some(IpAddr::from([10, 0, 0, 1])),
+            deadline: None,
+            validate_only: false,
+            peer: None,
+            trace_parent: None,
+            session_id: None,
+            telemetry_opt_out: false,
+            locale: None,
+            client_gone: CancellationToken::new(),
+        };
+
+        assert!(limits
+            .check("pizza-map", config, alice.caller_key(), now)
+            .is_ok());
+        // Bob shares Alice's NAT address but has his own budget.
+        assert!(limits
+            .check("pizza-map", config, bob.caller_key(), now)
+            .is_ok());
+        // Budgets are per tool.
+        assert!(limits
+            .check("pizza-list", config, alice.caller_key(), now)
+            .is_ok());
+        let err = limits
+            .check("pizza-map", config, alice.caller_key(), now)
+            .unwrap_err();
+        assert!(matches!(err, ToolCallError::RateLimited { .. }));
+
+        let error = err.into_error_data();
+        assert_eq!(error.code, model::ErrorCode(RATE_LIMITED_ERROR_CODE));
+        let data = error.data.expect("rate limit errors carry retry data");
+        assert_eq!(data["retryable"], JsonValue::Bool(true));
+        let retry_after_ms = data["retryAfterMs"].as_u64().unwrap();
+        assert!(retry_after_ms > 0 && retry_after_ms <= 60_000);
+    }
+
+    #[test]
+    fn test_registry_not_loaded_result_is_distinct() {
+        let path = PathBuf::from("/srv/widgets.json");
+        let result = registry_not_loaded_result(&path);
+        assert_eq!(result.is_error, Some(true));
+        assert_eq!(
+            result.structured_content.as_ref().unwrap()["error"]["code"],
+            REGISTRY_NOT_LOADED_CODE
+        );
+        assert_eq!(
+            result.structured_content.as_ref().unwrap()["error"]["retryable"],
+            true
+        );
+        match &result.content[0].raw {
+            model::RawContent::Text(text) => assert!(text.text.starts_with("Warning:")),
+            _ => panic!("Expected text content"),
+        }
+
+        let error = ToolCallError::RegistryNotLoaded {
+            manifest_path: path,
+        }
+        .into_error_data();
+        assert_eq!(error.code, model::ErrorCode(-32033));
+        assert_ne!(error.code, model::ErrorCode::RESOURCE_NOT_FOUND);
+        assert_eq!(error.data.unwrap()["code"], REGISTRY_NOT_LOADED_CODE);
+    }
+
+    #[test]
+    fn test_capabilities_reflect_registry_contents() {
+        initialize_widgets_for_tests();
+        let capabilities = build_server_capabilities();
+        assert!(capabilities.tools.is_some());
+        assert!(capabilities.resources.is_some());
+        assert!(capabilities.prompts.is_none());
+        assert!(capabilities.completions.is_none());
+    }
+
+    #[test]
+    fn test_caller_key_falls_back_to_ip() {
+        let ctx = CallContext {
+            identity: Identity::anonymous(),
+            client_ip: Some(IpAddr::from([127, 0, 0, 1])),
+            deadline: None,
+            validate_only: false,
+            peer: None,
+            trace_parent: None,
+            session_id: None,
+            telemetry_opt_out: false,
+            locale: None,
+            client_gone: CancellationToken::new(),
+        };
+        assert_eq!(
+            ctx.caller_key(),
+            CallerKey::Ip(IpAddr::from([127, 0, 0, 1]))
+        );
+        assert_eq!(CallContext::default().caller_key(), CallerKey::Unknown);
+    }
+
+    #[tokio::test]
+    async fn test_call_past_client_deadline_times_out() {
+        initialize_widgets_for_tests();
+        let handler = PizzazServerHandler::new();
+        let ctx = CallContext {
+            deadline: Some(tokio::time::Instant::now()),
+            ..CallContext::default()
+        };
+        let err = handler
+            .call_tool_for_client(
+                &ctx,
+                "pizza-map",
+                serde_json::json!({"pizzaTopping": "basil"}),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, model::ErrorCode(DEADLINE_EXCEEDED_ERROR_CODE));
+
+        let ctx = CallContext {
+            deadline: deadline_from_timeout(Some(60_000)),
+            ..CallContext::default()
+        };
+        assert!(handler
+            .call_tool_for_client(
+                &ctx,
+                "pizza-map",
+                serde_json::json!({"pizzaTopping": "basil"}),
+            )
+            .await
+            .is_ok());
+        assert_eq!(deadline_from_timeout(Some(0)), None);
+    }
+
+    #[tokio::test]
+    async fn test_flags_reach_results_and_resource_reads() {
+        initialize_widgets_for_tests();
+        let flag_set: flags::FlagSet = serde_json::from_value(serde_json::json!({
+            "flags": { "newCheckout": true },
+            "widgets": { "pizza-map": { "showTraffic": false } }
+        }))
+        .unwrap();
+        let handler = PizzazServerHandler::new().with_flags(Flags::new(Arc::new(
+            flags::FileFlagProvider::new(None, flag_set),
+        )));
+
+        let result = handler
+            .call_widget_tool("pizza-map", serde_json::json!({"pizzaTopping": "basil"}))
+            .await
+            .unwrap();
+        assert_eq!(
+            result.structured_content[flags::FLAGS_KEY],
+            serde_json::json!({ "newCheckout": true, "showTraffic": false })
+        );
+
+        let content = handler
+            .read_widget_resource("ui://widget/pizza-list-2d2b.html")
+            .await
+            .unwrap();
+        assert_eq!(
+            content.meta.0[flags::FLAGS_META_KEY],
+            serde_json::json!({ "newCheckout": true })
+        );
+
+        let unflagged = PizzazServerHandler::new()
+            .call_widget_tool("pizza-map", serde_json::json!({"pizzaTopping": "basil"}))
+            .await
+            .unwrap();
+        assert!(unflagged.structured_content.get(flags::FLAGS_KEY).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_telemetry_opt_out_skips_analytics() {
+        initialize_widgets_for_tests();
+        let events = EventBus::default();
+        let mut published = events.subscribe();
+        let consent = Consent::default();
+        let handler = PizzazServerHandler::new()
+            .with_events(events)
+            .with_consent(consent.clone());
+        let arguments = serde_json::json!({"pizzaTopping": "basil"});
+
+        handler
+            .call_tool_for_client(&CallContext::default(), "pizza-map", arguments.clone())
+            .await
+            .unwrap();
+        let opted_out = CallContext {
+            session_id: Some("private-session".to_string()),
+            telemetry_opt_out: true,
+            ..CallContext::default()
+        };
+        let result = handler
+            .call_tool_for_client(&opted_out, "pizza-map", arguments)
+            .await
+            .unwrap();
+        assert_eq!(result.structured_content.unwrap()["pizzaTopping"], "basil");
+
+        let usage = crate::analytics::Usage::default();
+        let mut calls = 0;
+        while let Ok(event) = published.try_recv() {
+            calls += usize::from(matches!(event, Event::WidgetCalled { .. }));
+            usage.observe(&event);
+        }
+        assert_eq!(calls, 2);
+        assert_eq!(usage.report(Some("pizza-map"), 10).widgets[0].calls, 1);
+        assert!(consent.is_opted_out("private-session"));
+    }
+
+    #[tokio::test]
+    async fn test_notice_is_attached_to_matching_results() {
+        initialize_widgets_for_tests();
+        let notices = Notices::default();
+        let handler = PizzazServerHandler::new().with_notices(notices.clone());
+        let call = |name: &'static str| {
+            let handler = handler.clone();
+            async move {
+                handler
+                    .call_tool_for_client(
+                        &CallContext::default(),
+                        name,
+                        serde_json::json!({"pizzaTopping": "basil"}),
+                    )
+                    .await
+                    .unwrap()
+            }
+        };
+
+        let result = call("pizza-map").await;
+        assert!(!result
+            .meta
+            .unwrap()
+            .0
+            .contains_key(crate::notice::NOTICE_META_KEY));
+
+        notices.set(crate::notice::NoticeRequest {
+            message: "Ordering is temporarily unavailable".to_string(),
+            content: true,
+            widgets: vec!["pizza-map".to_string()],
+            ..Default::default()
+        });
+        let result = call("pizza-map").await;
+        let notice = &result.meta.as_ref().unwrap().0[crate::notice::NOTICE_META_KEY];
+        assert_eq!(notice["message"], "Ordering is temporarily unavailable");
+        assert_eq!(notice["level"], "info");
+        assert_eq!(
+            result.content.last().unwrap().as_text().unwrap().text,
+            "Notice: Ordering is temporarily unavailable"
+        );
+
+        let other = call("pizza-list").await;
+        assert!(!other
+            .meta
+            .unwrap()
+            .0
+            .contains_key(crate::notice::NOTICE_META_KEY));
+    }
+
+    #[tokio::test]
+    async fn test_experiment_assignments_follow_the_session() {
+        initialize_widgets_for_tests();
+        let handler = PizzazServerHandler::new().with_experiments(Experiments::new(
+            serde_json::from_value(serde_json::json!([
+                { "name": "map-layout", "variants": [{ "name": "grid" }] }
+            ]))
+            .unwrap(),
+        ));
+        let ctx = CallContext {
+            session_id: Some("session-1".to_string()),
+            ..CallContext::default()
+        };
+
+        let result = handler
+            .call_widget(
+                &ctx,
+                "pizza-map",
+                serde_json::json!({"pizzaTopping": "basil"}),
+            )
+            .await
+            .unwrap();
+        let expected = serde_json::json!({ "map-layout": "grid" });
+        assert_eq!(
+            result.structured_content[experiments::EXPERIMENTS_KEY],
+            expected
+        );
+        assert_eq!(result.meta.0[experiments::EXPERIMENTS_META_KEY], expected);
+
+        // Without a session or caller key there is nothing stable to bucket on.
+        let anonymous = handler
+            .call_widget(
+                &CallContext::default(),
+                "pizza-map",
+                serde_json::json!({"pizzaTopping": "basil"}),
+            )
+            .await
+            .unwrap();
+        assert!(!anonymous
+            .meta
+            .0
+            .contains_key(experiments::EXPERIMENTS_META_KEY));
+    }
+
+    #[tokio::test]
+    async fn test_trace_meta_continues_the_callers_trace() {
+        initialize_widgets_for_tests();
+        let arguments = serde_json::json!({"pizzaTopping": "basil"});
+        let ctx = CallContext {
+            trace_parent: TraceContext::from_traceparent(
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            ),
+            ..CallContext::default()
+        };
+
+        let untraced = PizzazServerHandler::new()
+            .call_tool_for_client(&ctx, "pizza-map", arguments.clone())
+            .await
+            .unwrap();
+        assert!(!untraced
+            .meta
+            .unwrap()
+            .0
+            .contains_key(crate::trace::TRACE_ID_META_KEY));
+
+        let traced = PizzazServerHandler::new()
+            .with_trace_meta(true)
+            .call_tool_for_client(&ctx, "pizza-map", arguments)
+            .await
+            .unwrap();
+        let meta = traced.meta.unwrap();
+        assert_eq!(
+            meta.0[crate::trace::TRACE_ID_META_KEY],
+            "4bf92f3577b34da6a3ce929d0e0e4736"
+        );
+        assert_ne!(meta.0[crate::trace::SPAN_ID_META_KEY], "00f067aa0ba902b7");
+        assert!(meta.0.contains_key("openai/outputTemplate"));
+    }
+
     #[tokio::test]
     async fn test_list_widget_resources() {
         initialize_widgets_for_tests();
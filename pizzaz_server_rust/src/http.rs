@@ -0,0 +1,22 @@
+//! The outbound HTTP client shared by webhooks, executor egress, object storage and the MCP
+//! client.
+
+/// Client for outbound calls: rustls for `https://`, no redirects, so a request never ends up at
+/// a host its caller did not name.
+pub(crate) fn http_client(user_agent: &'static str) -> reqwest::Client {
+    reqwest::Client::builder()
+        .user_agent(user_agent)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("HTTP client builds with the bundled TLS roots")
+}
+
+/// `error` as an [`std::io::Error`], keeping timeouts recognisable.
+pub(crate) fn transport_error(error: reqwest::Error) -> std::io::Error {
+    let kind = if error.is_timeout() {
+        std::io::ErrorKind::TimedOut
+    } else {
+        std::io::ErrorKind::Other
+    };
+    std::io::Error::new(kind, error)
+}
@@ -0,0 +1,648 @@
+//! Deferred results for tool calls that outlast client timeouts.
+//!
+//! With `WIDGETS_DEFER_AFTER_MS` set, a tool call still running after that long keeps running in
+//! the background and the client immediately gets a `pending` result carrying a handle. The
+//! `get-result` tool, callable from the widget, long-polls for the outcome, and the session receives
+//! a `notifications/message` from the `pizzaz/jobs` logger once it is ready.
+//!
+//! Background work is bounded: each caller and the whole server may only have so many jobs
+//! pending, a job still running after its maximum runtime is aborted, and drain and shutdown can
+//! wait for the pending ones or abort them.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex, Weak},
+    time::{Duration, Instant},
+};
+
+use rmcp::{
+    model::{Content, ErrorData, LoggingLevel, LoggingMessageNotificationParam, Meta},
+    service::{Peer, RoleServer},
+};
+use serde::Deserialize;
+use serde_json::{Map as JsonMap, Value as JsonValue};
+use tokio::{sync::watch, task::AbortHandle};
+use tracing::Instrument;
+
+use crate::{
+    executor::ToolFailure,
+    handler::{CallContext, ToolCallError, WidgetCallResult},
+    rate_limit::{CallerKey, PurgeExpired},
+};
+
+/// Tool that returns a deferred call's outcome.
+pub const GET_RESULT_TOOL_NAME: &str = "get-result";
+
+/// `_meta` key on pending results carrying the job handle.
+pub const JOB_HANDLE_META_KEY: &str = "pizzaz/jobHandle";
+
+/// Logger name on the notification sent when a deferred call finishes.
+pub const JOBS_LOGGER: &str = "pizzaz/jobs";
+
+/// Longest `get-result` will hold a request open waiting for the outcome.
+pub const MAX_WAIT: Duration = Duration::from_secs(25);
+
+/// How long a finished outcome stays available.
+pub const RETENTION: Duration = Duration::from_secs(300);
+
+const POLL_AFTER_MS: u64 = 1_000;
+
+const DEFAULT_MAX_RUNTIME: Duration = Duration::from_secs(600);
+const DEFAULT_MAX_PER_CALLER: usize = 8;
+const DEFAULT_MAX_JOBS: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeferConfig {
+    /// How long a call may run before the client gets a handle instead of the result.
+    pub defer_after: Duration,
+    /// How long a deferred call may keep running before it is aborted.
+    pub max_runtime: Duration,
+    /// Pending jobs one caller may have before its calls are rate limited.
+    pub max_per_caller: usize,
+    /// Pending jobs across all callers before calls are refused as overloaded.
+    pub max_jobs: usize,
+}
+
+impl DeferConfig {
+    /// Defers calls after `defer_after`, with the default runtime and job limits.
+    pub fn new(defer_after: Duration) -> Self {
+        Self {
+            defer_after,
+            max_runtime: DEFAULT_MAX_RUNTIME,
+            max_per_caller: DEFAULT_MAX_PER_CALLER,
+            max_jobs: DEFAULT_MAX_JOBS,
+        }
+    }
+
+    /// Returns `None` unless `WIDGETS_DEFER_AFTER_MS` is a positive number.
+    ///
+    /// `WIDGETS_DEFER_MAX_RUNTIME_MS`, `WIDGETS_DEFER_MAX_PER_CALLER` and `WIDGETS_DEFER_MAX_JOBS`
+    /// override the limits; invalid values fall back to the defaults.
+    pub(crate) fn from_env() -> Option<Self> {
+        let defer_after = std::env::var("WIDGETS_DEFER_AFTER_MS")
+            .ok()
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .filter(|value| *value > 0)?;
+        let mut config = Self::new(Duration::from_millis(defer_after));
+        if let Some(millis) = positive_env("WIDGETS_DEFER_MAX_RUNTIME_MS") {
+            config.max_runtime = Duration::from_millis(millis);
+        }
+        if let Some(max) = positive_env("WIDGETS_DEFER_MAX_PER_CALLER") {
+            config.max_per_caller = max as usize;
+        }
+        if let Some(max) = positive_env("WIDGETS_DEFER_MAX_JOBS") {
+            config.max_jobs = max as usize;
+        }
+        Some(config)
+    }
+}
+
+fn positive_env(name: &str) -> Option<u64> {
+    let raw = std::env::var(name).ok()?;
+    match raw.trim().parse::<u64>() {
+        Ok(value) if value > 0 => Some(value),
+        _ => {
+            tracing::warn!(value = %raw, "Ignoring invalid {name}");
+            None
+        }
+    }
+}
+
+type JobOutcome = Result<WidgetCallResult, ErrorData>;
+
+struct Finished {
+    at: Instant,
+    outcome: JobOutcome,
+}
+
+struct Job {
+    tool: String,
+    owner: CallerKey,
+    /// Shown while the job is pending, so the widget can render and poll.
+    pending_meta: Meta,
+    state: watch::Receiver<Option<Arc<Finished>>>,
+    /// Aborts the work; the job then finishes as failed.
+    task: AbortHandle,
+}
+
+impl Job {
+    fn is_pending(&self) -> bool {
+        self.state.borrow().is_none()
+    }
+}
+
+/// Aborts a task unless it is disarmed, so work dropped before deferral stops with its request.
+struct AbortOnDrop(Option<AbortHandle>);
+
+impl AbortOnDrop {
+    fn disarm(mut self) -> AbortHandle {
+        self.0.take().expect("abort guard already disarmed")
+    }
+}
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        if let Some(task) = self.0.take() {
+            task.abort();
+        }
+    }
+}
+
+/// Jobs by handle, shared by every clone of a handler.
+#[derive(Clone, Default)]
+pub struct JobStore {
+    jobs: Arc<Mutex<HashMap<String, Job>>>,
+}
+
+impl std::fmt::Debug for JobStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JobStore")
+            .field("jobs", &self.lock().len())
+            .finish()
+    }
+}
+
+impl PurgeExpired for Mutex<HashMap<String, Job>> {
+    fn purge_expired(&self, now: Instant) -> usize {
+        let Ok(mut jobs) = self.try_lock() else {
+            return 0;
+        };
+        let before = jobs.len();
+        jobs.retain(|_, job| {
+            job.state
+                .borrow()
+                .as_ref()
+                .is_none_or(|finished| now.duration_since(finished.at) < RETENTION)
+        });
+        before - jobs.len()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GetResultInput {
+    handle: String,
+    #[serde(default)]
+    wait_ms: u64,
+}
+
+impl JobStore {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Job>> {
+        self.jobs.lock().expect("job store lock poisoned")
+    }
+
+    /// Finished outcomes for the background sweep.
+    pub(crate) fn gc_state(&self) -> Weak<dyn PurgeExpired> {
+        let state: Arc<dyn PurgeExpired> = self.jobs.clone();
+        Arc::downgrade(&state)
+    }
+
+    /// Deferred calls still running.
+    pub fn pending(&self) -> usize {
+        self.lock().values().filter(|job| job.is_pending()).count()
+    }
+
+    /// Waits until every deferred call that is running now has finished.
+    pub async fn wait_for_pending(&self) {
+        let pending: Vec<_> = self
+            .lock()
+            .values()
+            .filter(|job| job.is_pending())
+            .map(|job| job.state.clone())
+            .collect();
+        for mut state in pending {
+            let _ = state.wait_for(Option::is_some).await;
+        }
+    }
+
+    /// Aborts every deferred call still running; returns how many there were.
+    ///
+    /// Aborted jobs finish as failed, so `get-result` reports them instead of pending forever.
+    pub fn abort_pending(&self) -> usize {
+        let jobs = self.lock();
+        let mut aborted = 0;
+        for job in jobs.values().filter(|job| job.is_pending()) {
+            job.task.abort();
+            aborted += 1;
+        }
+        aborted
+    }
+
+    /// Refuses a new call when its caller, or the server, already has too many pending jobs.
+    fn check_capacity(
+        &self,
+        config: DeferConfig,
+        owner: &CallerKey,
+        tool: &str,
+    ) -> Result<(), ToolCallError> {
+        let jobs = self.lock();
+        let pending = jobs.values().filter(|job| job.is_pending());
+        let (total, own) = pending.fold((0, 0), |(total, own), job| {
+            (total + 1, own + usize::from(&job.owner == owner))
+        });
+        if own >= config.max_per_caller {
+            tracing::warn!(tool, pending = own, "Caller has too many deferred calls");
+            return Err(ToolCallError::RateLimited {
+                tool: tool.to_string(),
+                retry_after: Duration::from_millis(POLL_AFTER_MS),
+            });
+        }
+        if total >= config.max_jobs {
+            tracing::warn!(tool, pending = total, "Too many deferred calls");
+            return Err(ToolCallError::Overloaded {
+                tool: tool.to_string(),
+                retry_after: Duration::from_millis(POLL_AFTER_MS),
+            });
+        }
+        Ok(())
+    }
+
+    /// Runs `work` in the background and waits up to `config.defer_after` for it.
+    ///
+    /// Returns the outcome when it arrives in time, otherwise a pending result with a handle. The
+    /// work is aborted if this future is dropped before deferring, for example at the client's
+    /// deadline, and once deferred if it outlasts `config.max_runtime`.
+    pub(crate) async fn run_or_defer<F>(
+        &self,
+        config: DeferConfig,
+        ctx: &CallContext,
+        tool: &str,
+        pending_meta: Meta,
+        work: F,
+    ) -> Result<WidgetCallResult, ToolCallError>
+    where
+        F: Future<Output = Result<WidgetCallResult, ToolCallError>> + Send + 'static,
+    {
+        let owner = ctx.caller_key();
+        self.check_capacity(config, &owner, tool)?;
+
+        // The work keeps the caller's span, so logs from a deferred call still join its trace.
+        let mut task = tokio::spawn(work.in_current_span());
+        let guard = AbortOnDrop(Some(task.abort_handle()));
+        if let Ok(joined) = tokio::time::timeout(config.defer_after, &mut task).await {
+            return joined.unwrap_or_else(|error| Err(task_failed(tool, error)));
+        }
+
+        let handle = uuid::Uuid::new_v4().to_string();
+        let (sender, state) = watch::channel(None);
+        self.lock().insert(
+            handle.clone(),
+            Job {
+                tool: tool.to_string(),
+                owner,
+                pending_meta: pending_meta.clone(),
+                state,
+                task: guard.disarm(),
+            },
+        );
+        tracing::info!(tool, handle = %handle, "Deferring slow tool call");
+
+        let peer = ctx.peer.clone();
+        let notify_handle = handle.clone();
+        let notify_tool = tool.to_string();
+        let max_runtime = config.max_runtime.saturating_sub(config.defer_after);
+        tokio::spawn(async move {
+            let joined = match tokio::time::timeout(max_runtime, &mut task).await {
+                Ok(joined) => joined,
+                Err(_) => {
+                    tracing::warn!(
+                        tool = %notify_tool,
+                        handle = %notify_handle,
+                        "Aborting deferred call past its maximum runtime"
+                    );
+                    task.abort();
+                    task.await
+                }
+            };
+            let outcome = joined
+                .unwrap_or_else(|error| Err(task_failed(&notify_tool, error)))
+                .map_err(ToolCallError::into_error_data);
+            let ok = matches!(&outcome, Ok(result) if !result.is_error);
+            sender.send_replace(Some(Arc::new(Finished {
+                at: Instant::now(),
+                outcome,
+            })));
+            if let Some(peer) = peer {
+                notify_ready(&peer, &notify_handle, &notify_tool, ok).await;
+            }
+        });
+
+        Ok(pending_result(&handle, tool, pending_meta))
+    }
+
+    /// `get-result`: the job's outcome, waiting up to `waitMs` for it to finish.
+    pub(crate) async fn get_result(
+        &self,
+        ctx: &CallContext,
+        arguments: JsonValue,
+    ) -> Result<WidgetCallResult, ToolCallError> {
+        let input: GetResultInput =
+            serde_json::from_value(arguments).map_err(ToolCallError::InvalidArguments)?;
+
+        // Other callers' handles are reported as unknown so their results cannot be read.
+        let job = self
+            .lock()
+            .get(&input.handle)
+            .filter(|job| job.owner == ctx.caller_key())
+            .map(|job| {
+                (
+                    job.tool.clone(),
+                    job.pending_meta.clone(),
+                    job.state.clone(),
+                )
+            });
+        let Some((tool, pending_meta, mut state)) = job else {
+            return Err(ToolCallError::Failed(ToolFailure::new(
+                "unknown_handle",
+                "Result not found",
+                "No result exists for that handle. Results are kept for five minutes after \
+                 they finish.",
+            )));
+        };
+
+        let wait = Duration::from_millis(input.wait_ms).min(MAX_WAIT);
+        let waited = tokio::time::timeout(wait, state.wait_for(Option::is_some))
+            .await
+            .ok()
+            .and_then(Result::ok)
+            .and_then(|finished| finished.clone());
+        match waited.or_else(|| state.borrow().clone()) {
+            Some(finished) => finished
+                .outcome
+                .clone()
+                .map_err(ToolCallError::DeferredFailure),
+            None => Ok(pending_result(&input.handle, &tool, pending_meta)),
+        }
+    }
+}
+
+fn task_failed(tool: &str, error: tokio::task::JoinError) -> ToolCallError {
+    if error.is_cancelled() {
+        tracing::debug!(tool, "Tool call task was aborted");
+    } else {
+        tracing::error!(tool, error = %error, "Tool call task failed");
+    }
+    ToolCallError::Failed(ToolFailure::new(
+        "internal_error",
+        "Something went wrong",
+        "The tool stopped unexpectedly. Try again.",
+    ))
+}
+
+fn pending_result(handle: &str, tool: &str, pending_meta: Meta) -> WidgetCallResult {
+    let mut structured = JsonMap::new();
+    structured.insert("status".to_string(), JsonValue::from("pending"));
+    structured.insert("handle".to_string(), JsonValue::from(handle));
+    structured.insert("pollAfterMs".to_string(), JsonValue::from(POLL_AFTER_MS));
+
+    let mut meta = pending_meta;
+    meta.0
+        .insert(JOB_HANDLE_META_KEY.to_string(), JsonValue::from(handle));
+    WidgetCallResult {
+        content: vec![Content::text(format!(
+            "{tool} is still running. Call {GET_RESULT_TOOL_NAME} with handle {handle} for the result."
+        ))],
+        structured_content: JsonValue::Object(structured),
+        meta,
+        is_error: false,
+    }
+}
+
+async fn notify_ready(peer: &Peer<RoleServer>, handle: &str, tool: &str, ok: bool) {
+    let notification = LoggingMessageNotificationParam {
+        level: LoggingLevel::Info,
+        logger: Some(JOBS_LOGGER.to_string()),
+        data: serde_json::json!({
+            "event": "job.completed",
+            "handle": handle,
+            "tool": tool,
+            "ok": ok,
+        }),
+    };
+    if let Err(error) = peer.notify_logging_message(notification).await {
+        tracing::debug!(handle, error = %error, "Could not announce finished job");
+    }
+}
+
+/// Input schema of the `get-result` tool.
+pub(crate) fn input_schema() -> JsonValue {
+    serde_json::json!({
+        "type": "object",
+        "properties": {
+            "handle": {
+                "type": "string",
+                "description": "Handle from a pending tool result."
+            },
+            "waitMs": {
+                "type": "integer",
+                "description": "How long to wait for the result before returning pending again.",
+                "minimum": 0,
+                "maximum": MAX_WAIT.as_millis() as u64
+            }
+        },
+        "required": ["handle"],
+        "additionalProperties": false
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finished_call(text: &str) -> WidgetCallResult {
+        WidgetCallResult {
+            content: vec![Content::text(text)],
+            structured_content: serde_json::json!({ "done": true }),
+            meta: Meta::default(),
+            is_error: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn fast_calls_return_directly_and_slow_ones_defer() {
+        let store = JobStore::default();
+        let config = DeferConfig::new(Duration::from_millis(20));
+        let ctx = CallContext::default();
+
+        let fast = store
+            .run_or_defer(config, &ctx, "pizza-map", Meta::default(), async {
+                Ok(finished_call("fast"))
+            })
+            .await
+            .unwrap();
+        assert_eq!(fast.structured_content["done"], true);
+
+        let (release, released) = tokio::sync::oneshot::channel::<()>();
+        let pending = store
+            .run_or_defer(config, &ctx, "pizza-map", Meta::default(), async move {
+                let _ = released.await;
+                Ok(finished_call("slow"))
+            })
+            .await
+            .unwrap();
+        assert_eq!(pending.structured_content["status"], "pending");
+        let handle = pending.structured_content["handle"].as_str().unwrap();
+        assert_eq!(pending.meta.0[JOB_HANDLE_META_KEY], handle);
+
+        let poll = store
+            .get_result(&ctx, serde_json::json!({ "handle": handle }))
+            .await
+            .unwrap();
+        assert_eq!(poll.structured_content["status"], "pending");
+
+        release.send(()).unwrap();
+        let done = store
+            .get_result(
+                &ctx,
+                serde_json::json!({ "handle": handle, "waitMs": 1000 }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(done.structured_content["done"], true);
+
+        let stranger = CallContext {
+            client_ip: Some([10, 0, 0, 9].into()),
+            ..CallContext::default()
+        };
+        assert!(matches!(
+            store
+                .get_result(&stranger, serde_json::json!({ "handle": handle }))
+                .await,
+            Err(ToolCallError::Failed(failure)) if failure.code == "unknown_handle"
+        ));
+    }
+
+    #[tokio::test]
+    async fn sweep_drops_only_expired_outcomes() {
+        let store = JobStore::default();
+        let config = DeferConfig::new(Duration::from_millis(1));
+        store
+            .run_or_defer(
+                config,
+                &CallContext::default(),
+                "pizza-map",
+                Meta::default(),
+                async {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    Ok(finished_call("slow"))
+                },
+            )
+            .await
+            .unwrap();
+
+        let state = store.gc_state().upgrade().unwrap();
+        assert_eq!(state.purge_expired(Instant::now() + RETENTION * 2), 0);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(state.purge_expired(Instant::now()), 0);
+        assert_eq!(state.purge_expired(Instant::now() + RETENTION * 2), 1);
+    }
+
+    #[tokio::test]
+    async fn pending_jobs_are_capped_per_caller_and_globally() {
+        let store = JobStore::default();
+        let config = DeferConfig {
+            max_per_caller: 1,
+            max_jobs: 2,
+            ..DeferConfig::new(Duration::from_millis(1))
+        };
+        let caller = |last: u8| CallContext {
+            client_ip: Some([10, 0, 0, last].into()),
+            ..CallContext::default()
+        };
+        let never = || std::future::pending::<Result<WidgetCallResult, ToolCallError>>();
+
+        for last in [1, 2] {
+            let pending = store
+                .run_or_defer(config, &caller(last), "pizza-map", Meta::default(), never())
+                .await
+                .unwrap();
+            assert_eq!(pending.structured_content["status"], "pending");
+        }
+        assert_eq!(store.pending(), 2);
+
+        assert!(matches!(
+            store
+                .run_or_defer(config, &caller(1), "pizza-map", Meta::default(), never())
+                .await,
+            Err(ToolCallError::RateLimited { .. })
+        ));
+        assert!(matches!(
+            store
+                .run_or_defer(config, &caller(3), "pizza-map", Meta::default(), never())
+                .await,
+            Err(ToolCallError::Overloaded { .. })
+        ));
+
+        assert_eq!(store.abort_pending(), 2);
+        tokio::time::timeout(Duration::from_secs(1), store.wait_for_pending())
+            .await
+            .unwrap();
+        assert_eq!(store.pending(), 0);
+        assert!(store
+            .run_or_defer(config, &caller(3), "pizza-map", Meta::default(), async {
+                Ok(finished_call("fast"))
+            })
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn jobs_past_their_maximum_runtime_are_aborted() {
+        let store = JobStore::default();
+        let config = DeferConfig {
+            max_runtime: Duration::from_millis(50),
+            ..DeferConfig::new(Duration::from_millis(1))
+        };
+        let ctx = CallContext::default();
+        let pending = store
+            .run_or_defer(
+                config,
+                &ctx,
+                "pizza-map",
+                Meta::default(),
+                std::future::pending(),
+            )
+            .await
+            .unwrap();
+        let handle = pending.structured_content["handle"].as_str().unwrap();
+
+        assert!(matches!(
+            store
+                .get_result(
+                    &ctx,
+                    serde_json::json!({ "handle": handle, "waitMs": 1000 }),
+                )
+                .await,
+            Err(ToolCallError::DeferredFailure(_))
+        ));
+        assert_eq!(store.pending(), 0);
+    }
+
+    #[tokio::test]
+    async fn work_dropped_before_deferral_is_aborted() {
+        let store = JobStore::default();
+        let (started, ran) = tokio::sync::oneshot::channel::<()>();
+        let (_keep, dropped) = tokio::sync::oneshot::channel::<()>();
+        let ctx = CallContext::default();
+        let call = store.run_or_defer(
+            DeferConfig::new(Duration::from_secs(60)),
+            &ctx,
+            "pizza-map",
+            Meta::default(),
+            async move {
+                let _started = started;
+                let _ = dropped.await;
+                Ok(finished_call("slow"))
+            },
+        );
+        assert!(tokio::time::timeout(Duration::from_millis(20), call)
+            .await
+            .is_err());
+        // The task owned `started`; aborting it drops the sender.
+        assert!(tokio::time::timeout(Duration::from_secs(1), ran)
+            .await
+            .unwrap()
+            .is_err());
+    }
+}
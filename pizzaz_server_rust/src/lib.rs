@@ -3,8 +3,62 @@
 //! This library provides an MCP server that exposes pizza-themed widgets
 //! for integration with ChatGPT and other MCP clients.
 
+pub mod analytics;
+pub mod arguments;
+pub mod augment_memory;
+pub mod auth;
+pub mod batch;
+pub mod build_info;
+pub mod capture;
+pub mod clients;
+pub mod config;
+pub mod conformance;
+pub mod connections;
+pub mod consent;
+pub mod contract;
+pub mod cors;
+pub mod egress;
+#[cfg(feature = "embedded-manifest")]
+mod embedded;
+pub mod env_compat;
+pub mod error;
+pub mod events;
+pub mod executor;
+pub mod experiments;
+pub mod fixtures;
+pub mod flags;
 pub mod handler;
+mod http;
+pub mod jobs;
+pub mod lifecycle;
+pub mod lint;
+pub mod listeners;
+pub mod load_shed;
+pub mod manifest_check;
+pub mod manifest_env;
+pub mod manifest_format;
+pub mod manifest_generator;
+pub mod manifest_schema;
+pub mod mcp_client;
+pub mod notice;
+#[cfg(feature = "object-storage")]
+pub mod object_storage;
+pub mod publish;
+pub mod rate_limit;
+pub mod refresh_scope;
+pub mod repl;
+pub mod scan;
+pub mod secrets;
+pub mod shutdown;
+pub mod signing;
+pub mod snapshot;
+pub mod sse_backpressure;
+#[doc(hidden)]
+pub mod sse_rewrite;
+pub mod supervisor;
+pub mod trace;
 pub mod types;
+pub mod webhooks;
 pub mod widgets;
 pub mod widgets_manifest;
 
@@ -13,10 +67,13 @@ mod test_helpers;
 
 use async_stream::stream;
 use axum::{
-    extract::ConnectInfo,
-    http::{header, HeaderMap, HeaderValue, Request, Response, StatusCode},
+    extract::{
+        rejection::{BytesRejection, JsonRejection},
+        ConnectInfo, Path, Query,
+    },
+    http::{header, HeaderMap, Request, Response, StatusCode},
     response::IntoResponse,
-    routing::{any_service, get, post},
+    routing::{any_service, delete, get, post, put},
     Extension, Json, Router,
 };
 use bytes::Bytes;
@@ -27,32 +84,143 @@ use rmcp::transport::{
     streamable_http_server::session::local::LocalSessionManager, StreamableHttpServerConfig,
     StreamableHttpService,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::{
-    collections::HashMap,
+    collections::BTreeMap,
     convert::Infallible,
     net::{IpAddr, SocketAddr},
-    sync::Arc,
-    time::{Duration, Instant},
+    path::PathBuf,
+    sync::{Arc, Weak},
+    time::Instant,
 };
 use subtle::ConstantTimeEq;
 use time::{format_description::well_known::Iso8601, OffsetDateTime};
 use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
 use tower::Service;
-use tower_http::cors::CorsLayer;
+
+use error::ApiError;
+use rate_limit::{parse_rate_limit_config, RateLimitConfig, RateLimiter};
+use sse_rewrite::SseRewriter;
 
 type McpResponse = Response<BoxBody<Bytes, Infallible>>;
 
+/// Section of the manifest guide that documents the operator endpoints.
+const REFRESH_DOCS: &str = "mcp-server-refresh-workflow";
+const ROLLBACK_DOCS: &str = "registry-rollback";
+
 #[derive(Clone)]
 struct AppState {
     refresh: RefreshState,
+    auth: Arc<auth::AuthConfig>,
+    dev: DevState,
+    capture: Option<Arc<capture::CaptureRecorder>>,
+    load: Arc<load_shed::LoadMonitor>,
+    lifecycle: Arc<lifecycle::Lifecycle>,
+    sessions: Arc<LocalSessionManager>,
+    jobs: jobs::JobStore,
+    backpressure: Arc<sse_backpressure::BackpressureMetrics>,
+    augment_memory: Arc<augment_memory::AugmentMemory>,
+    config: Arc<config::EffectiveConfig>,
+    executors: executor::Executors,
+    events: events::EventBus,
+    publishing: Arc<publish::Publishing>,
+    notices: notice::Notices,
+    usage: analytics::Usage,
+    consent: consent::Consent,
+    supervisor: supervisor::Supervisor,
+}
+
+/// Developer-only endpoints, enabled with `WIDGETS_DEV_ENDPOINTS=1`.
+#[derive(Clone)]
+struct DevState {
+    enabled: bool,
+    // Kept apart from the MCP handler so simulated calls never drain real tool budgets.
+    simulator: handler::PizzazServerHandler,
+}
+
+impl DevState {
+    fn from_env(handler_options: &HandlerOptions) -> Self {
+        Self {
+            enabled: env_flag("WIDGETS_DEV_ENDPOINTS"),
+            simulator: handler_options.handler(),
+        }
+    }
+}
+
+/// Tool call behaviour shared by the MCP handler and the simulator.
+#[derive(Debug, Clone)]
+struct HandlerOptions {
+    prefetch_hints: bool,
+    lenient_arguments: bool,
+    trace_meta: bool,
+    flags: flags::Flags,
+    experiments: experiments::Experiments,
+    // Shared with the admin API, so a notice set there reaches every session.
+    notices: notice::Notices,
+    // Only attached to the MCP handler, so simulated calls are not counted.
+    usage: analytics::Usage,
+    // Shared with the capture recorder, which redacts opted-out sessions.
+    consent: consent::Consent,
+    clients: clients::ClientProfiles,
+    batch: Option<batch::BatchConfig>,
+    deferred: Option<jobs::DeferConfig>,
+    // One table, so executor bindings changed at runtime apply to simulated calls too.
+    executors: executor::Executors,
+}
+
+impl HandlerOptions {
+    fn from_env() -> Self {
+        Self {
+            prefetch_hints: env_flag("WIDGETS_PREFETCH_HINTS"),
+            lenient_arguments: env_flag("WIDGETS_LENIENT_ARGUMENTS"),
+            trace_meta: env_flag("WIDGETS_TRACE_META"),
+            flags: flags::Flags::from_env(),
+            experiments: experiments::Experiments::from_env(),
+            notices: notice::Notices::from_env(),
+            usage: analytics::Usage::from_env(),
+            consent: consent::Consent::default(),
+            clients: clients::ClientProfiles::from_env(),
+            batch: batch::BatchConfig::from_env(),
+            deferred: jobs::DeferConfig::from_env(),
+            executors: executor::Executors::from_env(),
+        }
+    }
+
+    fn handler(&self) -> handler::PizzazServerHandler {
+        handler::PizzazServerHandler::new()
+            .with_prefetch_hints(self.prefetch_hints)
+            .with_lenient_arguments(self.lenient_arguments)
+            .with_trace_meta(self.trace_meta)
+            .with_flags(self.flags.clone())
+            .with_experiments(self.experiments.clone())
+            .with_notices(self.notices.clone())
+            .with_consent(self.consent.clone())
+            .with_client_profiles(self.clients.clone())
+            .with_batch_tool(self.batch)
+            .with_deferred_results(self.deferred)
+            .with_executors(self.executors.clone())
+    }
+}
+
+/// Reads a boolean environment flag; `1`, `true`, `yes` and `on` enable it.
+pub(crate) fn env_flag(variable: &str) -> bool {
+    std::env::var(variable)
+        .map(|value| {
+            matches!(
+                value.trim().to_ascii_lowercase().as_str(),
+                "1" | "true" | "yes" | "on"
+            )
+        })
+        .unwrap_or(false)
 }
 
 #[derive(Clone)]
 struct RefreshState {
     token: Option<Arc<Vec<u8>>>,
-    rate_limiter: Arc<Mutex<RateLimiter>>,
+    scopes: refresh_scope::RefreshScopes,
+    rate_limiter: Arc<Mutex<RateLimiter<IpAddr>>>,
 }
 
 impl RefreshState {
@@ -64,6 +232,7 @@ impl RefreshState {
 
         Self {
             token,
+            scopes: config.scopes.clone(),
             rate_limiter: Arc::new(Mutex::new(RateLimiter::new(
                 config.rate_limit.max_requests,
                 config.rate_limit.window,
@@ -75,86 +244,34 @@ impl RefreshState {
         self.token.is_some()
     }
 
-    fn token_bytes(&self) -> Option<&[u8]> {
-        self.token.as_deref().map(|vec| vec.as_slice())
+    /// Refresh endpoints also exist when only scoped tokens are configured.
+    fn accepts_refresh(&self) -> bool {
+        self.is_enabled() || !self.scopes.is_empty()
     }
-}
 
-struct RateLimiter {
-    limit: u64,
-    window: Duration,
-    buckets: HashMap<IpAddr, RateLimitBucket>,
-}
-
-impl RateLimiter {
-    fn new(limit: u64, window: Duration) -> Self {
-        Self {
-            limit,
-            window,
-            buckets: HashMap::new(),
-        }
+    fn rate_limit_state(&self) -> std::sync::Weak<dyn rate_limit::PurgeExpired> {
+        let state: Arc<dyn rate_limit::PurgeExpired> = self.rate_limiter.clone();
+        Arc::downgrade(&state)
     }
 
-    fn check(&mut self, ip: IpAddr, now: Instant) -> Result<(), RateLimitRejection> {
-        if self.buckets.len() > 1000 {
-            self.cleanup_expired(now);
-        }
-
-        let entry = self.buckets.entry(ip).or_insert_with(|| RateLimitBucket {
-            window_start: now,
-            count: 0,
-        });
-
-        if now.duration_since(entry.window_start) >= self.window {
-            entry.window_start = now;
-            entry.count = 0;
-        }
-
-        if entry.count < self.limit {
-            entry.count += 1;
-            return Ok(());
-        }
-
-        let elapsed = now.duration_since(entry.window_start);
-        let remaining = self
-            .window
-            .checked_sub(elapsed)
-            .unwrap_or_else(|| Duration::from_secs(0));
-
-        Err(RateLimitRejection {
-            retry_after: if remaining.is_zero() {
-                Duration::from_secs(1)
-            } else {
-                remaining
-            },
-        })
+    fn token_bytes(&self) -> Option<&[u8]> {
+        self.token.as_deref().map(|vec| vec.as_slice())
     }
 
-    fn cleanup_expired(&mut self, now: Instant) {
-        self.buckets
-            .retain(|_, bucket| now.duration_since(bucket.window_start) < self.window * 2);
+    /// Compares a presented bearer token with the operator token in constant time.
+    fn token_matches(&self, provided: &str) -> bool {
+        self.token_bytes().is_some_and(|expected| {
+            expected.len() == provided.len() && expected.ct_eq(provided.as_bytes()).unwrap_u8() == 1
+        })
     }
 }
 
-struct RateLimitBucket {
-    window_start: Instant,
-    count: u64,
-}
-
-struct RateLimitRejection {
-    retry_after: Duration,
-}
-
 struct RefreshConfig {
     token: Option<String>,
+    scopes: refresh_scope::RefreshScopes,
     rate_limit: RateLimitConfig,
 }
 
-struct RateLimitConfig {
-    max_requests: u64,
-    window: Duration,
-}
-
 impl RefreshConfig {
     fn from_env() -> Self {
         let token = std::env::var("WIDGETS_REFRESH_TOKEN")
@@ -163,82 +280,18 @@ impl RefreshConfig {
             .filter(|s| !s.is_empty());
 
         let rate_limit = parse_rate_limit_config(
+            "WIDGETS_REFRESH_RATE_LIMIT",
             std::env::var("WIDGETS_REFRESH_RATE_LIMIT")
                 .ok()
                 .map(|s| s.trim().to_string())
                 .filter(|s| !s.is_empty()),
         );
 
-        Self { token, rate_limit }
-    }
-}
-
-fn parse_rate_limit_config(raw: Option<String>) -> RateLimitConfig {
-    let default = RateLimitConfig {
-        max_requests: 10,
-        window: Duration::from_secs(60),
-    };
-
-    let Some(raw) = raw else {
-        return default;
-    };
-
-    let (count_str, window_str) =
-        match raw.split_once('/') {
-            Some(parts) => parts,
-            None => {
-                tracing::warn!(
-                "Invalid WIDGETS_REFRESH_RATE_LIMIT value '{}'; falling back to default {} per {}s",
-                raw, default.max_requests, default.window.as_secs()
-            );
-                return default;
-            }
-        };
-
-    let max_requests = match count_str.parse::<u64>() {
-        Ok(value) if value > 0 => value,
-        _ => {
-            tracing::warn!(
-                "Invalid rate limit count '{}' in '{}'; using default {}",
-                count_str,
-                raw,
-                default.max_requests
-            );
-            return default;
-        }
-    };
-
-    let (magnitude_str, unit) = window_str.split_at(window_str.len().saturating_sub(1));
-    let magnitude = match magnitude_str.parse::<u64>() {
-        Ok(value) if value > 0 => value,
-        _ => {
-            tracing::warn!(
-                "Invalid rate limit window '{}' in '{}'; using default {}s",
-                window_str,
-                raw,
-                default.window.as_secs()
-            );
-            return default;
-        }
-    };
-
-    let window = match unit {
-        "s" | "S" => Duration::from_secs(magnitude),
-        "m" | "M" => Duration::from_secs(magnitude * 60),
-        _ => {
-            tracing::warn!(
-                "Unsupported rate limit unit '{}' in '{}'; using default window {}s",
-                unit,
-                raw,
-                default.window.as_secs()
-            );
-            return default;
+        Self {
+            token,
+            scopes: refresh_scope::RefreshScopes::from_env(),
+            rate_limit,
         }
-    };
-
-    RateLimitConfig {
-        max_requests,
-        window,
     }
 }
 
@@ -259,6 +312,77 @@ fn parse_rate_limit_config(raw: Option<String>) -> RateLimitConfig {
 /// }
 /// ```
 pub fn create_app() -> Router {
+    create_app_with_cors(cors::CorsConfig::from_env())
+}
+
+/// The MCP handler [`create_app`] serves, configured from the environment, for calling tools
+/// in-process without HTTP (see [`repl`]).
+pub fn create_handler() -> handler::PizzazServerHandler {
+    HandlerOptions::from_env().handler()
+}
+
+/// Creates the application with an explicit CORS policy, e.g. one with a custom origin validator.
+pub fn create_app_with_cors(cors: cors::CorsConfig) -> Router {
+    app_routes(cors).merged()
+}
+
+/// [`create_app`] together with the counters its [`shutdown::ShutdownReport`] is built from.
+pub fn create_app_with_stats() -> (Router, shutdown::ServerStats) {
+    let routes = app_routes(cors::CorsConfig::from_env());
+    let stats = routes.stats.clone();
+    (routes.merged(), stats)
+}
+
+/// The application of [`create_app`], split so the MCP endpoint and the operator endpoints can be
+/// served on different listeners (see [`listeners`]).
+pub fn create_split_app() -> SplitApp {
+    app_routes(cors::CorsConfig::from_env()).split()
+}
+
+/// The two halves of the application; both share one state.
+pub struct SplitApp {
+    /// `/mcp`, `/readyz` and the signing public key.
+    pub mcp: Router,
+    /// `/readyz`, the signing public key and the `/internal` endpoints.
+    pub admin: Router,
+    pub stats: shutdown::ServerStats,
+}
+
+/// Route groups before the shared state and fallback are attached.
+struct AppRoutes {
+    mcp: Router,
+    /// Unauthenticated routes served on every listener.
+    public: Router,
+    operator: Router,
+    state: AppState,
+    stats: shutdown::ServerStats,
+}
+
+impl AppRoutes {
+    fn merged(self) -> Router {
+        let router = self.mcp.merge(self.public).merge(self.operator);
+        finish_app(router, self.state)
+    }
+
+    fn split(self) -> SplitApp {
+        SplitApp {
+            mcp: finish_app(self.mcp.merge(self.public.clone()), self.state.clone()),
+            admin: finish_app(self.operator.merge(self.public), self.state),
+            stats: self.stats,
+        }
+    }
+}
+
+fn finish_app(router: Router, state: AppState) -> Router {
+    router
+        .fallback(error::route_not_found)
+        .layer(Extension(state))
+        .layer(axum::middleware::from_fn(error::assign_request_id))
+}
+
+fn app_routes(cors: cors::CorsConfig) -> AppRoutes {
+    let started = std::time::Instant::now();
+    let started_at = time::OffsetDateTime::now_utc();
     widgets::bootstrap_registry();
 
     let refresh_config = RefreshConfig::from_env();
@@ -273,35 +397,389 @@ pub fn create_app() -> Router {
     } else {
         tracing::info!("Widgets refresh endpoint disabled; set WIDGETS_REFRESH_TOKEN to enable");
     }
+    if !refresh_config.scopes.is_empty() {
+        tracing::info!(
+            scopes = refresh_config.scopes.scopes().len(),
+            "Scoped refresh tokens enabled"
+        );
+    }
+
+    let auth_config = auth::AuthConfig::from_env();
+    if auth_config.is_enabled() {
+        tracing::info!("MCP bearer authentication enabled");
+    }
+
+    let handler_options = HandlerOptions::from_env();
+    let dev_state = DevState::from_env(&handler_options);
+    if dev_state.enabled {
+        tracing::warn!("Developer endpoints enabled; do not expose this server publicly");
+    }
+
+    let capture = capture::CaptureRecorder::from_env();
+    if let Some(recorder) = &capture {
+        tracing::warn!(
+            dir = %recorder.dir().display(),
+            "Capturing redacted MCP traffic to disk"
+        );
+    }
+
+    let webhooks = webhooks::Webhooks::from_env();
+    if let Some(config) = webhooks.config() {
+        tracing::info!(
+            endpoints = config.urls.len(),
+            signed = config.secret.is_some(),
+            "Registry webhooks enabled"
+        );
+    }
+
+    let publishing = publish::Publishing::from_env();
+    if publishing.is_enabled() {
+        tracing::info!(
+            publishers = publishing.publishers().len(),
+            "Manifest publishing enabled"
+        );
+    }
+
+    let lifecycle = Arc::new(lifecycle::Lifecycle::from_env());
+    let supervisor = supervisor::Supervisor::default();
+    let events = events::EventBus::default();
+    events.forward_registry_changes(&supervisor);
+    events.spawn_subscriber(&supervisor, "audit_events", events::audit);
+    let usage = handler_options.usage.clone();
+    events.spawn_subscriber(&supervisor, "analytics_events", move |event| {
+        usage.observe(event)
+    });
+    if webhooks.config().is_some() {
+        let webhooks = webhooks.clone();
+        events.spawn_subscriber(&supervisor, "webhook_events", move |event| {
+            webhooks.observe(event)
+        });
+    }
+    let manifest_watch = widgets::ManifestWatchConfig::from_env();
+    if let Some(watch) = manifest_watch {
+        spawn_manifest_watcher(
+            &supervisor,
+            watch,
+            Arc::downgrade(&lifecycle),
+            events.clone(),
+        );
+    }
+    let scheduled_refresh = widgets::ScheduledRefreshConfig::from_env();
+    if let Some(schedule) = scheduled_refresh {
+        spawn_scheduled_refresh(
+            &supervisor,
+            schedule,
+            Arc::downgrade(&lifecycle),
+            events.clone(),
+        );
+    }
+
+    let load_config = load_shed::LoadShedConfig::from_env();
+    let load_monitor = Arc::new(load_shed::LoadMonitor::new(load_config));
+    load_monitor.spawn_lag_probe(&supervisor);
 
     let session_manager = Arc::new(LocalSessionManager::default());
     let config = StreamableHttpServerConfig::default();
     // Wrap the core MCP handler with the streamable transport so each request gets its own session.
+    // Sessions share one handler so per-tool rate limit budgets apply across connections.
+    let mcp_handler = handler_options.handler().with_events(events.clone());
+    let gc_interval = rate_limit::gc_interval_from_env();
+    rate_limit::spawn_gc(
+        &supervisor,
+        vec![
+            refresh_state.rate_limit_state(),
+            mcp_handler.rate_limit_state(),
+            mcp_handler.job_state(),
+            handler_options.consent.gc_state(),
+            dev_state.simulator.rate_limit_state(),
+        ],
+        gc_interval,
+    );
+    let stats_handler = mcp_handler.clone();
     let streamable_service = StreamableHttpService::new(
-        || Ok(handler::PizzazServerHandler::new()),
-        session_manager,
+        move || Ok(mcp_handler.clone()),
+        session_manager.clone(),
         config,
     );
 
     // Add a response decorator that ensures widget metadata is present on all outgoing messages.
-    let augmented_service = MetaAugmentService::new(streamable_service);
+    // SSE responses are also re-queued per connection so slow readers cannot pile up frames.
+    let backpressure_config = sse_backpressure::BackpressureConfig::from_env();
+    let backpressure_metrics = Arc::new(sse_backpressure::BackpressureMetrics::default());
+    let strict_conformance = env_flag("WIDGETS_STRICT_CONFORMANCE");
+    if strict_conformance {
+        tracing::warn!("Strict MCP conformance checks enabled; outgoing messages are validated");
+    }
+    let augment_memory_config = augment_memory::AugmentMemoryConfig::from_env();
+    let augment_memory = Arc::new(augment_memory::AugmentMemory::new(augment_memory_config));
+    let augmented_service = MetaAugmentService::new(
+        streamable_service,
+        backpressure_config,
+        backpressure_metrics.clone(),
+        strict_conformance,
+        augment_memory.clone(),
+    );
+
+    let effective_config = config::EffectiveConfig::collect(config::ConfigSources {
+        refresh_token_set: refresh_state.is_enabled(),
+        refresh_scopes: &refresh_state.scopes,
+        refresh_rate_limit: refresh_config.rate_limit,
+        rate_limit_gc_interval: gc_interval,
+        auth: &auth_config,
+        cors: &cors,
+        load_shed: load_config,
+        backpressure: backpressure_config,
+        augment_memory: augment_memory_config,
+        capture: capture.as_deref(),
+        manifest_watch,
+        scheduled_refresh,
+        dev_endpoints: dev_state.enabled,
+        strict_conformance,
+        prefetch_hints: handler_options.prefetch_hints,
+        lenient_arguments: handler_options.lenient_arguments,
+        trace_meta: handler_options.trace_meta,
+        feature_flags: handler_options.flags.is_enabled(),
+        experiments: handler_options.experiments.names(),
+        client_profiles: handler_options.clients.names(),
+        webhooks: webhooks.config(),
+        publishers: publishing.publishers(),
+        content_scan: scan::policy(),
+        template_lint: lint::settings(),
+        signing_key: signing::public_key(),
+        egress: egress::policy(),
+        secrets: secrets::secrets().settings(),
+        batch: handler_options.batch,
+        defer_after_ms: handler_options
+            .deferred
+            .map(|deferred| deferred.defer_after.as_millis()),
+    });
 
     let app_state = AppState {
         refresh: refresh_state,
+        auth: Arc::new(auth_config),
+        dev: dev_state,
+        capture,
+        load: load_monitor,
+        lifecycle,
+        sessions: session_manager,
+        jobs: stats_handler.jobs().clone(),
+        backpressure: backpressure_metrics,
+        augment_memory,
+        config: Arc::new(effective_config),
+        executors: handler_options.executors.clone(),
+        events,
+        publishing: Arc::new(publishing),
+        notices: handler_options.notices.clone(),
+        usage: handler_options.usage.clone(),
+        consent: handler_options.consent.clone(),
+        supervisor,
     };
 
-    Router::new()
-        .route("/mcp", any_service(augmented_service))
-        .route("/internal/widgets/refresh", post(refresh_widgets_handler))
+    // Only the MCP endpoint is meant for browsers. Operator endpoints never answer CORS
+    // preflights, so a page cannot even attempt a token-bearing cross-origin request.
+    let mcp_routes = Router::new()
+        .route(
+            "/mcp",
+            any_service(augmented_service)
+                // Inside authentication, so only authenticated traffic is captured.
+                .layer(axum::middleware::from_fn(capture::capture_mcp))
+                .layer(axum::middleware::from_fn(auth::authenticate_mcp))
+                .layer(axum::middleware::from_fn(load_shed::shed_load))
+                .layer(axum::middleware::from_fn(lifecycle::refuse_new_sessions)),
+        )
+        .layer(cors.layer());
+
+    let public = Router::new()
+        .route("/readyz", get(lifecycle::readiness_handler))
+        .route(
+            "/.well-known/pizzaz-signing-key",
+            get(signing::public_key_handler),
+        );
+
+    let internal_routes = Router::new()
+        .route(
+            "/internal/drain",
+            get(lifecycle::drain_handler).post(lifecycle::drain_handler),
+        )
+        .route(
+            "/internal/read-only",
+            get(lifecycle::read_only_handler).put(lifecycle::read_only_handler),
+        )
+        .route(
+            "/internal/notice",
+            get(notice::notice_handler)
+                .put(notice::notice_handler)
+                .delete(notice::notice_handler),
+        )
+        .route("/internal/analytics", get(analytics::analytics_handler))
+        .route("/internal/config", get(config::config_handler))
+        .route("/internal/version", get(build_info::version_handler))
+        .route("/internal/widgets", get(widgets_list_handler))
         .route("/internal/widgets/status", get(widgets_status_handler))
-        .layer(Extension(app_state))
-        .layer(CorsLayer::permissive())
+        .route("/internal/widgets/diff", get(widgets_diff_handler))
+        .route("/internal/widgets/export", get(widgets_export_handler))
+        .route(
+            "/internal/widgets/{id}/history",
+            get(widget_history_handler),
+        )
+        .route(
+            "/internal/widgets/{id}/simulate",
+            post(simulate_widget_handler),
+        );
+
+    // Everything that changes the registry or its bindings is refused while read-only.
+    let registry_write_routes = Router::new()
+        .route("/internal/widgets/refresh", post(refresh_widgets_handler))
+        .route(
+            "/internal/widgets/{id}/refresh",
+            post(refresh_widget_handler),
+        )
+        .route(
+            "/internal/widgets/manifest-path",
+            post(manifest_path_handler),
+        )
+        .route("/internal/widgets/rollback", post(rollback_handler))
+        .route("/internal/widgets/publish", post(publish::submit_handler))
+        .route(
+            "/internal/widgets/publish/{id}",
+            delete(publish::withdraw_handler),
+        )
+        .route(
+            "/internal/widgets/publish/{id}/approve",
+            post(publish::approve_handler),
+        )
+        .route(
+            "/internal/widgets/{id}/executor",
+            put(executor::bind_executor_handler).delete(executor::unbind_executor_handler),
+        )
+        .route_layer(axum::middleware::from_fn(lifecycle::refuse_writes));
+
+    AppRoutes {
+        mcp: mcp_routes,
+        public,
+        operator: internal_routes.merge(registry_write_routes),
+        stats: shutdown::ServerStats {
+            load: app_state.load.clone(),
+            sessions: app_state.sessions.clone(),
+            handler: stats_handler,
+            lifecycle: app_state.lifecycle.clone(),
+            started,
+            started_at,
+        },
+        state: app_state,
+    }
+}
+
+/// Reloads the registry whenever the active manifest changes on disk, until the app is dropped.
+///
+/// Nothing is reloaded while read-only; changes made during a freeze are picked up once it lifts.
+fn spawn_manifest_watcher(
+    supervisor: &supervisor::Supervisor,
+    config: widgets::ManifestWatchConfig,
+    lifecycle: Weak<lifecycle::Lifecycle>,
+    events: events::EventBus,
+) {
+    supervisor.spawn("manifest_watcher", move || {
+        watch_manifest(config, lifecycle.clone(), events.clone())
+    });
+}
+
+async fn watch_manifest(
+    config: widgets::ManifestWatchConfig,
+    lifecycle: Weak<lifecycle::Lifecycle>,
+    events: events::EventBus,
+) {
+    let mut watcher = widgets::ManifestWatcher::new(widgets::manifest_path(), config.debounce);
+    let mut ticker = tokio::time::interval(config.poll_interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        match lifecycle.upgrade() {
+            Some(lifecycle) if lifecycle.read_only().is_some() => continue,
+            Some(_) => {}
+            None => break,
+        }
+        let path = widgets::manifest_path();
+        if !watcher.poll(&path, Instant::now()) {
+            continue;
+        }
+
+        tracing::info!(manifest = %path.display(), "Widget manifest changed on disk; reloading");
+        let report = widgets::reload_registry().await;
+        if !report.deduplicated {
+            events.publish(events::Event::registry_reloaded("watch", &report.result));
+        }
+        if let Err(error) = &report.result {
+            tracing::warn!(error = %error, "Keeping previous widget manifest");
+        }
+    }
+}
+
+/// Reloads the registry on `config`'s schedule, until the app is dropped.
+///
+/// Runs that fall in read-only mode are skipped, like watched reloads.
+fn spawn_scheduled_refresh(
+    supervisor: &supervisor::Supervisor,
+    config: widgets::ScheduledRefreshConfig,
+    lifecycle: Weak<lifecycle::Lifecycle>,
+    events: events::EventBus,
+) {
+    tracing::info!(
+        interval_secs = config.interval.as_secs(),
+        jitter_secs = config.jitter.as_secs(),
+        "Scheduled widget manifest refresh enabled"
+    );
+    supervisor.spawn("scheduled_refresh", move || {
+        refresh_on_schedule(config, lifecycle.clone(), events.clone())
+    });
+}
+
+async fn refresh_on_schedule(
+    config: widgets::ScheduledRefreshConfig,
+    lifecycle: Weak<lifecycle::Lifecycle>,
+    events: events::EventBus,
+) {
+    loop {
+        let delay = config.next_delay(uuid::Uuid::new_v4().as_u64_pair().0);
+        widgets::schedule_refresh(delay);
+        tokio::time::sleep(delay).await;
+        match lifecycle.upgrade() {
+            Some(lifecycle) if lifecycle.read_only().is_some() => {
+                widgets::record_scheduled_refresh(None);
+                continue;
+            }
+            Some(_) => {}
+            None => break,
+        }
+
+        let report = widgets::reload_registry().await;
+        widgets::record_scheduled_refresh(Some(&report.result));
+        if !report.deduplicated {
+            events.publish(events::Event::registry_reloaded("schedule", &report.result));
+        }
+        match &report.result {
+            Ok(outcome) => tracing::debug!(
+                widgets = outcome.widget_count,
+                "Scheduled widget manifest refresh succeeded"
+            ),
+            Err(error) => tracing::warn!(
+                error = %error,
+                "Scheduled widget manifest refresh failed; keeping previous widget manifest"
+            ),
+        }
+    }
 }
 
 /// Wraps an MCP HTTP service and injects widget metadata into JSON and SSE responses.
 #[derive(Clone)]
 struct MetaAugmentService<S> {
     inner: S,
+    backpressure: sse_backpressure::BackpressureConfig,
+    backpressure_metrics: Arc<sse_backpressure::BackpressureMetrics>,
+    /// Validates each outgoing message against the MCP schema (see [`conformance`]).
+    strict_conformance: bool,
+    /// Budget for bytes buffered while augmenting (see [`augment_memory`]).
+    memory: Arc<augment_memory::AugmentMemory>,
 }
 
 impl<S> MetaAugmentService<S>
@@ -311,8 +789,20 @@ where
     S::Future: Send + 'static,
 {
     /// Constructs a new service wrapper that augments outgoing MCP messages with widget metadata.
-    fn new(service: S) -> Self {
-        Self { inner: service }
+    fn new(
+        service: S,
+        backpressure: sse_backpressure::BackpressureConfig,
+        backpressure_metrics: Arc<sse_backpressure::BackpressureMetrics>,
+        strict_conformance: bool,
+        memory: Arc<augment_memory::AugmentMemory>,
+    ) -> Self {
+        Self {
+            inner: service,
+            backpressure,
+            backpressure_metrics,
+            strict_conformance,
+            memory,
+        }
     }
 }
 
@@ -335,10 +825,22 @@ where
     }
 
     /// Calls the wrapped service and conditionally augments JSON or SSE responses with widget metadata.
-    fn call(&mut self, request: Request<axum::body::Body>) -> Self::Future {
+    fn call(&mut self, mut request: Request<axum::body::Body>) -> Self::Future {
+        // Cancelled if the client goes away, so the request's tool calls stop with it.
+        let client_gone = CancellationToken::new();
+        request
+            .extensions_mut()
+            .insert(handler::ClientGone(client_gone.clone()));
         let future = self.inner.call(request);
+        let backpressure = self.backpressure;
+        let backpressure_metrics = Arc::clone(&self.backpressure_metrics);
+        let strict_conformance = self.strict_conformance;
+        let memory = Arc::clone(&self.memory);
         Box::pin(async move {
+            // Dropped mid-await when the client disconnects before the response starts.
+            let disconnect = client_gone.clone().drop_guard();
             let response = future.await?;
+            disconnect.disarm();
             // Only attempt augmentation if the response advertises a supported content type.
             let Some(kind) = classify_response(&response) else {
                 if let Some(content_type) = response
@@ -357,14 +859,45 @@ where
                 return Ok(response);
             };
 
-            let (mut parts, body) = response.into_parts();
+            let (mut parts, mut body) = response.into_parts();
+            let mut reservation = memory.reserve();
 
             match kind {
                 ResponseContentType::Json => {
-                    let collected = match body.collect().await {
-                        Ok(collected) => collected.to_bytes(),
-                        Err(_) => Bytes::new(),
-                    };
+                    let announced = parts
+                        .headers
+                        .get(header::CONTENT_LENGTH)
+                        .and_then(|value| value.to_str().ok()?.parse::<u64>().ok());
+                    if announced.is_some_and(|length| !reservation.try_resize(length)) {
+                        tracing::debug!(
+                            "MetaAugmentService: response too large for the buffer budget; passing through"
+                        );
+                        memory.record_passthrough();
+                        return Ok(Response::from_parts(parts, body));
+                    }
+
+                    let mut collected = Vec::new();
+                    while let Some(frame) = body.frame().await {
+                        let Ok(frame) = frame;
+                        let Ok(data) = frame.into_data() else {
+                            continue;
+                        };
+                        collected.extend_from_slice(&data);
+                        if !reservation.try_resize(collected.len() as u64) {
+                            tracing::debug!(
+                                "MetaAugmentService: buffer budget exhausted; passing response through"
+                            );
+                            memory.record_passthrough();
+                            let buffered = futures::stream::once(futures::future::ready(Ok(
+                                Frame::data(Bytes::from(collected)),
+                            )));
+                            let body = http_body_util::BodyExt::boxed(StreamBody::new(
+                                buffered.chain(http_body_util::BodyStream::new(body)),
+                            ));
+                            return Ok(Response::from_parts(parts, body));
+                        }
+                    }
+                    let collected = Bytes::from(collected);
 
                     parts.headers.remove(header::TRANSFER_ENCODING);
                     parts.headers.remove(header::CONTENT_LENGTH);
@@ -377,12 +910,24 @@ where
                             tracing::debug!(
                                 "MetaAugmentService: unable to parse JSON body; bypassing augmentation"
                             );
-                            let body = Full::new(collected).boxed();
+                            let body = augment_memory::AccountedBody::new(
+                                Full::new(collected),
+                                reservation,
+                            )
+                            .boxed();
                             return Ok(Response::from_parts(parts, body));
                         }
                     };
 
                     augment_widget_metadata(&mut json);
+                    if strict_conformance {
+                        conformance::report(&json);
+                    }
+                    if let Some(seconds) = rate_limited_retry_after(&json) {
+                        parts
+                            .headers
+                            .insert(header::RETRY_AFTER, header::HeaderValue::from(seconds));
+                    }
 
                     let serialized = match serde_json::to_vec(&json) {
                         Ok(bytes) => bytes,
@@ -403,7 +948,14 @@ where
                         );
                     }
 
-                    let body = Full::new(Bytes::from(serialized)).boxed();
+                    // The parsed and original copies are gone; the output stays until it is sent.
+                    drop(json);
+                    reservation.resize(serialized.len() as u64);
+                    let body = augment_memory::AccountedBody::new(
+                        Full::new(Bytes::from(serialized)),
+                        reservation,
+                    )
+                    .boxed();
                     Ok(Response::from_parts(parts, body))
                 }
                 ResponseContentType::Sse => {
@@ -413,7 +965,9 @@ where
                     let mut data_stream = body.into_data_stream();
                     // Buffer incomplete SSE events so we can rewrite each event atomically once its full content arrives.
                     let stream = stream! {
-                        let mut buffer = String::new();
+                        let mut rewriter =
+                            SseRewriter::new().with_strict_conformance(strict_conformance);
+                        let mut passthrough = false;
                         while let Some(chunk_result) = data_stream.next().await {
                             let chunk = match chunk_result {
                                 Ok(chunk) => chunk,
@@ -422,40 +976,36 @@ where
                                     continue;
                                 }
                             };
-
-                            let normalized_chunk = match std::str::from_utf8(&chunk) {
-                                Ok(text) => text.replace("\r\n", "\n"),
-                                Err(_) => {
-                                    tracing::debug!("MetaAugmentService: encountered non UTF-8 SSE chunk; flushing buffer");
-                                    if !buffer.is_empty() {
-                                        let leftover = std::mem::take(&mut buffer);
-                                        yield Ok::<Frame<Bytes>, Infallible>(Frame::data(Bytes::from(leftover)));
-                                    }
-                                    yield Ok::<Frame<Bytes>, Infallible>(Frame::data(chunk));
-                                    continue;
-                                }
-                            };
-
-                            buffer.push_str(&normalized_chunk);
-
-                            while let Some(event) = drain_complete_event(&mut buffer) {
-                                let (frame, event_changed) = frame_from_event(event);
-                                if event_changed {
-                                    tracing::trace!("MetaAugmentService: augmented SSE event");
+                            if passthrough {
+                                yield Ok::<Frame<Bytes>, Infallible>(Frame::data(chunk));
+                                continue;
+                            }
+                            for bytes in rewriter.push(&chunk) {
+                                yield Ok::<Frame<Bytes>, Infallible>(Frame::data(bytes));
+                            }
+                            if !reservation.try_resize(rewriter.buffered_bytes() as u64) {
+                                tracing::debug!(
+                                    "MetaAugmentService: buffer budget exhausted; passing SSE stream through"
+                                );
+                                memory.record_passthrough();
+                                passthrough = true;
+                                reservation.resize(0);
+                                for bytes in rewriter.take_unrewritten() {
+                                    yield Ok::<Frame<Bytes>, Infallible>(Frame::data(bytes));
                                 }
-                                yield Ok::<Frame<Bytes>, Infallible>(frame);
                             }
                         }
-
-                        if !buffer.is_empty() {
-                            let (frame, event_changed) = frame_from_event(std::mem::take(&mut buffer));
-                            if event_changed {
-                                tracing::trace!("MetaAugmentService: augmented trailing SSE event");
-                            }
-                            yield Ok::<Frame<Bytes>, Infallible>(frame);
+                        for bytes in rewriter.finish() {
+                            yield Ok::<Frame<Bytes>, Infallible>(Frame::data(bytes));
                         }
                     };
 
+                    let stream = sse_backpressure::bounded(
+                        stream,
+                        backpressure,
+                        backpressure_metrics,
+                        client_gone,
+                    );
                     let response_body = http_body_util::BodyExt::boxed(StreamBody::new(stream));
                     Ok(Response::from_parts(parts, response_body))
                 }
@@ -468,66 +1018,189 @@ async fn refresh_widgets_handler(
     Extension(state): Extension<AppState>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     headers: HeaderMap,
-) -> impl IntoResponse {
+) -> Result<Json<RefreshResponse>, ApiError> {
+    let caller = authorize_refresh(&state, addr, &headers).await?;
+    let path = widgets::manifest_path();
+    caller.require_manifest(&path)?;
+    caller.audit(addr, "refresh", &path);
+
+    let report = widgets::reload_registry().await;
+    // A coalesced request shares a reload whose events were already sent.
+    if !report.deduplicated {
+        state
+            .events
+            .publish(events::Event::registry_reloaded("refresh", &report.result));
+    }
+    match registry_load_payload(report.result) {
+        Ok(mut payload) => {
+            payload.deduplicated = Some(report.deduplicated);
+            Ok(Json(payload))
+        }
+        Err(mut error) => {
+            if let Some(Value::Object(details)) = error.details_mut() {
+                details.insert("deduplicated".into(), Value::Bool(report.deduplicated));
+            }
+            Err(error)
+        }
+    }
+}
+
+/// Checks the operator token guarding every `/internal` endpoint except status and readiness.
+///
+/// Operator endpoints do not exist, as far as callers can tell, until `WIDGETS_REFRESH_TOKEN` is set.
+pub(crate) fn require_operator(
+    state: &AppState,
+    addr: SocketAddr,
+    headers: &HeaderMap,
+) -> Result<(), ApiError> {
     if !state.refresh.is_enabled() {
-        return StatusCode::NOT_FOUND.into_response();
+        return Err(ApiError::not_found("Operator endpoints are disabled"));
     }
 
-    let Some(expected) = state.refresh.token_bytes() else {
-        return StatusCode::NOT_FOUND.into_response();
-    };
+    let authorized = auth::extract_bearer_token(headers)
+        .is_some_and(|provided| state.refresh.token_matches(provided));
+    if !authorized {
+        tracing::warn!(ip = %addr.ip(), "Invalid operator token provided");
+        return Err(
+            ApiError::unauthorized("widgets-refresh", auth::bearer_error(headers))
+                .with_docs(REFRESH_DOCS),
+        );
+    }
+    Ok(())
+}
 
-    let Some(provided) = extract_bearer_token(&headers) else {
-        return unauthorized_response("Missing or invalid bearer token");
-    };
+/// Accepts the operator token or a scoped refresh token; scopes are checked by each handler.
+fn authenticate_refresh(
+    state: &AppState,
+    addr: SocketAddr,
+    headers: &HeaderMap,
+) -> Result<refresh_scope::RefreshCaller, ApiError> {
+    if !state.refresh.accepts_refresh() {
+        return Err(ApiError::not_found("Operator endpoints are disabled"));
+    }
 
-    if expected.len() != provided.as_bytes().len()
-        || expected.ct_eq(provided.as_bytes()).unwrap_u8() == 0
-    {
-        tracing::warn!(ip = %addr.ip(), "Invalid widgets refresh token provided");
-        return unauthorized_response("Missing or invalid bearer token");
+    let provided = auth::extract_bearer_token(headers);
+    if provided.is_some_and(|provided| state.refresh.token_matches(provided)) {
+        return Ok(refresh_scope::RefreshCaller::Operator);
+    }
+    match provided.and_then(|provided| state.refresh.scopes.identify(provided)) {
+        Some(scope) => Ok(refresh_scope::RefreshCaller::Scoped(scope.clone())),
+        None => {
+            tracing::warn!(ip = %addr.ip(), "Invalid refresh token provided");
+            Err(
+                ApiError::unauthorized("widgets-refresh", auth::bearer_error(headers))
+                    .with_docs(REFRESH_DOCS),
+            )
+        }
     }
+}
+
+/// Authenticates a refresh and charges the caller's refresh rate limit budget.
+async fn authorize_refresh(
+    state: &AppState,
+    addr: SocketAddr,
+    headers: &HeaderMap,
+) -> Result<refresh_scope::RefreshCaller, ApiError> {
+    let caller = authenticate_refresh(state, addr, headers)?;
 
     let ip = addr.ip();
     let now = Instant::now();
     let mut limiter = state.refresh.rate_limiter.lock().await;
     if let Err(rejection) = limiter.check(ip, now) {
         drop(limiter);
-        let retry_seconds = rejection.retry_after.as_secs().max(1);
+        let retry_seconds = handler::retry_after_seconds(rejection.retry_after);
         tracing::warn!(ip = %ip, retry_after = retry_seconds, "Widgets refresh rate limit exceeded");
+        state.events.publish(events::Event::RateLimited {
+            limit: events::RateLimit::Refresh { ip },
+            retry_after: rejection.retry_after,
+        });
+        return Err(ApiError::rate_limited(retry_seconds).with_docs(REFRESH_DOCS));
+    }
+    drop(limiter);
 
-        let metadata = widgets::registry_metadata();
-        let response = RefreshResponse {
-            success: false,
-            widgets_loaded: widgets::get_all_widgets().len(),
-            schema_version: metadata.schema_version.clone(),
-            manifest_timestamp: format_optional_timestamp(metadata.manifest_generated_at),
-            message: Some(format!(
-                "Rate limit exceeded. Retry after {} seconds.",
-                retry_seconds
-            )),
-        };
+    Ok(caller)
+}
+
+#[derive(Serialize)]
+struct WidgetRefreshResponse {
+    success: bool,
+    widget_id: String,
+    content_hash: String,
+    /// Set when the widget's HTML file is missing and the cached copy stays in service.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    degraded: Option<String>,
+}
 
-        let mut http_response = build_refresh_response(StatusCode::TOO_MANY_REQUESTS, response);
-        if let Ok(value) = HeaderValue::from_str(&retry_seconds.to_string()) {
-            http_response
-                .headers_mut()
-                .insert(header::RETRY_AFTER, value);
+/// Reloads a single widget's manifest entry and assets without rebuilding the whole registry.
+async fn refresh_widget_handler(
+    Extension(state): Extension<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<WidgetRefreshResponse>, ApiError> {
+    let caller = authorize_refresh(&state, addr, &headers).await?;
+    let path = widgets::registry_metadata().manifest_path;
+    caller.require_manifest(&path)?;
+    caller.audit(addr, "refresh_widget", &path);
+
+    match widgets::refresh_widget(&id).await {
+        Ok(widget) => {
+            state
+                .events
+                .publish(events::Event::WidgetRefreshed(Arc::clone(&widget)));
+            Ok(Json(WidgetRefreshResponse {
+                success: true,
+                widget_id: widget.id.clone(),
+                content_hash: widget.content_hash.clone(),
+                degraded: widget.degradation(),
+            }))
+        }
+        Err(error) => {
+            tracing::warn!(widget_id = %id, error = %error, "Widget refresh failed");
+            let (status, code) = match error {
+                widgets::WidgetRefreshError::RegistryNotLoaded => {
+                    (StatusCode::SERVICE_UNAVAILABLE, "registry_not_loaded")
+                }
+                widgets::WidgetRefreshError::UnknownWidget(_)
+                | widgets::WidgetRefreshError::MissingFromManifest(_) => {
+                    (StatusCode::NOT_FOUND, "unknown_widget")
+                }
+                widgets::WidgetRefreshError::Disabled(_) => {
+                    (StatusCode::CONFLICT, "widget_disabled")
+                }
+                widgets::WidgetRefreshError::Unsupported { .. } => {
+                    (StatusCode::CONFLICT, "widget_unsupported")
+                }
+                _ => (StatusCode::BAD_REQUEST, "invalid_widget"),
+            };
+            Err(ApiError::new(status, code, error.to_string())
+                .with_docs(REFRESH_DOCS)
+                .with_details(serde_json::json!({ "widget_id": id })))
         }
-        return http_response;
     }
-    drop(limiter);
+}
 
-    match widgets::reload_registry() {
+/// Maps the outcome of loading a manifest onto the refresh response format.
+pub(crate) fn registry_load_payload(
+    result: Result<widgets::RegistryReloadOutcome, widgets::LoadError>,
+) -> Result<RefreshResponse, ApiError> {
+    let schema_errors = result
+        .as_ref()
+        .err()
+        .and_then(widgets::LoadError::schema_violations)
+        .map(<[_]>::to_vec);
+    let error = match result {
         Ok(outcome) => {
-            let response = RefreshResponse {
+            return Ok(RefreshResponse {
                 success: true,
                 widgets_loaded: outcome.widget_count,
                 schema_version: outcome.schema_version,
                 manifest_timestamp: format_optional_timestamp(outcome.manifest_timestamp),
-                message: None,
-            };
-            build_refresh_response(StatusCode::OK, response)
+                diff: outcome.diff,
+                unsupported_widgets: outcome.unsupported,
+                deduplicated: None,
+                not_modified: outcome.not_modified,
+            });
         }
         Err(widgets::LoadError::NotFound { path }) => {
             let metadata = widgets::registry_metadata();
@@ -537,15 +1210,11 @@ async fn refresh_widgets_handler(
                 format!("Manifest not found at {}", path.display())
             };
             tracing::warn!(manifest = %path.display(), "{}", message);
-
-            let response = RefreshResponse {
-                success: false,
-                widgets_loaded: widgets::get_all_widgets().len(),
-                schema_version: metadata.schema_version.clone(),
-                manifest_timestamp: format_optional_timestamp(metadata.manifest_generated_at),
-                message: Some(message),
-            };
-            build_refresh_response(StatusCode::SERVICE_UNAVAILABLE, response)
+            ApiError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "manifest_not_found",
+                message,
+            )
         }
         Err(widgets::LoadError::Validation { path, error }) => {
             tracing::error!(
@@ -553,27 +1222,120 @@ async fn refresh_widgets_handler(
                 error = %error,
                 "Widget manifest refresh failed"
             );
-            let metadata = widgets::registry_metadata();
-            let response = RefreshResponse {
-                success: false,
-                widgets_loaded: widgets::get_all_widgets().len(),
-                schema_version: metadata.schema_version.clone(),
-                manifest_timestamp: format_optional_timestamp(metadata.manifest_generated_at),
-                message: Some(error.to_string()),
-            };
-            build_refresh_response(StatusCode::BAD_REQUEST, response)
+            ApiError::bad_request("manifest_invalid", error.to_string())
         }
+    };
+
+    // Failed loads keep the previous registry; report what is still being served.
+    let metadata = widgets::registry_metadata();
+    let mut details = serde_json::json!({
+        "widgets_loaded": widgets::get_all_widgets().len(),
+        "schema_version": metadata.schema_version.clone(),
+        "manifest_timestamp": format_optional_timestamp(metadata.manifest_generated_at),
+    });
+    if let Some(schema_errors) = schema_errors {
+        details["schema_errors"] = serde_json::json!(schema_errors);
     }
+    Err(error.with_docs(REFRESH_DOCS).with_details(details))
+}
+
+#[derive(Deserialize)]
+struct ManifestPathRequest {
+    path: PathBuf,
+}
+
+/// Repoints the registry at another manifest; the switch only happens if that manifest loads.
+async fn manifest_path_handler(
+    Extension(state): Extension<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    body: Result<Bytes, BytesRejection>,
+) -> Result<Json<RefreshResponse>, ApiError> {
+    let caller = authenticate_refresh(&state, addr, &headers)?;
+
+    let request: ManifestPathRequest = serde_json::from_slice(&body?).map_err(|error| {
+        ApiError::bad_request("invalid_body", format!("Invalid request body: {error}"))
+            .with_docs(REFRESH_DOCS)
+    })?;
+    // Switching away replaces the current manifest's widgets, so both ends must be in scope.
+    caller.require_manifest(&widgets::manifest_path())?;
+    caller.require_manifest(&request.path)?;
+    caller.audit(addr, "manifest_path", &request.path);
+
+    tracing::info!(ip = %addr.ip(), path = %request.path.display(), "Manifest path change requested");
+    let result = widgets::switch_manifest_path(request.path).await;
+    state
+        .events
+        .publish(events::Event::registry_reloaded("manifest_path", &result));
+    registry_load_payload(result).map(Json)
+}
+
+#[derive(Serialize)]
+struct RollbackResponse {
+    success: bool,
+    widgets_loaded: usize,
+    schema_version: Option<String>,
+    manifest_timestamp: Option<String>,
+    manifest_path: String,
+    /// Older registries still available for further rollbacks.
+    history_remaining: usize,
+    diff: widgets::RegistryDiff,
+}
+
+/// Swaps back to the registry that was active before the last reload, switch or widget refresh.
+async fn rollback_handler(
+    Extension(state): Extension<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Json<RollbackResponse>, ApiError> {
+    require_operator(&state, addr, &headers)?;
+
+    let Some(rollback) = widgets::rollback_registry().await else {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "no_previous_registry",
+            "No earlier registry is kept to roll back to",
+        )
+        .with_docs(ROLLBACK_DOCS)
+        .with_details(serde_json::json!({
+            "history_depth": widgets::registry_history_depth(),
+        })));
+    };
+    refresh_scope::RefreshCaller::Operator.audit(addr, "rollback", &rollback.manifest_path);
+    state.events.publish(events::Event::registry_reloaded(
+        "rollback",
+        &Ok(rollback.outcome.clone()),
+    ));
+
+    let outcome = rollback.outcome;
+    Ok(Json(RollbackResponse {
+        success: true,
+        widgets_loaded: outcome.widget_count,
+        schema_version: outcome.schema_version,
+        manifest_timestamp: format_optional_timestamp(outcome.manifest_timestamp),
+        manifest_path: rollback.manifest_path.display().to_string(),
+        history_remaining: rollback.remaining,
+        diff: outcome.diff,
+    }))
 }
 
 #[derive(Serialize)]
-struct RefreshResponse {
+pub(crate) struct RefreshResponse {
     success: bool,
     widgets_loaded: usize,
     schema_version: Option<String>,
     manifest_timestamp: Option<String>,
+    /// Widgets added, removed or changed by the load, against the registry it replaced.
+    diff: widgets::RegistryDiff,
+    /// Widgets skipped because this server cannot meet their `requires`, with the reason.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    unsupported_widgets: BTreeMap<String, String>,
+    /// Set on refresh responses; true when a concurrent reload's result was shared.
     #[serde(skip_serializing_if = "Option::is_none")]
-    message: Option<String>,
+    deduplicated: Option<bool>,
+    /// True when the manifest source was unchanged, so the registry was kept as it was.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    not_modified: bool,
 }
 
 #[derive(Serialize)]
@@ -584,65 +1346,257 @@ struct StatusResponse {
     last_successful_load: Option<String>,
     manifest_path: String,
     manifest_exists: bool,
+    loaded_manifest_path: Option<String>,
+    /// Why the registry was loaded from a cached copy of the manifest, when it was.
+    degraded: Option<String>,
+    /// Earlier registries `POST /internal/widgets/rollback` can return to.
+    registry_history: usize,
+    content_hashes: BTreeMap<String, String>,
+    /// Widgets the manifest lists with `enabled: false`.
+    disabled_widgets: Vec<String>,
+    /// Widgets whose `requires` this server cannot meet, with the reason.
+    unsupported_widgets: BTreeMap<String, String>,
+    /// Widgets served from cached HTML because their HTML file is missing, with the reason.
+    degraded_widgets: BTreeMap<String, String>,
+    /// Each loaded widget's `dependsOn`, for widgets that declare any.
+    widget_dependencies: BTreeMap<String, Vec<String>>,
+    /// Reload attempts, failures by kind, last duration and HTML bytes loaded.
+    load_metrics: widgets::LoadMetrics,
+    /// Outcomes of `WIDGETS_REFRESH_INTERVAL` reloads, when scheduled.
+    scheduled_refresh: Option<widgets::ScheduledRefreshMetrics>,
+    /// Health of each supervised background task, by name.
+    background_tasks: BTreeMap<&'static str, supervisor::TaskHealth>,
+    sse_backpressure: sse_backpressure::BackpressureSnapshot,
+    /// Bytes buffered by responses being augmented, against `MCP_AUGMENT_MAX_BUFFERED_BYTES`.
+    augment_memory: augment_memory::AugmentMemorySnapshot,
+    executors: executor::ExecutorStatus,
+    /// The manifest publish awaiting approval, if any.
+    pending_publish: Option<publish::PendingPublish>,
+    /// Set while registry changes are refused.
+    read_only: Option<lifecycle::ReadOnly>,
+    /// The maintenance notice attached to tool results, if any.
+    notice: Option<notice::Notice>,
 }
 
-async fn widgets_status_handler(Extension(_state): Extension<AppState>) -> impl IntoResponse {
+async fn widgets_status_handler(Extension(state): Extension<AppState>) -> impl IntoResponse {
     let metadata = widgets::registry_metadata();
+    let active_path = widgets::manifest_path();
     let response = StatusResponse {
         registry_initialized: metadata.registry_initialized,
         widgets_count: widgets::get_all_widgets().len(),
         schema_version: metadata.schema_version.clone(),
         last_successful_load: format_optional_timestamp(metadata.last_successful_load),
-        manifest_path: metadata.manifest_path.display().to_string(),
-        manifest_exists: metadata.manifest_exists,
+        manifest_path: active_path.display().to_string(),
+        manifest_exists: active_path.exists(),
+        loaded_manifest_path: metadata
+            .registry_initialized
+            .then(|| metadata.manifest_path.display().to_string()),
+        degraded: metadata.degraded.clone(),
+        registry_history: widgets::registry_history_len(),
+        content_hashes: widgets::get_all_widgets()
+            .iter()
+            .map(|widget| (widget.id.clone(), widget.content_hash.clone()))
+            .collect(),
+        disabled_widgets: widgets::registry().disabled_widgets().to_vec(),
+        unsupported_widgets: widgets::registry().unsupported_widgets().clone(),
+        degraded_widgets: widgets::get_all_widgets()
+            .iter()
+            .filter_map(|widget| Some((widget.id.clone(), widget.degradation()?)))
+            .collect(),
+        widget_dependencies: widgets::registry().dependencies(),
+        load_metrics: metadata.load_metrics.clone(),
+        scheduled_refresh: widgets::scheduled_refresh_metrics(),
+        background_tasks: state.supervisor.status(),
+        sse_backpressure: state.backpressure.snapshot(),
+        augment_memory: state.augment_memory.snapshot(),
+        executors: state.executors.status(),
+        pending_publish: state.publishing.pending().await,
+        read_only: state.lifecycle.read_only(),
+        notice: state.notices.current(),
     };
 
     Json(response)
 }
 
-fn unauthorized_response(message: &str) -> axum::response::Response {
-    let metadata = widgets::registry_metadata();
-    let payload = RefreshResponse {
-        success: false,
-        widgets_loaded: widgets::get_all_widgets().len(),
-        schema_version: metadata.schema_version.clone(),
-        manifest_timestamp: format_optional_timestamp(metadata.manifest_generated_at),
-        message: Some(message.to_string()),
+#[derive(Debug, Deserialize)]
+struct WidgetListQuery {
+    tag: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct WidgetListResponse {
+    /// The `?tag=` filter applied, if any.
+    tag: Option<String>,
+    widgets: Vec<WidgetSummary>,
+}
+
+#[derive(Debug, Serialize)]
+struct WidgetSummary {
+    id: String,
+    title: String,
+    template_uri: String,
+    tags: Vec<String>,
+    content_hash: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deprecation: Option<String>,
+}
+
+/// Lists loaded widgets, optionally only those carrying `?tag=`.
+async fn widgets_list_handler(Query(query): Query<WidgetListQuery>) -> Json<WidgetListResponse> {
+    let tag = query
+        .tag
+        .map(|tag| tag.trim().to_ascii_lowercase())
+        .filter(|tag| !tag.is_empty());
+    let widgets = match &tag {
+        Some(tag) => widgets::get_widgets_by_tag(tag),
+        None => widgets::get_all_widgets(),
     };
-    let mut response = build_refresh_response(StatusCode::UNAUTHORIZED, payload);
-    response.headers_mut().insert(
-        header::WWW_AUTHENTICATE,
-        HeaderValue::from_static("Bearer realm=\"widgets-refresh\""),
-    );
-    response
+    Json(WidgetListResponse {
+        tag,
+        widgets: widgets
+            .iter()
+            .map(|widget| WidgetSummary {
+                id: widget.id.clone(),
+                title: widget.title.clone(),
+                template_uri: widget.template_uri.clone(),
+                tags: widget.tags.clone(),
+                content_hash: widget.content_hash.clone(),
+                deprecation: widget.deprecation.clone(),
+            })
+            .collect(),
+    })
 }
 
-fn build_refresh_response(
-    status: StatusCode,
-    payload: RefreshResponse,
-) -> axum::response::Response {
-    let mut response = Json(payload).into_response();
-    *response.status_mut() = status;
-    response
+#[derive(Debug, Serialize)]
+struct RegistryDiffResponse {
+    /// When the active registry was swapped in; `None` before the first load.
+    recorded_at: Option<String>,
+    #[serde(flatten)]
+    diff: widgets::RegistryDiff,
 }
 
-fn format_optional_timestamp(value: Option<OffsetDateTime>) -> Option<String> {
-    value.and_then(|timestamp| timestamp.format(&Iso8601::DEFAULT).ok())
+/// Reports how the active registry differs from the one it replaced.
+async fn widgets_diff_handler() -> Json<RegistryDiffResponse> {
+    let recorded = widgets::last_registry_diff();
+    Json(RegistryDiffResponse {
+        recorded_at: format_optional_timestamp(recorded.as_ref().map(|last| last.recorded_at)),
+        diff: recorded.map(|last| last.diff).unwrap_or_default(),
+    })
 }
 
-fn extract_bearer_token(headers: &HeaderMap) -> Option<&str> {
-    let value = headers.get(header::AUTHORIZATION)?;
-    let value = value.to_str().ok()?.trim();
-    let mut parts = value.splitn(2, ' ');
-    let scheme = parts.next()?.to_ascii_lowercase();
-    if scheme != "bearer" {
-        return None;
+/// Header on `GET /internal/widgets/export` naming the manifest the registry was loaded from.
+pub const EXPORT_MANIFEST_PATH_HEADER: &str = "x-pizzaz-manifest-path";
+
+/// Header on `GET /internal/widgets/export` carrying when the registry was loaded, ISO 8601.
+pub const EXPORT_LOADED_AT_HEADER: &str = "x-pizzaz-loaded-at";
+
+/// Serializes the active registry back into manifest JSON, for comparing what is served with
+/// what is on disk. Guarded by the operator token, since it carries resolved `${VAR}` values.
+async fn widgets_export_handler(
+    Extension(state): Extension<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<(HeaderMap, Json<widgets_manifest::WidgetManifest>), ApiError> {
+    require_operator(&state, addr, &headers)?;
+
+    let registry = widgets::registry();
+    let metadata = registry.metadata();
+    let mut headers = HeaderMap::new();
+    let values = [
+        (
+            EXPORT_MANIFEST_PATH_HEADER,
+            Some(metadata.manifest_path.display().to_string()),
+        ),
+        (
+            EXPORT_LOADED_AT_HEADER,
+            format_optional_timestamp(metadata.last_successful_load),
+        ),
+    ];
+    for (name, value) in values {
+        if let Some(value) = value.and_then(|value| header::HeaderValue::try_from(value).ok()) {
+            headers.insert(name, value);
+        }
     }
-    let token = parts.next()?.trim();
-    if token.is_empty() {
-        return None;
+    Ok((headers, Json(registry.to_manifest())))
+}
+
+#[derive(Debug, Serialize)]
+struct WidgetHistoryResponse {
+    widget_id: String,
+    /// False when the widget has been removed from the manifest since it was last served.
+    loaded: bool,
+    /// Content changes, newest first.
+    history: Vec<WidgetChangeEntry>,
+}
+
+#[derive(Debug, Serialize)]
+struct WidgetChangeEntry {
+    content_hash: String,
+    size_bytes: usize,
+    recorded_at: Option<String>,
+    manifest_path: String,
+    manifest_generated_at: Option<String>,
+}
+
+/// Lists when a widget's HTML changed, across reloads, refreshes and rollbacks.
+async fn widget_history_handler(
+    Path(id): Path<String>,
+) -> Result<Json<WidgetHistoryResponse>, ApiError> {
+    let history = widgets::widget_history(&id);
+    let loaded = widgets::get_widget_by_id(&id).is_some();
+    if history.is_empty() && !loaded {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "unknown_widget",
+            format!("Widget {id} has never been loaded"),
+        )
+        .with_docs(REFRESH_DOCS));
     }
-    Some(token)
+    Ok(Json(WidgetHistoryResponse {
+        widget_id: id,
+        loaded,
+        history: history
+            .into_iter()
+            .map(|change| WidgetChangeEntry {
+                content_hash: change.content_hash,
+                size_bytes: change.size_bytes,
+                recorded_at: format_optional_timestamp(Some(change.recorded_at)),
+                manifest_path: change.manifest_path.display().to_string(),
+                manifest_generated_at: format_optional_timestamp(change.manifest_generated_at),
+            })
+            .collect(),
+    }))
+}
+
+/// Runs a widget tool with the posted arguments and returns what an MCP client would receive.
+async fn simulate_widget_handler(
+    Extension(state): Extension<AppState>,
+    Path(id): Path<String>,
+    arguments: Result<Json<Value>, JsonRejection>,
+) -> Result<Json<Value>, ApiError> {
+    if !state.dev.enabled {
+        return Err(ApiError::not_found("Developer endpoints are disabled"));
+    }
+    let Json(arguments) = arguments?;
+
+    let ctx = handler::CallContext::default();
+    match state
+        .dev
+        .simulator
+        .call_tool_for_client(&ctx, &id, arguments)
+        .await
+    {
+        Ok(result) => Ok(Json(serde_json::json!({ "result": result }))),
+        Err(error) => Err(
+            ApiError::bad_request("tool_call_failed", error.message.clone())
+                .with_docs("simulating-tool-calls")
+                .with_details(serde_json::to_value(&error).unwrap_or_default()),
+        ),
+    }
+}
+
+fn format_optional_timestamp(value: Option<OffsetDateTime>) -> Option<String> {
+    value.and_then(|timestamp| timestamp.format(&Iso8601::DEFAULT).ok())
 }
 
 /// Identifies whether a response body is JSON or server-sent events based on the `Content-Type` header.
@@ -668,13 +1622,43 @@ enum ResponseContentType {
     Sse,
 }
 
+/// Seconds to advertise in `Retry-After` when a JSON-RPC response is a tool rate limit rejection.
+fn rate_limited_retry_after(payload: &Value) -> Option<u64> {
+    let error = payload.get("error")?;
+    if error.get("code")?.as_i64()? != i64::from(handler::RATE_LIMITED_ERROR_CODE) {
+        return None;
+    }
+    let retry_after_ms = error.get("data")?.get("retryAfterMs")?.as_u64()?;
+    Some(handler::retry_after_seconds(
+        std::time::Duration::from_millis(retry_after_ms),
+    ))
+}
+
+/// `result` as it appears on the wire, after the HTTP layer's `_meta` augmentation.
+pub(crate) fn wire_result(result: &impl serde::Serialize) -> Value {
+    let mut payload = serde_json::json!({ "result": result });
+    augment_widget_metadata(&mut payload);
+    payload["result"].take()
+}
+
 /// Injects `_meta` entries for known widgets into tools, resources, and templates within the MCP payload.
-fn augment_widget_metadata(payload: &mut Value) {
+pub(crate) fn augment_widget_metadata(payload: &mut Value) {
     let Some(result) = payload.get_mut("result") else {
         tracing::trace!("augment_widget_metadata: no result field present");
         return;
     };
 
+    // `initialize` results identify the exact build serving the session.
+    if let Some(server_info) = result.get_mut("serverInfo").and_then(Value::as_object_mut) {
+        if let Value::Object(meta) = server_info
+            .entry("_meta".to_string())
+            .or_insert_with(|| Value::Object(Default::default()))
+        {
+            meta.entry(build_info::BUILD_META_KEY.to_string())
+                .or_insert_with(build_info::build_meta);
+        }
+    }
+
     // Attach widget metadata to any tool definitions returned by the MCP handler.
     if let Some(tools) = result.get_mut("tools").and_then(Value::as_array_mut) {
         for tool in tools {
@@ -686,7 +1670,11 @@ fn augment_widget_metadata(payload: &mut Value) {
                         );
                         object
                             .entry("_meta".to_string())
-                            .or_insert_with(|| serde_json::Value::Object(widget.meta().0));
+                            .or_insert_with(|| serde_json::Value::Object(widget.tool_meta().0));
+                    } else if let Some(meta) = handler::builtin_tool_meta(name) {
+                        object
+                            .entry("_meta".to_string())
+                            .or_insert_with(|| serde_json::Value::Object(meta.0));
                     } else {
                         tracing::trace!(
                             "augment_widget_metadata: tool '{name}' not found in registry"
@@ -707,7 +1695,7 @@ fn augment_widget_metadata(payload: &mut Value) {
                         );
                         object
                             .entry("_meta".to_string())
-                            .or_insert_with(|| serde_json::Value::Object(widget.meta().0));
+                            .or_insert_with(|| serde_json::Value::Object(widget.resource_meta().0));
                     } else {
                         tracing::trace!(
                             "augment_widget_metadata: resource '{uri}' not found in registry"
@@ -732,7 +1720,7 @@ fn augment_widget_metadata(payload: &mut Value) {
                         );
                         object
                             .entry("_meta".to_string())
-                            .or_insert_with(|| serde_json::Value::Object(widget.meta().0));
+                            .or_insert_with(|| serde_json::Value::Object(widget.resource_meta().0));
                     } else {
                         tracing::trace!(
                             "augment_widget_metadata: template '{uri}' not found in registry"
@@ -744,103 +1732,6 @@ fn augment_widget_metadata(payload: &mut Value) {
     }
 }
 
-/// Removes and returns the next complete SSE event (terminated by a blank line) from the buffer.
-fn drain_complete_event(buffer: &mut String) -> Option<String> {
-    let boundary = buffer.find("\n\n")?;
-    let mut extracted: String = buffer.drain(..boundary + 2).collect();
-    if extracted.ends_with("\n\n") {
-        extracted.truncate(extracted.len() - 2);
-    }
-    Some(extracted)
-}
-
-/// Converts an SSE event payload into a `Frame`, augmenting metadata and normalising terminators.
-fn frame_from_event(event: String) -> (Frame<Bytes>, bool) {
-    let (mut processed, event_changed) = augment_sse_event(&event);
-    if !processed.ends_with("\n\n") {
-        processed.push_str("\n\n");
-    }
-    (Frame::data(Bytes::from(processed)), event_changed)
-}
-
-#[cfg_attr(not(test), allow(dead_code))]
-/// Attempts to augment every SSE event in the provided stream, returning `None` when no changes occur.
-fn augment_sse_stream(original: &str) -> Option<String> {
-    let normalized = original.replace("\r\n", "\n");
-    let mut changed_any = false;
-    let mut output = String::with_capacity(normalized.len());
-
-    // Walk each SSE event (terminated by a blank line) and try to inject widget metadata.
-    for segment in normalized.split_inclusive("\n\n") {
-        let (event_body, separator) = if segment.ends_with("\n\n") {
-            (&segment[..segment.len() - 2], "\n\n")
-        } else {
-            (segment, "")
-        };
-
-        let (processed_event, event_changed) = augment_sse_event(event_body);
-        if event_changed {
-            tracing::trace!("augment_sse_stream: augmented SSE event detected");
-            changed_any = true;
-        }
-
-        output.push_str(&processed_event);
-        output.push_str(separator);
-    }
-
-    if changed_any {
-        Some(output)
-    } else {
-        tracing::trace!("augment_sse_stream: no SSE events modified");
-        None
-    }
-}
-
-/// Augments a single SSE event in-place, returning the rewritten payload and whether it changed.
-fn augment_sse_event(event: &str) -> (String, bool) {
-    if event.is_empty() {
-        return (String::new(), false);
-    }
-
-    // Track whether any `data:` lines were rewritten so callers can decide whether to flush the event.
-    let mut event_changed = false;
-    let mut lines_out = Vec::new();
-
-    for line in event.split('\n') {
-        if let Some(rest) = line.strip_prefix("data:") {
-            let trimmed = rest.trim_start();
-            if trimmed.is_empty() {
-                lines_out.push(line.to_string());
-                continue;
-            }
-
-            if let Ok(mut json_value) = serde_json::from_str::<Value>(trimmed) {
-                let original_value = json_value.clone();
-                augment_widget_metadata(&mut json_value);
-                if json_value != original_value {
-                    tracing::trace!("augment_sse_event: modified JSON data line");
-                    event_changed = true;
-                }
-
-                if let Ok(serialized) = serde_json::to_string(&json_value) {
-                    let prefix = &rest[..rest.len() - trimmed.len()];
-                    lines_out.push(format!("data:{}{}", prefix, serialized));
-                    continue;
-                }
-            } else {
-                tracing::trace!(
-                    "augment_sse_event: skipping non-JSON data line '{}'",
-                    trimmed
-                );
-            }
-        }
-
-        lines_out.push(line.to_string());
-    }
-
-    (lines_out.join("\n"), event_changed)
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -918,34 +1809,73 @@ mod tests {
         }
     }
 
-    /// Validates that SSE payloads carrying JSON tool results receive injected widget metadata.
+    /// Rate limit rejections surface their wait as a `Retry-After` header, rounded up.
     #[test]
-    fn augment_sse_stream_injects_meta() {
-        initialize_widgets_for_tests();
-        let original = concat!(
-            "event: message\r\n",
-            "data: {\"jsonrpc\":\"2.0\",\"id\":2,\"result\":{\"tools\":[{\"name\":\"pizza-map\"}]}}\r\n",
-            "\r\n"
-        );
+    fn rate_limited_retry_after_reads_error_data() {
+        let payload = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "error": {
+                "code": handler::RATE_LIMITED_ERROR_CODE,
+                "message": "Rate limit exceeded",
+                "data": { "retryable": true, "retryAfterMs": 1500 }
+            }
+        });
+        assert_eq!(rate_limited_retry_after(&payload), Some(2));
 
-        let augmented = augment_sse_stream(original).expect("stream should be augmented");
-        assert!(
-            augmented.contains("\"_meta\""),
-            "Augmented stream must contain _meta"
-        );
-        assert!(
-            augmented.contains("\"openai/outputTemplate\""),
-            "Widget metadata should be injected"
-        );
+        let other = serde_json::json!({"jsonrpc": "2.0", "id": 3, "error": {"code": -32602}});
+        assert_eq!(rate_limited_retry_after(&other), None);
     }
 
-    /// Confirms that non-JSON SSE messages pass through without modification.
-    #[test]
-    fn augment_sse_stream_preserves_non_json_data() {
-        let original = concat!(": heartbeat\n", "data: ping\n", "\n");
-        assert!(
-            augment_sse_stream(original).is_none(),
-            "Non-JSON SSE payloads should remain untouched"
-        );
+    /// Responses that would take buffering over budget are forwarded untouched.
+    #[tokio::test]
+    async fn augmentation_passes_through_responses_over_the_buffer_budget() {
+        initialize_widgets_for_tests();
+        let memory = Arc::new(augment_memory::AugmentMemory::new(
+            augment_memory::AugmentMemoryConfig {
+                max_buffered_bytes: Some(256),
+            },
+        ));
+        let respond = |payload: Value| {
+            let bytes = Bytes::from(payload.to_string());
+            tower::service_fn(move |_request: Request<axum::body::Body>| {
+                let response = Response::builder()
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Full::new(bytes.clone()).boxed())
+                    .unwrap();
+                async move { Ok::<_, Infallible>(response) }
+            })
+        };
+        let call = |payload: Value| {
+            let mut service = MetaAugmentService::new(
+                respond(payload),
+                sse_backpressure::BackpressureConfig::default(),
+                Arc::default(),
+                false,
+                Arc::clone(&memory),
+            );
+            service.call(Request::new(axum::body::Body::empty()))
+        };
+
+        let small = serde_json::json!({"jsonrpc": "2.0", "id": 1, "result": {"tools": [{"name": "pizza-map"}]}});
+        let response = call(small).await.unwrap();
+        // The augmented body holds its bytes against the budget until it is consumed.
+        assert!(memory.snapshot().buffered_bytes > 0);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let augmented: Value = serde_json::from_slice(&body).unwrap();
+        assert!(augmented["result"]["tools"][0]["_meta"].is_object());
+        assert_eq!(memory.snapshot().buffered_bytes, 0);
+
+        let large = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "result": {"tools": [{"name": "pizza-map", "description": "x".repeat(512)}]}
+        });
+        let response = call(large.clone()).await.unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, large.to_string());
+        let snapshot = memory.snapshot();
+        assert_eq!(snapshot.passthrough_responses, 1);
+        assert_eq!(snapshot.buffered_bytes, 0);
     }
 }
@@ -0,0 +1,303 @@
+//! Readiness reporting and connection draining for rolling deploys, and the read-only switch
+//! operators flip during incident freezes.
+
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use axum::{
+    body::Bytes,
+    extract::{rejection::BytesRejection, ConnectInfo, Request},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{error::ApiError, widgets, AppState};
+
+/// JSON-RPC error code returned to clients opening a session on a draining instance.
+pub const DRAINING_ERROR_CODE: i32 = -32031;
+
+const SESSION_ID_HEADER: &str = "mcp-session-id";
+
+const READ_ONLY_DOCS: &str = "read-only-mode";
+
+/// Process-wide lifecycle flags shared by the readiness probe and the MCP endpoint.
+#[derive(Debug, Default)]
+pub struct Lifecycle {
+    draining: AtomicBool,
+    read_only: Mutex<Option<ReadOnly>>,
+}
+
+/// Why and since when registry changes are refused.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReadOnly {
+    pub reason: Option<String>,
+    /// RFC 3339 time the switch was turned on.
+    pub since: String,
+}
+
+impl Lifecycle {
+    /// Starts read-only when `WIDGETS_READ_ONLY` is set, so a freeze survives restarts.
+    pub(crate) fn from_env() -> Self {
+        let lifecycle = Self::default();
+        if crate::env_flag("WIDGETS_READ_ONLY") {
+            lifecycle.set_read_only(Some("WIDGETS_READ_ONLY".to_string()));
+        }
+        lifecycle
+    }
+
+    pub fn read_only(&self) -> Option<ReadOnly> {
+        self.read_only_lock().clone()
+    }
+
+    /// Refuses registry changes until [`Lifecycle::clear_read_only`]; keeps the original `since`
+    /// if already read-only.
+    pub fn set_read_only(&self, reason: Option<String>) {
+        let mut read_only = self.read_only_lock();
+        let since = read_only
+            .take()
+            .map(|current| current.since)
+            .unwrap_or_else(|| {
+                OffsetDateTime::now_utc()
+                    .format(&Rfc3339)
+                    .unwrap_or_default()
+            });
+        *read_only = Some(ReadOnly { reason, since });
+    }
+
+    pub fn clear_read_only(&self) {
+        self.read_only_lock().take();
+    }
+
+    fn read_only_lock(&self) -> std::sync::MutexGuard<'_, Option<ReadOnly>> {
+        self.read_only.lock().expect("read-only lock poisoned")
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// Marks the instance as draining; returns false if it already was.
+    pub fn start_draining(&self) -> bool {
+        !self.draining.swap(true, Ordering::SeqCst)
+    }
+}
+
+#[derive(Serialize)]
+struct ReadinessResponse {
+    ready: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<&'static str>,
+    /// Set while the registry was loaded from a cached manifest; the instance stays ready.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    degraded: Option<String>,
+}
+
+/// `GET /readyz`: 200 while the instance should receive traffic, 503 otherwise.
+///
+/// An instance whose registry has never loaded would answer `tools/list` with nothing, so it
+/// stays out of rotation until the manifest is in place.
+pub(crate) async fn readiness_handler(Extension(state): Extension<AppState>) -> Response {
+    let metadata = widgets::registry_metadata();
+    let reason = if state.lifecycle.is_draining() {
+        Some("draining")
+    } else if !metadata.registry_initialized {
+        Some("registry_not_loaded")
+    } else {
+        None
+    };
+    let status = match reason {
+        Some(_) => StatusCode::SERVICE_UNAVAILABLE,
+        None => StatusCode::OK,
+    };
+    (
+        status,
+        Json(ReadinessResponse {
+            ready: reason.is_none(),
+            reason,
+            degraded: metadata.degraded,
+        }),
+    )
+        .into_response()
+}
+
+#[derive(Serialize)]
+struct DrainResponse {
+    draining: bool,
+    active_sessions: usize,
+    in_flight_requests: usize,
+    open_streams: usize,
+    pending_jobs: usize,
+    quiesced: bool,
+}
+
+/// `POST /internal/drain` starts draining; `GET` polls progress. Both need the operator token.
+pub(crate) async fn drain_handler(
+    Extension(state): Extension<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    method: Method,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(error) = crate::require_operator(&state, addr, &headers) {
+        return error.into_response();
+    }
+
+    if method == Method::POST && state.lifecycle.start_draining() {
+        tracing::warn!(ip = %addr.ip(), "Drain requested; refusing new MCP sessions");
+    }
+
+    let active_sessions = state.sessions.sessions.read().await.len();
+    let in_flight_requests = state.load.in_flight();
+    let open_streams = state.backpressure.open_streams();
+    let pending_jobs = state.jobs.pending();
+    let draining = state.lifecycle.is_draining();
+    // Sessions are reported but not waited for: the session manager never expires them.
+    Json(DrainResponse {
+        draining,
+        active_sessions,
+        in_flight_requests,
+        open_streams,
+        pending_jobs,
+        quiesced: draining && in_flight_requests == 0 && open_streams == 0 && pending_jobs == 0,
+    })
+    .into_response()
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ReadOnlyRequest {
+    enabled: bool,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct ReadOnlyResponse {
+    read_only: bool,
+    #[serde(flatten)]
+    state: Option<ReadOnly>,
+}
+
+/// `PUT /internal/read-only` turns the switch on or off; `GET` reports it. Both need the operator
+/// token.
+pub(crate) async fn read_only_handler(
+    Extension(state): Extension<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    method: Method,
+    headers: HeaderMap,
+    body: Result<Bytes, BytesRejection>,
+) -> Result<Json<ReadOnlyResponse>, ApiError> {
+    crate::require_operator(&state, addr, &headers)?;
+
+    if method == Method::PUT {
+        let request: ReadOnlyRequest = serde_json::from_slice(&body?).map_err(|error| {
+            ApiError::bad_request("invalid_body", format!("Invalid request body: {error}"))
+                .with_docs(READ_ONLY_DOCS)
+        })?;
+        if request.enabled {
+            tracing::warn!(ip = %addr.ip(), reason = ?request.reason, "Read-only mode enabled");
+            state.lifecycle.set_read_only(request.reason);
+        } else {
+            tracing::warn!(ip = %addr.ip(), "Read-only mode disabled");
+            state.lifecycle.clear_read_only();
+        }
+    }
+
+    let read_only = state.lifecycle.read_only();
+    Ok(Json(ReadOnlyResponse {
+        read_only: read_only.is_some(),
+        state: read_only,
+    }))
+}
+
+/// Middleware for routes that change the registry; answers 503 while read-only is on.
+///
+/// MCP traffic never passes through it and keeps serving the current registry.
+pub(crate) async fn refuse_writes(
+    Extension(state): Extension<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(read_only) = state.lifecycle.read_only() else {
+        return next.run(request).await;
+    };
+    ApiError::new(
+        StatusCode::SERVICE_UNAVAILABLE,
+        "read_only",
+        "The server is in read-only mode; registry changes are refused",
+    )
+    .with_docs(READ_ONLY_DOCS)
+    .with_details(serde_json::to_value(read_only).unwrap_or_default())
+    .into_response()
+}
+
+/// Middleware that refuses session-initiating `/mcp` POSTs while the instance drains.
+///
+/// Requests carrying an `Mcp-Session-Id` belong to existing conversations and pass through.
+pub(crate) async fn refuse_new_sessions(
+    Extension(state): Extension<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let opens_session =
+        request.method() == Method::POST && !request.headers().contains_key(SESSION_ID_HEADER);
+    if !(opens_session && state.lifecycle.is_draining()) {
+        return next.run(request).await;
+    }
+
+    let mut response = (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": null,
+            "error": {
+                "code": DRAINING_ERROR_CODE,
+                "message": "Server is draining; connect to another instance",
+                "data": { "retryable": true, "retryAfterMs": 1000 },
+            },
+        })),
+    )
+        .into_response();
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(1u64));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn start_draining_is_idempotent() {
+        let lifecycle = Lifecycle::default();
+        assert!(!lifecycle.is_draining());
+        assert!(lifecycle.start_draining());
+        assert!(!lifecycle.start_draining());
+        assert!(lifecycle.is_draining());
+    }
+
+    #[test]
+    fn read_only_keeps_its_original_start_time() {
+        let lifecycle = Lifecycle::default();
+        assert_eq!(lifecycle.read_only(), None);
+
+        lifecycle.set_read_only(Some("incident".to_string()));
+        let since = lifecycle.read_only().unwrap().since;
+        lifecycle.set_read_only(Some("still frozen".to_string()));
+        let read_only = lifecycle.read_only().unwrap();
+        assert_eq!(read_only.reason.as_deref(), Some("still frozen"));
+        assert_eq!(read_only.since, since);
+
+        lifecycle.clear_read_only();
+        assert_eq!(lifecycle.read_only(), None);
+    }
+}
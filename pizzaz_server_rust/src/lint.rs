@@ -0,0 +1,506 @@
+//! Linting of widget HTML against what the Apps SDK host expects.
+//!
+//! ChatGPT renders a widget's HTML in a sandboxed frame and expects it to mount into a root
+//! element from a script, without navigating the page around it. Mistakes here show up as a blank
+//! or broken widget rather than an error, so every widget read from a local or fetched HTML asset
+//! is linted when it loads, and `pizzaz_lint` runs the same rules over a manifest from the command
+//! line. The rules are:
+//!
+//! - `missing_root`: no element whose `id` ends in `root`, such as `<div id="pizzaz-root">`;
+//! - `top_level_navigation`: `top.location`, `parent.location`, `target="_top"` or a
+//!   `<meta http-equiv="refresh">`;
+//! - `missing_bootstrap`: no `<script>` to load the widget bundle;
+//! - `oversized_html`: HTML larger than `WIDGETS_LINT_MAX_HTML_BYTES` (1 MiB by default);
+//! - `inline_event_handler`: `on…=` attributes such as `onclick`;
+//! - `javascript_url`: `href`, `src`, `action` or `formaction` set to a `javascript:` URL;
+//! - `unclosed_tag`: elements left open or closed without being opened, outside `<script>` and
+//!   `<style>`. Void elements and those whose end tag HTML lets you omit, such as `<p>` and
+//!   `<li>`, are not reported.
+//!
+//! `WIDGETS_LINT_POLICY` takes the same `warn`/`block`/`off` grammar as `WIDGETS_SCAN_POLICY`.
+//! It defaults to `warn`, except that `inline_event_handler` and `javascript_url` block unless
+//! the policy names them.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use anyhow::{bail, Context};
+use regex::Regex;
+use serde::Serialize;
+
+use crate::{
+    scan::{Finding, ScanAction, ScanPolicy},
+    widgets::is_remote_path,
+    widgets_manifest::read_manifest,
+};
+
+pub const MISSING_ROOT: &str = "missing_root";
+pub const TOP_LEVEL_NAVIGATION: &str = "top_level_navigation";
+pub const MISSING_BOOTSTRAP: &str = "missing_bootstrap";
+pub const OVERSIZED_HTML: &str = "oversized_html";
+pub const INLINE_EVENT_HANDLER: &str = "inline_event_handler";
+pub const JAVASCRIPT_URL: &str = "javascript_url";
+pub const UNCLOSED_TAG: &str = "unclosed_tag";
+
+/// Elements that never have an end tag.
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+/// Elements whose end tag may be omitted, so leaving them open is valid HTML.
+const OPTIONAL_END_ELEMENTS: &[&str] = &[
+    "body", "caption", "colgroup", "dd", "dt", "head", "html", "li", "optgroup", "option", "p",
+    "rp", "rt", "tbody", "td", "tfoot", "th", "thead", "tr",
+];
+
+pub const DEFAULT_MAX_HTML_BYTES: usize = 1024 * 1024;
+
+static ROOT_ELEMENT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)<[a-z][a-z0-9-]*\b[^>]*?\bid\s*=\s*["']?[\w-]*root["'\s/>]"#)
+        .expect("root element pattern compiles")
+});
+static SCRIPT_ELEMENT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)<script\b").expect("script element pattern compiles"));
+static TOP_LOCATION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b(top|parent)\s*\.\s*location\b").expect("top location pattern compiles")
+});
+static TOP_TARGET: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)\btarget\s*=\s*["']?(_top|_parent)\b"#).expect("top target pattern compiles")
+});
+static META_REFRESH: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)<meta\b[^>]*\bhttp-equiv\s*=\s*["']?refresh\b"#)
+        .expect("meta refresh pattern compiles")
+});
+static RAW_TEXT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?is)<!--.*?-->|<script\b[^>]*>.*?</script\s*>|<style\b[^>]*>.*?</style\s*>")
+        .expect("raw text pattern compiles")
+});
+static TAG: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"<(/?)([a-zA-Z][a-zA-Z0-9-]*)((?:"[^"]*"|'[^']*'|[^'">])*)>"#)
+        .expect("tag pattern compiles")
+});
+static EVENT_HANDLER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)(?:^|\s)(on[a-z]+)\s*="#).expect("event handler pattern compiles")
+});
+static JAVASCRIPT_URL_ATTRIBUTE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)(?:^|\s)(href|src|action|formaction)\s*=\s*["']?\s*javascript:"#)
+        .expect("javascript URL pattern compiles")
+});
+
+/// Lint rules and their limits.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TemplateLinter {
+    pub max_html_bytes: usize,
+}
+
+impl Default for TemplateLinter {
+    fn default() -> Self {
+        Self {
+            max_html_bytes: DEFAULT_MAX_HTML_BYTES,
+        }
+    }
+}
+
+impl TemplateLinter {
+    pub fn from_env() -> Self {
+        let max_html_bytes = match std::env::var("WIDGETS_LINT_MAX_HTML_BYTES") {
+            Ok(raw) => raw.trim().parse().unwrap_or_else(|_| {
+                tracing::warn!(value = %raw, "Ignoring invalid WIDGETS_LINT_MAX_HTML_BYTES");
+                DEFAULT_MAX_HTML_BYTES
+            }),
+            Err(_) => DEFAULT_MAX_HTML_BYTES,
+        };
+        Self { max_html_bytes }
+    }
+
+    /// Every rule's findings for one widget's HTML, in rule order.
+    pub fn lint(&self, html: &str) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        if !ROOT_ELEMENT.is_match(html) {
+            findings.push(Finding::new(
+                MISSING_ROOT,
+                "no root mount element; add an element such as <div id=\"pizzaz-root\"></div> \
+                 for the bundle to render into",
+            ));
+        }
+
+        if let Some(capture) = TOP_LOCATION.captures(html) {
+            findings.push(Finding::new(
+                TOP_LEVEL_NAVIGATION,
+                format!(
+                    "uses {}.location; the widget runs in a sandboxed frame, so open links with \
+                     window.openai.openExternal instead",
+                    &capture[1]
+                ),
+            ));
+        }
+        if let Some(capture) = TOP_TARGET.captures(html) {
+            findings.push(Finding::new(
+                TOP_LEVEL_NAVIGATION,
+                format!(
+                    "a link targets {}; open links with window.openai.openExternal instead",
+                    capture[1].to_ascii_lowercase()
+                ),
+            ));
+        }
+        if META_REFRESH.is_match(html) {
+            findings.push(Finding::new(
+                TOP_LEVEL_NAVIGATION,
+                "<meta http-equiv=\"refresh\"> navigates the widget frame; remove it",
+            ));
+        }
+
+        if !SCRIPT_ELEMENT.is_match(html) {
+            findings.push(Finding::new(
+                MISSING_BOOTSTRAP,
+                "no <script>; the HTML must load the bundle that reads window.openai and mounts \
+                 the widget",
+            ));
+        }
+
+        if html.len() > self.max_html_bytes {
+            findings.push(Finding::new(
+                OVERSIZED_HTML,
+                format!(
+                    "HTML is {} bytes, over the {} byte limit; move inline assets into the \
+                     widget's css and js files",
+                    html.len(),
+                    self.max_html_bytes
+                ),
+            ));
+        }
+
+        // Markup only: script and style bodies and comments are not tags or attributes.
+        let markup = RAW_TEXT.replace_all(html, "");
+        let tags: Vec<_> = TAG.captures_iter(&markup).collect();
+        if let Some(attribute) = tags.iter().find_map(|tag| EVENT_HANDLER.captures(&tag[3])) {
+            findings.push(Finding::new(
+                INLINE_EVENT_HANDLER,
+                format!(
+                    "uses an inline {} attribute; attach event listeners from the widget script",
+                    attribute[1].to_ascii_lowercase()
+                ),
+            ));
+        }
+        if let Some(attribute) = tags
+            .iter()
+            .find_map(|tag| JAVASCRIPT_URL_ATTRIBUTE.captures(&tag[3]))
+        {
+            findings.push(Finding::new(
+                JAVASCRIPT_URL,
+                format!(
+                    "sets {} to a javascript: URL; handle the action from the widget script",
+                    attribute[1].to_ascii_lowercase()
+                ),
+            ));
+        }
+        let unbalanced = unbalanced_tags(tags.iter().map(|tag| {
+            let closing = !tag[1].is_empty();
+            let self_closing = tag[3].trim_end().ends_with('/');
+            (tag[2].to_ascii_lowercase(), closing, self_closing)
+        }));
+        if !unbalanced.is_empty() {
+            let mut problems = unbalanced.iter().take(3).cloned().collect::<Vec<_>>();
+            if unbalanced.len() > 3 {
+                problems.push(format!("{} more", unbalanced.len() - 3));
+            }
+            findings.push(Finding::new(
+                UNCLOSED_TAG,
+                format!(
+                    "unbalanced tags ({}); close every element so the frame parses the markup \
+                     as written",
+                    problems.join(", ")
+                ),
+            ));
+        }
+        findings
+    }
+}
+
+/// Problems with `(name, closing, self_closing)` tags in document order, such as
+/// `<div> is never closed`.
+fn unbalanced_tags(tags: impl Iterator<Item = (String, bool, bool)>) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut open: Vec<String> = Vec::new();
+    let report_unclosed = |name: &str, problems: &mut Vec<String>| {
+        if !OPTIONAL_END_ELEMENTS.contains(&name) {
+            problems.push(format!("<{name}> is never closed"));
+        }
+    };
+    for (name, closing, self_closing) in tags {
+        if VOID_ELEMENTS.contains(&name.as_str()) || (self_closing && !closing) {
+            continue;
+        }
+        if !closing {
+            open.push(name);
+            continue;
+        }
+        match open.iter().rposition(|element| *element == name) {
+            Some(index) => {
+                for element in open.drain(index..).skip(1) {
+                    report_unclosed(&element, &mut problems);
+                }
+            }
+            None => problems.push(format!("</{name}> has no opening tag")),
+        }
+    }
+    for element in open {
+        report_unclosed(&element, &mut problems);
+    }
+    problems
+}
+
+/// Findings for one manifest entry, from [`lint_manifest`].
+#[derive(Debug, Clone)]
+pub struct WidgetLint {
+    pub widget_id: String,
+    /// The local HTML file linted, or `None` when the entry's HTML is remote and was skipped.
+    pub html_path: Option<PathBuf>,
+    pub findings: Vec<Finding>,
+}
+
+/// Lints the local HTML file (`htmlPath` or `assets.html`) of every enabled widget in the
+/// manifest at `path`.
+pub fn lint_manifest(path: &Path, linter: &TemplateLinter) -> anyhow::Result<Vec<WidgetLint>> {
+    let manifest = read_manifest(path)?;
+    let manifest_dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let mut results = Vec::new();
+    for entry in manifest.widgets.iter().filter(|entry| entry.enabled) {
+        let reference = entry
+            .html_path
+            .as_deref()
+            .or_else(|| entry.assets.as_ref()?.html.as_deref())
+            .map(str::trim)
+            .filter(|reference| !is_remote_path(reference));
+        let Some(reference) = reference else {
+            results.push(WidgetLint {
+                widget_id: entry.id.clone(),
+                html_path: None,
+                findings: Vec::new(),
+            });
+            continue;
+        };
+        let html_path = manifest_dir.join(reference);
+        let html = std::fs::read_to_string(&html_path).with_context(|| {
+            format!(
+                "Failed to read HTML for widget {} at {}",
+                entry.id,
+                html_path.display()
+            )
+        })?;
+        results.push(WidgetLint {
+            widget_id: entry.id.clone(),
+            findings: linter.lint(&html),
+            html_path: Some(html_path),
+        });
+    }
+    Ok(results)
+}
+
+/// What `/internal/config` reports about linting.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LintSettings {
+    pub policy: ScanPolicy,
+    pub max_html_bytes: usize,
+}
+
+struct Linting {
+    policy: ScanPolicy,
+    linter: TemplateLinter,
+}
+
+/// The lint policy before `WIDGETS_LINT_POLICY`: warn, except that inline event handlers and
+/// `javascript:` URLs block.
+pub fn default_policy() -> ScanPolicy {
+    let mut policy = ScanPolicy::default();
+    for rule in [INLINE_EVENT_HANDLER, JAVASCRIPT_URL] {
+        policy.rules.insert(rule.to_string(), ScanAction::Block);
+    }
+    policy
+}
+
+static LINTING: LazyLock<Linting> = LazyLock::new(|| Linting {
+    policy: match std::env::var("WIDGETS_LINT_POLICY") {
+        Ok(raw) => default_policy()
+            .with_overrides(&raw)
+            .unwrap_or_else(|error| {
+                tracing::warn!(error = %error, "Ignoring invalid WIDGETS_LINT_POLICY");
+                default_policy()
+            }),
+        Err(_) => default_policy(),
+    },
+    linter: TemplateLinter::from_env(),
+});
+
+pub fn settings() -> LintSettings {
+    LintSettings {
+        policy: LINTING.policy.clone(),
+        max_html_bytes: LINTING.linter.max_html_bytes,
+    }
+}
+
+/// Applies `policy` to the findings for `html`, returning warnings to record, or an error when a
+/// finding blocks the widget.
+pub fn check_with(
+    policy: &ScanPolicy,
+    linter: &TemplateLinter,
+    widget_id: &str,
+    html: &str,
+) -> anyhow::Result<Vec<String>> {
+    let mut warnings = Vec::new();
+    let mut blocked = Vec::new();
+    for finding in linter.lint(html) {
+        let message = format!("{}: {}", finding.rule, finding.message);
+        match policy.action(&finding.rule) {
+            ScanAction::Off => {}
+            ScanAction::Warn => {
+                tracing::warn!(widget_id, rule = %finding.rule, "Widget template lint: {}", finding.message);
+                warnings.push(format!("Template lint {message}"));
+            }
+            ScanAction::Block => blocked.push(message),
+        }
+    }
+    if !blocked.is_empty() {
+        bail!(
+            "Widget HTML blocked by template lint: {}",
+            blocked.join("; ")
+        );
+    }
+    Ok(warnings)
+}
+
+/// Lints a widget with the configured rules and policy.
+pub(crate) fn check(widget_id: &str, html: &str) -> anyhow::Result<Vec<String>> {
+    check_with(&LINTING.policy, &LINTING.linter, widget_id, html)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLEAN: &str = r#"<!doctype html>
+<html>
+  <head><script type="module" src="https://cdn.example/pizzaz-map.js"></script></head>
+  <body><div id="pizzaz-map-root"></div></body>
+</html>"#;
+
+    #[test]
+    fn clean_templates_have_no_findings() {
+        assert!(TemplateLinter::default().lint(CLEAN).is_empty());
+    }
+
+    #[test]
+    fn findings_name_the_rule_and_the_fix() {
+        let html = r#"<html><head><meta http-equiv="refresh" content="0;url=/"></head>
+<body><div id="app"></div><a href="/menu" target="_TOP">Menu</a>
+<button onclick="window.top.location = '/order'">Order</button></body></html>"#;
+        let findings = TemplateLinter { max_html_bytes: 64 }.lint(html);
+        let rules: Vec<_> = findings
+            .iter()
+            .map(|finding| finding.rule.as_str())
+            .collect();
+        assert_eq!(
+            rules,
+            vec![
+                MISSING_ROOT,
+                TOP_LEVEL_NAVIGATION,
+                TOP_LEVEL_NAVIGATION,
+                TOP_LEVEL_NAVIGATION,
+                MISSING_BOOTSTRAP,
+                OVERSIZED_HTML,
+                INLINE_EVENT_HANDLER,
+            ]
+        );
+        assert!(findings[1].message.starts_with("uses top.location"));
+        assert!(findings[2].message.starts_with("a link targets _top"));
+        assert!(findings[5]
+            .message
+            .contains(&format!("HTML is {} bytes", html.len())));
+    }
+
+    #[test]
+    fn policy_decides_between_warning_and_blocking() {
+        let html = r#"<div id="root"></div>"#;
+        let linter = TemplateLinter::default();
+
+        let warnings = check_with(&ScanPolicy::default(), &linter, "pizza-map", html).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("Template lint missing_bootstrap: no <script>"));
+
+        let policy = ScanPolicy::parse("block,missing_bootstrap=off").unwrap();
+        assert!(check_with(&policy, &linter, "pizza-map", html)
+            .unwrap()
+            .is_empty());
+
+        let policy = ScanPolicy::parse("missing_bootstrap=block").unwrap();
+        let error = check_with(&policy, &linter, "pizza-map", html).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Widget HTML blocked by template lint: missing_bootstrap"));
+    }
+
+    #[test]
+    fn unsafe_attributes_and_unbalanced_tags_are_found_outside_scripts() {
+        let html = r#"<div id="root"><!-- <span> --><p>Order<ul><li>One<li>Two</ul>
+<a HREF=" javascript:order()">Go</a><img src="x.png" OnError="retry()"><br/>
+<section><span></div></em>
+<script type="module">if (a < b && c > d) { el.innerHTML = "<b onclick=x>"; }</script></div>"#;
+        let findings = TemplateLinter::default().lint(html);
+        let found: Vec<_> = findings
+            .iter()
+            .map(|finding| (finding.rule.as_str(), finding.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    INLINE_EVENT_HANDLER,
+                    "uses an inline onerror attribute; attach event listeners from the widget \
+                     script"
+                ),
+                (
+                    JAVASCRIPT_URL,
+                    "sets href to a javascript: URL; handle the action from the widget script"
+                ),
+                (
+                    UNCLOSED_TAG,
+                    "unbalanced tags (<section> is never closed, <span> is never closed, </em> \
+                     has no opening tag, 1 more); close every element so the frame parses the \
+                     markup as written"
+                ),
+            ]
+        );
+
+        let policy = default_policy();
+        let error = check_with(&policy, &TemplateLinter::default(), "pizza-map", html).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Widget HTML blocked by template lint: inline_event_handler"));
+        let policy = default_policy()
+            .with_overrides("inline_event_handler=warn,javascript_url=off")
+            .unwrap();
+        let warnings = check_with(&policy, &TemplateLinter::default(), "pizza-map", html).unwrap();
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[1].starts_with("Template lint unclosed_tag: "));
+    }
+
+    #[test]
+    fn fixture_manifest_lints_clean() {
+        let results = lint_manifest(
+            Path::new("tests/fixtures/widgets.json"),
+            &TemplateLinter::default(),
+        )
+        .unwrap();
+        assert_eq!(results.len(), 5);
+        for result in results {
+            assert!(
+                result.html_path.is_some(),
+                "{} was skipped",
+                result.widget_id
+            );
+            assert!(result.findings.is_empty(), "{result:?}");
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! Addresses the server listens on.
+//!
+//! By default everything is served on `0.0.0.0:$PORT` (port 8000 when `PORT` is unset).
+//! `MCP_LISTEN` replaces that with a comma-separated list, e.g. `0.0.0.0:8000,[::1]:8000` to add
+//! IPv6 loopback. `MCP_ADMIN_LISTEN` moves the operator endpoints (`/internal/...`) to listeners of
+//! their own, e.g. `127.0.0.1:9000`, so they can stay off the public interface. `/mcp` is then
+//! served only on the `MCP_LISTEN` addresses; `/readyz` is served on both.
+//!
+//! Each entry is an `address:port`, a bare address that takes `$PORT` (`::`, `127.0.0.1`), or a
+//! bare port that listens on `0.0.0.0`.
+//!
+//! With `MCP_REUSE_PORT=1` every listener sets `SO_REUSEPORT` (Unix only), so a new process can
+//! bind the same addresses while the old one is still serving. The kernel spreads new connections
+//! across both until the old process gets SIGTERM and closes its listeners.
+
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+};
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use tokio::net::{TcpListener, TcpSocket};
+
+pub const DEFAULT_PORT: u16 = 8000;
+
+/// The listeners to open.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ListenerConfig {
+    /// Serve `/mcp`, and the operator endpoints unless `admin` is set.
+    pub mcp: Vec<SocketAddr>,
+    /// Serve the operator endpoints only.
+    pub admin: Option<Vec<SocketAddr>>,
+    /// Bind with `SO_REUSEPORT`, so several processes can share the addresses.
+    pub reuse_port: bool,
+}
+
+impl ListenerConfig {
+    /// Reads `PORT`, `MCP_LISTEN` and `MCP_ADMIN_LISTEN`. An invalid `PORT` falls back to 8000, as
+    /// it always has; an invalid address list is an error, since guessing could expose the
+    /// operator endpoints.
+    pub fn from_env() -> Result<Self> {
+        let port = match std::env::var("PORT") {
+            Ok(raw) => raw.trim().parse().unwrap_or_else(|_| {
+                tracing::warn!(value = %raw, "Ignoring invalid PORT");
+                DEFAULT_PORT
+            }),
+            Err(_) => DEFAULT_PORT,
+        };
+        let read = |variable: &str| {
+            std::env::var(variable)
+                .ok()
+                .filter(|value| !value.trim().is_empty())
+                .map(|value| {
+                    parse_addresses(&value, port).with_context(|| format!("Invalid {variable}"))
+                })
+                .transpose()
+        };
+
+        let mcp = read("MCP_LISTEN")?
+            .unwrap_or_else(|| vec![SocketAddr::from((Ipv4Addr::UNSPECIFIED, port))]);
+        let admin = read("MCP_ADMIN_LISTEN")?;
+        if let Some(shared) = admin.iter().flatten().find(|address| mcp.contains(address)) {
+            bail!("{shared} is listed in both MCP_LISTEN and MCP_ADMIN_LISTEN");
+        }
+        Ok(Self {
+            mcp,
+            admin,
+            reuse_port: crate::env_flag("MCP_REUSE_PORT"),
+        })
+    }
+
+    /// Binds one of the configured addresses.
+    pub fn bind(&self, address: SocketAddr) -> io::Result<TcpListener> {
+        bind(address, self.reuse_port)
+    }
+}
+
+/// Binds and listens on `address`, optionally with `SO_REUSEPORT`.
+pub fn bind(address: SocketAddr, reuse_port: bool) -> io::Result<TcpListener> {
+    let socket = match address {
+        SocketAddr::V4(_) => TcpSocket::new_v4()?,
+        SocketAddr::V6(_) => TcpSocket::new_v6()?,
+    };
+    // What `TcpListener::bind` does on Unix: restarts need not wait out TIME_WAIT.
+    #[cfg(unix)]
+    socket.set_reuseaddr(true)?;
+    if reuse_port {
+        #[cfg(unix)]
+        socket.set_reuseport(true)?;
+        #[cfg(not(unix))]
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "MCP_REUSE_PORT needs SO_REUSEPORT, which this platform lacks",
+        ));
+    }
+    socket.bind(address)?;
+    socket.listen(1024)
+}
+
+/// Parses a comma-separated address list; entries without a port use `default_port`.
+pub fn parse_addresses(raw: &str, default_port: u16) -> Result<Vec<SocketAddr>> {
+    let mut addresses = Vec::new();
+    for entry in raw
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        let address = parse_address(entry, default_port)
+            .with_context(|| format!("{entry:?} is not an address, address:port or port"))?;
+        if !addresses.contains(&address) {
+            addresses.push(address);
+        }
+    }
+    if addresses.is_empty() {
+        bail!("no addresses given");
+    }
+    Ok(addresses)
+}
+
+fn parse_address(entry: &str, default_port: u16) -> Option<SocketAddr> {
+    if let Ok(address) = entry.parse::<SocketAddr>() {
+        return Some(address);
+    }
+    let bare = entry
+        .strip_prefix('[')
+        .and_then(|rest| rest.strip_suffix(']'))
+        .unwrap_or(entry);
+    if let Ok(ip) = bare.parse::<IpAddr>() {
+        return Some(SocketAddr::new(ip, default_port));
+    }
+    entry
+        .parse::<u16>()
+        .ok()
+        .map(|port| SocketAddr::from((Ipv4Addr::UNSPECIFIED, port)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addresses_take_the_default_port_when_they_have_none() {
+        let addresses = parse_addresses("0.0.0.0:8000, ::, [::1], 9000, 127.0.0.1, 9000", 8080);
+        let expected: Vec<SocketAddr> = [
+            "0.0.0.0:8000",
+            "[::]:8080",
+            "[::1]:8080",
+            "0.0.0.0:9000",
+            "127.0.0.1:8080",
+        ]
+        .iter()
+        .map(|address| address.parse().unwrap())
+        .collect();
+        assert_eq!(addresses.unwrap(), expected);
+
+        let error = parse_addresses("localhost:8000", 8000).unwrap_err();
+        assert!(format!("{error:#}").contains("\"localhost:8000\" is not an address"));
+        assert!(parse_addresses(" , ", 8000).is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn reuse_port_lets_two_listeners_share_an_address() {
+        let first = bind("127.0.0.1:0".parse().unwrap(), true).unwrap();
+        let address = first.local_addr().unwrap();
+        let second = bind(address, true).unwrap();
+        assert_eq!(second.local_addr().unwrap(), address);
+
+        let exclusive = bind("127.0.0.1:0".parse().unwrap(), false).unwrap();
+        assert!(bind(exclusive.local_addr().unwrap(), false).is_err());
+    }
+}
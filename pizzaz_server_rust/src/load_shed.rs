@@ -0,0 +1,307 @@
+//! Overload protection for the MCP endpoint.
+//!
+//! New `/mcp` POSTs are rejected with a retriable JSON-RPC error while too many requests are
+//! in flight or the runtime is lagging. GET streams are never shed, so established SSE
+//! connections keep flowing.
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Weak,
+    },
+    time::{Duration, Instant},
+};
+
+use async_stream::stream;
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use futures::StreamExt;
+use http_body_util::{BodyExt, Limited};
+use serde_json::Value;
+
+use crate::{supervisor::Supervisor, AppState};
+
+/// JSON-RPC error code returned when a request is shed; clients should retry after a pause.
+pub const OVERLOADED_ERROR_CODE: i32 = -32030;
+
+const DEFAULT_MAX_IN_FLIGHT: usize = 256;
+const DEFAULT_MAX_LOOP_LAG: Duration = Duration::from_millis(250);
+const LAG_PROBE_INTERVAL: Duration = Duration::from_millis(100);
+const RETRY_AFTER_SECONDS: u64 = 1;
+
+/// Most of a shed request's body read to recover its id; larger bodies get `id: null`.
+const MAX_ID_PROBE_BYTES: usize = 4 * 1024;
+
+/// Thresholds above which new requests are rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadShedConfig {
+    pub max_in_flight: usize,
+    pub max_loop_lag: Duration,
+}
+
+impl Default for LoadShedConfig {
+    fn default() -> Self {
+        Self {
+            max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+            max_loop_lag: DEFAULT_MAX_LOOP_LAG,
+        }
+    }
+}
+
+impl LoadShedConfig {
+    /// Reads `MCP_MAX_IN_FLIGHT` and `MCP_MAX_LOOP_LAG_MS`, keeping defaults for invalid values.
+    pub(crate) fn from_env() -> Self {
+        let default = Self::default();
+        let read = |variable: &str| {
+            std::env::var(variable)
+                .ok()
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .filter(|value| *value > 0)
+        };
+
+        Self {
+            max_in_flight: read("MCP_MAX_IN_FLIGHT")
+                .map(|value| value as usize)
+                .unwrap_or(default.max_in_flight),
+            max_loop_lag: read("MCP_MAX_LOOP_LAG_MS")
+                .map(Duration::from_millis)
+                .unwrap_or(default.max_loop_lag),
+        }
+    }
+}
+
+/// Why a request was shed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverloadReason {
+    InFlight(usize),
+    LoopLag(Duration),
+}
+
+/// Tracks in-flight MCP requests and the most recent runtime scheduling lag.
+#[derive(Debug)]
+pub struct LoadMonitor {
+    config: LoadShedConfig,
+    in_flight: AtomicUsize,
+    loop_lag_ms: AtomicU64,
+}
+
+impl LoadMonitor {
+    pub fn new(config: LoadShedConfig) -> Self {
+        Self {
+            config,
+            in_flight: AtomicUsize::new(0),
+            loop_lag_ms: AtomicU64::new(0),
+        }
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    pub fn loop_lag(&self) -> Duration {
+        Duration::from_millis(self.loop_lag_ms.load(Ordering::Relaxed))
+    }
+
+    /// Returns the reason a new request should be rejected, if any.
+    pub fn overload(&self) -> Option<OverloadReason> {
+        let in_flight = self.in_flight();
+        if in_flight >= self.config.max_in_flight {
+            return Some(OverloadReason::InFlight(in_flight));
+        }
+        let lag = self.loop_lag();
+        if lag > self.config.max_loop_lag {
+            return Some(OverloadReason::LoopLag(lag));
+        }
+        None
+    }
+
+    /// Counts a request as in flight until the returned guard is dropped.
+    pub fn track(self: &Arc<Self>) -> InFlightGuard {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlightGuard {
+            monitor: Arc::clone(self),
+        }
+    }
+
+    fn record_lag(&self, lag: Duration) {
+        self.loop_lag_ms
+            .store(lag.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Samples scheduling lag on the current runtime until the monitor is dropped.
+    pub(crate) fn spawn_lag_probe(self: &Arc<Self>, supervisor: &Supervisor) {
+        let monitor: Weak<Self> = Arc::downgrade(self);
+        supervisor.spawn("lag_probe", move || {
+            let monitor = monitor.clone();
+            async move {
+                loop {
+                    let started = Instant::now();
+                    tokio::time::sleep(LAG_PROBE_INTERVAL).await;
+                    let Some(monitor) = monitor.upgrade() else {
+                        break;
+                    };
+                    monitor.record_lag(started.elapsed().saturating_sub(LAG_PROBE_INTERVAL));
+                }
+            }
+        });
+    }
+}
+
+/// Decrements the in-flight count when dropped.
+#[derive(Debug)]
+pub struct InFlightGuard {
+    monitor: Arc<LoadMonitor>,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.monitor.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Middleware that sheds new `/mcp` POSTs under overload and counts the ones it admits.
+pub(crate) async fn shed_load(
+    Extension(state): Extension<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if request.method() != Method::POST {
+        return next.run(request).await;
+    }
+
+    let monitor = &state.load;
+    if let Some(reason) = monitor.overload() {
+        tracing::warn!(
+            ?reason,
+            in_flight = monitor.in_flight(),
+            "Shedding MCP request under load"
+        );
+        return overloaded_response(request).await;
+    }
+
+    let guard = monitor.track();
+    let response = next.run(request).await;
+
+    // Streamed responses stay in flight until their body has been fully sent.
+    let (parts, body) = response.into_parts();
+    let mut data = body.into_data_stream();
+    let tracked = stream! {
+        let _guard = guard;
+        while let Some(chunk) = data.next().await {
+            yield chunk;
+        }
+    };
+    Response::from_parts(parts, Body::from_stream(tracked))
+}
+
+async fn overloaded_response(request: Request) -> Response {
+    // Echo the request id when it is cheap to recover so clients can correlate the error.
+    let id = match Limited::new(request.into_body(), MAX_ID_PROBE_BYTES)
+        .collect()
+        .await
+    {
+        Ok(collected) => serde_json::from_slice::<Value>(&collected.to_bytes())
+            .ok()
+            .and_then(|message| message.get("id").cloned())
+            .unwrap_or(Value::Null),
+        Err(_) => Value::Null,
+    };
+
+    let mut response = (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": {
+                "code": OVERLOADED_ERROR_CODE,
+                "message": "Server is overloaded; retry shortly",
+                "data": {
+                    "retryable": true,
+                    "retryAfterMs": RETRY_AFTER_SECONDS * 1000,
+                },
+            },
+        })),
+    )
+        .into_response();
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(RETRY_AFTER_SECONDS));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overload_tracks_in_flight_guards() {
+        let monitor = Arc::new(LoadMonitor::new(LoadShedConfig {
+            max_in_flight: 2,
+            max_loop_lag: Duration::from_secs(1),
+        }));
+
+        let first = monitor.track();
+        assert_eq!(monitor.overload(), None);
+        let second = monitor.track();
+        assert_eq!(monitor.overload(), Some(OverloadReason::InFlight(2)));
+
+        drop(first);
+        drop(second);
+        assert_eq!(monitor.in_flight(), 0);
+        assert_eq!(monitor.overload(), None);
+    }
+
+    #[test]
+    fn overload_reports_loop_lag() {
+        let monitor = LoadMonitor::new(LoadShedConfig {
+            max_in_flight: 10,
+            max_loop_lag: Duration::from_millis(100),
+        });
+        monitor.record_lag(Duration::from_millis(50));
+        assert_eq!(monitor.overload(), None);
+        monitor.record_lag(Duration::from_millis(400));
+        assert_eq!(
+            monitor.overload(),
+            Some(OverloadReason::LoopLag(Duration::from_millis(400)))
+        );
+    }
+
+    #[tokio::test]
+    async fn overloaded_response_is_retriable_json_rpc_error() {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/mcp")
+            .body(Body::from(
+                r#"{"jsonrpc":"2.0","id":7,"method":"tools/list"}"#,
+            ))
+            .unwrap();
+        let response = overloaded_response(request).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let json: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["id"], 7);
+        assert_eq!(json["error"]["code"], OVERLOADED_ERROR_CODE);
+        assert_eq!(json["error"]["data"]["retryable"], true);
+
+        let padding = " ".repeat(MAX_ID_PROBE_BYTES);
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/mcp")
+            .body(Body::from(format!(
+                r#"{{"jsonrpc":"2.0","id":8,"method":"tools/list"{padding}}}"#
+            )))
+            .unwrap();
+        let response = overloaded_response(request).await;
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let json: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["id"], Value::Null);
+    }
+}
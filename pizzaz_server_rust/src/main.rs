@@ -1,61 +1,236 @@
 //! Pizzaz MCP Server - Binary entry point
 
-use std::net::SocketAddr;
 use tokio::signal;
 use tracing::{info, warn};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use tracing_subscriber::{
+    fmt::writer::BoxMakeWriter, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter,
+};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     // Load environment variables from .env if present for local development.
     let _ = dotenvy::dotenv();
 
+    // `repl` runs tools in-process and `--check-manifest` only loads the manifest, instead of
+    // serving; keep their logs to warnings on stderr.
+    let mode = std::env::args().nth(1);
+    let repl = mode.as_deref() == Some("repl");
+    let check = mode.as_deref() == Some("--check-manifest");
+    let (default_filter, writer) = if repl || check {
+        (
+            "pizzaz_server_rust=warn",
+            BoxMakeWriter::new(std::io::stderr),
+        )
+    } else {
+        (
+            "pizzaz_server_rust=info,tower_http=debug,rmcp=info",
+            BoxMakeWriter::new(std::io::stdout),
+        )
+    };
+
     // Initialize tracing subscriber
     tracing_subscriber::registry()
-        .with(
-            EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "pizzaz_server_rust=info,tower_http=debug,rmcp=info".into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
+        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| default_filter.into()))
+        .with(tracing_subscriber::fmt::layer().with_writer(writer))
         .init();
 
-    // Parse port from environment or use default
-    let port: u16 = std::env::var("PORT")
-        .ok()
-        .and_then(|p| p.parse().ok())
-        .unwrap_or(8000);
+    // Map names used by the Python and Node examples before anything reads the environment.
+    pizzaz_server_rust::env_compat::apply();
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    if check {
+        return check_manifest().await;
+    }
+
+    if repl {
+        pizzaz_server_rust::widgets::bootstrap_registry();
+        let repl = pizzaz_server_rust::repl::Repl::new(pizzaz_server_rust::create_handler());
+        repl.run(
+            tokio::io::BufReader::new(tokio::io::stdin()),
+            tokio::io::stdout(),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let listeners = pizzaz_server_rust::listeners::ListenerConfig::from_env()?;
+    let tuning = pizzaz_server_rust::connections::ConnectionTuning::from_env()?;
 
     info!("<U Pizzaz MCP Server starting...");
-    info!("   Listening on: http://{}", addr);
-    info!("   HTTP endpoint: POST http://{}/mcp", addr);
-    info!(
-        "   SSE stream: GET http://{}/mcp (with Session-Id header)",
-        addr
-    );
+    for addr in &listeners.mcp {
+        info!("   Listening on: http://{}", addr);
+        info!("   HTTP endpoint: POST http://{}/mcp", addr);
+        info!(
+            "   SSE stream: GET http://{}/mcp (with Session-Id header)",
+            addr
+        );
+    }
+    for addr in listeners.admin.iter().flatten() {
+        info!("   Operator endpoints: http://{}/internal", addr);
+    }
+    if listeners.reuse_port {
+        info!("   Sharing listener addresses with SO_REUSEPORT");
+    }
     info!("   Press Ctrl+C to stop");
 
-    // Create TCP listener
-    let listener = tokio::net::TcpListener::bind(addr).await?;
+    // Optionally load the manifest before accepting connections so clients never see an empty registry.
+    // A process sharing the port through SO_REUSEPORT gets connections as soon as it binds.
+    let preload = pizzaz_server_rust::widgets::PreloadConfig::from_env().or_else(|| {
+        listeners
+            .reuse_port
+            .then(pizzaz_server_rust::widgets::PreloadConfig::configured)
+    });
+    if let Some(preload) = preload {
+        info!(
+            timeout_seconds = preload.timeout.as_secs(),
+            "Preloading widget manifest before binding"
+        );
+        let outcome = pizzaz_server_rust::widgets::preload_registry(preload)
+            .await
+            .map_err(|error| anyhow::anyhow!("widget manifest preload failed: {error}"))?;
+        info!(widgets = outcome.widget_count, "Widget manifest preloaded");
+    }
+
+    // Bind every listener before serving any, so a bad address fails startup as a whole.
+    let mut bound = Vec::new();
+    let (apps, stats) = match &listeners.admin {
+        None => {
+            let (app, stats) = pizzaz_server_rust::create_app_with_stats();
+            (vec![(listeners.mcp.clone(), app)], stats)
+        }
+        Some(admin) => {
+            let split = pizzaz_server_rust::create_split_app();
+            let apps = vec![
+                (listeners.mcp.clone(), split.mcp),
+                (admin.clone(), split.admin),
+            ];
+            (apps, split.stats)
+        }
+    };
+    for (addrs, app) in apps {
+        for addr in addrs {
+            let listener = listeners
+                .bind(addr)
+                .map_err(|error| anyhow::anyhow!("failed to listen on {addr}: {error}"))?;
+            bound.push((listener, app.clone()));
+        }
+    }
+
+    #[cfg(unix)]
+    tokio::spawn(reload_manifest_on_hangup());
 
-    // Create app
-    let app = pizzaz_server_rust::create_app();
+    // Start every listener with a shared graceful shutdown
+    let (shutdown, _) = tokio::sync::watch::channel(false);
+    let mut servers = tokio::task::JoinSet::new();
+    for (listener, app) in bound {
+        servers.spawn(pizzaz_server_rust::connections::serve(
+            listener,
+            app,
+            tuning.clone(),
+            shutdown.subscribe(),
+        ));
+    }
 
-    // Start server with graceful shutdown
-    axum::serve(
-        listener,
-        app.into_make_service_with_connect_info::<SocketAddr>(),
+    let reason = tokio::select! {
+        reason = shutdown_signal() => reason,
+        Some(result) = servers.join_next() => {
+            result??;
+            anyhow::bail!("a listener stopped unexpectedly");
+        }
+    };
+
+    // Fail readiness, give open connections and deferred calls the grace period, then abort what is
+    // left and report.
+    let shutdown_config = pizzaz_server_rust::shutdown::ShutdownConfig::from_env();
+    stats.start_draining();
+    let begin = stats.snapshot().await;
+    shutdown.send_replace(true);
+    let drained = tokio::time::timeout(shutdown_config.grace, async {
+        while let Some(result) = servers.join_next().await {
+            result??;
+        }
+        stats.wait_for_jobs().await;
+        anyhow::Ok(())
+    })
+    .await;
+    let end = stats.snapshot().await;
+    let grace_period_exceeded = match drained {
+        Ok(result) => {
+            result?;
+            false
+        }
+        Err(_) => {
+            warn!(
+                grace_seconds = shutdown_config.grace.as_secs(),
+                "Grace period ended; aborting open connections"
+            );
+            servers.shutdown().await;
+            stats.abort_jobs();
+            true
+        }
+    };
+    pizzaz_server_rust::shutdown::ShutdownReport::new(
+        reason,
+        &stats,
+        begin,
+        end,
+        shutdown_config.grace,
+        grace_period_exceeded,
     )
-    .with_graceful_shutdown(shutdown_signal())
-    .await?;
+    .publish(&shutdown_config)
+    .await;
 
-    info!("Server shut down gracefully");
+    info!("Server shut down");
     Ok(())
 }
 
-/// Handles Ctrl+C signal for graceful shutdown
-async fn shutdown_signal() {
+/// `--check-manifest [<path>] [--strict]`: loads the manifest without binding any port, prints a
+/// report and exits non-zero when it would fail to load.
+async fn check_manifest() -> anyhow::Result<()> {
+    let mut path = None;
+    let mut strict = false;
+    for arg in std::env::args().skip(2) {
+        match arg.as_str() {
+            "--strict" => strict = true,
+            _ if path.is_none() => path = Some(std::path::PathBuf::from(arg)),
+            other => anyhow::bail!("unexpected argument: {other}"),
+        }
+    }
+    let path = path.unwrap_or_else(pizzaz_server_rust::widgets::resolve_manifest_path);
+    let check = pizzaz_server_rust::manifest_check::ManifestCheck::run(&path).await;
+    print!("{}", check.report());
+    if !check.passed(strict) {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Re-reads `.env` and `WIDGETS_MANIFEST_PATH` on SIGHUP and switches to the resolved manifest.
+#[cfg(unix)]
+async fn reload_manifest_on_hangup() {
+    let mut hangup = match signal::unix::signal(signal::unix::SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(error) => {
+            warn!("Unable to install SIGHUP handler: {error}");
+            return;
+        }
+    };
+
+    while hangup.recv().await.is_some() {
+        let _ = dotenvy::dotenv_override();
+        let path = pizzaz_server_rust::widgets::resolve_manifest_path();
+        info!(
+            "Received SIGHUP, loading widget manifest from {}",
+            path.display()
+        );
+        match pizzaz_server_rust::widgets::switch_manifest_path(path).await {
+            Ok(outcome) => info!(widgets = outcome.widget_count, "Widget manifest reloaded"),
+            Err(error) => warn!("Keeping previous widget manifest: {error}"),
+        }
+    }
+}
+
+/// Waits for Ctrl+C or SIGTERM and returns which one arrived.
+async fn shutdown_signal() -> &'static str {
     let ctrl_c = async {
         signal::ctrl_c()
             .await
@@ -76,9 +251,11 @@ async fn shutdown_signal() {
     tokio::select! {
         _ = ctrl_c => {
             warn!("Received Ctrl+C, shutting down...");
+            "SIGINT"
         },
         _ = terminate => {
             warn!("Received SIGTERM, shutting down...");
+            "SIGTERM"
         },
     }
 }
@@ -0,0 +1,158 @@
+//! `pizzaz_server_rust --check-manifest [<path>] [--strict]`: a pre-deploy gate.
+//!
+//! The manifest is loaded exactly as the server would load it, with the schema, `${VAR}`
+//! references, asset checksums, content scan, template lint, duplicates, dependencies and
+//! [`crate::widgets::RegistryLimits`], but nothing is served and no port is bound. A report goes
+//! to stdout, and the exit status is non-zero when the load fails, or with `--strict` when any
+//! widget loaded with warnings.
+
+use std::{
+    fmt::Write as _,
+    path::{Path, PathBuf},
+};
+
+use crate::widgets::{self, LoadError, WidgetsRegistry};
+
+/// Outcome of checking one manifest.
+#[derive(Debug)]
+pub struct ManifestCheck {
+    pub path: PathBuf,
+    pub result: Result<WidgetsRegistry, LoadError>,
+}
+
+impl ManifestCheck {
+    /// Loads the manifest at `path` (which may be an object-storage URL when that feature is
+    /// enabled) without installing it.
+    pub async fn run(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            result: widgets::load_registry(path).await,
+        }
+    }
+
+    /// True when the manifest itself loaded and, with `strict`, no widget has warnings. A cached
+    /// copy standing in for an unreachable bucket manifest does not pass.
+    pub fn passed(&self, strict: bool) -> bool {
+        match &self.result {
+            Ok(registry) if registry.metadata().degraded.is_some() => false,
+            Ok(registry) => {
+                !strict
+                    || registry
+                        .widgets()
+                        .iter()
+                        .all(|widget| widget.warnings.is_empty())
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// A plain-text report: one line per widget on success, or every reason the load failed.
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        let registry = match &self.result {
+            Ok(registry) => registry,
+            Err(error) => {
+                let _ = writeln!(report, "{}: invalid", self.path.display());
+                match error.schema_violations() {
+                    Some(violations) => {
+                        for violation in violations {
+                            let _ = writeln!(report, "  {violation}");
+                        }
+                    }
+                    None => {
+                        let _ = writeln!(report, "  {error}");
+                    }
+                }
+                return report;
+            }
+        };
+
+        let widgets = registry.widgets();
+        let _ = writeln!(
+            report,
+            "{}: ok (schema {}, {} widgets, {} bytes of HTML)",
+            self.path.display(),
+            registry
+                .metadata()
+                .schema_version
+                .as_deref()
+                .unwrap_or("unknown"),
+            widgets.len(),
+            registry.html_bytes(),
+        );
+        if let Some(reason) = &registry.metadata().degraded {
+            let _ = writeln!(report, "  degraded: {reason}");
+        }
+        for widget in &widgets {
+            if widget.warnings.is_empty() {
+                let _ = writeln!(report, "  {:<28} ok", widget.id);
+            }
+            for warning in &widget.warnings {
+                let _ = writeln!(report, "  {:<28} warn: {warning}", widget.id);
+            }
+        }
+        for id in registry.disabled_widgets() {
+            let _ = writeln!(report, "  {id:<28} disabled");
+        }
+        for (id, reason) in registry.unsupported_widgets() {
+            let _ = writeln!(report, "  {id:<28} skipped: {reason}");
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn reports_each_widget_or_every_schema_violation() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("pizzaz-aaaa.html"), "<div></div>").unwrap();
+        let path = dir.path().join("widgets.json");
+        let mut manifest = serde_json::json!({
+            "schemaVersion": "1.0.0",
+            "widgets": [{
+                "id": "pizza-map",
+                "title": "Pizza Map",
+                "templateUri": "ui://widget/pizza-map.html",
+                "invoking": "Invoking",
+                "invoked": "Invoked",
+                "htmlPath": "pizzaz-aaaa.html",
+                "responseText": "Rendered!"
+            }]
+        });
+        std::fs::write(&path, manifest.to_string()).unwrap();
+
+        let check = ManifestCheck::run(&path).await;
+        assert!(check.passed(false));
+        // The bare `<div>` has no root element or script, so the template lint warns.
+        assert!(!check.passed(true));
+        let report = check.report();
+        assert!(
+            report.contains(": ok (schema 1.0.0, 1 widgets, 11 bytes of HTML)"),
+            "{report}"
+        );
+        assert!(
+            report.contains("pizza-map                    warn: "),
+            "{report}"
+        );
+
+        manifest["widgets"][0]
+            .as_object_mut()
+            .unwrap()
+            .remove("templateUri");
+        manifest["widgets"][0]["title"] = serde_json::json!(7);
+        std::fs::write(&path, manifest.to_string()).unwrap();
+        let check = ManifestCheck::run(&path).await;
+        assert!(!check.passed(false));
+        assert_eq!(
+            check.report(),
+            format!(
+                "{}: invalid\n  widgets[0].templateUri: missing\n  widgets[0].title: expected \
+                 string, found number\n",
+                path.display()
+            )
+        );
+    }
+}
@@ -0,0 +1,167 @@
+//! `${VAR}` interpolation in manifest strings.
+//!
+//! Any string in a manifest or overlay may reference an environment variable as `${CDN_HOST}`,
+//! so one manifest artifact can be promoted across environments that serve assets from different
+//! hosts. References are resolved when the manifest is parsed, before it is checked against the
+//! schema. Every reference to an unset variable is reported together, with its field path, and
+//! fails the load; a variable set to an empty string is substituted as empty.
+//!
+//! Only upper-case names (`[A-Z_][A-Z0-9_]*`) are variables, so JavaScript template literals such
+//! as `${count}` in inline HTML pass through untouched. `$${VAR}` stands for a literal `${VAR}`.
+//! Object keys are never interpolated.
+
+use std::{borrow::Cow, fmt, sync::LazyLock};
+
+use regex::{Captures, Regex};
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+
+static REFERENCE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\$(\$)?\{([A-Z_][A-Z0-9_]*)\}").expect("reference pattern is valid")
+});
+
+/// A reference to an environment variable that is not set.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UnsetVariable {
+    /// Field path such as `widgets[0].html`.
+    pub path: String,
+    pub variable: String,
+}
+
+impl fmt::Display for UnsetVariable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ${{{}}}", self.path, self.variable)
+    }
+}
+
+/// Every reference to an unset variable in a manifest.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("manifest references unset environment variables: {}", join(.0))]
+pub struct UnsetVariablesError(pub Vec<UnsetVariable>);
+
+fn join(unset: &[UnsetVariable]) -> String {
+    unset
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Replaces the `${VAR}` references in every string of `value` from the process environment.
+pub fn interpolate(value: &mut JsonValue) -> Result<(), UnsetVariablesError> {
+    interpolate_with(value, |name| std::env::var(name).ok())
+}
+
+/// Like [`interpolate`], reading variables through `lookup`.
+pub fn interpolate_with(
+    value: &mut JsonValue,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<(), UnsetVariablesError> {
+    let mut unset = Vec::new();
+    walk(value, String::new(), &lookup, &mut unset);
+    if unset.is_empty() {
+        Ok(())
+    } else {
+        Err(UnsetVariablesError(unset))
+    }
+}
+
+fn walk(
+    value: &mut JsonValue,
+    path: String,
+    lookup: &impl Fn(&str) -> Option<String>,
+    unset: &mut Vec<UnsetVariable>,
+) {
+    match value {
+        JsonValue::String(text) => {
+            if let Cow::Owned(replaced) = substitute(text, &path, lookup, unset) {
+                *text = replaced;
+            }
+        }
+        JsonValue::Array(items) => {
+            for (index, item) in items.iter_mut().enumerate() {
+                walk(item, format!("{path}[{index}]"), lookup, unset);
+            }
+        }
+        JsonValue::Object(object) => {
+            for (name, field) in object.iter_mut() {
+                let field_path = if path.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{path}.{name}")
+                };
+                walk(field, field_path, lookup, unset);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn substitute<'a>(
+    text: &'a str,
+    path: &str,
+    lookup: &impl Fn(&str) -> Option<String>,
+    unset: &mut Vec<UnsetVariable>,
+) -> Cow<'a, str> {
+    REFERENCE.replace_all(text, |captures: &Captures| {
+        let name = &captures[2];
+        if captures.get(1).is_some() {
+            return format!("${{{name}}}");
+        }
+        lookup(name).unwrap_or_else(|| {
+            unset.push(UnsetVariable {
+                path: path.to_string(),
+                variable: name.to_string(),
+            });
+            String::new()
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn references_are_resolved_and_unset_ones_reported_by_path() {
+        let lookup = |name: &str| match name {
+            "CDN_HOST" => Some("cdn.example.com".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        };
+        let mut manifest = serde_json::json!({
+            "widgets": [{
+                "html": "https://${CDN_HOST}/pizza.html",
+                "responseText": "Served${EMPTY} from ${CDN_HOST}; $${CDN_HOST} is literal",
+                "assets": { "js": "<script>`${count}`</script>" },
+                "${CDN_HOST}": 1
+            }]
+        });
+        interpolate_with(&mut manifest, lookup).unwrap();
+        assert_eq!(
+            manifest,
+            serde_json::json!({
+                "widgets": [{
+                    "html": "https://cdn.example.com/pizza.html",
+                    "responseText": "Served from cdn.example.com; ${CDN_HOST} is literal",
+                    "assets": { "js": "<script>`${count}`</script>" },
+                    "${CDN_HOST}": 1
+                }]
+            })
+        );
+
+        let mut manifest = serde_json::json!({
+            "widgets": [
+                { "html": "https://${CDN_HOST}/a.html" },
+                { "csp": { "connect_domains": ["https://${API_HOST}", "https://${CDN_HOST}"] } }
+            ],
+            "generatedAt": "${BUILD_TIME}"
+        });
+        let error = interpolate_with(&mut manifest, lookup).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "manifest references unset environment variables: \
+             generatedAt: ${BUILD_TIME}; widgets[1].csp.connect_domains[0]: ${API_HOST}"
+        );
+    }
+}
@@ -0,0 +1,119 @@
+//! Manifest file formats other than JSON.
+//!
+//! Manifests and overlays are picked up by extension: `.yaml`/`.yml` files are read as YAML with
+//! `serde_yaml`, `.toml` files as TOML with `toml`, and anything else as JSON. Whatever the
+//! format, the document is turned into the same JSON value and then checked against the manifest
+//! schema, so field names, types and error paths are identical across formats. TOML date-times
+//! become strings, so `generatedAt = 2024-10-15T10:30:00Z` works unquoted.
+
+use std::{fmt, path::Path};
+
+use anyhow::{anyhow, Result};
+use serde_json::{Map, Value as JsonValue};
+
+/// How a manifest file is written, chosen by its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl ManifestFormat {
+    pub fn for_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("yaml" | "yml") => Self::Yaml,
+            Some("toml") => Self::Toml,
+            _ => Self::Json,
+        }
+    }
+
+    /// Parses `data` into the JSON value it describes.
+    pub fn parse(self, data: &[u8]) -> Result<JsonValue> {
+        match self {
+            Self::Json => Ok(serde_json::from_slice(data)?),
+            Self::Yaml => Ok(serde_yaml::from_slice(data)?),
+            Self::Toml => {
+                let text =
+                    std::str::from_utf8(data).map_err(|_| anyhow!("file is not valid UTF-8"))?;
+                Ok(toml_to_json(toml::from_str(text)?))
+            }
+        }
+    }
+}
+
+impl fmt::Display for ManifestFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Json => "JSON",
+            Self::Yaml => "YAML",
+            Self::Toml => "TOML",
+        })
+    }
+}
+
+/// Converts a TOML document to JSON; date-times, which JSON lacks, become RFC 3339 strings.
+fn toml_to_json(value: toml::Value) -> JsonValue {
+    match value {
+        toml::Value::String(text) => JsonValue::String(text),
+        toml::Value::Integer(number) => JsonValue::from(number),
+        toml::Value::Float(number) => JsonValue::from(number),
+        toml::Value::Boolean(flag) => JsonValue::Bool(flag),
+        toml::Value::Datetime(datetime) => JsonValue::String(datetime.to_string()),
+        toml::Value::Array(items) => items.into_iter().map(toml_to_json).collect(),
+        toml::Value::Table(table) => JsonValue::Object(
+            table
+                .into_iter()
+                .map(|(key, value)| (key, toml_to_json(value)))
+                .collect::<Map<_, _>>(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> JsonValue {
+        serde_json::from_str(include_str!("../tests/fixtures/widgets.json")).unwrap()
+    }
+
+    #[test]
+    fn format_follows_the_extension() {
+        assert_eq!(
+            ManifestFormat::for_path(Path::new("widgets.yaml")),
+            ManifestFormat::Yaml
+        );
+        assert_eq!(
+            ManifestFormat::for_path(Path::new("w.YML")),
+            ManifestFormat::Yaml
+        );
+        assert_eq!(
+            ManifestFormat::for_path(Path::new("widgets.toml")),
+            ManifestFormat::Toml
+        );
+        assert_eq!(
+            ManifestFormat::for_path(Path::new("widgets.json")),
+            ManifestFormat::Json
+        );
+        assert_eq!(
+            ManifestFormat::for_path(Path::new("widgets")),
+            ManifestFormat::Json
+        );
+    }
+
+    #[test]
+    fn fixture_translations_match_the_json_manifest() {
+        let yaml = ManifestFormat::Yaml
+            .parse(include_bytes!("../tests/fixtures/widgets.yaml"))
+            .unwrap();
+        assert_eq!(yaml, fixture());
+        let toml = ManifestFormat::Toml
+            .parse(include_bytes!("../tests/fixtures/widgets.toml"))
+            .unwrap();
+        assert_eq!(toml, fixture());
+    }
+}
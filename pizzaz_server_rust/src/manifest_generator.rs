@@ -0,0 +1,413 @@
+//! Builds `widgets.json` from a directory of built widget files.
+//!
+//! Every `.html` file in the directory becomes a widget. A trailing content hash in the file name
+//! (`pizzaz-list-2d2b.html`) is dropped to get the widget id, and `.css` and `.js` files with the
+//! same stem are attached as its assets, along with their SHA-256 checksums. Entries of an
+//! existing manifest are matched by id or by the bundle their HTML asset came from, and keep their
+//! hand-written fields; only `html`, the asset references and checksums, and `generatedAt` are
+//! regenerated. [`write_manifest`] loads the result the way the server would before putting it in
+//! place, so a bad manifest never reaches disk.
+//!
+//! The `pizzaz_manifest` binary wraps this for build scripts.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+    time::SystemTime,
+};
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{
+    widgets::{self, is_remote_path, WidgetsRegistry},
+    widgets_manifest::{
+        WidgetManifest, WidgetManifestAssetChecksums, WidgetManifestAssets, WidgetManifestEntry,
+    },
+};
+
+/// Schema version written when there is no existing manifest to take it from.
+pub const DEFAULT_SCHEMA_VERSION: &str = "1.0.0";
+
+/// Base URL for widget HTML when neither `--base-url` nor `WIDGETS_ASSET_BASE_URL` is set.
+pub const DEFAULT_ASSET_BASE_URL: &str = "http://localhost:4444/";
+
+static HASH_SUFFIX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(.+)-[0-9a-f]{4,}$").expect("hash suffix pattern compiles"));
+
+/// Options for generating a manifest.
+#[derive(Debug, Clone)]
+pub struct ManifestGenerator {
+    build_dir: PathBuf,
+    base_url: String,
+    existing: Option<WidgetManifest>,
+}
+
+/// A generated manifest and what the generator noticed on the way.
+#[derive(Debug)]
+pub struct GeneratedManifest {
+    pub manifest: WidgetManifest,
+    pub warnings: Vec<String>,
+}
+
+/// Built files sharing one stem, e.g. `pizzaz-2d2b.{html,css,js}`.
+struct Bundle {
+    name: String,
+    html: PathBuf,
+    css: Option<PathBuf>,
+    js: Option<PathBuf>,
+    modified: SystemTime,
+}
+
+impl ManifestGenerator {
+    pub fn new(build_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            build_dir: build_dir.into(),
+            base_url: DEFAULT_ASSET_BASE_URL.to_string(),
+            existing: None,
+        }
+    }
+
+    /// URL prefix for each widget's `html` field.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        let mut base_url = base_url.into();
+        if !base_url.ends_with('/') {
+            base_url.push('/');
+        }
+        self.base_url = base_url;
+        self
+    }
+
+    /// Keeps titles, messages, roles and other hand-written fields from `existing`.
+    pub fn with_existing(mut self, existing: WidgetManifest) -> Self {
+        self.existing = Some(existing);
+        self
+    }
+
+    /// Generates the manifest, with asset references relative to `out_dir`.
+    pub fn generate(&self, out_dir: &Path) -> Result<GeneratedManifest> {
+        let mut warnings = Vec::new();
+        let bundles = self.scan(&mut warnings)?;
+        if bundles.is_empty() {
+            bail!("No widget HTML files found in {}", self.build_dir.display());
+        }
+
+        let mut existing: Vec<WidgetManifestEntry> = self
+            .existing
+            .as_ref()
+            .map(|manifest| manifest.widgets.clone())
+            .unwrap_or_default();
+        let mut widgets = Vec::with_capacity(bundles.len());
+        for bundle in bundles.values() {
+            let matched = existing
+                .iter()
+                .position(|entry| {
+                    entry.id == bundle.name || entry_bundle(entry).as_deref() == Some(&bundle.name)
+                })
+                .map(|index| existing.remove(index));
+            let assets = WidgetManifestAssets {
+                html: Some(asset_reference(&bundle.html, out_dir)),
+                css: bundle
+                    .css
+                    .as_deref()
+                    .map(|css| asset_reference(css, out_dir)),
+                js: bundle.js.as_deref().map(|js| asset_reference(js, out_dir)),
+                sha256: Some(WidgetManifestAssetChecksums {
+                    html: Some(file_sha256(&bundle.html)?),
+                    css: bundle.css.as_deref().map(file_sha256).transpose()?,
+                    js: bundle.js.as_deref().map(file_sha256).transpose()?,
+                }),
+            };
+            let html = format!("{}{}", self.base_url, file_name(&bundle.html));
+            let entry = match matched {
+                // The generated `assets.html` replaces a hand-written `htmlPath`.
+                Some(entry) => WidgetManifestEntry {
+                    html,
+                    html_path: None,
+                    assets: Some(assets),
+                    ..entry
+                },
+                None => new_entry(&bundle.name, html, assets),
+            };
+            widgets.push(entry);
+        }
+
+        // Entries served entirely from remote URLs have nothing in the build output to match.
+        for entry in existing {
+            if references_local_files(&entry) {
+                warnings.push(format!(
+                    "Dropped widget {}: its files are not in {}",
+                    entry.id,
+                    self.build_dir.display()
+                ));
+            } else {
+                widgets.push(entry);
+            }
+        }
+        widgets.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let schema_version = match &self.existing {
+            Some(manifest) => manifest.schema_version.clone(),
+            None => DEFAULT_SCHEMA_VERSION.to_string(),
+        };
+        Ok(GeneratedManifest {
+            manifest: WidgetManifest {
+                schema_version,
+                generated_at: OffsetDateTime::now_utc().format(&Rfc3339).ok(),
+                widgets,
+            },
+            warnings,
+        })
+    }
+
+    /// Groups the build directory's files into bundles keyed by widget name.
+    fn scan(&self, warnings: &mut Vec<String>) -> Result<BTreeMap<String, Bundle>> {
+        let entries = fs::read_dir(&self.build_dir)
+            .with_context(|| format!("Failed to read {}", self.build_dir.display()))?;
+        let mut files: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file())
+            .collect();
+        files.sort();
+
+        let mut bundles: BTreeMap<String, Bundle> = BTreeMap::new();
+        for html in files
+            .iter()
+            .filter(|path| path.extension().is_some_and(|ext| ext == "html"))
+        {
+            let sibling = |extension: &str| {
+                let candidate = html.with_extension(extension);
+                files.contains(&candidate).then_some(candidate)
+            };
+            let bundle = Bundle {
+                name: bundle_name(html),
+                html: html.clone(),
+                css: sibling("css"),
+                js: sibling("js"),
+                modified: fs::metadata(html)
+                    .and_then(|metadata| metadata.modified())
+                    .unwrap_or(SystemTime::UNIX_EPOCH),
+            };
+            if let Some(other) = bundles.get(&bundle.name) {
+                let (kept, ignored) = if bundle.modified > other.modified {
+                    (&bundle, other)
+                } else {
+                    (other, &bundle)
+                };
+                warnings.push(format!(
+                    "Widget {} has several builds; using {} and ignoring {}",
+                    bundle.name,
+                    file_name(&kept.html),
+                    file_name(&ignored.html)
+                ));
+                if bundle.modified <= other.modified {
+                    continue;
+                }
+            }
+            bundles.insert(bundle.name.clone(), bundle);
+        }
+        Ok(bundles)
+    }
+}
+
+/// Writes `manifest` to `path` once it loads cleanly, returning the registry it produces.
+///
+/// The manifest is written next to `path` first, so asset references resolve exactly as they will
+/// from `path`, and only renamed into place after the server's own validation passes.
+pub fn write_manifest(manifest: &WidgetManifest, path: &Path) -> Result<WidgetsRegistry> {
+    let mut json =
+        serde_json::to_string_pretty(manifest).context("Failed to serialize the manifest")?;
+    json.push('\n');
+    let staging = path.with_extension("json.tmp");
+    fs::write(&staging, json).with_context(|| format!("Failed to write {}", staging.display()))?;
+
+    let registry = match widgets::load_registry_from_path(&staging) {
+        Ok(registry) => registry,
+        Err(error) => {
+            let _ = fs::remove_file(&staging);
+            return Err(anyhow::Error::new(error).context("Generated manifest does not load"));
+        }
+    };
+    fs::rename(&staging, path).with_context(|| {
+        format!(
+            "Failed to move the manifest into place at {}",
+            path.display()
+        )
+    })?;
+    Ok(registry)
+}
+
+fn new_entry(name: &str, html: String, assets: WidgetManifestAssets) -> WidgetManifestEntry {
+    let label = name
+        .split(['-', '_'])
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    WidgetManifestEntry {
+        id: name.to_string(),
+        title: format!("Show {label}"),
+        template_uri: format!("ui://widget/{name}.html"),
+        invoking: format!("Loading {label}"),
+        invoked: format!("Loaded {label}"),
+        html,
+        html_path: None,
+        response_text: format!("Rendered {label}!"),
+        assets: Some(assets),
+        allowed_roles: Vec::new(),
+        rate_limit: None,
+        input_schema: None,
+        tags: Vec::new(),
+        order: None,
+        enabled: true,
+        locales: BTreeMap::new(),
+        requires: None,
+        csp: None,
+        widget_domain: None,
+        meta: None,
+        deprecated: false,
+        deprecation_message: None,
+        depends_on: Vec::new(),
+    }
+}
+
+/// Widget name for a built file: its stem without a trailing content hash.
+fn bundle_name(path: &Path) -> String {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    match HASH_SUFFIX.captures(&stem) {
+        Some(capture) => capture[1].to_string(),
+        None => stem,
+    }
+}
+
+/// Bundle an existing entry's local HTML asset was built from.
+fn entry_bundle(entry: &WidgetManifestEntry) -> Option<String> {
+    let html = entry.assets.as_ref()?.html.as_deref()?;
+    (!is_remote_path(html)).then(|| bundle_name(Path::new(html)))
+}
+
+fn references_local_files(entry: &WidgetManifestEntry) -> bool {
+    entry.html_path.is_some()
+        || entry.assets.as_ref().is_some_and(|assets| {
+            [&assets.html, &assets.css, &assets.js]
+                .into_iter()
+                .flatten()
+                .any(|reference| !is_remote_path(reference))
+        })
+}
+
+/// `file` relative to `out_dir` when it lies inside it, otherwise its absolute path.
+fn asset_reference(file: &Path, out_dir: &Path) -> String {
+    let file = fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf());
+    let out_dir = fs::canonicalize(out_dir).unwrap_or_else(|_| out_dir.to_path_buf());
+    match file.strip_prefix(&out_dir) {
+        Ok(relative) => relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"),
+        Err(_) => file.display().to_string(),
+    }
+}
+
+fn file_sha256(path: &Path) -> Result<String> {
+    let contents = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(widgets::sha256_hex(&contents))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generates_entries_from_built_files_and_keeps_existing_fields() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("pizzaz-2d2b.html"),
+            "<div id=\"map\"></div>",
+        )
+        .unwrap();
+        fs::write(dir.path().join("pizzaz-2d2b.css"), "div {}").unwrap();
+        fs::write(dir.path().join("pizzaz-list-2d2b.html"), "<ul></ul>").unwrap();
+        fs::write(dir.path().join("pizzaz-list-2d2b.js"), "render()").unwrap();
+
+        let existing: WidgetManifest = serde_json::from_value(serde_json::json!({
+            "schemaVersion": "1.2.0",
+            "widgets": [
+                {
+                    "id": "pizza-map",
+                    "title": "Show Pizza Map",
+                    "templateUri": "ui://widget/pizza-map.html",
+                    "invoking": "Hand-tossing a map",
+                    "invoked": "Served a fresh map",
+                    "html": "http://localhost:4444/pizzaz-0000.html",
+                    "responseText": "Rendered a pizza map!",
+                    "assets": { "html": "pizzaz-0000.html" }
+                },
+                {
+                    "id": "pizza-video",
+                    "title": "Show Pizza Video",
+                    "templateUri": "ui://widget/pizza-video.html",
+                    "invoking": "Hand-tossing a video",
+                    "invoked": "Served a fresh video",
+                    "html": "https://cdn.example/pizzaz-video.html",
+                    "responseText": "Rendered a pizza video!",
+                    "assets": { "html": "https://cdn.example/pizzaz-video.html" }
+                }
+            ]
+        }))
+        .unwrap();
+
+        let generated = ManifestGenerator::new(dir.path())
+            .with_base_url("https://widgets.example")
+            .with_existing(existing)
+            .generate(dir.path())
+            .unwrap();
+        let widgets = &generated.manifest.widgets;
+        let ids: Vec<_> = widgets.iter().map(|widget| widget.id.as_str()).collect();
+        assert_eq!(ids, vec!["pizza-map", "pizza-video", "pizzaz-list"]);
+
+        let map = &widgets[0];
+        assert_eq!(map.invoking, "Hand-tossing a map");
+        assert_eq!(map.html, "https://widgets.example/pizzaz-2d2b.html");
+        let assets = map.assets.as_ref().unwrap();
+        assert_eq!(assets.html.as_deref(), Some("pizzaz-2d2b.html"));
+        assert_eq!(assets.css.as_deref(), Some("pizzaz-2d2b.css"));
+        assert_eq!(assets.js, None);
+        let checksums = assets.sha256.as_ref().unwrap();
+        assert_eq!(
+            checksums.css.as_deref(),
+            Some(widgets::sha256_hex(b"div {}").as_str())
+        );
+        assert_eq!(checksums.js, None);
+
+        let list = &widgets[2];
+        assert_eq!(list.title, "Show Pizzaz List");
+        assert_eq!(list.template_uri, "ui://widget/pizzaz-list.html");
+        assert_eq!(generated.manifest.schema_version, "1.2.0");
+
+        let out = dir.path().join("widgets.json");
+        let registry = write_manifest(&generated.manifest, &out).unwrap();
+        assert_eq!(registry.widgets().len(), 3);
+        assert!(out.exists());
+        assert!(!dir.path().join("widgets.json.tmp").exists());
+    }
+}
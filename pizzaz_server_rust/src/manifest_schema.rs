@@ -0,0 +1,256 @@
+//! Validation of manifests against the embedded JSON Schema.
+//!
+//! `schemas/widgets-manifest.schema.json` describes the manifest format and is compiled into the
+//! server. Every manifest is checked against it before it is deserialized, so a mistake is
+//! reported with the path to the offending field (`widgets[3].templateUri: missing`) rather than
+//! as a serde error with a line and column. Editors that understand JSON Schema can use the same
+//! file through a `"$schema"` reference in the manifest.
+//!
+//! The validator implements the keywords the schema uses: `type`, `required`, `properties`,
+//! `additionalProperties`, `items`, `minLength` and local `$ref`s into `$defs`.
+
+use std::{fmt, sync::LazyLock};
+
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+
+/// The manifest JSON Schema, as shipped in `schemas/`.
+pub const MANIFEST_SCHEMA: &str = include_str!("../schemas/widgets-manifest.schema.json");
+
+static SCHEMA: LazyLock<JsonValue> = LazyLock::new(|| {
+    serde_json::from_str(MANIFEST_SCHEMA).expect("embedded manifest schema is valid JSON")
+});
+
+/// One place a manifest does not match the schema.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SchemaViolation {
+    /// Field path such as `widgets[3].templateUri`; empty for the manifest itself.
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() {
+            "manifest"
+        } else {
+            &self.path
+        };
+        write!(f, "{path}: {}", self.message)
+    }
+}
+
+/// Every violation found in a manifest.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("manifest does not match the schema: {}", join(.0))]
+pub struct ManifestSchemaError(pub Vec<SchemaViolation>);
+
+fn join(violations: &[SchemaViolation]) -> String {
+    violations
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Checks `manifest` against the embedded schema.
+pub fn validate(manifest: &JsonValue) -> Result<(), ManifestSchemaError> {
+    validate_with(&SCHEMA, manifest)
+}
+
+/// Checks `value` against `schema`, resolving `$ref`s within `schema`.
+pub fn validate_with(schema: &JsonValue, value: &JsonValue) -> Result<(), ManifestSchemaError> {
+    let mut validator = Validator {
+        root: schema,
+        violations: Vec::new(),
+    };
+    validator.check(schema, value, String::new());
+    if validator.violations.is_empty() {
+        Ok(())
+    } else {
+        Err(ManifestSchemaError(validator.violations))
+    }
+}
+
+struct Validator<'a> {
+    root: &'a JsonValue,
+    violations: Vec<SchemaViolation>,
+}
+
+impl<'a> Validator<'a> {
+    fn violation(&mut self, path: &str, message: impl Into<String>) {
+        self.violations.push(SchemaViolation {
+            path: path.to_string(),
+            message: message.into(),
+        });
+    }
+
+    fn resolve(&self, reference: &str) -> Option<&'a JsonValue> {
+        self.root.pointer(reference.strip_prefix('#')?)
+    }
+
+    fn check(&mut self, schema: &'a JsonValue, value: &JsonValue, path: String) {
+        if let Some(reference) = schema.get("$ref").and_then(JsonValue::as_str) {
+            match self.resolve(reference) {
+                Some(target) => self.check(target, value, path),
+                None => self.violation(&path, format!("schema reference {reference} not found")),
+            }
+            return;
+        }
+
+        if let Some(expected) = schema.get("type") {
+            let kinds: Vec<&str> = match expected {
+                JsonValue::Array(kinds) => kinds.iter().filter_map(JsonValue::as_str).collect(),
+                kind => kind.as_str().into_iter().collect(),
+            };
+            if !kinds.iter().any(|kind| matches_type(kind, value)) {
+                self.violation(
+                    &path,
+                    format!(
+                        "expected {}, found {}",
+                        kinds.join(" or "),
+                        type_name(value)
+                    ),
+                );
+                return;
+            }
+        }
+
+        match value {
+            JsonValue::Object(object) => {
+                for name in schema
+                    .get("required")
+                    .and_then(JsonValue::as_array)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(JsonValue::as_str)
+                {
+                    if !object.contains_key(name) {
+                        self.violation(&join_path(&path, name), "missing");
+                    }
+                }
+                let properties = schema.get("properties").and_then(JsonValue::as_object);
+                for (name, entry) in object {
+                    let property = properties.and_then(|properties| properties.get(name));
+                    match (property, schema.get("additionalProperties")) {
+                        (Some(property), _) => self.check(property, entry, join_path(&path, name)),
+                        (None, Some(JsonValue::Bool(false))) => {
+                            self.violation(&join_path(&path, name), "unknown field")
+                        }
+                        (None, Some(additional)) if additional.is_object() => {
+                            self.check(additional, entry, join_path(&path, name))
+                        }
+                        (None, _) => {}
+                    }
+                }
+            }
+            JsonValue::Array(items) => {
+                if let Some(item_schema) = schema.get("items") {
+                    for (index, item) in items.iter().enumerate() {
+                        self.check(item_schema, item, format!("{path}[{index}]"));
+                    }
+                }
+            }
+            JsonValue::String(text) => {
+                let min_length = schema.get("minLength").and_then(JsonValue::as_u64);
+                match min_length {
+                    Some(1) if text.is_empty() => self.violation(&path, "must not be empty"),
+                    Some(min) if (text.chars().count() as u64) < min => {
+                        self.violation(&path, format!("must be at least {min} characters"))
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn join_path(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{path}.{name}")
+    }
+}
+
+fn matches_type(expected: &str, value: &JsonValue) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &JsonValue) -> &'static str {
+    match value {
+        JsonValue::Object(_) => "object",
+        JsonValue::Array(_) => "array",
+        JsonValue::String(_) => "string",
+        JsonValue::Bool(_) => "boolean",
+        JsonValue::Null => "null",
+        JsonValue::Number(_) => "number",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn fixture_manifest_matches_the_schema() {
+        let manifest: JsonValue =
+            serde_json::from_str(include_str!("../tests/fixtures/widgets.json")).unwrap();
+        assert_eq!(validate(&manifest), Ok(()));
+    }
+
+    #[test]
+    fn violations_name_the_field_path() {
+        let widget = json!({
+            "id": "pizza-map", "title": "Map", "templateUri": "ui://widget/pizza-map.html",
+            "invoking": "Loading", "invoked": "Loaded", "responseText": "Done"
+        });
+        let mut broken = widget.clone();
+        broken.as_object_mut().unwrap().remove("templateUri");
+        broken["tags"] = json!(["maps", 3]);
+        broken["assets"] = json!({ "html": 1 });
+        let manifest = json!({
+            "schemaVersion": "",
+            "widgets": [widget, broken]
+        });
+
+        let error = validate(&manifest).unwrap_err();
+        let messages: Vec<String> = error.0.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "schemaVersion: must not be empty",
+                "widgets[1].templateUri: missing",
+                "widgets[1].assets.html: expected string or null, found number",
+                "widgets[1].tags[1]: expected string, found number",
+            ]
+        );
+        assert!(error
+            .to_string()
+            .starts_with("manifest does not match the schema: schemaVersion: must not be empty; "));
+
+        let error = validate(&json!([])).unwrap_err();
+        assert_eq!(
+            error.0[0].to_string(),
+            "manifest: expected object, found array"
+        );
+    }
+
+    #[test]
+    fn additional_properties_can_be_closed() {
+        let schema = json!({ "type": "object", "additionalProperties": false });
+        let error = validate_with(&schema, &json!({ "extra": true })).unwrap_err();
+        assert_eq!(error.0[0].to_string(), "extra: unknown field");
+    }
+}
@@ -0,0 +1,395 @@
+//! A minimal MCP client for the streamable HTTP transport, behind the `pizzaz-client` binary.
+//!
+//! [`McpClient::connect`] runs the `initialize` handshake against a running server and keeps the
+//! `Mcp-Session-Id` it hands out; every later request reuses that session. Responses may arrive as
+//! plain JSON or as an SSE stream, and SSE frames are read only until the matching response shows
+//! up, so keep-alive pings never hold a call open:
+//!
+//! ```no_run
+//! # async fn poke() -> Result<(), pizzaz_server_rust::mcp_client::McpClientError> {
+//! use pizzaz_server_rust::mcp_client::McpClient;
+//!
+//! let mut client = McpClient::new("http://127.0.0.1:8000/mcp").connect().await?;
+//! let result = client
+//!     .call_tool("pizza-map", serde_json::json!({ "pizzaTopping": "pepperoni" }))
+//!     .await?;
+//! println!("{}", result["structuredContent"]);
+//! client.close().await;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Both `http://` and `https://` servers can be reached.
+
+use futures::TryStreamExt;
+use serde_json::{json, Value as JsonValue};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio_util::io::StreamReader;
+
+use crate::http::{http_client, transport_error};
+
+const SESSION_HEADER: &str = "mcp-session-id";
+
+/// Largest JSON body or single SSE line read back from the server.
+const MAX_MESSAGE_BYTES: u64 = 16 * 1024 * 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum McpClientError {
+    #[error("{0} is not an http:// or https:// URL")]
+    InvalidUrl(String),
+    #[error("Request to {url} failed: {source}")]
+    Io {
+        url: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("{method} returned HTTP {status}: {body}")]
+    Http {
+        method: String,
+        status: u16,
+        body: String,
+    },
+    #[error("{method} failed with JSON-RPC error {code}: {message}")]
+    Rpc {
+        method: String,
+        code: i64,
+        message: String,
+        data: Option<JsonValue>,
+    },
+    #[error("{method} returned a malformed response: {reason}")]
+    Malformed { method: String, reason: String },
+}
+
+/// A session with one MCP server.
+#[derive(Debug)]
+pub struct McpClient {
+    url: String,
+    http: reqwest::Client,
+    token: Option<String>,
+    session: Option<String>,
+    next_id: u64,
+    server: JsonValue,
+}
+
+/// What came back from one HTTP exchange.
+struct Reply {
+    status: u16,
+    session: Option<String>,
+    message: Option<JsonValue>,
+    body: String,
+}
+
+impl McpClient {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            http: http_client("pizzaz-client"),
+            token: None,
+            session: None,
+            next_id: 1,
+            server: JsonValue::Null,
+        }
+    }
+
+    /// Sends `Authorization: Bearer <token>`, for servers with `MCP_AUTH_TOKENS` set.
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Runs the `initialize` handshake and opens a session.
+    pub async fn connect(mut self) -> Result<Self, McpClientError> {
+        let params = json!({
+            // Reads `structuredContent`, so the server has no reason to repeat it as text.
+            "protocolVersion": rmcp::model::ProtocolVersion::V_2025_06_18,
+            "capabilities": {},
+            "clientInfo": {
+                "name": "pizzaz-client",
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+        });
+        let (session, result) = self.exchange("initialize", Some(params)).await?;
+        self.session = session;
+        self.server = result;
+        self.notify("notifications/initialized").await?;
+        Ok(self)
+    }
+
+    /// The `initialize` result: server info, capabilities and instructions.
+    pub fn server(&self) -> &JsonValue {
+        &self.server
+    }
+
+    pub fn session_id(&self) -> Option<&str> {
+        self.session.as_deref()
+    }
+
+    pub async fn list_tools(&mut self) -> Result<JsonValue, McpClientError> {
+        self.request("tools/list", json!({})).await
+    }
+
+    pub async fn list_resources(&mut self) -> Result<JsonValue, McpClientError> {
+        self.request("resources/list", json!({})).await
+    }
+
+    pub async fn list_resource_templates(&mut self) -> Result<JsonValue, McpClientError> {
+        self.request("resources/templates/list", json!({})).await
+    }
+
+    pub async fn read_resource(&mut self, uri: &str) -> Result<JsonValue, McpClientError> {
+        self.request("resources/read", json!({ "uri": uri })).await
+    }
+
+    /// Calls `name` and returns the whole `CallToolResult`, `_meta` and `structuredContent`
+    /// included. A result with `isError` set is still `Ok`.
+    pub async fn call_tool(
+        &mut self,
+        name: &str,
+        arguments: JsonValue,
+    ) -> Result<JsonValue, McpClientError> {
+        self.request(
+            "tools/call",
+            json!({ "name": name, "arguments": arguments }),
+        )
+        .await
+    }
+
+    /// Sends any request on the session and returns its `result`.
+    pub async fn request(
+        &mut self,
+        method: &str,
+        params: JsonValue,
+    ) -> Result<JsonValue, McpClientError> {
+        self.exchange(method, Some(params))
+            .await
+            .map(|(_, result)| result)
+    }
+
+    /// Ends the session on the server. Failures are ignored; the server expires it anyway.
+    pub async fn close(self) {
+        if self.session.is_some() {
+            let _ = self.send("DELETE", None).await;
+        }
+    }
+
+    async fn notify(&mut self, method: &str) -> Result<(), McpClientError> {
+        let body = json!({ "jsonrpc": "2.0", "method": method });
+        let reply = self.send("POST", Some((&body, None))).await?;
+        if !(200..300).contains(&reply.status) {
+            return Err(McpClientError::Http {
+                method: method.to_string(),
+                status: reply.status,
+                body: reply.body,
+            });
+        }
+        Ok(())
+    }
+
+    async fn exchange(
+        &mut self,
+        method: &str,
+        params: Option<JsonValue>,
+    ) -> Result<(Option<String>, JsonValue), McpClientError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let mut body = json!({ "jsonrpc": "2.0", "id": id, "method": method });
+        if let Some(params) = params {
+            body["params"] = params;
+        }
+
+        let reply = self.send("POST", Some((&body, Some(id)))).await?;
+        let malformed = |reason: &str| McpClientError::Malformed {
+            method: method.to_string(),
+            reason: reason.to_string(),
+        };
+        if reply.status != 200 {
+            return Err(McpClientError::Http {
+                method: method.to_string(),
+                status: reply.status,
+                body: reply.body,
+            });
+        }
+        let mut message = reply
+            .message
+            .ok_or_else(|| malformed("no response for the request id"))?;
+        if let Some(error) = message.get("error") {
+            return Err(McpClientError::Rpc {
+                method: method.to_string(),
+                code: error["code"].as_i64().unwrap_or_default(),
+                message: error["message"].as_str().unwrap_or_default().to_string(),
+                data: error.get("data").cloned(),
+            });
+        }
+        match message.get_mut("result") {
+            Some(result) => Ok((reply.session, result.take())),
+            None => Err(malformed("response has neither result nor error")),
+        }
+    }
+
+    /// One HTTP exchange. `body` carries the JSON-RPC message and, for requests, the id whose
+    /// response ends the read.
+    async fn send(
+        &self,
+        method: &str,
+        body: Option<(&JsonValue, Option<u64>)>,
+    ) -> Result<Reply, McpClientError> {
+        let io = |source| McpClientError::Io {
+            url: self.url.clone(),
+            source,
+        };
+        let url = reqwest::Url::parse(&self.url)
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+            .ok_or_else(|| McpClientError::InvalidUrl(self.url.clone()))?;
+        let method = reqwest::Method::from_bytes(method.as_bytes())
+            .map_err(|_| io(std::io::Error::other("invalid HTTP method")))?;
+
+        let mut request = self
+            .http
+            .request(method, url)
+            .header("Accept", "application/json, text/event-stream");
+        if let Some((message, _)) = body {
+            request = request
+                .header("Content-Type", "application/json")
+                .body(serde_json::to_vec(message).expect("JSON-RPC message serializes"));
+        }
+        if let Some(session) = &self.session {
+            request = request.header("Mcp-Session-Id", session);
+        }
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|error| io(transport_error(error)))?;
+
+        let status = response.status().as_u16();
+        let header = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let session = header(SESSION_HEADER);
+        let event_stream =
+            header("content-type").is_some_and(|value| value.starts_with("text/event-stream"));
+        let mut reader = BufReader::new(StreamReader::new(
+            response.bytes_stream().map_err(transport_error),
+        ))
+        .take(MAX_MESSAGE_BYTES);
+
+        let request_id = body.and_then(|(_, id)| id);
+        let (message, body) = match request_id {
+            Some(id) if status == 200 && event_stream => (
+                read_event_stream(&mut reader, id).await.map_err(io)?,
+                String::new(),
+            ),
+            _ => {
+                let mut body = String::new();
+                reader.read_to_string(&mut body).await.map_err(io)?;
+                let message = request_id
+                    .and_then(|_| serde_json::from_str::<JsonValue>(&body).ok())
+                    .filter(|message| message.is_object());
+                (message, body)
+            }
+        };
+        Ok(Reply {
+            status,
+            session,
+            message,
+            body,
+        })
+    }
+}
+
+/// Tool arguments typed on a command line: a single JSON object, or `key=value` pairs whose values
+/// are JSON when they parse as JSON and strings otherwise, so `count=3` is a number.
+pub fn parse_cli_arguments<S: AsRef<str>>(args: &[S]) -> Result<JsonValue, String> {
+    if let [single] = args {
+        let single = single.as_ref();
+        if single.trim_start().starts_with('{') {
+            return serde_json::from_str::<JsonValue>(single)
+                .ok()
+                .filter(JsonValue::is_object)
+                .ok_or_else(|| "tool arguments are not a JSON object".to_string());
+        }
+    }
+    let mut arguments = serde_json::Map::new();
+    for arg in args {
+        let arg = arg.as_ref();
+        let Some((key, value)) = arg.split_once('=') else {
+            return Err(format!("expected key=value, got {arg}"));
+        };
+        let value =
+            serde_json::from_str(value).unwrap_or_else(|_| JsonValue::String(value.to_string()));
+        arguments.insert(key.to_string(), value);
+    }
+    Ok(JsonValue::Object(arguments))
+}
+
+/// Reads SSE events until the response to `id` arrives. Server requests and notifications sent
+/// on the same stream are skipped.
+async fn read_event_stream(
+    reader: &mut (impl tokio::io::AsyncBufRead + Unpin),
+    id: u64,
+) -> std::io::Result<Option<JsonValue>> {
+    let mut data = String::new();
+    loop {
+        let mut line = String::new();
+        let read = reader.read_line(&mut line).await?;
+        let line = line.trim_end_matches(['\r', '\n']);
+        if read == 0 || line.is_empty() {
+            if let Ok(message) = serde_json::from_str::<JsonValue>(&data) {
+                if message["id"] == json!(id) && message.get("method").is_none() {
+                    return Ok(Some(message));
+                }
+            }
+            data.clear();
+            if read == 0 {
+                return Ok(None);
+            }
+        } else if let Some(chunk) = line.strip_prefix("data:") {
+            if !data.is_empty() {
+                data.push('\n');
+            }
+            data.push_str(chunk.strip_prefix(' ').unwrap_or(chunk));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn event_stream_reads_up_to_the_matching_response() {
+        let stream = b"data: \n\n\
+            event: message\ndata: {\"jsonrpc\":\"2.0\",\"method\":\"notifications/progress\"}\n\n\
+            data: {\"jsonrpc\":\"2.0\",\"id\":2,\"result\":{}}\n\n\
+            data: {\"jsonrpc\":\"2.0\",\"id\":3,\n\
+            data: \"result\":{\"ok\":true}}\n\n\
+            : keep-alive\n\n";
+        let mut reader = BufReader::new(&stream[..]);
+        let message = read_event_stream(&mut reader, 3).await.unwrap().unwrap();
+        assert_eq!(message["result"]["ok"], json!(true));
+
+        let mut reader = BufReader::new(&stream[..]);
+        assert!(read_event_stream(&mut reader, 9).await.unwrap().is_none());
+    }
+
+    #[test]
+    fn cli_arguments_accept_json_or_key_value_pairs() {
+        assert_eq!(
+            parse_cli_arguments(&["pizzaTopping=basil", "count=3", "hot=true"]).unwrap(),
+            json!({ "pizzaTopping": "basil", "count": 3, "hot": true })
+        );
+        assert_eq!(
+            parse_cli_arguments(&[r#"{"pizzaTopping": "basil"}"#]).unwrap(),
+            json!({ "pizzaTopping": "basil" })
+        );
+        assert!(parse_cli_arguments(&["pizzaTopping"]).is_err());
+        assert!(parse_cli_arguments(&["{not json"]).is_err());
+    }
+}
@@ -0,0 +1,179 @@
+//! Operator-set maintenance notices attached to tool results.
+//!
+//! While a notice is set, every matching tool result carries it under `_meta["pizzaz/notice"]`,
+//! so widgets can show a banner such as "ordering temporarily unavailable" without shipping new
+//! HTML. A notice can also add a text content block for clients that render no widget. Operators
+//! manage it with `PUT`/`DELETE /internal/notice`; `WIDGETS_NOTICE` sets one at startup.
+
+use std::{
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+};
+
+use axum::{
+    body::Bytes,
+    extract::{rejection::BytesRejection, ConnectInfo},
+    http::{HeaderMap, Method},
+    Extension, Json,
+};
+use rmcp::model::{CallToolResult, Content, Meta};
+use serde::{Deserialize, Serialize};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{error::ApiError, AppState};
+
+/// Tool result `_meta` key carrying the active notice.
+pub const NOTICE_META_KEY: &str = "pizzaz/notice";
+
+const NOTICE_DOCS: &str = "maintenance-notices";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NoticeLevel {
+    #[default]
+    Info,
+    Warning,
+    Critical,
+}
+
+/// A maintenance message shown alongside tool results.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Notice {
+    pub message: String,
+    pub level: NoticeLevel,
+    /// Also add the message as a text content block.
+    pub content: bool,
+    /// Widgets the notice applies to; empty means every tool.
+    pub widgets: Vec<String>,
+    /// RFC 3339 time the notice was set.
+    pub since: String,
+}
+
+impl Notice {
+    fn applies_to(&self, tool: &str) -> bool {
+        self.widgets.is_empty() || self.widgets.iter().any(|id| id == tool)
+    }
+
+    /// Adds the notice to `result`'s `_meta`, and as a content block when requested.
+    pub fn apply(&self, result: &mut CallToolResult) {
+        result.meta.get_or_insert_with(Meta::default).0.insert(
+            NOTICE_META_KEY.to_string(),
+            serde_json::json!({
+                "message": self.message,
+                "level": self.level,
+                "since": self.since,
+            }),
+        );
+        if self.content {
+            result
+                .content
+                .push(Content::text(format!("Notice: {}", self.message)));
+        }
+    }
+}
+
+/// The notice currently set, if any. Clones share it.
+#[derive(Debug, Clone, Default)]
+pub struct Notices {
+    current: Arc<RwLock<Option<Notice>>>,
+}
+
+impl Notices {
+    /// A notice for every tool from `WIDGETS_NOTICE`, if set.
+    pub(crate) fn from_env() -> Self {
+        let notices = Self::default();
+        if let Some(message) = std::env::var("WIDGETS_NOTICE")
+            .ok()
+            .filter(|message| !message.trim().is_empty())
+        {
+            notices.set(NoticeRequest {
+                message,
+                ..NoticeRequest::default()
+            });
+        }
+        notices
+    }
+
+    pub fn current(&self) -> Option<Notice> {
+        self.current.read().expect("notice lock poisoned").clone()
+    }
+
+    /// The notice for results of `tool`, if one is set and applies to it.
+    pub fn for_tool(&self, tool: &str) -> Option<Notice> {
+        self.current().filter(|notice| notice.applies_to(tool))
+    }
+
+    /// Replaces the current notice.
+    pub fn set(&self, request: NoticeRequest) -> Notice {
+        let notice = Notice {
+            message: request.message,
+            level: request.level,
+            content: request.content,
+            widgets: request.widgets,
+            since: OffsetDateTime::now_utc()
+                .format(&Rfc3339)
+                .unwrap_or_default(),
+        };
+        *self.current.write().expect("notice lock poisoned") = Some(notice.clone());
+        notice
+    }
+
+    /// Removes the current notice, returning it.
+    pub fn clear(&self) -> Option<Notice> {
+        self.current.write().expect("notice lock poisoned").take()
+    }
+}
+
+/// Body of `PUT /internal/notice`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NoticeRequest {
+    pub message: String,
+    #[serde(default)]
+    pub level: NoticeLevel,
+    #[serde(default)]
+    pub content: bool,
+    #[serde(default)]
+    pub widgets: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct NoticeResponse {
+    notice: Option<Notice>,
+}
+
+/// `PUT /internal/notice` sets the notice, `DELETE` clears it and `GET` reports it. All need the
+/// operator token.
+pub(crate) async fn notice_handler(
+    Extension(state): Extension<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    method: Method,
+    headers: HeaderMap,
+    body: Result<Bytes, BytesRejection>,
+) -> Result<Json<NoticeResponse>, ApiError> {
+    crate::require_operator(&state, addr, &headers)?;
+
+    let notice = if method == Method::PUT {
+        let request: NoticeRequest = serde_json::from_slice(&body?).map_err(|error| {
+            ApiError::bad_request("invalid_body", format!("Invalid request body: {error}"))
+                .with_docs(NOTICE_DOCS)
+        })?;
+        if request.message.trim().is_empty() {
+            return Err(
+                ApiError::bad_request("invalid_body", "Notice message must not be empty")
+                    .with_docs(NOTICE_DOCS),
+            );
+        }
+        let notice = state.notices.set(request);
+        tracing::info!(ip = %addr.ip(), level = ?notice.level, message = %notice.message, "Maintenance notice set");
+        Some(notice)
+    } else if method == Method::DELETE {
+        if state.notices.clear().is_some() {
+            tracing::info!(ip = %addr.ip(), "Maintenance notice cleared");
+        }
+        None
+    } else {
+        state.notices.current()
+    };
+    Ok(Json(NoticeResponse { notice }))
+}
@@ -0,0 +1,940 @@
+//! Widget manifests kept in S3 or Google Cloud Storage (cargo feature `object-storage`).
+//!
+//! Point `WIDGETS_MANIFEST_PATH` at `s3://bucket/path/widgets.json` (or `gs://…`) and the registry
+//! bootstraps and refreshes from the bucket instead of disk. Local asset references in the manifest
+//! resolve against the manifest's key the same way they resolve against its directory on disk, and
+//! are fetched along with it, so nothing needs a writable filesystem.
+//!
+//! Requests use path-style addressing and are signed with AWS Signature Version 4, which GCS also
+//! accepts with HMAC keys. Credentials come from `WIDGETS_OBJECT_STORE_ACCESS_KEY_ID`,
+//! `WIDGETS_OBJECT_STORE_SECRET_ACCESS_KEY` and `WIDGETS_OBJECT_STORE_SESSION_TOKEN`, falling back to
+//! the standard `AWS_*` variables; without any, requests are sent unsigned. The providers' public
+//! endpoints are reached over HTTPS; plain HTTP is only used when `WIDGETS_OBJECT_STORE_ENDPOINT`
+//! names an `http://` endpoint, such as an in-cluster MinIO.
+//!
+//! Each object's `ETag` (or `Last-Modified`) is remembered with the registry it loaded, and a
+//! refresh first asks the bucket with conditional requests whether any object changed; when none
+//! did, the registry is kept without downloading or parsing anything.
+//!
+//! With `WIDGETS_MANIFEST_CACHE_PATH` set, every bucket manifest that loads is also written to that
+//! file with its assets, so a server that starts while the bucket is unreachable can serve the last
+//! good copy instead of nothing.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::Path,
+    sync::{Arc, LazyLock, RwLock},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context};
+use futures::future::BoxFuture;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use time::{format_description::well_known::Iso8601, OffsetDateTime};
+
+use crate::{
+    http::{http_client, transport_error},
+    widgets::{hex, is_remote_path, sha256_hex, LoadError},
+    widgets_manifest::{parse_manifest, WidgetManifest},
+};
+
+/// Largest object body read into memory.
+const MAX_OBJECT_BYTES: u64 = 32 * 1024 * 1024;
+
+/// Payload hash S3 accepts for a request without a body.
+const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provider {
+    S3,
+    Gcs,
+}
+
+/// A manifest location such as `s3://bucket/widgets/widgets.json`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectUrl {
+    pub provider: Provider,
+    pub bucket: String,
+    pub key: String,
+}
+
+impl ObjectUrl {
+    /// Returns `None` unless `path` is an `s3://` or `gs://` URL naming a bucket and key.
+    pub fn parse(path: &Path) -> Option<Self> {
+        let raw = path.to_str()?;
+        let (provider, rest) = if let Some(rest) = raw.strip_prefix("s3://") {
+            (Provider::S3, rest)
+        } else if let Some(rest) = raw.strip_prefix("gs://") {
+            (Provider::Gcs, rest)
+        } else {
+            return None;
+        };
+        let (bucket, key) = rest.split_once('/')?;
+        if bucket.is_empty() || key.is_empty() || key.ends_with('/') {
+            return None;
+        }
+        Some(Self {
+            provider,
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+        })
+    }
+
+    /// The key `reference` names when read relative to this object, as a path is to a directory.
+    /// References starting with `/` are keys from the bucket root.
+    fn resolve(&self, reference: &str) -> String {
+        let mut segments: Vec<&str> = match reference.strip_prefix('/') {
+            Some(_) => Vec::new(),
+            None => self.key.split('/').collect(),
+        };
+        segments.pop();
+        for segment in reference.split('/') {
+            match segment {
+                "" | "." => {}
+                ".." => {
+                    segments.pop();
+                }
+                segment => segments.push(segment),
+            }
+        }
+        segments.join("/")
+    }
+
+    fn display_key(&self, key: &str) -> String {
+        let scheme = match self.provider {
+            Provider::S3 => "s3",
+            Provider::Gcs => "gs",
+        };
+        format!("{scheme}://{}/{key}", self.bucket)
+    }
+}
+
+/// Access keys for signing requests.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credentials")
+            .field("access_key_id", &self.access_key_id)
+            .field("session_token", &self.session_token.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectStoreConfig {
+    /// Base URL requests go to, e.g. `https://s3.us-east-1.amazonaws.com`.
+    pub endpoint: String,
+    /// Signing region; GCS uses `auto`.
+    pub region: String,
+    /// Requests are unsigned without credentials, which suits public buckets.
+    pub credentials: Option<Credentials>,
+    /// Limit on each request, from connecting to reading the whole body.
+    pub timeout: Duration,
+}
+
+impl ObjectStoreConfig {
+    /// Settings for `provider` from the environment.
+    ///
+    /// `WIDGETS_OBJECT_STORE_ENDPOINT` overrides the provider's public HTTPS endpoint, for
+    /// S3-compatible stores. Only an endpoint set there can be `http://`.
+    pub fn from_env(provider: Provider) -> Self {
+        let var = |names: &[&str]| {
+            names
+                .iter()
+                .find_map(|name| std::env::var(name).ok())
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let region = match provider {
+            Provider::S3 => var(&[
+                "WIDGETS_OBJECT_STORE_REGION",
+                "AWS_REGION",
+                "AWS_DEFAULT_REGION",
+            ])
+            .unwrap_or_else(|| "us-east-1".to_string()),
+            Provider::Gcs => {
+                var(&["WIDGETS_OBJECT_STORE_REGION"]).unwrap_or_else(|| "auto".to_string())
+            }
+        };
+        let endpoint = var(&["WIDGETS_OBJECT_STORE_ENDPOINT"]).unwrap_or_else(|| match provider {
+            Provider::S3 => format!("https://s3.{region}.amazonaws.com"),
+            Provider::Gcs => "https://storage.googleapis.com".to_string(),
+        });
+        let credentials = var(&["WIDGETS_OBJECT_STORE_ACCESS_KEY_ID", "AWS_ACCESS_KEY_ID"])
+            .zip(var(&[
+                "WIDGETS_OBJECT_STORE_SECRET_ACCESS_KEY",
+                "AWS_SECRET_ACCESS_KEY",
+            ]))
+            .map(|(access_key_id, secret_access_key)| Credentials {
+                access_key_id,
+                secret_access_key,
+                session_token: var(&["WIDGETS_OBJECT_STORE_SESSION_TOKEN", "AWS_SESSION_TOKEN"]),
+            });
+        if credentials.is_some() && endpoint.starts_with("http://") {
+            tracing::warn!(
+                endpoint = %endpoint,
+                "WIDGETS_OBJECT_STORE_ENDPOINT is plain HTTP; signed requests are sent unencrypted"
+            );
+        }
+        Self {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            region,
+            credentials,
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Status, body and validator of a `GET`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ObjectResponse {
+    pub status: u16,
+    pub body: Vec<u8>,
+    /// The `ETag` header, or `Last-Modified` when there is no `ETag`.
+    pub validator: Option<String>,
+}
+
+/// Sends one HTTP GET and returns the response.
+pub trait ObjectStoreTransport: Send + Sync {
+    fn get<'a>(
+        &'a self,
+        url: &'a str,
+        headers: &'a [(&'static str, String)],
+        timeout: Duration,
+    ) -> BoxFuture<'a, std::io::Result<ObjectResponse>>;
+}
+
+static TRANSPORT: LazyLock<RwLock<Arc<dyn ObjectStoreTransport>>> =
+    LazyLock::new(|| RwLock::new(Arc::new(HttpClient::default())));
+
+/// Replaces the transport used to fetch manifests, e.g. with one that serves fixtures in tests.
+pub fn set_transport(transport: Arc<dyn ObjectStoreTransport>) {
+    *TRANSPORT
+        .write()
+        .expect("object store transport lock poisoned") = transport;
+}
+
+/// Fetches with a shared [`reqwest::Client`], over TLS for `https://` endpoints.
+struct HttpClient(reqwest::Client);
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        Self(http_client("pizzaz-manifest"))
+    }
+}
+
+impl ObjectStoreTransport for HttpClient {
+    fn get<'a>(
+        &'a self,
+        url: &'a str,
+        headers: &'a [(&'static str, String)],
+        timeout: Duration,
+    ) -> BoxFuture<'a, std::io::Result<ObjectResponse>> {
+        Box::pin(async move {
+            let mut request = self.0.get(url).timeout(timeout);
+            for (name, value) in headers {
+                request = request.header(*name, value);
+            }
+            let mut response = request.send().await.map_err(transport_error)?;
+            let header = |name: &str| {
+                response
+                    .headers()
+                    .get(name)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_string)
+            };
+            let validator = header("etag").or_else(|| header("last-modified"));
+            let status = response.status().as_u16();
+
+            let mut body = Vec::new();
+            while let Some(chunk) = response.chunk().await.map_err(transport_error)? {
+                if (body.len() + chunk.len()) as u64 > MAX_OBJECT_BYTES {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("object is larger than {MAX_OBJECT_BYTES} bytes"),
+                    ));
+                }
+                body.extend_from_slice(&chunk);
+            }
+            Ok(ObjectResponse {
+                status,
+                body,
+                validator,
+            })
+        })
+    }
+}
+
+/// A bucket client for one provider's settings.
+pub struct ObjectStore {
+    config: ObjectStoreConfig,
+    transport: Arc<dyn ObjectStoreTransport>,
+}
+
+/// The bucket could not be reached or answered with a server error, as opposed to the object
+/// being missing or refused.
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct StoreUnavailable(String);
+
+/// A manifest read from a bucket, with the contents of the local assets it references.
+#[derive(Debug)]
+pub(crate) struct FetchedManifest {
+    pub(crate) manifest: WidgetManifest,
+    /// The manifest object as stored, before `${VAR}` references were resolved.
+    pub(crate) data: Vec<u8>,
+    /// Validators of the manifest and every asset, by object URL; empty when any object had none.
+    pub(crate) validators: BTreeMap<String, String>,
+    /// Asset contents keyed by the reference as written in the manifest.
+    pub(crate) assets: HashMap<String, String>,
+}
+
+impl ObjectStore {
+    /// Fetches over HTTP or HTTPS, as the endpoint says.
+    pub fn new(config: ObjectStoreConfig) -> Self {
+        Self::with_transport(config, Arc::new(HttpClient::default()))
+    }
+
+    pub fn with_transport(
+        config: ObjectStoreConfig,
+        transport: Arc<dyn ObjectStoreTransport>,
+    ) -> Self {
+        Self { config, transport }
+    }
+
+    fn from_env(provider: Provider) -> Self {
+        let transport = TRANSPORT
+            .read()
+            .expect("object store transport lock poisoned")
+            .clone();
+        Self::with_transport(ObjectStoreConfig::from_env(provider), transport)
+    }
+
+    /// Body of `key` in `bucket`, or `None` when the object does not exist.
+    pub async fn get(&self, bucket: &str, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        Ok(self
+            .get_object(bucket, key, None)
+            .await?
+            .map(|response| response.body))
+    }
+
+    /// Like [`ObjectStore::get`], returning the validator too. With `validator`, the request is
+    /// conditional and a `304 Not Modified` answer comes back with an empty body.
+    async fn get_object(
+        &self,
+        bucket: &str,
+        key: &str,
+        validator: Option<&str>,
+    ) -> anyhow::Result<Option<ObjectResponse>> {
+        let path = format!("/{}/{}", uri_encode(bucket, false), uri_encode(key, true));
+        let url = format!("{}{path}", self.config.endpoint);
+        let mut headers = self.signed_headers(&path, OffsetDateTime::now_utc());
+        if let Some(validator) = validator {
+            headers.push(conditional_header(validator));
+        }
+        let response = self
+            .transport
+            .get(&url, &headers, self.config.timeout)
+            .await
+            .map_err(|error| StoreUnavailable(format!("GET {url} failed: {error}")))?;
+        match response.status {
+            200 => Ok(Some(response)),
+            304 if validator.is_some() => Ok(Some(response)),
+            404 => Ok(None),
+            status => {
+                let detail = String::from_utf8_lossy(&response.body);
+                let detail: String = detail.chars().take(200).collect();
+                let message = format!("GET {url} returned {status}: {}", detail.trim());
+                if status >= 500 {
+                    Err(StoreUnavailable(message).into())
+                } else {
+                    Err(anyhow!(message))
+                }
+            }
+        }
+    }
+
+    /// Headers for a signed `GET` of `path`; only the unsigned payload hash when there are no
+    /// credentials.
+    fn signed_headers(&self, path: &str, now: OffsetDateTime) -> Vec<(&'static str, String)> {
+        let mut headers = vec![("x-amz-content-sha256", EMPTY_SHA256.to_string())];
+        let Some(credentials) = &self.config.credentials else {
+            return headers;
+        };
+        // The authority as the client sends it in `Host`, without a default port.
+        let host = reqwest::Url::parse(&self.config.endpoint)
+            .ok()
+            .and_then(|url| {
+                let host = url.host_str()?.to_string();
+                Some(match url.port() {
+                    Some(port) => format!("{host}:{port}"),
+                    None => host,
+                })
+            })
+            .unwrap_or_default();
+        let date = amz_date(now);
+        headers.push(("x-amz-date", date.clone()));
+        if let Some(token) = &credentials.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let mut signed: Vec<(&str, &str)> = headers
+            .iter()
+            .map(|(name, value)| (*name, value.as_str()))
+            .collect();
+        signed.push(("host", &host));
+        let authorization = sign_v4(
+            credentials,
+            &self.config.region,
+            "s3",
+            &SignedRequest {
+                method: "GET",
+                path,
+                query: "",
+                headers: &signed,
+                payload_sha256: EMPTY_SHA256,
+                date: &date,
+            },
+        );
+        headers.push(("authorization", authorization));
+        headers
+    }
+
+    /// Reads the manifest at `url` and every local asset it references.
+    pub(crate) async fn fetch_manifest(
+        &self,
+        url: &ObjectUrl,
+    ) -> Result<FetchedManifest, LoadError> {
+        let path = Path::new(&url.display_key(&url.key)).to_path_buf();
+        let invalid = |error: anyhow::Error| LoadError::Validation {
+            path: path.clone(),
+            error,
+        };
+
+        let response = self
+            .get_object(&url.bucket, &url.key, None)
+            .await
+            .map_err(invalid)?;
+        let Some(ObjectResponse {
+            body: data,
+            validator,
+            ..
+        }) = response
+        else {
+            return Err(LoadError::NotFound { path });
+        };
+        let manifest = parse_manifest(&data, &path).map_err(invalid)?;
+
+        let references: BTreeSet<String> = manifest
+            .widgets
+            .iter()
+            .flat_map(|entry| {
+                let assets = entry.assets.as_ref();
+                [
+                    entry.html_path.as_ref(),
+                    assets.and_then(|assets| assets.html.as_ref()),
+                    assets.and_then(|assets| assets.css.as_ref()),
+                    assets.and_then(|assets| assets.js.as_ref()),
+                ]
+            })
+            .filter_map(|reference| reference.map(|reference| reference.trim()))
+            .filter(|reference| !reference.is_empty() && !is_remote_path(reference))
+            .map(str::to_string)
+            .collect();
+        let fetches = references.into_iter().map(|reference| async move {
+            let key = url.resolve(&reference);
+            let response = self
+                .get_object(&url.bucket, &key, None)
+                .await?
+                .ok_or_else(|| anyhow!("Asset object does not exist: {}", url.display_key(&key)))?;
+            let contents = String::from_utf8(response.body)
+                .with_context(|| format!("Asset {} is not UTF-8", url.display_key(&key)))?;
+            anyhow::Ok((
+                reference,
+                contents,
+                (url.display_key(&key), response.validator),
+            ))
+        });
+        let fetched = futures::future::try_join_all(fetches)
+            .await
+            .map_err(invalid)?;
+
+        let mut assets = HashMap::new();
+        let mut validators = vec![(path.display().to_string(), validator)];
+        for (reference, contents, validator) in fetched {
+            assets.insert(reference, contents);
+            validators.push(validator);
+        }
+        // Without a validator for every object, a change could go unnoticed.
+        let validators = validators
+            .into_iter()
+            .map(|(object, validator)| Some((object, validator?)))
+            .collect::<Option<_>>()
+            .unwrap_or_default();
+
+        Ok(FetchedManifest {
+            manifest,
+            data,
+            validators,
+            assets,
+        })
+    }
+
+    /// True when no object in `validators` (object URL to validator, from
+    /// [`FetchedManifest::validators`]) changed since, by conditional requests.
+    pub(crate) async fn unchanged(
+        &self,
+        validators: &BTreeMap<String, String>,
+    ) -> anyhow::Result<bool> {
+        let checks = validators.iter().map(|(object, validator)| async move {
+            let url = ObjectUrl::parse(Path::new(object))
+                .ok_or_else(|| anyhow!("{object} is not an object URL"))?;
+            let response = self
+                .get_object(&url.bucket, &url.key, Some(validator))
+                .await?;
+            anyhow::Ok(response.is_some_and(|response| response.status == 304))
+        });
+        Ok(!validators.is_empty()
+            && futures::future::try_join_all(checks)
+                .await?
+                .into_iter()
+                .all(|unchanged| unchanged))
+    }
+}
+
+/// `If-None-Match` for an `ETag`, which is always quoted, or `If-Modified-Since` for a date.
+fn conditional_header(validator: &str) -> (&'static str, String) {
+    if validator.starts_with('"') || validator.starts_with("W/") {
+        ("if-none-match", validator.to_string())
+    } else {
+        ("if-modified-since", validator.to_string())
+    }
+}
+
+/// True when `error` means the bucket could not be reached, so a cached copy may stand in.
+pub(crate) fn is_unavailable(error: &LoadError) -> bool {
+    match error {
+        LoadError::Validation { error, .. } => error.downcast_ref::<StoreUnavailable>().is_some(),
+        LoadError::NotFound { .. } => false,
+    }
+}
+
+/// The last good copy of a bucket manifest, as written to `WIDGETS_MANIFEST_CACHE_PATH`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CachedManifest {
+    url: String,
+    /// ISO 8601 time the copy was fetched.
+    fetched_at: String,
+    /// The manifest object as stored, so `${VAR}` references resolve again when it is read.
+    manifest: String,
+    assets: HashMap<String, String>,
+}
+
+/// Replaces the copy at `cache` with the manifest `data` and `assets` fetched from `url`.
+///
+/// The file is written next to `cache` and renamed over it, so a crash never leaves half a copy.
+pub(crate) fn store_cached(
+    cache: &Path,
+    url: &ObjectUrl,
+    data: &[u8],
+    assets: &HashMap<String, String>,
+) -> anyhow::Result<()> {
+    let cached = CachedManifest {
+        url: url.display_key(&url.key),
+        fetched_at: OffsetDateTime::now_utc().format(&Iso8601::DEFAULT)?,
+        manifest: String::from_utf8(data.to_vec()).context("Manifest is not UTF-8")?,
+        assets: assets.clone(),
+    };
+    let staging = cache.with_extension("tmp");
+    std::fs::write(&staging, serde_json::to_vec(&cached)?)
+        .with_context(|| format!("Failed to write {}", staging.display()))?;
+    std::fs::rename(&staging, cache)
+        .with_context(|| format!("Failed to replace {}", cache.display()))
+}
+
+/// The copy of `url` at `cache`, with the time it was fetched.
+pub(crate) fn load_cached(
+    cache: &Path,
+    url: &ObjectUrl,
+) -> anyhow::Result<(FetchedManifest, String)> {
+    let contents =
+        std::fs::read(cache).with_context(|| format!("Failed to read {}", cache.display()))?;
+    let cached: CachedManifest = serde_json::from_slice(&contents)
+        .with_context(|| format!("{} is not a cached manifest", cache.display()))?;
+    let path = url.display_key(&url.key);
+    if cached.url != path {
+        return Err(anyhow!(
+            "{} holds {}, not {path}",
+            cache.display(),
+            cached.url
+        ));
+    }
+    let data = cached.manifest.into_bytes();
+    let manifest = parse_manifest(&data, Path::new(&path))?;
+    Ok((
+        FetchedManifest {
+            manifest,
+            data,
+            validators: BTreeMap::new(),
+            assets: cached.assets,
+        },
+        cached.fetched_at,
+    ))
+}
+
+/// Reads the manifest at `url` with settings from the environment.
+pub(crate) async fn fetch_manifest(url: &ObjectUrl) -> Result<FetchedManifest, LoadError> {
+    ObjectStore::from_env(url.provider)
+        .fetch_manifest(url)
+        .await
+}
+
+/// [`ObjectStore::unchanged`] with settings from the environment.
+pub(crate) async fn unchanged(
+    url: &ObjectUrl,
+    validators: &BTreeMap<String, String>,
+) -> anyhow::Result<bool> {
+    ObjectStore::from_env(url.provider)
+        .unchanged(validators)
+        .await
+}
+
+struct SignedRequest<'a> {
+    method: &'a str,
+    /// Already URI-encoded.
+    path: &'a str,
+    query: &'a str,
+    /// Lowercase names.
+    headers: &'a [(&'a str, &'a str)],
+    payload_sha256: &'a str,
+    /// `YYYYMMDD'T'HHMMSS'Z'`, matching the signed `x-amz-date` header.
+    date: &'a str,
+}
+
+/// The `Authorization` header value for `request` under AWS Signature Version 4.
+fn sign_v4(
+    credentials: &Credentials,
+    region: &str,
+    service: &str,
+    request: &SignedRequest<'_>,
+) -> String {
+    let mut headers = request.headers.to_vec();
+    headers.sort_by_key(|(name, _)| *name);
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{name}:{}\n", value.trim()))
+        .collect();
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{canonical_headers}\n{signed_headers}\n{}",
+        request.method, request.path, request.query, request.payload_sha256
+    );
+
+    let day = &request.date[..8];
+    let scope = format!("{day}/{region}/{service}/aws4_request");
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{scope}\n{}",
+        request.date,
+        sha256_hex(canonical_request.as_bytes())
+    );
+
+    let key = [day, region, service, "aws4_request"].iter().fold(
+        format!("AWS4{}", credentials.secret_access_key).into_bytes(),
+        |key, part| hmac_sha256(&key, part.as_bytes()).to_vec(),
+    );
+    let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+        credentials.access_key_id
+    )
+}
+
+fn amz_date(now: OffsetDateTime) -> String {
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        now.year(),
+        u8::from(now.month()),
+        now.day(),
+        now.hour(),
+        now.minute(),
+        now.second()
+    )
+}
+
+/// Percent-encodes everything but unreserved characters, and `/` when `keep_slashes` is set.
+fn uri_encode(value: &str, keep_slashes: bool) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(char::from(byte))
+            }
+            b'/' if keep_slashes => encoded.push('/'),
+            byte => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac.finalize().into_bytes().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn signature_matches_aws_test_suite() {
+        // `get-vanilla` from the AWS Signature Version 4 test suite.
+        let credentials = Credentials {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+            session_token: None,
+        };
+        let authorization = sign_v4(
+            &credentials,
+            "us-east-1",
+            "service",
+            &SignedRequest {
+                method: "GET",
+                path: "/",
+                query: "",
+                headers: &[
+                    ("host", "example.amazonaws.com"),
+                    ("x-amz-date", "20150830T123600Z"),
+                ],
+                payload_sha256: EMPTY_SHA256,
+                date: "20150830T123600Z",
+            },
+        );
+        assert_eq!(
+            authorization,
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+    }
+
+    #[tokio::test]
+    async fn manifest_assets_resolve_against_its_key() {
+        type Request = (String, Vec<(&'static str, String)>);
+
+        #[derive(Default)]
+        struct Bucket {
+            requests: Mutex<Vec<Request>>,
+        }
+        impl ObjectStoreTransport for Bucket {
+            fn get<'a>(
+                &'a self,
+                url: &'a str,
+                headers: &'a [(&'static str, String)],
+                _timeout: Duration,
+            ) -> BoxFuture<'a, std::io::Result<ObjectResponse>> {
+                self.requests
+                    .lock()
+                    .unwrap()
+                    .push((url.to_string(), headers.to_vec()));
+                let body = match url {
+                    "http://minio:9000/widgets/prod/widgets.json" => serde_json::json!({
+                        "schemaVersion": "1.0.0",
+                        "widgets": [{
+                            "id": "pizza-map",
+                            "title": "Show Pizza Map",
+                            "templateUri": "ui://widget/pizza-map.html",
+                            "invoking": "Hand-tossing a map",
+                            "invoked": "Served a fresh map",
+                            "html": "https://cdn.example/pizza-map.html",
+                            "responseText": "Rendered a pizza map!",
+                            "assets": { "html": "../html/pizza-map.html", "js": "https://cdn.example/map.js" }
+                        }]
+                    })
+                    .to_string(),
+                    "http://minio:9000/widgets/html/pizza-map.html" => "<div id=\"map\"></div>".to_string(),
+                    _ => {
+                        return Box::pin(async { Ok(ObjectResponse { status: 404, ..Default::default() }) })
+                    }
+                };
+                let etag = format!("\"{}\"", &sha256_hex(body.as_bytes())[..8]);
+                let unchanged = headers
+                    .iter()
+                    .any(|(name, value)| *name == "if-none-match" && *value == etag);
+                Box::pin(async move {
+                    Ok(if unchanged {
+                        ObjectResponse {
+                            status: 304,
+                            ..Default::default()
+                        }
+                    } else {
+                        ObjectResponse {
+                            status: 200,
+                            body: body.into_bytes(),
+                            validator: Some(etag),
+                        }
+                    })
+                })
+            }
+        }
+
+        let transport = Arc::new(Bucket::default());
+        let store = ObjectStore::with_transport(
+            ObjectStoreConfig {
+                endpoint: "http://minio:9000".to_string(),
+                region: "us-east-1".to_string(),
+                credentials: Some(Credentials {
+                    access_key_id: "pizzaz".to_string(),
+                    secret_access_key: "s3cret".to_string(),
+                    session_token: None,
+                }),
+                timeout: Duration::from_secs(1),
+            },
+            transport.clone(),
+        );
+        let url = ObjectUrl::parse(Path::new("s3://widgets/prod/widgets.json")).unwrap();
+        let fetched = store.fetch_manifest(&url).await.unwrap();
+
+        assert_eq!(fetched.manifest.widgets.len(), 1);
+        assert_eq!(
+            fetched.assets,
+            HashMap::from([(
+                "../html/pizza-map.html".to_string(),
+                "<div id=\"map\"></div>".to_string()
+            )])
+        );
+        let requests = transport.requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0]
+            .1
+            .iter()
+            .any(|(name, value)| *name == "authorization"
+                && value.starts_with("AWS4-HMAC-SHA256 Credential=pizzaz/")));
+
+        let missing = ObjectUrl::parse(Path::new("s3://widgets/staging/widgets.json")).unwrap();
+        assert!(matches!(
+            store.fetch_manifest(&missing).await,
+            Err(LoadError::NotFound { .. })
+        ));
+
+        // Conditional requests tell whether any object changed since the fetch.
+        assert_eq!(
+            fetched.validators.keys().collect::<Vec<_>>(),
+            vec![
+                "s3://widgets/html/pizza-map.html",
+                "s3://widgets/prod/widgets.json"
+            ]
+        );
+        assert!(store.unchanged(&fetched.validators).await.unwrap());
+        let requests = transport.requests.lock().unwrap().clone();
+        assert!(requests[3..]
+            .iter()
+            .all(|(_, headers)| headers.iter().any(|(name, _)| *name == "if-none-match")));
+        let mut stale = fetched.validators.clone();
+        stale.insert(
+            "s3://widgets/html/pizza-map.html".to_string(),
+            "\"0ld\"".to_string(),
+        );
+        assert!(!store.unchanged(&stale).await.unwrap());
+        assert!(!store.unchanged(&BTreeMap::new()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn unreachable_buckets_fall_back_to_the_cached_copy() {
+        struct Outage(u16);
+
+        impl ObjectStoreTransport for Outage {
+            fn get<'a>(
+                &'a self,
+                _url: &'a str,
+                _headers: &'a [(&'static str, String)],
+                _timeout: Duration,
+            ) -> BoxFuture<'a, std::io::Result<ObjectResponse>> {
+                let status = self.0;
+                Box::pin(async move {
+                    match status {
+                        0 => Err(std::io::ErrorKind::ConnectionRefused.into()),
+                        status => Ok(ObjectResponse {
+                            status,
+                            ..Default::default()
+                        }),
+                    }
+                })
+            }
+        }
+
+        let url = ObjectUrl::parse(Path::new("s3://widgets/prod/widgets.json")).unwrap();
+        let store = |status| {
+            ObjectStore::with_transport(
+                ObjectStoreConfig {
+                    endpoint: "http://minio:9000".to_string(),
+                    region: "us-east-1".to_string(),
+                    credentials: None,
+                    timeout: Duration::from_secs(1),
+                },
+                Arc::new(Outage(status)),
+            )
+        };
+        for (status, unavailable) in [(0, true), (503, true), (403, false)] {
+            let error = store(status).fetch_manifest(&url).await.unwrap_err();
+            assert_eq!(is_unavailable(&error), unavailable, "{status}: {error}");
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("widgets.json");
+        let data = serde_json::json!({
+            "schemaVersion": "1.0.0",
+            "widgets": [{
+                "id": "pizza-map",
+                "title": "Pizza Map",
+                "templateUri": "ui://widget/pizza-map.html",
+                "invoking": "Invoking",
+                "invoked": "Invoked",
+                "htmlPath": "pizza-map.html",
+                "responseText": "Rendered!"
+            }]
+        })
+        .to_string();
+        let assets = HashMap::from([(
+            "pizza-map.html".to_string(),
+            "<div id=\"pizza-map-root\"></div>".to_string(),
+        )]);
+        store_cached(&cache, &url, data.as_bytes(), &assets).unwrap();
+
+        let (fetched, fetched_at) = load_cached(&cache, &url).unwrap();
+        assert_eq!(fetched.manifest.widgets[0].id, "pizza-map");
+        assert_eq!(fetched.data, data.as_bytes());
+        assert_eq!(fetched.assets, assets);
+        assert!(OffsetDateTime::parse(&fetched_at, &Iso8601::DEFAULT).is_ok());
+
+        let staging = ObjectUrl::parse(Path::new("s3://widgets/staging/widgets.json")).unwrap();
+        let error = load_cached(&cache, &staging).unwrap_err();
+        assert!(error.to_string().ends_with(
+            "holds s3://widgets/prod/widgets.json, not s3://widgets/staging/widgets.json"
+        ));
+    }
+
+    #[test]
+    fn provider_endpoints_default_to_https() {
+        assert!(ObjectStoreConfig::from_env(Provider::S3)
+            .endpoint
+            .starts_with("https://s3."));
+        assert_eq!(
+            ObjectStoreConfig::from_env(Provider::Gcs).endpoint,
+            "https://storage.googleapis.com"
+        );
+    }
+}
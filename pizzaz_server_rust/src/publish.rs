@@ -0,0 +1,342 @@
+//! Manifest publishing with two-person approval.
+//!
+//! With `WIDGETS_PUBLISHER_TOKENS` set (same `token=subject[:roles]` format as `MCP_AUTH_TOKENS`),
+//! a publisher can push a manifest to `POST /internal/widgets/publish`. It is validated and
+//! staged next to the active manifest, but not served. A second publisher approves it with
+//! `POST /internal/widgets/publish/{id}/approve`, which swaps it into place and reloads the
+//! registry; the submitter cannot approve their own change. The manifest it replaces is kept as
+//! `widgets.previous.json`, and put back if the reload fails, so the file on disk always matches
+//! the registry being served. At most one publish is pending at a time, and any publisher can
+//! withdraw it with `DELETE /internal/widgets/publish/{id}`.
+
+use std::{
+    net::SocketAddr,
+    path::{Path as FsPath, PathBuf},
+};
+
+use axum::{
+    body::Bytes,
+    extract::{rejection::BytesRejection, ConnectInfo, Path},
+    http::{HeaderMap, StatusCode},
+    Extension, Json,
+};
+use serde::Serialize;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tokio::sync::{Mutex, MutexGuard};
+
+use crate::{auth, error::ApiError, widgets, AppState, RefreshResponse};
+
+const PUBLISH_DOCS: &str = "manifest-publishing";
+
+/// Publisher identities and the publish awaiting approval, if any.
+#[derive(Debug, Default)]
+pub(crate) struct Publishing {
+    publishers: auth::AuthConfig,
+    pending: Mutex<Option<PendingPublish>>,
+}
+
+/// A validated manifest waiting for a second publisher.
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingPublish {
+    pub id: String,
+    pub submitted_by: String,
+    /// RFC 3339 submission time.
+    pub submitted_at: String,
+    /// Lowercase hex SHA-256 of the submitted manifest.
+    pub sha256: String,
+    pub widgets_count: usize,
+    /// The manifest this publish replaces once approved.
+    pub manifest_path: PathBuf,
+    #[serde(skip)]
+    staging_path: PathBuf,
+}
+
+#[derive(Serialize)]
+pub(crate) struct PublishResponse {
+    id: String,
+    submitted_by: String,
+    approved_by: String,
+    #[serde(flatten)]
+    refresh: RefreshResponse,
+}
+
+#[derive(Serialize)]
+pub(crate) struct WithdrawResponse {
+    withdrawn: String,
+    withdrawn_by: String,
+}
+
+impl Publishing {
+    pub(crate) fn from_env() -> Self {
+        Self {
+            publishers: auth::AuthConfig::from_env_var("WIDGETS_PUBLISHER_TOKENS"),
+            pending: Mutex::default(),
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.publishers.is_enabled()
+    }
+
+    pub(crate) fn publishers(&self) -> Vec<String> {
+        self.publishers
+            .identities()
+            .filter_map(|identity| identity.subject.clone())
+            .collect()
+    }
+
+    /// The publish awaiting approval, for the status endpoint.
+    pub(crate) async fn pending(&self) -> Option<PendingPublish> {
+        self.lock().await.clone()
+    }
+
+    /// Held across submission and approval, file I/O and reload included, so they never overlap.
+    async fn lock(&self) -> MutexGuard<'_, Option<PendingPublish>> {
+        self.pending.lock().await
+    }
+
+    /// The publisher subject behind the request's bearer token.
+    fn authenticate(&self, addr: SocketAddr, headers: &HeaderMap) -> Result<String, ApiError> {
+        if !self.is_enabled() {
+            return Err(ApiError::not_found("Manifest publishing is disabled"));
+        }
+        auth::extract_bearer_token(headers)
+            .and_then(|token| self.publishers.identify(token))
+            .and_then(|identity| identity.subject)
+            .ok_or_else(|| {
+                tracing::warn!(ip = %addr.ip(), "Invalid publisher token provided");
+                ApiError::unauthorized("widgets-publish", auth::bearer_error(headers))
+                    .with_docs(PUBLISH_DOCS)
+            })
+    }
+}
+
+/// `POST /internal/widgets/publish`: validates and stages a manifest for approval.
+pub(crate) async fn submit_handler(
+    Extension(state): Extension<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    body: Result<Bytes, BytesRejection>,
+) -> Result<(StatusCode, Json<PendingPublish>), ApiError> {
+    let publishing = &state.publishing;
+    let submitted_by = publishing.authenticate(addr, &headers)?;
+    let body = body?;
+
+    let mut pending = publishing.lock().await;
+    if let Some(existing) = pending.as_ref() {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "publish_pending",
+            "Another manifest is awaiting approval; approve or withdraw it first",
+        )
+        .with_docs(PUBLISH_DOCS)
+        .with_details(serde_json::to_value(existing).unwrap_or_default()));
+    }
+
+    // Staged beside the active manifest so relative asset paths resolve the same way.
+    let manifest_path = widgets::manifest_path();
+    let staging_path = sibling_path(&manifest_path, "pending");
+    let registry = {
+        let staging_path = staging_path.clone();
+        let body = body.clone();
+        tokio::task::spawn_blocking(move || stage(&staging_path, &body))
+            .await
+            .map_err(|error| staging_failed(error.to_string()))??
+    };
+
+    let publish = PendingPublish {
+        id: uuid::Uuid::new_v4().to_string(),
+        submitted_by,
+        submitted_at: OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .unwrap_or_default(),
+        sha256: widgets::sha256_hex(&body),
+        widgets_count: registry.widgets().len(),
+        manifest_path,
+        staging_path,
+    };
+    tracing::info!(
+        id = %publish.id,
+        submitted_by = %publish.submitted_by,
+        sha256 = %publish.sha256,
+        "Manifest submitted for approval"
+    );
+    *pending = Some(publish.clone());
+    Ok((StatusCode::ACCEPTED, Json(publish)))
+}
+
+/// Writes `body` to `staging_path` and loads it as a registry, removing the file if it is invalid.
+fn stage(staging_path: &FsPath, body: &[u8]) -> Result<widgets::WidgetsRegistry, ApiError> {
+    let registry = std::fs::write(staging_path, body)
+        .map_err(|error| staging_failed(format!("Could not stage the manifest: {error}")))
+        .and_then(|()| {
+            widgets::load_registry_from_path(staging_path).map_err(|error| {
+                let invalid = ApiError::bad_request("manifest_invalid", error.to_string())
+                    .with_docs(PUBLISH_DOCS);
+                match error.schema_violations() {
+                    Some(violations) => {
+                        invalid.with_details(serde_json::json!({ "schema_errors": violations }))
+                    }
+                    None => invalid,
+                }
+            })
+        });
+    if registry.is_err() {
+        let _ = std::fs::remove_file(staging_path);
+    }
+    registry
+}
+
+fn staging_failed(message: String) -> ApiError {
+    ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "staging_failed", message)
+}
+
+fn promotion_failed(error: std::io::Error) -> ApiError {
+    ApiError::new(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "promotion_failed",
+        format!("Could not replace the manifest: {error}"),
+    )
+}
+
+/// `POST /internal/widgets/publish/{id}/approve`: promotes the pending manifest.
+pub(crate) async fn approve_handler(
+    Extension(state): Extension<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<PublishResponse>, ApiError> {
+    let publishing = &state.publishing;
+    let approved_by = publishing.authenticate(addr, &headers)?;
+
+    let mut pending = publishing.lock().await;
+    let publish = matching_publish(pending.as_ref(), &id)?;
+    if publish.submitted_by == approved_by {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "self_approval",
+            "A manifest must be approved by someone other than its submitter",
+        )
+        .with_docs(PUBLISH_DOCS));
+    }
+    if widgets::manifest_path() != publish.manifest_path {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "manifest_path_changed",
+            "The active manifest path changed after this publish was submitted; withdraw and resubmit it",
+        )
+        .with_docs(PUBLISH_DOCS));
+    }
+    // The staged file must still be exactly what was reviewed.
+    let staged = tokio::fs::read(&publish.staging_path)
+        .await
+        .unwrap_or_default();
+    if widgets::sha256_hex(&staged) != publish.sha256 {
+        let _ = tokio::fs::remove_file(&publish.staging_path).await;
+        *pending = None;
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "staged_manifest_changed",
+            "The staged manifest was modified or removed after submission; resubmit it",
+        )
+        .with_docs(PUBLISH_DOCS));
+    }
+
+    let backup_path = sibling_path(&publish.manifest_path, "previous");
+    let had_previous = match tokio::fs::copy(&publish.manifest_path, &backup_path).await {
+        Ok(_) => true,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => false,
+        Err(error) => return Err(promotion_failed(error)),
+    };
+    tokio::fs::rename(&publish.staging_path, &publish.manifest_path)
+        .await
+        .map_err(promotion_failed)?;
+    let publish = pending.take().expect("matched publish is pending");
+    tracing::info!(
+        id = %publish.id,
+        submitted_by = %publish.submitted_by,
+        approved_by = %approved_by,
+        "Manifest publish approved"
+    );
+
+    let report = widgets::reload_registry().await;
+    if report.result.is_err() {
+        // The registry still serves the previous manifest, so the file goes back to match it.
+        let restored = if had_previous {
+            tokio::fs::rename(&backup_path, &publish.manifest_path).await
+        } else {
+            tokio::fs::remove_file(&publish.manifest_path).await
+        };
+        match restored {
+            Ok(()) => tracing::warn!(
+                id = %publish.id,
+                manifest = %publish.manifest_path.display(),
+                "Published manifest failed to load; restored the previous manifest"
+            ),
+            Err(error) => tracing::error!(
+                id = %publish.id,
+                manifest = %publish.manifest_path.display(),
+                error = %error,
+                "Published manifest failed to load and the previous manifest could not be restored"
+            ),
+        }
+    }
+    drop(pending);
+    state
+        .events
+        .publish(crate::events::Event::registry_reloaded(
+            "publish",
+            &report.result,
+        ));
+    let refresh = crate::registry_load_payload(report.result)?;
+    Ok(Json(PublishResponse {
+        id: publish.id,
+        submitted_by: publish.submitted_by,
+        approved_by,
+        refresh,
+    }))
+}
+
+/// `DELETE /internal/widgets/publish/{id}`: discards the pending manifest.
+pub(crate) async fn withdraw_handler(
+    Extension(state): Extension<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<WithdrawResponse>, ApiError> {
+    let publishing = &state.publishing;
+    let withdrawn_by = publishing.authenticate(addr, &headers)?;
+
+    let mut pending = publishing.lock().await;
+    let publish = matching_publish(pending.as_ref(), &id)?;
+    let _ = tokio::fs::remove_file(&publish.staging_path).await;
+    tracing::info!(id = %id, withdrawn_by = %withdrawn_by, "Manifest publish withdrawn");
+    *pending = None;
+    Ok(Json(WithdrawResponse {
+        withdrawn: id,
+        withdrawn_by,
+    }))
+}
+
+fn matching_publish<'a>(
+    pending: Option<&'a PendingPublish>,
+    id: &str,
+) -> Result<&'a PendingPublish, ApiError> {
+    pending
+        .filter(|publish| publish.id == id)
+        .ok_or_else(|| ApiError::not_found(format!("No pending publish with id {id}")))
+}
+
+/// `widgets.json` is staged as `widgets.pending.json` and backed up as `widgets.previous.json` in
+/// the same directory. The extension is kept, so the files are read in the active manifest's format.
+fn sibling_path(manifest_path: &FsPath, label: &str) -> PathBuf {
+    let stem = manifest_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "widgets".to_string());
+    let extension = manifest_path
+        .extension()
+        .map(|extension| extension.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "json".to_string());
+    manifest_path.with_file_name(format!("{stem}.{label}.{extension}"))
+}
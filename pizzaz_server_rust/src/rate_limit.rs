@@ -0,0 +1,358 @@
+//! Fixed-window rate limiting shared by the HTTP endpoints and tool calls.
+//!
+//! Buckets whose window has ended are swept by a background task (see [`spawn_gc`]) rather than
+//! on the request path, so no caller pays for cleanup.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    hash::Hash,
+    net::IpAddr,
+    sync::Weak,
+    time::{Duration, Instant},
+};
+
+use crate::supervisor::Supervisor;
+
+const DEFAULT_GC_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Fixed-window limiter tracking one bucket per key.
+#[derive(Debug)]
+pub struct RateLimiter<K> {
+    limit: u64,
+    window: Duration,
+    buckets: HashMap<K, RateLimitBucket>,
+}
+
+impl<K: Hash + Eq> RateLimiter<K> {
+    pub fn new(limit: u64, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Returns the configuration this limiter was built with.
+    pub fn config(&self) -> RateLimitConfig {
+        RateLimitConfig {
+            max_requests: self.limit,
+            window: self.window,
+        }
+    }
+
+    pub fn check(&mut self, key: K, now: Instant) -> Result<(), RateLimitRejection> {
+        let entry = self.buckets.entry(key).or_insert_with(|| RateLimitBucket {
+            window_start: now,
+            count: 0,
+        });
+
+        if now.duration_since(entry.window_start) >= self.window {
+            entry.window_start = now;
+            entry.count = 0;
+        }
+
+        if entry.count < self.limit {
+            entry.count += 1;
+            return Ok(());
+        }
+
+        let elapsed = now.duration_since(entry.window_start);
+        let remaining = self
+            .window
+            .checked_sub(elapsed)
+            .unwrap_or_else(|| Duration::from_secs(0));
+
+        Err(RateLimitRejection {
+            retry_after: if remaining.is_zero() {
+                Duration::from_secs(1)
+            } else {
+                remaining
+            },
+        })
+    }
+
+    /// Drops buckets whose window has ended; a fresh bucket behaves identically. Returns how many.
+    pub fn purge_expired(&mut self, now: Instant) -> usize {
+        let before = self.buckets.len();
+        self.buckets
+            .retain(|_, bucket| now.duration_since(bucket.window_start) < self.window);
+        before - self.buckets.len()
+    }
+
+    /// Number of keys with a live bucket.
+    pub fn tracked_keys(&self) -> usize {
+        self.buckets.len()
+    }
+}
+
+/// State holding rate limit buckets that the background sweep can purge.
+pub trait PurgeExpired: Send + Sync {
+    /// Removes expired buckets, returning how many were dropped.
+    fn purge_expired(&self, now: Instant) -> usize;
+}
+
+impl<K: Hash + Eq + Send> PurgeExpired for tokio::sync::Mutex<RateLimiter<K>> {
+    fn purge_expired(&self, now: Instant) -> usize {
+        // A contended limiter is serving a request; sweep it on the next tick instead of waiting.
+        self.try_lock()
+            .map(|mut limiter| limiter.purge_expired(now))
+            .unwrap_or(0)
+    }
+}
+
+/// Interval between sweeps, from `RATE_LIMIT_GC_INTERVAL_SECS` (default 60).
+pub(crate) fn gc_interval_from_env() -> Duration {
+    std::env::var("RATE_LIMIT_GC_INTERVAL_SECS")
+        .ok()
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .filter(|value| *value > 0)
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_GC_INTERVAL)
+}
+
+/// Periodically purges expired buckets from `targets` until all of them have been dropped.
+pub(crate) fn spawn_gc(
+    supervisor: &Supervisor,
+    targets: Vec<Weak<dyn PurgeExpired>>,
+    interval: Duration,
+) {
+    supervisor.spawn("rate_limit_gc", move || {
+        let targets = targets.clone();
+        sweep(targets, interval)
+    });
+}
+
+async fn sweep(targets: Vec<Weak<dyn PurgeExpired>>, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        let now = Instant::now();
+        let mut alive = false;
+        let mut purged = 0;
+        for target in targets.iter().filter_map(Weak::upgrade) {
+            alive = true;
+            purged += target.purge_expired(now);
+        }
+        if !alive {
+            break;
+        }
+        if purged > 0 {
+            tracing::debug!(purged, "Purged expired rate limit buckets");
+        }
+    }
+}
+
+#[derive(Debug)]
+struct RateLimitBucket {
+    window_start: Instant,
+    count: u64,
+}
+
+/// Returned when a key has exhausted its budget for the current window.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitRejection {
+    pub retry_after: Duration,
+}
+
+/// Identifies who a rate limit budget is charged to.
+///
+/// Authenticated callers are keyed on their subject so users behind a shared NAT do not
+/// compete for the same budget; unauthenticated callers fall back to their IP address.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CallerKey {
+    Subject(String),
+    Ip(IpAddr),
+    Unknown,
+}
+
+impl fmt::Display for CallerKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallerKey::Subject(subject) => write!(f, "subject:{subject}"),
+            CallerKey::Ip(ip) => write!(f, "ip:{ip}"),
+            CallerKey::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// Maximum number of requests allowed per window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitConfig {
+    pub max_requests: u64,
+    pub window: Duration,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            max_requests: 10,
+            window: Duration::from_secs(60),
+        }
+    }
+}
+
+impl fmt::Display for RateLimitConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}s", self.max_requests, self.window.as_secs())
+    }
+}
+
+/// Parses a `count/window` specification such as `10/60s` or `5/2m`.
+pub fn parse_rate_limit(raw: &str) -> Result<RateLimitConfig, String> {
+    let raw = raw.trim();
+    let Some((count_str, window_str)) = raw.split_once('/') else {
+        return Err(format!("invalid rate limit '{raw}'; expected count/window"));
+    };
+
+    let max_requests = match count_str.trim().parse::<u64>() {
+        Ok(value) if value > 0 => value,
+        _ => return Err(format!("invalid rate limit count '{count_str}' in '{raw}'")),
+    };
+
+    let window_str = window_str.trim();
+    let invalid_window = || format!("invalid rate limit window '{window_str}' in '{raw}'");
+    let Some((unit_index, unit)) = window_str.char_indices().last() else {
+        return Err(invalid_window());
+    };
+    let unit_secs: u64 = match unit {
+        's' | 'S' => 1,
+        'm' | 'M' => 60,
+        _ => return Err(format!("unsupported rate limit unit '{unit}' in '{raw}'")),
+    };
+    let window = match window_str[..unit_index].parse::<u64>() {
+        Ok(magnitude) if magnitude > 0 => magnitude
+            .checked_mul(unit_secs)
+            .map(Duration::from_secs)
+            .ok_or_else(|| format!("rate limit window '{window_str}' in '{raw}' is too long"))?,
+        _ => return Err(invalid_window()),
+    };
+
+    Ok(RateLimitConfig {
+        max_requests,
+        window,
+    })
+}
+
+/// Parses an optional environment value, falling back to the default on error.
+pub(crate) fn parse_rate_limit_config(variable: &str, raw: Option<String>) -> RateLimitConfig {
+    let default = RateLimitConfig::default();
+    let Some(raw) = raw else {
+        return default;
+    };
+
+    match parse_rate_limit(&raw) {
+        Ok(config) => config,
+        Err(error) => {
+            tracing::warn!(
+                "Invalid {} value: {}; falling back to default {} per {}s",
+                variable,
+                error,
+                default.max_requests,
+                default.window.as_secs()
+            );
+            default
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rate_limit_accepts_seconds_and_minutes() {
+        assert_eq!(
+            parse_rate_limit("10/60s").unwrap(),
+            RateLimitConfig {
+                max_requests: 10,
+                window: Duration::from_secs(60)
+            }
+        );
+        assert_eq!(
+            parse_rate_limit("3/2m").unwrap().window,
+            Duration::from_secs(120)
+        );
+    }
+
+    #[test]
+    fn parse_rate_limit_rejects_malformed_values() {
+        assert!(parse_rate_limit("10").is_err());
+        assert!(parse_rate_limit("0/60s").is_err());
+        assert!(parse_rate_limit("10/60h").is_err());
+        assert!(parse_rate_limit("10/").is_err());
+        assert_eq!(
+            parse_rate_limit("10/5é").unwrap_err(),
+            "unsupported rate limit unit 'é' in '10/5é'"
+        );
+        assert!(parse_rate_limit(&format!("10/{}m", u64::MAX))
+            .unwrap_err()
+            .ends_with("is too long"));
+        assert_eq!(
+            parse_rate_limit_config("TEST_LIMIT", Some("bogus".into())),
+            RateLimitConfig::default()
+        );
+    }
+
+    #[test]
+    fn limiter_tracks_budgets_per_key() {
+        let mut limiter = RateLimiter::new(1, Duration::from_secs(60));
+        let now = Instant::now();
+        let alice = CallerKey::Subject("alice".into());
+        let bob = CallerKey::Subject("bob".into());
+
+        assert!(limiter.check(alice.clone(), now).is_ok());
+        assert!(limiter.check(bob, now).is_ok());
+        let rejection = limiter.check(alice.clone(), now).unwrap_err();
+        assert!(rejection.retry_after <= Duration::from_secs(60));
+
+        assert!(limiter.check(alice, now + Duration::from_secs(61)).is_ok());
+    }
+
+    #[test]
+    fn purge_expired_only_drops_finished_windows() {
+        let mut limiter = RateLimiter::new(5, Duration::from_secs(60));
+        let start = Instant::now();
+        for port in 0..3u8 {
+            limiter
+                .check(IpAddr::from([10, 0, 0, port]), start)
+                .unwrap();
+        }
+        limiter
+            .check(IpAddr::from([10, 0, 0, 9]), start + Duration::from_secs(30))
+            .unwrap();
+        assert_eq!(limiter.tracked_keys(), 4);
+
+        assert_eq!(limiter.purge_expired(start + Duration::from_secs(59)), 0);
+        assert_eq!(limiter.purge_expired(start + Duration::from_secs(60)), 3);
+        assert_eq!(limiter.tracked_keys(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gc_task_sweeps_on_schedule() {
+        let limiter = std::sync::Arc::new(tokio::sync::Mutex::new(RateLimiter::new(
+            1,
+            Duration::from_millis(10),
+        )));
+        limiter
+            .lock()
+            .await
+            .check(IpAddr::from([127, 0, 0, 1]), Instant::now())
+            .unwrap();
+
+        let target: std::sync::Arc<dyn PurgeExpired> = limiter.clone();
+        spawn_gc(
+            &Supervisor::default(),
+            vec![std::sync::Arc::downgrade(&target)],
+            Duration::from_secs(5),
+        );
+        drop(target);
+
+        // Buckets age on the wall clock, which paused Tokio time does not advance.
+        std::thread::sleep(Duration::from_millis(20));
+        tokio::time::sleep(Duration::from_secs(6)).await;
+        assert_eq!(limiter.lock().await.tracked_keys(), 0);
+    }
+}
@@ -0,0 +1,243 @@
+//! Refresh tokens limited to particular manifests.
+//!
+//! `WIDGETS_REFRESH_SCOPES` gives each team its own refresh token, valid only while the active
+//! manifest is one of theirs:
+//!
+//! ```json
+//! {"team-a": {"token": "…", "manifests": ["/srv/widgets/team-a/*"]}}
+//! ```
+//!
+//! A pattern is an exact manifest path, or a prefix followed by `*`. Scoped tokens can refresh the
+//! registry or a widget and switch between manifests in scope; every other operator endpoint still
+//! needs `WIDGETS_REFRESH_TOKEN`. Each authorized change is recorded under the `pizzaz::audit`
+//! target with the scope that made it.
+
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    path::{Component, Path},
+    sync::Arc,
+};
+
+use axum::http::StatusCode;
+use serde::Deserialize;
+use subtle::ConstantTimeEq;
+
+use crate::error::{ApiError, BearerError};
+
+const SCOPE_DOCS: &str = "scoped-refresh-tokens";
+
+/// A refresh token and the manifests it may reload.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RefreshScope {
+    #[serde(skip)]
+    pub name: String,
+    token: String,
+    pub manifests: Vec<String>,
+}
+
+impl std::fmt::Debug for RefreshScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RefreshScope")
+            .field("name", &self.name)
+            .field("manifests", &self.manifests)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RefreshScope {
+    pub fn new(name: impl Into<String>, token: impl Into<String>, manifests: Vec<String>) -> Self {
+        Self {
+            name: name.into(),
+            token: token.into(),
+            manifests,
+        }
+    }
+
+    /// True when `path` matches one of the scope's manifest patterns.
+    ///
+    /// Paths with `..` never match, so a prefix cannot be escaped.
+    pub fn allows(&self, path: &Path) -> bool {
+        if path
+            .components()
+            .any(|component| component == Component::ParentDir)
+        {
+            return false;
+        }
+        let path = path.to_string_lossy();
+        self.manifests
+            .iter()
+            .any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => path.starts_with(prefix),
+                None => path == pattern.as_str(),
+            })
+    }
+}
+
+/// Configured scoped refresh tokens. Clones share them.
+#[derive(Debug, Clone, Default)]
+pub struct RefreshScopes {
+    scopes: Arc<Vec<RefreshScope>>,
+}
+
+impl RefreshScopes {
+    pub fn new(scopes: Vec<RefreshScope>) -> Self {
+        Self {
+            scopes: Arc::new(scopes),
+        }
+    }
+
+    /// Scopes from `WIDGETS_REFRESH_SCOPES`; none when unset or invalid.
+    pub(crate) fn from_env() -> Self {
+        let Ok(json) = std::env::var("WIDGETS_REFRESH_SCOPES") else {
+            return Self::default();
+        };
+        match serde_json::from_str::<BTreeMap<String, RefreshScope>>(&json) {
+            Ok(scopes) => Self::new(
+                scopes
+                    .into_iter()
+                    .filter(|(name, scope)| {
+                        let usable = !scope.token.trim().is_empty();
+                        if !usable {
+                            tracing::warn!(scope = %name, "Ignoring refresh scope without a token");
+                        }
+                        usable
+                    })
+                    .map(|(name, scope)| RefreshScope { name, ..scope })
+                    .collect(),
+            ),
+            Err(error) => {
+                tracing::warn!(error = %error, "Ignoring invalid WIDGETS_REFRESH_SCOPES");
+                Self::default()
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scopes.is_empty()
+    }
+
+    pub fn scopes(&self) -> &[RefreshScope] {
+        &self.scopes
+    }
+
+    /// The scope whose token is `provided`, compared in constant time.
+    pub(crate) fn identify(&self, provided: &str) -> Option<&RefreshScope> {
+        let mut matched = None;
+        for scope in self.scopes.iter() {
+            let equal = scope.token.len() == provided.len()
+                && scope
+                    .token
+                    .as_bytes()
+                    .ct_eq(provided.as_bytes())
+                    .unwrap_u8()
+                    == 1;
+            if equal {
+                matched = Some(scope);
+            }
+        }
+        matched
+    }
+}
+
+/// Who an authorized refresh request acts for.
+#[derive(Debug, Clone)]
+pub(crate) enum RefreshCaller {
+    /// `WIDGETS_REFRESH_TOKEN`, valid for every manifest.
+    Operator,
+    Scoped(RefreshScope),
+}
+
+impl RefreshCaller {
+    /// The scope name recorded in audit logs.
+    pub(crate) fn scope(&self) -> &str {
+        match self {
+            Self::Operator => "operator",
+            Self::Scoped(scope) => &scope.name,
+        }
+    }
+
+    /// Refuses with `403` unless the caller may change the registry loaded from `path`.
+    pub(crate) fn require_manifest(&self, path: &Path) -> Result<(), ApiError> {
+        match self {
+            Self::Scoped(scope) if !scope.allows(path) => {
+                let name = &scope.name;
+                tracing::warn!(
+                    target: "pizzaz::audit",
+                    scope = %name,
+                    manifest = %path.display(),
+                    "Refresh refused outside token scope"
+                );
+                Err(ApiError::new(
+                    StatusCode::FORBIDDEN,
+                    "out_of_scope",
+                    format!(
+                        "Refresh token scope {name} does not cover {}",
+                        path.display()
+                    ),
+                )
+                .with_docs(SCOPE_DOCS)
+                .with_details(serde_json::json!({
+                    "scope": name,
+                    "manifest_path": path.display().to_string(),
+                }))
+                .with_bearer_challenge("widgets-refresh", BearerError::InsufficientScope))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Records an authorized registry change.
+    pub(crate) fn audit(&self, addr: SocketAddr, action: &str, path: &Path) {
+        tracing::info!(
+            target: "pizzaz::audit",
+            scope = %self.scope(),
+            action,
+            manifest = %path.display(),
+            ip = %addr.ip(),
+            "Registry change authorized"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scopes_match_exact_paths_and_prefixes() {
+        let scopes = RefreshScopes::new(vec![
+            RefreshScope::new(
+                "team-a",
+                "token-a",
+                vec!["/srv/widgets/team-a/*".to_string()],
+            ),
+            RefreshScope::new(
+                "team-b",
+                "token-b",
+                vec!["s3://widgets/team-b/widgets.json".to_string()],
+            ),
+        ]);
+
+        let team_a = scopes.identify("token-a").unwrap();
+        assert!(team_a.allows(Path::new("/srv/widgets/team-a/v2.json")));
+        assert!(!team_a.allows(Path::new("/srv/widgets/team-b/v2.json")));
+        assert!(!team_a.allows(Path::new("/srv/widgets/team-a/../team-b/v2.json")));
+
+        let team_b = RefreshCaller::Scoped(scopes.identify("token-b").unwrap().clone());
+        assert!(team_b
+            .require_manifest(Path::new("s3://widgets/team-b/widgets.json"))
+            .is_ok());
+        let refused = team_b
+            .require_manifest(Path::new("/srv/widgets/team-a/v2.json"))
+            .unwrap_err();
+        assert_eq!(refused.status(), StatusCode::FORBIDDEN);
+        assert_eq!(refused.code(), "out_of_scope");
+
+        assert!(scopes.identify("token-c").is_none());
+        assert!(RefreshCaller::Operator
+            .require_manifest(Path::new("/anything.json"))
+            .is_ok());
+    }
+}
@@ -0,0 +1,254 @@
+//! An interactive prompt for calling tools and reading resources in-process.
+//!
+//! `pizzaz_server_rust repl` loads the registry the same way the server does and reads commands
+//! from stdin instead of binding a port:
+//!
+//! ```text
+//! pizzaz> call pizza-map pizzaTopping=pepperoni
+//! pizzaz> read pizza-map
+//! ```
+//!
+//! Results are printed as pretty JSON, exactly as `tools/call` and `resources/read` return them to
+//! clients, widget `_meta` included. Without a line editor the terminal owns the input line, so
+//! completion works by typing Tab and then Enter: the candidates for the word before the Tab are
+//! printed instead of running the line.
+
+use serde_json::{json, Value as JsonValue};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{
+    auth::Identity,
+    handler::{CallContext, PizzazServerHandler},
+    mcp_client::parse_cli_arguments,
+    widgets, wire_result,
+};
+
+const PROMPT: &str = "pizzaz> ";
+
+const COMMANDS: &[&str] = &[
+    "call",
+    "exit",
+    "help",
+    "read",
+    "resources",
+    "tools",
+    "widgets",
+];
+
+const HELP: &str = "\
+commands:
+  tools                                  list tools and their titles
+  resources                              list widget resources
+  widgets                                list widget ids and template URIs
+  call <tool> [<json> | key=value...]    call a tool and print the result
+  read <widget id | uri>                 read a widget resource
+  help                                   show this help
+  exit                                   leave the prompt (also quit or Ctrl+D)
+completion: type Tab then Enter to list candidates for the current word";
+
+/// What a line of input produced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplOutput {
+    Text(String),
+    Quit,
+}
+
+/// Runs REPL commands against one handler.
+pub struct Repl {
+    handler: PizzazServerHandler,
+}
+
+impl Repl {
+    pub fn new(handler: PizzazServerHandler) -> Self {
+        Self { handler }
+    }
+
+    /// Prompts on `output` and runs lines from `input` until `exit` or end of input.
+    pub async fn run(
+        &self,
+        input: impl AsyncBufRead + Unpin,
+        mut output: impl AsyncWrite + Unpin,
+    ) -> std::io::Result<()> {
+        let widgets = widgets::get_all_widgets().len();
+        output
+            .write_all(format!("{widgets} widgets loaded; type help for commands\n").as_bytes())
+            .await?;
+        let mut lines = input.lines();
+        loop {
+            output.write_all(PROMPT.as_bytes()).await?;
+            output.flush().await?;
+            let Some(line) = lines.next_line().await? else {
+                output.write_all(b"\n").await?;
+                return Ok(());
+            };
+            match self.execute(&line).await {
+                ReplOutput::Quit => return Ok(()),
+                ReplOutput::Text(text) if text.is_empty() => {}
+                ReplOutput::Text(text) => {
+                    output.write_all(text.as_bytes()).await?;
+                    output.write_all(b"\n").await?;
+                }
+            }
+        }
+    }
+
+    /// Runs one line of input. A line containing a Tab lists completions instead.
+    pub async fn execute(&self, line: &str) -> ReplOutput {
+        if let Some((before, _)) = line.split_once('\t') {
+            return ReplOutput::Text(self.complete(before).await.join("  "));
+        }
+        let line = line.trim();
+        let (command, rest) = line
+            .split_once(char::is_whitespace)
+            .map(|(command, rest)| (command, rest.trim()))
+            .unwrap_or((line, ""));
+        let text = match command {
+            "" => String::new(),
+            "exit" | "quit" => return ReplOutput::Quit,
+            "help" => HELP.to_string(),
+            "tools" => self.tools().await,
+            "resources" => self.resources().await,
+            "widgets" => widgets::get_all_widgets()
+                .iter()
+                .map(|widget| format!("{:<28} {}", widget.id, widget.template_uri))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            "call" => self.call(rest).await,
+            "read" => self.read(rest).await,
+            other => format!("unknown command {other:?}; type help for commands"),
+        };
+        ReplOutput::Text(text)
+    }
+
+    /// Candidates for the last word of `line`.
+    pub async fn complete(&self, line: &str) -> Vec<String> {
+        let mut words: Vec<&str> = line.split_whitespace().collect();
+        if line.is_empty() || line.ends_with(char::is_whitespace) {
+            words.push("");
+        }
+        let prefix = words.last().copied().unwrap_or_default();
+        let candidates: Vec<String> = match words.as_slice() {
+            [_] => COMMANDS.iter().map(|command| command.to_string()).collect(),
+            ["call", _] => self.tool_names().await,
+            ["read", _] => widgets::get_all_widgets()
+                .iter()
+                .flat_map(|widget| [widget.id.clone(), widget.template_uri.clone()])
+                .collect(),
+            _ => Vec::new(),
+        };
+        candidates
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(prefix))
+            .collect()
+    }
+
+    async fn tool_names(&self) -> Vec<String> {
+        self.handler
+            .list_tools_result(&Identity::anonymous())
+            .await
+            .tools
+            .into_iter()
+            .map(|tool| tool.name.to_string())
+            .collect()
+    }
+
+    async fn tools(&self) -> String {
+        let tools = wire_result(&self.handler.list_tools_result(&Identity::anonymous()).await);
+        tools["tools"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|tool| {
+                format!(
+                    "{:<28} {}",
+                    tool["name"].as_str().unwrap_or_default(),
+                    tool["title"].as_str().unwrap_or_default()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    async fn resources(&self) -> String {
+        pretty(&wire_result(&self.handler.list_resources_result().await))
+    }
+
+    async fn call(&self, rest: &str) -> String {
+        let (name, arguments) = rest
+            .split_once(char::is_whitespace)
+            .map(|(name, arguments)| (name, arguments.trim()))
+            .unwrap_or((rest, ""));
+        if name.is_empty() {
+            return "usage: call <tool> [<json> | key=value...]".to_string();
+        }
+        let arguments = if arguments.starts_with('{') {
+            parse_cli_arguments(&[arguments])
+        } else {
+            parse_cli_arguments(&arguments.split_whitespace().collect::<Vec<_>>())
+        };
+        let arguments = match arguments {
+            Ok(arguments) => arguments,
+            Err(error) => return format!("error: {error}"),
+        };
+        match self
+            .handler
+            .call_tool_for_client(&CallContext::default(), name, arguments)
+            .await
+        {
+            Ok(result) => pretty(&wire_result(&result)),
+            Err(error) => format!("error: {}", pretty(&json!(error))),
+        }
+    }
+
+    async fn read(&self, target: &str) -> String {
+        if target.is_empty() {
+            return "usage: read <widget id | uri>".to_string();
+        }
+        let uri = widgets::get_widget_by_id(target)
+            .map(|widget| widget.template_uri.clone())
+            .unwrap_or_else(|| target.to_string());
+        match self.handler.read_resource_result(&uri).await {
+            Ok(result) => pretty(&wire_result(&result)),
+            Err(error) => format!("error: {}", pretty(&json!(error))),
+        }
+    }
+}
+
+fn pretty(value: &JsonValue) -> String {
+    serde_json::to_string_pretty(value).expect("JSON value serializes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::initialize_widgets_for_tests;
+
+    #[tokio::test]
+    async fn repl_calls_tools_and_completes_widget_ids() {
+        initialize_widgets_for_tests();
+        let repl = Repl::new(PizzazServerHandler::new());
+
+        let ReplOutput::Text(text) = repl.execute("call pizza-map pizzaTopping=basil").await else {
+            panic!("call should print a result");
+        };
+        let result: JsonValue = serde_json::from_str(&text).unwrap();
+        assert_eq!(result["structuredContent"]["pizzaTopping"], "basil");
+        assert!(result["_meta"]["openai/outputTemplate"].is_string());
+
+        let ReplOutput::Text(text) = repl.execute("read pizza-map").await else {
+            panic!("read should print the resource");
+        };
+        assert!(text.contains("text/html+skybridge"));
+
+        assert_eq!(repl.complete("re").await, vec!["read", "resources"]);
+        assert!(repl
+            .complete("call pizza-")
+            .await
+            .contains(&"pizza-map".to_string()));
+        assert_eq!(
+            repl.execute("call pizza-m\t").await,
+            ReplOutput::Text(repl.complete("call pizza-m").await.join("  "))
+        );
+        assert_eq!(repl.execute("quit").await, ReplOutput::Quit);
+    }
+}
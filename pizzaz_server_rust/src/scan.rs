@@ -0,0 +1,343 @@
+//! Security scanning of widget HTML at manifest load.
+//!
+//! Widget HTML runs inside users' ChatGPT sessions, so every widget is scanned whenever it is
+//! loaded or refreshed. The built-in scanner reports:
+//!
+//! - `insecure_script`: scripts, stylesheets or frames loaded over plain `http://`;
+//! - `blocked_domain`: references to a host in `WIDGETS_SCAN_BLOCKED_DOMAINS` or its subdomains;
+//! - `dynamic_code`: `eval(…)` and `new Function(…)`.
+//!
+//! `WIDGETS_SCAN_POLICY` decides what a finding does: `warn` (the default) loads the widget and
+//! records a warning, `block` fails the load and `off` ignores it. A bare action sets the default
+//! and `rule=action` entries override it, e.g. `block,dynamic_code=warn`. Embedders add their own
+//! rules with [`add_scanner`]; their findings follow the same policy.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, LazyLock, RwLock},
+};
+
+use anyhow::bail;
+use regex::Regex;
+use serde::Serialize;
+
+pub const INSECURE_SCRIPT: &str = "insecure_script";
+pub const BLOCKED_DOMAIN: &str = "blocked_domain";
+pub const DYNAMIC_CODE: &str = "dynamic_code";
+
+/// One problem a scanner found in a widget's HTML.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    /// Rule name the policy is keyed on, e.g. `insecure_script`.
+    pub rule: String,
+    pub message: String,
+}
+
+impl Finding {
+    pub fn new(rule: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            rule: rule.into(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Inspects a widget's HTML before it is served.
+pub trait ContentScanner: Send + Sync {
+    fn scan(&self, widget_id: &str, html: &str) -> Vec<Finding>;
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScanAction {
+    Off,
+    #[default]
+    Warn,
+    Block,
+}
+
+impl ScanAction {
+    fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "off" => Some(Self::Off),
+            "warn" => Some(Self::Warn),
+            "block" => Some(Self::Block),
+            _ => None,
+        }
+    }
+}
+
+/// What each rule's findings do.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ScanPolicy {
+    pub default: ScanAction,
+    pub rules: BTreeMap<String, ScanAction>,
+}
+
+impl ScanPolicy {
+    /// Parses `block,dynamic_code=warn`-style policies.
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        Self::default().with_overrides(raw)
+    }
+
+    /// This policy with the actions in `raw` applied on top; rules `raw` does not name keep
+    /// their action here.
+    pub fn with_overrides(self, raw: &str) -> Result<Self, String> {
+        let mut policy = self;
+        for entry in raw
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let (rule, action) = match entry.split_once('=') {
+                Some((rule, action)) => (Some(rule.trim()), action),
+                None => (None, entry),
+            };
+            let action = ScanAction::parse(action)
+                .ok_or_else(|| format!("unknown scan action in {entry:?}"))?;
+            match rule {
+                Some(rule) => {
+                    policy.rules.insert(rule.to_string(), action);
+                }
+                None => policy.default = action,
+            }
+        }
+        Ok(policy)
+    }
+
+    /// Policy from `WIDGETS_SCAN_POLICY`; warn-only when unset or invalid.
+    fn from_env() -> Self {
+        let Ok(raw) = std::env::var("WIDGETS_SCAN_POLICY") else {
+            return Self::default();
+        };
+        Self::parse(&raw).unwrap_or_else(|error| {
+            tracing::warn!(error = %error, "Ignoring invalid WIDGETS_SCAN_POLICY");
+            Self::default()
+        })
+    }
+
+    pub fn action(&self, rule: &str) -> ScanAction {
+        self.rules.get(rule).copied().unwrap_or(self.default)
+    }
+}
+
+/// The `insecure_script`, `blocked_domain` and `dynamic_code` rules.
+#[derive(Debug, Clone, Default)]
+pub struct BuiltinScanner {
+    /// Hosts whose subdomains are also blocked, lowercase.
+    pub blocked_domains: Vec<String>,
+}
+
+static INSECURE_SUBRESOURCE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)<(script|link|iframe)\b[^>]*?\b(?:src|href)\s*=\s*["']?(http://[^"'\s>]+)"#)
+        .expect("insecure subresource pattern compiles")
+});
+static URL_HOST: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)(?:https?:)?//([a-z0-9](?:[a-z0-9.-]*[a-z0-9])?)")
+        .expect("URL host pattern compiles")
+});
+static DYNAMIC_CODE_CALL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b(eval|new\s+Function)\s*\(").expect("dynamic code pattern compiles")
+});
+
+impl BuiltinScanner {
+    fn from_env() -> Self {
+        Self {
+            blocked_domains: std::env::var("WIDGETS_SCAN_BLOCKED_DOMAINS")
+                .unwrap_or_default()
+                .split(',')
+                .map(|domain| domain.trim().trim_start_matches('.').to_ascii_lowercase())
+                .filter(|domain| !domain.is_empty())
+                .collect(),
+        }
+    }
+
+    fn is_blocked(&self, host: &str) -> bool {
+        self.blocked_domains.iter().any(|domain| {
+            host == domain
+                || host
+                    .strip_suffix(domain.as_str())
+                    .is_some_and(|prefix| prefix.ends_with('.'))
+        })
+    }
+}
+
+impl ContentScanner for BuiltinScanner {
+    fn scan(&self, _widget_id: &str, html: &str) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for capture in INSECURE_SUBRESOURCE.captures_iter(html) {
+            findings.push(Finding::new(
+                INSECURE_SCRIPT,
+                format!(
+                    "<{}> loads {} over plain HTTP",
+                    capture[1].to_ascii_lowercase(),
+                    &capture[2]
+                ),
+            ));
+        }
+
+        let mut blocked: Vec<String> = URL_HOST
+            .captures_iter(html)
+            .map(|capture| capture[1].to_ascii_lowercase())
+            .filter(|host| self.is_blocked(host))
+            .collect();
+        blocked.sort();
+        blocked.dedup();
+        for host in blocked {
+            findings.push(Finding::new(
+                BLOCKED_DOMAIN,
+                format!("references blocked host {host}"),
+            ));
+        }
+
+        if let Some(capture) = DYNAMIC_CODE_CALL.captures(html) {
+            let call = capture[1].split_whitespace().collect::<Vec<_>>().join(" ");
+            findings.push(Finding::new(DYNAMIC_CODE, format!("calls {call}()")));
+        }
+        findings
+    }
+}
+
+/// Scanners and the policy applied to their findings.
+pub struct Scanning {
+    policy: ScanPolicy,
+    scanners: Vec<Arc<dyn ContentScanner>>,
+}
+
+impl Scanning {
+    pub fn new(policy: ScanPolicy, scanners: Vec<Arc<dyn ContentScanner>>) -> Self {
+        Self { policy, scanners }
+    }
+
+    fn from_env() -> Self {
+        Self::new(
+            ScanPolicy::from_env(),
+            vec![Arc::new(BuiltinScanner::from_env())],
+        )
+    }
+
+    pub fn policy(&self) -> &ScanPolicy {
+        &self.policy
+    }
+
+    /// Scans `html`, returning warnings to record, or an error when a finding blocks the widget.
+    pub fn check(&self, widget_id: &str, html: &str) -> anyhow::Result<Vec<String>> {
+        let mut warnings = Vec::new();
+        let mut blocked = Vec::new();
+        let findings = self
+            .scanners
+            .iter()
+            .flat_map(|scanner| scanner.scan(widget_id, html));
+        for finding in findings {
+            let message = format!("{}: {}", finding.rule, finding.message);
+            match self.policy.action(&finding.rule) {
+                ScanAction::Off => {}
+                ScanAction::Warn => {
+                    tracing::warn!(widget_id, rule = %finding.rule, "Widget content scan: {}", finding.message);
+                    warnings.push(format!("Content scan {message}"));
+                }
+                ScanAction::Block => blocked.push(message),
+            }
+        }
+        if !blocked.is_empty() {
+            bail!(
+                "Widget HTML blocked by content scan: {}",
+                blocked.join("; ")
+            );
+        }
+        Ok(warnings)
+    }
+}
+
+static SCANNING: LazyLock<RwLock<Scanning>> = LazyLock::new(|| RwLock::new(Scanning::from_env()));
+
+/// Runs `scanner` on every widget loaded from now on, alongside the built-in rules.
+pub fn add_scanner(scanner: Arc<dyn ContentScanner>) {
+    SCANNING
+        .write()
+        .expect("scanning lock poisoned")
+        .scanners
+        .push(scanner);
+}
+
+/// The policy in effect, for the config endpoint.
+pub fn policy() -> ScanPolicy {
+    SCANNING
+        .read()
+        .expect("scanning lock poisoned")
+        .policy
+        .clone()
+}
+
+/// Scans a widget with the configured scanners and policy.
+pub(crate) fn check(widget_id: &str, html: &str) -> anyhow::Result<Vec<String>> {
+    SCANNING
+        .read()
+        .expect("scanning lock poisoned")
+        .check(widget_id, html)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HTML: &str = r#"<div id="root"></div>
+<script src="http://cdn.example/widget.js"></script>
+<img src="https://pixel.tracker.example/p.gif">
+<script>const render = new Function("return 1");</script>"#;
+
+    fn scanning(policy: &str) -> Scanning {
+        Scanning::new(
+            ScanPolicy::parse(policy).unwrap(),
+            vec![Arc::new(BuiltinScanner {
+                blocked_domains: vec!["tracker.example".to_string()],
+            })],
+        )
+    }
+
+    #[test]
+    fn builtin_rules_find_downgrades_blocked_hosts_and_dynamic_code() {
+        let findings = BuiltinScanner {
+            blocked_domains: vec!["tracker.example".to_string()],
+        }
+        .scan("pizza-map", HTML);
+        let rules: Vec<_> = findings
+            .iter()
+            .map(|finding| finding.rule.as_str())
+            .collect();
+        assert_eq!(rules, vec![INSECURE_SCRIPT, BLOCKED_DOMAIN, DYNAMIC_CODE]);
+        assert_eq!(
+            findings[0].message,
+            "<script> loads http://cdn.example/widget.js over plain HTTP"
+        );
+        assert_eq!(
+            findings[1].message,
+            "references blocked host pixel.tracker.example"
+        );
+        assert_eq!(findings[2].message, "calls new Function()");
+    }
+
+    #[test]
+    fn policy_decides_between_warning_and_blocking() {
+        let warnings = scanning("").check("pizza-map", HTML).unwrap();
+        assert_eq!(warnings.len(), 3);
+
+        let warnings = scanning("block,insecure_script=off,blocked_domain=warn,dynamic_code=off")
+            .check("pizza-map", HTML)
+            .unwrap();
+        assert_eq!(
+            warnings,
+            vec!["Content scan blocked_domain: references blocked host pixel.tracker.example"]
+        );
+
+        let error = scanning("dynamic_code=block")
+            .check("pizza-map", HTML)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Widget HTML blocked by content scan: dynamic_code: calls new Function()"
+        );
+
+        assert!(ScanPolicy::parse("quarantine").is_err());
+    }
+}
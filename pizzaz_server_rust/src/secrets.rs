@@ -0,0 +1,493 @@
+//! Credentials for executor integrations.
+//!
+//! Executors look secrets up by name with [`Invocation::secret`](crate::executor::Invocation::secret)
+//! rather than reading them from the manifest or hard-coding them. `WIDGETS_SECRETS_PROVIDER`
+//! picks where they come from:
+//!
+//! - `env` (the default): `WIDGETS_SECRET_<NAME>`, with the name uppercased and `-` or `.` turned
+//!   into `_`.
+//! - `file`: one file per secret in `WIDGETS_SECRETS_DIR` (default `/run/secrets`), as mounted by
+//!   Docker and Kubernetes.
+//! - `vault`: the `value` field of the KV v2 secret `<WIDGETS_VAULT_PATH>/<name>` (default path
+//!   `pizzaz`) under `WIDGETS_VAULT_MOUNT` (default `secret`), read from `VAULT_ADDR` with
+//!   `VAULT_TOKEN`. `VAULT_ADDR` must be an `https://` URL, so the token never travels in clear.
+//!
+//! Values are cached for `WIDGETS_SECRETS_TTL_SECS` (default 300) so a rotated secret is picked up
+//! within that time without a restart; [`invalidate`] drops one sooner, e.g. after the upstream
+//! rejects it. Embedders can install their own provider with [`set_provider`].
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use futures::future::BoxFuture;
+use serde::Serialize;
+
+use crate::{
+    egress::{EgressClient, EgressPolicy, EgressRequest},
+    executor::ToolFailure,
+};
+
+const DEFAULT_TTL: Duration = Duration::from_secs(300);
+
+/// A secret value. Its `Debug` output never includes the value.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(Arc<str>);
+
+impl Secret {
+    pub fn new(value: impl Into<Arc<str>>) -> Self {
+        Self(value.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Secret([redacted])")
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SecretsError {
+    #[error("Secret name {0:?} may only contain letters, digits, '-', '_' and '.'")]
+    InvalidName(String),
+    #[error("Secret {0} is not configured")]
+    NotFound(String),
+    #[error("Secret {name} could not be read: {message}")]
+    Unavailable { name: String, message: String },
+    #[error("Vault address {0:?} is not an https:// URL")]
+    InsecureVaultAddress(String),
+}
+
+impl From<SecretsError> for ToolFailure {
+    fn from(error: SecretsError) -> Self {
+        let retryable = matches!(error, SecretsError::Unavailable { .. });
+        let failure = ToolFailure::new(
+            "secret_unavailable",
+            "Integration not configured",
+            error.to_string(),
+        );
+        if retryable {
+            failure.retryable()
+        } else {
+            failure
+        }
+    }
+}
+
+/// Looks secrets up by name.
+pub trait SecretsProvider: Send + Sync {
+    /// Short label for the config endpoint, e.g. `vault`.
+    fn kind(&self) -> &'static str;
+
+    fn fetch<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<Secret, SecretsError>>;
+}
+
+/// Reads `WIDGETS_SECRET_<NAME>`.
+#[derive(Debug, Clone, Default)]
+pub struct EnvSecrets;
+
+impl EnvSecrets {
+    pub fn variable(name: &str) -> String {
+        format!(
+            "WIDGETS_SECRET_{}",
+            name.to_ascii_uppercase().replace(['-', '.'], "_")
+        )
+    }
+}
+
+impl SecretsProvider for EnvSecrets {
+    fn kind(&self) -> &'static str {
+        "env"
+    }
+
+    fn fetch<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<Secret, SecretsError>> {
+        Box::pin(async move {
+            std::env::var(Self::variable(name))
+                .ok()
+                .filter(|value| !value.is_empty())
+                .map(Secret::new)
+                .ok_or_else(|| SecretsError::NotFound(name.to_string()))
+        })
+    }
+}
+
+/// Reads `<dir>/<name>`, without a trailing newline.
+#[derive(Debug, Clone)]
+pub struct FileSecrets {
+    pub dir: PathBuf,
+}
+
+impl FileSecrets {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+impl SecretsProvider for FileSecrets {
+    fn kind(&self) -> &'static str {
+        "file"
+    }
+
+    fn fetch<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<Secret, SecretsError>> {
+        Box::pin(async move {
+            let path = self.dir.join(name);
+            match tokio::fs::read_to_string(&path).await {
+                Ok(value) => Ok(Secret::new(value.trim_end_matches(['\r', '\n']))),
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                    Err(SecretsError::NotFound(name.to_string()))
+                }
+                Err(error) => Err(SecretsError::Unavailable {
+                    name: name.to_string(),
+                    message: format!("{}: {error}", path.display()),
+                }),
+            }
+        })
+    }
+}
+
+/// Reads the `value` field of KV v2 secrets from HashiCorp Vault.
+pub struct VaultSecrets {
+    address: String,
+    token: Secret,
+    mount: String,
+    path: String,
+    client: EgressClient,
+}
+
+impl std::fmt::Debug for VaultSecrets {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VaultSecrets")
+            .field("address", &self.address)
+            .field("mount", &self.mount)
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl VaultSecrets {
+    /// Talks to Vault through an [`EgressClient`] limited to Vault's own host. `address` must be
+    /// an `https://` URL.
+    pub fn new(
+        address: impl Into<String>,
+        token: Secret,
+        mount: impl Into<String>,
+        path: impl Into<String>,
+    ) -> Result<Self, SecretsError> {
+        let address = address.into().trim_end_matches('/').to_string();
+        let host = reqwest::Url::parse(&address)
+            .ok()
+            .filter(|url| url.scheme() == "https")
+            .and_then(|url| url.host_str().map(str::to_string))
+            .ok_or_else(|| SecretsError::InsecureVaultAddress(address.clone()))?;
+        Ok(Self {
+            client: EgressClient::new(EgressPolicy::new([host])).for_caller("secrets"),
+            address,
+            token,
+            mount: mount.into(),
+            path: path.into(),
+        })
+    }
+
+    /// Sends requests with `client` instead, e.g. one with a test transport.
+    pub fn with_client(mut self, client: EgressClient) -> Self {
+        self.client = client;
+        self
+    }
+
+    fn url(&self, name: &str) -> String {
+        let path = self.path.trim_matches('/');
+        if path.is_empty() {
+            format!("{}/v1/{}/data/{name}", self.address, self.mount)
+        } else {
+            format!("{}/v1/{}/data/{path}/{name}", self.address, self.mount)
+        }
+    }
+}
+
+impl SecretsProvider for VaultSecrets {
+    fn kind(&self) -> &'static str {
+        "vault"
+    }
+
+    fn fetch<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<Secret, SecretsError>> {
+        Box::pin(async move {
+            let unavailable = |message: String| SecretsError::Unavailable {
+                name: name.to_string(),
+                message,
+            };
+            let request = EgressRequest::get(self.url(name))
+                .header("X-Vault-Token", self.token.expose().to_string());
+            let response = self
+                .client
+                .send(request)
+                .await
+                .map_err(|error| unavailable(error.to_string()))?;
+            match response.status {
+                200 => {}
+                404 => return Err(SecretsError::NotFound(name.to_string())),
+                status => return Err(unavailable(format!("Vault returned HTTP {status}"))),
+            }
+            let body = response
+                .json()
+                .map_err(|error| unavailable(format!("invalid Vault response: {error}")))?;
+            body["data"]["data"]["value"]
+                .as_str()
+                .map(Secret::new)
+                .ok_or_else(|| unavailable("secret has no string \"value\" field".to_string()))
+        })
+    }
+}
+
+/// A provider with a time-limited cache in front of it. Clones share the cache.
+#[derive(Clone)]
+pub struct Secrets {
+    provider: Arc<dyn SecretsProvider>,
+    ttl: Duration,
+    cache: Arc<Mutex<HashMap<String, (Secret, Instant)>>>,
+}
+
+impl std::fmt::Debug for Secrets {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Secrets")
+            .field("provider", &self.provider.kind())
+            .field("ttl", &self.ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+/// What the config endpoint reports about secrets; never any values.
+#[derive(Debug, Clone, Serialize)]
+pub struct SecretsSettings {
+    pub provider: &'static str,
+    pub ttl_secs: u64,
+}
+
+impl Secrets {
+    pub fn new(provider: Arc<dyn SecretsProvider>, ttl: Duration) -> Self {
+        Self {
+            provider,
+            ttl,
+            cache: Arc::default(),
+        }
+    }
+
+    /// The provider chosen by `WIDGETS_SECRETS_PROVIDER`, falling back to `env`.
+    fn from_env() -> Self {
+        let ttl = std::env::var("WIDGETS_SECRETS_TTL_SECS")
+            .ok()
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_TTL);
+        let kind = std::env::var("WIDGETS_SECRETS_PROVIDER").unwrap_or_default();
+        let provider: Arc<dyn SecretsProvider> = match kind.trim() {
+            "" | "env" => Arc::new(EnvSecrets),
+            "file" => Arc::new(FileSecrets::new(
+                std::env::var("WIDGETS_SECRETS_DIR").unwrap_or_else(|_| "/run/secrets".into()),
+            )),
+            "vault" => match (std::env::var("VAULT_ADDR"), std::env::var("VAULT_TOKEN")) {
+                (Ok(address), Ok(token)) => match VaultSecrets::new(
+                    address,
+                    Secret::new(token),
+                    std::env::var("WIDGETS_VAULT_MOUNT").unwrap_or_else(|_| "secret".into()),
+                    std::env::var("WIDGETS_VAULT_PATH").unwrap_or_else(|_| "pizzaz".into()),
+                ) {
+                    Ok(vault) => Arc::new(vault),
+                    Err(error) => {
+                        tracing::error!(error = %error, "Refusing to send VAULT_TOKEN; using env");
+                        Arc::new(EnvSecrets)
+                    }
+                },
+                _ => {
+                    tracing::warn!(
+                        "WIDGETS_SECRETS_PROVIDER=vault needs VAULT_ADDR and VAULT_TOKEN; using env"
+                    );
+                    Arc::new(EnvSecrets)
+                }
+            },
+            other => {
+                tracing::warn!(provider = %other, "Unknown WIDGETS_SECRETS_PROVIDER; using env");
+                Arc::new(EnvSecrets)
+            }
+        };
+        Self::new(provider, ttl)
+    }
+
+    pub fn settings(&self) -> SecretsSettings {
+        SecretsSettings {
+            provider: self.provider.kind(),
+            ttl_secs: self.ttl.as_secs(),
+        }
+    }
+
+    /// The secret called `name`, from the cache while it is fresh.
+    pub async fn get(&self, name: &str) -> Result<Secret, SecretsError> {
+        if !valid_name(name) {
+            return Err(SecretsError::InvalidName(name.to_string()));
+        }
+        if let Some((secret, fetched)) = self.lock().get(name) {
+            if fetched.elapsed() < self.ttl {
+                return Ok(secret.clone());
+            }
+        }
+
+        let secret = self.provider.fetch(name).await.inspect_err(|error| {
+            tracing::warn!(secret = %name, provider = self.provider.kind(), error = %error, "Secret lookup failed");
+        })?;
+        self.lock()
+            .insert(name.to_string(), (secret.clone(), Instant::now()));
+        Ok(secret)
+    }
+
+    /// Forgets the cached value of `name`, so the next lookup reads it from the provider.
+    pub fn invalidate(&self, name: &str) {
+        self.lock().remove(name);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, (Secret, Instant)>> {
+        self.cache.lock().expect("secrets cache lock poisoned")
+    }
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        && Path::new(name).components().count() == 1
+}
+
+static SECRETS: LazyLock<RwLock<Secrets>> = LazyLock::new(|| RwLock::new(Secrets::from_env()));
+
+/// Replaces the provider executors read secrets from, with a fresh cache.
+pub fn set_provider(provider: Arc<dyn SecretsProvider>) {
+    let mut secrets = SECRETS.write().expect("secrets lock poisoned");
+    *secrets = Secrets::new(provider, secrets.ttl);
+}
+
+/// The configured provider and cache.
+pub fn secrets() -> Secrets {
+    SECRETS.read().expect("secrets lock poisoned").clone()
+}
+
+/// Looks up `name` with the configured provider.
+pub async fn get(name: &str) -> Result<Secret, SecretsError> {
+    secrets().get(name).await
+}
+
+/// Drops the cached value of `name`; see [`Secrets::invalidate`].
+pub fn invalidate(name: &str) {
+    secrets().invalidate(name);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::egress::{EgressResponse, EgressTransport};
+
+    #[tokio::test]
+    async fn cached_secrets_refresh_after_the_ttl() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("maps-api-key"), "first\n").unwrap();
+        let secrets = Secrets::new(
+            Arc::new(FileSecrets::new(dir.path())),
+            Duration::from_millis(50),
+        );
+
+        assert_eq!(secrets.get("maps-api-key").await.unwrap().expose(), "first");
+        std::fs::write(dir.path().join("maps-api-key"), "second\n").unwrap();
+        assert_eq!(secrets.get("maps-api-key").await.unwrap().expose(), "first");
+        secrets.invalidate("maps-api-key");
+        assert_eq!(
+            secrets.get("maps-api-key").await.unwrap().expose(),
+            "second"
+        );
+
+        std::fs::write(dir.path().join("maps-api-key"), "third").unwrap();
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(secrets.get("maps-api-key").await.unwrap().expose(), "third");
+
+        assert!(matches!(
+            secrets.get("../etc/passwd").await,
+            Err(SecretsError::InvalidName(_))
+        ));
+        assert!(matches!(
+            secrets.get("missing").await,
+            Err(SecretsError::NotFound(_))
+        ));
+        assert_eq!(
+            format!("{:?}", Secret::new("hunter2")),
+            "Secret([redacted])"
+        );
+    }
+
+    #[tokio::test]
+    async fn vault_reads_the_kv_v2_value_field() {
+        struct Vault;
+
+        impl EgressTransport for Vault {
+            fn send<'a>(
+                &'a self,
+                request: &'a EgressRequest,
+                _max_body_bytes: u64,
+            ) -> BoxFuture<'a, std::io::Result<EgressResponse>> {
+                Box::pin(async move {
+                    let authorized = request
+                        .headers
+                        .contains(&("X-Vault-Token", "root-token".to_string()));
+                    let (status, body) = match request.url.as_str() {
+                        "https://vault.internal:8200/v1/secret/data/pizzaz/maps-api-key"
+                            if authorized =>
+                        {
+                            (200, r#"{"data":{"data":{"value":"abc123"},"metadata":{}}}"#)
+                        }
+                        _ if !authorized => (403, "{}"),
+                        _ => (404, "{}"),
+                    };
+                    Ok(EgressResponse {
+                        status,
+                        body: body.as_bytes().to_vec(),
+                    })
+                })
+            }
+        }
+
+        assert!(matches!(
+            VaultSecrets::new(
+                "http://vault.internal:8200/",
+                Secret::new("root-token"),
+                "secret",
+                "pizzaz",
+            ),
+            Err(SecretsError::InsecureVaultAddress(_))
+        ));
+
+        let vault = VaultSecrets::new(
+            "https://vault.internal:8200/",
+            Secret::new("root-token"),
+            "secret",
+            "pizzaz",
+        )
+        .unwrap();
+        let vault = vault.with_client(EgressClient::with_transport(
+            EgressPolicy::new(["vault.internal"]),
+            Arc::new(Vault),
+        ));
+        assert_eq!(
+            vault.fetch("maps-api-key").await.unwrap().expose(),
+            "abc123"
+        );
+        assert!(matches!(
+            vault.fetch("other").await,
+            Err(SecretsError::NotFound(_))
+        ));
+    }
+}
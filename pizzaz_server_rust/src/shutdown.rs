@@ -0,0 +1,250 @@
+//! The report logged when the server shuts down.
+//!
+//! On SIGTERM or Ctrl+C the app starts draining, the listeners stop accepting connections and open
+//! ones and [deferred calls](crate::jobs) get
+//! `MCP_SHUTDOWN_GRACE_SECONDS` (default 20) to finish; whatever is still running then is
+//! aborted. A summary is then logged at `info`: why the server stopped, uptime, sessions,
+//! requests and deferred calls at the start of shutdown, how many sessions closed and requests
+//! and deferred calls were cut off, and the tool calls served. With `MCP_SHUTDOWN_REPORT_URL` set it is also POSTed there as a
+//! `server.shutdown` event, in the same format and with the same signature as the registry
+//! [`webhooks`](crate::webhooks), so incident timelines record when and how pods were cycled.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use rmcp::transport::streamable_http_server::session::local::LocalSessionManager;
+use serde::Serialize;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{
+    handler::PizzazServerHandler,
+    lifecycle::Lifecycle,
+    load_shed::LoadMonitor,
+    webhooks::{WebhookConfig, Webhooks, SERVER_SHUTDOWN},
+};
+
+const DEFAULT_GRACE: Duration = Duration::from_secs(20);
+
+/// How long to wait for connections, and where to send the report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShutdownConfig {
+    pub grace: Duration,
+    pub report_url: Option<String>,
+}
+
+impl ShutdownConfig {
+    /// Reads `MCP_SHUTDOWN_GRACE_SECONDS` (invalid values fall back to 20) and
+    /// `MCP_SHUTDOWN_REPORT_URL`.
+    pub fn from_env() -> Self {
+        let grace = match std::env::var("MCP_SHUTDOWN_GRACE_SECONDS") {
+            Ok(raw) => raw
+                .trim()
+                .parse()
+                .map(Duration::from_secs)
+                .unwrap_or_else(|_| {
+                    tracing::warn!(value = %raw, "Ignoring invalid MCP_SHUTDOWN_GRACE_SECONDS");
+                    DEFAULT_GRACE
+                }),
+            Err(_) => DEFAULT_GRACE,
+        };
+        Self {
+            grace,
+            report_url: std::env::var("MCP_SHUTDOWN_REPORT_URL")
+                .ok()
+                .map(|url| url.trim().to_string())
+                .filter(|url| !url.is_empty()),
+        }
+    }
+}
+
+/// Live counters of one app, for the shutdown report.
+#[derive(Clone)]
+pub struct ServerStats {
+    pub(crate) load: Arc<LoadMonitor>,
+    pub(crate) sessions: Arc<LocalSessionManager>,
+    pub(crate) handler: PizzazServerHandler,
+    pub(crate) lifecycle: Arc<Lifecycle>,
+    pub(crate) started: Instant,
+    pub(crate) started_at: OffsetDateTime,
+}
+
+/// The counters at one moment.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StatsSnapshot {
+    pub sessions: usize,
+    pub in_flight_requests: usize,
+    pub pending_jobs: usize,
+    pub tool_calls: u64,
+}
+
+impl ServerStats {
+    pub async fn snapshot(&self) -> StatsSnapshot {
+        StatsSnapshot {
+            sessions: self.sessions.sessions.read().await.len(),
+            in_flight_requests: self.load.in_flight(),
+            pending_jobs: self.handler.jobs().pending(),
+            tool_calls: self.handler.tool_calls(),
+        }
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Waits for the deferred calls running now to finish.
+    pub async fn wait_for_jobs(&self) {
+        self.handler.jobs().wait_for_pending().await;
+    }
+
+    /// Aborts the deferred calls still running; returns how many there were.
+    pub fn abort_jobs(&self) -> usize {
+        self.handler.jobs().abort_pending()
+    }
+
+    /// Fails `/readyz` and refuses new sessions for the rest of the shutdown, as
+    /// `POST /internal/drain` does; returns false if the app was already draining.
+    pub fn start_draining(&self) -> bool {
+        self.lifecycle.start_draining()
+    }
+}
+
+/// What happened during a shutdown.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ShutdownReport {
+    /// `SIGTERM`, or `SIGINT` for Ctrl+C.
+    pub reason: String,
+    pub started_at: String,
+    pub uptime_seconds: u64,
+    /// MCP sessions open when shutdown began.
+    pub sessions_at_shutdown: usize,
+    /// Of those, the sessions their clients closed before the server stopped.
+    pub sessions_drained: usize,
+    /// MCP POST requests, including their response streams, in flight when shutdown began.
+    pub requests_in_flight: usize,
+    /// Requests still running when the grace period ran out.
+    pub requests_aborted: usize,
+    /// Deferred calls still running when shutdown began.
+    pub jobs_pending: usize,
+    /// Deferred calls still running when the grace period ran out.
+    pub jobs_aborted: usize,
+    /// `tools/call` requests served since startup.
+    pub tool_calls: u64,
+    pub grace_period_seconds: u64,
+    /// Whether the grace period ran out before every connection finished.
+    pub grace_period_exceeded: bool,
+}
+
+impl ShutdownReport {
+    /// Compares the counters from when shutdown began with those from when it ended.
+    pub fn new(
+        reason: &str,
+        stats: &ServerStats,
+        begin: StatsSnapshot,
+        end: StatsSnapshot,
+        grace: Duration,
+        grace_period_exceeded: bool,
+    ) -> Self {
+        Self {
+            reason: reason.to_string(),
+            started_at: stats.started_at.format(&Rfc3339).unwrap_or_default(),
+            uptime_seconds: stats.uptime().as_secs(),
+            sessions_at_shutdown: begin.sessions,
+            sessions_drained: begin.sessions.saturating_sub(end.sessions),
+            requests_in_flight: begin.in_flight_requests,
+            requests_aborted: if grace_period_exceeded {
+                end.in_flight_requests
+            } else {
+                0
+            },
+            jobs_pending: begin.pending_jobs,
+            jobs_aborted: if grace_period_exceeded {
+                end.pending_jobs
+            } else {
+                0
+            },
+            tool_calls: end.tool_calls,
+            grace_period_seconds: grace.as_secs(),
+            grace_period_exceeded,
+        }
+    }
+
+    /// Logs the report and, when a report URL is configured, delivers it before returning.
+    pub async fn publish(&self, config: &ShutdownConfig) {
+        tracing::info!(
+            reason = %self.reason,
+            uptime_seconds = self.uptime_seconds,
+            sessions_at_shutdown = self.sessions_at_shutdown,
+            sessions_drained = self.sessions_drained,
+            requests_in_flight = self.requests_in_flight,
+            requests_aborted = self.requests_aborted,
+            jobs_pending = self.jobs_pending,
+            jobs_aborted = self.jobs_aborted,
+            tool_calls = self.tool_calls,
+            grace_period_exceeded = self.grace_period_exceeded,
+            "Shutdown report"
+        );
+        let Some(url) = &config.report_url else {
+            return;
+        };
+        // Few, short attempts: the orchestrator will kill the process soon.
+        let webhooks = Webhooks::new(WebhookConfig {
+            urls: vec![url.clone()],
+            secret: std::env::var("WIDGETS_WEBHOOK_SECRET")
+                .ok()
+                .filter(|secret| !secret.is_empty()),
+            max_attempts: 2,
+            retry_delay: Duration::from_millis(500),
+            timeout: Duration::from_secs(3),
+        });
+        let data = serde_json::to_value(self).unwrap_or_default();
+        webhooks.deliver(SERVER_SHUTDOWN, &data).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_counts_drained_sessions_and_aborts_only_after_the_grace_period() {
+        let stats = ServerStats {
+            load: Arc::new(LoadMonitor::new(
+                crate::load_shed::LoadShedConfig::from_env(),
+            )),
+            sessions: Arc::new(LocalSessionManager::default()),
+            handler: PizzazServerHandler::new(),
+            lifecycle: Arc::default(),
+            started: Instant::now(),
+            started_at: OffsetDateTime::UNIX_EPOCH,
+        };
+        let begin = StatsSnapshot {
+            sessions: 5,
+            in_flight_requests: 3,
+            pending_jobs: 2,
+            tool_calls: 40,
+        };
+        let end = StatsSnapshot {
+            sessions: 2,
+            in_flight_requests: 1,
+            pending_jobs: 1,
+            tool_calls: 41,
+        };
+
+        let report = ShutdownReport::new("SIGTERM", &stats, begin, end, DEFAULT_GRACE, true);
+        assert_eq!(report.started_at, "1970-01-01T00:00:00Z");
+        assert_eq!(report.sessions_at_shutdown, 5);
+        assert_eq!(report.sessions_drained, 3);
+        assert_eq!(report.requests_in_flight, 3);
+        assert_eq!(report.requests_aborted, 1);
+        assert_eq!(report.jobs_pending, 2);
+        assert_eq!(report.jobs_aborted, 1);
+        assert_eq!(report.tool_calls, 41);
+        assert_eq!(report.grace_period_seconds, 20);
+
+        let report = ShutdownReport::new("SIGTERM", &stats, begin, end, DEFAULT_GRACE, false);
+        assert_eq!(report.requests_aborted, 0);
+        assert_eq!(report.jobs_aborted, 0);
+    }
+}
@@ -0,0 +1,218 @@
+//! Ed25519 signatures over widget HTML, published in resource `_meta`.
+//!
+//! With `WIDGETS_SIGNING_KEY=<key id>=<hex seed>` set, every widget is signed when it loads and
+//! its resource, template and `resources/read` entries carry `_meta["pizzaz/signature"]`:
+//!
+//! ```json
+//! {"algorithm": "ed25519", "keyId": "publisher-2025", "value": "<hex>"}
+//! ```
+//!
+//! `value` is the Ed25519 signature of the exact HTML text served. Only the server holds the
+//! private key; gateways and clients verify with the public key from
+//! `GET /.well-known/pizzaz-signing-key`, so they can check what they render is what the publisher
+//! shipped without being able to sign anything themselves. The key id lets verifiers pick the
+//! right public key while it is rotated.
+
+use std::sync::LazyLock;
+
+use ed25519_dalek::{Signer, Verifier};
+use serde::Serialize;
+
+use crate::widgets::hex;
+
+/// Resource `_meta` key carrying the widget HTML signature.
+pub const SIGNATURE_META_KEY: &str = "pizzaz/signature";
+
+pub const ALGORITHM: &str = "ed25519";
+
+/// The private key widget HTML is signed with.
+#[derive(Clone)]
+pub struct SigningKey {
+    pub key_id: String,
+    key: ed25519_dalek::SigningKey,
+}
+
+impl std::fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SigningKey")
+            .field("key_id", &self.key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The public half of a [`SigningKey`], safe to hand to anyone who verifies widget HTML.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicKey {
+    pub algorithm: &'static str,
+    pub key_id: String,
+    /// Lowercase hex of the 32-byte Ed25519 public key.
+    #[serde(serialize_with = "serialize_verifying_key")]
+    pub public_key: ed25519_dalek::VerifyingKey,
+}
+
+/// Signature metadata attached to a widget's resources.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Signature {
+    pub algorithm: &'static str,
+    pub key_id: String,
+    /// Lowercase hex of the 64-byte Ed25519 signature over the HTML.
+    pub value: String,
+}
+
+impl SigningKey {
+    /// A key from its 32-byte Ed25519 seed.
+    pub fn new(key_id: impl Into<String>, seed: [u8; 32]) -> Self {
+        Self {
+            key_id: key_id.into(),
+            key: ed25519_dalek::SigningKey::from_bytes(&seed),
+        }
+    }
+
+    /// Parses `<key id>=<hex seed>`; `None` when the id is empty or the seed is not 32 hex bytes.
+    pub fn parse(raw: &str) -> Option<Self> {
+        let (key_id, seed) = raw.split_once('=')?;
+        let key_id = key_id.trim();
+        if key_id.is_empty() {
+            return None;
+        }
+        let seed = decode_hex(seed.trim())?.try_into().ok()?;
+        Some(Self::new(key_id, seed))
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey {
+            algorithm: ALGORITHM,
+            key_id: self.key_id.clone(),
+            public_key: self.key.verifying_key(),
+        }
+    }
+
+    pub fn sign(&self, html: &str) -> Signature {
+        Signature {
+            algorithm: ALGORITHM,
+            key_id: self.key_id.clone(),
+            value: hex(&self.key.sign(html.as_bytes()).to_bytes()),
+        }
+    }
+}
+
+impl PublicKey {
+    /// True when `signature` was made over `html` by the private half of this key.
+    pub fn verify(&self, html: &str, signature: &Signature) -> bool {
+        if signature.algorithm != ALGORITHM || signature.key_id != self.key_id {
+            return false;
+        }
+        let Some(bytes) = decode_hex(&signature.value).and_then(|bytes| bytes.try_into().ok())
+        else {
+            return false;
+        };
+        self.public_key
+            .verify(
+                html.as_bytes(),
+                &ed25519_dalek::Signature::from_bytes(&bytes),
+            )
+            .is_ok()
+    }
+}
+
+static SIGNING_KEY: LazyLock<Option<SigningKey>> = LazyLock::new(|| {
+    let raw = std::env::var("WIDGETS_SIGNING_KEY").ok()?;
+    let key = SigningKey::parse(&raw);
+    if key.is_none() {
+        tracing::warn!(
+            "Ignoring WIDGETS_SIGNING_KEY; expected <key id>=<64 hex character Ed25519 seed>"
+        );
+    }
+    key
+});
+
+/// The configured key's public half, for the config and well-known endpoints.
+pub fn public_key() -> Option<PublicKey> {
+    SIGNING_KEY.as_ref().map(SigningKey::public_key)
+}
+
+/// Signs `html` with the configured key, if any.
+pub(crate) fn sign(html: &str) -> Option<Signature> {
+    SIGNING_KEY.as_ref().map(|key| key.sign(html))
+}
+
+/// `GET /.well-known/pizzaz-signing-key`: the public key verifiers check signatures with.
+pub(crate) async fn public_key_handler() -> axum::response::Response {
+    use axum::response::IntoResponse;
+
+    match public_key() {
+        Some(key) => axum::Json(key).into_response(),
+        None => crate::error::ApiError::new(
+            axum::http::StatusCode::NOT_FOUND,
+            "signing_disabled",
+            "widget HTML signing is not configured",
+        )
+        .into_response(),
+    }
+}
+
+fn serialize_verifying_key<S: serde::Serializer>(
+    key: &ed25519_dalek::VerifyingKey,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&hex(key.as_bytes()))
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|at| u8::from_str_radix(&text[at..at + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: &str = "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60";
+
+    #[test]
+    fn signatures_verify_only_for_the_signed_html() {
+        let key = SigningKey::parse(&format!("publisher-2025={SEED}")).unwrap();
+        let public = key.public_key();
+        let signature = key.sign("<div id=\"map\"></div>");
+        assert_eq!(signature.key_id, "publisher-2025");
+        assert_eq!(signature.value.len(), 128);
+        assert!(public.verify("<div id=\"map\"></div>", &signature));
+        assert!(!public.verify("<div id=\"map\"></div><script>", &signature));
+        assert!(!SigningKey::new("publisher-2025", [7; 32])
+            .public_key()
+            .verify("<div id=\"map\"></div>", &signature));
+
+        assert_eq!(
+            serde_json::to_value(&signature).unwrap()["keyId"],
+            "publisher-2025"
+        );
+        assert!(SigningKey::parse(&format!("={SEED}")).is_none());
+        assert!(SigningKey::parse("publisher-2025=s3cret").is_none());
+    }
+
+    #[test]
+    fn public_key_matches_rfc_8032() {
+        // RFC 8032 section 7.1, test 1.
+        let key = SigningKey::parse(&format!("k={SEED}")).unwrap();
+        assert_eq!(
+            serde_json::to_value(key.public_key()).unwrap(),
+            serde_json::json!({
+                "algorithm": "ed25519",
+                "keyId": "k",
+                "publicKey": "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+            })
+        );
+        assert_eq!(
+            key.sign("").value,
+            "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e06522490155\
+             5fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b"
+        );
+    }
+}
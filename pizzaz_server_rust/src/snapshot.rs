@@ -0,0 +1,161 @@
+//! Registry snapshots for cold starts.
+//!
+//! With `WIDGETS_REGISTRY_SNAPSHOT_PATH` set, every successful load writes the registry as served
+//! to that file: the manifest with HTML inlined and overlays and `${VAR}` references resolved,
+//! plus every local asset it references. When the manifest source is unreachable at startup and
+//! nothing from it is serving yet, the snapshot is loaded instead and `GET /internal/widgets/status`
+//! reports why under `degraded`. The next successful load replaces it.
+//!
+//! Because `${VAR}` references are resolved, the file can hold the values of environment
+//! variables, secrets included. It is created readable by its owner only (mode `0600` on Unix).
+//!
+//! Unlike `WIDGETS_MANIFEST_CACHE_PATH`, which keeps the bucket objects of an object-storage
+//! manifest, a snapshot works for any manifest source and needs nothing but the file to load.
+
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use time::{format_description::well_known::Iso8601, OffsetDateTime};
+
+use crate::{widgets::LoadError, widgets_manifest::WidgetManifest};
+
+/// Where the registry snapshot is kept, from `WIDGETS_REGISTRY_SNAPSHOT_PATH`, if set.
+///
+/// Like the overlay path, the variable is read on every load.
+pub fn snapshot_path() -> Option<PathBuf> {
+    std::env::var("WIDGETS_REGISTRY_SNAPSHOT_PATH")
+        .ok()
+        .filter(|path| !path.trim().is_empty())
+        .map(PathBuf::from)
+}
+
+/// A registry as written to `WIDGETS_REGISTRY_SNAPSHOT_PATH`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RegistrySnapshot {
+    /// The manifest path the registry was loaded from; a snapshot only stands in for that one.
+    pub manifest_path: String,
+    /// ISO 8601 time the snapshot was written.
+    pub saved_at: String,
+    pub manifest: WidgetManifest,
+    /// Contents of the local assets `manifest` references, keyed by reference.
+    pub assets: HashMap<String, String>,
+}
+
+/// Replaces the snapshot at `file` with `manifest` and `assets`, loaded from `manifest_path`.
+///
+/// The file is written next to `file` and renamed over it, so a crash never leaves half a snapshot.
+/// Both are created with mode `0600`. Blocking; call it off the async executor.
+pub(crate) fn store(
+    file: &Path,
+    manifest_path: &Path,
+    manifest: WidgetManifest,
+    assets: HashMap<String, String>,
+) -> anyhow::Result<()> {
+    let snapshot = RegistrySnapshot {
+        manifest_path: manifest_path.display().to_string(),
+        saved_at: OffsetDateTime::now_utc().format(&Iso8601::DEFAULT)?,
+        manifest,
+        assets,
+    };
+    let staging = file.with_extension("tmp");
+    write_private(&staging, &serde_json::to_vec(&snapshot)?)
+        .with_context(|| format!("Failed to write {}", staging.display()))?;
+    std::fs::rename(&staging, file).with_context(|| format!("Failed to replace {}", file.display()))
+}
+
+/// Writes `contents` to a new file at `path` that only its owner can read.
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    // A leftover from an interrupted write may have wider permissions; start from scratch.
+    match std::fs::remove_file(path) {
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => return Err(error),
+        _ => {}
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents)
+}
+
+/// The snapshot at `file`, when it was taken of `manifest_path`.
+pub(crate) fn load(file: &Path, manifest_path: &Path) -> anyhow::Result<RegistrySnapshot> {
+    let contents =
+        std::fs::read(file).with_context(|| format!("Failed to read {}", file.display()))?;
+    // Read as written: the manifest was already resolved, so `${VAR}` is not expanded again.
+    let snapshot: RegistrySnapshot = serde_json::from_slice(&contents)
+        .with_context(|| format!("{} is not a registry snapshot", file.display()))?;
+    let expected = manifest_path.display().to_string();
+    if snapshot.manifest_path != expected {
+        return Err(anyhow!(
+            "{} holds {}, not {expected}",
+            file.display(),
+            snapshot.manifest_path
+        ));
+    }
+    Ok(snapshot)
+}
+
+/// Whether `error` means the manifest source could not be read, rather than that it was read and
+/// found invalid. Only then may a snapshot stand in.
+pub(crate) fn is_unreachable(error: &LoadError) -> bool {
+    #[cfg(feature = "object-storage")]
+    if crate::object_storage::is_unavailable(error) {
+        return true;
+    }
+    match error {
+        LoadError::NotFound { .. } => true,
+        LoadError::Validation { error, .. } => error
+            .chain()
+            .any(|cause| cause.downcast_ref::<std::io::Error>().is_some()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshots_only_stand_in_for_their_manifest_and_unreachable_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("snapshot.json");
+        let manifest: WidgetManifest = serde_json::from_value(serde_json::json!({
+            "schemaVersion": "1.0.0",
+            "widgets": [],
+        }))
+        .unwrap();
+        let assets = HashMap::from([("app.css".to_string(), "body {}".to_string())]);
+        store(&file, Path::new("/srv/widgets.json"), manifest, assets).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&file).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let snapshot = load(&file, Path::new("/srv/widgets.json")).unwrap();
+        assert_eq!(snapshot.assets["app.css"], "body {}");
+        assert!(load(&file, Path::new("/srv/other.json")).is_err());
+
+        let missing = LoadError::NotFound {
+            path: PathBuf::from("/srv/widgets.json"),
+        };
+        let unreadable = LoadError::Validation {
+            path: PathBuf::from("/srv/widgets.json"),
+            error: anyhow::Error::from(std::io::Error::from(std::io::ErrorKind::TimedOut))
+                .context("Failed to read widget manifest"),
+        };
+        let invalid = LoadError::Validation {
+            path: PathBuf::from("/srv/widgets.json"),
+            error: anyhow!("Widget entry missing templateUri for pizza-map"),
+        };
+        assert!(is_unreachable(&missing));
+        assert!(is_unreachable(&unreadable));
+        assert!(!is_unreachable(&invalid));
+    }
+}
@@ -0,0 +1,533 @@
+//! Bounded per-connection send queues for SSE responses.
+//!
+//! Frames from the MCP transport are pulled eagerly into a queue that the client drains at its
+//! own pace. When a slow client lets the queue fill up, the configured policy either drops the
+//! oldest non-critical event (notifications, keep-alives) or closes the stream with a hint
+//! telling the client where to resume.
+//!
+//! A client that disconnects, or leaves queued events unread for `MCP_SSE_STALL_TIMEOUT_SECS`,
+//! cancels the stream's [`CancellationToken`]. The transport stream is dropped at once instead of
+//! being drained into a dead socket, and tool calls made by the request stop with it.
+
+use std::{
+    collections::VecDeque,
+    convert::Infallible,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use async_stream::stream;
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use http_body::Frame;
+use serde::Serialize;
+use serde_json::Value;
+use tokio::{sync::Notify, time::Instant};
+use tokio_util::sync::CancellationToken;
+
+const DEFAULT_QUEUE_LIMIT: usize = 64;
+const DEFAULT_STALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What to do when a connection's send queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackpressurePolicy {
+    /// Drop the oldest queued event that is not a JSON-RPC response or request.
+    DropOldest,
+    /// Stop the stream and tell the client which event to resume after.
+    Close,
+}
+
+/// Send queue settings read from `MCP_SSE_QUEUE_LIMIT`, `MCP_SSE_BACKPRESSURE_POLICY` and
+/// `MCP_SSE_STALL_TIMEOUT_SECS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackpressureConfig {
+    pub queue_limit: usize,
+    pub policy: BackpressurePolicy,
+    /// How long queued events may wait for the client to read before it is treated as gone.
+    /// `None` waits forever.
+    pub stall_timeout: Option<Duration>,
+}
+
+impl Default for BackpressureConfig {
+    fn default() -> Self {
+        Self {
+            queue_limit: DEFAULT_QUEUE_LIMIT,
+            policy: BackpressurePolicy::DropOldest,
+            stall_timeout: Some(DEFAULT_STALL_TIMEOUT),
+        }
+    }
+}
+
+impl BackpressureConfig {
+    pub(crate) fn from_env() -> Self {
+        let default = Self::default();
+        let queue_limit = std::env::var("MCP_SSE_QUEUE_LIMIT")
+            .ok()
+            .and_then(|value| value.trim().parse::<usize>().ok())
+            .filter(|value| *value > 0)
+            .unwrap_or(default.queue_limit);
+        let policy = match std::env::var("MCP_SSE_BACKPRESSURE_POLICY") {
+            Ok(value) => match value.trim().to_ascii_lowercase().as_str() {
+                "drop-oldest" => BackpressurePolicy::DropOldest,
+                "close" => BackpressurePolicy::Close,
+                other => {
+                    tracing::warn!(
+                        "Invalid MCP_SSE_BACKPRESSURE_POLICY value '{other}'; using drop-oldest"
+                    );
+                    default.policy
+                }
+            },
+            Err(_) => default.policy,
+        };
+        let stall_timeout = match std::env::var("MCP_SSE_STALL_TIMEOUT_SECS") {
+            Ok(value) => match value.trim().parse::<u64>() {
+                Ok(0) => None,
+                Ok(seconds) => Some(Duration::from_secs(seconds)),
+                Err(_) => {
+                    tracing::warn!(
+                        "Invalid MCP_SSE_STALL_TIMEOUT_SECS value '{value}'; using {}",
+                        DEFAULT_STALL_TIMEOUT.as_secs()
+                    );
+                    default.stall_timeout
+                }
+            },
+            Err(_) => default.stall_timeout,
+        };
+
+        Self {
+            queue_limit,
+            policy,
+            stall_timeout,
+        }
+    }
+}
+
+/// Counters for backpressure events across all connections.
+#[derive(Debug, Default)]
+pub struct BackpressureMetrics {
+    dropped_events: AtomicU64,
+    closed_streams: AtomicU64,
+    stalled_streams: AtomicU64,
+    disconnected_streams: AtomicU64,
+    open_streams: AtomicUsize,
+}
+
+/// Point-in-time copy of [`BackpressureMetrics`].
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub struct BackpressureSnapshot {
+    pub dropped_events: u64,
+    pub closed_streams: u64,
+    /// Streams cancelled because the client left events unread past the stall timeout.
+    pub stalled_streams: u64,
+    /// Streams cancelled because the client went away before the transport finished.
+    pub disconnected_streams: u64,
+}
+
+impl BackpressureMetrics {
+    pub fn snapshot(&self) -> BackpressureSnapshot {
+        BackpressureSnapshot {
+            dropped_events: self.dropped_events.load(Ordering::Relaxed),
+            closed_streams: self.closed_streams.load(Ordering::Relaxed),
+            stalled_streams: self.stalled_streams.load(Ordering::Relaxed),
+            disconnected_streams: self.disconnected_streams.load(Ordering::Relaxed),
+        }
+    }
+
+    /// SSE response streams, standalone `GET /mcp` ones included, not yet finished or dropped.
+    pub fn open_streams(&self) -> usize {
+        self.open_streams.load(Ordering::Relaxed)
+    }
+}
+
+/// Counts one stream as open until dropped.
+struct OpenStream(Arc<BackpressureMetrics>);
+
+impl OpenStream {
+    fn new(metrics: Arc<BackpressureMetrics>) -> Self {
+        metrics.open_streams.fetch_add(1, Ordering::Relaxed);
+        Self(metrics)
+    }
+}
+
+impl Drop for OpenStream {
+    fn drop(&mut self) {
+        self.0.open_streams.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug)]
+struct QueuedEvent {
+    bytes: Bytes,
+    critical: bool,
+    event_id: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct SendQueue {
+    events: VecDeque<QueuedEvent>,
+    finished: bool,
+    last_delivered_id: Option<String>,
+    /// When the client last took an event while others were queued; `None` when it is caught up.
+    waiting_since: Option<Instant>,
+}
+
+/// Outcome of offering one event to a full or non-full queue.
+#[derive(Debug, PartialEq, Eq)]
+enum Offer {
+    Queued,
+    DroppedOldest,
+    Closed,
+}
+
+impl SendQueue {
+    fn offer(&mut self, event: QueuedEvent, config: BackpressureConfig) -> Offer {
+        self.waiting_since.get_or_insert_with(Instant::now);
+        if self.events.len() < config.queue_limit {
+            self.events.push_back(event);
+            return Offer::Queued;
+        }
+
+        if config.policy == BackpressurePolicy::DropOldest {
+            if let Some(index) = self.events.iter().position(|queued| !queued.critical) {
+                self.events.remove(index);
+                self.events.push_back(event);
+                return Offer::DroppedOldest;
+            }
+            if !event.critical {
+                return Offer::DroppedOldest;
+            }
+        }
+
+        // Either the policy says close, or every queued event must be delivered in order.
+        self.events.push_back(resume_hint(self.resume_point()));
+        self.finished = true;
+        Offer::Closed
+    }
+
+    /// The last event id the client will have seen once everything queued is delivered.
+    fn resume_point(&self) -> Option<String> {
+        self.events
+            .iter()
+            .rev()
+            .find_map(|queued| queued.event_id.clone())
+            .or_else(|| self.last_delivered_id.clone())
+    }
+
+    fn take(&mut self) -> Option<QueuedEvent> {
+        let event = self.events.pop_front()?;
+        if event.event_id.is_some() {
+            self.last_delivered_id = event.event_id.clone();
+        }
+        self.waiting_since = (!self.events.is_empty()).then(Instant::now);
+        Some(event)
+    }
+}
+
+/// Resolves once the client has left `queue` unread for `timeout`.
+async fn stall(queue: &Mutex<SendQueue>, timeout: Duration) {
+    loop {
+        let since = queue.lock().expect("sse queue lock poisoned").waiting_since;
+        match since {
+            Some(since) if since.elapsed() >= timeout => return,
+            Some(since) => tokio::time::sleep_until(since + timeout).await,
+            None => tokio::time::sleep(timeout).await,
+        }
+    }
+}
+
+/// Re-exposes `inner` through a bounded queue governed by `config`.
+///
+/// `client_gone` is cancelled when the returned stream is dropped before `inner` finished, or when
+/// the client stalls; either way `inner` is dropped without being read further.
+pub(crate) fn bounded<S>(
+    inner: S,
+    config: BackpressureConfig,
+    metrics: Arc<BackpressureMetrics>,
+    client_gone: CancellationToken,
+) -> impl Stream<Item = Result<Frame<Bytes>, Infallible>> + Send
+where
+    S: Stream<Item = Result<Frame<Bytes>, Infallible>> + Send + 'static,
+{
+    let open = OpenStream::new(Arc::clone(&metrics));
+    let queue = Arc::new(Mutex::new(SendQueue::default()));
+    let ready = Arc::new(Notify::new());
+    let pump_done = CancellationToken::new();
+
+    let pump_queue = Arc::clone(&queue);
+    let pump_ready = Arc::clone(&ready);
+    let pump_gone = client_gone.clone();
+    let pump_finished = pump_done.clone();
+    tokio::spawn(async move {
+        let _finished = pump_finished.drop_guard();
+        let mut inner = std::pin::pin!(inner);
+        let stall = async {
+            match config.stall_timeout {
+                Some(timeout) => stall(&pump_queue, timeout).await,
+                None => std::future::pending().await,
+            }
+        };
+        let mut stall = std::pin::pin!(stall);
+        loop {
+            let frame = tokio::select! {
+                frame = inner.next() => frame,
+                () = pump_gone.cancelled() => {
+                    metrics.disconnected_streams.fetch_add(1, Ordering::Relaxed);
+                    tracing::debug!("SSE client went away; cancelling the transport stream");
+                    return;
+                }
+                () = &mut stall => {
+                    metrics.stalled_streams.fetch_add(1, Ordering::Relaxed);
+                    tracing::warn!("SSE client stopped reading; cancelling the transport stream");
+                    let mut queue = pump_queue.lock().expect("sse queue lock poisoned");
+                    queue.events.clear();
+                    queue.finished = true;
+                    drop(queue);
+                    pump_gone.cancel();
+                    pump_ready.notify_one();
+                    return;
+                }
+            };
+            let Some(Ok(frame)) = frame else {
+                break;
+            };
+            let Ok(bytes) = frame.into_data() else {
+                continue;
+            };
+            let event = classify(bytes);
+            let offer = pump_queue
+                .lock()
+                .expect("sse queue lock poisoned")
+                .offer(event, config);
+            match offer {
+                Offer::Queued => {}
+                Offer::DroppedOldest => {
+                    metrics.dropped_events.fetch_add(1, Ordering::Relaxed);
+                    tracing::debug!("SSE send queue full; dropped oldest non-critical event");
+                }
+                Offer::Closed => {
+                    metrics.closed_streams.fetch_add(1, Ordering::Relaxed);
+                    tracing::warn!("SSE send queue full; closing stream with resume hint");
+                    pump_ready.notify_one();
+                    return;
+                }
+            }
+            pump_ready.notify_one();
+        }
+        pump_queue.lock().expect("sse queue lock poisoned").finished = true;
+        pump_ready.notify_one();
+    });
+
+    stream! {
+        let _open = open;
+        // Tell the pump, and the request's tool calls, that the client went away.
+        let _gone = ClientGuard {
+            client_gone,
+            pump_done,
+        };
+        loop {
+            let next = {
+                let mut queue = queue.lock().expect("sse queue lock poisoned");
+                match queue.take() {
+                    Some(event) => Some(Some(event.bytes)),
+                    None if queue.finished => None,
+                    None => Some(None),
+                }
+            };
+            match next {
+                Some(Some(bytes)) => yield Ok(Frame::data(bytes)),
+                Some(None) => ready.notified().await,
+                None => break,
+            }
+        }
+    }
+}
+
+/// Cancels `client_gone` if the response body is dropped while the pump is still reading. Once
+/// `inner` has finished there is nothing left to cancel.
+struct ClientGuard {
+    client_gone: CancellationToken,
+    pump_done: CancellationToken,
+}
+
+impl Drop for ClientGuard {
+    fn drop(&mut self) {
+        if !self.pump_done.is_cancelled() {
+            self.client_gone.cancel();
+        }
+    }
+}
+
+/// JSON-RPC messages carrying an `id` (responses and server requests) must never be dropped.
+fn classify(bytes: Bytes) -> QueuedEvent {
+    let text = String::from_utf8_lossy(&bytes);
+    let mut event_id = None;
+    let mut critical = false;
+    for line in text.lines() {
+        if let Some(id) = line.strip_prefix("id:") {
+            event_id = Some(id.trim().to_string());
+        } else if let Some(data) = line.strip_prefix("data:") {
+            critical |= serde_json::from_str::<Value>(data.trim())
+                .map(|message| message.get("id").is_some_and(|id| !id.is_null()))
+                .unwrap_or(false);
+        }
+    }
+    QueuedEvent {
+        bytes,
+        critical,
+        event_id,
+    }
+}
+
+fn resume_hint(last_event_id: Option<String>) -> QueuedEvent {
+    let payload = serde_json::json!({
+        "reason": "send queue full",
+        "lastEventId": last_event_id,
+    });
+    QueuedEvent {
+        bytes: Bytes::from(format!(
+            "event: backpressure\ndata: {payload}\nretry: 1000\n\n"
+        )),
+        critical: true,
+        event_id: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(id: &str, message: Value) -> QueuedEvent {
+        classify(Bytes::from(format!("id: {id}\ndata: {message}\n\n")))
+    }
+
+    fn notification(id: &str) -> QueuedEvent {
+        event(
+            id,
+            serde_json::json!({"jsonrpc": "2.0", "method": "notifications/progress"}),
+        )
+    }
+
+    fn response(id: &str) -> QueuedEvent {
+        event(
+            id,
+            serde_json::json!({"jsonrpc": "2.0", "id": 1, "result": {}}),
+        )
+    }
+
+    #[test]
+    fn drop_oldest_keeps_critical_events() {
+        let config = BackpressureConfig {
+            queue_limit: 2,
+            policy: BackpressurePolicy::DropOldest,
+            ..BackpressureConfig::default()
+        };
+        let mut queue = SendQueue::default();
+        assert_eq!(queue.offer(response("1"), config), Offer::Queued);
+        assert_eq!(queue.offer(notification("2"), config), Offer::Queued);
+        assert_eq!(queue.offer(response("3"), config), Offer::DroppedOldest);
+
+        let ids: Vec<_> = queue
+            .events
+            .iter()
+            .map(|queued| queued.event_id.clone().unwrap())
+            .collect();
+        assert_eq!(ids, vec!["1", "3"]);
+
+        // Nothing left to drop: a critical event forces the stream closed.
+        assert_eq!(queue.offer(response("4"), config), Offer::Closed);
+        assert!(queue.finished);
+    }
+
+    #[test]
+    fn close_policy_appends_resume_hint() {
+        let config = BackpressureConfig {
+            queue_limit: 1,
+            policy: BackpressurePolicy::Close,
+            ..BackpressureConfig::default()
+        };
+        let mut queue = SendQueue::default();
+        queue.offer(notification("7"), config);
+        assert_eq!(queue.offer(notification("8"), config), Offer::Closed);
+
+        let hint = String::from_utf8_lossy(&queue.events.back().unwrap().bytes).into_owned();
+        assert!(hint.starts_with("event: backpressure"));
+        assert!(hint.contains("\"lastEventId\":\"7\""));
+    }
+
+    #[tokio::test]
+    async fn bounded_stream_delivers_all_frames_when_client_keeps_up() {
+        let frames: Vec<Result<Frame<Bytes>, Infallible>> = (0..3)
+            .map(|i| Ok(Frame::data(Bytes::from(format!("data: {i}\n\n")))))
+            .collect();
+        let metrics = Arc::new(BackpressureMetrics::default());
+        let delivered: Vec<_> = bounded(
+            futures::stream::iter(frames),
+            BackpressureConfig::default(),
+            Arc::clone(&metrics),
+            CancellationToken::new(),
+        )
+        .collect()
+        .await;
+
+        assert_eq!(delivered.len(), 3);
+        assert_eq!(metrics.snapshot().dropped_events, 0);
+    }
+
+    /// One event, then a transport that stays open, as while a tool call is still running. The
+    /// receiver errors once the transport stream has been dropped.
+    fn open_transport() -> (
+        impl Stream<Item = Result<Frame<Bytes>, Infallible>> + Send,
+        tokio::sync::oneshot::Receiver<()>,
+    ) {
+        let (alive, dropped) = tokio::sync::oneshot::channel::<()>();
+        let first = futures::stream::iter([Ok(Frame::data(Bytes::from_static(b"data: 1\n\n")))]);
+        let rest = futures::stream::pending().map(move |frame| {
+            let _ = &alive;
+            frame
+        });
+        (first.chain(rest), dropped)
+    }
+
+    #[tokio::test]
+    async fn dropping_the_response_cancels_the_transport() {
+        let (transport, dropped) = open_transport();
+        let metrics = Arc::new(BackpressureMetrics::default());
+        let client_gone = CancellationToken::new();
+        let mut stream = Box::pin(bounded(
+            transport,
+            BackpressureConfig::default(),
+            Arc::clone(&metrics),
+            client_gone.clone(),
+        ));
+        assert!(stream.next().await.is_some());
+        assert_eq!(metrics.open_streams(), 1);
+
+        drop(stream);
+        assert_eq!(metrics.open_streams(), 0);
+        assert!(client_gone.is_cancelled());
+        assert!(dropped.await.is_err());
+        assert_eq!(metrics.snapshot().disconnected_streams, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stalled_client_cancels_the_transport() {
+        let (transport, dropped) = open_transport();
+        let metrics = Arc::new(BackpressureMetrics::default());
+        let client_gone = CancellationToken::new();
+        let config = BackpressureConfig {
+            stall_timeout: Some(Duration::from_secs(5)),
+            ..BackpressureConfig::default()
+        };
+        // The client never reads the queued event.
+        let stream = bounded(transport, config, Arc::clone(&metrics), client_gone.clone());
+
+        client_gone.cancelled().await;
+        assert!(dropped.await.is_err());
+        assert_eq!(metrics.snapshot().stalled_streams, 1);
+        let delivered: Vec<_> = stream.collect().await;
+        assert!(delivered.is_empty());
+    }
+}
@@ -0,0 +1,354 @@
+//! Incremental rewriting of `/mcp` SSE response bodies.
+//!
+//! [`SseRewriter`] receives the body in whatever chunks the transport produces, buffers partial
+//! events, and emits each complete event once with widget `_meta` injected into its JSON `data:`
+//! lines. It is public only so property tests and the fuzz targets under `fuzz/` can drive it;
+//! it is not a supported API.
+//!
+//! Invariants, for any input:
+//!
+//! 1. Chunking does not matter. For UTF-8 input, the concatenated output is the same however
+//!    the input is split, including inside a `\r\n` pair or a multi-byte character.
+//! 2. Line endings are normalized: `\r\n` becomes `\n`.
+//! 3. Every emitted event ends with a blank line (`\n\n`), and events are never merged or split.
+//! 4. Events without JSON `data:` lines come out as they went in, apart from 2 and 3.
+//! 5. Bytes that are not UTF-8 are flushed through unmodified, after anything buffered before
+//!    them. Nothing panics.
+
+use bytes::Bytes;
+use serde_json::Value;
+
+use crate::{augment_widget_metadata, conformance};
+
+/// Rewrites an SSE body chunk by chunk.
+#[derive(Debug, Default)]
+pub struct SseRewriter {
+    /// Decoded text not yet forming a complete event. Only a trailing `\r` may be unnormalized.
+    buffer: String,
+    /// Bytes of a UTF-8 sequence cut off at the end of the last chunk.
+    partial: Vec<u8>,
+    strict_conformance: bool,
+}
+
+impl SseRewriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Validates each rewritten event against the MCP schema, see [`crate::conformance`].
+    pub fn with_strict_conformance(mut self, enabled: bool) -> Self {
+        self.strict_conformance = enabled;
+        self
+    }
+
+    /// Feeds one chunk and returns the output it completes, in order.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<Bytes> {
+        let mut bytes = std::mem::take(&mut self.partial);
+        bytes.extend_from_slice(chunk);
+
+        let text_len = match std::str::from_utf8(&bytes) {
+            Ok(_) => bytes.len(),
+            // Only an incomplete sequence at the very end can be completed by the next chunk.
+            Err(error) if error.error_len().is_none() => error.valid_up_to(),
+            Err(_) => {
+                tracing::debug!("SseRewriter: encountered non UTF-8 SSE chunk; flushing buffer");
+                let mut output = Vec::with_capacity(2);
+                if !self.buffer.is_empty() {
+                    output.push(Bytes::from(std::mem::take(&mut self.buffer)));
+                }
+                output.push(Bytes::from(bytes));
+                return output;
+            }
+        };
+        self.partial = bytes.split_off(text_len);
+        let text = String::from_utf8(bytes).expect("prefix was validated as UTF-8");
+
+        // A `\r` ending the previous chunk pairs with a `\n` starting this one.
+        if self.buffer.ends_with('\r') && text.starts_with('\n') {
+            self.buffer.pop();
+        }
+        self.buffer.push_str(&text.replace("\r\n", "\n"));
+
+        let mut output = Vec::new();
+        while let Some(event) = drain_complete_event(&mut self.buffer) {
+            output.push(self.rewrite(event));
+        }
+        output
+    }
+
+    /// Bytes held while waiting for the rest of an event.
+    pub fn buffered_bytes(&self) -> usize {
+        self.buffer.len() + self.partial.len()
+    }
+
+    /// Hands back what is held without rewriting it, for a stream that stops being augmented.
+    pub fn take_unrewritten(&mut self) -> Vec<Bytes> {
+        [
+            std::mem::take(&mut self.buffer).into_bytes(),
+            std::mem::take(&mut self.partial),
+        ]
+        .into_iter()
+        .filter(|bytes| !bytes.is_empty())
+        .map(Bytes::from)
+        .collect()
+    }
+
+    /// Flushes whatever is left once the body ends: a trailing event without its blank line is
+    /// terminated, and a cut-off UTF-8 sequence is passed through as is.
+    pub fn finish(&mut self) -> Vec<Bytes> {
+        let mut output = Vec::new();
+        if !self.buffer.is_empty() {
+            let event = std::mem::take(&mut self.buffer);
+            output.push(self.rewrite(event));
+        }
+        if !self.partial.is_empty() {
+            output.push(Bytes::from(std::mem::take(&mut self.partial)));
+        }
+        output
+    }
+
+    fn rewrite(&self, event: String) -> Bytes {
+        let (mut processed, event_changed) = augment_sse_event(&event);
+        if event_changed {
+            tracing::trace!("SseRewriter: augmented SSE event");
+        }
+        if self.strict_conformance {
+            conformance::report_sse_event(&processed);
+        }
+        if !processed.ends_with("\n\n") {
+            processed.push_str("\n\n");
+        }
+        Bytes::from(processed)
+    }
+}
+
+/// Rewrites a whole body delivered as `chunks`; the concatenation of everything the rewriter emits.
+pub fn rewrite_chunks<'a>(chunks: impl IntoIterator<Item = &'a [u8]>) -> Vec<u8> {
+    let mut rewriter = SseRewriter::new();
+    let mut output = Vec::new();
+    for chunk in chunks {
+        for bytes in rewriter.push(chunk) {
+            output.extend_from_slice(&bytes);
+        }
+    }
+    for bytes in rewriter.finish() {
+        output.extend_from_slice(&bytes);
+    }
+    output
+}
+
+/// Removes and returns the next complete SSE event (terminated by a blank line) from the buffer.
+pub fn drain_complete_event(buffer: &mut String) -> Option<String> {
+    let boundary = buffer.find("\n\n")?;
+    let mut extracted: String = buffer.drain(..boundary + 2).collect();
+    if extracted.ends_with("\n\n") {
+        extracted.truncate(extracted.len() - 2);
+    }
+    Some(extracted)
+}
+
+/// Attempts to augment every SSE event in the provided stream, returning `None` when no changes occur.
+pub fn augment_sse_stream(original: &str) -> Option<String> {
+    let normalized = original.replace("\r\n", "\n");
+    let mut changed_any = false;
+    let mut output = String::with_capacity(normalized.len());
+
+    // Walk each SSE event (terminated by a blank line) and try to inject widget metadata.
+    for segment in normalized.split_inclusive("\n\n") {
+        let (event_body, separator) = match segment.strip_suffix("\n\n") {
+            Some(body) => (body, "\n\n"),
+            None => (segment, ""),
+        };
+
+        let (processed_event, event_changed) = augment_sse_event(event_body);
+        if event_changed {
+            tracing::trace!("augment_sse_stream: augmented SSE event detected");
+            changed_any = true;
+        }
+
+        output.push_str(&processed_event);
+        output.push_str(separator);
+    }
+
+    if changed_any {
+        Some(output)
+    } else {
+        tracing::trace!("augment_sse_stream: no SSE events modified");
+        None
+    }
+}
+
+/// Augments a single SSE event in-place, returning the rewritten payload and whether it changed.
+pub fn augment_sse_event(event: &str) -> (String, bool) {
+    if event.is_empty() {
+        return (String::new(), false);
+    }
+
+    // Track whether any `data:` lines were rewritten so callers can decide whether to flush the event.
+    let mut event_changed = false;
+    let mut lines_out = Vec::new();
+
+    for line in event.split('\n') {
+        if let Some(rest) = line.strip_prefix("data:") {
+            let trimmed = rest.trim_start();
+            if trimmed.is_empty() {
+                lines_out.push(line.to_string());
+                continue;
+            }
+
+            if let Ok(mut json_value) = serde_json::from_str::<Value>(trimmed) {
+                let original_value = json_value.clone();
+                augment_widget_metadata(&mut json_value);
+                if json_value != original_value {
+                    tracing::trace!("augment_sse_event: modified JSON data line");
+                    event_changed = true;
+                }
+
+                if let Ok(serialized) = serde_json::to_string(&json_value) {
+                    let prefix = &rest[..rest.len() - trimmed.len()];
+                    lines_out.push(format!("data:{}{}", prefix, serialized));
+                    continue;
+                }
+            } else {
+                tracing::trace!(
+                    "augment_sse_event: skipping non-JSON data line '{}'",
+                    trimmed
+                );
+            }
+        }
+
+        lines_out.push(line.to_string());
+    }
+
+    (lines_out.join("\n"), event_changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::initialize_widgets_for_tests;
+
+    /// Validates that SSE payloads carrying JSON tool results receive injected widget metadata.
+    #[test]
+    fn augment_sse_stream_injects_meta() {
+        initialize_widgets_for_tests();
+        let original = concat!(
+            "event: message\r\n",
+            "data: {\"jsonrpc\":\"2.0\",\"id\":2,\"result\":{\"tools\":[{\"name\":\"pizza-map\"}]}}\r\n",
+            "\r\n"
+        );
+
+        let augmented = augment_sse_stream(original).expect("stream should be augmented");
+        assert!(
+            augmented.contains("\"_meta\""),
+            "Augmented stream must contain _meta"
+        );
+        assert!(
+            augmented.contains("\"openai/outputTemplate\""),
+            "Widget metadata should be injected"
+        );
+    }
+
+    /// Confirms that non-JSON SSE messages pass through without modification.
+    #[test]
+    fn augment_sse_stream_preserves_non_json_data() {
+        let original = concat!(": heartbeat\n", "data: ping\n", "\n");
+        assert!(
+            augment_sse_stream(original).is_none(),
+            "Non-JSON SSE payloads should remain untouched"
+        );
+    }
+
+    /// xorshift64*, so the property test needs no extra dependency and failures reproduce.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+        }
+
+        fn below(&mut self, bound: usize) -> usize {
+            (self.next() % bound as u64) as usize
+        }
+    }
+
+    /// Invariants 1 to 4, plus idempotence, over generated bodies mixing CRLF, multi-byte text and JSON events.
+    #[test]
+    fn rewriting_is_independent_of_chunk_boundaries() {
+        initialize_widgets_for_tests();
+        const PIECES: &[&str] = &[
+            "event: message\n",
+            "event: message\r\n",
+            "data: {\"jsonrpc\":\"2.0\",\"id\":1,\"result\":{\"tools\":[{\"name\":\"pizza-map\"}]}}\n",
+            "data: {\"jsonrpc\":\"2.0\",\"id\":2,\"result\":{\"note\":\"piñata 🍕\"}}\r\n",
+            "data: ping\n",
+            "data:\n",
+            ": keep-alive ✓\n",
+            "id: 7\r\n",
+            "\n",
+            "\r\n",
+            "\r",
+        ];
+
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for case in 0..500 {
+            let body: String = (0..rng.below(12) + 1)
+                .map(|_| PIECES[rng.below(PIECES.len())])
+                .collect();
+            let bytes = body.as_bytes();
+            let whole = rewrite_chunks([bytes]);
+
+            let mut cuts: Vec<usize> = (0..rng.below(6))
+                .map(|_| rng.below(bytes.len() + 1))
+                .collect();
+            cuts.sort_unstable();
+            let mut chunks = Vec::new();
+            let mut start = 0;
+            for cut in cuts.into_iter().chain([bytes.len()]) {
+                chunks.push(&bytes[start..cut]);
+                start = cut;
+            }
+            let split = rewrite_chunks(chunks.iter().copied());
+            assert_eq!(split, whole, "case {case}: {body:?} split as {chunks:?}");
+
+            let output = String::from_utf8(whole).expect("UTF-8 in, UTF-8 out");
+            // A lone `\r` is not a line ending here, so it may still precede a `\n`.
+            let lone_cr = body.replace("\r\n", "\n").contains('\r');
+            assert!(
+                lone_cr || !output.contains("\r\n"),
+                "case {case}: {output:?}"
+            );
+            assert!(
+                output.is_empty() || output.ends_with("\n\n"),
+                "case {case}: {output:?}"
+            );
+            if !body.contains('{') {
+                assert_eq!(output.trim_end(), body.replace("\r\n", "\n").trim_end());
+            }
+            for event in output.split("\n\n") {
+                assert_eq!(augment_sse_event(event).0, event, "rewriting is idempotent");
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_utf8_is_flushed_through() {
+        let mut rewriter = SseRewriter::new();
+        assert!(rewriter.push(b"data: ping").is_empty());
+        let output = rewriter.push(b"\xff\n\n");
+        assert_eq!(
+            output,
+            [Bytes::from("data: ping"), Bytes::from(&b"\xff\n\n"[..])]
+        );
+        assert!(rewriter.finish().is_empty());
+
+        let mut rewriter = SseRewriter::new();
+        assert!(rewriter
+            .push("data: caf\u{e9}".as_bytes().split_last().unwrap().1)
+            .is_empty());
+        assert_eq!(rewriter.finish().len(), 2);
+    }
+}
@@ -0,0 +1,196 @@
+//! Ownership of the server's long-running background tasks.
+//!
+//! The manifest watcher, scheduled refresh, rate limit sweep, event loop lag probe and
+//! [event bus](crate::events) subscribers each run as a supervised task. A task that panics is logged and started again after a backoff that doubles
+//! from one second up to a minute, and resets once the task has stayed up for that minute. A task
+//! that returns, usually because the app that owned it was dropped, is finished and not restarted.
+//! `GET /internal/widgets/status` reports every task's health under `background_tasks`.
+//!
+//! Per-session and per-request work, such as list change notifiers, SSE pumps and webhook
+//! deliveries, ends with its session or request and is not supervised.
+
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use time::{format_description::well_known::Iso8601, OffsetDateTime};
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskState {
+    Running,
+    /// Waiting out the backoff after a panic.
+    Restarting,
+    /// Returned, and will not be started again.
+    Finished,
+}
+
+/// One supervised task, as reported in the status endpoint.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TaskHealth {
+    pub state: TaskState,
+    /// Times the task was started again after panicking.
+    pub restarts: u64,
+    /// ISO 8601 time the current run started.
+    pub started_at: Option<String>,
+    pub last_panic: Option<String>,
+    pub last_panic_at: Option<String>,
+}
+
+/// Starts background tasks and restarts them when they panic.
+#[derive(Debug, Clone)]
+pub struct Supervisor {
+    tasks: Arc<Mutex<BTreeMap<&'static str, TaskHealth>>>,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Self::with_backoff(INITIAL_BACKOFF, MAX_BACKOFF)
+    }
+}
+
+impl Supervisor {
+    /// A supervisor whose restart delay doubles from `initial` up to `max`.
+    pub fn with_backoff(initial: Duration, max: Duration) -> Self {
+        Self {
+            tasks: Arc::default(),
+            initial_backoff: initial,
+            max_backoff: max,
+        }
+    }
+
+    /// Runs the future `task` returns as `name`, calling `task` again for a fresh one whenever
+    /// the previous run panics.
+    ///
+    /// Without a Tokio runtime nothing is started.
+    pub fn spawn<F, Fut>(&self, name: &'static str, mut task: F)
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            tracing::debug!(task = name, "No Tokio runtime; background task not started");
+            return;
+        };
+        let supervisor = self.clone();
+        let handle = runtime.clone();
+        runtime.spawn(async move {
+            let mut backoff = supervisor.initial_backoff;
+            loop {
+                supervisor.update(name, |health| {
+                    health.state = TaskState::Running;
+                    health.started_at = now();
+                });
+                let started = Instant::now();
+                let panic = match handle.spawn(task()).await {
+                    Ok(()) => None,
+                    Err(error) if error.is_panic() => Some(panic_message(error.into_panic())),
+                    // Cancelled along with the runtime.
+                    Err(_) => None,
+                };
+                let Some(panic) = panic else {
+                    supervisor.update(name, |health| health.state = TaskState::Finished);
+                    return;
+                };
+
+                if started.elapsed() >= supervisor.max_backoff {
+                    backoff = supervisor.initial_backoff;
+                }
+                tracing::error!(
+                    task = name,
+                    panic = %panic,
+                    retry_in_ms = backoff.as_millis() as u64,
+                    "Background task panicked; restarting"
+                );
+                supervisor.update(name, |health| {
+                    health.state = TaskState::Restarting;
+                    health.restarts += 1;
+                    health.last_panic = Some(panic);
+                    health.last_panic_at = now();
+                });
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(supervisor.max_backoff);
+            }
+        });
+    }
+
+    /// Health of every task started so far, by name.
+    pub fn status(&self) -> BTreeMap<&'static str, TaskHealth> {
+        self.tasks.lock().expect("supervisor lock poisoned").clone()
+    }
+
+    fn update(&self, name: &'static str, update: impl FnOnce(&mut TaskHealth)) {
+        let mut tasks = self.tasks.lock().expect("supervisor lock poisoned");
+        update(tasks.entry(name).or_insert(TaskHealth {
+            state: TaskState::Running,
+            restarts: 0,
+            started_at: None,
+            last_panic: None,
+            last_panic_at: None,
+        }));
+    }
+}
+
+fn now() -> Option<String> {
+    OffsetDateTime::now_utc().format(&Iso8601::DEFAULT).ok()
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "panicked with a non-string payload".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test]
+    async fn panicking_tasks_are_restarted_until_they_finish() {
+        let supervisor =
+            Supervisor::with_backoff(Duration::from_millis(5), Duration::from_millis(20));
+        let runs = Arc::new(AtomicU32::new(0));
+        let task_runs = Arc::clone(&runs);
+        supervisor.spawn("flaky", move || {
+            let runs = Arc::clone(&task_runs);
+            async move {
+                if runs.fetch_add(1, Ordering::SeqCst) < 2 {
+                    panic!("manifest watcher lost its footing");
+                }
+            }
+        });
+
+        let health = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Some(health) = supervisor.status().get("flaky") {
+                    if health.state == TaskState::Finished {
+                        return health.clone();
+                    }
+                }
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("task finishes");
+
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+        assert_eq!(health.restarts, 2);
+        assert_eq!(
+            health.last_panic.as_deref(),
+            Some("manifest watcher lost its footing")
+        );
+        assert!(health.last_panic_at.is_some());
+    }
+}
@@ -0,0 +1,123 @@
+//! Trace context on tool results, for joining widget telemetry with server logs.
+//!
+//! With `WIDGETS_TRACE_META=1`, every `tools/call` runs inside a `tool_call` span carrying a trace
+//! and span id, and the result's `_meta` repeats them as `pizzaz/traceId` and `pizzaz/spanId`. The
+//! trace id continues the caller's W3C `traceparent` (from `_meta` or the HTTP header) when one is
+//! sent; otherwise a new trace is started.
+
+use rmcp::model::Meta;
+use serde_json::Value as JsonValue;
+
+/// `_meta` key carrying the trace id of the call that produced a result.
+pub const TRACE_ID_META_KEY: &str = "pizzaz/traceId";
+
+/// `_meta` key carrying the server span id of the call that produced a result.
+pub const SPAN_ID_META_KEY: &str = "pizzaz/spanId";
+
+/// W3C trace context header, also accepted as a `_meta` key.
+pub const TRACEPARENT: &str = "traceparent";
+
+/// Trace and span ids of one tool call, as lowercase hex.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    pub trace_id: String,
+    pub span_id: String,
+}
+
+impl TraceContext {
+    /// A span for a new call: a child of `parent` when given, otherwise the root of a new trace.
+    pub fn start(parent: Option<&TraceContext>) -> Self {
+        let random = uuid::Uuid::new_v4().simple().to_string();
+        Self {
+            trace_id: parent.map_or_else(
+                || uuid::Uuid::new_v4().simple().to_string(),
+                |parent| parent.trace_id.clone(),
+            ),
+            span_id: random[..16].to_string(),
+        }
+    }
+
+    /// Parses a version `00` `traceparent` value. All-zero ids are invalid and rejected.
+    pub fn from_traceparent(value: &str) -> Option<Self> {
+        let mut fields = value.trim().split('-');
+        let (version, trace_id, span_id, flags) = (
+            fields.next()?,
+            fields.next()?,
+            fields.next()?,
+            fields.next()?,
+        );
+        if version != "00"
+            || fields.next().is_some()
+            || !is_hex_id(trace_id, 32)
+            || !is_hex_id(span_id, 16)
+            || !is_hex_id(flags, 2)
+        {
+            return None;
+        }
+        let all_zero = |id: &str| id.bytes().all(|byte| byte == b'0');
+        if all_zero(trace_id) || all_zero(span_id) {
+            return None;
+        }
+        Some(Self {
+            trace_id: trace_id.to_string(),
+            span_id: span_id.to_string(),
+        })
+    }
+
+    /// Adds `pizzaz/traceId` and `pizzaz/spanId` to `meta`.
+    pub fn insert_into(&self, meta: &mut Meta) {
+        meta.0.insert(
+            TRACE_ID_META_KEY.to_string(),
+            JsonValue::from(self.trace_id.as_str()),
+        );
+        meta.0.insert(
+            SPAN_ID_META_KEY.to_string(),
+            JsonValue::from(self.span_id.as_str()),
+        );
+    }
+}
+
+fn is_hex_id(value: &str, len: usize) -> bool {
+    value.len() == len
+        && value
+            .bytes()
+            .all(|byte| byte.is_ascii_digit() || (b'a'..=b'f').contains(&byte))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn child_spans_keep_the_callers_trace_id() {
+        let parent = TraceContext::from_traceparent(
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        )
+        .unwrap();
+        assert_eq!(parent.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+
+        let child = TraceContext::start(Some(&parent));
+        assert_eq!(child.trace_id, parent.trace_id);
+        assert_ne!(child.span_id, parent.span_id);
+        assert_eq!(child.span_id.len(), 16);
+
+        let root = TraceContext::start(None);
+        assert_eq!(root.trace_id.len(), 32);
+        assert_ne!(root.trace_id, parent.trace_id);
+    }
+
+    #[test]
+    fn malformed_traceparents_are_ignored() {
+        for value in [
+            "",
+            "01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+        ] {
+            assert_eq!(TraceContext::from_traceparent(value), None, "{value}");
+        }
+    }
+}
@@ -1,19 +1,33 @@
 //! Widget registry backed by the generated manifest.
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
     fs,
     path::{Path, PathBuf},
-    sync::{Arc, LazyLock, RwLock},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, LazyLock, Mutex, RwLock,
+    },
+    time::{Duration, Instant, SystemTime},
 };
 
 use anyhow::{bail, Context, Result};
-use semver::Version;
+use arc_swap::ArcSwap;
+use semver::{Version, VersionReq};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
 use time::{format_description::well_known::Iso8601, OffsetDateTime};
+use tokio::sync::broadcast;
 use tracing::{debug, error, info, warn};
 
+use crate::auth::Identity;
+use crate::manifest_schema::{ManifestSchemaError, SchemaViolation};
+use crate::rate_limit::{parse_rate_limit, RateLimitConfig};
+use crate::snapshot;
 use crate::widgets_manifest::{
-    read_manifest, WidgetManifest, WidgetManifestEntry, SUPPORTED_SCHEMA_MAJOR,
+    merge_patch, read_manifest, read_overlay, WidgetCsp, WidgetManifest, WidgetManifestAssets,
+    WidgetManifestEntry, WidgetManifestLocale, SERVER_FEATURES, SERVER_SCHEMA_VERSION,
+    SUPPORTED_SCHEMA_MAJOR,
 };
 
 /// Represents a widget with all metadata required for MCP integration.
@@ -27,10 +41,130 @@ pub struct Widget {
     pub html: String,
     pub response_text: String,
     pub assets: WidgetAssets,
+    pub allowed_roles: Vec<String>,
+    pub rate_limit: Option<RateLimitConfig>,
+    /// Lowercase, deduplicated tags from the manifest, in manifest order.
+    pub tags: Vec<String>,
+    /// The manifest's `order`; see [`listing_order`].
+    pub order: Option<i64>,
+    /// Lowercase hex SHA-256 of `html`, computed at load time.
+    pub content_hash: String,
+    /// Signature over `html` when a signing key is configured.
+    pub signature: Option<crate::signing::Signature>,
+    /// Tool input schema from the manifest, or [`default_input_schema`].
+    pub input_schema: serde_json::Value,
+    /// Problems found while loading that did not stop the widget from being served.
+    pub warnings: Vec<String>,
+    /// Local file `html` was read from; `None` for inline, URL and fetched HTML.
+    pub html_file: Option<PathBuf>,
+    /// Why this widget is served from the HTML cached by an earlier load, if it is.
+    pub degraded: Option<String>,
+    /// Localized variants keyed by lowercase locale tag; empty on the variants themselves.
+    pub locales: BTreeMap<String, Arc<Widget>>,
+    /// The locale tag this variant was built for; `None` for the widget itself.
+    pub locale: Option<String>,
+    /// Why callers should move off this widget, when the manifest marks it deprecated.
+    pub deprecation: Option<String>,
+    /// Validated CSP origins, emitted as `openai/widgetCSP`.
+    pub csp: Option<WidgetCsp>,
+    /// Validated origin, emitted as `openai/widgetDomain`.
+    pub widget_domain: Option<String>,
+    /// The manifest's `meta` object, merge-patched over the keys [`Widget::meta`] generates.
+    pub meta_overrides: serde_json::Map<String, serde_json::Value>,
+    /// Values for the `{{name}}` placeholders in `invoking`, `invoked` and `response_text`: the
+    /// input schema's defaults, until [`Widget::with_arguments`] binds a call's arguments.
+    pub template_values: serde_json::Map<String, serde_json::Value>,
+    /// Ids of the widgets this one depends on, checked against the registry when it loads.
+    pub depends_on: Vec<String>,
+}
+
+/// Resource `_meta` key carrying the widget HTML hash.
+pub const CONTENT_HASH_META_KEY: &str = "pizzaz/contentHash";
+
+/// Tool `_meta` key listing the widget's tags.
+pub const TAGS_META_KEY: &str = "pizzaz/tags";
+
+/// Tool result `_meta` key listing assets a client can fetch before rendering the widget.
+pub const PREFETCH_META_KEY: &str = "pizzaz/prefetch";
+
+/// `_meta` key carrying the widget's Content Security Policy origins.
+pub const CSP_META_KEY: &str = "openai/widgetCSP";
+
+/// `_meta` key carrying the origin the widget is served under.
+pub const WIDGET_DOMAIN_META_KEY: &str = "openai/widgetDomain";
+
+/// Tool `_meta` keys set on deprecated widgets: `true`, and the deprecation message.
+pub const DEPRECATED_META_KEY: &str = "pizzaz/deprecated";
+pub const DEPRECATION_MESSAGE_META_KEY: &str = "pizzaz/deprecationMessage";
+
+/// Used when a deprecated widget has no `deprecationMessage`.
+pub const DEFAULT_DEPRECATION_MESSAGE: &str = "This tool will be removed; avoid calling it.";
+
+/// Resource `_meta` key carrying the reason a widget is served from cached HTML.
+pub const DEGRADED_META_KEY: &str = "pizzaz/degraded";
+
+/// Request `_meta` key carrying the user's locale, e.g. `fr-CA`. Results served from a localized
+/// variant echo the variant's tag under the same key.
+pub const LOCALE_META_KEY: &str = "openai/locale";
+
+/// Prefix of the template URIs manifests are written with.
+pub const MANIFEST_TEMPLATE_URI_PREFIX: &str = "ui://widget/";
+
+/// What clients see in place of [`MANIFEST_TEMPLATE_URI_PREFIX`], from
+/// `WIDGETS_TEMPLATE_URI_PREFIX`. Read once, so every load, lookup and response agrees.
+static TEMPLATE_URI_PREFIX: LazyLock<Option<String>> = LazyLock::new(|| {
+    let raw = std::env::var("WIDGETS_TEMPLATE_URI_PREFIX").ok()?;
+    parse_template_uri_prefix(&raw).unwrap_or_else(|error| {
+        warn!(value = %raw, %error, "Ignoring invalid WIDGETS_TEMPLATE_URI_PREFIX");
+        None
+    })
+});
+
+/// The configured template URI prefix, e.g. `resource://pizzaz/`; `None` serves manifest URIs
+/// unchanged.
+pub fn template_uri_prefix() -> Option<&'static str> {
+    TEMPLATE_URI_PREFIX.as_deref()
+}
+
+/// Checks that `raw` starts with a URI scheme; blank means no prefix.
+fn parse_template_uri_prefix(raw: &str) -> Result<Option<String>> {
+    let prefix = raw.trim();
+    if prefix.is_empty() {
+        return Ok(None);
+    }
+    let Some((scheme, rest)) = prefix.split_once(':') else {
+        bail!("{prefix:?} has no URI scheme");
+    };
+    let valid_scheme = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    if !valid_scheme {
+        bail!("{scheme:?} is not a URI scheme");
+    }
+    if rest.is_empty() || prefix.contains(char::is_whitespace) {
+        bail!("{prefix:?} is not a URI prefix");
+    }
+    Ok(Some(prefix.to_string()))
+}
+
+/// `uri` as clients see it: a leading `ui://widget/` is replaced by `prefix`. URIs the manifest
+/// spells some other way are left alone.
+pub fn client_template_uri(uri: &str, prefix: Option<&str>) -> String {
+    match (prefix, uri.strip_prefix(MANIFEST_TEMPLATE_URI_PREFIX)) {
+        (Some(prefix), Some(path)) => format!("{prefix}{path}"),
+        _ => uri.to_string(),
+    }
 }
 
 impl Widget {
-    /// Generates OpenAI-specific metadata for widget integration.
+    /// Returns true when the identity may list and call this widget.
+    pub fn is_accessible_by(&self, identity: &Identity) -> bool {
+        self.allowed_roles.is_empty() || identity.has_any_role(&self.allowed_roles)
+    }
+
+    /// Generates OpenAI-specific metadata for widget integration, with the manifest's `meta`
+    /// overrides applied.
     pub fn meta(&self) -> rmcp::model::Meta {
         let mut map = serde_json::Map::new();
         map.insert(
@@ -39,19 +173,249 @@ impl Widget {
         );
         map.insert(
             "openai/toolInvocation/invoking".to_string(),
-            serde_json::json!(self.invoking),
+            serde_json::json!(self.render(&self.invoking)),
         );
         map.insert(
             "openai/toolInvocation/invoked".to_string(),
-            serde_json::json!(self.invoked),
+            serde_json::json!(self.render(&self.invoked)),
+        );
+        map.insert(
+            "openai/widgetAccessible".to_string(),
+            serde_json::json!(true),
         );
-        map.insert("openai/widgetAccessible".to_string(), serde_json::json!(true));
         map.insert(
             "openai/resultCanProduceWidget".to_string(),
             serde_json::json!(true),
         );
+        if let Some(csp) = &self.csp {
+            let mut policy = serde_json::json!({
+                "connect_domains": csp.connect_domains,
+                "resource_domains": csp.resource_domains,
+            });
+            if !csp.frame_domains.is_empty() {
+                policy["frame_domains"] = serde_json::json!(csp.frame_domains);
+            }
+            map.insert(CSP_META_KEY.to_string(), policy);
+        }
+        if let Some(domain) = &self.widget_domain {
+            map.insert(
+                WIDGET_DOMAIN_META_KEY.to_string(),
+                serde_json::json!(domain),
+            );
+        }
+        for (key, value) in &self.meta_overrides {
+            if value.is_null() {
+                map.remove(key);
+            } else {
+                merge_patch(
+                    map.entry(key.clone()).or_insert(serde_json::Value::Null),
+                    value,
+                );
+            }
+        }
         rmcp::model::Meta(map)
     }
+
+    /// Metadata for the widget's tool definition: [`Widget::meta`] plus its tags and deprecation,
+    /// if any.
+    pub fn tool_meta(&self) -> rmcp::model::Meta {
+        let mut meta = self.meta();
+        if !self.tags.is_empty() {
+            meta.0
+                .insert(TAGS_META_KEY.to_string(), serde_json::json!(self.tags));
+        }
+        if let Some(message) = &self.deprecation {
+            meta.0
+                .insert(DEPRECATED_META_KEY.to_string(), serde_json::json!(true));
+            meta.0.insert(
+                DEPRECATION_MESSAGE_META_KEY.to_string(),
+                serde_json::json!(message),
+            );
+        }
+        meta
+    }
+
+    /// The tool description: the title, followed by the deprecation notice for deprecated
+    /// widgets so the model prefers other tools.
+    pub fn tool_description(&self) -> String {
+        match &self.deprecation {
+            Some(message) => format!("{} (Deprecated: {message})", self.title),
+            None => self.title.clone(),
+        }
+    }
+
+    /// The response text, with placeholders filled in from [`Widget::template_values`].
+    pub fn response_text(&self) -> String {
+        self.render(&self.response_text)
+    }
+
+    /// This widget with its placeholders bound to a call's `arguments`. Widgets without
+    /// placeholders are returned as they are.
+    pub fn with_arguments(self: &Arc<Self>, arguments: &serde_json::Value) -> Arc<Widget> {
+        let templated = [&self.invoking, &self.invoked, &self.response_text]
+            .into_iter()
+            .any(|text| placeholders(text).next().is_some());
+        match arguments.as_object() {
+            Some(arguments) if templated => Arc::new(Widget {
+                template_values: arguments.clone(),
+                ..Widget::clone(self)
+            }),
+            _ => Arc::clone(self),
+        }
+    }
+
+    fn render(&self, template: &str) -> String {
+        render_template(template, &self.template_values)
+    }
+
+    /// Why the HTML served for this widget may be stale: it was carried over from an earlier
+    /// load, or the file it was read from has since disappeared.
+    pub fn degradation(&self) -> Option<String> {
+        if let Some(reason) = &self.degraded {
+            return Some(reason.clone());
+        }
+        let file = self.html_file.as_ref().filter(|file| !file.is_file())?;
+        Some(format!(
+            "HTML file {} is missing; serving the copy loaded earlier",
+            file.display()
+        ))
+    }
+
+    /// Bytes of HTML this widget holds in memory, counting locale variants with HTML of their own.
+    pub fn html_bytes(&self) -> u64 {
+        let variants = self
+            .locales
+            .values()
+            .filter(|variant| variant.content_hash != self.content_hash)
+            .map(|variant| variant.html.len());
+        (self.html.len() + variants.sum::<usize>()) as u64
+    }
+
+    /// The manifest entry this widget is served as; see [`WidgetsRegistry::to_manifest`].
+    pub fn to_manifest_entry(&self) -> WidgetManifestEntry {
+        let template_uri =
+            match template_uri_prefix().and_then(|prefix| self.template_uri.strip_prefix(prefix)) {
+                Some(path) => format!("{MANIFEST_TEMPLATE_URI_PREFIX}{path}"),
+                None => self.template_uri.clone(),
+            };
+        let assets = [&self.assets.html, &self.assets.css, &self.assets.js]
+            .iter()
+            .any(|asset| asset.is_some())
+            .then(|| WidgetManifestAssets {
+                html: self.assets.html.clone(),
+                css: self.assets.css.clone(),
+                js: self.assets.js.clone(),
+                sha256: None,
+            });
+        let locales = self
+            .locales
+            .iter()
+            .map(|(tag, variant)| {
+                let differs =
+                    |value: &String, base: &String| (value != base).then(|| value.clone());
+                let locale = WidgetManifestLocale {
+                    title: differs(&variant.title, &self.title),
+                    invoking: differs(&variant.invoking, &self.invoking),
+                    invoked: differs(&variant.invoked, &self.invoked),
+                    response_text: differs(&variant.response_text, &self.response_text),
+                    html_path: variant
+                        .html_file
+                        .as_ref()
+                        .filter(|_| variant.content_hash != self.content_hash)
+                        .map(|path| path.display().to_string()),
+                };
+                (tag.clone(), locale)
+            })
+            .collect();
+
+        WidgetManifestEntry {
+            id: self.id.clone(),
+            title: self.title.clone(),
+            template_uri,
+            invoking: self.invoking.clone(),
+            invoked: self.invoked.clone(),
+            html: self.html.clone(),
+            html_path: None,
+            response_text: self.response_text.clone(),
+            assets,
+            allowed_roles: self.allowed_roles.clone(),
+            rate_limit: self
+                .rate_limit
+                .map(|limit| format!("{}/{}s", limit.max_requests, limit.window.as_secs())),
+            input_schema: Some(self.input_schema.clone()),
+            tags: self.tags.clone(),
+            order: self.order,
+            enabled: true,
+            locales,
+            requires: None,
+            csp: self.csp.clone(),
+            widget_domain: self.widget_domain.clone(),
+            meta: (!self.meta_overrides.is_empty()).then(|| self.meta_overrides.clone()),
+            deprecated: self.deprecation.is_some(),
+            deprecation_message: self.deprecation.clone(),
+            depends_on: self.depends_on.clone(),
+        }
+    }
+
+    /// The variant for `locale`, trying less specific tags (`fr-ca`, then `fr`) before falling back
+    /// to the widget itself.
+    pub fn localized(self: &Arc<Self>, locale: Option<&str>) -> Arc<Widget> {
+        let Some(mut tag) = locale.and_then(normalize_locale) else {
+            return Arc::clone(self);
+        };
+        loop {
+            if let Some(variant) = self.locales.get(&tag) {
+                return Arc::clone(variant);
+            }
+            match tag.rfind('-') {
+                Some(cut) => tag.truncate(cut),
+                None => return Arc::clone(self),
+            }
+        }
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags
+            .iter()
+            .any(|own| own.eq_ignore_ascii_case(tag.trim()))
+    }
+
+    /// Prefetch hints for the output template and its remote assets.
+    ///
+    /// The template entry carries the content hash so clients can skip a `resources/read` when
+    /// they already hold that version; asset URLs embed their build hash in the file name.
+    /// Local asset paths are left out because clients cannot fetch them directly.
+    pub fn prefetch_hints(&self) -> serde_json::Value {
+        let mut hints = vec![serde_json::json!({
+            "url": self.template_uri,
+            "as": "document",
+            "sha256": self.content_hash,
+        })];
+        let assets = [("style", &self.assets.css), ("script", &self.assets.js)];
+        for (kind, url) in assets {
+            if let Some(url) = url.as_deref().filter(|url| is_remote_path(url)) {
+                hints.push(serde_json::json!({ "url": url, "as": kind }));
+            }
+        }
+        serde_json::Value::Array(hints)
+    }
+
+    /// Metadata for the widget's resource and template entries, including its content hash and
+    /// signature.
+    pub fn resource_meta(&self) -> rmcp::model::Meta {
+        let mut meta = self.meta();
+        meta.0.insert(
+            CONTENT_HASH_META_KEY.to_string(),
+            serde_json::json!(self.content_hash),
+        );
+        if let Some(signature) = &self.signature {
+            meta.0.insert(
+                crate::signing::SIGNATURE_META_KEY.to_string(),
+                serde_json::json!(signature),
+            );
+        }
+        meta
+    }
 }
 
 /// Optional asset metadata associated with a widget.
@@ -62,6 +426,105 @@ pub struct WidgetAssets {
     pub js: Option<String>,
 }
 
+/// Caps on what one manifest load may hold in memory, so a malformed manifest is rejected
+/// instead of being read in whole.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct RegistryLimits {
+    /// Enabled widgets, from `WIDGETS_MAX_WIDGETS`.
+    pub max_widgets: usize,
+    /// HTML bytes per widget or locale variant, from `WIDGETS_MAX_HTML_BYTES`.
+    pub max_html_bytes: u64,
+    /// HTML bytes across the registry, from `WIDGETS_MAX_REGISTRY_BYTES`.
+    pub max_registry_bytes: u64,
+}
+
+impl Default for RegistryLimits {
+    fn default() -> Self {
+        Self {
+            max_widgets: 256,
+            max_html_bytes: 8 * 1024 * 1024,
+            max_registry_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+impl RegistryLimits {
+    /// The limits from the environment; unset, invalid or zero values keep the defaults.
+    pub fn from_env() -> Self {
+        fn limit<T: std::str::FromStr + Default + PartialEq>(variable: &str, default: T) -> T {
+            match std::env::var(variable) {
+                Ok(raw) => match raw.trim().parse::<T>() {
+                    Ok(value) if value != T::default() => value,
+                    _ => {
+                        warn!(value = %raw, "Ignoring invalid {variable}");
+                        default
+                    }
+                },
+                Err(_) => default,
+            }
+        }
+        let defaults = Self::default();
+        Self {
+            max_widgets: limit("WIDGETS_MAX_WIDGETS", defaults.max_widgets),
+            max_html_bytes: limit("WIDGETS_MAX_HTML_BYTES", defaults.max_html_bytes),
+            max_registry_bytes: limit("WIDGETS_MAX_REGISTRY_BYTES", defaults.max_registry_bytes),
+        }
+    }
+
+    fn check_html_bytes(&self, label: &str, bytes: u64) -> Result<()> {
+        if bytes > self.max_html_bytes {
+            bail!(
+                "HTML for widget {label} is {bytes} bytes, over the limit of {} \
+                 (WIDGETS_MAX_HTML_BYTES)",
+                self.max_html_bytes
+            );
+        }
+        Ok(())
+    }
+
+    fn check_registry_bytes(&self, bytes: u64) -> Result<()> {
+        if bytes > self.max_registry_bytes {
+            bail!(
+                "Widget HTML totals more than {} bytes (WIDGETS_MAX_REGISTRY_BYTES)",
+                self.max_registry_bytes
+            );
+        }
+        Ok(())
+    }
+}
+
+/// What a load does with a widget whose id or template URI an earlier manifest entry already
+/// uses, from `WIDGETS_DUPLICATE_POLICY`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DuplicatePolicy {
+    /// Fail the load; the right choice for production manifests.
+    #[default]
+    Error,
+    /// Keep the earlier entry and skip the later one.
+    FirstWins,
+    /// Replace the earlier entry, or entries, with the later one.
+    LastWins,
+}
+
+impl DuplicatePolicy {
+    /// The policy from the environment; unset or invalid values mean [`DuplicatePolicy::Error`].
+    pub fn from_env() -> Self {
+        match std::env::var("WIDGETS_DUPLICATE_POLICY") {
+            Ok(value) => match value.trim().to_ascii_lowercase().as_str() {
+                "error" => Self::Error,
+                "first-wins" => Self::FirstWins,
+                "last-wins" => Self::LastWins,
+                other => {
+                    warn!("Invalid WIDGETS_DUPLICATE_POLICY value '{other}'; using error");
+                    Self::Error
+                }
+            },
+            Err(_) => Self::Error,
+        }
+    }
+}
+
 /// Registry metadata useful for diagnostics and health checks.
 #[derive(Debug, Clone)]
 pub struct RegistryMetadata {
@@ -71,6 +534,14 @@ pub struct RegistryMetadata {
     pub manifest_generated_at: Option<OffsetDateTime>,
     pub last_successful_load: Option<OffsetDateTime>,
     pub registry_initialized: bool,
+    /// Why the registry was loaded from a cached copy of its manifest rather than the manifest
+    /// itself, e.g. because the bucket was unreachable at startup.
+    pub degraded: Option<String>,
+    /// `ETag` or `Last-Modified` of each object an object-storage manifest was loaded from, by
+    /// object URL, so a refresh can ask whether any changed; empty for manifests on disk.
+    pub source_validators: BTreeMap<String, String>,
+    /// Load counters since the process started; filled in by [`registry_metadata`].
+    pub load_metrics: LoadMetrics,
 }
 
 impl RegistryMetadata {
@@ -82,16 +553,23 @@ impl RegistryMetadata {
             manifest_generated_at: None,
             last_successful_load: None,
             registry_initialized: false,
+            degraded: None,
+            source_validators: BTreeMap::new(),
+            load_metrics: LoadMetrics::default(),
         }
     }
 }
 
 /// In-memory widget registry with fast lookups by ID or template URI.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct WidgetsRegistry {
     widgets: Vec<Arc<Widget>>,
     widgets_by_id: HashMap<String, Arc<Widget>>,
     widgets_by_uri: HashMap<String, Arc<Widget>>,
+    /// Ids of manifest entries with `enabled: false`, sorted.
+    disabled: Vec<String>,
+    /// Widgets whose `requires` this server cannot meet, with the reason.
+    unsupported: BTreeMap<String, String>,
     metadata: RegistryMetadata,
 }
 
@@ -101,44 +579,129 @@ impl WidgetsRegistry {
             widgets: Vec::new(),
             widgets_by_id: HashMap::new(),
             widgets_by_uri: HashMap::new(),
+            disabled: Vec::new(),
+            unsupported: BTreeMap::new(),
             metadata: RegistryMetadata::empty(manifest_path),
         }
     }
 
+    /// Builds a registry from `manifest`. A widget whose local HTML file is missing is taken from
+    /// `fallback`, marked degraded, when `fallback` has it.
+    ///
+    /// Fails as soon as the manifest goes over one of `limits`, before reading any more HTML.
+    /// Entries that reuse an earlier widget's id or template URI are handled per `duplicates`.
     fn from_manifest(
         manifest: WidgetManifest,
         manifest_path: PathBuf,
+        assets: &AssetSource,
+        fallback: Option<&WidgetsRegistry>,
+        limits: &RegistryLimits,
+        duplicates: DuplicatePolicy,
         load_timestamp: OffsetDateTime,
     ) -> Result<Self> {
         validate_schema_version(&manifest.schema_version)?;
+        let enabled = manifest
+            .widgets
+            .iter()
+            .filter(|entry| entry.enabled)
+            .count();
+        if enabled > limits.max_widgets {
+            bail!(
+                "Manifest has {enabled} enabled widgets, over the limit of {} \
+                 (WIDGETS_MAX_WIDGETS)",
+                limits.max_widgets
+            );
+        }
 
         let mut widgets: Vec<Arc<Widget>> = Vec::with_capacity(manifest.widgets.len());
         let mut by_id = HashMap::with_capacity(manifest.widgets.len());
         let mut by_uri = HashMap::with_capacity(manifest.widgets.len());
-        let manifest_dir = manifest_path
-            .parent()
-            .map(Path::to_path_buf)
-            .unwrap_or_else(|| PathBuf::from("."));
 
+        let mut disabled = Vec::new();
+        let mut unsupported = BTreeMap::new();
+        let mut html_bytes = 0;
         for entry in manifest.widgets {
-            let widget = Arc::new(widget_from_entry(&entry, &manifest_dir)?);
-
-            if by_id.contains_key(&widget.id) {
-                bail!("Duplicate widget id detected in manifest: {}", widget.id);
+            if !entry.enabled {
+                disabled.push(entry.id.trim().to_string());
+                continue;
             }
-            if by_uri.contains_key(&widget.template_uri) {
-                bail!(
-                    "Duplicate widget templateUri detected in manifest: {}",
-                    widget.template_uri
-                );
+            if let Some(reason) = unmet_requirements(&entry)? {
+                warn!(widget_id = %entry.id.trim(), %reason, "Skipping widget this server cannot serve");
+                unsupported.insert(entry.id.trim().to_string(), reason);
+                continue;
+            }
+            let widget = match widget_from_entry(&entry, assets, limits) {
+                Ok(widget) => Arc::new(widget),
+                Err(error) => {
+                    match fallback.and_then(|fallback| cached_widget(fallback, &entry, assets)) {
+                        Some(widget) => Arc::new(widget),
+                        None => return Err(error),
+                    }
+                }
+            };
+
+            let duplicate_id = by_id.contains_key(&widget.id);
+            let duplicate_uri = by_uri.contains_key(&widget.template_uri);
+            match duplicates {
+                _ if !duplicate_id && !duplicate_uri => {}
+                DuplicatePolicy::Error if duplicate_id => {
+                    bail!("Duplicate widget id detected in manifest: {}", widget.id);
+                }
+                DuplicatePolicy::Error => {
+                    bail!(
+                        "Duplicate widget templateUri detected in manifest: {}",
+                        widget.template_uri
+                    );
+                }
+                DuplicatePolicy::FirstWins => {
+                    warn!(
+                        widget_id = %widget.id,
+                        template_uri = %widget.template_uri,
+                        "Skipping duplicate widget; the earlier entry wins"
+                    );
+                    continue;
+                }
+                DuplicatePolicy::LastWins => {
+                    widgets.retain(|existing| {
+                        let replaced = existing.id == widget.id
+                            || existing.template_uri == widget.template_uri;
+                        if replaced {
+                            warn!(
+                                widget_id = %existing.id,
+                                template_uri = %existing.template_uri,
+                                replaced_by = %widget.id,
+                                "Replacing duplicate widget; the later entry wins"
+                            );
+                            by_id.remove(&existing.id);
+                            by_uri.remove(&existing.template_uri);
+                            html_bytes -= existing.html_bytes();
+                        }
+                        !replaced
+                    });
+                }
             }
 
+            html_bytes += widget.html_bytes();
+            limits.check_registry_bytes(html_bytes)?;
+
             by_id.insert(widget.id.clone(), Arc::clone(&widget));
             by_uri.insert(widget.template_uri.clone(), Arc::clone(&widget));
             widgets.push(widget);
         }
 
-        widgets.sort_by(|a, b| a.id.cmp(&b.id));
+        widgets.sort_by(|a, b| listing_order(a, b));
+        disabled.sort();
+
+        let skipped = unmet_dependencies(&widgets, &disabled, &unsupported)?;
+        if !skipped.is_empty() {
+            for (id, reason) in &skipped {
+                warn!(widget_id = %id, %reason, "Skipping widget this server cannot serve");
+            }
+            widgets.retain(|widget| !skipped.contains_key(&widget.id));
+            by_id.retain(|id, _| !skipped.contains_key(id));
+            by_uri.retain(|_, widget| !skipped.contains_key(&widget.id));
+            unsupported.extend(skipped);
+        }
 
         let generated_at = manifest
             .generated_at
@@ -153,12 +716,17 @@ impl WidgetsRegistry {
             manifest_generated_at: generated_at,
             last_successful_load: Some(load_timestamp),
             registry_initialized: true,
+            degraded: None,
+            source_validators: BTreeMap::new(),
+            load_metrics: LoadMetrics::default(),
         };
 
         Ok(Self {
             widgets,
             widgets_by_id: by_id,
             widgets_by_uri: by_uri,
+            disabled,
+            unsupported,
             metadata,
         })
     }
@@ -173,15 +741,164 @@ impl WidgetsRegistry {
         self.widgets.clone()
     }
 
+    /// Ids of widgets the manifest lists but disables.
+    pub fn disabled_widgets(&self) -> &[String] {
+        &self.disabled
+    }
+
+    /// Widgets skipped because they require a newer schema version or an unknown feature, with
+    /// the reason for each.
+    pub fn unsupported_widgets(&self) -> &BTreeMap<String, String> {
+        &self.unsupported
+    }
+
+    /// Bytes of widget HTML held in memory, counting locale variants with HTML of their own.
+    pub fn html_bytes(&self) -> u64 {
+        self.widgets.iter().map(|widget| widget.html_bytes()).sum()
+    }
+
+    /// Each widget's `dependsOn`, for widgets that declare any.
+    pub fn dependencies(&self) -> BTreeMap<String, Vec<String>> {
+        self.widgets
+            .iter()
+            .filter(|widget| !widget.depends_on.is_empty())
+            .map(|widget| (widget.id.clone(), widget.depends_on.clone()))
+            .collect()
+    }
+
+    /// The registry as a manifest, as it is being served: HTML inlined, `${VAR}` references and
+    /// overlays resolved, defaults filled in. Disabled and unsupported widgets were never loaded
+    /// and are left out, as is locale HTML, which is listed by the file it was read from.
+    pub fn to_manifest(&self) -> WidgetManifest {
+        WidgetManifest {
+            schema_version: self
+                .metadata
+                .schema_version
+                .clone()
+                .unwrap_or_else(|| SERVER_SCHEMA_VERSION.to_string()),
+            generated_at: self
+                .metadata
+                .manifest_generated_at
+                .and_then(|timestamp| timestamp.format(&Iso8601::DEFAULT).ok()),
+            widgets: self
+                .widgets
+                .iter()
+                .map(|widget| widget.to_manifest_entry())
+                .collect(),
+        }
+    }
+
+    /// [`Self::to_manifest`] with the contents of every local asset it references, read from
+    /// `source`, so the registry can be rebuilt without the manifest's source.
+    fn to_snapshot(
+        &self,
+        source: &AssetSource,
+    ) -> Result<(WidgetManifest, HashMap<String, String>)> {
+        let manifest = self.to_manifest();
+        let mut assets = HashMap::new();
+        let local = |reference: &Option<String>| {
+            reference
+                .as_deref()
+                .map(str::trim)
+                .filter(|reference| !reference.is_empty() && !is_remote_path(reference))
+                .map(str::to_string)
+        };
+        for (entry, widget) in manifest.widgets.iter().zip(&self.widgets) {
+            let references = entry.assets.as_ref();
+            if let Some(reference) = local(&references.and_then(|assets| assets.html.clone())) {
+                assets.insert(reference, widget.html.clone());
+            }
+            for reference in [
+                references.and_then(|assets| assets.css.clone()),
+                references.and_then(|assets| assets.js.clone()),
+            ] {
+                if let Some(reference) = local(&reference) {
+                    let contents = String::from_utf8(source.bytes(&reference)?)
+                        .with_context(|| format!("Asset {reference} is not UTF-8"))?;
+                    assets.insert(reference, contents);
+                }
+            }
+            for (tag, locale) in &entry.locales {
+                if let (Some(reference), Some(variant)) =
+                    (local(&locale.html_path), widget.locales.get(tag))
+                {
+                    assets.insert(reference, variant.html.clone());
+                }
+            }
+        }
+        Ok((manifest, assets))
+    }
+
+    /// Returns a copy of the registry with `widget` replacing the entry that has the same id.
+    fn with_widget(&self, widget: Widget) -> Result<Self, WidgetRefreshError> {
+        if let Some(other) = self
+            .widgets_by_uri
+            .get(&widget.template_uri)
+            .filter(|other| other.id != widget.id)
+        {
+            return Err(WidgetRefreshError::DuplicateTemplateUri {
+                id: widget.id.clone(),
+                uri: other.template_uri.clone(),
+            });
+        }
+
+        let widget = Arc::new(widget);
+        let mut widgets: Vec<Arc<Widget>> = self
+            .widgets
+            .iter()
+            .map(|existing| {
+                if existing.id == widget.id {
+                    Arc::clone(&widget)
+                } else {
+                    Arc::clone(existing)
+                }
+            })
+            .collect();
+        // The refreshed entry may have moved.
+        widgets.sort_by(|a, b| listing_order(a, b));
+
+        Ok(Self {
+            widgets_by_id: widgets
+                .iter()
+                .map(|widget| (widget.id.clone(), Arc::clone(widget)))
+                .collect(),
+            widgets_by_uri: widgets
+                .iter()
+                .map(|widget| (widget.template_uri.clone(), Arc::clone(widget)))
+                .collect(),
+            widgets,
+            disabled: self.disabled.clone(),
+            unsupported: self.unsupported.clone(),
+            metadata: self.metadata.clone(),
+        })
+    }
+
     fn widget_by_id(&self, id: &str) -> Option<Arc<Widget>> {
         self.widgets_by_id.get(id).cloned()
     }
 
+    /// Finds a widget by the URI clients see, or by its manifest `ui://widget/` URI.
     fn widget_by_uri(&self, uri: &str) -> Option<Arc<Widget>> {
-        self.widgets_by_uri.get(uri).cloned()
+        self.widgets_by_uri
+            .get(uri)
+            .or_else(|| {
+                let prefix = template_uri_prefix()?;
+                self.widgets_by_uri
+                    .get(&client_template_uri(uri, Some(prefix)))
+            })
+            .cloned()
     }
 }
 
+/// How widgets are listed: by `order`, lowest first, then those without one, ties broken by id.
+fn listing_order(a: &Widget, b: &Widget) -> std::cmp::Ordering {
+    a.order
+        .is_none()
+        .cmp(&b.order.is_none())
+        .then(a.order.cmp(&b.order))
+        .then_with(|| a.id.cmp(&b.id))
+}
+
 fn log_registry_success(registry: &WidgetsRegistry) {
     let widget_count = registry.widgets.len();
     let schema = registry
@@ -196,6 +913,15 @@ fn log_registry_success(registry: &WidgetsRegistry) {
         manifest = %registry.metadata.manifest_path.display(),
         "Loaded widgets manifest"
     );
+    if !registry.disabled.is_empty() {
+        info!(disabled = ?registry.disabled, "Skipped disabled widgets");
+    }
+    if !registry.unsupported.is_empty() {
+        warn!(
+            unsupported = ?registry.unsupported.keys().collect::<Vec<_>>(),
+            "Skipped widgets this server cannot serve"
+        );
+    }
 
     if let Some(timestamp) = registry.metadata.manifest_generated_at {
         if let Ok(formatted) = timestamp.format(&Iso8601::DEFAULT) {
@@ -207,85 +933,768 @@ fn log_registry_success(registry: &WidgetsRegistry) {
     }
 }
 
-fn widget_from_entry(entry: &WidgetManifestEntry, manifest_dir: &Path) -> Result<Widget> {
+/// Lowercase hex SHA-256 of `bytes`.
+pub(crate) fn sha256_hex(bytes: &[u8]) -> String {
+    hex(&Sha256::digest(bytes))
+}
+
+/// Lowercase hex of `bytes`.
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Where a manifest's local asset references are read from.
+#[derive(Clone)]
+pub(crate) enum AssetSource {
+    /// The manifest's directory on disk.
+    Dir(PathBuf),
+    /// Contents fetched along with an object-storage manifest or kept in a registry snapshot,
+    /// keyed by reference.
+    Fetched(HashMap<String, String>),
+}
+
+impl AssetSource {
+    /// Where a local reference lives on disk; `None` for fetched assets.
+    fn local_path(&self, reference: &str) -> Option<PathBuf> {
+        match self {
+            Self::Dir(manifest_dir) => Some(manifest_dir.join(reference)),
+            Self::Fetched(_) => None,
+        }
+    }
+
+    fn for_manifest(path: &Path) -> Self {
+        Self::Dir(
+            path.parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|| PathBuf::from(".")),
+        )
+    }
+
+    fn check(&self, reference: &str) -> Result<()> {
+        match self {
+            Self::Dir(manifest_dir) => {
+                let candidate = if Path::new(reference).is_absolute() {
+                    PathBuf::from(reference)
+                } else {
+                    manifest_dir.join(reference)
+                };
+
+                if !candidate.exists() {
+                    bail!("Asset path does not exist: {}", candidate.display());
+                }
+                if !candidate.is_file() {
+                    bail!("Asset path is not a file: {}", candidate.display());
+                }
+                Ok(())
+            }
+            Self::Fetched(assets) => {
+                if !assets.contains_key(reference) {
+                    bail!("Asset object was not fetched: {reference}");
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Raw contents of a local asset, for checksum verification.
+    fn bytes(&self, reference: &str) -> Result<Vec<u8>> {
+        match self {
+            Self::Dir(manifest_dir) => {
+                let asset_path = manifest_dir.join(reference);
+                fs::read(&asset_path)
+                    .with_context(|| format!("Failed to read asset {}", asset_path.display()))
+            }
+            Self::Fetched(assets) => assets
+                .get(reference)
+                .map(|contents| contents.clone().into_bytes())
+                .with_context(|| format!("Asset object was not fetched: {reference}")),
+        }
+    }
+
+    /// Reads an HTML asset, failing before it is read when it is larger than `limits` allow.
+    fn read(&self, widget_id: &str, reference: &str, limits: &RegistryLimits) -> Result<String> {
+        match self {
+            Self::Dir(manifest_dir) => {
+                let asset_path = manifest_dir.join(reference);
+                if let Ok(metadata) = fs::metadata(&asset_path) {
+                    limits.check_html_bytes(widget_id, metadata.len())?;
+                }
+                fs::read_to_string(&asset_path).with_context(|| {
+                    format!(
+                        "Failed to read HTML asset for widget {} at {}",
+                        widget_id,
+                        asset_path.display()
+                    )
+                })
+            }
+            Self::Fetched(assets) => {
+                let html = assets.get(reference).with_context(|| {
+                    format!("HTML asset {reference} for widget {widget_id} was not fetched")
+                })?;
+                limits.check_html_bytes(widget_id, html.len() as u64)?;
+                Ok(html.clone())
+            }
+        }
+    }
+}
+
+/// The local HTML file `entry` reads, if it is missing from disk.
+fn missing_html_file(entry: &WidgetManifestEntry, source: &AssetSource) -> Option<PathBuf> {
+    let reference = entry
+        .html_path
+        .as_deref()
+        .or_else(|| entry.assets.as_ref()?.html.as_deref())
+        .map(str::trim)
+        .filter(|reference| !reference.is_empty() && !is_remote_path(reference))?;
+    source.local_path(reference).filter(|path| !path.exists())
+}
+
+/// `entry`'s widget as loaded into `fallback`, marked degraded, when `entry`'s HTML file is gone.
+/// The widget keeps its earlier definition until the file is back: serving the cached HTML beats
+/// failing every read mid-session.
+fn cached_widget(
+    fallback: &WidgetsRegistry,
+    entry: &WidgetManifestEntry,
+    source: &AssetSource,
+) -> Option<Widget> {
+    let missing = missing_html_file(entry, source)?;
+    let mut widget = Widget::clone(fallback.widget_by_id(entry.id.trim())?.as_ref());
+    if widget.degraded.is_some() {
+        return Some(widget);
+    }
+    let reason = format!(
+        "HTML file {} is missing; serving the copy loaded earlier",
+        missing.display()
+    );
+    warn!(widget_id = %widget.id, "{reason}");
+    widget.warnings.push(reason.clone());
+    widget.html_file = Some(missing);
+    widget.degraded = Some(reason);
+    Some(widget)
+}
+
+fn widget_from_entry(
+    entry: &WidgetManifestEntry,
+    source: &AssetSource,
+    limits: &RegistryLimits,
+) -> Result<Widget> {
     if entry.id.trim().is_empty() {
         bail!("Widget entry missing id");
     }
     if entry.template_uri.trim().is_empty() {
         bail!("Widget entry missing templateUri for {}", entry.id);
     }
-    if entry.html.trim().is_empty() {
-        bail!("Widget entry missing html for {}", entry.id);
+    let html_path = entry
+        .html_path
+        .as_deref()
+        .map(str::trim)
+        .filter(|path| !path.is_empty());
+    if entry.html.trim().is_empty() && html_path.is_none() {
+        bail!("Widget entry missing html or htmlPath for {}", entry.id);
+    }
+    if let Some(path) = html_path.filter(|path| is_remote_path(path)) {
+        bail!(
+            "htmlPath for widget {} must be a local file, not {path}; put URLs in html",
+            entry.id
+        );
     }
 
+    let manifest_assets = entry.assets.clone().unwrap_or_default();
+    let checksums = manifest_assets.sha256.unwrap_or_default();
     let assets = WidgetAssets {
         html: validate_asset_path(
-            entry.assets.as_ref().and_then(|a| a.html.as_deref()),
-            manifest_dir,
+            manifest_assets.html.as_deref(),
+            checksums.html.as_deref(),
+            source,
         )
         .context("validating html asset")?,
         css: validate_asset_path(
-            entry.assets.as_ref().and_then(|a| a.css.as_deref()),
-            manifest_dir,
+            manifest_assets.css.as_deref(),
+            checksums.css.as_deref(),
+            source,
         )
         .context("validating css asset")?,
         js: validate_asset_path(
-            entry.assets.as_ref().and_then(|a| a.js.as_deref()),
-            manifest_dir,
+            manifest_assets.js.as_deref(),
+            checksums.js.as_deref(),
+            source,
         )
         .context("validating js asset")?,
     };
 
+    let rate_limit = entry
+        .rate_limit
+        .as_deref()
+        .map(|raw| parse_rate_limit(raw).map_err(anyhow::Error::msg))
+        .transpose()
+        .with_context(|| format!("Invalid rateLimit for widget {}", entry.id))?;
+
     let html_source_url = entry.html.trim().to_string();
-    let html = match assets.html.as_deref() {
-        Some(reference) if !is_remote_path(reference) => {
-            let asset_path = manifest_dir.join(reference);
-            fs::read_to_string(&asset_path).with_context(|| {
-                format!(
-                    "Failed to read HTML asset for widget {} at {}",
-                    entry.id,
-                    asset_path.display()
-                )
-            })?
+    let mut warnings = Vec::new();
+    let html_file = html_path
+        .or(assets.html.as_deref())
+        .filter(|reference| !is_remote_path(reference))
+        .and_then(|reference| source.local_path(reference));
+    let html = match (html_path, assets.html.as_deref()) {
+        (Some(path), _) => {
+            source.check(path).context("validating htmlPath")?;
+            source.read(&entry.id, path, limits)?
         }
-        Some(reference) => {
+        (None, Some(reference)) if !is_remote_path(reference) => {
+            source.read(&entry.id, reference, limits)?
+        }
+        (None, Some(reference)) => {
             warn!(
                 widget_id = %entry.id,
                 "HTML asset reference '{}' is remote; using manifest URL as fallback",
                 reference
             );
+            warnings.push(format!(
+                "HTML asset reference '{reference}' is remote; using manifest URL as fallback"
+            ));
             html_source_url.clone()
         }
-        None => {
+        (None, None) => {
             warn!(
                 widget_id = %entry.id,
                 "Widget missing HTML asset reference; using manifest URL as fallback"
             );
+            warnings.push(
+                "Widget missing HTML asset reference; using manifest URL as fallback".to_string(),
+            );
             html_source_url.clone()
         }
     };
+    limits.check_html_bytes(&entry.id, html.len() as u64)?;
+
+    warnings.extend(
+        crate::scan::check(&entry.id, &html)
+            .with_context(|| format!("Widget {} failed the content scan", entry.id))?,
+    );
+    // A bare URL fallback is not HTML, so there is nothing to lint.
+    if !is_remote_path(html.trim()) {
+        warnings.extend(
+            crate::lint::check(&entry.id, &html)
+                .with_context(|| format!("Widget {} failed the template lint", entry.id))?,
+        );
+    }
+
+    let content_hash = sha256_hex(html.as_bytes());
+    let signature = crate::signing::sign(&html);
+
+    let input_schema = match &entry.input_schema {
+        Some(schema) => validate_input_schema(schema)
+            .with_context(|| format!("Invalid inputSchema for widget {}", entry.id))?,
+        None => default_input_schema(),
+    };
+
+    let csp = entry
+        .csp
+        .as_ref()
+        .map(validate_csp)
+        .transpose()
+        .with_context(|| format!("Invalid csp for widget {}", entry.id))?;
+    let widget_domain = entry
+        .widget_domain
+        .as_deref()
+        .map(str::trim)
+        .filter(|domain| !domain.is_empty())
+        .map(|domain| validate_origin(domain, false))
+        .transpose()
+        .with_context(|| format!("Invalid widgetDomain for widget {}", entry.id))?;
+    let meta_overrides = match &entry.meta {
+        Some(meta) => validate_meta_overrides(meta)
+            .with_context(|| format!("Invalid meta for widget {}", entry.id))?,
+        None => serde_json::Map::new(),
+    };
+    let deprecation_message = entry
+        .deprecation_message
+        .as_deref()
+        .map(str::trim)
+        .filter(|message| !message.is_empty());
+    let deprecation = match (entry.deprecated, deprecation_message) {
+        (true, message) => Some(message.unwrap_or(DEFAULT_DEPRECATION_MESSAGE).to_string()),
+        (false, Some(_)) => {
+            warn!(
+                widget_id = %entry.id,
+                "deprecationMessage is ignored because deprecated is not set"
+            );
+            warnings
+                .push("deprecationMessage is ignored because deprecated is not set".to_string());
+            None
+        }
+        (false, None) => None,
+    };
 
-    Ok(Widget {
+    let template_values = input_schema_defaults(&input_schema);
+    let mut widget = Widget {
         id: entry.id.trim().to_string(),
         title: entry.title.trim().to_string(),
-        template_uri: entry.template_uri.trim().to_string(),
+        template_uri: client_template_uri(entry.template_uri.trim(), template_uri_prefix()),
         invoking: entry.invoking.trim().to_string(),
         invoked: entry.invoked.trim().to_string(),
         html,
         response_text: entry.response_text.trim().to_string(),
         assets,
-    })
+        allowed_roles: entry
+            .allowed_roles
+            .iter()
+            .map(|role| role.trim().to_string())
+            .filter(|role| !role.is_empty())
+            .collect(),
+        rate_limit,
+        tags: normalize_tags(&entry.tags),
+        order: entry.order,
+        content_hash,
+        signature,
+        input_schema,
+        warnings,
+        html_file,
+        degraded: None,
+        locales: BTreeMap::new(),
+        locale: None,
+        deprecation,
+        csp,
+        widget_domain,
+        meta_overrides,
+        template_values,
+        depends_on: normalize_dependencies(&entry.depends_on),
+    };
+    validate_placeholders(&widget)
+        .with_context(|| format!("Invalid placeholder in widget {}", entry.id))?;
+    add_locale_variants(&mut widget, entry, source, limits)?;
+    Ok(widget)
 }
 
-fn validate_schema_version(schema: &str) -> Result<()> {
-    let version = Version::parse(schema)
-        .with_context(|| format!("Invalid schemaVersion in widget manifest: {schema}"))?;
-    if version.major != SUPPORTED_SCHEMA_MAJOR {
-        bail!(
-            "Unsupported schemaVersion: {} (expected major {})",
-            schema,
-            SUPPORTED_SCHEMA_MAJOR
-        );
+/// Lowercase form of a locale tag such as `pt_BR`, or `None` when it is not one.
+fn normalize_locale(tag: &str) -> Option<String> {
+    let tag = tag.trim().replace('_', "-").to_ascii_lowercase();
+    let valid = tag.split('-').all(|subtag| {
+        (1..=8).contains(&subtag.len()) && subtag.bytes().all(|byte| byte.is_ascii_alphanumeric())
+    });
+    valid.then_some(tag)
+}
+
+/// Builds `widget`'s variants for the manifest entry's `locales`. Text the locale leaves out is
+/// taken from the widget, and locale HTML is scanned, linted and signed like the widget's own.
+fn add_locale_variants(
+    widget: &mut Widget,
+    entry: &WidgetManifestEntry,
+    source: &AssetSource,
+    limits: &RegistryLimits,
+) -> Result<()> {
+    for (tag, overrides) in &entry.locales {
+        let key = normalize_locale(tag)
+            .with_context(|| format!("Invalid locale tag {tag:?} for widget {}", widget.id))?;
+        if widget.locales.contains_key(&key) {
+            bail!("Duplicate locale {tag} for widget {}", widget.id);
+        }
+        let text = |value: &Option<String>, fallback: &str| {
+            value
+                .as_deref()
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .unwrap_or(fallback)
+                .to_string()
+        };
+        let mut variant = Widget {
+            title: text(&overrides.title, &widget.title),
+            invoking: text(&overrides.invoking, &widget.invoking),
+            invoked: text(&overrides.invoked, &widget.invoked),
+            response_text: text(&overrides.response_text, &widget.response_text),
+            warnings: Vec::new(),
+            locales: BTreeMap::new(),
+            locale: Some(key.clone()),
+            ..widget.clone()
+        };
+        validate_placeholders(&variant)
+            .with_context(|| format!("Invalid placeholder in widget {} locale {tag}", widget.id))?;
+
+        let html_path = overrides
+            .html_path
+            .as_deref()
+            .map(str::trim)
+            .filter(|path| !path.is_empty());
+        if let Some(path) = html_path {
+            if is_remote_path(path) {
+                bail!(
+                    "htmlPath for widget {} locale {tag} must be a local file, not {path}",
+                    widget.id
+                );
+            }
+            source
+                .check(path)
+                .with_context(|| format!("validating htmlPath for locale {tag}"))?;
+            let label = format!("{} ({key})", widget.id);
+            let html = source.read(&label, path, limits)?;
+            let mut warnings = crate::scan::check(&label, &html)
+                .with_context(|| format!("Widget {label} failed the content scan"))?;
+            warnings.extend(
+                crate::lint::check(&label, &html)
+                    .with_context(|| format!("Widget {label} failed the template lint"))?,
+            );
+            widget.warnings.extend(
+                warnings
+                    .into_iter()
+                    .map(|warning| format!("Locale {key}: {warning}")),
+            );
+            variant.content_hash = sha256_hex(html.as_bytes());
+            variant.signature = crate::signing::sign(&html);
+            variant.html_file = source.local_path(path);
+            variant.html = html;
+        }
+        widget.locales.insert(key, Arc::new(variant));
+    }
+    Ok(())
+}
+
+/// Trimmed dependency ids in manifest order, without blanks or repeats.
+fn normalize_dependencies(ids: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::with_capacity(ids.len());
+    for id in ids.iter().map(|id| id.trim()).filter(|id| !id.is_empty()) {
+        if !normalized.iter().any(|existing| existing == id) {
+            normalized.push(id.to_string());
+        }
+    }
+    normalized
+}
+
+/// Checks every widget's `dependsOn` against the rest of the registry.
+///
+/// A dependency on a widget this server cannot serve is not an error: the dependent is returned,
+/// with the reason, to be skipped along with it. Dependencies on disabled or unknown widgets and
+/// dependency cycles fail, since the manifest would break the flow they belong to.
+fn unmet_dependencies(
+    widgets: &[Arc<Widget>],
+    disabled: &[String],
+    unsupported: &BTreeMap<String, String>,
+) -> Result<BTreeMap<String, String>> {
+    let graph: BTreeMap<&str, &[String]> = widgets
+        .iter()
+        .map(|widget| (widget.id.as_str(), widget.depends_on.as_slice()))
+        .collect();
+    for (id, dependencies) in &graph {
+        for dependency in dependencies.iter() {
+            if dependency == id {
+                bail!("Widget {id} depends on itself");
+            }
+            if graph.contains_key(dependency.as_str()) || unsupported.contains_key(dependency) {
+                continue;
+            }
+            if disabled.contains(dependency) {
+                bail!("Widget {id} depends on {dependency}, which is disabled");
+            }
+            bail!("Widget {id} depends on {dependency}, which is not in the manifest");
+        }
+    }
+
+    let mut skipped = BTreeMap::new();
+    loop {
+        let newly_skipped: Vec<(String, String)> = graph
+            .iter()
+            .filter(|(id, _)| !skipped.contains_key(**id))
+            .filter_map(|(id, dependencies)| {
+                let dependency = dependencies.iter().find(|dependency| {
+                    unsupported.contains_key(*dependency) || skipped.contains_key(*dependency)
+                })?;
+                Some((
+                    id.to_string(),
+                    format!("depends on {dependency}, which this server cannot serve"),
+                ))
+            })
+            .collect();
+        if newly_skipped.is_empty() {
+            break;
+        }
+        skipped.extend(newly_skipped);
+    }
+
+    if let Some(cycle) = dependency_cycle(&graph) {
+        bail!("Widget dependency cycle: {}", cycle.join(" -> "));
+    }
+    Ok(skipped)
+}
+
+/// The first cycle in `graph`, as the ids around it with the first repeated at the end.
+fn dependency_cycle(graph: &BTreeMap<&str, &[String]>) -> Option<Vec<String>> {
+    fn visit<'a>(
+        id: &'a str,
+        graph: &BTreeMap<&'a str, &'a [String]>,
+        path: &mut Vec<&'a str>,
+        done: &mut HashSet<&'a str>,
+    ) -> Option<Vec<String>> {
+        if done.contains(id) {
+            return None;
+        }
+        if let Some(start) = path.iter().position(|visiting| *visiting == id) {
+            let mut cycle: Vec<String> = path[start..].iter().map(|id| id.to_string()).collect();
+            cycle.push(id.to_string());
+            return Some(cycle);
+        }
+        path.push(id);
+        for dependency in graph.get(id).copied().unwrap_or_default() {
+            if let Some(cycle) = visit(dependency, graph, path, done) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+        done.insert(id);
+        None
+    }
+
+    let mut done = HashSet::new();
+    graph
+        .keys()
+        .find_map(|id| visit(id, graph, &mut Vec::new(), &mut done))
+}
+
+fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let tag = tag.trim().to_ascii_lowercase();
+        if !tag.is_empty() && !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    normalized
+}
+
+/// Input schema for widgets whose manifest entry does not declare one.
+pub fn default_input_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "object",
+        "properties": {
+            "pizzaTopping": {
+                "type": "string",
+                "description": "Topping to mention when rendering the widget."
+            }
+        },
+        "required": ["pizzaTopping"],
+        "additionalProperties": false
+    })
+}
+
+/// `_meta` keys the server derives from other fields, which a manifest `meta` may not replace.
+const RESERVED_META_KEYS: &[&str] = &[
+    "openai/outputTemplate",
+    "openai/toolInvocation/invoking",
+    "openai/toolInvocation/invoked",
+    LOCALE_META_KEY,
+    CSP_META_KEY,
+    WIDGET_DOMAIN_META_KEY,
+];
+
+/// Normalizes every origin in `csp`, dropping duplicates.
+fn validate_csp(csp: &WidgetCsp) -> Result<WidgetCsp> {
+    let directive = |name: &str, sources: &[String]| -> Result<Vec<String>> {
+        let mut origins: Vec<String> = Vec::with_capacity(sources.len());
+        for source in sources {
+            let origin = validate_origin(source, true).with_context(|| format!("In {name}"))?;
+            if !origins.contains(&origin) {
+                origins.push(origin);
+            }
+        }
+        Ok(origins)
+    };
+    Ok(WidgetCsp {
+        connect_domains: directive("connectDomains", &csp.connect_domains)?,
+        resource_domains: directive("resourceDomains", &csp.resource_domains)?,
+        frame_domains: directive("frameDomains", &csp.frame_domains)?,
+    })
+}
+
+/// Checks that `source` is a bare origin such as `https://api.example.com:8443`, returning it
+/// lowercased without a trailing slash. CSP sources may also use `wss://` and a leading `*.`
+/// wildcard; a widget domain may not.
+fn validate_origin(source: &str, csp_source: bool) -> Result<String> {
+    let origin = source.trim().trim_end_matches('/').to_ascii_lowercase();
+    let malformed = || format!("{source:?} is not an origin such as https://example.com");
+    let (scheme, authority) = origin.split_once("://").with_context(malformed)?;
+    let schemes: &[&str] = if csp_source {
+        &["https", "wss"]
+    } else {
+        &["https"]
+    };
+    if !schemes.contains(&scheme) {
+        bail!("{source:?} must use {}", schemes.join(" or "));
+    }
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (host, Some(port)),
+        None => (authority, None),
+    };
+    if port.is_some_and(|port| port.parse::<u16>().is_err()) {
+        bail!("{source:?} has an invalid port");
+    }
+    let host = match host.strip_prefix("*.") {
+        Some(rest) if csp_source => rest,
+        _ => host,
+    };
+    let valid_host = !host.is_empty()
+        && host.split('.').all(|label| {
+            (1..=63).contains(&label.len())
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .bytes()
+                    .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-')
+        });
+    if !valid_host {
+        bail!(malformed());
+    }
+    Ok(origin)
+}
+
+fn validate_meta_overrides(
+    meta: &serde_json::Map<String, serde_json::Value>,
+) -> Result<serde_json::Map<String, serde_json::Value>> {
+    for key in meta.keys() {
+        if RESERVED_META_KEYS.contains(&key.as_str()) || key.starts_with("pizzaz/") {
+            bail!("{key} is set by the server and cannot be overridden");
+        }
+    }
+    Ok(meta.clone())
+}
+
+/// Checks that every placeholder in the widget's text names an input schema property.
+fn validate_placeholders(widget: &Widget) -> Result<()> {
+    let properties = widget
+        .input_schema
+        .get("properties")
+        .and_then(serde_json::Value::as_object);
+    for (field, text) in [
+        ("invoking", &widget.invoking),
+        ("invoked", &widget.invoked),
+        ("responseText", &widget.response_text),
+    ] {
+        for (_, name) in placeholders(text) {
+            if !properties.is_some_and(|properties| properties.contains_key(name)) {
+                bail!("{field} uses {{{{{name}}}}}, which is not an inputSchema property");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The `default` of each input schema property that has one.
+fn input_schema_defaults(schema: &serde_json::Value) -> serde_json::Map<String, serde_json::Value> {
+    let mut defaults = serde_json::Value::Object(serde_json::Map::new());
+    crate::arguments::apply_defaults(schema, &mut defaults)
+}
+
+/// The `{{name}}` placeholders in `template`, as the byte range they cover and the trimmed name.
+/// An unclosed `{{` is plain text.
+fn placeholders(template: &str) -> impl Iterator<Item = (std::ops::Range<usize>, &str)> {
+    let mut offset = 0;
+    std::iter::from_fn(move || {
+        let start = offset + template[offset..].find("{{")?;
+        let end = start + 2 + template[start + 2..].find("}}")? + 2;
+        offset = end;
+        Some((start..end, template[start + 2..end - 2].trim()))
+    })
+}
+
+/// Replaces each `{{name}}` in `template` with `values[name]`. Strings are inserted as they are,
+/// arrays as their items separated by commas, and missing or `null` values as nothing.
+pub fn render_template(
+    template: &str,
+    values: &serde_json::Map<String, serde_json::Value>,
+) -> String {
+    fn text(value: &serde_json::Value) -> String {
+        match value {
+            serde_json::Value::Null => String::new(),
+            serde_json::Value::String(text) => text.clone(),
+            serde_json::Value::Array(items) => {
+                items.iter().map(text).collect::<Vec<_>>().join(", ")
+            }
+            other => other.to_string(),
+        }
+    }
+
+    let mut rendered = String::with_capacity(template.len());
+    let mut copied = 0;
+    for (range, name) in placeholders(template) {
+        rendered.push_str(&template[copied..range.start]);
+        rendered.push_str(&values.get(name).map(text).unwrap_or_default());
+        copied = range.end;
+    }
+    rendered.push_str(&template[copied..]);
+    rendered
+}
+
+fn validate_input_schema(schema: &serde_json::Value) -> Result<serde_json::Value> {
+    if schema.get("type").and_then(serde_json::Value::as_str) != Some("object") {
+        bail!("inputSchema must have \"type\": \"object\"");
+    }
+    if let Some(properties) = schema.get("properties") {
+        if !properties.is_object() {
+            bail!("inputSchema properties must be an object");
+        }
+    }
+    // Calls are parsed into `ToolInput`, so every schema-valid call must carry a topping string.
+    let topping = schema.pointer("/properties/pizzaTopping");
+    if topping.and_then(|topping| topping.get("type")) != Some(&serde_json::json!("string")) {
+        bail!("inputSchema must declare a \"pizzaTopping\" property of type \"string\"");
+    }
+    let required = schema
+        .get("required")
+        .and_then(serde_json::Value::as_array)
+        .is_some_and(|required| required.iter().any(|name| name == "pizzaTopping"));
+    let defaulted = topping
+        .and_then(|topping| topping.get("default"))
+        .is_some_and(serde_json::Value::is_string);
+    if !required && !defaulted {
+        bail!("inputSchema must list \"pizzaTopping\" in required or give it a string default");
+    }
+    Ok(schema.clone())
+}
+
+/// Why this server cannot serve `entry`, or `None` when it meets the entry's `requires`.
+///
+/// A requirement that does not parse fails the load; one this server does not meet only skips the
+/// widget, since it is usually written for a newer server.
+fn unmet_requirements(entry: &WidgetManifestEntry) -> Result<Option<String>> {
+    let Some(requires) = &entry.requires else {
+        return Ok(None);
+    };
+    let mut unmet = Vec::new();
+    if let Some(raw) = requires.schema_version.as_deref().map(str::trim) {
+        let requirement = VersionReq::parse(raw).with_context(|| {
+            format!(
+                "Invalid requires.schemaVersion {raw:?} for widget {}",
+                entry.id.trim()
+            )
+        })?;
+        let server = Version::parse(SERVER_SCHEMA_VERSION).expect("server schema version is valid");
+        if !requirement.matches(&server) {
+            unmet.push(format!(
+                "requires schema version {raw}, server implements {SERVER_SCHEMA_VERSION}"
+            ));
+        }
+    }
+    let missing: Vec<&str> = requires
+        .features
+        .iter()
+        .map(|feature| feature.trim())
+        .filter(|feature| !SERVER_FEATURES.contains(feature))
+        .collect();
+    if !missing.is_empty() {
+        unmet.push(format!(
+            "requires unsupported features {}",
+            missing.join(", ")
+        ));
+    }
+    Ok((!unmet.is_empty()).then(|| unmet.join("; ")))
+}
+
+fn validate_schema_version(schema: &str) -> Result<()> {
+    let version = Version::parse(schema)
+        .with_context(|| format!("Invalid schemaVersion in widget manifest: {schema}"))?;
+    if version.major != SUPPORTED_SCHEMA_MAJOR {
+        bail!(
+            "Unsupported schemaVersion: {} (expected major {})",
+            schema,
+            SUPPORTED_SCHEMA_MAJOR
+        );
     }
     Ok(())
 }
@@ -301,37 +1710,41 @@ fn file_timestamp(path: &Path) -> Option<OffsetDateTime> {
         .map(OffsetDateTime::from)
 }
 
-fn validate_asset_path(asset: Option<&str>, manifest_dir: &Path) -> Result<Option<String>> {
-    let Some(raw) = asset else {
+/// Checks that a local asset exists and, when the manifest gives one, matches its checksum.
+fn validate_asset_path(
+    asset: Option<&str>,
+    expected_sha256: Option<&str>,
+    source: &AssetSource,
+) -> Result<Option<String>> {
+    let Some(trimmed) = asset.map(str::trim).filter(|asset| !asset.is_empty()) else {
+        if expected_sha256.is_some() {
+            bail!("sha256 is set but the asset reference is missing");
+        }
         return Ok(None);
     };
 
-    let trimmed = raw.trim();
-    if trimmed.is_empty() {
-        return Ok(None);
-    }
-
     if is_remote_path(trimmed) {
+        if expected_sha256.is_some() {
+            bail!("sha256 can only be verified for local assets, not {trimmed}");
+        }
         return Ok(Some(trimmed.to_string()));
     }
 
-    let candidate = if Path::new(trimmed).is_absolute() {
-        PathBuf::from(trimmed)
-    } else {
-        manifest_dir.join(trimmed)
-    };
-
-    if !candidate.exists() {
-        bail!("Asset path does not exist: {}", candidate.display());
-    }
-    if !candidate.is_file() {
-        bail!("Asset path is not a file: {}", candidate.display());
+    source.check(trimmed)?;
+    if let Some(expected) = expected_sha256 {
+        let expected = expected.trim().to_ascii_lowercase();
+        let actual = sha256_hex(&source.bytes(trimmed)?);
+        if actual != expected {
+            bail!(
+                "Checksum mismatch for {trimmed}: manifest expects sha256 {expected}, file has {actual}; \
+                 the manifest and the deployed assets are out of sync"
+            );
+        }
     }
-
     Ok(Some(trimmed.to_string()))
 }
 
-fn is_remote_path(value: &str) -> bool {
+pub(crate) fn is_remote_path(value: &str) -> bool {
     value.starts_with("http://") || value.starts_with("https://") || value.starts_with("//")
 }
 
@@ -339,44 +1752,375 @@ fn now_utc() -> OffsetDateTime {
     OffsetDateTime::now_utc()
 }
 
-static WIDGETS_MANIFEST_PATH: LazyLock<PathBuf> = LazyLock::new(resolve_manifest_path);
+// Mutable so operators can repoint the server without a restart (admin API or SIGHUP).
+static WIDGETS_MANIFEST_PATH: LazyLock<RwLock<PathBuf>> =
+    LazyLock::new(|| RwLock::new(resolve_manifest_path()));
 
-// TODO we need to handle the case when the manifest is stored remotely in cloud storage
-fn resolve_manifest_path() -> PathBuf {
+/// Resolves the manifest path from `WIDGETS_MANIFEST_PATH`, falling back to the default.
+///
+/// With the `object-storage` feature the variable may also hold an `s3://` or `gs://` URL.
+pub fn resolve_manifest_path() -> PathBuf {
     std::env::var("WIDGETS_MANIFEST_PATH")
         .map(PathBuf::from)
         .unwrap_or_else(|_| PathBuf::from("../assets/widgets.json"))
 }
 
-static REGISTRY: LazyLock<RwLock<Arc<WidgetsRegistry>>> = LazyLock::new(|| {
+/// The overlay manifest from `WIDGETS_MANIFEST_OVERLAY_PATH`, if set.
+///
+/// The variable is read on every load, so a reload after `.env` changes picks up a new overlay.
+pub fn manifest_overlay_path() -> Option<PathBuf> {
+    std::env::var("WIDGETS_MANIFEST_OVERLAY_PATH")
+        .ok()
+        .filter(|path| !path.trim().is_empty())
+        .map(PathBuf::from)
+}
+
+/// Where the last good copy of an object-storage manifest is kept, from
+/// `WIDGETS_MANIFEST_CACHE_PATH`, if set.
+///
+/// Like the overlay path, the variable is read on every load.
+pub fn manifest_cache_path() -> Option<PathBuf> {
+    std::env::var("WIDGETS_MANIFEST_CACHE_PATH")
+        .ok()
+        .filter(|path| !path.trim().is_empty())
+        .map(PathBuf::from)
+}
+
+/// The active registry. Readers never block: a reload builds the new registry off to the side and
+/// swaps the pointer, while requests already holding the old one finish with it.
+static REGISTRY: LazyLock<ArcSwap<WidgetsRegistry>> = LazyLock::new(|| {
     let manifest_path = manifest_path();
-    RwLock::new(Arc::new(WidgetsRegistry::empty(manifest_path)))
+    ArcSwap::from_pointee(WidgetsRegistry::empty(manifest_path))
 });
 
-/// Returns the configured manifest path.
+/// Returns the active manifest path.
 pub fn manifest_path() -> PathBuf {
-    WIDGETS_MANIFEST_PATH.clone()
+    WIDGETS_MANIFEST_PATH
+        .read()
+        .expect("manifest path lock poisoned")
+        .clone()
+}
+
+fn set_manifest_path(path: PathBuf) {
+    *WIDGETS_MANIFEST_PATH
+        .write()
+        .expect("manifest path lock poisoned") = path;
 }
 
 /// Returns a clone of the current registry (cheap due to Arc).
 pub fn registry() -> Arc<WidgetsRegistry> {
-    REGISTRY.read().expect("registry lock poisoned").clone()
+    REGISTRY.load_full()
+}
+
+/// Swaps in `new_registry` and returns how it differs from the registry it replaced.
+fn swap_registry(new_registry: Arc<WidgetsRegistry>) -> RegistryDiff {
+    let now = OffsetDateTime::now_utc();
+    CHANGELOG
+        .lock()
+        .expect("widget changelog lock poisoned")
+        .record(&new_registry, now);
+    let previous = REGISTRY.swap(Arc::clone(&new_registry));
+    let diff = RegistryDiff::between(&previous, &new_registry);
+    *LAST_DIFF.lock().expect("registry diff lock poisoned") = Some(RecordedDiff {
+        diff: diff.clone(),
+        recorded_at: now,
+    });
+    // Sending fails only when nobody is subscribed.
+    let _ = REGISTRY_CHANGES.send(RegistryChange { diff: diff.clone() });
+    diff
+}
+
+/// Sent to [`subscribe_registry_changes`] receivers each time a registry is installed.
+#[derive(Debug, Clone)]
+pub struct RegistryChange {
+    /// Empty when the new registry serves the same widgets as the old one.
+    pub diff: RegistryDiff,
+}
+
+/// Receivers that fall further behind than this see `RecvError::Lagged`.
+const REGISTRY_CHANGE_CAPACITY: usize = 16;
+
+static REGISTRY_CHANGES: LazyLock<broadcast::Sender<RegistryChange>> =
+    LazyLock::new(|| broadcast::channel(REGISTRY_CHANGE_CAPACITY).0);
+
+/// Subscribes to registry swaps: reloads, manifest path switches, rollbacks and single-widget
+/// refreshes. Only swaps made after the call are received.
+pub fn subscribe_registry_changes() -> broadcast::Receiver<RegistryChange> {
+    REGISTRY_CHANGES.subscribe()
+}
+
+/// Widgets that differ between two registries, each list sorted by id.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RegistryDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<WidgetDiff>,
+}
+
+/// A widget present in both registries whose definition changed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WidgetDiff {
+    pub id: String,
+    /// Fields that changed; `html` means the content hash changed.
+    pub fields: Vec<&'static str>,
+    pub previous_hash: String,
+    pub current_hash: String,
+}
+
+impl RegistryDiff {
+    pub fn between(previous: &WidgetsRegistry, current: &WidgetsRegistry) -> Self {
+        let mut diff = Self::default();
+        for widget in &current.widgets {
+            let Some(before) = previous.widgets_by_id.get(&widget.id) else {
+                diff.added.push(widget.id.clone());
+                continue;
+            };
+            let fields = changed_fields(before, widget);
+            if !fields.is_empty() {
+                diff.changed.push(WidgetDiff {
+                    id: widget.id.clone(),
+                    fields,
+                    previous_hash: before.content_hash.clone(),
+                    current_hash: widget.content_hash.clone(),
+                });
+            }
+        }
+        diff.removed = previous
+            .widgets
+            .iter()
+            .filter(|widget| !current.widgets_by_id.contains_key(&widget.id))
+            .map(|widget| widget.id.clone())
+            .collect();
+        diff.added.sort();
+        diff.removed.sort();
+        diff.changed.sort_by(|a, b| a.id.cmp(&b.id));
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+fn changed_fields(before: &Widget, after: &Widget) -> Vec<&'static str> {
+    let comparisons = [
+        ("html", before.content_hash != after.content_hash),
+        ("title", before.title != after.title),
+        ("templateUri", before.template_uri != after.template_uri),
+        ("invoking", before.invoking != after.invoking),
+        ("invoked", before.invoked != after.invoked),
+        ("responseText", before.response_text != after.response_text),
+        ("inputSchema", before.input_schema != after.input_schema),
+        ("allowedRoles", before.allowed_roles != after.allowed_roles),
+        ("rateLimit", before.rate_limit != after.rate_limit),
+        ("tags", before.tags != after.tags),
+        ("order", before.order != after.order),
+        ("deprecated", before.deprecation != after.deprecation),
+        ("csp", before.csp != after.csp),
+        ("widgetDomain", before.widget_domain != after.widget_domain),
+        ("meta", before.meta_overrides != after.meta_overrides),
+        ("dependsOn", before.depends_on != after.depends_on),
+        ("locales", locales_changed(before, after)),
+    ];
+    comparisons
+        .into_iter()
+        .filter_map(|(field, changed)| changed.then_some(field))
+        .collect()
+}
+
+fn locales_changed(before: &Widget, after: &Widget) -> bool {
+    !before.locales.keys().eq(after.locales.keys())
+        || before
+            .locales
+            .values()
+            .zip(after.locales.values())
+            .any(|(before, after)| !changed_fields(before, after).is_empty())
+}
+
+/// The diff computed by the most recent registry swap.
+#[derive(Debug, Clone)]
+pub struct RecordedDiff {
+    pub diff: RegistryDiff,
+    pub recorded_at: OffsetDateTime,
+}
+
+static LAST_DIFF: Mutex<Option<RecordedDiff>> = Mutex::new(None);
+
+/// How the active registry differs from the one it replaced, or `None` before the first swap.
+pub fn last_registry_diff() -> Option<RecordedDiff> {
+    LAST_DIFF
+        .lock()
+        .expect("registry diff lock poisoned")
+        .clone()
+}
+
+/// Registries replaced by reloads, switches and widget refreshes, newest last.
+struct RegistryHistory {
+    depth: usize,
+    entries: VecDeque<Arc<WidgetsRegistry>>,
+}
+
+impl RegistryHistory {
+    fn new(depth: usize) -> Self {
+        Self {
+            depth,
+            entries: VecDeque::with_capacity(depth),
+        }
+    }
+
+    /// Remembers `registry`, dropping the oldest entry beyond `depth`. Empty registries are skipped.
+    fn record(&mut self, registry: Arc<WidgetsRegistry>) {
+        if self.depth == 0 || !registry.metadata.registry_initialized {
+            return;
+        }
+        if self.entries.len() == self.depth {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(registry);
+    }
+}
+
+/// How many replaced registries are kept for rollback, from `WIDGETS_REGISTRY_HISTORY`.
+///
+/// Defaults to 5; `0` disables rollback.
+pub fn registry_history_depth() -> usize {
+    std::env::var("WIDGETS_REGISTRY_HISTORY")
+        .ok()
+        .and_then(|value| value.trim().parse::<usize>().ok())
+        .unwrap_or(5)
+}
+
+static HISTORY: LazyLock<Mutex<RegistryHistory>> =
+    LazyLock::new(|| Mutex::new(RegistryHistory::new(registry_history_depth())));
+
+/// Swaps in `new_registry`, keeping the one it replaces for [`rollback_registry`].
+fn replace_registry(new_registry: Arc<WidgetsRegistry>) -> RegistryDiff {
+    let previous = registry();
+    HISTORY
+        .lock()
+        .expect("registry history lock poisoned")
+        .record(previous);
+    swap_registry(new_registry)
+}
+
+/// Number of registries [`rollback_registry`] can currently step back through.
+pub fn registry_history_len() -> usize {
+    HISTORY
+        .lock()
+        .expect("registry history lock poisoned")
+        .entries
+        .len()
+}
+
+/// A version of a widget's HTML, recorded when a registry serving it was swapped in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WidgetChange {
+    pub content_hash: String,
+    pub size_bytes: usize,
+    pub recorded_at: OffsetDateTime,
+    /// Manifest the registry was loaded from, and its `generatedAt`.
+    pub manifest_path: PathBuf,
+    pub manifest_generated_at: Option<OffsetDateTime>,
+}
+
+/// Per-widget content changes across reloads, oldest first, at most `depth` per widget.
+///
+/// Widgets removed from the manifest keep their history, so it still answers when they last
+/// changed.
+struct WidgetChangelog {
+    depth: usize,
+    entries: HashMap<String, VecDeque<WidgetChange>>,
+}
+
+impl WidgetChangelog {
+    fn new(depth: usize) -> Self {
+        Self {
+            depth,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Records every widget in `registry` whose content hash differs from its last entry.
+    fn record(&mut self, registry: &WidgetsRegistry, now: OffsetDateTime) {
+        if self.depth == 0 || !registry.metadata.registry_initialized {
+            return;
+        }
+        for widget in &registry.widgets {
+            let changes = self.entries.entry(widget.id.clone()).or_default();
+            if changes
+                .back()
+                .is_some_and(|last| last.content_hash == widget.content_hash)
+            {
+                continue;
+            }
+            if changes.len() == self.depth {
+                changes.pop_front();
+            }
+            changes.push_back(WidgetChange {
+                content_hash: widget.content_hash.clone(),
+                size_bytes: widget.html.len(),
+                recorded_at: now,
+                manifest_path: registry.metadata.manifest_path.clone(),
+                manifest_generated_at: registry.metadata.manifest_generated_at,
+            });
+        }
+    }
 }
 
-fn swap_registry(new_registry: Arc<WidgetsRegistry>) {
-    let mut lock = REGISTRY.write().expect("registry lock poisoned");
-    *lock = new_registry;
+/// How many content changes are kept per widget, from `WIDGETS_CHANGELOG_DEPTH`.
+///
+/// Defaults to 20; `0` disables the changelog.
+pub fn widget_changelog_depth() -> usize {
+    std::env::var("WIDGETS_CHANGELOG_DEPTH")
+        .ok()
+        .and_then(|value| value.trim().parse::<usize>().ok())
+        .unwrap_or(20)
+}
+
+static CHANGELOG: LazyLock<Mutex<WidgetChangelog>> =
+    LazyLock::new(|| Mutex::new(WidgetChangelog::new(widget_changelog_depth())));
+
+/// Content changes recorded for widget `id`, newest first; empty when it was never served.
+pub fn widget_history(id: &str) -> Vec<WidgetChange> {
+    CHANGELOG
+        .lock()
+        .expect("widget changelog lock poisoned")
+        .entries
+        .get(id)
+        .map(|changes| changes.iter().rev().cloned().collect())
+        .unwrap_or_default()
 }
 
 /// Attempts to bootstrap the registry from disk during startup.
+///
+/// The manifest path is re-read from the environment so it reflects the latest configuration.
 pub fn bootstrap_registry() {
-    let path = manifest_path();
-    match load_registry_from_path(&path) {
+    let path = resolve_manifest_path();
+    set_manifest_path(path.clone());
+    #[cfg(feature = "object-storage")]
+    if crate::object_storage::ObjectUrl::parse(&path).is_some() {
+        bootstrap_from_object_storage(path);
+        return;
+    }
+    let started = Instant::now();
+    let result = read_local_manifest(&path).and_then(|(manifest, assets)| {
+        let registry = build_registry(&path, manifest, &assets, None)?;
+        write_snapshot(&path, &registry, &assets);
+        Ok(registry)
+    });
+    record_load(started, &result);
+    match result.or_else(|error| snapshot_fallback(&path, error)) {
         Ok(registry) => {
             log_registry_success(&registry);
             swap_registry(Arc::new(registry));
         }
         Err(LoadError::NotFound { path }) => {
+            #[cfg(feature = "embedded-manifest")]
+            if let Some(registry) = embedded_registry(&path) {
+                log_registry_success(&registry);
+                swap_registry(Arc::new(registry));
+                return;
+            }
             warn!(
                 manifest = %path.display(),
                 "No widgets available - manifest not found at {}",
@@ -394,71 +2138,991 @@ pub fn bootstrap_registry() {
     }
 }
 
-/// Attempts to load a registry from the given path.
-pub fn load_registry_from_path(path: &Path) -> Result<WidgetsRegistry, LoadError> {
-    if !path.exists() {
-        return Err(LoadError::NotFound {
-            path: path.to_path_buf(),
-        });
+/// The registry built from the manifest compiled into the binary, standing in for the missing
+/// manifest at `path`. See [`crate::embedded`].
+#[cfg(feature = "embedded-manifest")]
+fn embedded_registry(path: &Path) -> Option<WidgetsRegistry> {
+    let loaded = crate::embedded::manifest().and_then(|(manifest, assets)| {
+        build_registry(path, manifest, &AssetSource::Fetched(assets), None)
+            .map_err(|error| anyhow::anyhow!("{error}"))
+    });
+    match loaded {
+        Ok(mut registry) => {
+            let reason = format!(
+                "manifest not found at {}; serving the manifest embedded at build time",
+                path.display()
+            );
+            warn!(manifest = %path.display(), reason = %reason, "Widget manifest missing");
+            registry.metadata.degraded = Some(reason);
+            Some(registry)
+        }
+        Err(error) => {
+            error!(error = %format!("{error:#}"), "Embedded widget manifest failed to load");
+            None
+        }
     }
+}
 
-    let manifest = read_manifest(path).map_err(|error| LoadError::Validation {
-        path: path.to_path_buf(),
-        error,
-    })?;
-
-    let registry = WidgetsRegistry::from_manifest(manifest, path.to_path_buf(), now_utc())
-        .map_err(|error| LoadError::Validation {
-            path: path.to_path_buf(),
-            error,
-        })?;
+/// Fetching needs the runtime, so an object-storage manifest is loaded in the background. Use
+/// `WIDGETS_PRELOAD` to have it in place before the listener binds.
+#[cfg(feature = "object-storage")]
+fn bootstrap_from_object_storage(path: PathBuf) {
+    let current = registry_metadata();
+    if current.registry_initialized && current.manifest_path == path {
+        return;
+    }
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+        warn!(
+            manifest = %path.display(),
+            "No Tokio runtime; object-storage manifest will load on the first refresh"
+        );
+        return;
+    };
+    runtime.spawn(async move {
+        if let Err(error) = reload_registry().await.result {
+            error!(error = %error, "Failed to load widget manifest from object storage");
+        }
+    });
+}
 
-    Ok(registry)
+/// Attempts to load a registry from the manifest file at the given path.
+pub fn load_registry_from_path(path: &Path) -> Result<WidgetsRegistry, LoadError> {
+    let (manifest, assets) = read_local_manifest(path)?;
+    build_registry(path, manifest, &assets, None)
 }
 
-/// Outcome of a successful registry reload.
-#[derive(Debug, Clone)]
-pub struct RegistryReloadOutcome {
-    pub widget_count: usize,
-    pub schema_version: Option<String>,
-    pub manifest_timestamp: Option<OffsetDateTime>,
+/// Loads a registry from `path`, which may also be an object-storage URL when the
+/// `object-storage` feature is enabled.
+///
+/// When `path` is the manifest already loaded, a widget whose HTML file has disappeared keeps
+/// the HTML from the current registry and is marked degraded rather than failing the load.
+pub async fn load_registry(path: &Path) -> Result<WidgetsRegistry, LoadError> {
+    let started = Instant::now();
+    let result = async {
+        let source = read_manifest_with_assets(path).await?;
+        let current = registry();
+        let fallback = (current.metadata.registry_initialized
+            && current.metadata.manifest_path == path)
+            .then_some(current.as_ref());
+        let mut registry = build_registry(path, source.manifest, &source.assets, fallback)?;
+        if source.degraded.is_none() {
+            save_snapshot(path, &registry, &source.assets).await;
+        }
+        registry.metadata.degraded = source.degraded;
+        registry.metadata.source_validators = source.validators;
+        #[cfg(feature = "object-storage")]
+        if let (Some((url, data)), AssetSource::Fetched(assets), Some(cache)) =
+            (&source.fetched, &source.assets, manifest_cache_path())
+        {
+            if let Err(error) = crate::object_storage::store_cached(&cache, url, data, assets) {
+                warn!(
+                    cache = %cache.display(),
+                    error = %error,
+                    "Failed to cache widget manifest"
+                );
+            }
+        }
+        Ok(registry)
+    }
+    .await;
+    record_load(started, &result);
+    result
 }
 
-/// Reloads the registry from disk and swaps it into place.
-pub fn reload_registry() -> Result<RegistryReloadOutcome, LoadError> {
-    let path = manifest_path();
-    let registry = load_registry_from_path(&path)?;
+/// Writes `registry`, loaded from `path`, to the configured snapshot file on the blocking pool,
+/// so the async load path never waits on disk.
+async fn save_snapshot(path: &Path, registry: &WidgetsRegistry, source: &AssetSource) {
+    if snapshot::snapshot_path().is_none() {
+        return;
+    }
+    let (path, registry, source) = (path.to_path_buf(), registry.clone(), source.clone());
+    if let Err(error) =
+        tokio::task::spawn_blocking(move || write_snapshot(&path, &registry, &source)).await
+    {
+        warn!(error = %error, "Registry snapshot task failed");
+    }
+}
 
-    let outcome = RegistryReloadOutcome {
-        widget_count: registry.widgets.len(),
-        schema_version: registry.metadata.schema_version.clone(),
-        manifest_timestamp: registry.metadata.manifest_generated_at,
+/// Writes `registry`, loaded from `path`, to the configured snapshot file. Blocking: local assets
+/// are read and the file written with `std::fs`. Failures are logged: the registry itself loaded
+/// fine.
+fn write_snapshot(path: &Path, registry: &WidgetsRegistry, source: &AssetSource) {
+    let Some(file) = snapshot::snapshot_path() else {
+        return;
     };
+    let saved = registry
+        .to_snapshot(source)
+        .and_then(|(manifest, assets)| snapshot::store(&file, path, manifest, assets));
+    if let Err(error) = saved {
+        warn!(
+            snapshot = %file.display(),
+            error = %format!("{error:#}"),
+            "Failed to write registry snapshot"
+        );
+    }
+}
 
-    log_registry_success(&registry);
-    swap_registry(Arc::new(registry));
-
-    Ok(outcome)
+/// Loads the registry snapshot in place of `path` when loading it failed with `error` because its
+/// source is unreachable, and no registry from `path` is serving yet; with one serving, a failed
+/// load keeps it instead. Otherwise, or without a usable snapshot, `error` is returned.
+fn snapshot_fallback(path: &Path, error: LoadError) -> Result<WidgetsRegistry, LoadError> {
+    let current = registry();
+    let serving = current.metadata.registry_initialized && current.metadata.manifest_path == path;
+    let Some(file) =
+        snapshot::snapshot_path().filter(|_| !serving && snapshot::is_unreachable(&error))
+    else {
+        return Err(error);
+    };
+    let loaded = snapshot::load(&file, path).and_then(|snapshot| {
+        let assets = AssetSource::Fetched(snapshot.assets);
+        let registry = build_registry(path, snapshot.manifest, &assets, None)
+            .map_err(|error| anyhow::anyhow!("{error}"))?;
+        Ok((registry, snapshot.saved_at))
+    });
+    match loaded {
+        Ok((mut registry, saved_at)) => {
+            let reason = format!(
+                "{error}; serving the registry snapshot saved at {} from {saved_at}",
+                file.display()
+            );
+            warn!(
+                manifest = %path.display(),
+                reason = %reason,
+                "Widget manifest source unreachable"
+            );
+            registry.metadata.degraded = Some(reason);
+            Ok(registry)
+        }
+        Err(snapshot_error) => {
+            warn!(
+                snapshot = %file.display(),
+                error = %format!("{snapshot_error:#}"),
+                "No usable registry snapshot"
+            );
+            Err(error)
+        }
+    }
 }
 
-/// Returns all available widgets.
-pub fn get_all_widgets() -> Vec<Arc<Widget>> {
-    registry().widgets()
+/// Counters for registry loads since the process started, so alerts can catch reloads that
+/// start failing while the previous registry keeps serving.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct LoadMetrics {
+    /// Loads started at bootstrap, by preload, refresh, watcher, schedule, path switch or publish.
+    pub attempts: u64,
+    pub successes: u64,
+    /// Failed loads by [`LoadError::kind`].
+    pub failures: BTreeMap<&'static str, u64>,
+    /// Failed loads since the last successful one.
+    pub consecutive_failures: u64,
+    /// How long the most recent load took, successful or not.
+    pub last_duration_ms: Option<u64>,
+    /// ISO 8601 time and message of the most recent failure.
+    pub last_failure_at: Option<String>,
+    pub last_error: Option<String>,
+    /// HTML bytes read by every successful load.
+    pub html_bytes_loaded: u64,
+    /// HTML bytes held by the active registry.
+    pub html_bytes: u64,
 }
 
-/// Looks up a widget by its ID (tool name).
-pub fn get_widget_by_id(id: &str) -> Option<Arc<Widget>> {
-    registry().widget_by_id(id)
+static LOAD_METRICS: LazyLock<Mutex<LoadMetrics>> = LazyLock::new(Mutex::default);
+
+fn record_load(started: Instant, result: &Result<WidgetsRegistry, LoadError>) {
+    LOAD_METRICS
+        .lock()
+        .expect("load metrics lock poisoned")
+        .record(started.elapsed(), result);
+}
+
+impl LoadMetrics {
+    fn record(&mut self, duration: Duration, result: &Result<WidgetsRegistry, LoadError>) {
+        self.attempts += 1;
+        self.last_duration_ms = Some(duration.as_millis() as u64);
+        match result {
+            Ok(registry) => {
+                self.successes += 1;
+                self.consecutive_failures = 0;
+                self.html_bytes_loaded += registry.html_bytes();
+            }
+            Err(error) => {
+                *self.failures.entry(error.kind()).or_default() += 1;
+                self.consecutive_failures += 1;
+                self.last_failure_at = now_utc().format(&Iso8601::DEFAULT).ok();
+                self.last_error = Some(error.to_string());
+            }
+        }
+    }
+}
+
+/// Load counters, with `html_bytes` taken from the active registry.
+pub fn load_metrics() -> LoadMetrics {
+    let mut metrics = LOAD_METRICS
+        .lock()
+        .expect("load metrics lock poisoned")
+        .clone();
+    metrics.html_bytes = REGISTRY.load().html_bytes();
+    metrics
+}
+
+fn build_registry(
+    path: &Path,
+    manifest: WidgetManifest,
+    assets: &AssetSource,
+    fallback: Option<&WidgetsRegistry>,
+) -> Result<WidgetsRegistry, LoadError> {
+    WidgetsRegistry::from_manifest(
+        manifest,
+        path.to_path_buf(),
+        assets,
+        fallback,
+        &RegistryLimits::from_env(),
+        DuplicatePolicy::from_env(),
+        now_utc(),
+    )
+    .map_err(|error| LoadError::Validation {
+        path: path.to_path_buf(),
+        error,
+    })
+}
+
+/// A manifest read for loading.
+struct ManifestSource {
+    manifest: WidgetManifest,
+    /// Where its asset references resolve.
+    assets: AssetSource,
+    /// Why this is a cached copy rather than the manifest itself.
+    degraded: Option<String>,
+    /// See [`RegistryMetadata::source_validators`].
+    validators: BTreeMap<String, String>,
+    /// The object-storage URL and manifest object, when freshly fetched, to cache once it loads.
+    #[cfg(feature = "object-storage")]
+    fetched: Option<(crate::object_storage::ObjectUrl, Vec<u8>)>,
+}
+
+/// Reads the manifest at `path` and where its asset references resolve.
+async fn read_manifest_with_assets(path: &Path) -> Result<ManifestSource, LoadError> {
+    #[cfg(feature = "object-storage")]
+    if let Some(url) = crate::object_storage::ObjectUrl::parse(path) {
+        let (fetched, degraded) = fetch_or_cached(path, &url).await?;
+        let manifest = apply_overlay(path, fetched.manifest)?;
+        return Ok(ManifestSource {
+            manifest,
+            assets: AssetSource::Fetched(fetched.assets),
+            validators: fetched.validators,
+            fetched: degraded.is_none().then_some((url, fetched.data)),
+            degraded,
+        });
+    }
+    let (manifest, assets) = read_local_manifest(path)?;
+    Ok(ManifestSource {
+        manifest,
+        assets,
+        degraded: None,
+        validators: BTreeMap::new(),
+        #[cfg(feature = "object-storage")]
+        fetched: None,
+    })
+}
+
+/// Fetches the manifest at `url`, or reads the cached copy when the bucket is unreachable and no
+/// registry from `path` is serving yet; with one serving, a failed load keeps it instead.
+///
+/// The reason the copy stands in is returned along with it.
+#[cfg(feature = "object-storage")]
+async fn fetch_or_cached(
+    path: &Path,
+    url: &crate::object_storage::ObjectUrl,
+) -> Result<(crate::object_storage::FetchedManifest, Option<String>), LoadError> {
+    use crate::object_storage;
+
+    let error = match object_storage::fetch_manifest(url).await {
+        Ok(fetched) => return Ok((fetched, None)),
+        Err(error) => error,
+    };
+    let current = registry();
+    let serving = current.metadata.registry_initialized && current.metadata.manifest_path == path;
+    let Some(cache) = manifest_cache_path().filter(|_| !serving) else {
+        return Err(error);
+    };
+    if !object_storage::is_unavailable(&error) {
+        return Err(error);
+    }
+    match object_storage::load_cached(&cache, url) {
+        Ok((fetched, fetched_at)) => {
+            let reason = format!(
+                "{error}; serving the copy cached at {} from {fetched_at}",
+                cache.display()
+            );
+            warn!(
+                manifest = %path.display(),
+                reason = %reason,
+                "Widget manifest source unreachable"
+            );
+            Ok((fetched, Some(reason)))
+        }
+        Err(cache_error) => {
+            warn!(
+                cache = %cache.display(),
+                error = %cache_error,
+                "No usable cached widget manifest"
+            );
+            Err(error)
+        }
+    }
+}
+
+fn read_local_manifest(path: &Path) -> Result<(WidgetManifest, AssetSource), LoadError> {
+    if !path.exists() {
+        return Err(LoadError::NotFound {
+            path: path.to_path_buf(),
+        });
+    }
+
+    let manifest = read_manifest(path).map_err(|error| LoadError::Validation {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    let manifest = apply_overlay(path, manifest)?;
+    Ok((manifest, AssetSource::for_manifest(path)))
+}
+
+/// Patches `manifest` with the configured overlay. Overlay asset paths resolve against the base
+/// manifest, like its own.
+fn apply_overlay(path: &Path, manifest: WidgetManifest) -> Result<WidgetManifest, LoadError> {
+    let Some(overlay_path) = manifest_overlay_path() else {
+        return Ok(manifest);
+    };
+    read_overlay(&overlay_path)
+        .and_then(|overlay| overlay.apply(manifest))
+        .with_context(|| {
+            format!(
+                "Failed to apply manifest overlay {}",
+                overlay_path.display()
+            )
+        })
+        .map_err(|error| LoadError::Validation {
+            path: path.to_path_buf(),
+            error,
+        })
+}
+
+/// Outcome of a successful registry reload.
+#[derive(Debug, Clone)]
+pub struct RegistryReloadOutcome {
+    pub widget_count: usize,
+    pub schema_version: Option<String>,
+    pub manifest_timestamp: Option<OffsetDateTime>,
+    /// Changes against the registry this one replaced.
+    pub diff: RegistryDiff,
+    /// Widgets skipped because this server cannot meet their `requires`, with the reason.
+    pub unsupported: BTreeMap<String, String>,
+    /// True when the manifest source reported nothing changed since the active registry loaded,
+    /// so it was kept without reading the manifest.
+    pub not_modified: bool,
+}
+
+/// Result of a serialized reload request.
+#[derive(Debug)]
+pub struct ReloadReport {
+    pub result: Result<RegistryReloadOutcome, LoadError>,
+    /// True when the request was answered by a reload that started after it arrived.
+    pub deduplicated: bool,
+}
+
+#[derive(Debug)]
+struct CompletedReload {
+    sequence: u64,
+    path: PathBuf,
+    result: Result<RegistryReloadOutcome, LoadError>,
+}
+
+// Reloads run one at a time under this lock, which also remembers the last completed reload.
+static RELOAD_LOCK: tokio::sync::Mutex<Option<CompletedReload>> =
+    tokio::sync::Mutex::const_new(None);
+static RELOADS_STARTED: AtomicU64 = AtomicU64::new(0);
+
+/// Reloads the registry from disk and swaps it into place.
+///
+/// Concurrent callers are serialized. A caller that queued behind a reload which started after
+/// it arrived already observes a fresh read of the manifest, so it shares that result instead
+/// of loading again.
+pub async fn reload_registry() -> ReloadReport {
+    let arrived_after = RELOADS_STARTED.load(Ordering::SeqCst);
+    let mut last = RELOAD_LOCK.lock().await;
+    let path = manifest_path();
+
+    if let Some(completed) = last
+        .as_ref()
+        .filter(|completed| completed.sequence > arrived_after && completed.path == path)
+    {
+        debug!(manifest = %path.display(), "Coalescing duplicate registry reload");
+        return ReloadReport {
+            result: duplicate_result(&completed.result),
+            deduplicated: true,
+        };
+    }
+
+    let sequence = RELOADS_STARTED.fetch_add(1, Ordering::SeqCst) + 1;
+    let result = match unchanged_source(&path).await {
+        Some(outcome) => Ok(outcome),
+        None => load_registry(&path)
+            .await
+            .or_else(|error| snapshot_fallback(&path, error))
+            .map(install_registry),
+    };
+    *last = Some(CompletedReload {
+        sequence,
+        path,
+        result: duplicate_result(&result),
+    });
+
+    ReloadReport {
+        result,
+        deduplicated: false,
+    }
+}
+
+pub(crate) fn duplicate_result(
+    result: &Result<RegistryReloadOutcome, LoadError>,
+) -> Result<RegistryReloadOutcome, LoadError> {
+    match result {
+        Ok(outcome) => Ok(outcome.clone()),
+        Err(error) => Err(error.duplicate()),
+    }
+}
+
+fn install_registry(registry: WidgetsRegistry) -> RegistryReloadOutcome {
+    let mut outcome = RegistryReloadOutcome::of(&registry);
+    log_registry_success(&registry);
+    outcome.diff = replace_registry(Arc::new(registry));
+    outcome
+}
+
+impl RegistryReloadOutcome {
+    fn of(registry: &WidgetsRegistry) -> Self {
+        Self {
+            widget_count: registry.widgets.len(),
+            schema_version: registry.metadata.schema_version.clone(),
+            manifest_timestamp: registry.metadata.manifest_generated_at,
+            diff: RegistryDiff::default(),
+            unsupported: registry.unsupported.clone(),
+            not_modified: false,
+        }
+    }
+}
+
+/// The outcome of keeping the active registry, when it came from `path` in object storage and
+/// conditional requests show none of its objects changed since; `None` when the manifest must be
+/// loaded.
+///
+/// An overlay can change without the bucket objects changing, so with one configured every
+/// reload loads the manifest.
+async fn unchanged_source(path: &Path) -> Option<RegistryReloadOutcome> {
+    #[cfg(feature = "object-storage")]
+    {
+        let url = crate::object_storage::ObjectUrl::parse(path)?;
+        let current = registry();
+        let metadata = &current.metadata;
+        if !metadata.registry_initialized
+            || metadata.manifest_path != path
+            || metadata.source_validators.is_empty()
+            || manifest_overlay_path().is_some()
+        {
+            return None;
+        }
+        match crate::object_storage::unchanged(&url, &metadata.source_validators).await {
+            Ok(true) => {
+                debug!(
+                    manifest = %path.display(),
+                    "Widget manifest not modified; keeping registry"
+                );
+                let mut outcome = RegistryReloadOutcome::of(&current);
+                outcome.not_modified = true;
+                Some(outcome)
+            }
+            Ok(false) => None,
+            Err(error) => {
+                debug!(error = %error, "Conditional manifest check failed; reloading");
+                None
+            }
+        }
+    }
+    #[cfg(not(feature = "object-storage"))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+/// Result of stepping back to the previous registry.
+#[derive(Debug, Clone)]
+pub struct RegistryRollback {
+    pub outcome: RegistryReloadOutcome,
+    /// Manifest the restored registry was loaded from; it becomes the active path again.
+    pub manifest_path: PathBuf,
+    /// Older registries still available for further rollbacks.
+    pub remaining: usize,
+}
+
+/// Swaps the registry back to the one it most recently replaced.
+///
+/// Returns `None` when no earlier registry is kept. The registry being rolled back from is
+/// discarded, so repeated calls step further back. The active manifest path follows the restored
+/// registry, so a later reload reads the manifest it came from.
+pub async fn rollback_registry() -> Option<RegistryRollback> {
+    let mut last = RELOAD_LOCK.lock().await;
+    let (previous, remaining) = {
+        let mut history = HISTORY.lock().expect("registry history lock poisoned");
+        let previous = history.entries.pop_back()?;
+        (previous, history.entries.len())
+    };
+    // Reloads queued before the rollback must read the manifest again, not share an older result.
+    *last = None;
+
+    let path = previous.metadata.manifest_path.clone();
+    let current = manifest_path();
+    if current != path {
+        info!(
+            from = %current.display(),
+            to = %path.display(),
+            "Switching widget manifest path"
+        );
+        set_manifest_path(path.clone());
+    }
+    let mut outcome = RegistryReloadOutcome::of(&previous);
+    info!(
+        manifest = %path.display(),
+        widgets = outcome.widget_count,
+        remaining,
+        "Rolled back widget registry"
+    );
+    outcome.diff = swap_registry(previous);
+
+    Some(RegistryRollback {
+        outcome,
+        manifest_path: path,
+        remaining,
+    })
+}
+
+/// Errors returned when refreshing a single widget.
+#[derive(Debug, thiserror::Error)]
+pub enum WidgetRefreshError {
+    #[error("Widget manifest has never been loaded; run a full refresh first")]
+    RegistryNotLoaded,
+    #[error("Widget {0} is not loaded; run a full refresh to add new widgets")]
+    UnknownWidget(String),
+    #[error("Widget {0} is no longer present in the manifest")]
+    MissingFromManifest(String),
+    #[error("Widget {0} is disabled in the manifest; run a full refresh to unload it")]
+    Disabled(String),
+    #[error(
+        "Widget {id} cannot be served by this server ({reason}); run a full refresh to unload it"
+    )]
+    Unsupported { id: String, reason: String },
+    #[error("Widget {id} uses template URI {uri}, which belongs to another widget")]
+    DuplicateTemplateUri { id: String, uri: String },
+    #[error("Failed to refresh widget {id}: {error:#}")]
+    Invalid { id: String, error: anyhow::Error },
+}
+
+/// Reloads one widget's manifest entry and assets, leaving every other widget untouched.
+///
+/// Only the target entry is validated, so this is much quicker than a full reload while
+/// iterating on a single widget. Adding or removing widgets still requires a full reload.
+pub async fn refresh_widget(id: &str) -> Result<Arc<Widget>, WidgetRefreshError> {
+    let _serialized = RELOAD_LOCK.lock().await;
+    let current = registry();
+    if !current.metadata.registry_initialized {
+        return Err(WidgetRefreshError::RegistryNotLoaded);
+    }
+    if current.widget_by_id(id).is_none() {
+        return Err(WidgetRefreshError::UnknownWidget(id.to_string()));
+    }
+
+    let path = current.metadata.manifest_path.clone();
+    let invalid = |error| WidgetRefreshError::Invalid {
+        id: id.to_string(),
+        error,
+    };
+    let ManifestSource {
+        manifest, assets, ..
+    } = read_manifest_with_assets(&path)
+        .await
+        .map_err(|error| invalid(error.into()))?;
+    let entry = manifest
+        .widgets
+        .iter()
+        .find(|entry| entry.id.trim() == id)
+        .ok_or_else(|| WidgetRefreshError::MissingFromManifest(id.to_string()))?;
+    if !entry.enabled {
+        return Err(WidgetRefreshError::Disabled(id.to_string()));
+    }
+    if let Some(reason) = unmet_requirements(entry).map_err(invalid)? {
+        return Err(WidgetRefreshError::Unsupported {
+            id: id.to_string(),
+            reason,
+        });
+    }
+    let limits = RegistryLimits::from_env();
+    let widget = match widget_from_entry(entry, &assets, &limits) {
+        Ok(widget) => widget,
+        Err(error) => cached_widget(&current, entry, &assets).ok_or_else(|| invalid(error))?,
+    };
+
+    let updated = current.with_widget(widget)?;
+    limits
+        .check_registry_bytes(updated.html_bytes())
+        .map_err(invalid)?;
+    let skipped = unmet_dependencies(&updated.widgets, &updated.disabled, &updated.unsupported)
+        .map_err(invalid)?;
+    if let Some(reason) = skipped.get(id) {
+        return Err(WidgetRefreshError::Unsupported {
+            id: id.to_string(),
+            reason: reason.clone(),
+        });
+    }
+    let widget = updated
+        .widget_by_id(id)
+        .expect("refreshed widget is present in the updated registry");
+    info!(
+        widget_id = %id,
+        content_hash = %widget.content_hash,
+        "Refreshed widget from manifest"
+    );
+    replace_registry(Arc::new(updated));
+
+    Ok(widget)
+}
+
+/// Loads the manifest at `path` and, only if it is valid, makes it the active manifest path.
+///
+/// On failure both the previous path and the previous registry stay in place. Switches are
+/// serialized with [`reload_registry`] but never coalesced.
+pub async fn switch_manifest_path(path: PathBuf) -> Result<RegistryReloadOutcome, LoadError> {
+    let mut last = RELOAD_LOCK.lock().await;
+    let sequence = RELOADS_STARTED.fetch_add(1, Ordering::SeqCst) + 1;
+    let result = switch_manifest_path_locked(path.clone()).await;
+    *last = Some(CompletedReload {
+        sequence,
+        path,
+        result: duplicate_result(&result),
+    });
+    result
+}
+
+async fn switch_manifest_path_locked(path: PathBuf) -> Result<RegistryReloadOutcome, LoadError> {
+    let registry = load_registry(&path).await?;
+    let previous = manifest_path();
+    if previous != path {
+        info!(
+            from = %previous.display(),
+            to = %path.display(),
+            "Switching widget manifest path"
+        );
+    }
+    set_manifest_path(path);
+    Ok(install_registry(registry))
+}
+
+/// Startup preloading, enabled with `WIDGETS_PRELOAD=1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PreloadConfig {
+    pub timeout: Duration,
+    pub retry_interval: Duration,
+}
+
+impl PreloadConfig {
+    /// Returns the preload settings, or `None` when preloading is not enabled.
+    pub fn from_env() -> Option<Self> {
+        crate::env_flag("WIDGETS_PRELOAD").then(Self::configured)
+    }
+
+    /// The preload settings from `WIDGETS_PRELOAD_TIMEOUT_SECS`, for callers that preload
+    /// whether or not `WIDGETS_PRELOAD` is set.
+    pub fn configured() -> Self {
+        let timeout = std::env::var("WIDGETS_PRELOAD_TIMEOUT_SECS")
+            .ok()
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(Duration::from_secs(30));
+
+        Self {
+            timeout,
+            retry_interval: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Loads the manifest before the server binds its listener, retrying until `config.timeout`.
+///
+/// Covers deploys where the manifest is still being copied into place when the process starts.
+pub async fn preload_registry(config: PreloadConfig) -> Result<RegistryReloadOutcome, LoadError> {
+    let path = manifest_path();
+    let registry = load_with_retry(&path, config)
+        .await
+        .or_else(|error| snapshot_fallback(&path, error))?;
+    Ok(install_registry(registry))
+}
+
+async fn load_with_retry(path: &Path, config: PreloadConfig) -> Result<WidgetsRegistry, LoadError> {
+    let deadline = tokio::time::Instant::now() + config.timeout;
+    loop {
+        match load_registry(path).await {
+            Ok(registry) => return Ok(registry),
+            Err(error) if tokio::time::Instant::now() + config.retry_interval > deadline => {
+                return Err(error)
+            }
+            Err(error) => {
+                warn!(error = %error, "Widget manifest not ready; retrying preload");
+                tokio::time::sleep(config.retry_interval).await;
+            }
+        }
+    }
+}
+
+/// Automatic reloads when the manifest file changes on disk.
+///
+/// On by default; `WIDGETS_WATCH_MANIFEST=0` turns it off. The manifest's size and modification
+/// time are polled, so this also works on network and container filesystems without inotify.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ManifestWatchConfig {
+    pub poll_interval: Duration,
+    /// How long the file must stay unchanged before it is reloaded, so half-written deploys are
+    /// not picked up.
+    pub debounce: Duration,
+}
+
+impl ManifestWatchConfig {
+    /// Returns the watch settings, or `None` when watching is disabled.
+    pub fn from_env() -> Option<Self> {
+        let disabled = std::env::var("WIDGETS_WATCH_MANIFEST").is_ok_and(|value| {
+            matches!(
+                value.trim().to_ascii_lowercase().as_str(),
+                "0" | "false" | "no" | "off"
+            )
+        });
+        if disabled {
+            return None;
+        }
+        let millis = |variable: &str, default: u64| {
+            std::env::var(variable)
+                .ok()
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .filter(|millis| *millis > 0)
+                .map(Duration::from_millis)
+                .unwrap_or(Duration::from_millis(default))
+        };
+        Some(Self {
+            poll_interval: millis("WIDGETS_WATCH_INTERVAL_MS", 1000),
+            debounce: millis("WIDGETS_WATCH_DEBOUNCE_MS", 500),
+        })
+    }
+}
+
+/// Size and modification time of a manifest file; `None` while it is missing.
+type ManifestFingerprint = Option<(u64, SystemTime)>;
+
+fn manifest_fingerprint(path: &Path) -> ManifestFingerprint {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.len(), metadata.modified().ok()?))
+}
+
+/// Decides when a watched manifest has changed and settled.
+#[derive(Debug)]
+pub struct ManifestWatcher {
+    debounce: Duration,
+    path: PathBuf,
+    seen: ManifestFingerprint,
+    changed_at: Option<Instant>,
+}
+
+impl ManifestWatcher {
+    /// Starts watching `path` from its current state.
+    pub fn new(path: PathBuf, debounce: Duration) -> Self {
+        Self {
+            debounce,
+            seen: manifest_fingerprint(&path),
+            path,
+            changed_at: None,
+        }
+    }
+
+    /// Checks `path` (the active manifest path) and returns true when it should be reloaded.
+    ///
+    /// A switch to another path only resets the baseline, since switching already loaded it.
+    pub fn poll(&mut self, path: &Path, now: Instant) -> bool {
+        if path != self.path {
+            *self = Self::new(path.to_path_buf(), self.debounce);
+            return false;
+        }
+        let current = manifest_fingerprint(path);
+        if current != self.seen {
+            self.seen = current;
+            self.changed_at = Some(now);
+            return false;
+        }
+        match self.changed_at {
+            Some(changed_at) if now.duration_since(changed_at) >= self.debounce => {
+                self.changed_at = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Full reloads on a schedule, for environments that cannot reach the refresh endpoint.
+///
+/// Off unless `WIDGETS_REFRESH_INTERVAL` is set to a duration such as `300`, `90s`, `5m` or `1h`.
+/// Each wait adds a random delay of up to `WIDGETS_REFRESH_JITTER` (a tenth of the interval by
+/// default), so instances started together do not all hit the manifest source at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScheduledRefreshConfig {
+    pub interval: Duration,
+    pub jitter: Duration,
+}
+
+impl ScheduledRefreshConfig {
+    /// Returns the schedule, or `None` when scheduled reloads are off.
+    pub fn from_env() -> Option<Self> {
+        let raw = std::env::var("WIDGETS_REFRESH_INTERVAL").ok()?;
+        let interval = parse_interval(&raw)
+            .map_err(|error| {
+                warn!(error = %error, "Ignoring invalid WIDGETS_REFRESH_INTERVAL");
+            })
+            .ok()
+            .filter(|interval| !interval.is_zero())?;
+        let default_jitter = interval / 10;
+        let jitter = match std::env::var("WIDGETS_REFRESH_JITTER") {
+            Ok(raw) => parse_interval(&raw).unwrap_or_else(|error| {
+                warn!(error = %error, "Ignoring invalid WIDGETS_REFRESH_JITTER");
+                default_jitter
+            }),
+            Err(_) => default_jitter,
+        };
+        Some(Self { interval, jitter })
+    }
+
+    /// The wait before the next reload: the interval plus `sample` reduced into the jitter.
+    pub fn next_delay(&self, sample: u64) -> Duration {
+        let jitter_ms = self.jitter.as_millis() as u64;
+        self.interval + Duration::from_millis(sample % (jitter_ms + 1))
+    }
+}
+
+/// Parses `300`, `90s`, `5m` or `1h`; bare numbers are seconds, and `off` is zero.
+pub fn parse_interval(raw: &str) -> Result<Duration, String> {
+    let raw = raw.trim();
+    if raw.eq_ignore_ascii_case("off") {
+        return Ok(Duration::ZERO);
+    }
+    let (magnitude, unit_secs) = match raw.char_indices().last() {
+        Some((index, 's' | 'S')) => (&raw[..index], 1),
+        Some((index, 'm' | 'M')) => (&raw[..index], 60),
+        Some((index, 'h' | 'H')) => (&raw[..index], 3600),
+        _ => (raw, 1),
+    };
+    magnitude
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .and_then(|magnitude| magnitude.checked_mul(unit_secs))
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("invalid interval '{raw}'; expected e.g. 300, 90s, 5m or 1h"))
+}
+
+/// Outcomes of scheduled reloads since the process started.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ScheduledRefreshMetrics {
+    /// Reloads the schedule ran, successful or not.
+    pub runs: u64,
+    pub failures: u64,
+    /// Failed scheduled reloads since the last successful one.
+    pub consecutive_failures: u64,
+    /// Runs skipped because the server was read-only.
+    pub skipped: u64,
+    /// ISO 8601 times of the latest run and the next one.
+    pub last_run_at: Option<String>,
+    pub next_run_at: Option<String>,
+    pub last_error: Option<String>,
+}
+
+/// `None` until a schedule is running.
+static SCHEDULED_REFRESH: Mutex<Option<ScheduledRefreshMetrics>> = Mutex::new(None);
+
+fn update_scheduled_refresh(update: impl FnOnce(&mut ScheduledRefreshMetrics)) {
+    update(
+        SCHEDULED_REFRESH
+            .lock()
+            .expect("scheduled refresh lock poisoned")
+            .get_or_insert_with(Default::default),
+    );
+}
+
+/// Records that the next scheduled reload runs after `delay`.
+pub fn schedule_refresh(delay: Duration) {
+    let next = now_utc() + delay;
+    update_scheduled_refresh(|metrics| metrics.next_run_at = next.format(&Iso8601::DEFAULT).ok());
+}
+
+/// Records a scheduled reload: `None` when it was skipped.
+pub fn record_scheduled_refresh(result: Option<&Result<RegistryReloadOutcome, LoadError>>) {
+    update_scheduled_refresh(|metrics| {
+        let Some(result) = result else {
+            metrics.skipped += 1;
+            return;
+        };
+        metrics.runs += 1;
+        metrics.last_run_at = now_utc().format(&Iso8601::DEFAULT).ok();
+        match result {
+            Ok(_) => metrics.consecutive_failures = 0,
+            Err(error) => {
+                metrics.failures += 1;
+                metrics.consecutive_failures += 1;
+                metrics.last_error = Some(error.to_string());
+            }
+        }
+    });
+}
+
+/// Scheduled reload counters, or `None` when no schedule is running.
+pub fn scheduled_refresh_metrics() -> Option<ScheduledRefreshMetrics> {
+    SCHEDULED_REFRESH
+        .lock()
+        .expect("scheduled refresh lock poisoned")
+        .clone()
+}
+
+/// Returns all available widgets.
+pub fn get_all_widgets() -> Vec<Arc<Widget>> {
+    REGISTRY.load().widgets()
+}
+
+/// Looks up a widget by its ID (tool name).
+pub fn get_widget_by_id(id: &str) -> Option<Arc<Widget>> {
+    REGISTRY.load().widget_by_id(id)
+}
+
+/// Widgets carrying `tag`, compared case-insensitively, sorted by id.
+pub fn get_widgets_by_tag(tag: &str) -> Vec<Arc<Widget>> {
+    get_all_widgets()
+        .into_iter()
+        .filter(|widget| widget.has_tag(tag))
+        .collect()
 }
 
 /// Looks up a widget by its template URI.
 pub fn get_widget_by_uri(uri: &str) -> Option<Arc<Widget>> {
-    registry().widget_by_uri(uri)
+    REGISTRY.load().widget_by_uri(uri)
 }
 
 /// Returns registry metadata for diagnostics.
 pub fn registry_metadata() -> RegistryMetadata {
-    registry().metadata.clone()
+    let mut metadata = registry().metadata.clone();
+    metadata.load_metrics = load_metrics();
+    metadata
 }
 
 /// Errors that can occur while loading the manifest.
@@ -477,7 +3141,7 @@ impl std::fmt::Display for LoadError {
             LoadError::Validation { path, error } => {
                 write!(
                     f,
-                    "failed to load manifest at {}: {}",
+                    "failed to load manifest at {}: {:#}",
                     path.display(),
                     error
                 )
@@ -488,6 +3152,42 @@ impl std::fmt::Display for LoadError {
 
 impl std::error::Error for LoadError {}
 
+impl LoadError {
+    /// `not_found`, `schema` when the manifest does not match the schema, or `invalid`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LoadError::NotFound { .. } => "not_found",
+            LoadError::Validation { .. } if self.schema_violations().is_some() => "schema",
+            LoadError::Validation { .. } => "invalid",
+        }
+    }
+
+    /// Where the manifest does not match the schema, when that is why it failed to load.
+    pub fn schema_violations(&self) -> Option<&[SchemaViolation]> {
+        match self {
+            LoadError::Validation { error, .. } => error
+                .downcast_ref::<ManifestSchemaError>()
+                .map(|error| error.0.as_slice()),
+            LoadError::NotFound { .. } => None,
+        }
+    }
+
+    /// Copies the error so one reload result can be handed to several callers.
+    fn duplicate(&self) -> Self {
+        match self {
+            LoadError::NotFound { path } => LoadError::NotFound { path: path.clone() },
+            LoadError::Validation { path, error } => LoadError::Validation {
+                path: path.clone(),
+                // Kept typed so refresh responses can still list each violation.
+                error: match error.downcast_ref::<ManifestSchemaError>() {
+                    Some(schema_error) => schema_error.clone().into(),
+                    None => anyhow::anyhow!("{error:#}"),
+                },
+            },
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -544,25 +3244,1321 @@ mod tests {
         let registry = load_registry_from_path(manifest_path.path()).unwrap();
         assert_eq!(registry.widgets.len(), 1);
         assert_eq!(registry.widgets[0].id, "pizza-map");
+        assert_eq!(
+            registry.widgets[0].content_hash,
+            "93636fa047400919d3a6fa2c57a114596546d97c9681645ed857fb014fb8fb18"
+        );
+        assert_eq!(
+            registry.widgets[0].resource_meta().0[CONTENT_HASH_META_KEY],
+            serde_json::json!(registry.widgets[0].content_hash)
+        );
         assert!(registry.metadata.registry_initialized);
     }
 
     #[test]
-    fn load_registry_missing_manifest() {
-        let missing = PathBuf::from("does-not-exist.json");
-        let result = load_registry_from_path(&missing);
-        assert!(matches!(result, Err(LoadError::NotFound { .. })));
-    }
+    fn yaml_and_toml_manifests_load_like_json() {
+        let hashes = |path: &str| {
+            let registry = load_registry_from_path(Path::new(path)).unwrap();
+            registry
+                .widgets
+                .iter()
+                .map(|widget| (widget.id.clone(), widget.content_hash.clone()))
+                .collect::<Vec<_>>()
+        };
+        let json = hashes("tests/fixtures/widgets.json");
+        assert_eq!(json.len(), 5);
+        assert_eq!(hashes("tests/fixtures/widgets.yaml"), json);
+        assert_eq!(hashes("tests/fixtures/widgets.toml"), json);
 
-    #[test]
-    fn asset_validation_allows_remote() {
-        let result = validate_asset_path(Some("https://example.com/test.js"), Path::new("."));
-        assert!(result.is_ok());
+        let dir = tempfile::tempdir().unwrap();
+        let manifest_path = dir.path().join("widgets.yml");
+        std::fs::write(
+            &manifest_path,
+            "schemaVersion: 1.0.0\nwidgets:\n  - id: [map\n",
+        )
+        .unwrap();
+        let error = format!("{:#}", load_registry_from_path(&manifest_path).unwrap_err());
+        assert!(
+            error.contains("Failed to parse widget manifest YAML"),
+            "{error}"
+        );
+        assert!(error.contains("at line"), "{error}");
     }
 
     #[test]
-    fn asset_validation_rejects_missing_file() {
-        let result = validate_asset_path(Some("missing.css"), Path::new("."));
-        assert!(result.is_err());
+    fn html_path_replaces_inline_html() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("html")).unwrap();
+        std::fs::write(
+            dir.path().join("html/pizza-map.html"),
+            "<div id=\"root\"></div>",
+        )
+        .unwrap();
+        let manifest_path = dir.path().join("widgets.json");
+        let load = |entry: serde_json::Value| {
+            let mut manifest = sample_manifest_json();
+            manifest["widgets"] = serde_json::json!([entry]);
+            std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+            load_registry_from_path(&manifest_path)
+        };
+        let mut entry = sample_manifest_json()["widgets"][0].clone();
+        entry.as_object_mut().unwrap().remove("html");
+        entry.as_object_mut().unwrap().remove("assets");
+
+        entry["htmlPath"] = serde_json::json!("html/pizza-map.html");
+        let registry = load(entry.clone()).unwrap();
+        assert_eq!(registry.widgets[0].html, "<div id=\"root\"></div>");
+        assert_eq!(
+            registry.widgets[0].content_hash,
+            sha256_hex(b"<div id=\"root\"></div>")
+        );
+
+        entry["htmlPath"] = serde_json::json!("https://cdn.example/pizza-map.html");
+        let error = format!("{:#}", load(entry.clone()).unwrap_err());
+        assert!(error.contains("must be a local file"), "{error}");
+
+        entry["htmlPath"] = serde_json::json!("html/missing.html");
+        let error = format!("{:#}", load(entry.clone()).unwrap_err());
+        assert!(error.contains("validating htmlPath"), "{error}");
+
+        entry.as_object_mut().unwrap().remove("htmlPath");
+        let error = format!("{:#}", load(entry).unwrap_err());
+        assert!(error.contains("missing html or htmlPath"), "{error}");
+    }
+
+    #[test]
+    fn manifests_over_the_registry_limits_fail_to_load() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("pizzaz-aaaa.html"), "<div>map</div>").unwrap();
+        std::fs::write(dir.path().join("pizzaz-bbbb.html"), "<div>list</div>").unwrap();
+        let mut manifest = sample_manifest_json();
+        let mut list = manifest["widgets"][0].clone();
+        list["id"] = serde_json::json!("pizza-list");
+        list["templateUri"] = serde_json::json!("ui://widget/pizza-list.html");
+        list["assets"]["html"] = serde_json::json!("pizzaz-bbbb.html");
+        manifest["widgets"].as_array_mut().unwrap().push(list);
+        let manifest: WidgetManifest = serde_json::from_value(manifest).unwrap();
+        let build = |limits: RegistryLimits| {
+            WidgetsRegistry::from_manifest(
+                manifest.clone(),
+                dir.path().join("widgets.json"),
+                &AssetSource::Dir(dir.path().to_path_buf()),
+                None,
+                &limits,
+                DuplicatePolicy::Error,
+                now_utc(),
+            )
+            .map_err(|error| format!("{error:#}"))
+        };
+
+        let registry = build(RegistryLimits::default()).unwrap();
+        assert_eq!(registry.html_bytes(), 29);
+
+        let error = build(RegistryLimits {
+            max_widgets: 1,
+            ..RegistryLimits::default()
+        })
+        .unwrap_err();
+        assert!(
+            error.contains("2 enabled widgets, over the limit of 1"),
+            "{error}"
+        );
+
+        let error = build(RegistryLimits {
+            max_html_bytes: 14,
+            ..RegistryLimits::default()
+        })
+        .unwrap_err();
+        assert!(
+            error.contains("HTML for widget pizza-list is 15 bytes, over the limit of 14"),
+            "{error}"
+        );
+
+        let error = build(RegistryLimits {
+            max_registry_bytes: 28,
+            ..RegistryLimits::default()
+        })
+        .unwrap_err();
+        assert!(error.contains("totals more than 28 bytes"), "{error}");
+    }
+
+    #[test]
+    fn widgets_are_listed_by_order_then_id() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("pizzaz-aaaa.html"), "<div>map</div>").unwrap();
+        let mut manifest = sample_manifest_json();
+        let base = manifest["widgets"][0].clone();
+        let widget = |id: &str, order: Option<i64>| {
+            let mut widget = base.clone();
+            widget["id"] = serde_json::json!(id);
+            widget["templateUri"] = serde_json::json!(format!("ui://widget/{id}.html"));
+            if let Some(order) = order {
+                widget["order"] = serde_json::json!(order);
+            }
+            widget
+        };
+        manifest["widgets"] = serde_json::json!([
+            widget("pizza-map", None),
+            widget("pizza-list", Some(2)),
+            widget("pizza-albums", None),
+            widget("pizza-carousel", Some(-1)),
+            widget("pizza-video", Some(2)),
+        ]);
+        let manifest: WidgetManifest = serde_json::from_value(manifest).unwrap();
+        let registry = WidgetsRegistry::from_manifest(
+            manifest,
+            dir.path().join("widgets.json"),
+            &AssetSource::Dir(dir.path().to_path_buf()),
+            None,
+            &RegistryLimits::default(),
+            DuplicatePolicy::Error,
+            now_utc(),
+        )
+        .unwrap();
+
+        let ids: Vec<String> = registry
+            .widgets()
+            .iter()
+            .map(|widget| widget.id.clone())
+            .collect();
+        assert_eq!(
+            ids,
+            [
+                "pizza-carousel",
+                "pizza-list",
+                "pizza-video",
+                "pizza-albums",
+                "pizza-map"
+            ]
+        );
+        assert_eq!(registry.to_manifest().widgets[0].order, Some(-1));
+    }
+
+    #[test]
+    fn snapshots_rebuild_the_registry_without_its_source() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("map.html"), "<div>map</div>").unwrap();
+        std::fs::write(dir.path().join("map.fr.html"), "<div>carte</div>").unwrap();
+        std::fs::write(dir.path().join("map.css"), "body {}").unwrap();
+        let manifest_path = dir.path().join("widgets.json");
+        let mut manifest = sample_manifest_json();
+        manifest["widgets"][0]["assets"] = serde_json::json!({
+            "html": "map.html",
+            "css": "map.css",
+            "js": "https://cdn.example/map.js",
+        });
+        manifest["widgets"][0]["locales"] =
+            serde_json::json!({ "fr": { "title": "Carte", "htmlPath": "map.fr.html" } });
+        std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+        let registry = load_registry_from_path(&manifest_path).unwrap();
+
+        let (snapshot, assets) = registry
+            .to_snapshot(&AssetSource::for_manifest(&manifest_path))
+            .unwrap();
+        assert_eq!(assets["map.css"], "body {}");
+        assert!(!assets.contains_key("https://cdn.example/map.js"));
+        drop(dir);
+
+        let restored = build_registry(
+            &manifest_path,
+            snapshot,
+            &AssetSource::Fetched(assets),
+            None,
+        )
+        .unwrap();
+        let widget = &restored.widgets()[0];
+        assert_eq!(widget.html, "<div>map</div>");
+        assert_eq!(widget.content_hash, registry.widgets()[0].content_hash);
+        assert_eq!(widget.localized(Some("fr")).html, "<div>carte</div>");
+    }
+
+    #[cfg(feature = "embedded-manifest")]
+    #[test]
+    fn embedded_manifest_stands_in_for_a_missing_one() {
+        let path = Path::new("/nonexistent/widgets.json");
+        let registry = embedded_registry(path).unwrap();
+        assert_eq!(registry.metadata.manifest_path, path);
+        assert!(registry.metadata.degraded.is_some());
+        let widgets = registry.widgets();
+        assert_eq!(widgets.len(), 5);
+        let map = widgets
+            .iter()
+            .find(|widget| widget.id == "pizza-map")
+            .unwrap();
+        assert!(map.html.contains("pizzaz-root"));
+    }
+
+    #[test]
+    fn duplicate_widgets_follow_the_duplicate_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("pizzaz-aaaa.html"), "<div>map</div>").unwrap();
+        let mut manifest = sample_manifest_json();
+        let mut again = manifest["widgets"][0].clone();
+        again["title"] = serde_json::json!("Pizza Map v2");
+        let mut list = manifest["widgets"][0].clone();
+        list["id"] = serde_json::json!("pizza-list");
+        manifest["widgets"] = serde_json::json!([manifest["widgets"][0], again, list]);
+        let manifest: WidgetManifest = serde_json::from_value(manifest).unwrap();
+        let build = |duplicates| {
+            WidgetsRegistry::from_manifest(
+                manifest.clone(),
+                dir.path().join("widgets.json"),
+                &AssetSource::Dir(dir.path().to_path_buf()),
+                None,
+                &RegistryLimits::default(),
+                duplicates,
+                now_utc(),
+            )
+        };
+        let titles = |registry: &WidgetsRegistry| {
+            registry
+                .widgets
+                .iter()
+                .map(|widget| (widget.id.clone(), widget.title.clone()))
+                .collect::<Vec<_>>()
+        };
+
+        let error = build(DuplicatePolicy::Error).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Duplicate widget id detected in manifest: pizza-map"
+        );
+
+        let registry = build(DuplicatePolicy::FirstWins).unwrap();
+        assert_eq!(
+            titles(&registry),
+            vec![("pizza-map".to_string(), "Pizza Map".to_string())]
+        );
+
+        // The list reuses the map's template URI, so it replaces the second map entry too.
+        let registry = build(DuplicatePolicy::LastWins).unwrap();
+        assert_eq!(
+            titles(&registry),
+            vec![("pizza-list".to_string(), "Pizza Map".to_string())]
+        );
+        assert!(registry.widget_by_id("pizza-map").is_none());
+        assert_eq!(registry.html_bytes(), 14);
+    }
+
+    #[test]
+    fn missing_html_file_falls_back_to_the_cached_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let html_file = dir.path().join("pizzaz-aaaa.html");
+        std::fs::write(&html_file, "<div id=\"root\"></div>").unwrap();
+        let manifest_path = dir.path().join("widgets.json");
+        std::fs::write(&manifest_path, sample_manifest_json().to_string()).unwrap();
+        let loaded = load_registry_from_path(&manifest_path).unwrap();
+        assert_eq!(loaded.widgets[0].html_file.as_ref(), Some(&html_file));
+        assert_eq!(loaded.widgets[0].degradation(), None);
+
+        std::fs::remove_file(&html_file).unwrap();
+        let reason = loaded.widgets[0].degradation().unwrap();
+        assert!(reason.contains("is missing"), "{reason}");
+        assert!(load_registry_from_path(&manifest_path).is_err());
+
+        let (manifest, assets) = read_local_manifest(&manifest_path).unwrap();
+        let registry = build_registry(&manifest_path, manifest, &assets, Some(&loaded)).unwrap();
+        let widget = &registry.widgets[0];
+        assert_eq!(widget.html, "<div id=\"root\"></div>");
+        assert_eq!(widget.degraded.as_deref(), Some(reason.as_str()));
+        assert_eq!(widget.warnings.last(), Some(&reason));
+
+        let (manifest, assets) = read_local_manifest(&manifest_path).unwrap();
+        let again = build_registry(&manifest_path, manifest, &assets, Some(&registry)).unwrap();
+        assert_eq!(again.widgets[0].warnings, widget.warnings);
+
+        let mut manifest = sample_manifest_json();
+        manifest["widgets"][0]["id"] = serde_json::json!("pizza-new");
+        std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+        let (manifest, assets) = read_local_manifest(&manifest_path).unwrap();
+        assert!(build_registry(&manifest_path, manifest, &assets, Some(&loaded)).is_err());
+    }
+
+    #[test]
+    fn allowed_roles_restrict_widget_access() {
+        let manifest_path = NamedTempFile::new().expect("tmp manifest");
+        let manifest_dir = manifest_path.path().parent().unwrap().to_path_buf();
+        std::fs::write(manifest_dir.join("pizzaz-aaaa.html"), "<div></div>").unwrap();
+
+        let mut manifest = sample_manifest_json();
+        manifest["widgets"][0]["allowedRoles"] = serde_json::json!(["admin", " "]);
+        serde_json::to_writer(&manifest_path, &manifest).unwrap();
+
+        let registry = load_registry_from_path(manifest_path.path()).unwrap();
+        let widget = &registry.widgets[0];
+        assert_eq!(widget.allowed_roles, vec!["admin".to_string()]);
+        assert!(!widget.is_accessible_by(&Identity::anonymous()));
+        assert!(widget.is_accessible_by(&Identity {
+            subject: Some("alice".into()),
+            roles: vec!["admin".into()],
+        }));
+    }
+
+    #[test]
+    fn prefetch_hints_cover_template_and_remote_assets() {
+        let manifest_path = NamedTempFile::new().expect("tmp manifest");
+        let manifest_dir = manifest_path.path().parent().unwrap().to_path_buf();
+        std::fs::write(manifest_dir.join("pizzaz-aaaa.html"), "<div></div>").unwrap();
+        std::fs::write(manifest_dir.join("pizzaz-aaaa.js"), "").unwrap();
+
+        let mut manifest = sample_manifest_json();
+        manifest["widgets"][0]["assets"]["css"] =
+            serde_json::json!("https://cdn.example.com/pizzaz-aaaa.css");
+        manifest["widgets"][0]["assets"]["js"] = serde_json::json!("pizzaz-aaaa.js");
+        serde_json::to_writer(&manifest_path, &manifest).unwrap();
+
+        let registry = load_registry_from_path(manifest_path.path()).unwrap();
+        let widget = &registry.widgets[0];
+        assert_eq!(
+            widget.prefetch_hints(),
+            serde_json::json!([
+                {
+                    "url": "ui://widget/pizza-map.html",
+                    "as": "document",
+                    "sha256": widget.content_hash,
+                },
+                { "url": "https://cdn.example.com/pizzaz-aaaa.css", "as": "style" },
+            ])
+        );
+    }
+
+    #[test]
+    fn rate_limit_is_parsed_and_validated() {
+        let manifest_path = NamedTempFile::new().expect("tmp manifest");
+        let manifest_dir = manifest_path.path().parent().unwrap().to_path_buf();
+        std::fs::write(manifest_dir.join("pizzaz-aaaa.html"), "<div></div>").unwrap();
+
+        let mut manifest = sample_manifest_json();
+        manifest["widgets"][0]["rateLimit"] = serde_json::json!("5/1m");
+        serde_json::to_writer(&manifest_path, &manifest).unwrap();
+        let registry = load_registry_from_path(manifest_path.path()).unwrap();
+        let limit = registry.widgets[0].rate_limit.expect("rate limit parsed");
+        assert_eq!(limit.max_requests, 5);
+        assert_eq!(limit.window.as_secs(), 60);
+
+        manifest["widgets"][0]["rateLimit"] = serde_json::json!("lots");
+        std::fs::write(manifest_path.path(), manifest.to_string()).unwrap();
+        let result = load_registry_from_path(manifest_path.path());
+        assert!(matches!(result, Err(LoadError::Validation { .. })));
+    }
+
+    #[test]
+    fn input_schema_must_describe_an_object() {
+        let manifest_path = NamedTempFile::new().expect("tmp manifest");
+        let manifest_dir = manifest_path.path().parent().unwrap().to_path_buf();
+        std::fs::write(manifest_dir.join("pizzaz-aaaa.html"), "<div></div>").unwrap();
+
+        let mut manifest = sample_manifest_json();
+        serde_json::to_writer(&manifest_path, &manifest).unwrap();
+        let registry = load_registry_from_path(manifest_path.path()).unwrap();
+        assert_eq!(registry.widgets[0].input_schema, default_input_schema());
+
+        manifest["widgets"][0]["inputSchema"] = serde_json::json!({"type": "string"});
+        std::fs::write(manifest_path.path(), manifest.to_string()).unwrap();
+        let result = load_registry_from_path(manifest_path.path());
+        assert!(matches!(result, Err(LoadError::Validation { .. })));
+
+        // Every call the schema accepts must still parse as `ToolInput`.
+        for schema in [
+            serde_json::json!({ "type": "object", "properties": { "size": { "type": "integer" } } }),
+            serde_json::json!({
+                "type": "object",
+                "properties": { "pizzaTopping": { "type": "string" } }
+            }),
+        ] {
+            manifest["widgets"][0]["inputSchema"] = schema;
+            std::fs::write(manifest_path.path(), manifest.to_string()).unwrap();
+            let error = load_registry_from_path(manifest_path.path()).unwrap_err();
+            assert!(format!("{error:#}").contains("pizzaTopping"), "{error:#}");
+        }
+        manifest["widgets"][0]["inputSchema"] = serde_json::json!({
+            "type": "object",
+            "properties": { "pizzaTopping": { "type": "string", "default": "basil" } }
+        });
+        std::fs::write(manifest_path.path(), manifest.to_string()).unwrap();
+        assert!(load_registry_from_path(manifest_path.path()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn queued_reloads_are_coalesced() {
+        crate::test_helpers::initialize_widgets_for_tests();
+
+        let guard = RELOAD_LOCK.lock().await;
+        let first = tokio::spawn(reload_registry());
+        let second = tokio::spawn(reload_registry());
+        // Let both requests register their arrival and queue on the lock.
+        for _ in 0..5 {
+            tokio::task::yield_now().await;
+        }
+        drop(guard);
+
+        let first = first.await.unwrap();
+        let second = second.await.unwrap();
+        assert!(first.result.is_ok());
+        assert!(second.result.is_ok());
+        assert!(!first.deduplicated);
+        assert!(second.deduplicated);
+    }
+
+    #[test]
+    fn overlays_patch_base_entries_by_id() {
+        let manifest: WidgetManifest = serde_json::from_value(sample_manifest_json()).unwrap();
+        let overlay: crate::widgets_manifest::ManifestOverlay =
+            serde_json::from_value(serde_json::json!({
+                "widgets": [{
+                    "id": "pizza-map",
+                    "templateUri": "ui://widget/pizza-map-staging.html",
+                    "responseText": "Rendered a staging map!",
+                    "assets": { "css": "https://staging.example.com/pizzaz.css" },
+                    "rateLimit": null
+                }]
+            }))
+            .unwrap();
+
+        let patched = overlay.apply(manifest.clone()).unwrap();
+        let entry = &patched.widgets[0];
+        assert_eq!(entry.template_uri, "ui://widget/pizza-map-staging.html");
+        assert_eq!(entry.response_text, "Rendered a staging map!");
+        assert_eq!(entry.title, manifest.widgets[0].title);
+        let assets = entry.assets.as_ref().unwrap();
+        assert_eq!(assets.html.as_deref(), Some("pizzaz-aaaa.html"));
+        assert_eq!(
+            assets.css.as_deref(),
+            Some("https://staging.example.com/pizzaz.css")
+        );
+
+        let unknown: crate::widgets_manifest::ManifestOverlay =
+            serde_json::from_value(serde_json::json!({ "widgets": [{ "id": "pizza-typo" }] }))
+                .unwrap();
+        let error = unknown.apply(manifest.clone()).unwrap_err();
+        assert!(error.to_string().contains("pizza-typo"));
+
+        let invalid: crate::widgets_manifest::ManifestOverlay = serde_json::from_value(
+            serde_json::json!({ "widgets": [{ "id": "pizza-map", "title": null }] }),
+        )
+        .unwrap();
+        assert!(invalid.apply(manifest).is_err());
+    }
+
+    #[test]
+    fn disabled_widgets_are_skipped_without_validating_assets() {
+        let manifest_path = NamedTempFile::new().expect("tmp manifest");
+        let manifest_dir = manifest_path.path().parent().unwrap().to_path_buf();
+        std::fs::write(manifest_dir.join("pizzaz-aaaa.html"), "<div></div>").unwrap();
+
+        let mut manifest = sample_manifest_json();
+        let mut broken = manifest["widgets"][0].clone();
+        broken["id"] = serde_json::json!("pizza-broken");
+        broken["templateUri"] = serde_json::json!("ui://widget/pizza-broken.html");
+        broken["assets"]["html"] = serde_json::json!("missing-bundle.html");
+        broken["enabled"] = serde_json::json!(false);
+        manifest["widgets"].as_array_mut().unwrap().push(broken);
+        serde_json::to_writer(&manifest_path, &manifest).unwrap();
+
+        let registry = load_registry_from_path(manifest_path.path()).unwrap();
+        assert_eq!(registry.widgets.len(), 1);
+        assert!(registry.widget_by_id("pizza-broken").is_none());
+        assert!(registry
+            .widget_by_uri("ui://widget/pizza-broken.html")
+            .is_none());
+        assert_eq!(registry.disabled_widgets(), ["pizza-broken"]);
+
+        let entry: WidgetManifestEntry =
+            serde_json::from_value(manifest["widgets"][0].clone()).unwrap();
+        assert!(entry.enabled);
+        assert!(serde_json::to_value(&entry)
+            .unwrap()
+            .get("enabled")
+            .is_none());
+    }
+
+    #[test]
+    fn widgets_requiring_more_than_the_server_offers_are_skipped() {
+        let manifest_path = NamedTempFile::new().expect("tmp manifest");
+        let manifest_dir = manifest_path.path().parent().unwrap().to_path_buf();
+        std::fs::write(manifest_dir.join("pizzaz-aaaa.html"), "<div></div>").unwrap();
+
+        let mut manifest = sample_manifest_json();
+        manifest["widgets"][0]["requires"] =
+            serde_json::json!({ "schemaVersion": "1.1", "features": ["resourceTemplates"] });
+        for (id, requires) in [
+            (
+                "pizza-next",
+                serde_json::json!({ "schemaVersion": ">=1.2" }),
+            ),
+            (
+                "pizza-holo",
+                serde_json::json!({ "features": ["locales", "holograms"] }),
+            ),
+        ] {
+            let mut entry = manifest["widgets"][0].clone();
+            entry["id"] = serde_json::json!(id);
+            entry["templateUri"] = serde_json::json!(format!("ui://widget/{id}.html"));
+            entry["assets"]["html"] = serde_json::json!("missing-bundle.html");
+            entry["requires"] = requires;
+            manifest["widgets"].as_array_mut().unwrap().push(entry);
+        }
+        serde_json::to_writer(&manifest_path, &manifest).unwrap();
+
+        let registry = load_registry_from_path(manifest_path.path()).unwrap();
+        assert_eq!(registry.widgets.len(), 1);
+        assert!(registry.widget_by_id("pizza-map").is_some());
+        assert!(registry.widget_by_id("pizza-next").is_none());
+        let unsupported = registry.unsupported_widgets();
+        assert_eq!(
+            unsupported.get("pizza-next").map(String::as_str),
+            Some("requires schema version >=1.2, server implements 1.1.0")
+        );
+        assert_eq!(
+            unsupported.get("pizza-holo").map(String::as_str),
+            Some("requires unsupported features holograms")
+        );
+        assert_eq!(
+            RegistryReloadOutcome::of(&registry).unsupported,
+            *unsupported
+        );
+
+        manifest["widgets"][0]["requires"] = serde_json::json!({ "schemaVersion": "one" });
+        serde_json::to_writer(
+            std::fs::File::create(manifest_path.path()).unwrap(),
+            &manifest,
+        )
+        .unwrap();
+        let error = load_registry_from_path(manifest_path.path()).unwrap_err();
+        assert!(format!("{error:#}").contains("Invalid requires.schemaVersion"));
+    }
+
+    #[test]
+    fn widget_dependencies_are_checked_when_the_manifest_loads() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("pizzaz-aaaa.html"), "<div></div>").unwrap();
+        let manifest_path = dir.path().join("widgets.json");
+        let load = |map: serde_json::Value, order_depends_on: serde_json::Value| {
+            let mut manifest = sample_manifest_json();
+            let mut order = manifest["widgets"][0].clone();
+            order["id"] = serde_json::json!("pizza-order");
+            order["templateUri"] = serde_json::json!("ui://widget/pizza-order.html");
+            order["dependsOn"] = order_depends_on;
+            crate::widgets_manifest::merge_patch(&mut manifest["widgets"][0], &map);
+            manifest["widgets"].as_array_mut().unwrap().push(order);
+            std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+            load_registry_from_path(&manifest_path).map_err(|error| error.to_string())
+        };
+        let none = serde_json::json!({});
+
+        let registry = load(
+            none.clone(),
+            serde_json::json!([" pizza-map ", "pizza-map"]),
+        )
+        .unwrap();
+        assert_eq!(
+            registry.dependencies(),
+            BTreeMap::from([("pizza-order".to_string(), vec!["pizza-map".to_string()])])
+        );
+
+        let error = load(
+            serde_json::json!({ "enabled": false }),
+            serde_json::json!(["pizza-map"]),
+        )
+        .unwrap_err();
+        assert!(
+            error.contains("pizza-order depends on pizza-map, which is disabled"),
+            "{error}"
+        );
+        let error = load(none.clone(), serde_json::json!(["pizza-menu"])).unwrap_err();
+        assert!(
+            error.contains("depends on pizza-menu, which is not in the manifest"),
+            "{error}"
+        );
+        let error = load(none.clone(), serde_json::json!(["pizza-order"])).unwrap_err();
+        assert!(error.contains("pizza-order depends on itself"), "{error}");
+        let error = load(
+            serde_json::json!({ "dependsOn": ["pizza-order"] }),
+            serde_json::json!(["pizza-map"]),
+        )
+        .unwrap_err();
+        assert!(
+            error.contains("dependency cycle: pizza-map -> pizza-order -> pizza-map"),
+            "{error}"
+        );
+
+        let registry = load(
+            serde_json::json!({ "requires": { "features": ["holograms"] } }),
+            serde_json::json!(["pizza-map"]),
+        )
+        .unwrap();
+        assert!(registry.widgets.is_empty());
+        assert_eq!(
+            registry
+                .unsupported_widgets()
+                .get("pizza-order")
+                .map(String::as_str),
+            Some("depends on pizza-map, which this server cannot serve")
+        );
+    }
+
+    #[test]
+    fn csp_and_widget_domain_are_validated_and_emitted() {
+        let manifest_path = NamedTempFile::new().expect("tmp manifest");
+        let manifest_dir = manifest_path.path().parent().unwrap().to_path_buf();
+        std::fs::write(manifest_dir.join("pizzaz-aaaa.html"), "<div></div>").unwrap();
+
+        let mut manifest = sample_manifest_json();
+        manifest["widgets"][0]["csp"] = serde_json::json!({
+            "connectDomains": ["https://API.example.com/", "wss://live.example.com:8443"],
+            "resourceDomains": ["https://*.cdn.example.com", "https://*.cdn.example.com"],
+        });
+        manifest["widgets"][0]["widgetDomain"] = serde_json::json!("https://pizzaz.example.com");
+        serde_json::to_writer(&manifest_path, &manifest).unwrap();
+        let registry = load_registry_from_path(manifest_path.path()).unwrap();
+        let meta = registry.widget_by_id("pizza-map").unwrap().meta();
+        assert_eq!(
+            meta.0[CSP_META_KEY],
+            serde_json::json!({
+                "connect_domains": ["https://api.example.com", "wss://live.example.com:8443"],
+                "resource_domains": ["https://*.cdn.example.com"],
+            })
+        );
+        assert_eq!(
+            meta.0[WIDGET_DOMAIN_META_KEY],
+            serde_json::json!("https://pizzaz.example.com")
+        );
+
+        for (field, value, expected) in [
+            (
+                "csp",
+                serde_json::json!({ "connectDomains": ["https://api.example.com; script-src *"] }),
+                "is not an origin",
+            ),
+            (
+                "csp",
+                serde_json::json!({ "frameDomains": ["http://maps.example.com"] }),
+                "must use https or wss",
+            ),
+            (
+                "csp",
+                serde_json::json!({ "resourceDomains": ["https://cdn.example.com/assets"] }),
+                "is not an origin",
+            ),
+            (
+                "widgetDomain",
+                serde_json::json!("https://*.example.com"),
+                "is not an origin",
+            ),
+        ] {
+            let mut manifest = sample_manifest_json();
+            manifest["widgets"][0][field] = value;
+            serde_json::to_writer(
+                std::fs::File::create(manifest_path.path()).unwrap(),
+                &manifest,
+            )
+            .unwrap();
+            let error = format!(
+                "{:#}",
+                load_registry_from_path(manifest_path.path()).unwrap_err()
+            );
+            assert!(error.contains(&format!("Invalid {field}")), "{error}");
+            assert!(error.contains(expected), "{error}");
+        }
+    }
+
+    #[test]
+    fn deprecated_widgets_are_served_with_a_notice() {
+        let manifest_path = NamedTempFile::new().expect("tmp manifest");
+        let manifest_dir = manifest_path.path().parent().unwrap().to_path_buf();
+        std::fs::write(manifest_dir.join("pizzaz-aaaa.html"), "<div></div>").unwrap();
+
+        let mut manifest = sample_manifest_json();
+        manifest["widgets"][0]["deprecationMessage"] = serde_json::json!("Use pizza-map-v2.");
+        serde_json::to_writer(&manifest_path, &manifest).unwrap();
+        let current = load_registry_from_path(manifest_path.path()).unwrap();
+        let widget = current.widget_by_id("pizza-map").unwrap();
+        assert_eq!(widget.deprecation, None);
+        assert_eq!(widget.tool_description(), widget.title);
+        assert!(widget
+            .warnings
+            .iter()
+            .any(|warning| warning.starts_with("deprecationMessage is ignored")));
+
+        manifest["widgets"][0]["deprecated"] = serde_json::json!(true);
+        manifest["widgets"][0]["locales"] = serde_json::json!({ "fr": { "title": "Carte" } });
+        serde_json::to_writer(
+            std::fs::File::create(manifest_path.path()).unwrap(),
+            &manifest,
+        )
+        .unwrap();
+        let deprecated = load_registry_from_path(manifest_path.path()).unwrap();
+        let widget = deprecated.widget_by_id("pizza-map").unwrap();
+        assert_eq!(widget.deprecation.as_deref(), Some("Use pizza-map-v2."));
+        assert_eq!(
+            widget.tool_description(),
+            format!("{} (Deprecated: Use pizza-map-v2.)", widget.title)
+        );
+        let meta = widget.tool_meta();
+        assert_eq!(meta.0[DEPRECATED_META_KEY], serde_json::json!(true));
+        assert_eq!(
+            meta.0[DEPRECATION_MESSAGE_META_KEY],
+            serde_json::json!("Use pizza-map-v2.")
+        );
+        let french = widget.localized(Some("fr"));
+        assert_eq!(
+            french.tool_description(),
+            "Carte (Deprecated: Use pizza-map-v2.)"
+        );
+        assert!(
+            changed_fields(&current.widget_by_id("pizza-map").unwrap(), &widget)
+                .contains(&"deprecated")
+        );
+
+        manifest["widgets"][0]
+            .as_object_mut()
+            .unwrap()
+            .remove("deprecationMessage");
+        serde_json::to_writer(
+            std::fs::File::create(manifest_path.path()).unwrap(),
+            &manifest,
+        )
+        .unwrap();
+        let registry = load_registry_from_path(manifest_path.path()).unwrap();
+        assert_eq!(
+            registry
+                .widget_by_id("pizza-map")
+                .unwrap()
+                .deprecation
+                .as_deref(),
+            Some(DEFAULT_DEPRECATION_MESSAGE)
+        );
+    }
+
+    #[test]
+    fn manifest_meta_is_merged_into_widget_meta() {
+        let manifest_path = NamedTempFile::new().expect("tmp manifest");
+        let manifest_dir = manifest_path.path().parent().unwrap().to_path_buf();
+        std::fs::write(manifest_dir.join("pizzaz-aaaa.html"), "<div></div>").unwrap();
+
+        let mut manifest = sample_manifest_json();
+        manifest["widgets"][0]["meta"] = serde_json::json!({
+            "openai/widgetPrefersBorder": true,
+            "openai/widgetAccessible": null,
+            "acme/flags": { "beta": true },
+        });
+        serde_json::to_writer(&manifest_path, &manifest).unwrap();
+        let registry = load_registry_from_path(manifest_path.path()).unwrap();
+        let widget = registry.widget_by_id("pizza-map").unwrap();
+
+        let meta = widget.meta();
+        assert_eq!(
+            meta.0["openai/widgetPrefersBorder"],
+            serde_json::json!(true)
+        );
+        assert!(!meta.0.contains_key("openai/widgetAccessible"));
+        assert_eq!(meta.0["acme/flags"]["beta"], serde_json::json!(true));
+        assert_eq!(
+            meta.0["openai/outputTemplate"],
+            serde_json::json!(widget.template_uri)
+        );
+        assert_eq!(
+            widget.resource_meta().0["openai/widgetPrefersBorder"],
+            serde_json::json!(true)
+        );
+
+        manifest["widgets"][0]["meta"] =
+            serde_json::json!({ "openai/outputTemplate": "ui://widget/other.html" });
+        serde_json::to_writer(
+            std::fs::File::create(manifest_path.path()).unwrap(),
+            &manifest,
+        )
+        .unwrap();
+        let error = load_registry_from_path(manifest_path.path()).unwrap_err();
+        assert!(format!("{error:#}")
+            .contains("openai/outputTemplate is set by the server and cannot be overridden"));
+    }
+
+    #[test]
+    fn locale_variants_override_text_and_html() {
+        let manifest_path = NamedTempFile::new().expect("tmp manifest");
+        let manifest_dir = manifest_path.path().parent().unwrap().to_path_buf();
+        std::fs::write(manifest_dir.join("pizzaz-aaaa.html"), "<div></div>").unwrap();
+        std::fs::write(manifest_dir.join("pizzaz-aaaa.fr.html"), "<div>fr</div>").unwrap();
+
+        let mut manifest = sample_manifest_json();
+        manifest["widgets"][0]["locales"] = serde_json::json!({
+            "fr": {
+                "title": "Carte des pizzas",
+                "responseText": "Carte affichée !",
+                "htmlPath": "pizzaz-aaaa.fr.html"
+            },
+            "pt_BR": { "invoked": "Pronto", "title": " " }
+        });
+        serde_json::to_writer(&manifest_path, &manifest).unwrap();
+
+        let registry = load_registry_from_path(manifest_path.path()).unwrap();
+        let widget = registry.widget_by_id("pizza-map").unwrap();
+        assert_eq!(widget.locales.keys().collect::<Vec<_>>(), ["fr", "pt-br"]);
+
+        let french = widget.localized(Some("fr-CA"));
+        assert_eq!(french.locale.as_deref(), Some("fr"));
+        assert_eq!(french.title, "Carte des pizzas");
+        assert_eq!(french.response_text, "Carte affichée !");
+        assert_eq!(french.invoking, widget.invoking);
+        assert_eq!(french.html, "<div>fr</div>");
+        assert_ne!(french.content_hash, widget.content_hash);
+
+        let brazilian = widget.localized(Some("pt-BR"));
+        assert_eq!(brazilian.invoked, "Pronto");
+        assert_eq!(brazilian.title, widget.title);
+        assert_eq!(brazilian.html, widget.html);
+
+        for locale in [None, Some("de-DE"), Some("not a tag")] {
+            assert!(Arc::ptr_eq(&widget.localized(locale), &widget));
+        }
+
+        let mut changed = manifest.clone();
+        changed["widgets"][0]["locales"]["fr"]["title"] = serde_json::json!("Carte");
+        serde_json::to_writer(
+            std::fs::File::create(manifest_path.path()).unwrap(),
+            &changed,
+        )
+        .unwrap();
+        let updated = load_registry_from_path(manifest_path.path()).unwrap();
+        let updated = updated.widget_by_id("pizza-map").unwrap();
+        assert_eq!(changed_fields(&widget, &updated), ["locales"]);
+
+        for (locales, message) in [
+            (serde_json::json!({ "fr fr": {} }), "Invalid locale tag"),
+            (
+                serde_json::json!({ "pt-BR": {}, "pt_BR": {} }),
+                "Duplicate locale",
+            ),
+            (
+                serde_json::json!({ "fr": { "htmlPath": "https://example.com/fr.html" } }),
+                "must be a local file",
+            ),
+        ] {
+            let mut broken = manifest.clone();
+            broken["widgets"][0]["locales"] = locales;
+            serde_json::to_writer(
+                std::fs::File::create(manifest_path.path()).unwrap(),
+                &broken,
+            )
+            .unwrap();
+            let error = load_registry_from_path(manifest_path.path()).unwrap_err();
+            assert!(format!("{error:#}").contains(message), "{error:#}");
+        }
+    }
+
+    #[test]
+    fn with_widget_replaces_one_entry_and_guards_uris() {
+        let manifest_path = NamedTempFile::new().expect("tmp manifest");
+        let manifest_dir = manifest_path.path().parent().unwrap().to_path_buf();
+        std::fs::write(manifest_dir.join("pizzaz-aaaa.html"), "<div></div>").unwrap();
+        std::fs::write(manifest_dir.join("pizzaz-bbbb.html"), "<p></p>").unwrap();
+
+        let mut manifest = sample_manifest_json();
+        let mut second = manifest["widgets"][0].clone();
+        second["id"] = serde_json::json!("pizza-list");
+        second["templateUri"] = serde_json::json!("ui://widget/pizza-list.html");
+        manifest["widgets"].as_array_mut().unwrap().push(second);
+        serde_json::to_writer(&manifest_path, &manifest).unwrap();
+        let registry = load_registry_from_path(manifest_path.path()).unwrap();
+
+        let mut entry: WidgetManifestEntry =
+            serde_json::from_value(manifest["widgets"][0].clone()).unwrap();
+        entry.assets.as_mut().unwrap().html = Some("pizzaz-bbbb.html".into());
+        let updated = registry
+            .with_widget(
+                widget_from_entry(
+                    &entry,
+                    &AssetSource::Dir(manifest_dir.clone()),
+                    &RegistryLimits::default(),
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        assert_eq!(updated.widgets.len(), 2);
+        assert_eq!(updated.widget_by_id("pizza-map").unwrap().html, "<p></p>");
+        assert!(Arc::ptr_eq(
+            &updated.widget_by_id("pizza-list").unwrap(),
+            &registry.widget_by_id("pizza-list").unwrap()
+        ));
+
+        entry.template_uri = "ui://widget/pizza-list.html".into();
+        let clash = registry.with_widget(
+            widget_from_entry(
+                &entry,
+                &AssetSource::Dir(manifest_dir.clone()),
+                &RegistryLimits::default(),
+            )
+            .unwrap(),
+        );
+        assert!(matches!(
+            clash,
+            Err(WidgetRefreshError::DuplicateTemplateUri { .. })
+        ));
+    }
+
+    #[test]
+    fn registry_history_keeps_the_newest_loaded_registries() {
+        let manifest_path = NamedTempFile::new().expect("tmp manifest");
+        let manifest_dir = manifest_path.path().parent().unwrap().to_path_buf();
+        std::fs::write(manifest_dir.join("pizzaz-aaaa.html"), "<div></div>").unwrap();
+        serde_json::to_writer(&manifest_path, &sample_manifest_json()).unwrap();
+        let loaded = || Arc::new(load_registry_from_path(manifest_path.path()).unwrap());
+
+        let mut history = RegistryHistory::new(2);
+        history.record(Arc::new(WidgetsRegistry::empty(PathBuf::from("unused"))));
+        assert!(history.entries.is_empty());
+
+        let (first, second, third) = (loaded(), loaded(), loaded());
+        history.record(Arc::clone(&first));
+        history.record(Arc::clone(&second));
+        history.record(Arc::clone(&third));
+        assert_eq!(history.entries.len(), 2);
+        assert!(Arc::ptr_eq(&history.entries[0], &second));
+        assert!(Arc::ptr_eq(history.entries.back().unwrap(), &third));
+
+        let mut disabled = RegistryHistory::new(0);
+        disabled.record(first);
+        assert!(disabled.entries.is_empty());
+    }
+
+    #[test]
+    fn widget_changelog_records_content_changes_only() {
+        let manifest_path = NamedTempFile::new().expect("tmp manifest");
+        let manifest_dir = manifest_path.path().parent().unwrap().to_path_buf();
+        let html_path = manifest_dir.join("pizzaz-aaaa.html");
+        serde_json::to_writer(&manifest_path, &sample_manifest_json()).unwrap();
+        let loaded = |html: &str| {
+            std::fs::write(&html_path, html).unwrap();
+            load_registry_from_path(manifest_path.path()).unwrap()
+        };
+        let at = |seconds| OffsetDateTime::UNIX_EPOCH + Duration::from_secs(seconds);
+
+        let mut changelog = WidgetChangelog::new(2);
+        changelog.record(&WidgetsRegistry::empty(PathBuf::from("unused")), at(0));
+        assert!(changelog.entries.is_empty());
+
+        changelog.record(&loaded("<div></div>"), at(1));
+        changelog.record(&loaded("<div></div>"), at(2));
+        let changes = &changelog.entries["pizza-map"];
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].recorded_at, at(1));
+        assert_eq!(changes[0].size_bytes, 11);
+        assert_eq!(changes[0].manifest_path, manifest_path.path());
+        assert!(changes[0].manifest_generated_at.is_some());
+
+        changelog.record(&loaded("<p></p>"), at(3));
+        changelog.record(&loaded("<div></div>"), at(4));
+        let changes = &changelog.entries["pizza-map"];
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].recorded_at, at(3));
+        assert_eq!(changes[1].recorded_at, at(4));
+        assert_ne!(changes[0].content_hash, changes[1].content_hash);
+    }
+
+    #[test]
+    fn registry_diff_reports_added_removed_and_changed_widgets() {
+        let manifest_path = NamedTempFile::new().expect("tmp manifest");
+        let manifest_dir = manifest_path.path().parent().unwrap().to_path_buf();
+        std::fs::write(manifest_dir.join("pizzaz-aaaa.html"), "<div></div>").unwrap();
+        std::fs::write(manifest_dir.join("pizzaz-bbbb.html"), "<p></p>").unwrap();
+        let widget = |id: &str, html: &str| {
+            let mut entry = sample_manifest_json()["widgets"][0].clone();
+            entry["id"] = serde_json::json!(id);
+            entry["templateUri"] = serde_json::json!(format!("ui://widget/{id}.html"));
+            entry["assets"]["html"] = serde_json::json!(html);
+            entry
+        };
+        let loaded = |widgets: Vec<serde_json::Value>| {
+            let mut manifest = sample_manifest_json();
+            manifest["widgets"] = serde_json::json!(widgets);
+            serde_json::to_writer(std::fs::File::create(&manifest_path).unwrap(), &manifest)
+                .unwrap();
+            load_registry_from_path(manifest_path.path()).unwrap()
+        };
+
+        let before = loaded(vec![
+            widget("pizza-map", "pizzaz-aaaa.html"),
+            widget("pizza-list", "pizzaz-aaaa.html"),
+            widget("pizza-video", "pizzaz-aaaa.html"),
+        ]);
+        let mut retitled = widget("pizza-list", "pizzaz-aaaa.html");
+        retitled["title"] = serde_json::json!("Pizza List v2");
+        let after = loaded(vec![
+            widget("pizza-map", "pizzaz-bbbb.html"),
+            retitled,
+            widget("pizza-albums", "pizzaz-aaaa.html"),
+        ]);
+
+        let diff = RegistryDiff::between(&before, &after);
+        assert_eq!(diff.added, ["pizza-albums"]);
+        assert_eq!(diff.removed, ["pizza-video"]);
+        let changed: Vec<_> = diff
+            .changed
+            .iter()
+            .map(|change| (change.id.as_str(), change.fields.clone()))
+            .collect();
+        assert_eq!(
+            changed,
+            [("pizza-list", vec!["title"]), ("pizza-map", vec!["html"])]
+        );
+        assert_ne!(diff.changed[1].previous_hash, diff.changed[1].current_hash);
+
+        assert!(RegistryDiff::between(&after, &after).is_empty());
+        let empty = WidgetsRegistry::empty(PathBuf::from("unused"));
+        assert_eq!(RegistryDiff::between(&empty, &after).added.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn preload_retries_until_manifest_appears() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("widgets.json");
+        std::fs::write(dir.path().join("pizzaz-aaaa.html"), "<div></div>").unwrap();
+        let config = PreloadConfig {
+            timeout: Duration::from_secs(5),
+            retry_interval: Duration::from_millis(20),
+        };
+
+        let writer_path = path.clone();
+        let writer = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(60)).await;
+            std::fs::write(&writer_path, sample_manifest_json().to_string()).unwrap();
+        });
+        let registry = load_with_retry(&path, config).await.unwrap();
+        writer.await.unwrap();
+        assert_eq!(registry.widgets.len(), 1);
+
+        let missing = PreloadConfig {
+            timeout: Duration::from_millis(50),
+            ..config
+        };
+        let result = load_with_retry(&dir.path().join("missing.json"), missing).await;
+        assert!(matches!(result, Err(LoadError::NotFound { .. })));
+    }
+
+    #[test]
+    fn manifest_watcher_waits_for_writes_to_settle() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("widgets.json");
+        fs::write(&path, "{}").unwrap();
+        let debounce = Duration::from_millis(500);
+        let mut watcher = ManifestWatcher::new(path.clone(), debounce);
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+
+        assert!(!watcher.poll(&path, at(1000)));
+
+        fs::write(&path, r#"{"widgets": []}"#).unwrap();
+        assert!(!watcher.poll(&path, at(2000)));
+        // Still being written: the quiet period starts over.
+        fs::write(&path, r#"{"widgets": [], "schemaVersion": "1.0.0"}"#).unwrap();
+        assert!(!watcher.poll(&path, at(2200)));
+        assert!(!watcher.poll(&path, at(2600)));
+        assert!(watcher.poll(&path, at(2700)));
+        assert!(!watcher.poll(&path, at(3700)));
+
+        // Switching paths resets the baseline instead of reloading.
+        let other = dir.path().join("other.json");
+        fs::write(&other, "{}").unwrap();
+        assert!(!watcher.poll(&other, at(4000)));
+        assert!(!watcher.poll(&other, at(5000)));
+    }
+
+    #[test]
+    fn scheduled_refresh_waits_for_the_interval_plus_jitter() {
+        assert_eq!(parse_interval("300"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_interval(" 90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_interval("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_interval("1H"), Ok(Duration::from_secs(3600)));
+        assert!(parse_interval(&format!("{}h", u64::MAX)).is_err());
+        assert_eq!(parse_interval("off"), Ok(Duration::ZERO));
+        assert!(parse_interval("5 minutes").is_err());
+        assert!(parse_interval("m").is_err());
+
+        let schedule = ScheduledRefreshConfig {
+            interval: Duration::from_secs(300),
+            jitter: Duration::from_secs(30),
+        };
+        assert_eq!(schedule.next_delay(0), Duration::from_secs(300));
+        assert_eq!(schedule.next_delay(30_000), Duration::from_secs(330));
+        assert_eq!(schedule.next_delay(30_001), Duration::from_secs(300));
+        let steady = ScheduledRefreshConfig {
+            jitter: Duration::ZERO,
+            ..schedule
+        };
+        assert_eq!(steady.next_delay(u64::MAX), Duration::from_secs(300));
+    }
+
+    #[test]
+    fn load_metrics_count_attempts_failures_and_html_bytes() {
+        let manifest_path = NamedTempFile::new().expect("tmp manifest");
+        let manifest_dir = manifest_path.path().parent().unwrap().to_path_buf();
+        std::fs::write(manifest_dir.join("pizzaz-aaaa.html"), "<div></div>").unwrap();
+        serde_json::to_writer(&manifest_path, &sample_manifest_json()).unwrap();
+
+        let mut metrics = LoadMetrics::default();
+        let loaded = load_registry_from_path(manifest_path.path());
+        assert_eq!(loaded.as_ref().unwrap().html_bytes(), 11);
+        metrics.record(Duration::from_millis(12), &loaded);
+        let missing = load_registry_from_path(Path::new("does-not-exist.json"));
+        metrics.record(Duration::from_millis(3), &missing);
+        std::fs::write(manifest_path.path(), r#"{"widgets": []}"#).unwrap();
+        let invalid = load_registry_from_path(manifest_path.path());
+        assert_eq!(invalid.as_ref().unwrap_err().kind(), "schema");
+        metrics.record(Duration::from_millis(4), &invalid);
+
+        assert_eq!((metrics.attempts, metrics.successes), (3, 1));
+        assert_eq!(
+            metrics.failures,
+            BTreeMap::from([("not_found", 1), ("schema", 1)])
+        );
+        assert_eq!(metrics.consecutive_failures, 2);
+        assert_eq!(metrics.last_duration_ms, Some(4));
+        assert_eq!(metrics.html_bytes_loaded, 11);
+        assert!(metrics
+            .last_error
+            .as_deref()
+            .is_some_and(|error| error.contains("schemaVersion: missing")));
+        assert!(metrics.last_failure_at.is_some());
+
+        metrics.record(Duration::from_millis(5), &loaded);
+        assert_eq!(metrics.consecutive_failures, 0);
+        assert_eq!(metrics.html_bytes_loaded, 22);
+    }
+
+    #[test]
+    fn load_registry_missing_manifest() {
+        let missing = PathBuf::from("does-not-exist.json");
+        let result = load_registry_from_path(&missing);
+        assert!(matches!(result, Err(LoadError::NotFound { .. })));
+    }
+
+    #[test]
+    fn asset_validation_allows_remote() {
+        let result = validate_asset_path(
+            Some("https://example.com/test.js"),
+            None,
+            &AssetSource::Dir(PathBuf::from(".")),
+        );
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn asset_validation_rejects_missing_file() {
+        let result = validate_asset_path(
+            Some("missing.css"),
+            None,
+            &AssetSource::Dir(PathBuf::from(".")),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn asset_checksums_must_match_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("pizzaz-2d2b.css"), "div {}").unwrap();
+        let source = AssetSource::Dir(dir.path().to_path_buf());
+        let actual = sha256_hex(b"div {}");
+
+        assert!(validate_asset_path(
+            Some("pizzaz-2d2b.css"),
+            Some(&actual.to_uppercase()),
+            &source
+        )
+        .is_ok());
+
+        let stale = "0".repeat(64);
+        let error = validate_asset_path(Some("pizzaz-2d2b.css"), Some(&stale), &source)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains(&format!(
+            "manifest expects sha256 {stale}, file has {actual}"
+        )));
+
+        assert!(
+            validate_asset_path(Some("https://cdn.example/a.css"), Some(&stale), &source).is_err()
+        );
+    }
+
+    #[test]
+    fn template_uris_take_the_configured_prefix() {
+        assert_eq!(
+            client_template_uri("ui://widget/pizza-map.html", Some("resource://pizzaz/")),
+            "resource://pizzaz/pizza-map.html"
+        );
+        assert_eq!(
+            client_template_uri("ui://widget/pizza-map.html", None),
+            "ui://widget/pizza-map.html"
+        );
+        assert_eq!(
+            client_template_uri("custom://maps/pizza.html", Some("resource://pizzaz/")),
+            "custom://maps/pizza.html"
+        );
+
+        assert_eq!(parse_template_uri_prefix("  ").unwrap(), None);
+        assert_eq!(
+            parse_template_uri_prefix(" acme+ui:widgets/ ")
+                .unwrap()
+                .as_deref(),
+            Some("acme+ui:widgets/")
+        );
+        assert!(parse_template_uri_prefix("widgets/").is_err());
+        assert!(parse_template_uri_prefix("1ui://widget/").is_err());
+        assert!(parse_template_uri_prefix("resource:").is_err());
+        assert!(parse_template_uri_prefix("resource://my widgets/").is_err());
+    }
+
+    #[test]
+    fn placeholders_are_filled_from_defaults_and_call_arguments() {
+        let manifest_path = NamedTempFile::new().expect("tmp manifest");
+        let manifest_dir = manifest_path.path().parent().unwrap().to_path_buf();
+        std::fs::write(manifest_dir.join("pizzaz-aaaa.html"), "<div></div>").unwrap();
+
+        let mut manifest = sample_manifest_json();
+        manifest["widgets"][0]["invoking"] = serde_json::json!("Tossing {{ pizzaTopping }}");
+        manifest["widgets"][0]["responseText"] =
+            serde_json::json!("{{size}} {{pizzaTopping}} pizza with {{extras}}{{missing");
+        manifest["widgets"][0]["inputSchema"] = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "pizzaTopping": { "type": "string", "default": "cheese" },
+                "size": { "type": "integer" },
+                "extras": { "type": "array" }
+            }
+        });
+        serde_json::to_writer(&manifest_path, &manifest).unwrap();
+        let registry = load_registry_from_path(manifest_path.path()).unwrap();
+        let widget = registry.widget_by_id("pizza-map").unwrap();
+        assert_eq!(
+            widget.meta().0["openai/toolInvocation/invoking"],
+            serde_json::json!("Tossing cheese")
+        );
+        assert_eq!(widget.response_text(), " cheese pizza with {{missing");
+
+        let call = widget.with_arguments(&serde_json::json!({
+            "pizzaTopping": "{{size}}",
+            "size": 12,
+            "extras": ["basil", "oil"]
+        }));
+        assert_eq!(
+            call.meta().0["openai/toolInvocation/invoking"],
+            serde_json::json!("Tossing {{size}}")
+        );
+        assert_eq!(
+            call.response_text(),
+            "12 {{size}} pizza with basil, oil{{missing"
+        );
+        assert_eq!(call.invoked, "Invoked");
+
+        manifest["widgets"][0]["locales"] =
+            serde_json::json!({ "fr": { "invoked": "Pizza {{garniture}} servie" } });
+        serde_json::to_writer(
+            std::fs::File::create(manifest_path.path()).unwrap(),
+            &manifest,
+        )
+        .unwrap();
+        let error = load_registry_from_path(manifest_path.path()).unwrap_err();
+        assert!(format!("{error:#}")
+            .contains("invoked uses {{garniture}}, which is not an inputSchema property"));
     }
 }
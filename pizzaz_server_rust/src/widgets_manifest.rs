@@ -1,19 +1,44 @@
 //! Manifest types and parsing helpers for the widget registry.
 
-use std::{fs, path::Path};
+use std::{collections::BTreeMap, fs, path::Path};
 
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 
+use crate::{manifest_env, manifest_format::ManifestFormat, manifest_schema};
+
 /// Canonical schema version supported by the server.
 pub const SUPPORTED_SCHEMA_MAJOR: u64 = 1;
 
+/// Newest manifest schema version whose features this server implements.
+pub const SERVER_SCHEMA_VERSION: &str = "1.1.0";
+
+/// Features a widget can list in `requires.features`, sorted.
+pub const SERVER_FEATURES: &[&str] = &[
+    "csp",
+    "deferredResults",
+    "dependsOn",
+    "deprecation",
+    "envInterpolation",
+    "htmlPath",
+    "inputSchemaDefaults",
+    "locales",
+    "meta",
+    "order",
+    "resourceLinks",
+    "resourceTemplates",
+    "signedHtml",
+    "structuredContent",
+    "templateVariables",
+    "widgetDomain",
+];
+
 /// Top-level manifest structure.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct WidgetManifest {
     pub schema_version: String,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub generated_at: Option<String>,
     #[serde(default)]
     pub widgets: Vec<WidgetManifestEntry>,
@@ -28,26 +53,257 @@ pub struct WidgetManifestEntry {
     pub template_uri: String,
     pub invoking: String,
     pub invoked: String,
+    /// Inline HTML or the URL of the HTML bundle; optional when `html_path` is set.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub html: String,
+    /// Local file holding the widget HTML, relative to the manifest. Takes precedence over `html`
+    /// and `assets.html`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub html_path: Option<String>,
     pub response_text: String,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub assets: Option<WidgetManifestAssets>,
+    /// Roles permitted to see and call this widget; empty means everyone.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_roles: Vec<String>,
+    /// Per-caller call budget for this tool, e.g. `5/60s`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<String>,
+    /// JSON Schema for the tool arguments; properties may declare a `default`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_schema: Option<serde_json::Value>,
+    /// Free-form group names, e.g. `["maps", "beta"]`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Position in `tools/list` and `resources/list`, lowest first. Widgets without one follow
+    /// those with one, and ties are broken by id.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<i64>,
+    /// Disabled widgets stay in the manifest but are neither validated nor served.
+    #[serde(default = "enabled_by_default", skip_serializing_if = "is_enabled")]
+    pub enabled: bool,
+    /// Localized copy keyed by locale tag, e.g. `fr` or `pt-BR`, chosen by `openai/locale`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub locales: BTreeMap<String, WidgetManifestLocale>,
+    /// What the server must support to serve this widget; others skip it with a warning.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requires: Option<WidgetRequirements>,
+    /// Origins the widget may reach, emitted as `openai/widgetCSP`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub csp: Option<WidgetCsp>,
+    /// Origin the widget is served under, e.g. `https://pizzaz.example.com`, emitted as
+    /// `openai/widgetDomain`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub widget_domain: Option<String>,
+    /// Extra `_meta` keys, e.g. `{"openai/widgetPrefersBorder": true}`, merge-patched into
+    /// [`crate::widgets::Widget::meta`]; `null` removes a default key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<serde_json::Map<String, serde_json::Value>>,
+    /// Deprecated widgets are still served, but their tool tells the model to avoid them.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deprecated: bool,
+    /// What to use instead, e.g. `Use pizza-map-v2.`; shown only when `deprecated` is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deprecation_message: Option<String>,
+    /// Ids of widgets this one's flow needs, e.g. `["pizza-map"]` for an order widget.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<String>,
+}
+
+/// Content Security Policy sources for a widget, each an origin such as `https://api.example.com`
+/// or `https://*.example.com`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct WidgetCsp {
+    /// Origins for `fetch`, XHR and WebSocket connections.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub connect_domains: Vec<String>,
+    /// Origins for scripts, styles, images and fonts.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub resource_domains: Vec<String>,
+    /// Origins the widget may embed in iframes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub frame_domains: Vec<String>,
+}
+
+/// Server schema version and features a widget depends on.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct WidgetRequirements {
+    /// Semver requirement on [`SERVER_SCHEMA_VERSION`], e.g. `1.1` or `>=1.1.0`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema_version: Option<String>,
+    /// Names from [`SERVER_FEATURES`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+}
+
+/// Overrides for one locale; fields left out fall back to the widget's own.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct WidgetManifestLocale {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invoking: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invoked: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_text: Option<String>,
+    /// Local file holding this locale's HTML, relative to the manifest.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub html_path: Option<String>,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+fn is_enabled(enabled: &bool) -> bool {
+    *enabled
 }
 
 /// Optional asset paths associated with a widget manifest entry.
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct WidgetManifestAssets {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub html: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub css: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub js: Option<String>,
+    /// Expected lowercase hex SHA-256 of each local asset file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<WidgetManifestAssetChecksums>,
+}
+
+/// Asset checksums verified when the manifest loads; each one is optional.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct WidgetManifestAssetChecksums {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub html: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub css: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub js: Option<String>,
 }
 
 /// Reads and deserializes a manifest from disk.
 pub fn read_manifest(path: &Path) -> Result<WidgetManifest> {
-    let data = fs::read_to_string(path)
+    let data = fs::read(path)
+        .with_context(|| format!("Failed to read widget manifest at {}", path.display()))?;
+    parse_manifest(&data, path)
+}
+
+/// Reads a manifest from disk with its `${VAR}` references left in place, for tools that write
+/// it back out.
+pub fn read_manifest_verbatim(path: &Path) -> Result<WidgetManifest> {
+    let data = fs::read(path)
         .with_context(|| format!("Failed to read widget manifest at {}", path.display()))?;
-    let manifest: WidgetManifest = serde_json::from_str(&data)
-        .with_context(|| format!("Failed to parse widget manifest JSON at {}", path.display()))?;
+    parse_manifest_value(&data, path, false)
+}
+
+/// Parses a manifest read from `path`, in the format its extension names (see
+/// [`crate::manifest_format`]). `${VAR}` references are resolved (see [`crate::manifest_env`]),
+/// then it is checked against the manifest schema so mistakes are reported by field path (see
+/// [`crate::manifest_schema`]).
+pub fn parse_manifest(data: &[u8], path: &Path) -> Result<WidgetManifest> {
+    parse_manifest_value(data, path, true)
+}
+
+fn parse_manifest_value(data: &[u8], path: &Path, interpolate: bool) -> Result<WidgetManifest> {
+    let format = ManifestFormat::for_path(path);
+    let failed = || {
+        format!(
+            "Failed to parse widget manifest {format} at {}",
+            path.display()
+        )
+    };
+    let mut value = format.parse(data).with_context(failed)?;
+    if interpolate {
+        manifest_env::interpolate(&mut value)?;
+    }
+    manifest_schema::validate(&value)?;
+    let manifest: WidgetManifest = serde_json::from_value(value).with_context(failed)?;
     Ok(manifest)
 }
+
+/// Per-environment patches applied to a base manifest's widget entries by id.
+///
+/// Each entry in `widgets` names a base widget by `id` and is merged into it as a JSON merge
+/// patch (RFC 7386): fields present in the overlay replace the base values, nested objects such
+/// as `assets` merge field by field, and `null` removes an optional field.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ManifestOverlay {
+    #[serde(default)]
+    pub widgets: Vec<serde_json::Value>,
+}
+
+impl ManifestOverlay {
+    /// Returns `manifest` with every overlay entry merged into the base entry with the same id.
+    ///
+    /// Fails when an overlay entry has no id or names a widget the base manifest does not have,
+    /// so a typo cannot silently leave one environment on base values.
+    pub fn apply(&self, mut manifest: WidgetManifest) -> Result<WidgetManifest> {
+        for patch in &self.widgets {
+            let id = patch
+                .get("id")
+                .and_then(serde_json::Value::as_str)
+                .map(str::trim)
+                .filter(|id| !id.is_empty())
+                .context("Overlay widget entry missing id")?;
+            let entry = manifest
+                .widgets
+                .iter_mut()
+                .find(|entry| entry.id.trim() == id)
+                .with_context(|| format!("Overlay widget {id} is not in the base manifest"))?;
+
+            let mut merged = serde_json::to_value(&*entry)?;
+            merge_patch(&mut merged, patch);
+            *entry = serde_json::from_value(merged)
+                .with_context(|| format!("Overlay for widget {id} is not a valid entry"))?;
+        }
+        Ok(manifest)
+    }
+}
+
+/// Reads and deserializes an overlay manifest from disk.
+pub fn read_overlay(path: &Path) -> Result<ManifestOverlay> {
+    let data = fs::read(path)
+        .with_context(|| format!("Failed to read manifest overlay at {}", path.display()))?;
+    let format = ManifestFormat::for_path(path);
+    let failed = || {
+        format!(
+            "Failed to parse manifest overlay {format} at {}",
+            path.display()
+        )
+    };
+    let mut value = format.parse(&data).with_context(failed)?;
+    manifest_env::interpolate(&mut value)?;
+    serde_json::from_value(value).with_context(failed)
+}
+
+/// Applies an RFC 7386 JSON merge patch: objects merge key by key and `null` removes a key.
+pub(crate) fn merge_patch(target: &mut serde_json::Value, patch: &serde_json::Value) {
+    let serde_json::Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = serde_json::Value::Object(serde_json::Map::new());
+    }
+    let target = target.as_object_mut().expect("target is an object");
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge_patch(
+                target.entry(key.clone()).or_insert(serde_json::Value::Null),
+                value,
+            );
+        }
+    }
+}